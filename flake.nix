@@ -41,9 +41,24 @@
       };
 
       kra = mkCrate "kra";
+
+      # Lints of the build without uuid, where Uuid is a validated string
+      kra-clippy-no-default-features = let
+        src = craneLib.cleanCargoSource (craneLib.path ./kra);
+        args = {
+          inherit src;
+          cargoLock = ./Cargo.lock;
+          cargoExtraArgs = "--no-default-features";
+        };
+      in
+        craneLib.cargoClippy (args
+          // {
+            cargoArtifacts = craneLib.buildDepsOnly args;
+            cargoClippyExtraArgs = "--all-targets -- -D warnings";
+          });
     in {
       checks = {
-        inherit kra;
+        inherit kra kra-clippy-no-default-features;
       };
 
       packages = {
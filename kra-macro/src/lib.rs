@@ -23,13 +23,11 @@ pub fn parse_tag(item: TokenStream) -> TokenStream {
     }
     .named;
     // Two interleaved parts - one is all get_attr(), other are fields in struct construction
-    let tokens: Vec<TokenStream2> = fields
-        .iter()
-        .map(|item| gen_get_attr(item))
-        .flatten()
-        .collect();
+    let tokens: Vec<TokenStream2> = fields.iter().flat_map(gen_get_attr).collect();
     let tokens_first = tokens.iter().step_by(2);
     let tokens_second = tokens.iter().skip(1).step_by(2);
+    // Description of every attribute that is read from XML
+    let schema: Vec<TokenStream2> = fields.iter().filter_map(gen_schema).collect();
     // Extra args
     let extra_args: TokenStream2 = syn::parse_str(&match item
         .attrs
@@ -42,7 +40,7 @@ pub fn parse_tag(item: TokenStream) -> TokenStream {
         })) => format!(", {}", args),
         _ => String::default(),
     })
-    .unwrap_or(TokenStream2::default());
+    .unwrap_or_default();
     quote! {
         impl #ident {
             pub(crate) fn parse_tag(tag: &BytesStart #extra_args) -> Result<Self, MetadataErrorReason> {
@@ -51,6 +49,10 @@ pub fn parse_tag(item: TokenStream) -> TokenStream {
                     #( #tokens_second ),*
                 })
             }
+
+            pub(crate) const XML_ATTRIBUTES: &'static [AttributeSchema] = &[
+                #( #schema ),*
+            ];
         }
     }
     .into()
@@ -74,7 +76,7 @@ fn gen_get_attr(item: &Field) -> [TokenStream2; 2] {
         // For this, I could search for existence of XmlAttr attribute, regardless of whether
         // it was successfully parsed. That would indicate that the user made a mistake instead
         // of not adding the attribute at all.
-        .expect(format!("expected XmlAttr attribute on field {}", ident).as_str())
+        .unwrap_or_else(|| panic!("expected XmlAttr attribute on field {}", ident))
         .unwrap();
     let qname = attr.qname.unwrap_or(ident.to_string());
     //TODO: remove requirement for function override when default parsing is implemented
@@ -87,6 +89,30 @@ fn gen_get_attr(item: &Field) -> [TokenStream2; 2] {
         syn::parse_str(fun_override.as_str()).expect("could not parse function override");
     //TODO: default parsing behaviour
 
+    // Optional attributes are parsed only if they are present, and the field is an Option
+    if attr.optional == Some(true) {
+        let pre_parse = pre_parse.map(|pre_parse| {
+            let pre_parse: syn::Expr =
+                syn::parse_str(pre_parse.as_str()).expect("could not parse pre-parsing code");
+            quote! {
+                let #ident = #ident.#pre_parse;
+            }
+        });
+        let tokens_first = quote! {
+            let #ident = match event_get_optional_attr(&tag, #qname)? {
+                Some(#ident) => {
+                    #pre_parse
+                    Some(#fun_override)
+                }
+                None => None,
+            };
+        };
+        let tokens_second = quote! {
+            #ident: #ident
+        };
+        return [tokens_first, tokens_second];
+    }
+
     // First part of output - statement to get attribute from XML
     let tokens_first = match (extract_data, pre_parse) {
         (Some(false), _) => TokenStream2::default(),
@@ -108,6 +134,57 @@ fn gen_get_attr(item: &Field) -> [TokenStream2; 2] {
     [tokens_first, tokens_second]
 }
 
+// Description of the field's XML attribute, unless the field is not extracted from one
+fn gen_schema(item: &Field) -> Option<TokenStream2> {
+    let ident = item.ident.as_ref().unwrap();
+    let attr = item
+        .attrs
+        .iter()
+        .map(|x| XmlAttr::from_meta(&x.meta))
+        .find(|x| x.is_ok())?
+        .unwrap();
+    if attr.extract_data == Some(false) {
+        return None;
+    }
+    let qname = attr.qname.unwrap_or(ident.to_string());
+    let field = ident.to_string();
+    let ty = &item.ty;
+    let rust_type = quote!(#ty)
+        .to_string()
+        .replace(" < ", "<")
+        .replace(" <", "<")
+        .replace("< ", "<")
+        .replace(" >", ">")
+        .replace(" :: ", "::")
+        .replace(" ,", ",");
+    let optional = attr.optional == Some(true);
+    // Lines of doc comments, joined into one
+    let doc = item
+        .attrs
+        .iter()
+        .filter_map(|x| match &x.meta {
+            syn::Meta::NameValue(meta) if meta.path.is_ident("doc") => match &meta.value {
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(line),
+                    ..
+                }) => Some(line.value().trim().to_owned()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    Some(quote! {
+        AttributeSchema {
+            qname: #qname,
+            field: #field,
+            rust_type: #rust_type,
+            optional: #optional,
+            doc: #doc,
+        }
+    })
+}
+
 // Attribute which stores qname of a struct field
 #[derive(Debug, FromMeta)]
 pub(crate) struct XmlAttr {
@@ -116,12 +193,13 @@ pub(crate) struct XmlAttr {
     pub(crate) qname: Option<String>,
     // Parsing function override
     // The string is parsed and then inserted as-is
-    #[darling(default)]
     pub(crate) fun_override: Option<String>,
     // Code to append immediately after data extraction (like unescape_value())
     pub(crate) pre_parse: Option<String>,
     // Allow not including data extraction
     pub(crate) extract_data: Option<bool>,
+    // Attribute may be missing, in which case the field (an Option) is None
+    pub(crate) optional: Option<bool>,
 }
 
 // Attribute to add extra arguments for the resulting function
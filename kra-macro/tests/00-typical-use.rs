@@ -2,6 +2,7 @@ use kra_macro::ParseTag;
 
 #[derive(ParseTag)]
 struct Thing {
+    /// Who made the thing.
     #[XmlAttr(qname = "author", fun_override = "todo!()")]
     author: String,
     #[XmlAttr(qname = "x", fun_override = "todo!()")]
@@ -10,10 +11,21 @@ struct Thing {
     y: u32,
     #[XmlAttr(qname = "on", fun_override = "override_bool(on)")]
     on: bool,
+    #[XmlAttr(qname = "label", optional = true, fun_override = "todo!()")]
+    label: Option<u32>,
 }
 
 struct BytesStart();
 
+#[allow(dead_code)]
+struct AttributeSchema {
+    qname: &'static str,
+    field: &'static str,
+    rust_type: &'static str,
+    optional: bool,
+    doc: &'static str,
+}
+
 struct MetadataErrorReason();
 
 struct Attribute();
@@ -22,8 +34,20 @@ fn event_get_attr(_tag: &BytesStart, _name: &str) -> Result<Attribute, MetadataE
     todo!()
 }
 
+fn event_get_optional_attr(
+    _tag: &BytesStart,
+    _name: &str,
+) -> Result<Option<Attribute>, MetadataErrorReason> {
+    todo!()
+}
+
 fn override_bool(item: Attribute) -> bool {
     todo!()
 }
 
-fn main() {}
+fn main() {
+    assert_eq!(Thing::XML_ATTRIBUTES.len(), 5);
+    assert_eq!(Thing::XML_ATTRIBUTES[0].doc, "Who made the thing.");
+    assert_eq!(Thing::XML_ATTRIBUTES[4].rust_type, "Option<u32>");
+    assert!(Thing::XML_ATTRIBUTES[4].optional);
+}
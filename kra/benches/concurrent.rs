@@ -0,0 +1,88 @@
+//! Decoding of every layer of a large document, one by one with `KraFile::decode_layer()`
+//! and from several threads with `LayerReader`.
+//!
+//! Run with `cargo bench --features testing --bench concurrent`.
+
+use std::{
+    env, process, thread,
+    time::{Duration, Instant},
+};
+
+use kra::id::Uuid;
+use kra::{
+    testing::{DocumentBuilder, LayerBuilder},
+    KraFile,
+};
+
+const LAYERS: u32 = 128;
+const TILES: i32 = 16;
+const ROUNDS: u32 = 5;
+
+fn document() -> DocumentBuilder {
+    let mut document = DocumentBuilder::new(64 * TILES as u32, 64 * TILES as u32);
+    for index in 1..=LAYERS {
+        let mut layer = LayerBuilder::paint(&format!("Layer {}", index));
+        for tile in 0..TILES * TILES {
+            let value = (index as i32 + tile) as u8;
+            layer = layer.fill_tile(tile % TILES * 64, tile / TILES * 64, [value, 0, 0, 255]);
+        }
+        document = document.layer(layer);
+    }
+    document
+}
+
+fn serial(file: &mut KraFile, uuids: &[Uuid]) {
+    for uuid in uuids {
+        file.decode_layer(uuid).unwrap();
+    }
+}
+
+fn concurrent(file: &KraFile, uuids: &[Uuid], threads: usize) {
+    let reader = file.layer_reader().unwrap();
+    thread::scope(|scope| {
+        for chunk in uuids.chunks(uuids.len().div_ceil(threads)) {
+            let reader = &reader;
+            scope.spawn(move || {
+                for uuid in chunk {
+                    reader.decode_layer(uuid).unwrap();
+                }
+            });
+        }
+    });
+}
+
+// Best of several rounds
+fn measure(mut run: impl FnMut()) -> Duration {
+    (0..ROUNDS)
+        .map(|_| {
+            let start = Instant::now();
+            run();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let path = env::temp_dir().join(format!("kra-bench-concurrent-{}.kra", process::id()));
+    document().write_to(&path).unwrap();
+    let mut file = KraFile::read(&path).unwrap();
+    let uuids: Vec<Uuid> = (1..=LAYERS as u128).map(Uuid::from_u128).collect();
+    let threads = thread::available_parallelism().map_or(4, |count| count.get());
+
+    let serial_time = measure(|| serial(&mut file, &uuids));
+    println!("serial:     {:>10.2?}", serial_time);
+    let mut counts = vec![1, 2, 4, threads];
+    counts.sort();
+    counts.dedup();
+    for count in counts {
+        let time = measure(|| concurrent(&file, &uuids, count));
+        println!(
+            "{:>2} threads: {:>10.2?} ({:.1}x)",
+            count,
+            time,
+            serial_time.as_secs_f64() / time.as_secs_f64()
+        );
+    }
+    let _ = std::fs::remove_file(path);
+}
@@ -0,0 +1,74 @@
+//! Save every paint layer of the document as a PNG image.
+//!
+//! `cargo run --example export_layers --features image -- image.kra out/` writes the layers
+//! as `out/{path}/{name}.png` and prints where each one is placed on the canvas, which PNG
+//! images cannot hold.
+
+use std::{
+    env::args,
+    fs,
+    path::{Path, PathBuf},
+    process::exit,
+};
+
+use kra::{
+    id::Uuid,
+    layer::{Node, NodeType},
+    naming::{NameTemplate, SanitizeOptions},
+    KraFile,
+};
+
+// UUIDs of paint layers, in document order
+fn paint_layers(nodes: &[Node], out: &mut Vec<Uuid>) {
+    for node in nodes {
+        match node.node_type() {
+            NodeType::PaintLayer(_) => out.push(node.uuid()),
+            NodeType::GroupLayer(props) => paint_layers(props.layers(), out),
+            _ => {}
+        }
+    }
+}
+
+fn export(path: &Path, out: &Path) -> Result<(), String> {
+    let mut file = KraFile::read(path).map_err(|err| err.to_string())?;
+    let template: NameTemplate = "{path}/{name}.png".parse().expect("template is valid");
+    let names = file.export_names(&template, &SanitizeOptions::new());
+    let mut layers = Vec::new();
+    paint_layers(file.layers(), &mut layers);
+
+    for uuid in layers {
+        let data = file.decode_layer(&uuid).map_err(|err| err.to_string())?;
+        // Layers without pixels make no image
+        if data.width() == 0 || data.height() == 0 {
+            println!("{}: empty", names[&uuid]);
+            continue;
+        }
+        let target = out.join(&names[&uuid]);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+        }
+        data.to_rgba_image()
+            .map_err(|err| err.to_string())?
+            .save_with_format(&target, image::ImageFormat::Png)
+            .map_err(|err| err.to_string())?;
+        println!(
+            "{}: {}x{} at ({}, {})",
+            names[&uuid],
+            data.width(),
+            data.height(),
+            data.x(),
+            data.y()
+        );
+    }
+    Ok(())
+}
+
+fn main() {
+    let mut args = args().skip(1);
+    let path: PathBuf = args.next().expect("Expected path to file").into();
+    let out: PathBuf = args.next().expect("Expected output directory").into();
+    if let Err(what) = export(&path, &out) {
+        eprintln!("{}: {}", path.display(), what);
+        exit(1);
+    }
+}
@@ -0,0 +1,110 @@
+//! Print a JSON manifest of the document: the image, its author and the tree of nodes.
+//!
+//! `cargo run --example manifest -- image.kra` prints the manifest to standard output.
+//! It is made from [`kra::model`], whose types only change along with its version.
+
+use std::{env::args, fmt::Write, path::PathBuf, process::exit};
+
+use kra::{
+    model::{self, Document, LayerCommon, Node, Properties},
+    KraFile,
+};
+
+fn string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// Properties of the layer and its child layers, if the node is a layer
+fn layer(properties: &Properties) -> (Option<&LayerCommon>, &[Node]) {
+    match properties {
+        Properties::Group { layer, layers, .. } => (Some(layer), layers),
+        Properties::Paint { layer, .. }
+        | Properties::File { layer, .. }
+        | Properties::Filter { layer, .. }
+        | Properties::Fill { layer, .. }
+        | Properties::Clone { layer, .. }
+        | Properties::Vector { layer } => (Some(layer), &[]),
+        _ => (None, &[]),
+    }
+}
+
+fn nodes(nodes: &[Node], indent: usize) -> String {
+    if nodes.is_empty() {
+        return "[]".to_owned();
+    }
+    let pad = "  ".repeat(indent);
+    let items: Vec<String> = nodes
+        .iter()
+        .map(|node| {
+            let (common, children) = layer(&node.properties);
+            let mut fields = vec![
+                format!("\"kind\": {}", string(&node.kind)),
+                format!("\"uuid\": {}", string(&node.uuid)),
+                format!("\"name\": {}", string(&node.name)),
+                format!("\"visible\": {}", node.visible),
+                format!("\"x\": {}", node.x),
+                format!("\"y\": {}", node.y),
+            ];
+            if let Some(common) = common {
+                fields.push(format!(
+                    "\"composite_op\": {}",
+                    string(&common.composite_op)
+                ));
+                fields.push(format!("\"opacity\": {}", common.opacity));
+            }
+            fields.push(format!(
+                "\"masks\": {}",
+                self::nodes(&node.masks, indent + 2)
+            ));
+            if let Properties::Group { .. } = node.properties {
+                fields.push(format!("\"layers\": {}", self::nodes(children, indent + 2)));
+            }
+            format!(
+                "{pad}  {{\n{pad}    {}\n{pad}  }}",
+                fields.join(&format!(",\n{pad}    "))
+            )
+        })
+        .collect();
+    format!("[\n{}\n{pad}]", items.join(",\n"))
+}
+
+fn manifest(document: &Document) -> String {
+    let image = &document.image;
+    let info = &document.info;
+    format!(
+        "{{\n  \"version\": {},\n  \"name\": {},\n  \"width\": {},\n  \"height\": {},\n  \
+         \"colorspace\": {},\n  \"krita_version\": {},\n  \"title\": {},\n  \"author\": {},\n  \
+         \"layers\": {}\n}}",
+        model::VERSION,
+        string(&image.name),
+        image.width,
+        image.height,
+        string(&image.colorspace),
+        string(&image.krita_version),
+        string(&info.title),
+        string(&info.author_full_name),
+        nodes(&document.layers, 1)
+    )
+}
+
+fn main() {
+    let path: PathBuf = args().nth(1).expect("Expected path to file").into();
+    match KraFile::read(&path) {
+        Ok(file) => println!("{}", manifest(&Document::from(&file))),
+        Err(what) => {
+            eprintln!("{}: {}", path.display(), what);
+            exit(1);
+        }
+    }
+}
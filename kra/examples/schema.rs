@@ -0,0 +1,13 @@
+//! Print the description of attributes that are read from nodes.
+//!
+//! `cargo run --example schema` prints Markdown, `cargo run --example schema -- --json` JSON.
+
+use std::env;
+
+fn main() {
+    if env::args().any(|arg| arg == "--json") {
+        println!("{}", kra::schema::to_json());
+    } else {
+        print!("{}", kra::schema::to_markdown());
+    }
+}
@@ -0,0 +1,18 @@
+use std::{env::args, path::PathBuf, process::exit};
+
+use kra::KraFile;
+
+// textconv driver for `git diff`, see KraFile::textconv()
+fn main() {
+    let path: PathBuf = args().nth(1).expect("Expected path to file").into();
+    let text = KraFile::read(&path)
+        .map_err(|err| err.to_string())
+        .and_then(|mut file| file.textconv().map_err(|err| err.to_string()));
+    match text {
+        Ok(text) => print!("{}", text),
+        Err(what) => {
+            eprintln!("{}: {}", path.display(), what);
+            exit(1);
+        }
+    }
+}
@@ -0,0 +1,32 @@
+//! Save a thumbnail of the document, made from its merged image.
+//!
+//! `cargo run --example thumbnail --features image -- image.kra thumbnail.png [size]`
+//! writes an image that fits into `size` by `size` pixels, 256 by default. Documents without
+//! `mergedimage.png` can only be read with `--features render`, which composites them.
+
+use std::{env::args, path::PathBuf, process::exit};
+
+use kra::KraFile;
+
+fn main() {
+    let mut args = args().skip(1);
+    let path: PathBuf = args.next().expect("Expected path to file").into();
+    let out: PathBuf = args.next().expect("Expected path to the thumbnail").into();
+    let size: u32 = args
+        .next()
+        .map_or(256, |size| size.parse().expect("Invalid size"));
+
+    let result = KraFile::read(&path)
+        .map_err(|err| err.to_string())
+        .and_then(|mut file| file.merged_image_decoded().map_err(|err| err.to_string()))
+        .and_then(|image| {
+            image
+                .thumbnail(size, size)
+                .save_with_format(&out, image::ImageFormat::Png)
+                .map_err(|err| err.to_string())
+        });
+    if let Err(what) = result {
+        eprintln!("{}: {}", path.display(), what);
+        exit(1);
+    }
+}
@@ -0,0 +1,81 @@
+//! Report problems of the document, like the ones a pipeline checks before accepting assets.
+//!
+//! `cargo run --example validate -- image.kra` prints what is wrong, one problem per line,
+//! and exits with 1 if there is anything, or with 2 if the document cannot be read.
+
+use std::{env::args, path::PathBuf, process::exit};
+
+use kra::{
+    config::{DoctypeCheck, InvalidUtf8, MimetypeCheck, ParsingConfiguration, UnknownAttributes},
+    layer::CompositeOp,
+    lint::LintRules,
+    KraFile,
+};
+
+// Problems of the document, for people to read
+fn problems(file: &mut KraFile) -> Result<Vec<String>, String> {
+    let mut problems: Vec<String> = Vec::new();
+    problems.extend(file.unexpected_mimetype().map(ToString::to_string));
+    problems.extend(file.doctype_mismatches().iter().map(ToString::to_string));
+    problems.extend(file.replaced_utf8().iter().map(ToString::to_string));
+    problems.extend(file.duplicate_uuids().iter().map(ToString::to_string));
+    problems.extend(file.unknown_attributes().iter().map(ToString::to_string));
+    problems.extend(
+        file.orphaned_entries()
+            .iter()
+            .map(|entry| format!("entry {entry} does not belong to any node")),
+    );
+
+    let paths = file.display_paths();
+    let empty = file.find_empty_layers().map_err(|err| err.to_string())?;
+    problems.extend(
+        empty
+            .empty()
+            .iter()
+            .map(|uuid| format!("layer {} is empty", paths[uuid])),
+    );
+    problems.extend(
+        empty
+            .hidden_with_content()
+            .iter()
+            .map(|uuid| format!("layer {} is hidden, but has content", paths[uuid])),
+    );
+
+    let rules = LintRules::new()
+        .max_dimensions(16384, 16384)
+        .forbid_blend_mode(CompositeOp::Dissolve);
+    problems.extend(
+        file.lint(&rules)
+            .violations()
+            .iter()
+            .map(ToString::to_string),
+    );
+    Ok(problems)
+}
+
+fn main() {
+    let path: PathBuf = args().nth(1).expect("Expected path to file").into();
+    // Anything that can be read past is reported instead of failing
+    let config = ParsingConfiguration::new()
+        .unknown_attributes(UnknownAttributes::Warn)
+        .doctype_check(DoctypeCheck::Warn)
+        .mimetype_check(MimetypeCheck::Warn)
+        .invalid_utf8(InvalidUtf8::Replace);
+    let result = KraFile::read_with_config(&path, config)
+        .map_err(|err| err.to_string())
+        .and_then(|mut file| problems(&mut file));
+    match result {
+        Ok(problems) => {
+            for problem in &problems {
+                println!("{}: {}", path.display(), problem);
+            }
+            if !problems.is_empty() {
+                exit(1);
+            }
+        }
+        Err(what) => {
+            eprintln!("{}: {}", path.display(), what);
+            exit(2);
+        }
+    }
+}
@@ -0,0 +1,272 @@
+//! Annotations - extra entries that Krita stores alongside the image.
+//!
+//! These are entries in the `annotations/` directory of the archive, such as
+//! the ICC profile, EXIF data or session and window layout state.
+//!
+//! Document-level metadata of pipelines is kept in an annotation of its own,
+//! see [`KraFile::pipeline_metadata()`](crate::KraFile::pipeline_metadata).
+//!
+//! Session artifacts that may be embedded by Krita or its plugins, like task sets and
+//! recorded actions, are recognised by [`Annotation::artifact()`].
+
+use std::collections::BTreeMap;
+
+use quick_xml::{
+    escape::{escape, unescape},
+    events::{BytesStart, Event},
+    Reader as XmlReader,
+};
+
+use crate::{
+    error::XmlError,
+    helper::{event_get_all_attrs, event_get_attr, event_get_optional_attr, next_xml_event},
+};
+
+/// Name of the annotation with pipeline metadata.
+///
+/// It holds an XML document with one `<entry key="...">value</entry>` per key.
+pub const PIPELINE_METADATA: &str = "kra-rs.pipeline";
+
+/// Content of an annotation.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum AnnotationContent {
+    /// XML document, such as session or window layout state.
+    Xml(String),
+    /// Any other data.
+    Binary(Vec<u8>),
+}
+
+impl AnnotationContent {
+    // XML is recognised by its first non-whitespace character
+    pub(crate) fn new(data: Vec<u8>) -> Self {
+        match String::from_utf8(data) {
+            Ok(text) if text.trim_start().starts_with('<') => AnnotationContent::Xml(text),
+            Ok(text) => AnnotationContent::Binary(text.into_bytes()),
+            Err(err) => AnnotationContent::Binary(err.into_bytes()),
+        }
+    }
+}
+
+/// One annotation of the document.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Annotation {
+    /// Name of the annotation, which is its path relative to `annotations/`.
+    pub(crate) name: String,
+    /// Content of the annotation.
+    pub(crate) content: AnnotationContent,
+}
+
+impl Annotation {
+    /// Name of the annotation, which is its path relative to `annotations/`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Content of the annotation.
+    pub fn content(&self) -> &AnnotationContent {
+        &self.content
+    }
+
+    /// Content as XML text, if it is XML.
+    pub fn as_xml(&self) -> Option<&str> {
+        match &self.content {
+            AnnotationContent::Xml(text) => Some(text),
+            AnnotationContent::Binary(_) => None,
+        }
+    }
+
+    /// Content as bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        match &self.content {
+            AnnotationContent::Xml(text) => text.as_bytes(),
+            AnnotationContent::Binary(data) => data,
+        }
+    }
+
+    /// Session artifact held by the annotation, recognised by the root element of its XML.
+    ///
+    /// This is best-effort: `None` is returned for annotations that are not artifacts
+    /// and for ones that cannot be parsed.
+    pub fn artifact(&self) -> Option<Artifact> {
+        parse_artifact(self.as_xml()?).ok().flatten()
+    }
+}
+
+/// Session artifact embedded into the document, see [`Annotation::artifact()`].
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum Artifact {
+    /// Task set, which is a list of actions shown in the task set docker.
+    Taskset(Taskset),
+    /// Actions recorded by the macro recorder of old versions of Krita, in order.
+    RecordedActions(Vec<RecordedAction>),
+}
+
+/// Task set: named list of actions, as in Krita's `.kts` files.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Taskset {
+    pub(crate) name: String,
+    pub(crate) version: String,
+    pub(crate) actions: Vec<String>,
+}
+
+impl Taskset {
+    /// Name of the task set.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Version of the format, empty if not given.
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Names of the actions (like `edit_undo`), in order.
+    pub fn actions(&self) -> &[String] {
+        &self.actions
+    }
+}
+
+/// One recorded action.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct RecordedAction {
+    pub(crate) id: String,
+    pub(crate) name: String,
+    pub(crate) attributes: Vec<(String, String)>,
+}
+
+impl RecordedAction {
+    /// Identifier of the kind of action (like `FreehandPaintAction`), empty if not given.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Name of the action, empty if not given.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// All attributes of the action, including `id` and `name`, in the order they were written.
+    /// Parameters written as child elements are not kept.
+    pub fn attributes(&self) -> &[(String, String)] {
+        &self.attributes
+    }
+}
+
+// The root element tells the kind of artifact; Ok(None) if it is not one
+fn parse_artifact(text: &str) -> Result<Option<Artifact>, XmlError> {
+    let mut reader = XmlReader::from_str(text);
+    reader.trim_text(true);
+    let root = loop {
+        match next_xml_event(&mut reader)? {
+            Event::Start(tag) | Event::Empty(tag) => break tag,
+            Event::Eof => return Ok(None),
+            _ => {}
+        }
+    };
+    match root.name().as_ref() {
+        b"Taskset" => {
+            let mut taskset = Taskset {
+                name: attr_or_empty(&root, "name")?,
+                version: attr_or_empty(&root, "version")?,
+                actions: Vec::new(),
+            };
+            loop {
+                match next_xml_event(&mut reader)? {
+                    Event::Start(tag) if tag.name().as_ref() == b"action" => {
+                        let action = reader.read_text(tag.name())?;
+                        taskset.actions.push(
+                            unescape(action.trim())
+                                .map_err(quick_xml::Error::from)?
+                                .into_owned(),
+                        );
+                    }
+                    Event::Eof => return Ok(Some(Artifact::Taskset(taskset))),
+                    _ => {}
+                }
+            }
+        }
+        b"RecordedActions" => {
+            let mut actions = Vec::new();
+            let mut depth = 0usize;
+            loop {
+                match next_xml_event(&mut reader)? {
+                    Event::Start(tag) => {
+                        if depth == 0 && tag.name().as_ref() == b"RecordedAction" {
+                            actions.push(recorded_action(&tag)?);
+                        }
+                        depth += 1;
+                    }
+                    Event::Empty(tag) if depth == 0 && tag.name().as_ref() == b"RecordedAction" => {
+                        actions.push(recorded_action(&tag)?);
+                    }
+                    Event::End(_) => depth = depth.saturating_sub(1),
+                    Event::Eof => return Ok(Some(Artifact::RecordedActions(actions))),
+                    _ => {}
+                }
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+fn recorded_action(tag: &BytesStart) -> Result<RecordedAction, XmlError> {
+    let attributes = event_get_all_attrs(tag)?;
+    let get = |key: &str| {
+        attributes
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.clone())
+            .unwrap_or_default()
+    };
+    Ok(RecordedAction {
+        id: get("id"),
+        name: get("name"),
+        attributes,
+    })
+}
+
+fn attr_or_empty(tag: &BytesStart, name: &str) -> Result<String, XmlError> {
+    Ok(match event_get_optional_attr(tag, name)? {
+        Some(attr) => attr.unescape_value()?.into_owned(),
+        None => String::new(),
+    })
+}
+
+// Entries of the pipeline metadata annotation; elements other than <entry> are skipped
+pub(crate) fn parse_pipeline_metadata(text: &str) -> Result<BTreeMap<String, String>, XmlError> {
+    let mut reader = XmlReader::from_str(text);
+    let mut metadata = BTreeMap::new();
+    loop {
+        match next_xml_event(&mut reader)? {
+            Event::Start(tag) if tag.name().as_ref() == b"entry" => {
+                let key = event_get_attr(&tag, "key")?.unescape_value()?.into_owned();
+                let value = reader.read_text(tag.name())?;
+                metadata.insert(
+                    key,
+                    unescape(&value)
+                        .map_err(quick_xml::Error::from)?
+                        .into_owned(),
+                );
+            }
+            Event::Empty(tag) if tag.name().as_ref() == b"entry" => {
+                let key = event_get_attr(&tag, "key")?.unescape_value()?.into_owned();
+                metadata.insert(key, String::new());
+            }
+            Event::Eof => return Ok(metadata),
+            _ => {}
+        }
+    }
+}
+
+pub(crate) fn write_pipeline_metadata(metadata: &BTreeMap<String, String>) -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<pipeline-metadata>\n");
+    for (key, value) in metadata {
+        out.push_str(&format!(
+            " <entry key=\"{}\">{}</entry>\n",
+            escape(key.as_str()),
+            escape(value.as_str())
+        ));
+    }
+    out.push_str("</pipeline-metadata>\n");
+    out
+}
@@ -0,0 +1,385 @@
+//! Storage of documents' entries.
+//!
+//! [`KraFile`] reads entries through [`ArchiveSource`], which is implemented
+//! for zip archives (in files or in memory, see [`ZipSource`]) and for directories
+//! that mirror their layout (see [`DirectorySource`]). [`pack()`] and [`unpack()`] convert
+//! between the two.
+
+use std::{
+    fmt::Debug,
+    fs::{self, File},
+    io::{self, Cursor, Read, Seek},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use zip::{
+    read::ZipFile,
+    result::{ZipError, ZipResult},
+    ZipArchive,
+};
+
+use crate::{
+    config::ParsingConfiguration,
+    error::{PackError, ReadKraError},
+    metadata::format_date,
+    writer::WriteOptions,
+    KraFile,
+};
+
+/// Source of entries of a document.
+///
+/// Errors are reported as [`ZipError`] whatever the source is, with
+/// [`ZipError::FileNotFound`] for missing entries.
+pub trait ArchiveSource: Debug + Send {
+    /// Names of all entries, in archive order. Names of directories end with `/`.
+    fn entry_names(&self) -> &[String];
+
+    /// Reader of the entry's contents.
+    fn by_name(&mut self, name: &str) -> ZipResult<ArchiveEntry<'_>>;
+
+    /// CRC-32 checksum of the entry's contents.
+    ///
+    /// By default, it is calculated by reading the whole entry.
+    fn crc32(&mut self, name: &str) -> ZipResult<u32> {
+        let mut entry = self.by_name(name)?;
+        let mut hasher = crc32fast::Hasher::new();
+        let mut buffer = [0; 8192];
+        loop {
+            match entry.read(&mut buffer)? {
+                0 => return Ok(hasher.finalize()),
+                read => hasher.update(&buffer[..read]),
+            }
+        }
+    }
+
+    /// The entry as it is stored in a zip archive, to copy it without recompressing.
+    /// Sources that are not zip archives return `None`.
+    fn raw_zip_entry(&mut self, _name: &str) -> Option<ZipResult<ZipFile<'_>>> {
+        None
+    }
+
+    /// Another, independent source of the same entries, so that they can be read
+    /// from several threads at once. Sources that cannot be opened again return `None`.
+    fn reopen(&self) -> Option<ZipResult<Box<dyn ArchiveSource>>> {
+        None
+    }
+
+    /// Size and modification time of the entry, without reading its contents.
+    ///
+    /// By default, only the size is known.
+    fn entry_info(&mut self, name: &str) -> ZipResult<EntryInfo> {
+        let size = self.by_name(name)?.size();
+        Ok(EntryInfo::new(name, size))
+    }
+}
+
+/// Size and modification time of an entry, see [`ArchiveSource::entry_info()`].
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct EntryInfo {
+    pub(crate) name: String,
+    pub(crate) size: u64,
+    pub(crate) compressed_size: Option<u64>,
+    pub(crate) modified: Option<String>,
+    pub(crate) changed: bool,
+}
+
+impl EntryInfo {
+    /// Entry of the given size, with nothing else known about it.
+    pub fn new(name: &str, size: u64) -> Self {
+        EntryInfo {
+            name: name.to_owned(),
+            size,
+            compressed_size: None,
+            modified: None,
+            changed: false,
+        }
+    }
+
+    /// Set the size of the entry as it is stored.
+    pub fn with_compressed_size(mut self, compressed_size: u64) -> Self {
+        self.compressed_size = Some(compressed_size);
+        self
+    }
+
+    /// Set when the entry was last modified, as `YYYY-MM-DDTHH:MM:SS`.
+    pub fn with_modified(mut self, modified: impl Into<String>) -> Self {
+        self.modified = Some(modified.into());
+        self
+    }
+
+    /// Name of the entry.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Size of the contents, in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Size of the entry as it is stored, if it is compressed by the source.
+    pub fn compressed_size(&self) -> Option<u64> {
+        self.compressed_size
+    }
+
+    /// When the entry was last modified, as `YYYY-MM-DDTHH:MM:SS`. Zip archives store
+    /// the local time of the one who saved them, directories give it in UTC.
+    pub fn modified(&self) -> Option<&str> {
+        self.modified.as_deref()
+    }
+
+    /// Whether the entry was changed in memory and not written yet, in which case
+    /// its size is the new one and nothing else is known.
+    pub fn changed(&self) -> bool {
+        self.changed
+    }
+}
+
+/// Contents of an entry, see [`ArchiveSource::by_name()`].
+pub struct ArchiveEntry<'a> {
+    size: u64,
+    reader: Box<dyn Read + 'a>,
+}
+
+impl<'a> ArchiveEntry<'a> {
+    /// Entry with the given (declared) size.
+    pub fn new<R: Read + 'a>(size: u64, reader: R) -> Self {
+        ArchiveEntry {
+            size,
+            reader: Box::new(reader),
+        }
+    }
+
+    /// Size of the contents, as declared by the source.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl Read for ArchiveEntry<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+/// Zip archive, like `.kra` files are.
+///
+/// Archives that were opened from a path or from bytes can be
+/// [reopened](ArchiveSource::reopen), others cannot.
+#[derive(Debug)]
+pub struct ZipSource<R: Read + Seek> {
+    zip: ZipArchive<R>,
+    // In archive order, which ZipArchive::file_names() does not keep
+    names: Vec<String>,
+    origin: Origin,
+}
+
+// Where the archive can be opened again from
+#[derive(Debug, Clone)]
+enum Origin {
+    Unknown,
+    Path(PathBuf),
+    Bytes(Arc<[u8]>),
+}
+
+impl<R: Read + Seek> ZipSource<R> {
+    /// Read the list of entries of the archive.
+    pub fn new(reader: R) -> ZipResult<Self> {
+        let mut zip = ZipArchive::new(reader)?;
+        let names = (0..zip.len())
+            .map(|index| zip.by_index_raw(index).map(|entry| entry.name().to_owned()))
+            .collect::<ZipResult<_>>()?;
+        Ok(ZipSource {
+            zip,
+            names,
+            origin: Origin::Unknown,
+        })
+    }
+
+    /// The underlying archive.
+    pub fn archive(&mut self) -> &mut ZipArchive<R> {
+        &mut self.zip
+    }
+}
+
+impl ZipSource<File> {
+    /// Open the archive at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> ZipResult<Self> {
+        Self::from_file(File::open(&path)?, path.as_ref())
+    }
+
+    // Archive in `file`, which was opened from `path`
+    pub(crate) fn from_file(file: File, path: &Path) -> ZipResult<Self> {
+        let mut source = Self::new(file)?;
+        // The working directory may change before the archive is opened again
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+        source.origin = Origin::Path(path);
+        Ok(source)
+    }
+}
+
+impl ZipSource<Cursor<Arc<[u8]>>> {
+    /// Archive that is already in memory.
+    pub fn from_bytes<D: Into<Arc<[u8]>>>(data: D) -> ZipResult<Self> {
+        let data = data.into();
+        let mut source = Self::new(Cursor::new(data.clone()))?;
+        source.origin = Origin::Bytes(data);
+        Ok(source)
+    }
+}
+
+impl<R: Read + Seek + Send + Debug> ArchiveSource for ZipSource<R> {
+    fn entry_names(&self) -> &[String] {
+        &self.names
+    }
+
+    fn by_name(&mut self, name: &str) -> ZipResult<ArchiveEntry<'_>> {
+        let entry = self.zip.by_name(name)?;
+        Ok(ArchiveEntry::new(entry.size(), entry))
+    }
+
+    fn crc32(&mut self, name: &str) -> ZipResult<u32> {
+        Ok(self.zip.by_name(name)?.crc32())
+    }
+
+    fn raw_zip_entry(&mut self, name: &str) -> Option<ZipResult<ZipFile<'_>>> {
+        let index = self.names.iter().position(|entry| entry == name)?;
+        Some(self.zip.by_index_raw(index))
+    }
+
+    fn reopen(&self) -> Option<ZipResult<Box<dyn ArchiveSource>>> {
+        let source: ZipResult<Box<dyn ArchiveSource>> = match &self.origin {
+            Origin::Unknown => return None,
+            Origin::Path(path) => ZipSource::open(path).map(|source| Box::new(source) as _),
+            Origin::Bytes(data) => {
+                ZipSource::from_bytes(data.clone()).map(|source| Box::new(source) as _)
+            }
+        };
+        Some(source)
+    }
+
+    fn entry_info(&mut self, name: &str) -> ZipResult<EntryInfo> {
+        let index = self
+            .names
+            .iter()
+            .position(|entry| entry == name)
+            .ok_or(ZipError::FileNotFound)?;
+        let entry = self.zip.by_index_raw(index)?;
+        let modified = entry.last_modified();
+        let modified = format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            modified.year(),
+            modified.month(),
+            modified.day(),
+            modified.hour(),
+            modified.minute(),
+            modified.second()
+        );
+        Ok(EntryInfo::new(name, entry.size())
+            .with_compressed_size(entry.compressed_size())
+            .with_modified(modified))
+    }
+}
+
+/// Directory with the same layout as a `.kra` archive (`mimetype`, `maindoc.xml`, and so on).
+///
+/// Only regular files are entries. Symbolic links, files with names that are not valid UTF-8,
+/// and hidden files and directories (like `.git` or `.DS_Store`) are ignored.
+#[derive(Debug, Clone)]
+pub struct DirectorySource {
+    root: PathBuf,
+    // `mimetype` first, others sorted
+    names: Vec<String>,
+}
+
+impl DirectorySource {
+    /// Collect the entries inside of `root`.
+    pub fn open<P: AsRef<Path>>(root: P) -> io::Result<Self> {
+        let root = root.as_ref().to_owned();
+        let mut names = Vec::new();
+        collect_files(&root, "", &mut names)?;
+        names.sort_by(|a, b| (a != "mimetype", a).cmp(&(b != "mimetype", b)));
+        Ok(DirectorySource { root, names })
+    }
+
+    /// The directory the entries are in.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+}
+
+impl ArchiveSource for DirectorySource {
+    fn entry_names(&self) -> &[String] {
+        &self.names
+    }
+
+    fn by_name(&mut self, name: &str) -> ZipResult<ArchiveEntry<'_>> {
+        // Only names that were found are opened, so they cannot point outside of the root
+        if !self.names.iter().any(|entry| entry == name) {
+            return Err(ZipError::FileNotFound);
+        }
+        let file = File::open(self.root.join(name))?;
+        Ok(ArchiveEntry::new(file.metadata()?.len(), file))
+    }
+
+    fn reopen(&self) -> Option<ZipResult<Box<dyn ArchiveSource>>> {
+        Some(Ok(Box::new(self.clone())))
+    }
+
+    fn entry_info(&mut self, name: &str) -> ZipResult<EntryInfo> {
+        if !self.names.iter().any(|entry| entry == name) {
+            return Err(ZipError::FileNotFound);
+        }
+        let metadata = fs::metadata(self.root.join(name))?;
+        let info = EntryInfo::new(name, metadata.len());
+        Ok(match metadata.modified() {
+            Ok(modified) => info.with_modified(format_date(modified)),
+            Err(_) => info,
+        })
+    }
+}
+
+// Appends paths of regular files under `dir`, relative to the root and separated with `/`
+fn collect_files(dir: &Path, prefix: &str, names: &mut Vec<String>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        if name.starts_with('.') {
+            continue;
+        }
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(&entry.path(), &format!("{}{}/", prefix, name), names)?;
+        } else if file_type.is_file() {
+            names.push(format!("{}{}", prefix, name));
+        }
+    }
+    Ok(())
+}
+
+// Entries are copied as they are, nodes do not need to be parsed
+fn copy_config() -> ParsingConfiguration {
+    ParsingConfiguration::default().parse_layers(false)
+}
+
+/// Pack the unpacked document in `dir` into an archive at `output`.
+///
+/// Contents of entries are kept as they are, their order and modification times are not.
+pub fn pack<P: AsRef<Path>, Q: AsRef<Path>>(dir: P, output: Q) -> Result<(), PackError> {
+    let source = DirectorySource::open(dir).map_err(ReadKraError::from)?;
+    let mut file = KraFile::read_from(source, copy_config())?;
+    file.write(File::create(output)?, &WriteOptions::new())?;
+    Ok(())
+}
+
+/// Unpack the archive at `path` into `dir`, see [`KraFile::write_dir()`].
+///
+/// Contents of entries are kept as they are, their order and modification times are not.
+pub fn unpack<P: AsRef<Path>, Q: AsRef<Path>>(path: P, dir: Q) -> Result<(), PackError> {
+    let mut file = KraFile::read_with_config(path, copy_config())?;
+    file.write_dir(dir, &WriteOptions::new())?;
+    Ok(())
+}
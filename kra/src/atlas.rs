@@ -0,0 +1,600 @@
+//! Packing layers into texture atlases, along with where each layer was placed.
+
+use crate::{
+    error::{AtlasError, DataError},
+    helper::json_string,
+    id::Uuid,
+    layer::NodeType,
+    KraFile,
+};
+
+/// Options of [`KraFile::pack_atlas()`].
+///
+/// By default, pages are at most 2048 pixels wide and high, and layers are 1 pixel apart.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct AtlasOptions {
+    pub(crate) max_size: u32,
+    pub(crate) padding: u32,
+}
+
+impl Default for AtlasOptions {
+    fn default() -> Self {
+        AtlasOptions {
+            max_size: 2048,
+            padding: 1,
+        }
+    }
+}
+
+impl AtlasOptions {
+    /// Default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the largest width and height of a page.
+    pub fn max_size(mut self, max_size: u32) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Largest width and height of a page.
+    pub fn get_max_size(&self) -> u32 {
+        self.max_size
+    }
+
+    /// Set how many transparent pixels are left between layers, so that they do not bleed
+    /// into each other when the atlas is sampled.
+    pub fn padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// How many transparent pixels are left between layers.
+    pub fn get_padding(&self) -> u32 {
+        self.padding
+    }
+}
+
+/// Where a layer was placed in an atlas.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Sprite {
+    /// The layer.
+    pub(crate) uuid: Uuid,
+    /// Name of the layer.
+    pub(crate) name: String,
+    /// Notes about the layer.
+    pub(crate) description: Option<String>,
+    /// Index of the page.
+    pub(crate) page: usize,
+    /// Horizontal position in the page.
+    pub(crate) x: u32,
+    /// Vertical position in the page.
+    pub(crate) y: u32,
+    /// Width of the trimmed pixels.
+    pub(crate) width: u32,
+    /// Height of the trimmed pixels.
+    pub(crate) height: u32,
+    /// Position of the trimmed pixels in the image.
+    pub(crate) source: (i32, i32),
+    /// Insets from guides that cross the sprite.
+    pub(crate) nine_slice: Option<NineSlice>,
+}
+
+impl Sprite {
+    /// The layer.
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    /// Name of the layer.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Notes about the layer, see [`Node::description()`](crate::layer::Node::description).
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Index of the page.
+    pub fn page(&self) -> usize {
+        self.page
+    }
+
+    /// Horizontal position in the page.
+    pub fn x(&self) -> u32 {
+        self.x
+    }
+
+    /// Vertical position in the page.
+    pub fn y(&self) -> u32 {
+        self.y
+    }
+
+    /// Width of the trimmed pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the trimmed pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Position of the trimmed pixels in the image, which places the sprite back where
+    /// the layer was.
+    pub fn source(&self) -> (i32, i32) {
+        self.source
+    }
+
+    /// Insets from guides that cross the sprite, see [`KraFile::nine_slice()`].
+    pub fn nine_slice(&self) -> Option<NineSlice> {
+        self.nine_slice
+    }
+}
+
+/// Distances from the edges of a sprite to the guides that cut it into nine slices. Corners
+/// are kept as they are when the sprite is stretched, edges are stretched along them, and the
+/// center is stretched both ways.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct NineSlice {
+    /// From the left edge to the first vertical guide.
+    pub(crate) left: u32,
+    /// From the top edge to the first horizontal guide.
+    pub(crate) top: u32,
+    /// From the last vertical guide to the right edge.
+    pub(crate) right: u32,
+    /// From the last horizontal guide to the bottom edge.
+    pub(crate) bottom: u32,
+}
+
+impl NineSlice {
+    /// From the left edge to the first vertical guide.
+    pub fn left(&self) -> u32 {
+        self.left
+    }
+
+    /// From the top edge to the first horizontal guide.
+    pub fn top(&self) -> u32 {
+        self.top
+    }
+
+    /// From the last vertical guide to the right edge.
+    pub fn right(&self) -> u32 {
+        self.right
+    }
+
+    /// From the last horizontal guide to the bottom edge.
+    pub fn bottom(&self) -> u32 {
+        self.bottom
+    }
+}
+
+/// One image of an atlas.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AtlasPage {
+    /// Width, in pixels.
+    pub(crate) width: u32,
+    /// Height, in pixels.
+    pub(crate) height: u32,
+    /// 8-bit RGBA values with straight alpha, row by row.
+    pub(crate) pixels: Vec<u8>,
+}
+
+impl AtlasPage {
+    /// Width, in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height, in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// 8-bit RGBA values with straight alpha, row by row.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// The page as an image of the `image` crate.
+    #[cfg(feature = "image")]
+    pub fn to_image(&self) -> image::RgbaImage {
+        image::RgbaImage::from_raw(self.width, self.height, self.pixels.clone())
+            .expect("buffer has the size of the page")
+    }
+}
+
+/// Layers packed into one or more pages, see [`KraFile::pack_atlas()`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Atlas {
+    /// Width and height of the image the layers come from.
+    pub(crate) image_size: (u32, u32),
+    /// Images of the atlas.
+    pub(crate) pages: Vec<AtlasPage>,
+    /// Placed layers, in the order they were given.
+    pub(crate) sprites: Vec<Sprite>,
+}
+
+impl Atlas {
+    /// Width and height of the image the layers come from.
+    pub fn image_size(&self) -> (u32, u32) {
+        self.image_size
+    }
+
+    /// Images of the atlas.
+    pub fn pages(&self) -> &[AtlasPage] {
+        &self.pages
+    }
+
+    /// Placed layers, in the order they were given.
+    pub fn sprites(&self) -> &[Sprite] {
+        &self.sprites
+    }
+
+    /// Placement of layers as JSON: an object with the `image` size, `pages` with their
+    /// sizes, and `sprites`, each with its `uuid`, `name`, `page`, position and size in
+    /// the page (`x`, `y`, `width`, `height`) and position in the image (`source_x`,
+    /// `source_y`). Sprites of layers with a [description](Sprite::description) also have
+    /// `description`, and ones with [nine-slice insets](Sprite::nine_slice) have `nine_slice`,
+    /// with `left`, `top`, `right` and `bottom`.
+    pub fn to_json(&self) -> String {
+        let pages: Vec<String> = self
+            .pages
+            .iter()
+            .map(|page| format!("{{\"width\":{},\"height\":{}}}", page.width, page.height))
+            .collect();
+        let sprites: Vec<String> = self
+            .sprites
+            .iter()
+            .map(|sprite| {
+                let description = sprite.description.as_ref().map_or(String::new(), |text| {
+                    format!(",\"description\":{}", json_string(text))
+                });
+                let nine_slice = sprite.nine_slice.map_or(String::new(), |insets| {
+                    format!(
+                        ",\"nine_slice\":{{\"left\":{},\"top\":{},\"right\":{},\"bottom\":{}}}",
+                        insets.left, insets.top, insets.right, insets.bottom
+                    )
+                });
+                format!(
+                    "{{\"uuid\":{},\"name\":{},\"page\":{},\"x\":{},\"y\":{},\"width\":{},\"height\":{},\"source_x\":{},\"source_y\":{}{}{}}}",
+                    json_string(&sprite.uuid.to_string()),
+                    json_string(&sprite.name),
+                    sprite.page,
+                    sprite.x,
+                    sprite.y,
+                    sprite.width,
+                    sprite.height,
+                    sprite.source.0,
+                    sprite.source.1,
+                    description,
+                    nine_slice
+                )
+            })
+            .collect();
+        format!(
+            "{{\"image\":{{\"width\":{},\"height\":{}}},\"pages\":[{}],\"sprites\":[{}]}}",
+            self.image_size.0,
+            self.image_size.1,
+            pages.join(","),
+            sprites.join(",")
+        )
+    }
+}
+
+// Trimmed pixels of a layer, before they are placed
+struct Bitmap {
+    uuid: Uuid,
+    name: String,
+    description: Option<String>,
+    source: (i32, i32),
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl KraFile {
+    /// Paint layers whose [custom property](crate::layer::Node::custom_property) `key`
+    /// is `value`, in document order. Useful to choose layers for [`pack_atlas()`](Self::pack_atlas).
+    pub fn tagged_layers(&self, key: &str, value: &str) -> Vec<Uuid> {
+        self.all_nodes()
+            .into_iter()
+            .filter(|node| matches!(node.node_type(), NodeType::PaintLayer(_)))
+            .filter(|node| node.custom_property(key) == Some(value))
+            .map(|node| node.uuid())
+            .collect()
+    }
+
+    /// Nine-slice insets of the area of the image at `x` and `y`, from its guides.
+    ///
+    /// Guides are rounded to whole pixels, and only those strictly inside the area are used.
+    /// The first and last of them on each axis are the insets, so there have to be at least
+    /// two different vertical and two different horizontal ones, or there are no insets.
+    pub fn nine_slice(&self, x: i32, y: i32, width: u32, height: u32) -> Option<NineSlice> {
+        // Distances of the first and last guide inside from the start of the area
+        let inside = |guides: Vec<f64>, start: i32, size: u32| {
+            let mut offsets = guides
+                .into_iter()
+                .map(|guide| guide.round() as i64 - start as i64)
+                .filter(|&offset| offset > 0 && offset < size as i64);
+            let first = offsets.next()?;
+            let (first, last) = offsets.fold((first, first), |(first, last), offset| {
+                (first.min(offset), last.max(offset))
+            });
+            (first < last).then(|| (first as u32, size - last as u32))
+        };
+        let (horizontal, vertical) = self.meta().guide_pixels();
+        let (left, right) = inside(vertical, x, width)?;
+        let (top, bottom) = inside(horizontal, y, height)?;
+        Some(NineSlice {
+            left,
+            top,
+            right,
+            bottom,
+        })
+    }
+
+    /// Pack paint layers into one or more pages.
+    ///
+    /// Layers are trimmed to their non-transparent pixels, converted to 8-bit RGBA like
+    /// [`LayerData::to_rgba8()`](crate::data::LayerData::to_rgba8) does, and placed with
+    /// a max-rects packer, larger ones first. Fully transparent layers are left out.
+    /// Visibility and opacity of layers are not applied. Sprites get nine-slice insets from
+    /// the guides that cross their trimmed pixels, see [`nine_slice()`](Self::nine_slice).
+    pub fn pack_atlas(
+        &mut self,
+        layers: &[Uuid],
+        options: &AtlasOptions,
+    ) -> Result<Atlas, AtlasError> {
+        let mut bitmaps = Vec::new();
+        for uuid in layers {
+            let node = self
+                .all_nodes()
+                .into_iter()
+                .find(|node| node.uuid() == *uuid)
+                .ok_or(DataError::UnknownNode(*uuid))?;
+            let (name, description) = (
+                node.name().to_owned(),
+                node.description().map(str::to_owned),
+            );
+            let data = self.decode_layer(uuid)?;
+            let pixels = data.to_rgba8()?;
+            let Some((left, top, width, height)) = trim(&pixels, data.width()) else {
+                continue;
+            };
+            if width > options.max_size || height > options.max_size {
+                return Err(AtlasError::TooLarge(*uuid));
+            }
+            let row = data.width() as usize * 4;
+            let pixels = pixels
+                .chunks_exact(row)
+                .skip(top as usize)
+                .take(height as usize)
+                .flat_map(|line| &line[left as usize * 4..(left + width) as usize * 4])
+                .copied()
+                .collect();
+            bitmaps.push(Bitmap {
+                uuid: *uuid,
+                name,
+                description,
+                source: (data.x() + left as i32, data.y() + top as i32),
+                width,
+                height,
+                pixels,
+            });
+        }
+
+        // Larger bitmaps first leave less unusable space
+        let mut order: Vec<usize> = (0..bitmaps.len()).collect();
+        order.sort_by_key(|&index| {
+            let bitmap = &bitmaps[index];
+            std::cmp::Reverse((
+                bitmap.width.max(bitmap.height),
+                bitmap.width * bitmap.height,
+            ))
+        });
+        // Padding is added to the right and bottom of every bitmap, so pages are larger
+        // by it to fit bitmaps of the largest size
+        let bin_size = options.max_size + options.padding;
+        let mut bins: Vec<MaxRects> = Vec::new();
+        let mut placements = vec![(0, 0, 0); bitmaps.len()];
+        for index in order {
+            let size = (
+                bitmaps[index].width + options.padding,
+                bitmaps[index].height + options.padding,
+            );
+            let placed = bins
+                .iter_mut()
+                .enumerate()
+                .find_map(|(page, bin)| Some((page, bin.insert(size)?)));
+            placements[index] = match placed {
+                Some((page, (x, y))) => (page, x, y),
+                None => {
+                    let mut bin = MaxRects::new(bin_size);
+                    let (x, y) = bin.insert(size).expect("bitmap fits into an empty page");
+                    bins.push(bin);
+                    (bins.len() - 1, x, y)
+                }
+            };
+        }
+
+        let mut pages = vec![
+            AtlasPage {
+                width: 0,
+                height: 0,
+                pixels: Vec::new(),
+            };
+            bins.len()
+        ];
+        for (bitmap, &(page, x, y)) in bitmaps.iter().zip(&placements) {
+            let page = &mut pages[page];
+            page.width = page.width.max(x + bitmap.width);
+            page.height = page.height.max(y + bitmap.height);
+        }
+        for page in &mut pages {
+            page.pixels = vec![0; page.width as usize * page.height as usize * 4];
+        }
+        let mut sprites = Vec::new();
+        for (bitmap, (page, x, y)) in bitmaps.into_iter().zip(placements) {
+            let target = &mut pages[page];
+            let row = target.width as usize * 4;
+            for (line, pixels) in bitmap
+                .pixels
+                .chunks_exact(bitmap.width as usize * 4)
+                .enumerate()
+            {
+                let start = (y as usize + line) * row + x as usize * 4;
+                target.pixels[start..start + pixels.len()].copy_from_slice(pixels);
+            }
+            let nine_slice = self.nine_slice(
+                bitmap.source.0,
+                bitmap.source.1,
+                bitmap.width,
+                bitmap.height,
+            );
+            sprites.push(Sprite {
+                uuid: bitmap.uuid,
+                name: bitmap.name,
+                description: bitmap.description,
+                page,
+                x,
+                y,
+                width: bitmap.width,
+                height: bitmap.height,
+                source: bitmap.source,
+                nine_slice,
+            });
+        }
+
+        Ok(Atlas {
+            image_size: (self.meta().width(), self.meta().height()),
+            pages,
+            sprites,
+        })
+    }
+}
+
+// Bounds of pixels with non-zero alpha, as (left, top, width, height)
+fn trim(pixels: &[u8], width: u32) -> Option<(u32, u32, u32, u32)> {
+    let (mut left, mut top, mut right, mut bottom) = (u32::MAX, u32::MAX, 0, 0);
+    for (index, pixel) in pixels.chunks_exact(4).enumerate() {
+        if pixel[3] == 0 {
+            continue;
+        }
+        let (x, y) = (index as u32 % width, index as u32 / width);
+        left = left.min(x);
+        top = top.min(y);
+        right = right.max(x + 1);
+        bottom = bottom.max(y + 1);
+    }
+    (left < right).then(|| (left, top, right - left, bottom - top))
+}
+
+// Free areas of a square page, as (x, y, width, height), that may overlap each other.
+// See "A Thousand Ways to Pack the Bin" by Jukka Jylänki.
+struct MaxRects {
+    free: Vec<(u32, u32, u32, u32)>,
+}
+
+impl MaxRects {
+    fn new(size: u32) -> Self {
+        MaxRects {
+            free: vec![(0, 0, size, size)],
+        }
+    }
+
+    // Places the rectangle where it leaves the shortest side, returns its position
+    fn insert(&mut self, (width, height): (u32, u32)) -> Option<(u32, u32)> {
+        let &(x, y, _, _) = self
+            .free
+            .iter()
+            .filter(|area| area.2 >= width && area.3 >= height)
+            .min_by_key(|area| {
+                let (left_x, left_y) = (area.2 - width, area.3 - height);
+                (left_x.min(left_y), left_x.max(left_y))
+            })?;
+
+        let (right, bottom) = (x + width, y + height);
+        let mut free = Vec::new();
+        for area in self.free.drain(..) {
+            let (area_right, area_bottom) = (area.0 + area.2, area.1 + area.3);
+            if x >= area_right || right <= area.0 || y >= area_bottom || bottom <= area.1 {
+                free.push(area);
+                continue;
+            }
+            // What is left of the area on each side of the placed rectangle
+            if x > area.0 {
+                free.push((area.0, area.1, x - area.0, area.3));
+            }
+            if right < area_right {
+                free.push((right, area.1, area_right - right, area.3));
+            }
+            if y > area.1 {
+                free.push((area.0, area.1, area.2, y - area.1));
+            }
+            if bottom < area_bottom {
+                free.push((area.0, bottom, area.2, area_bottom - bottom));
+            }
+        }
+
+        // Areas inside of other ones are not needed
+        let contains = |outer: &(u32, u32, u32, u32), inner: &(u32, u32, u32, u32)| {
+            inner.0 >= outer.0
+                && inner.1 >= outer.1
+                && inner.0 + inner.2 <= outer.0 + outer.2
+                && inner.1 + inner.3 <= outer.1 + outer.3
+        };
+        let mut kept: Vec<(u32, u32, u32, u32)> = Vec::new();
+        for (index, area) in free.iter().enumerate() {
+            let redundant = free.iter().enumerate().any(|(other_index, other)| {
+                other_index != index
+                    && contains(other, area)
+                    // Of equal areas, the first one is kept
+                    && (other != area || other_index < index)
+            });
+            if !redundant {
+                kept.push(*area);
+            }
+        }
+        self.free = kept;
+        Some((x, y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packs_without_overlap() {
+        let mut bin = MaxRects::new(100);
+        let sizes = [(100, 10), (50, 30), (50, 30), (30, 40), (20, 20), (70, 10)];
+        let placed: Vec<_> = sizes
+            .iter()
+            .map(|&size| (bin.insert(size).unwrap(), size))
+            .collect();
+        for (index, &((x, y), (width, height))) in placed.iter().enumerate() {
+            assert!(x + width <= 100 && y + height <= 100);
+            for &((other_x, other_y), (other_width, other_height)) in &placed[index + 1..] {
+                let apart = x + width <= other_x
+                    || other_x + other_width <= x
+                    || y + height <= other_y
+                    || other_y + other_height <= y;
+                assert!(apart);
+            }
+        }
+        assert_eq!(bin.insert((101, 1)), None);
+    }
+
+    #[test]
+    fn trims_transparent_pixels() {
+        let alpha = |x: usize, y: usize| (y * 4 + x) * 4 + 3;
+        let mut pixels = vec![0; 4 * 4 * 4];
+        pixels[alpha(2, 1)] = 255;
+        pixels[alpha(1, 2)] = 1;
+        assert_eq!(trim(&pixels, 4), Some((1, 1, 2, 2)));
+        assert_eq!(trim(&[0; 16], 2), None);
+    }
+}
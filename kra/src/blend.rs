@@ -0,0 +1,174 @@
+// Compositing of pixels with Krita's blending modes.
+//
+// Pixels are normalized channel values with alpha as the last channel, in the order
+// of the colorspace. Only separable modes are supported, which treat every color channel
+// the same way, so the order of color channels does not matter.
+
+use crate::layer::CompositeOp;
+
+// Blending function of a separable mode: (source, destination) -> result
+type BlendFn = fn(f32, f32) -> f32;
+
+// How the source pixel is combined with the destination one
+#[derive(Clone, Copy)]
+pub(crate) enum Blend {
+    Separable(BlendFn),
+    // Source alpha removes destination alpha
+    Erase,
+}
+
+impl Blend {
+    // None if the mode is not supported
+    pub(crate) fn new(op: CompositeOp) -> Option<Self> {
+        let function: BlendFn = match op {
+            CompositeOp::Normal => |src, _| src,
+            CompositeOp::Erase => return Some(Blend::Erase),
+            CompositeOp::Multiply => |src, dst| src * dst,
+            CompositeOp::Screen => screen,
+            CompositeOp::Overlay => |src, dst| hard_light(dst, src),
+            CompositeOp::HardLight => hard_light,
+            CompositeOp::SoftLight => |src, dst| match src > 0.5 {
+                true => dst + (2.0 * src - 1.0) * (dst.max(0.0).sqrt() - dst),
+                false => dst - (1.0 - 2.0 * src) * dst * (1.0 - dst),
+            },
+            CompositeOp::SoftLightSvg => |src, dst| match src > 0.5 {
+                true => {
+                    let d = match dst <= 0.25 {
+                        true => ((16.0 * dst - 12.0) * dst + 4.0) * dst,
+                        false => dst.max(0.0).sqrt(),
+                    };
+                    dst + (2.0 * src - 1.0) * (d - dst)
+                }
+                false => dst - (1.0 - 2.0 * src) * dst * (1.0 - dst),
+            },
+            CompositeOp::Darken => f32::min,
+            CompositeOp::Lighten => f32::max,
+            CompositeOp::Add => |src, dst| (src + dst).min(1.0),
+            CompositeOp::Subtract => |src, dst| (dst - src).max(0.0),
+            CompositeOp::Diff => |src, dst| (dst - src).abs(),
+            CompositeOp::Exclusion => |src, dst| src + dst - 2.0 * src * dst,
+            CompositeOp::Divide => |src, dst| match src == 0.0 {
+                true if dst == 0.0 => 0.0,
+                true => 1.0,
+                false => (dst / src).min(1.0),
+            },
+            CompositeOp::Dodge => |src, dst| match src >= 1.0 {
+                true if dst == 0.0 => 0.0,
+                true => 1.0,
+                false => (dst / (1.0 - src)).min(1.0),
+            },
+            CompositeOp::Burn => |src, dst| match src <= 0.0 {
+                true if dst >= 1.0 => 1.0,
+                true => 0.0,
+                false => (1.0 - (1.0 - dst) / src).max(0.0),
+            },
+            CompositeOp::LinearBurn => |src, dst| (src + dst - 1.0).max(0.0),
+            _ => return None,
+        };
+        Some(Blend::Separable(function))
+    }
+
+    // Composites `src` over `dst` in place. `opacity` multiplies the source alpha.
+    pub(crate) fn apply(&self, dst: &mut [f32], src: &[f32], opacity: f32) {
+        let alpha = dst.len() - 1;
+        let src_alpha = src[alpha] * opacity;
+        let dst_alpha = dst[alpha];
+        match self {
+            Blend::Erase => dst[alpha] = dst_alpha * (1.0 - src_alpha),
+            Blend::Separable(function) => {
+                let new_alpha = src_alpha + dst_alpha - src_alpha * dst_alpha;
+                if new_alpha <= 0.0 {
+                    dst[alpha] = 0.0;
+                    return;
+                }
+                for channel in 0..alpha {
+                    let (s, d) = (src[channel], dst[channel]);
+                    dst[channel] = (src_alpha * (1.0 - dst_alpha) * s
+                        + dst_alpha * (1.0 - src_alpha) * d
+                        + src_alpha * dst_alpha * function(s, d))
+                        / new_alpha;
+                }
+                dst[alpha] = new_alpha;
+            }
+        }
+    }
+
+    // Same as apply(), but for layers that inherit alpha: alpha of `dst` is kept, and its
+    // color moves towards the blended one as far as the source alpha goes
+    pub(crate) fn apply_inheriting_alpha(&self, dst: &mut [f32], src: &[f32], opacity: f32) {
+        let alpha = dst.len() - 1;
+        let src_alpha = src[alpha] * opacity;
+        match self {
+            Blend::Erase => {}
+            Blend::Separable(_) if dst[alpha] <= 0.0 => {}
+            Blend::Separable(function) => {
+                for channel in 0..alpha {
+                    let (s, d) = (src[channel], dst[channel]);
+                    dst[channel] = d + (function(s, d) - d) * src_alpha;
+                }
+            }
+        }
+    }
+}
+
+fn screen(src: f32, dst: f32) -> f32 {
+    src + dst - src * dst
+}
+
+fn hard_light(src: f32, dst: f32) -> f32 {
+    match src > 0.5 {
+        true => screen(2.0 * src - 1.0, dst),
+        false => 2.0 * src * dst,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normal_over_transparent() {
+        let mut dst = [0.0, 0.0, 0.0, 0.0];
+        Blend::new(CompositeOp::Normal)
+            .unwrap()
+            .apply(&mut dst, &[0.2, 0.4, 0.6, 1.0], 0.5);
+        assert_eq!(dst, [0.2, 0.4, 0.6, 0.5]);
+    }
+
+    #[test]
+    fn multiply_opaque() {
+        let mut dst = [0.5, 1.0, 0.0, 1.0];
+        Blend::new(CompositeOp::Multiply)
+            .unwrap()
+            .apply(&mut dst, &[0.5, 0.5, 0.5, 1.0], 1.0);
+        assert_eq!(dst, [0.25, 0.5, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn erase() {
+        let mut dst = [0.5, 0.5, 0.5, 1.0];
+        Blend::new(CompositeOp::Erase)
+            .unwrap()
+            .apply(&mut dst, &[0.0, 0.0, 0.0, 1.0], 0.25);
+        assert_eq!(dst, [0.5, 0.5, 0.5, 0.75]);
+    }
+
+    #[test]
+    fn inheriting_alpha() {
+        let mut dst = [0.5, 1.0, 0.0, 0.5];
+        Blend::new(CompositeOp::Multiply)
+            .unwrap()
+            .apply_inheriting_alpha(&mut dst, &[0.5, 0.5, 0.5, 1.0], 0.5);
+        assert_eq!(dst, [0.375, 0.75, 0.0, 0.5]);
+        let mut dst = [0.5, 0.5, 0.5, 0.0];
+        Blend::new(CompositeOp::Normal)
+            .unwrap()
+            .apply_inheriting_alpha(&mut dst, &[1.0, 1.0, 1.0, 1.0], 1.0);
+        assert_eq!(dst, [0.5, 0.5, 0.5, 0.0]);
+    }
+
+    #[test]
+    fn unsupported() {
+        assert!(Blend::new(CompositeOp::Hue).is_none());
+    }
+}
@@ -0,0 +1,327 @@
+//! Krita resource bundles (`.bundle` files), which share brushes, patterns, palettes
+//! and other resources.
+//!
+//! Bundles are zip archives like documents are, with `meta.xml` describing the bundle
+//! and `META-INF/manifest.xml` listing its resources.
+
+use std::{fs::File, path::Path};
+
+use quick_xml::{events::Event, Reader as XmlReader};
+
+use crate::{
+    archive::{ArchiveSource, DirectorySource, ZipSource},
+    config::Limits,
+    error::{ReadBundleError, XmlError},
+    helper::{event_get_attr, event_get_optional_attr, next_xml_event, strip_xml_prefix},
+    read_entry_limited,
+};
+
+const MIMETYPE: &str = "application/x-krita-resourcebundle";
+
+/// Description of a bundle, from its `meta.xml`. Missing values are empty.
+#[derive(Debug, PartialEq, Eq, Clone, Hash, Default)]
+pub struct BundleMeta {
+    /// Name of the bundle.
+    title: String,
+    /// Description of the bundle.
+    description: String,
+    /// Author of the bundle.
+    author: String,
+    /// Application that created the bundle.
+    generator: String,
+    /// Date of creation.
+    creation_date: String,
+    /// Date of the last modification.
+    date: String,
+    /// Version of the bundle.
+    version: String,
+    /// Other values, like `email`, `license` or `website`, in the order they were written.
+    user_defined: Vec<(String, String)>,
+}
+
+impl BundleMeta {
+    /// Name of the bundle.
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Description of the bundle.
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Author of the bundle.
+    pub fn author(&self) -> &str {
+        &self.author
+    }
+
+    /// Application that created the bundle.
+    pub fn generator(&self) -> &str {
+        &self.generator
+    }
+
+    /// Date of creation.
+    pub fn creation_date(&self) -> &str {
+        &self.creation_date
+    }
+
+    /// Date of the last modification.
+    pub fn date(&self) -> &str {
+        &self.date
+    }
+
+    /// Version of the bundle.
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Other values, like `email`, `license` or `website`, in the order they were written.
+    pub fn user_defined(&self) -> &[(String, String)] {
+        &self.user_defined
+    }
+
+    /// One of the other values, by its name.
+    pub fn user_defined_value(&self, name: &str) -> Option<&str> {
+        self.user_defined
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A resource listed in the manifest of a bundle.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Resource {
+    /// Kind of the resource, like `brushes`, `paintoppresets` or `patterns`.
+    kind: String,
+    /// Path of the entry in the bundle.
+    path: String,
+    /// MD5 checksum of the entry, as hexadecimal digits. Empty if it is not listed.
+    md5: String,
+    /// Tags of the resource.
+    tags: Vec<String>,
+}
+
+impl Resource {
+    /// Kind of the resource, like `brushes`, `paintoppresets` or `patterns`.
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    /// Path of the entry in the bundle.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// MD5 checksum of the entry, as hexadecimal digits. Empty if it is not listed.
+    pub fn md5(&self) -> &str {
+        &self.md5
+    }
+
+    /// Tags of the resource.
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// Name of the resource's file, without the directory.
+    pub fn file_name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+}
+
+/// A resource bundle.
+#[derive(Debug)]
+pub struct Bundle {
+    archive: Box<dyn ArchiveSource>,
+    limits: Limits,
+    meta: BundleMeta,
+    resources: Vec<Resource>,
+}
+
+impl Bundle {
+    /// Open and parse `.bundle` file, or a directory with an unpacked one.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, ReadBundleError> {
+        Self::read_with_limits(path, Limits::default())
+    }
+
+    /// Open and parse `.bundle` file (or a directory), with limits on sizes of entries.
+    pub fn read_with_limits<P: AsRef<Path>>(
+        path: P,
+        limits: Limits,
+    ) -> Result<Self, ReadBundleError> {
+        let path = path.as_ref();
+        if path.is_dir() {
+            return Self::read_from(DirectorySource::open(path)?, limits);
+        }
+        let archive = ZipSource::from_file(File::open(path)?, path)?;
+        Self::read_from(archive, limits)
+    }
+
+    /// Parse a bundle whose entries come from `archive`.
+    pub fn read_from<A: ArchiveSource + 'static>(
+        mut archive: A,
+        limits: Limits,
+    ) -> Result<Self, ReadBundleError> {
+        let mimetype =
+            read_entry_limited::<ReadBundleError>(&mut archive, "mimetype", limits.max_entry_size)?;
+        if mimetype.as_slice() != MIMETYPE.as_bytes() {
+            return Err(ReadBundleError::MimetypeMismatch);
+        }
+        let meta = read_xml(&mut archive, "meta.xml", &limits)?;
+        let manifest = read_xml(&mut archive, "META-INF/manifest.xml", &limits)?;
+        Ok(Bundle {
+            meta: parse_meta(strip_xml_prefix(&meta))?,
+            resources: parse_manifest(strip_xml_prefix(&manifest))?,
+            archive: Box::new(archive),
+            limits,
+        })
+    }
+
+    /// Description of the bundle.
+    pub fn meta(&self) -> &BundleMeta {
+        &self.meta
+    }
+
+    /// Resources of the bundle, in the order of the manifest.
+    pub fn resources(&self) -> &[Resource] {
+        &self.resources
+    }
+
+    /// Resources of one kind, like `brushes`.
+    pub fn resources_of_kind<'a>(&'a self, kind: &'a str) -> impl Iterator<Item = &'a Resource> {
+        self.resources
+            .iter()
+            .filter(move |resource| resource.kind == kind)
+    }
+
+    /// Contents of the resource's entry.
+    pub fn read_resource(&mut self, resource: &Resource) -> Result<Vec<u8>, ReadBundleError> {
+        read_entry_limited(
+            self.archive.as_mut(),
+            &resource.path,
+            self.limits.max_entry_size,
+        )
+    }
+
+    /// PNG image that shows what is in the bundle, if there is one.
+    pub fn preview(&mut self) -> Result<Option<Vec<u8>>, ReadBundleError> {
+        if !self
+            .archive
+            .entry_names()
+            .iter()
+            .any(|name| name == "preview.png")
+        {
+            return Ok(None);
+        }
+        read_entry_limited(
+            self.archive.as_mut(),
+            "preview.png",
+            self.limits.max_entry_size,
+        )
+        .map(Some)
+    }
+}
+
+fn read_xml(
+    archive: &mut dyn ArchiveSource,
+    name: &str,
+    limits: &Limits,
+) -> Result<String, ReadBundleError> {
+    let data = read_entry_limited::<ReadBundleError>(archive, name, limits.max_xml_size)?;
+    String::from_utf8(data).map_err(|err| XmlError::from(err).into())
+}
+
+// Elements are matched by their names without the namespace prefix; unknown ones are skipped
+fn parse_meta(text: &str) -> Result<BundleMeta, XmlError> {
+    let mut reader = XmlReader::from_str(text);
+    reader.trim_text(true);
+    let mut meta = BundleMeta::default();
+    loop {
+        match next_xml_event(&mut reader)? {
+            Event::Empty(tag) if local_name(tag.name().as_ref()) == b"meta-userdefined" => {
+                let name = event_get_attr(&tag, "meta:name")?.unescape_value()?;
+                let value = event_get_attr(&tag, "meta:value")?.unescape_value()?;
+                meta.user_defined
+                    .push((name.into_owned(), value.into_owned()));
+            }
+            Event::Start(tag) => {
+                let field = match local_name(tag.name().as_ref()) {
+                    b"title" => &mut meta.title,
+                    b"description" => &mut meta.description,
+                    // Older bundles use dc:creator
+                    b"author" | b"creator" if meta.author.is_empty() => &mut meta.author,
+                    b"generator" => &mut meta.generator,
+                    b"creation-date" => &mut meta.creation_date,
+                    b"dc-date" | b"date" => &mut meta.date,
+                    b"bundle-version" => &mut meta.version,
+                    // <meta:meta>, which holds the others
+                    b"meta" => continue,
+                    _ => {
+                        reader.read_to_end(tag.name())?;
+                        continue;
+                    }
+                };
+                *field = unescape_text(&reader.read_text(tag.name())?)?;
+            }
+            Event::Eof => return Ok(meta),
+            _ => {}
+        }
+    }
+}
+
+fn parse_manifest(text: &str) -> Result<Vec<Resource>, XmlError> {
+    let mut reader = XmlReader::from_str(text);
+    reader.trim_text(true);
+    let mut resources = Vec::new();
+    loop {
+        let (tag, has_tags) = match next_xml_event(&mut reader)? {
+            Event::Start(tag) if tag.name().as_ref() == b"manifest:file-entry" => (tag, true),
+            Event::Empty(tag) if tag.name().as_ref() == b"manifest:file-entry" => (tag, false),
+            Event::Eof => return Ok(resources),
+            _ => continue,
+        };
+        let path = event_get_attr(&tag, "manifest:full-path")?
+            .unescape_value()?
+            .into_owned();
+        let mut resource = Resource {
+            kind: event_get_attr(&tag, "manifest:media-type")?
+                .unescape_value()?
+                .into_owned(),
+            path: path.trim_start_matches('/').to_owned(),
+            md5: event_get_optional_attr(&tag, "manifest:md5sum")?
+                .map(|attr| attr.unescape_value().map(|value| value.into_owned()))
+                .transpose()?
+                .unwrap_or_default(),
+            tags: Vec::new(),
+        };
+        if has_tags {
+            loop {
+                match next_xml_event(&mut reader)? {
+                    Event::Start(tag) if tag.name().as_ref() == b"manifest:tag" => {
+                        let text = reader.read_text(tag.name())?;
+                        resource.tags.push(unescape_text(&text)?);
+                    }
+                    Event::End(_) => break,
+                    Event::Eof => {
+                        return Err(XmlError::MissingValue("</manifest:file-entry>".into()))
+                    }
+                    _ => {}
+                }
+            }
+        }
+        // The bundle itself is listed as "/"
+        if !resource.path.is_empty() {
+            resources.push(resource);
+        }
+    }
+}
+
+fn local_name(name: &[u8]) -> &[u8] {
+    name.rsplit(|&byte| byte == b':').next().unwrap_or(name)
+}
+
+fn unescape_text(text: &str) -> Result<String, XmlError> {
+    Ok(quick_xml::escape::unescape(text)
+        .map_err(quick_xml::Error::from)?
+        .into_owned())
+}
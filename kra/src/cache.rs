@@ -0,0 +1,377 @@
+//! Caches that save repeated work: [`Index`] of document fingerprints, kept on disk
+//! between scans.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use crate::{
+    data::LayerData,
+    error::ReadKraError,
+    fingerprint::{read_fingerprint_with_layers, Fingerprint},
+    id::Uuid,
+};
+
+// Cache of decoded layers, so that repeated decoding of the same layers
+// (for example, by viewers) does not decode tiles every time.
+//
+// Least recently used layers are evicted to keep the total size of pixels within the budget.
+
+pub(crate) struct LayerCache {
+    // In bytes, 0 disables caching
+    budget: usize,
+    used: usize,
+    // Incremented on every access, to find the least recently used layer
+    clock: u64,
+    layers: HashMap<Uuid, (LayerData, u64)>,
+}
+
+impl LayerCache {
+    pub(crate) fn new(budget: usize) -> Self {
+        LayerCache {
+            budget,
+            used: 0,
+            clock: 0,
+            layers: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn get(&mut self, uuid: &Uuid) -> Option<&LayerData> {
+        self.clock += 1;
+        let (data, last_used) = self.layers.get_mut(uuid)?;
+        *last_used = self.clock;
+        Some(data)
+    }
+
+    // Layers that are larger than the whole budget are not kept
+    pub(crate) fn insert(&mut self, uuid: Uuid, data: LayerData) {
+        let size = data.pixels.byte_len();
+        if size > self.budget {
+            return;
+        }
+        self.remove(&uuid);
+        while self.used + size > self.budget {
+            let oldest = self
+                .layers
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(uuid, _)| *uuid);
+            match oldest {
+                Some(oldest) => self.remove(&oldest),
+                None => break,
+            }
+        }
+        self.clock += 1;
+        self.used += size;
+        self.layers.insert(uuid, (data, self.clock));
+    }
+
+    fn remove(&mut self, uuid: &Uuid) {
+        if let Some((data, _)) = self.layers.remove(uuid) {
+            self.used -= data.pixels.byte_len();
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.layers.clear();
+        self.used = 0;
+    }
+}
+
+// Pixels would flood the output
+impl fmt::Debug for LayerCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LayerCache")
+            .field("budget", &self.budget)
+            .field("used", &self.used)
+            .field("layers", &self.layers.len())
+            .finish()
+    }
+}
+
+// First line of index files, raised when the format changes
+const INDEX_HEADER: &str = "kra-index 1";
+
+/// [Fingerprints](Fingerprint) of documents, along with what is needed to tell whether
+/// the documents changed since, so that repeated scans of many files only read changed ones.
+/// Layers of the documents are counted, see
+/// [`read_fingerprint_with_layers()`](crate::fingerprint::read_fingerprint_with_layers).
+///
+/// The index can be kept in a file between runs with [`load()`](Self::load) and
+/// [`save()`](Self::save). Documents are known by their paths as given, so the same
+/// document under different paths is indexed more than once.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Index {
+    entries: BTreeMap<PathBuf, IndexEntry>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct IndexEntry {
+    size: u64,
+    // Modification time since the Unix epoch
+    modified: Duration,
+    checksum: u32,
+    fingerprint: Fingerprint,
+}
+
+impl Index {
+    /// Empty index.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the index from a file, see [`save()`](Self::save).
+    ///
+    /// A missing file, or one written by another version of the library, gives an empty
+    /// index. Malformed lines are skipped, as their documents can be read again.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = match fs::File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::new()),
+            Err(err) => return Err(err),
+        };
+        let mut lines = BufReader::new(file).lines();
+        if lines.next().transpose()?.as_deref() != Some(INDEX_HEADER) {
+            return Ok(Self::new());
+        }
+        let mut entries = BTreeMap::new();
+        for line in lines {
+            if let Some((path, entry)) = parse_index_line(&line?) {
+                entries.insert(path, entry);
+            }
+        }
+        Ok(Index { entries })
+    }
+
+    /// Write the index into a file.
+    ///
+    /// Documents whose paths are not valid UTF-8 are left out.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut out = io::BufWriter::new(fs::File::create(path)?);
+        writeln!(out, "{}", INDEX_HEADER)?;
+        for (path, entry) in &self.entries {
+            let Some(path) = path.to_str() else {
+                continue;
+            };
+            let fingerprint = &entry.fingerprint;
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{:08x}\t{}\t{}\t{}\t{}\t{}",
+                escape(path),
+                entry.size,
+                entry.modified.as_secs(),
+                entry.modified.subsec_nanos(),
+                entry.checksum,
+                fingerprint.dimensions.0,
+                fingerprint.dimensions.1,
+                fingerprint
+                    .layer_count
+                    .map_or(String::new(), |count| count.to_string()),
+                escape(&fingerprint.modified),
+                escape(&fingerprint.krita_version),
+            )?;
+        }
+        out.flush()
+    }
+
+    /// Fingerprint of the document, read again only if the document changed since
+    /// it was indexed.
+    ///
+    /// Documents whose size and modification time did not change are not read at all.
+    /// If only the modification time changed, the contents are compared by checksum.
+    pub fn fingerprint<P: AsRef<Path>>(&mut self, path: P) -> Result<&Fingerprint, ReadKraError> {
+        let path = path.as_ref();
+        if !self.is_up_to_date(path)? {
+            let (size, modified, checksum) = stat_and_checksum(path)?;
+            let fingerprint = read_fingerprint_with_layers(path)?;
+            self.entries.insert(
+                path.to_owned(),
+                IndexEntry {
+                    size,
+                    modified,
+                    checksum,
+                    fingerprint,
+                },
+            );
+        }
+        Ok(&self.entries[path].fingerprint)
+    }
+
+    /// Whether the document is indexed, and did not change since.
+    pub fn is_up_to_date<P: AsRef<Path>>(&mut self, path: P) -> io::Result<bool> {
+        let path = path.as_ref();
+        let Some(entry) = self.entries.get_mut(path) else {
+            return Ok(false);
+        };
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(err) => return Err(err),
+        };
+        if metadata.len() != entry.size {
+            return Ok(false);
+        }
+        let modified = modified_since_epoch(&metadata)?;
+        if modified == entry.modified {
+            return Ok(true);
+        }
+        // Touched, but maybe not changed
+        let (_, _, checksum) = stat_and_checksum(path)?;
+        if checksum != entry.checksum {
+            return Ok(false);
+        }
+        entry.modified = modified;
+        Ok(true)
+    }
+
+    /// Forget documents that do not exist anymore.
+    pub fn prune(&mut self) {
+        self.entries.retain(|path, _| path.is_file());
+    }
+
+    /// Paths of indexed documents.
+    pub fn paths(&self) -> impl Iterator<Item = &Path> + '_ {
+        self.entries.keys().map(PathBuf::as_path)
+    }
+}
+
+// Size, modification time and CRC-32 of the whole file
+fn stat_and_checksum(path: &Path) -> io::Result<(u64, Duration, u32)> {
+    let mut file = fs::File::open(path)?;
+    let metadata = file.metadata()?;
+    let mut hasher = crc32fast::Hasher::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        match file.read(&mut buffer)? {
+            0 => break,
+            read => hasher.update(&buffer[..read]),
+        }
+    }
+    Ok((
+        metadata.len(),
+        modified_since_epoch(&metadata)?,
+        hasher.finalize(),
+    ))
+}
+
+fn modified_since_epoch(metadata: &fs::Metadata) -> io::Result<Duration> {
+    Ok(metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default())
+}
+
+fn parse_index_line(line: &str) -> Option<(PathBuf, IndexEntry)> {
+    let fields: Vec<_> = line.split('\t').collect();
+    let [path, size, secs, nanos, checksum, width, height, layer_count, modified, krita_version] =
+        fields[..]
+    else {
+        return None;
+    };
+    let entry = IndexEntry {
+        size: size.parse().ok()?,
+        modified: Duration::new(secs.parse().ok()?, nanos.parse().ok()?),
+        checksum: u32::from_str_radix(checksum, 16).ok()?,
+        fingerprint: Fingerprint {
+            dimensions: (width.parse().ok()?, height.parse().ok()?),
+            krita_version: unescape(krita_version)?,
+            layer_count: match layer_count {
+                "" => None,
+                count => Some(count.parse().ok()?),
+            },
+            modified: unescape(modified)?,
+        },
+    };
+    Some((PathBuf::from(unescape(path)?), entry))
+}
+
+// Tabs and line breaks separate fields and lines of index files
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape(text: &str) -> Option<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        out.push(match chars.next()? {
+            '\\' => '\\',
+            't' => '\t',
+            'n' => '\n',
+            'r' => '\r',
+            _ => return None,
+        });
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color::AlphaMode, data::PixelBuffer, Colorspace};
+
+    fn layer(size: usize) -> LayerData {
+        LayerData {
+            x: 0,
+            y: 0,
+            width: size as u32,
+            height: 1,
+            colorspace: Colorspace::GRAYA,
+            pixels: PixelBuffer::U8(vec![0; size]),
+            alpha_mode: AlphaMode::Straight,
+        }
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = LayerCache::new(10);
+        let ids: Vec<Uuid> = (0..3).map(Uuid::from_u128).collect();
+        cache.insert(ids[0], layer(4));
+        cache.insert(ids[1], layer(4));
+        cache.get(&ids[0]).unwrap();
+        cache.insert(ids[2], layer(4));
+        assert!(cache.get(&ids[0]).is_some());
+        assert!(cache.get(&ids[1]).is_none());
+        assert!(cache.get(&ids[2]).is_some());
+        assert_eq!(cache.used, 8);
+    }
+
+    #[test]
+    fn too_large_or_disabled() {
+        let mut cache = LayerCache::new(10);
+        cache.insert(Uuid::from_u128(0), layer(11));
+        assert_eq!(cache.used, 0);
+        let mut cache = LayerCache::new(0);
+        cache.insert(Uuid::from_u128(0), layer(1));
+        assert!(cache.layers.is_empty());
+    }
+
+    #[test]
+    fn replaces() {
+        let mut cache = LayerCache::new(10);
+        cache.insert(Uuid::from_u128(0), layer(6));
+        cache.insert(Uuid::from_u128(0), layer(8));
+        assert_eq!(cache.used, 8);
+        assert_eq!(cache.layers.len(), 1);
+    }
+
+    #[test]
+    fn escaped_fields() {
+        for text in ["plain", "tab\there", "line\nbreak\r", "back\\slash\\t"] {
+            assert!(!escape(text).contains(['\t', '\n', '\r']));
+            assert_eq!(unescape(&escape(text)).as_deref(), Some(text));
+        }
+        assert_eq!(unescape("dangling\\"), None);
+    }
+}
@@ -0,0 +1,443 @@
+//! Channel types and color-related helpers.
+
+use std::fmt::{self, Display};
+
+use crate::{data::PixelBuffer, tile::stores_bgra, Colorspace};
+
+/// Size and type of one channel of a pixel.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[non_exhaustive]
+pub enum ChannelDepth {
+    /// 8-bit unsigned integer.
+    U8,
+    /// 16-bit unsigned integer.
+    U16,
+    /// 16-bit floating point (half).
+    F16,
+    /// 32-bit floating point.
+    F32,
+}
+
+impl ChannelDepth {
+    /// Size of one channel, in bytes.
+    pub fn size(&self) -> usize {
+        match self {
+            ChannelDepth::U8 => 1,
+            ChannelDepth::U16 | ChannelDepth::F16 => 2,
+            ChannelDepth::F32 => 4,
+        }
+    }
+}
+
+impl Display for ChannelDepth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChannelDepth::U8 => write!(f, "8-bit integer"),
+            ChannelDepth::U16 => write!(f, "16-bit integer"),
+            ChannelDepth::F16 => write!(f, "16-bit float"),
+            ChannelDepth::F32 => write!(f, "32-bit float"),
+        }
+    }
+}
+
+/// Type of a single channel value.
+///
+/// Krita stores channel values in little-endian byte order.
+pub trait ChannelType: Copy + PartialOrd + fmt::Debug {
+    /// Depth that corresponds to this type.
+    const DEPTH: ChannelDepth;
+
+    /// Fully transparent alpha value.
+    const ZERO: Self;
+
+    /// Read the value from `DEPTH.size()` little-endian bytes.
+    fn from_le_bytes(bytes: &[u8]) -> Self;
+
+    /// Value normalized to `0.0..=1.0` (floating point values are not clamped).
+    fn to_f32(self) -> f32;
+}
+
+impl ChannelType for u8 {
+    const DEPTH: ChannelDepth = ChannelDepth::U8;
+    const ZERO: Self = 0;
+
+    fn from_le_bytes(bytes: &[u8]) -> Self {
+        bytes[0]
+    }
+
+    fn to_f32(self) -> f32 {
+        self as f32 / u8::MAX as f32
+    }
+}
+
+impl ChannelType for u16 {
+    const DEPTH: ChannelDepth = ChannelDepth::U16;
+    const ZERO: Self = 0;
+
+    fn from_le_bytes(bytes: &[u8]) -> Self {
+        u16::from_le_bytes([bytes[0], bytes[1]])
+    }
+
+    fn to_f32(self) -> f32 {
+        self as f32 / u16::MAX as f32
+    }
+}
+
+impl ChannelType for F16 {
+    const DEPTH: ChannelDepth = ChannelDepth::F16;
+    const ZERO: Self = F16(0);
+
+    fn from_le_bytes(bytes: &[u8]) -> Self {
+        F16(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn to_f32(self) -> f32 {
+        F16::to_f32(self)
+    }
+}
+
+impl ChannelType for f32 {
+    const DEPTH: ChannelDepth = ChannelDepth::F32;
+    const ZERO: Self = 0.0;
+
+    fn from_le_bytes(bytes: &[u8]) -> Self {
+        f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    fn to_f32(self) -> f32 {
+        self
+    }
+}
+
+/// IEEE 754 half-precision floating point value, stored as its bits.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+pub struct F16(pub u16);
+
+impl F16 {
+    /// Convert to single-precision floating point value.
+    pub fn to_f32(self) -> f32 {
+        let sign = ((self.0 & 0x8000) as u32) << 16;
+        let exponent = ((self.0 >> 10) & 0x1f) as u32;
+        let mantissa = (self.0 & 0x3ff) as u32;
+        let bits = match (exponent, mantissa) {
+            (0, 0) => sign,
+            // Subnormal, becomes normal in f32
+            (0, _) => {
+                let shift = mantissa.leading_zeros() - 21;
+                let mantissa = (mantissa << shift) & 0x3ff;
+                sign | ((113 - shift) << 23) | (mantissa << 13)
+            }
+            // Infinity or NaN
+            (0x1f, _) => sign | 0x7f80_0000 | (mantissa << 13),
+            _ => sign | ((exponent + 112) << 23) | (mantissa << 13),
+        };
+        f32::from_bits(bits)
+    }
+}
+
+impl F16 {
+    /// Convert from single-precision floating point value, rounding to nearest.
+    pub fn from_f32(value: f32) -> Self {
+        let bits = value.to_bits();
+        let sign = ((bits >> 16) & 0x8000) as u16;
+        let exponent = ((bits >> 23) & 0xff) as i32;
+        let mantissa = bits & 0x7f_ffff;
+        let half = match exponent {
+            // Infinity or NaN, keeping NaN a NaN
+            0xff => 0x7c00 | if mantissa == 0 { 0 } else { 0x200 },
+            // Too large, becomes infinity
+            143.. => 0x7c00,
+            // Normal
+            113..=142 => {
+                let half = (((exponent - 112) as u32) << 10) | (mantissa >> 13);
+                // Round half to even; a carry into the exponent is still correct
+                let rest = mantissa & 0x1fff;
+                let round = rest > 0x1000 || (rest == 0x1000 && half & 1 == 1);
+                (half + round as u32) as u16
+            }
+            // Subnormal
+            102..=112 => {
+                let mantissa = mantissa | 0x80_0000;
+                let shift = (126 - exponent) as u32;
+                let half = mantissa >> shift;
+                let rest = mantissa & ((1 << shift) - 1);
+                let midpoint = 1 << (shift - 1);
+                let round = rest > midpoint || (rest == midpoint && half & 1 == 1);
+                (half + round as u32) as u16
+            }
+            // Too small, becomes zero
+            _ => 0,
+        };
+        F16(sign | half)
+    }
+}
+
+impl PartialOrd for F16 {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.to_f32().partial_cmp(&other.to_f32())
+    }
+}
+
+impl Display for F16 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_f32())
+    }
+}
+
+/// Color model of a colorspace, regardless of its channel depth.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[non_exhaustive]
+pub enum ColorModel {
+    /// Red, green, blue.
+    Rgb,
+    /// Grayscale.
+    Gray,
+    /// Cyan, magenta, yellow, key.
+    Cmyk,
+    /// CIE L*a*b*.
+    Lab,
+    /// CIE XYZ.
+    Xyz,
+    /// Luma and chroma.
+    YCbCr,
+}
+
+/// How color channels relate to alpha.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+pub enum AlphaMode {
+    /// Color is independent of alpha. Krita stores pixels this way.
+    #[default]
+    Straight,
+    /// Color is multiplied by alpha.
+    Premultiplied,
+}
+
+/// Transfer function that is applied to color channels during a conversion.
+///
+/// Krita's floating point colorspaces are usually linear, while 8-bit images are expected
+/// to be sRGB-encoded; only rescaling values makes linear images look washed out.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+#[non_exhaustive]
+pub enum Transfer {
+    /// Values are only rescaled.
+    #[default]
+    Unchanged,
+    /// Encode linear values with the sRGB transfer function.
+    LinearToSrgb,
+    /// Decode sRGB-encoded values into linear ones.
+    SrgbToLinear,
+}
+
+impl Transfer {
+    // Applies the function to a normalized value. Values above 1.0 follow the extended
+    // curve, values below 0 the linear part of it.
+    pub(crate) fn apply(&self, value: f32) -> f32 {
+        match self {
+            Transfer::Unchanged => value,
+            Transfer::LinearToSrgb if value <= 0.003_130_8 => value * 12.92,
+            Transfer::LinearToSrgb => 1.055 * value.powf(1.0 / 2.4) - 0.055,
+            Transfer::SrgbToLinear if value <= 0.040_45 => value / 12.92,
+            Transfer::SrgbToLinear => ((value + 0.055) / 1.055).powf(2.4),
+        }
+    }
+}
+
+/// Options of conversions of pixels, such as
+/// [`KraFile::decode_layer_converted_with()`](crate::KraFile::decode_layer_converted_with).
+///
+/// By default, alpha is straight and values are only rescaled.
+#[derive(Debug, PartialEq, Eq, Clone, Hash, Default)]
+pub struct ConvertOptions {
+    pub(crate) alpha_mode: AlphaMode,
+    pub(crate) transfer: Transfer,
+}
+
+impl ConvertOptions {
+    /// Default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the alpha mode of converted pixels.
+    pub fn alpha_mode(mut self, alpha_mode: AlphaMode) -> Self {
+        self.alpha_mode = alpha_mode;
+        self
+    }
+
+    /// Alpha mode of converted pixels.
+    pub fn get_alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+
+    /// Set the transfer function that is applied to color channels.
+    pub fn transfer(mut self, transfer: Transfer) -> Self {
+        self.transfer = transfer;
+        self
+    }
+
+    /// Transfer function that is applied to color channels.
+    pub fn get_transfer(&self) -> Transfer {
+        self.transfer
+    }
+}
+
+// Changes alpha mode of a normalized pixel. Alpha is always the last channel.
+// Color of fully transparent pixels cannot be restored and becomes 0.
+pub(crate) fn change_alpha(pixel: &mut [f32], from: AlphaMode, to: AlphaMode) {
+    let Some((alpha, color)) = pixel.split_last_mut() else {
+        return;
+    };
+    match (from, to) {
+        (AlphaMode::Straight, AlphaMode::Premultiplied) => {
+            color.iter_mut().for_each(|value| *value *= *alpha)
+        }
+        (AlphaMode::Premultiplied, AlphaMode::Straight) if *alpha == 0.0 => color.fill(0.0),
+        (AlphaMode::Premultiplied, AlphaMode::Straight) => {
+            color.iter_mut().for_each(|value| *value /= *alpha)
+        }
+        _ => {}
+    }
+}
+
+// Converts interleaved channel values between colorspaces, through normalized f32 values.
+// Only changes of depth, and conversions between RGB and grayscale are supported.
+// Color profiles are not taken into account.
+pub(crate) fn convert_pixels(
+    pixels: &PixelBuffer,
+    from: Colorspace,
+    to: Colorspace,
+) -> Option<PixelBuffer> {
+    convert_pixels_with(
+        pixels,
+        (from, AlphaMode::Straight),
+        to,
+        &ConvertOptions::new(),
+    )
+}
+
+// Same as convert_pixels(), also changing the alpha mode and applying the transfer function
+// in the same pass
+pub(crate) fn convert_pixels_with(
+    pixels: &PixelBuffer,
+    (from, from_alpha): (Colorspace, AlphaMode),
+    to: Colorspace,
+    options: &ConvertOptions,
+) -> Option<PixelBuffer> {
+    let convert: fn(&[f32]) -> Vec<f32> = match (from.model(), to.model()) {
+        (a, b) if a == b => |pixel| pixel.to_vec(),
+        (ColorModel::Gray, ColorModel::Rgb) => |pixel| vec![pixel[0], pixel[0], pixel[0], pixel[1]],
+        (ColorModel::Rgb, ColorModel::Gray) => |pixel| {
+            vec![
+                0.2126 * pixel[0] + 0.7152 * pixel[1] + 0.0722 * pixel[2],
+                pixel[3],
+            ]
+        },
+        _ => return None,
+    };
+
+    let converted: Vec<f32> = normalize(pixels)
+        .chunks_exact(from.channel_count())
+        .flat_map(|pixel| {
+            let mut pixel = pixel.to_vec();
+            // Grayscale is computed from straight color
+            change_alpha(&mut pixel, from_alpha, AlphaMode::Straight);
+            let mut pixel = convert(&swap_blue_red(from, pixel));
+            if let Some((_, color)) = pixel.split_last_mut() {
+                color
+                    .iter_mut()
+                    .for_each(|value| *value = options.transfer.apply(*value));
+            }
+            change_alpha(&mut pixel, AlphaMode::Straight, options.alpha_mode);
+            swap_blue_red(to, pixel)
+        })
+        .collect();
+    Some(denormalize(converted, to.depth()))
+}
+
+// Channel values as f32, normalized to 0.0..=1.0 for integer depths
+pub(crate) fn normalize(pixels: &PixelBuffer) -> Vec<f32> {
+    match pixels {
+        PixelBuffer::U8(values) => values.iter().map(|value| value.to_f32()).collect(),
+        PixelBuffer::U16(values) => values.iter().map(|value| value.to_f32()).collect(),
+        PixelBuffer::F16(values) => values.iter().map(|value| value.to_f32()).collect(),
+        PixelBuffer::F32(values) => values.clone(),
+    }
+}
+
+// Inverse of normalize(); integer values are clamped and rounded
+pub(crate) fn denormalize(values: Vec<f32>, depth: ChannelDepth) -> PixelBuffer {
+    fn quantize(value: f32, max: f32) -> f32 {
+        (value.clamp(0.0, 1.0) * max).round()
+    }
+    match depth {
+        ChannelDepth::U8 => PixelBuffer::U8(
+            values
+                .iter()
+                .map(|value| quantize(*value, u8::MAX as f32) as u8)
+                .collect(),
+        ),
+        ChannelDepth::U16 => PixelBuffer::U16(
+            values
+                .iter()
+                .map(|value| quantize(*value, u16::MAX as f32) as u16)
+                .collect(),
+        ),
+        ChannelDepth::F16 => PixelBuffer::F16(values.into_iter().map(F16::from_f32).collect()),
+        ChannelDepth::F32 => PixelBuffer::F32(values),
+    }
+}
+
+// Integer RGB colorspaces store pixels as BGRA; swaps to RGBA and back
+fn swap_blue_red(colorspace: Colorspace, mut pixel: Vec<f32>) -> Vec<f32> {
+    if stores_bgra(colorspace) {
+        pixel.swap(0, 2);
+    }
+    pixel
+}
+
+// Interpret little-endian bytes as channel values
+pub(crate) fn to_channels<T: ChannelType>(bytes: &[u8]) -> Vec<T> {
+    bytes
+        .chunks_exact(T::DEPTH.size())
+        .map(T::from_le_bytes)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn half_from_f32() {
+        for bits in [
+            0x3c00, 0xc000, 0x3800, 0x0001, 0x03ff, 0x7bff, 0x7c00, 0x8000,
+        ] {
+            assert_eq!(F16::from_f32(F16(bits).to_f32()), F16(bits));
+        }
+        assert_eq!(F16::from_f32(1e6), F16(0x7c00));
+        assert_eq!(F16::from_f32(1e-10), F16(0));
+        assert!(F16::from_f32(f32::NAN).to_f32().is_nan());
+        // Halfway between 1.0 and the next value rounds to even
+        assert_eq!(F16::from_f32(1.0 + 2f32.powi(-11)), F16(0x3c00));
+    }
+
+    #[test]
+    fn srgb_transfer() {
+        for value in [0.0, 0.002, 0.2, 0.5, 1.0] {
+            let encoded = Transfer::LinearToSrgb.apply(value);
+            assert!((Transfer::SrgbToLinear.apply(encoded) - value).abs() < 1e-6);
+        }
+        // Middle gray
+        assert!((Transfer::LinearToSrgb.apply(0.18) - 0.4614).abs() < 1e-4);
+    }
+
+    #[test]
+    fn half_to_f32() {
+        assert_eq!(F16(0x3c00).to_f32(), 1.0);
+        assert_eq!(F16(0xc000).to_f32(), -2.0);
+        assert_eq!(F16(0x3800).to_f32(), 0.5);
+        assert_eq!(F16(0x0001).to_f32(), 2f32.powi(-24));
+        assert_eq!(F16(0x7c00).to_f32(), f32::INFINITY);
+        assert!(F16(0x7e00).to_f32().is_nan());
+    }
+}
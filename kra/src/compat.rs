@@ -0,0 +1,217 @@
+//! Whether blend modes of layers can be kept when documents are converted into other formats.
+
+use std::fmt::{self, Display};
+
+use crate::{id::Uuid, layer::CompositeOp, KraFile};
+
+/// Format that documents are converted into.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[non_exhaustive]
+pub enum Target {
+    /// Photoshop documents, with blend modes as their four-letter keys.
+    Psd,
+    /// OpenRaster, with blend modes as values of the `composite-op` attribute.
+    Ora,
+    /// CSS, with blend modes as values of `mix-blend-mode`.
+    Css,
+}
+
+impl Target {
+    /// Name of the blend mode in the target, if the target has the same one.
+    pub fn blend_mode(&self, op: CompositeOp) -> Option<&'static str> {
+        use CompositeOp as Op;
+        Some(match self {
+            Target::Psd => match op {
+                Op::Normal => "norm",
+                Op::Dissolve => "diss",
+                Op::Darken => "dark",
+                Op::Multiply => "mul ",
+                Op::Burn => "idiv",
+                Op::LinearBurn => "lbrn",
+                Op::DarkerColor => "dkCl",
+                Op::Lighten => "lite",
+                Op::Screen => "scrn",
+                Op::Dodge => "div ",
+                Op::LinearDodge | Op::Add => "lddg",
+                Op::LighterColor => "lgCl",
+                Op::Overlay => "over",
+                Op::SoftLight => "sLit",
+                Op::HardLight => "hLit",
+                Op::VividLight => "vLit",
+                Op::LinearLight => "lLit",
+                Op::PinLight => "pLit",
+                Op::HardMixPhotoshop => "hMix",
+                Op::Diff => "diff",
+                Op::Exclusion => "smud",
+                Op::Subtract => "fsub",
+                Op::Divide => "fdiv",
+                Op::Hue => "hue ",
+                Op::Saturation => "sat ",
+                Op::Color => "colr",
+                Op::Luminize => "lum ",
+                Op::PassThrough => "pass",
+                _ => return None,
+            },
+            Target::Ora => match op {
+                Op::Normal => "svg:src-over",
+                Op::Multiply => "svg:multiply",
+                Op::Screen => "svg:screen",
+                Op::Overlay => "svg:overlay",
+                Op::Darken => "svg:darken",
+                Op::Lighten => "svg:lighten",
+                Op::Dodge => "svg:color-dodge",
+                Op::Burn => "svg:color-burn",
+                Op::HardLight => "svg:hard-light",
+                Op::SoftLightSvg => "svg:soft-light",
+                Op::Diff => "svg:difference",
+                Op::Color => "svg:color",
+                Op::Luminize => "svg:luminosity",
+                Op::Hue => "svg:hue",
+                Op::Saturation => "svg:saturation",
+                Op::Add => "svg:plus",
+                Op::DestinationIn => "svg:dst-in",
+                Op::Erase => "svg:dst-out",
+                Op::DestinationAtop => "svg:dst-atop",
+                _ => return None,
+            },
+            Target::Css => match op {
+                Op::Normal => "normal",
+                Op::Multiply => "multiply",
+                Op::Screen => "screen",
+                Op::Overlay => "overlay",
+                Op::Darken => "darken",
+                Op::Lighten => "lighten",
+                Op::Dodge => "color-dodge",
+                Op::Burn => "color-burn",
+                Op::HardLight => "hard-light",
+                Op::SoftLightSvg => "soft-light",
+                Op::Diff => "difference",
+                Op::Exclusion => "exclusion",
+                Op::Hue => "hue",
+                Op::Saturation => "saturation",
+                Op::Color => "color",
+                Op::Luminize => "luminosity",
+                _ => return None,
+            },
+        })
+    }
+
+    /// Blend mode of the target that a layer would get: the same one if there is one, else
+    /// the closest one, or normal blending if there is nothing close.
+    pub fn substitute(&self, op: CompositeOp) -> &'static str {
+        use CompositeOp as Op;
+        if let Some(name) = self.blend_mode(op) {
+            return name;
+        }
+        // Variants of modes that the target has
+        let close = match op {
+            Op::SoftLightSvg | Op::SoftLightPegtopDelphi | Op::SoftLightIfsIllusions => {
+                Op::SoftLight
+            }
+            Op::SoftLight => Op::SoftLightSvg,
+            Op::HardMix | Op::HardMixSofterPhotoshop => Op::HardMixPhotoshop,
+            Op::Plus | Op::LinearDodge => Op::Add,
+            Op::InverseSubtract | Op::Minus => Op::Subtract,
+            Op::HueHsv | Op::HueHsl | Op::HueHsi => Op::Hue,
+            Op::SaturationHsv | Op::SaturationHsl | Op::SaturationHsi => Op::Saturation,
+            Op::ColorHsv | Op::ColorHsl | Op::ColorHsi => Op::Color,
+            Op::Value | Op::Lightness | Op::Intensity => Op::Luminize,
+            Op::EasyBurn | Op::GammaDark => Op::Burn,
+            Op::EasyDodge | Op::GammaLight => Op::Dodge,
+            Op::HardOverlay => Op::Overlay,
+            Op::Exclusion | Op::Equivalence | Op::Negation => Op::Diff,
+            Op::DarkerColor => Op::Darken,
+            Op::LighterColor => Op::Lighten,
+            _ => Op::Normal,
+        };
+        self.blend_mode(close)
+            .or_else(|| self.blend_mode(Op::Normal))
+            .expect("every target has normal blending")
+    }
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Target::Psd => "PSD",
+            Target::Ora => "OpenRaster",
+            Target::Css => "CSS",
+        })
+    }
+}
+
+/// Layer whose blend mode the target does not have.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct CompatIssue {
+    /// The layer.
+    pub(crate) node: Uuid,
+    /// Blend mode of the layer.
+    pub(crate) blend_mode: CompositeOp,
+    /// Blend mode of the target that the layer would get, see [`Target::substitute()`].
+    pub(crate) substitute: &'static str,
+}
+
+impl CompatIssue {
+    /// The layer.
+    pub fn node(&self) -> Uuid {
+        self.node
+    }
+
+    /// Blend mode of the layer.
+    pub fn blend_mode(&self) -> CompositeOp {
+        self.blend_mode
+    }
+
+    /// Blend mode of the target that the layer would get, see [`Target::substitute()`].
+    pub fn substitute(&self) -> &'static str {
+        self.substitute
+    }
+}
+
+/// Result of [`KraFile::compat_report()`].
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct CompatReport {
+    /// Format the document would be converted into.
+    pub(crate) target: Target,
+    /// Layers whose blend modes would change, in document order.
+    pub(crate) issues: Vec<CompatIssue>,
+}
+
+impl CompatReport {
+    /// Format the document would be converted into.
+    pub fn target(&self) -> Target {
+        self.target
+    }
+
+    /// Layers whose blend modes would change, in document order.
+    pub fn issues(&self) -> &[CompatIssue] {
+        &self.issues
+    }
+
+    /// Whether every layer keeps its blend mode.
+    pub fn is_lossless(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl KraFile {
+    /// Layers whose blend modes the target does not have, and what they would get instead,
+    /// so that conversion tools can warn before they lose something.
+    ///
+    /// Only blend modes are checked. Pass-through groups are layers with their own blend
+    /// mode, which is checked as it is.
+    pub fn compat_report(&self, target: Target) -> CompatReport {
+        let issues = self
+            .all_nodes()
+            .into_iter()
+            .filter_map(|node| Some((node.uuid(), node.as_layer()?.composite_op())))
+            .filter(|(_, op)| target.blend_mode(*op).is_none())
+            .map(|(node, blend_mode)| CompatIssue {
+                node,
+                blend_mode,
+                substitute: target.substitute(blend_mode),
+            })
+            .collect();
+        CompatReport { target, issues }
+    }
+}
@@ -0,0 +1,338 @@
+//! Configuration of reading.
+
+/// Configuration of [`KraFile::read_with_config()`](crate::KraFile::read_with_config).
+///
+/// Most users want one of the presets: [`full()`](Self::full) (the default),
+/// [`metadata_only()`](Self::metadata_only) or [`preview()`](Self::preview).
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct ParsingConfiguration {
+    pub(crate) limits: Limits,
+    pub(crate) parse_layers: bool,
+    pub(crate) keep_archive: bool,
+    pub(crate) colorspace_fallback: bool,
+    pub(crate) cache_budget: usize,
+    pub(crate) unknown_attributes: UnknownAttributes,
+    pub(crate) doctype_check: DoctypeCheck,
+    pub(crate) mimetype_check: MimetypeCheck,
+    pub(crate) load_file_layers: bool,
+    pub(crate) flatten_clone_layers: bool,
+    pub(crate) apply_selection_masks: bool,
+    pub(crate) invalid_utf8: InvalidUtf8,
+    pub(crate) duplicate_uuids: DuplicateUuids,
+}
+
+impl Default for ParsingConfiguration {
+    fn default() -> Self {
+        ParsingConfiguration {
+            limits: Limits::default(),
+            parse_layers: true,
+            keep_archive: true,
+            colorspace_fallback: false,
+            cache_budget: 0,
+            unknown_attributes: UnknownAttributes::Ignore,
+            doctype_check: DoctypeCheck::Error,
+            mimetype_check: MimetypeCheck::Error,
+            load_file_layers: false,
+            flatten_clone_layers: false,
+            apply_selection_masks: false,
+            invalid_utf8: InvalidUtf8::Error,
+            duplicate_uuids: DuplicateUuids::Regenerate,
+        }
+    }
+}
+
+impl ParsingConfiguration {
+    /// Default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read everything: metadata, the node tree, and keep the archive open
+    /// to read node data later. Same as the default.
+    pub fn full() -> Self {
+        Self::default()
+    }
+
+    /// Read only the document information and image metadata.
+    ///
+    /// Nodes are not parsed, and the archive is closed after reading.
+    pub fn metadata_only() -> Self {
+        Self::default().parse_layers(false).keep_archive(false)
+    }
+
+    /// Read everything, failing on attributes of nodes that are not read into the model.
+    /// Useful for finding out what changed in new versions of Krita.
+    pub fn strict() -> Self {
+        Self::default().unknown_attributes(UnknownAttributes::Error)
+    }
+
+    /// Read metadata and keep the archive open, so that the merged image
+    /// can be read, but do not parse nodes.
+    pub fn preview() -> Self {
+        Self::default().parse_layers(false)
+    }
+
+    /// Set whether the node tree is parsed. If not, the file has no layers.
+    pub fn parse_layers(mut self, parse_layers: bool) -> Self {
+        self.parse_layers = parse_layers;
+        self
+    }
+
+    /// Whether the node tree is parsed.
+    pub fn get_parse_layers(&self) -> bool {
+        self.parse_layers
+    }
+
+    /// Set whether the archive is kept open after reading.
+    /// If not, node data and the merged image cannot be read.
+    pub fn keep_archive(mut self, keep_archive: bool) -> Self {
+        self.keep_archive = keep_archive;
+        self
+    }
+
+    /// Whether the archive is kept open after reading.
+    pub fn get_keep_archive(&self) -> bool {
+        self.keep_archive
+    }
+
+    /// Set whether an unknown colorspace of the image is read as RGBA instead of
+    /// failing. See [`KraMetadata::colorspace_fallback()`](crate::metadata::KraMetadata::colorspace_fallback).
+    pub fn colorspace_fallback(mut self, colorspace_fallback: bool) -> Self {
+        self.colorspace_fallback = colorspace_fallback;
+        self
+    }
+
+    /// Whether an unknown colorspace of the image is read as RGBA.
+    pub fn get_colorspace_fallback(&self) -> bool {
+        self.colorspace_fallback
+    }
+
+    /// Set how many bytes of decoded pixels are kept in memory, so that decoding the same
+    /// layers again (see [`KraFile::decode_layer()`](crate::KraFile::decode_layer)) is fast.
+    /// Least recently used layers are forgotten first. 0 (the default) disables caching.
+    pub fn cache_budget(mut self, cache_budget: usize) -> Self {
+        self.cache_budget = cache_budget;
+        self
+    }
+
+    /// How many bytes of decoded pixels are kept in memory.
+    pub fn get_cache_budget(&self) -> usize {
+        self.cache_budget
+    }
+
+    /// Set what is done with attributes of nodes that are not read into the model.
+    /// They are kept (and written back) in any case.
+    pub fn unknown_attributes(mut self, unknown_attributes: UnknownAttributes) -> Self {
+        self.unknown_attributes = unknown_attributes;
+        self
+    }
+
+    /// What is done with attributes of nodes that are not read into the model.
+    pub fn get_unknown_attributes(&self) -> UnknownAttributes {
+        self.unknown_attributes
+    }
+
+    /// Set what is done when `DOCTYPE` of `maindoc.xml` or `documentinfo.xml` is not the one
+    /// Krita writes. Differences in whitespace and quote style are accepted in any case.
+    pub fn doctype_check(mut self, doctype_check: DoctypeCheck) -> Self {
+        self.doctype_check = doctype_check;
+        self
+    }
+
+    /// What is done when `DOCTYPE` of an XML entry is not the one Krita writes.
+    pub fn get_doctype_check(&self) -> DoctypeCheck {
+        self.doctype_check
+    }
+
+    /// Set what is done when the `mimetype` entry is missing or is not the one Krita writes,
+    /// as in repaired files or ones written by other tools.
+    pub fn mimetype_check(mut self, mimetype_check: MimetypeCheck) -> Self {
+        self.mimetype_check = mimetype_check;
+        self
+    }
+
+    /// What is done when the `mimetype` entry is missing or is not the one Krita writes.
+    pub fn get_mimetype_check(&self) -> MimetypeCheck {
+        self.mimetype_check
+    }
+
+    /// Set whether images linked by file layers are read when compositing the image (with
+    /// the `render` feature), so that the layers are drawn. If not, visible file layers
+    /// cannot be composited.
+    pub fn load_file_layers(mut self, load_file_layers: bool) -> Self {
+        self.load_file_layers = load_file_layers;
+        self
+    }
+
+    /// Whether images linked by file layers are read when compositing the image.
+    pub fn get_load_file_layers(&self) -> bool {
+        self.load_file_layers
+    }
+
+    /// Set whether clone layers are drawn when compositing the image (with the `render`
+    /// feature), with the current pixels of the layer they clone, moved by the offset
+    /// of the clone layer. If not, visible clone layers cannot be composited.
+    pub fn flatten_clone_layers(mut self, flatten_clone_layers: bool) -> Self {
+        self.flatten_clone_layers = flatten_clone_layers;
+        self
+    }
+
+    /// Whether clone layers are drawn when compositing the image.
+    pub fn get_flatten_clone_layers(&self) -> bool {
+        self.flatten_clone_layers
+    }
+
+    /// Set whether visible selection masks clip their layers when compositing the image
+    /// (with the `render` feature), so that only the selected part of a layer is drawn.
+    /// Only the [active](crate::layer::SelectionMaskProps::active) mask of a layer is
+    /// used, like in Krita. If not set, they are ignored, as Krita only uses them to
+    /// limit painting.
+    pub fn apply_selection_masks(mut self, apply_selection_masks: bool) -> Self {
+        self.apply_selection_masks = apply_selection_masks;
+        self
+    }
+
+    /// Whether visible selection masks clip their layers when compositing the image.
+    pub fn get_apply_selection_masks(&self) -> bool {
+        self.apply_selection_masks
+    }
+
+    /// Set what is done with XML entries that are not valid UTF-8, like names of layers
+    /// written by old versions of Krita or by other tools.
+    pub fn invalid_utf8(mut self, invalid_utf8: InvalidUtf8) -> Self {
+        self.invalid_utf8 = invalid_utf8;
+        self
+    }
+
+    /// What is done with XML entries that are not valid UTF-8.
+    pub fn get_invalid_utf8(&self) -> InvalidUtf8 {
+        self.invalid_utf8
+    }
+
+    /// Set what is done with nodes whose UUID is used by a node before them, as in
+    /// corrupt or hand-edited files.
+    pub fn duplicate_uuids(mut self, duplicate_uuids: DuplicateUuids) -> Self {
+        self.duplicate_uuids = duplicate_uuids;
+        self
+    }
+
+    /// What is done with nodes whose UUID is used by a node before them.
+    pub fn get_duplicate_uuids(&self) -> DuplicateUuids {
+        self.duplicate_uuids
+    }
+
+    /// Set limits on sizes of what is read.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Limits on sizes of what is read.
+    pub fn get_limits(&self) -> &Limits {
+        &self.limits
+    }
+}
+
+/// What is done with attributes of nodes that are not read into the model,
+/// see [`ParsingConfiguration::unknown_attributes()`].
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum UnknownAttributes {
+    /// Nothing.
+    #[default]
+    Ignore,
+    /// They are listed in [`KraFile::unknown_attributes()`](crate::KraFile::unknown_attributes).
+    Warn,
+    /// Reading fails with [`UnknownAttribute`](crate::error::UnknownAttribute).
+    Error,
+}
+
+/// What is done when `DOCTYPE` of an XML entry is not the one Krita writes,
+/// see [`ParsingConfiguration::doctype_check()`].
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum DoctypeCheck {
+    /// Reading fails.
+    #[default]
+    Error,
+    /// Mismatches are listed in [`KraFile::doctype_mismatches()`](crate::KraFile::doctype_mismatches).
+    Warn,
+}
+
+/// What is done when the `mimetype` entry is missing or is not the one Krita writes,
+/// see [`ParsingConfiguration::mimetype_check()`].
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum MimetypeCheck {
+    /// Reading fails.
+    #[default]
+    Error,
+    /// The document is read if its XML entries can be parsed, and the entry is given by
+    /// [`KraFile::unexpected_mimetype()`](crate::KraFile::unexpected_mimetype).
+    Warn,
+}
+
+/// What is done with XML entries that are not valid UTF-8,
+/// see [`ParsingConfiguration::invalid_utf8()`].
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum InvalidUtf8 {
+    /// Reading fails.
+    #[default]
+    Error,
+    /// Invalid sequences are replaced with U+FFFD, and the entries are listed in
+    /// [`KraFile::replaced_utf8()`](crate::KraFile::replaced_utf8).
+    Replace,
+}
+
+/// What is done with nodes whose UUID is used by a node before them,
+/// see [`ParsingConfiguration::duplicate_uuids()`].
+///
+/// Nodes are listed in [`KraFile::duplicate_uuids()`](crate::KraFile::duplicate_uuids)
+/// unless reading fails.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum DuplicateUuids {
+    /// Reading fails with [`DuplicateUuid`](crate::error::DuplicateUuid).
+    Error,
+    /// Such nodes are left out, together with their masks and children.
+    KeepFirst,
+    /// Such nodes get new random UUIDs.
+    #[default]
+    Regenerate,
+}
+
+/// Limits on sizes of what is read, which protect against decompression bombs and
+/// other malicious files.
+///
+/// By default, nothing is limited except nesting of groups, which would otherwise
+/// overflow the stack, and nesting of documents that file layers link to, which would
+/// otherwise open files without end. Set the limits when reading untrusted files.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct Limits {
+    /// Maximum size of an XML entry (like `maindoc.xml`), in bytes.
+    pub max_xml_size: u64,
+    /// Maximum size of any other entry that is read, in bytes.
+    pub max_entry_size: u64,
+    /// Maximum number of nodes (layers and masks).
+    pub max_nodes: usize,
+    /// Maximum nesting of group layers: 1 allows groups at the top level, but not groups
+    /// in them. 64 by default.
+    pub max_group_depth: usize,
+    /// Maximum width and height of the image, in pixels.
+    pub max_dimensions: (u32, u32),
+    /// Maximum number of pixels of the image, and of pixel data of a layer when it is decoded.
+    pub max_pixels: u64,
+    /// Maximum nesting of documents that file layers link to: 1 allows documents linked
+    /// from this one, but not the ones they link to. 16 by default.
+    pub max_file_layer_depth: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_xml_size: u64::MAX,
+            max_entry_size: u64::MAX,
+            max_nodes: usize::MAX,
+            max_group_depth: 64,
+            max_dimensions: (u32::MAX, u32::MAX),
+            max_pixels: u64::MAX,
+            max_file_layer_depth: 16,
+        }
+    }
+}
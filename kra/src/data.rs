@@ -1,7 +1,21 @@
 //! Types of layer/mask data.
 
 use core::fmt;
-use std::fmt::{Debug, Display};
+use std::{
+    fmt::{Debug, Display},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    color::{
+        change_alpha, convert_pixels_with, denormalize, normalize, to_channels, AlphaMode,
+        ChannelDepth, ConvertOptions, F16,
+    },
+    error::DataError,
+    geom::IRect,
+    id::Uuid,
+    Colorspace,
+};
 
 //TODO: store actual data
 /// Data that the node refers to via `filename` property.
@@ -12,6 +26,7 @@ pub enum NodeData {
     Unloaded(Unloaded),
 }
 
+/// Kinds of data that were not loaded yet.
 pub enum Unloaded {
     /// A compressed image.
     Image,
@@ -29,14 +44,6 @@ pub enum Unloaded {
     SelectionMask,
 }
 
-    /// Colorize mask information.
-    ColorizeMask,
-    /// Transformation mask information.
-    TransformMask,
-    /// Transparency mask information.
-    TransparencyMask,
-}
-
 impl Debug for Unloaded {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -65,20 +72,6 @@ impl Display for Unloaded {
     }
 }
 
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::DoesNotExist => write!(f, "DoesNotExist"),
-            Self::NotLoaded => write!(f, "NotLoaded"),
-            Self::Image => write!(f, "Image"),
-            Self::Vector => write!(f, "Vector"),
-            Self::Filter => write!(f, "Filter"),
-            Self::ColorizeMask => write!(f, "ColorizeMask"),
-            Self::TransformMask => write!(f, "TransformMask"),
-            Self::TransparencyMask => write!(f, "TransparencyMask"),
-        }
-    }
-}
-
 impl fmt::Debug for NodeData {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -96,3 +89,338 @@ impl fmt::Display for NodeData {
         }
     }
 }
+
+/// Paint layers found by [`KraFile::find_empty_layers()`](crate::KraFile::find_empty_layers).
+#[derive(Debug, Default, PartialEq, Eq, Clone, Hash)]
+pub struct EmptyLayers {
+    /// Layers that have no painted content.
+    pub(crate) empty: Vec<Uuid>,
+    /// Hidden layers that have painted content.
+    pub(crate) hidden_with_content: Vec<Uuid>,
+}
+
+impl EmptyLayers {
+    /// Layers that have no painted content.
+    pub fn empty(&self) -> &[Uuid] {
+        &self.empty
+    }
+
+    /// Hidden layers that have painted content.
+    pub fn hidden_with_content(&self) -> &[Uuid] {
+        &self.hidden_with_content
+    }
+}
+
+/// Where the image given by
+/// [`KraFile::merged_image_with_source()`](crate::KraFile::merged_image_with_source)
+/// came from.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum MergedImageSource {
+    /// `mergedimage.png` of the document, as Krita rendered it when saving.
+    Stored,
+    /// Composited from the layers, as the document has no `mergedimage.png`.
+    Composited,
+}
+
+/// Link from a file layer to the file it shows, see
+/// [`KraFile::file_layer_links()`](crate::KraFile::file_layer_links).
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct FileLayerLink {
+    pub(crate) document: Option<PathBuf>,
+    pub(crate) layer: Uuid,
+    pub(crate) source: PathBuf,
+    pub(crate) cycle: bool,
+}
+
+impl FileLayerLink {
+    /// Canonical path of the linked document that has the layer, or `None` for layers
+    /// of the document the links were collected from.
+    pub fn document(&self) -> Option<&Path> {
+        self.document.as_deref()
+    }
+
+    /// UUID of the file layer.
+    pub fn layer(&self) -> Uuid {
+        self.layer
+    }
+
+    /// Path to the linked file, canonical if the file exists.
+    pub fn source(&self) -> &Path {
+        &self.source
+    }
+
+    /// Whether the linked file is a `.kra` document.
+    pub fn is_document(&self) -> bool {
+        is_document(&self.source)
+    }
+
+    /// Whether the linked document is one of the documents that lead to the layer, so
+    /// that it cannot be composited.
+    pub fn is_cycle(&self) -> bool {
+        self.cycle
+    }
+}
+
+// Whether the file is a document, going by its extension
+pub(crate) fn is_document(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("kra"))
+}
+
+/// Canvas area suggested by [`KraFile::suggest_crop()`](crate::KraFile::suggest_crop).
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct CropSuggestion {
+    /// Left edge of the area, in pixels.
+    pub(crate) x: u32,
+    /// Top edge of the area, in pixels.
+    pub(crate) y: u32,
+    /// Width of the area, in pixels.
+    pub(crate) width: u32,
+    /// Height of the area, in pixels.
+    pub(crate) height: u32,
+}
+
+impl CropSuggestion {
+    /// Left edge of the area, in pixels.
+    pub fn x(&self) -> u32 {
+        self.x
+    }
+
+    /// Top edge of the area, in pixels.
+    pub fn y(&self) -> u32 {
+        self.y
+    }
+
+    /// Width of the area, in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the area, in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The area as a rectangle, to pass to [`KraFile::crop()`](crate::KraFile::crop).
+    pub fn rect(&self) -> IRect {
+        IRect::new(self.x as i32, self.y as i32, self.width, self.height)
+    }
+}
+
+/// Channel values, tagged with their depth.
+#[derive(Debug, PartialEq, Clone)]
+#[non_exhaustive]
+pub enum PixelBuffer {
+    /// 8-bit unsigned integer channels.
+    U8(Vec<u8>),
+    /// 16-bit unsigned integer channels.
+    U16(Vec<u16>),
+    /// 16-bit floating point channels.
+    F16(Vec<F16>),
+    /// 32-bit floating point channels.
+    F32(Vec<f32>),
+}
+
+impl PixelBuffer {
+    // Channel values from little-endian bytes, as Krita stores them
+    pub(crate) fn from_le_bytes(bytes: Vec<u8>, depth: ChannelDepth) -> Self {
+        match depth {
+            ChannelDepth::U8 => PixelBuffer::U8(bytes),
+            ChannelDepth::U16 => PixelBuffer::U16(to_channels(&bytes)),
+            ChannelDepth::F16 => PixelBuffer::F16(to_channels(&bytes)),
+            ChannelDepth::F32 => PixelBuffer::F32(to_channels(&bytes)),
+        }
+    }
+
+    // Size of the values in memory
+    pub(crate) fn byte_len(&self) -> usize {
+        match self {
+            PixelBuffer::U8(values) => values.len(),
+            PixelBuffer::U16(values) => values.len() * 2,
+            PixelBuffer::F16(values) => values.len() * 2,
+            PixelBuffer::F32(values) => values.len() * 4,
+        }
+    }
+
+    // Inverse of from_le_bytes()
+    pub(crate) fn to_le_bytes(&self) -> Vec<u8> {
+        match self {
+            PixelBuffer::U8(values) => values.clone(),
+            PixelBuffer::U16(values) => values
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect(),
+            PixelBuffer::F16(values) => values
+                .iter()
+                .flat_map(|value| value.0.to_le_bytes())
+                .collect(),
+            PixelBuffer::F32(values) => values
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect(),
+        }
+    }
+}
+
+/// Decoded pixel data of a node.
+///
+/// Pixels are stored row by row, with interleaved channels in the order Krita uses:
+/// BGRA for integer RGB colorspaces, RGBA for floating point ones.
+/// Alpha is straight, as Krita stores it, unless asked otherwise.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LayerData {
+    /// Horisontal position of the left edge, in image coordinates.
+    pub(crate) x: i32,
+    /// Vertical position of the top edge, in image coordinates.
+    pub(crate) y: i32,
+    /// Width, in pixels.
+    pub(crate) width: u32,
+    /// Height, in pixels.
+    pub(crate) height: u32,
+    /// Colorspace of the pixels.
+    pub(crate) colorspace: Colorspace,
+    /// Channel values.
+    pub(crate) pixels: PixelBuffer,
+    /// Whether color channels are multiplied by alpha.
+    pub(crate) alpha_mode: AlphaMode,
+}
+
+impl LayerData {
+    /// Horisontal position of the left edge, in image coordinates.
+    pub fn x(&self) -> i32 {
+        self.x
+    }
+
+    /// Vertical position of the top edge, in image coordinates.
+    pub fn y(&self) -> i32 {
+        self.y
+    }
+
+    /// Width, in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height, in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Colorspace of the pixels.
+    pub fn colorspace(&self) -> Colorspace {
+        self.colorspace
+    }
+
+    /// Channel values.
+    pub fn pixels(&self) -> &PixelBuffer {
+        &self.pixels
+    }
+
+    /// Whether color channels are multiplied by alpha.
+    pub fn alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+
+    /// The same pixels with color channels multiplied by alpha, or divided by it.
+    ///
+    /// Integer values are rounded to the depth of the channels. Color of fully transparent
+    /// pixels is lost when premultiplying.
+    pub fn into_alpha_mode(mut self, alpha_mode: AlphaMode) -> Self {
+        if self.alpha_mode != alpha_mode {
+            let mut values = normalize(&self.pixels);
+            values
+                .chunks_exact_mut(self.colorspace.channel_count())
+                .for_each(|pixel| change_alpha(pixel, self.alpha_mode, alpha_mode));
+            self.pixels = denormalize(values, self.colorspace.depth());
+            self.alpha_mode = alpha_mode;
+        }
+        self
+    }
+}
+
+// Conversions into the layouts other libraries use
+impl LayerData {
+    /// Pixels as 8-bit RGBA values with straight alpha, row by row.
+    ///
+    /// Pixels of other depths and grayscale pixels are converted, like
+    /// [`KraFile::decode_layer_converted()`](crate::KraFile::decode_layer_converted) does.
+    pub fn to_rgba8(&self) -> Result<Vec<u8>, DataError> {
+        self.to_rgba8_with(&ConvertOptions::new())
+    }
+
+    /// Same as [`to_rgba8()`](Self::to_rgba8), with the given alpha mode and transfer
+    /// function.
+    pub fn to_rgba8_with(&self, options: &ConvertOptions) -> Result<Vec<u8>, DataError> {
+        match self.converted(Colorspace::RGBA, options)? {
+            PixelBuffer::U8(mut values) => {
+                // Krita stores BGRA
+                values
+                    .chunks_exact_mut(4)
+                    .for_each(|pixel| pixel.swap(0, 2));
+                Ok(values)
+            }
+            _ => unreachable!("RGBA has 8-bit channels"),
+        }
+    }
+
+    /// Pixels as 32-bit floating point RGBA values with straight alpha, row by row.
+    ///
+    /// Values of integer depths are normalized to the range from 0 to 1.
+    pub fn to_rgba32f(&self) -> Result<Vec<f32>, DataError> {
+        self.to_rgba32f_with(&ConvertOptions::new())
+    }
+
+    /// Same as [`to_rgba32f()`](Self::to_rgba32f), with the given alpha mode and transfer
+    /// function.
+    pub fn to_rgba32f_with(&self, options: &ConvertOptions) -> Result<Vec<f32>, DataError> {
+        match self.converted(Colorspace::RGBAF32, options)? {
+            PixelBuffer::F32(values) => Ok(values),
+            _ => unreachable!("RGBAF32 has 32-bit floating point channels"),
+        }
+    }
+
+    /// The pixels as an image of the `image` crate, see [`to_rgba8()`](Self::to_rgba8).
+    #[cfg(feature = "image")]
+    pub fn to_rgba_image(&self) -> Result<image::RgbaImage, DataError> {
+        let image = image::RgbaImage::from_raw(self.width, self.height, self.to_rgba8()?);
+        Ok(image.expect("buffer has the size of the layer"))
+    }
+
+    /// The pixels as a floating point image of the `image` crate, see
+    /// [`to_rgba32f()`](Self::to_rgba32f).
+    #[cfg(feature = "image")]
+    pub fn to_rgba32f_image(&self) -> Result<image::Rgba32FImage, DataError> {
+        let image = image::Rgba32FImage::from_raw(self.width, self.height, self.to_rgba32f()?);
+        Ok(image.expect("buffer has the size of the layer"))
+    }
+
+    /// The pixels as a pixmap of `tiny-skia`, which holds 8-bit RGBA values with
+    /// premultiplied alpha, see [`to_rgba8_with()`](Self::to_rgba8_with).
+    ///
+    /// Layers without pixels give `None`, as a pixmap cannot be empty.
+    #[cfg(feature = "tiny-skia")]
+    pub fn to_pixmap(&self) -> Result<Option<tiny_skia::Pixmap>, DataError> {
+        let Some(size) = tiny_skia::IntSize::from_wh(self.width, self.height) else {
+            return Ok(None);
+        };
+        let options = ConvertOptions::new().alpha_mode(AlphaMode::Premultiplied);
+        Ok(tiny_skia::Pixmap::from_vec(
+            self.to_rgba8_with(&options)?,
+            size,
+        ))
+    }
+
+    fn converted(
+        &self,
+        target: Colorspace,
+        options: &ConvertOptions,
+    ) -> Result<PixelBuffer, DataError> {
+        convert_pixels_with(
+            &self.pixels,
+            (self.colorspace, self.alpha_mode),
+            target,
+            options,
+        )
+        .ok_or(DataError::UnsupportedConversion(self.colorspace, target))
+    }
+}
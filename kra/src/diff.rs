@@ -0,0 +1,132 @@
+//! Differences between two reads of a document.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    id::Uuid,
+    layer::{Node, NodeType},
+    KraFile,
+};
+
+/// One difference between two reads of a document, see [`diff()`].
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+#[non_exhaustive]
+pub enum Change {
+    /// Image metadata changed, for example its size or colorspace.
+    Metadata,
+    /// Document information changed, for example its title or author.
+    DocumentInfo,
+    /// The node was added.
+    NodeAdded(Uuid),
+    /// The node was removed.
+    NodeRemoved(Uuid),
+    /// Attributes of the node changed, for example its name, visibility or offset.
+    NodeChanged(Uuid),
+    /// The node was moved under another parent.
+    NodeMoved(Uuid),
+    /// Nodes under the parent (`None` is the top level) are in another order.
+    Reordered(Option<Uuid>),
+}
+
+// Where every node is, and the order of nodes under every parent
+struct Tree<'a> {
+    // In document order
+    list: Vec<Uuid>,
+    nodes: HashMap<Uuid, (Option<Uuid>, &'a Node)>,
+    order: Vec<(Option<Uuid>, Vec<Uuid>)>,
+}
+
+impl<'a> Tree<'a> {
+    fn new(file: &'a KraFile) -> Self {
+        fn walk<'a>(nodes: &'a [Node], parent: Option<Uuid>, tree: &mut Tree<'a>) {
+            tree.order
+                .push((parent, nodes.iter().map(|node| node.uuid()).collect()));
+            for node in nodes {
+                tree.list.push(node.uuid());
+                tree.nodes.insert(node.uuid(), (parent, node));
+                if !node.masks().is_empty() {
+                    walk(node.masks(), Some(node.uuid()), tree);
+                }
+                if let NodeType::GroupLayer(props) = node.node_type() {
+                    walk(props.layers(), Some(node.uuid()), tree);
+                }
+            }
+        }
+        let mut tree = Tree {
+            list: Vec::new(),
+            nodes: HashMap::new(),
+            order: Vec::new(),
+        };
+        walk(file.layers(), None, &mut tree);
+        tree
+    }
+
+    fn children(&self, parent: Option<Uuid>) -> &[Uuid] {
+        self.order
+            .iter()
+            .find(|(other, _)| *other == parent)
+            .map_or(&[], |(_, children)| children)
+    }
+}
+
+/// What changed between two reads of a document. Changes of nodes are in document order
+/// of `new`, followed by removed nodes and reordered parents.
+///
+/// Only metadata and the node tree are compared, pixels and other entries of the archive
+/// are not. Nodes are matched by their UUIDs, and their attributes are compared regardless
+/// of their order.
+pub fn diff(old: &KraFile, new: &KraFile) -> Vec<Change> {
+    let mut changes = Vec::new();
+    if old.meta() != new.meta() {
+        changes.push(Change::Metadata);
+    }
+    if old.doc_info() != new.doc_info() {
+        changes.push(Change::DocumentInfo);
+    }
+
+    let (old_tree, new_tree) = (Tree::new(old), Tree::new(new));
+    for uuid in &new_tree.list {
+        let (parent, node) = new_tree.nodes[uuid];
+        let Some((old_parent, old_node)) = old_tree.nodes.get(uuid) else {
+            changes.push(Change::NodeAdded(*uuid));
+            continue;
+        };
+        if *old_parent != parent {
+            changes.push(Change::NodeMoved(*uuid));
+        }
+        if sorted_attributes(old_node) != sorted_attributes(node) {
+            changes.push(Change::NodeChanged(*uuid));
+        }
+    }
+    changes.extend(
+        old_tree
+            .list
+            .iter()
+            .filter(|uuid| !new_tree.nodes.contains_key(uuid))
+            .map(|uuid| Change::NodeRemoved(*uuid)),
+    );
+
+    // Only nodes that stayed under the same parent are compared
+    for (parent, children) in &new_tree.order {
+        let old_children = old_tree.children(*parent);
+        let (old_set, new_set): (HashSet<_>, HashSet<_>) =
+            (old_children.iter().collect(), children.iter().collect());
+        let kept = |uuids: &[Uuid], others: &HashSet<&Uuid>| -> Vec<Uuid> {
+            uuids
+                .iter()
+                .filter(|uuid| others.contains(uuid))
+                .copied()
+                .collect()
+        };
+        if kept(children, &old_set) != kept(old_children, &new_set) {
+            changes.push(Change::Reordered(*parent));
+        }
+    }
+    changes
+}
+
+fn sorted_attributes(node: &Node) -> Vec<&(String, String)> {
+    let mut attributes: Vec<_> = node.attributes().iter().collect();
+    attributes.sort();
+    attributes
+}
@@ -0,0 +1,1359 @@
+//! Changing the node tree and document information.
+//!
+//! Changes are kept in memory, and are saved with [`KraFile::write()`].
+//! When the tree is changed, `<layers>` of `maindoc.xml` is written anew from the nodes and
+//! their [attributes](crate::layer::Node::attributes). Elements inside of nodes that are not
+//! parsed are kept as they were read, in
+//! [`Node::unparsed_elements()`](crate::layer::Node::unparsed_elements), and written back.
+//! The rest of `maindoc.xml` stays as it was, except for the size of the image when it is
+//! changed.
+//! `documentinfo.xml` is written anew when one of its fields is changed.
+
+use std::{borrow::Cow, ops::Range};
+
+use quick_xml::{escape::escape, events::Event, Reader as XmlReader};
+use regex::Regex;
+
+use crate::{
+    blend::Blend,
+    color::{convert_pixels, denormalize, normalize, ChannelDepth, ColorModel},
+    data::PixelBuffer,
+    error::{DataError, EditError, LimitError, MetadataErrorReason, XmlError},
+    geom::IRect,
+    helper::{event_get_all_attrs, set_attr},
+    id::Uuid,
+    layer::{is_valid_property_key, Node, NodeKind, NodeType},
+    metadata::DocInfoField,
+    parse_layer,
+    resample::{self, Block},
+    tile::{self, stores_bgra, Tile, TileHeader, TiledData},
+    Colorspace, KraFile, ParseContext,
+};
+
+/// How pixels are resampled by [`KraFile::scale()`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+#[non_exhaustive]
+pub enum ScaleFilter {
+    /// Nearest neighbour, which keeps hard edges.
+    Nearest,
+    /// Bilinear interpolation.
+    Bilinear,
+    /// Bicubic interpolation, which Krita uses by default.
+    #[default]
+    Bicubic,
+}
+
+/// Text that [`KraFile::add_text_layer()`] puts into a new vector layer.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TextLayer {
+    pub(crate) text: String,
+    pub(crate) name: Option<String>,
+    pub(crate) font: String,
+    pub(crate) size: f64,
+    pub(crate) position: (f64, f64),
+    pub(crate) color: [u8; 3],
+}
+
+impl TextLayer {
+    /// Black text in a 12pt sans-serif font, with its first line starting at the top left
+    /// corner. Lines are separated by `\n`.
+    pub fn new(text: &str) -> Self {
+        TextLayer {
+            text: text.to_owned(),
+            name: None,
+            font: "sans-serif".to_owned(),
+            size: 12.0,
+            position: (0.0, 0.0),
+            color: [0, 0, 0],
+        }
+    }
+
+    /// Text of the layer.
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+
+    /// Set the name of the layer.
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_owned());
+        self
+    }
+
+    /// Name of the layer, the first line of the text if it is not set.
+    pub fn get_name(&self) -> &str {
+        self.name
+            .as_deref()
+            .unwrap_or_else(|| self.text.lines().next().unwrap_or_default())
+    }
+
+    /// Set the font family, as CSS names it, like `"Noto Sans"` or `serif`.
+    pub fn font(mut self, font: &str) -> Self {
+        self.font = font.to_owned();
+        self
+    }
+
+    /// Font family, as CSS names it.
+    pub fn get_font(&self) -> &str {
+        &self.font
+    }
+
+    /// Set the font size, in points.
+    pub fn size(mut self, size: f64) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Font size, in points.
+    pub fn get_size(&self) -> f64 {
+        self.size
+    }
+
+    /// Set where the baseline of the first line starts, in pixels.
+    pub fn position(mut self, x: f64, y: f64) -> Self {
+        self.position = (x, y);
+        self
+    }
+
+    /// Where the baseline of the first line starts, in pixels.
+    pub fn get_position(&self) -> (f64, f64) {
+        self.position
+    }
+
+    /// Set the color of the text, as 8-bit RGB.
+    pub fn color(mut self, color: [u8; 3]) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Color of the text, as 8-bit RGB.
+    pub fn get_color(&self) -> [u8; 3] {
+        self.color
+    }
+
+    // Contents of the layer, as Krita stores shapes of vector layers. Coordinates of
+    // the SVG are in points.
+    fn to_svg(&self, width: u32, height: u32, (x_res, y_res): (f64, f64)) -> String {
+        let (width, height) = (width as f64 * 72.0 / x_res, height as f64 * 72.0 / y_res);
+        let (x, y) = (
+            self.position.0 * 72.0 / x_res,
+            self.position.1 * 72.0 / y_res,
+        );
+        let [red, green, blue] = self.color;
+        let lines: String = self
+            .text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| match index {
+                0 => format!("<tspan x=\"0\">{}</tspan>", escape(line)),
+                _ => format!("<tspan x=\"0\" dy=\"1.2em\">{}</tspan>", escape(line)),
+            })
+            .collect();
+        format!(
+            r##"<?xml version="1.0" standalone="no"?>
+<!DOCTYPE svg PUBLIC "-//W3C//DTD SVG 20010904//EN" "http://www.w3.org/TR/2001/REC-SVG-20010904/DTD/svg10.dtd">
+<!-- Created using Krita: https://krita.org -->
+<svg xmlns="http://www.w3.org/2000/svg"
+    xmlns:xlink="http://www.w3.org/1999/xlink"
+    xmlns:krita="http://krita.org/namespaces/svg/krita"
+    xmlns:sodipodi="http://sodipodi.sourceforge.net/DTD/sodipodi-0.dtd"
+    width="{width}pt"
+    height="{height}pt"
+    viewBox="0 0 {width} {height}">
+<defs/>
+<text id="shape0" krita:useRichText="true" text-rendering="auto" krita:textVersion="3" transform="translate({x}, {y})" fill="#{red:02x}{green:02x}{blue:02x}" stroke-opacity="0" stroke="#000000" stroke-width="0" style="font-family: {font};font-size: {size};">{lines}</text>
+</svg>
+"##,
+            font = escape(&self.font),
+            size = self.size,
+        )
+    }
+}
+
+/// How [`KraFile::add_watermark()`] places the image.
+#[cfg(feature = "image")]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct WatermarkOptions {
+    pub(crate) name: String,
+    pub(crate) position: (i32, i32),
+    pub(crate) opacity: u8,
+    pub(crate) update_merged_image: bool,
+}
+
+#[cfg(feature = "image")]
+impl Default for WatermarkOptions {
+    fn default() -> Self {
+        WatermarkOptions {
+            name: "Watermark".to_owned(),
+            position: (0, 0),
+            opacity: u8::MAX,
+            update_merged_image: true,
+        }
+    }
+}
+
+#[cfg(feature = "image")]
+impl WatermarkOptions {
+    /// Opaque layer named "Watermark" at the top left corner, also drawn over
+    /// `mergedimage.png`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the name of the layer.
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_owned();
+        self
+    }
+
+    /// Name of the layer.
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Set where the top left corner of the image goes, in pixels. Parts of the image
+    /// outside of the document are kept in the layer.
+    pub fn position(mut self, x: i32, y: i32) -> Self {
+        self.position = (x, y);
+        self
+    }
+
+    /// Where the top left corner of the image goes, in pixels.
+    pub fn get_position(&self) -> (i32, i32) {
+        self.position
+    }
+
+    /// Set opacity of the layer, from 0 to 255.
+    pub fn opacity(mut self, opacity: u8) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    /// Opacity of the layer, from 0 to 255.
+    pub fn get_opacity(&self) -> u8 {
+        self.opacity
+    }
+
+    /// Set whether the image is also drawn over `mergedimage.png`, so that the document
+    /// looks watermarked without opening it in Krita.
+    pub fn update_merged_image(mut self, update_merged_image: bool) -> Self {
+        self.update_merged_image = update_merged_image;
+        self
+    }
+
+    /// Whether the image is also drawn over `mergedimage.png`.
+    pub fn get_update_merged_image(&self) -> bool {
+        self.update_merged_image
+    }
+}
+
+// Text of maindoc.xml, and where its top-level <layers> is. Only <layers> is made anew when
+// the document is written, so sections that are not parsed (like animation) are kept.
+#[derive(Debug, Default)]
+pub(crate) struct MaindocSource {
+    text: String,
+    layers: Range<usize>,
+    // New size of the image, if it was changed
+    size: Option<(u32, u32)>,
+    modified: bool,
+}
+
+impl MaindocSource {
+    pub(crate) fn new(text: &str, layers: Range<usize>) -> Self {
+        MaindocSource {
+            text: text.to_owned(),
+            layers,
+            size: None,
+            modified: false,
+        }
+    }
+}
+
+// Paint layer whose pixels are changed along with the image
+struct PaintLayer {
+    uuid: Uuid,
+    filename: String,
+    x: i32,
+    y: i32,
+    colorspace: Colorspace,
+}
+
+impl KraFile {
+    /// Move the node under `new_parent` (or to the top level, if it is `None`),
+    /// so that it has the position `index` among its new siblings.
+    ///
+    /// Layers can only be placed at the top level or into groups, and masks only into
+    /// layers that are not groups. A group cannot be moved into itself, and groups cannot
+    /// be nested deeper than [`Limits::max_group_depth`](crate::config::Limits::max_group_depth)
+    /// of the configuration the file was read with. If the move is not allowed, nothing
+    /// is changed.
+    pub fn move_node(
+        &mut self,
+        id: &Uuid,
+        new_parent: Option<&Uuid>,
+        index: usize,
+    ) -> Result<(), EditError> {
+        let (old_parent, old_index) =
+            locate(&self.layers, None, id).ok_or(EditError::UnknownNode(*id))?;
+        let node = find(&self.layers, id).ok_or(EditError::UnknownNode(*id))?;
+        let is_mask = node.kind().is_mask();
+
+        let siblings = match new_parent {
+            None if is_mask => {
+                return Err(EditError::InvalidParent {
+                    node: *id,
+                    parent: None,
+                })
+            }
+            None => &self.layers,
+            Some(parent_id) => {
+                if parent_id == id || find(node.children(), parent_id).is_some() {
+                    return Err(EditError::Cycle(*id));
+                }
+                let parent =
+                    find(&self.layers, parent_id).ok_or(EditError::UnknownNode(*parent_id))?;
+                let allowed = match parent.node_type() {
+                    NodeType::GroupLayer(_) => !is_mask,
+                    other => is_mask && other.kind().is_layer(),
+                };
+                if !allowed {
+                    return Err(EditError::InvalidParent {
+                        node: *id,
+                        parent: Some(*parent_id),
+                    });
+                }
+                let depth = group_depth(&self.layers, parent_id).unwrap_or(0) + subtree_depth(node);
+                let max_group_depth = self.config.limits.max_group_depth;
+                if depth > max_group_depth {
+                    return Err(EditError::NestingTooDeep(max_group_depth));
+                }
+                parent.children()
+            }
+        };
+        // The node itself does not count if it stays under the same parent
+        let len = match old_parent.as_ref() == new_parent {
+            true => siblings.len() - 1,
+            false => siblings.len(),
+        };
+        if index > len {
+            return Err(EditError::IndexOutOfBounds { index, len });
+        }
+
+        let node = self.children_mut(old_parent.as_ref())?.remove(old_index);
+        self.children_mut(new_parent)?.insert(index, node);
+        self.source.modified = true;
+        Ok(())
+    }
+
+    /// Merge the paint layer into the paint layer right below it, like "Merge with Layer
+    /// Below" in Krita. Pixels of the upper layer are blended into the lower one using
+    /// the composition operator and opacity of the upper layer, and the upper layer is removed.
+    ///
+    /// Properties of the lower layer, including its own opacity and composition operator,
+    /// stay as they were. A hidden upper layer does not change the pixels, and one that
+    /// [inherits alpha](Node::inherits_alpha) does not change alpha of the lower one.
+    /// Layers with masks, composition operators that are not separable (like hue) and
+    /// colorspaces other than RGB and grayscale are not supported yet.
+    pub fn merge_down(&mut self, upper_id: &Uuid) -> Result<(), EditError> {
+        let (parent, index) =
+            locate(&self.layers, None, upper_id).ok_or(EditError::UnknownNode(*upper_id))?;
+        let siblings = match parent {
+            None => &self.layers[..],
+            Some(parent) => find(&self.layers, &parent)
+                .ok_or(EditError::UnknownNode(parent))?
+                .children(),
+        };
+        let upper = &siblings[index];
+        let NodeType::PaintLayer(upper_props) = upper.node_type() else {
+            return Err(EditError::NotPaintLayer(*upper_id));
+        };
+        let lower = siblings
+            .get(index + 1)
+            .ok_or(EditError::NoLayerBelow(*upper_id))?;
+        let NodeType::PaintLayer(lower_props) = lower.node_type() else {
+            return Err(EditError::NotPaintLayer(lower.uuid()));
+        };
+        for node in [upper, lower] {
+            if !node.masks().is_empty() {
+                return Err(EditError::HasMasks(node.uuid()));
+            }
+        }
+        let blend = Blend::new(upper_props.composite_op()).ok_or(
+            EditError::UnsupportedCompositeOp(upper_props.composite_op()),
+        )?;
+        let colorspace = lower_props.colorspace();
+        if !matches!(colorspace.model(), ColorModel::Rgb | ColorModel::Gray) {
+            return Err(EditError::UnsupportedColorspace(colorspace));
+        }
+        let opacity = match upper.visible() {
+            true => upper_props.opacity() as f32 / u8::MAX as f32,
+            false => 0.0,
+        };
+        let inherit_alpha = upper.inherits_alpha();
+        let upper_colorspace = upper_props.colorspace();
+        let lower_id = lower.uuid();
+        let lower_filename = lower.filename().to_owned();
+        let offset = (lower.x(), lower.y());
+
+        let upper_data = self.decode_layer(upper_id)?;
+        let upper_pixels =
+            convert_pixels(upper_data.pixels(), upper_colorspace, colorspace).ok_or(
+                DataError::UnsupportedConversion(upper_colorspace, colorspace),
+            )?;
+        let lower_data = self.decode_layer(&lower_id)?;
+        let pixel_size = colorspace.pixel_size();
+        let default_pixel = match self.read_default_pixel(&lower_filename)? {
+            Some(pixel) if pixel.len() == pixel_size => pixel,
+            _ => vec![0; pixel_size],
+        };
+
+        // Both layers, relative to the lower layer's offset
+        let rects: Vec<(i32, i32, usize, usize, Vec<f32>)> = [
+            (&lower_data, normalize(lower_data.pixels())),
+            (&upper_data, normalize(&upper_pixels)),
+        ]
+        .into_iter()
+        .filter(|(data, _)| data.width() > 0)
+        .map(|(data, pixels)| {
+            let (x, y) = (data.x() - offset.0, data.y() - offset.1);
+            (x, y, data.width() as usize, data.height() as usize, pixels)
+        })
+        .collect();
+
+        const TILE_SIZE: i32 = tile::TILE_SIZE as i32;
+        let snap = |value: i32| value.div_euclid(TILE_SIZE) * TILE_SIZE;
+        let left = rects.iter().map(|rect| snap(rect.0)).min().unwrap_or(0);
+        let top = rects.iter().map(|rect| snap(rect.1)).min().unwrap_or(0);
+        let right = rects
+            .iter()
+            .map(|rect| snap(rect.0 + rect.2 as i32 + TILE_SIZE - 1))
+            .max()
+            .unwrap_or(0);
+        let bottom = rects
+            .iter()
+            .map(|rect| snap(rect.1 + rect.3 as i32 + TILE_SIZE - 1))
+            .max()
+            .unwrap_or(0);
+        let (width, height) = ((right - left) as usize, (bottom - top) as usize);
+
+        let channels = colorspace.channel_count();
+        let default_values = normalize(&PixelBuffer::from_le_bytes(
+            default_pixel.clone(),
+            colorspace.depth(),
+        ));
+        let mut canvas = default_values.repeat(width * height);
+        for (layer, (x, y, rect_width, _, pixels)) in rects.iter().enumerate() {
+            // The lower layer goes first, as it is
+            let is_lower = layer == 0 && lower_data.width() > 0;
+            for (row, values) in pixels.chunks_exact(rect_width * channels).enumerate() {
+                let start = ((*y - top) as usize + row) * width + (*x - left) as usize;
+                let target = &mut canvas[start * channels..(start + rect_width) * channels];
+                match is_lower {
+                    true => target.copy_from_slice(values),
+                    false => {
+                        for (dst, src) in target
+                            .chunks_exact_mut(channels)
+                            .zip(values.chunks_exact(channels))
+                        {
+                            match inherit_alpha {
+                                true => blend.apply_inheriting_alpha(dst, src, opacity),
+                                false => blend.apply(dst, src, opacity),
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        let bytes = denormalize(canvas, colorspace.depth()).to_le_bytes();
+        let header = TileHeader {
+            tile_width: TILE_SIZE as u32,
+            tile_height: TILE_SIZE as u32,
+            pixel_size: pixel_size as u32,
+            tile_count: 0,
+        };
+        let tiles =
+            TiledData::from_dense(header, (left, top, width as u32), &bytes, &default_pixel);
+        let path = self.layer_entry_path(&lower_filename)?;
+        self.set_entry(path, tiles.write());
+
+        self.remove_node(parent, index)
+    }
+
+    /// Split the paint layer into grayscale layers, one for each color channel, like
+    /// "Separate Image" in Krita. Returns UUIDs of the new layers, in channel order.
+    ///
+    /// The new layers are placed above the layer, in the same order, and are named after
+    /// their channels ("Red", "Green", "Blue" for RGB). Channel values are copied as they
+    /// are, along with alpha, and have the same depth. Other properties are taken from
+    /// the layer, which is kept.
+    pub fn split_channels(&mut self, id: &Uuid) -> Result<Vec<Uuid>, EditError> {
+        let (parent, index) = locate(&self.layers, None, id).ok_or(EditError::UnknownNode(*id))?;
+        let node = find(&self.layers, id).ok_or(EditError::UnknownNode(*id))?;
+        let NodeType::PaintLayer(props) = node.node_type() else {
+            return Err(EditError::NotPaintLayer(*id));
+        };
+        let colorspace = props.colorspace();
+        let attributes = node.attributes().to_vec();
+        let filename = node.filename().to_owned();
+
+        let source = self.read_tiled_data(&filename)?;
+        let size = colorspace.depth().size();
+        let pixel_size = colorspace.pixel_size();
+        let gray = gray_colorspace(colorspace.depth());
+        let header = TileHeader {
+            pixel_size: gray.pixel_size() as u32,
+            tile_count: 0,
+            ..source.header
+        };
+        // Gray value from the channel, then alpha
+        let extract = |pixel: &[u8], channel: usize| {
+            let mut out = pixel[channel * size..(channel + 1) * size].to_vec();
+            out.extend_from_slice(&pixel[pixel_size - size..]);
+            out
+        };
+
+        let mut created = Vec::new();
+        for (position, (name, channel)) in color_channels(colorspace).into_iter().enumerate() {
+            let default_pixel = source
+                .default_pixel
+                .as_ref()
+                .filter(|pixel| pixel.len() == pixel_size)
+                .map(|pixel| extract(pixel, channel));
+            let empty = default_pixel.clone().unwrap_or_else(|| vec![0; size * 2]);
+            let tiles = source
+                .tiles
+                .iter()
+                .map(|tile| Tile {
+                    x: tile.x,
+                    y: tile.y,
+                    data: tile
+                        .data
+                        .chunks_exact(pixel_size)
+                        .flat_map(|pixel| extract(pixel, channel))
+                        .collect(),
+                })
+                .filter(|tile| {
+                    tile.data
+                        .chunks_exact(empty.len())
+                        .any(|pixel| pixel != empty)
+                })
+                .collect::<Vec<_>>();
+            let data = TiledData {
+                header: TileHeader {
+                    tile_count: tiles.len() as u32,
+                    ..header
+                },
+                tiles,
+                default_pixel,
+            };
+
+            let uuid = Uuid::new_v4();
+            let filename = self.unused_filename(NodeKind::PaintLayer);
+            let mut attributes = attributes.clone();
+            set_attr(&mut attributes, "name", name);
+            set_attr(&mut attributes, "uuid", &uuid.braced().to_string());
+            set_attr(&mut attributes, "filename", &filename);
+            set_attr(&mut attributes, "colorspacename", &gray.to_string());
+            set_attr(&mut attributes, "compositeop", "normal");
+            set_attr(&mut attributes, "channelflags", "");
+            attributes.retain(|(key, _)| key != "channellockflags");
+            let node = self.new_node(&attributes)?;
+            self.store_new_layer(uuid, &filename, data)?;
+            self.children_mut(parent.as_ref())?
+                .insert(index + position, node);
+            created.push(uuid);
+        }
+        self.source.modified = true;
+        Ok(created)
+    }
+
+    /// Add a paint layer with the coloring of the colorize mask, so that the flat colors
+    /// can be used without Krita. Returns the UUID of the new layer.
+    ///
+    /// The new layer is placed right above the layer of the mask, and is named after
+    /// the mask. The coloring is copied as Krita stored it when the document was saved,
+    /// see [`KraFile::decode_colorize_mask()`]. The mask is kept.
+    pub fn flatten_colorize_mask(&mut self, id: &Uuid) -> Result<Uuid, EditError> {
+        let (layer_id, _) = locate(&self.layers, None, id).ok_or(EditError::UnknownNode(*id))?;
+        let mask = find(&self.layers, id).ok_or(EditError::UnknownNode(*id))?;
+        let (NodeType::ColorizeMask(_), Some(layer_id)) = (mask.node_type(), layer_id) else {
+            return Err(EditError::NotColorizeMask(*id));
+        };
+        let name = mask.name().to_owned();
+        let (parent, index) =
+            locate(&self.layers, None, &layer_id).ok_or(EditError::UnknownNode(layer_id))?;
+        let (data, (x, y), colorspace) = self.read_coloring(id)?;
+
+        let uuid = Uuid::new_v4();
+        let filename = self.unused_filename(NodeKind::PaintLayer);
+        let attributes: Vec<(String, String)> = [
+            ("name", name),
+            ("uuid", uuid.braced().to_string()),
+            ("filename", filename.clone()),
+            ("visible", "1".to_owned()),
+            ("locked", "0".to_owned()),
+            ("colorlabel", "0".to_owned()),
+            ("x", x.to_string()),
+            ("y", y.to_string()),
+            ("intimeline", "0".to_owned()),
+            ("nodetype", "paintlayer".to_owned()),
+            ("compositeop", "normal".to_owned()),
+            ("opacity", "255".to_owned()),
+            ("collapsed", "0".to_owned()),
+            ("colorspacename", colorspace.to_string()),
+            ("channelflags", String::new()),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_owned(), value))
+        .collect();
+        let node = self.new_node(&attributes)?;
+        self.store_new_layer(uuid, &filename, data)?;
+        self.children_mut(parent.as_ref())?.insert(index, node);
+        self.source.modified = true;
+        Ok(uuid)
+    }
+
+    /// Add a vector layer with the text above all other layers, like the text tool of Krita
+    /// makes. Returns the UUID of the new layer.
+    ///
+    /// Only plain text is written: one font, size and color for all of it.
+    pub fn add_text_layer(&mut self, text: &TextLayer) -> Result<Uuid, EditError> {
+        let uuid = Uuid::new_v4();
+        let filename = self.unused_filename(NodeKind::VectorLayer);
+        let attributes: Vec<(String, String)> = [
+            ("name", text.get_name().to_owned()),
+            ("uuid", uuid.braced().to_string()),
+            ("filename", filename.clone()),
+            ("visible", "1".to_owned()),
+            ("locked", "0".to_owned()),
+            ("colorlabel", "0".to_owned()),
+            ("x", "0".to_owned()),
+            ("y", "0".to_owned()),
+            ("intimeline", "0".to_owned()),
+            ("nodetype", "shapelayer".to_owned()),
+            ("compositeop", "normal".to_owned()),
+            ("opacity", "255".to_owned()),
+            ("collapsed", "0".to_owned()),
+            ("channelflags", String::new()),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_owned(), value))
+        .collect();
+        let node = self.new_node(&attributes)?;
+
+        let resolution = (self.meta.x_res(), self.meta.y_res());
+        let svg = text.to_svg(self.meta.width(), self.meta.height(), resolution);
+        let path = format!(
+            "{}.shapelayer/content.svg",
+            self.layer_entry_path(&filename)?
+        );
+        self.set_entry(path.clone(), svg.into_bytes());
+        self.node_entries.insert(uuid, vec![path]);
+        self.layers.insert(0, node);
+        self.source.modified = true;
+        Ok(uuid)
+    }
+
+    /// Add a paint layer with the image above all other layers, for example a logo on review
+    /// copies of artwork. Returns the UUID of the new layer.
+    ///
+    /// The image is blended normally, with the opacity of the layer. `preview.png` is not
+    /// changed, see [`KraFile::regenerate_preview()`].
+    #[cfg(feature = "image")]
+    pub fn add_watermark(
+        &mut self,
+        image: &image::RgbaImage,
+        options: &WatermarkOptions,
+    ) -> Result<Uuid, EditError> {
+        const TILE_SIZE: i32 = tile::TILE_SIZE as i32;
+        let (x, y) = options.position;
+        let (width, height) = image.dimensions();
+        // Pixels go into whole tiles
+        let (left, top) = (
+            x.div_euclid(TILE_SIZE) * TILE_SIZE,
+            y.div_euclid(TILE_SIZE) * TILE_SIZE,
+        );
+        let padded =
+            |start: i32, end: i32| ((end - start + TILE_SIZE - 1) / TILE_SIZE * TILE_SIZE) as usize;
+        let dense_width = padded(left, x + width as i32);
+        let dense_height = padded(top, y + height as i32);
+        let mut pixels = vec![0; dense_width * dense_height * 4];
+        for (column, row, pixel) in image.enumerate_pixels() {
+            let [red, green, blue, alpha] = pixel.0;
+            let start = ((y - top) as usize + row as usize) * dense_width
+                + (x - left) as usize
+                + column as usize;
+            pixels[start * 4..start * 4 + 4].copy_from_slice(&[blue, green, red, alpha]);
+        }
+        let header = TileHeader {
+            tile_width: TILE_SIZE as u32,
+            tile_height: TILE_SIZE as u32,
+            pixel_size: 4,
+            tile_count: 0,
+        };
+        let data = TiledData::from_dense(header, (left, top, dense_width as u32), &pixels, &[0; 4]);
+
+        let uuid = Uuid::new_v4();
+        let filename = self.unused_filename(NodeKind::PaintLayer);
+        let attributes: Vec<(String, String)> = [
+            ("name", options.name.clone()),
+            ("uuid", uuid.braced().to_string()),
+            ("filename", filename.clone()),
+            ("visible", "1".to_owned()),
+            ("locked", "0".to_owned()),
+            ("colorlabel", "0".to_owned()),
+            ("x", "0".to_owned()),
+            ("y", "0".to_owned()),
+            ("intimeline", "0".to_owned()),
+            ("nodetype", "paintlayer".to_owned()),
+            ("compositeop", "normal".to_owned()),
+            ("opacity", options.opacity.to_string()),
+            ("collapsed", "0".to_owned()),
+            ("colorspacename", Colorspace::RGBA.to_string()),
+            ("channelflags", String::new()),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_owned(), value))
+        .collect();
+        let node = self.new_node(&attributes)?;
+
+        if options.update_merged_image {
+            let mut merged = self.merged_image_decoded()?.to_rgba8();
+            let blend = Blend::new(crate::layer::CompositeOp::Normal)
+                .expect("normal blending is supported");
+            let opacity = options.opacity as f32 / u8::MAX as f32;
+            let normalized = |pixel: [u8; 4]| pixel.map(|value| value as f32 / u8::MAX as f32);
+            for (column, row, pixel) in image.enumerate_pixels() {
+                let (Ok(target_x), Ok(target_y)) = (
+                    u32::try_from(x + column as i32),
+                    u32::try_from(y + row as i32),
+                ) else {
+                    continue;
+                };
+                let Some(target) = merged.get_pixel_mut_checked(target_x, target_y) else {
+                    continue;
+                };
+                let mut values = normalized(target.0);
+                blend.apply(&mut values, &normalized(pixel.0), opacity);
+                target.0 = values.map(|value| (value * u8::MAX as f32).round() as u8);
+            }
+            let mut png = Vec::new();
+            merged.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+            self.set_entry("mergedimage.png".to_owned(), png);
+        }
+
+        self.store_new_layer(uuid, &filename, data)?;
+        self.layers.insert(0, node);
+        self.source.modified = true;
+        Ok(uuid)
+    }
+
+    // Stores pixels of a layer that was just made, registering its entries
+    fn store_new_layer(
+        &mut self,
+        uuid: Uuid,
+        filename: &str,
+        data: TiledData,
+    ) -> Result<(), EditError> {
+        let path = self.layer_entry_path(filename)?;
+        let mut entries = vec![path.clone()];
+        if let Some(pixel) = &data.default_pixel {
+            let default_path = format!("{}.defaultpixel", path);
+            self.set_entry(default_path.clone(), pixel.clone());
+            entries.push(default_path);
+        }
+        self.set_entry(path, data.write());
+        self.node_entries.insert(uuid, entries);
+        Ok(())
+    }
+
+    /// Set a custom property of the node, see [`Node::custom_properties()`].
+    ///
+    /// Keys can only have ASCII letters, digits, `-`, `_` and `.`, so that they can be
+    /// a part of attribute names.
+    pub fn set_custom_property(
+        &mut self,
+        id: &Uuid,
+        key: &str,
+        value: &str,
+    ) -> Result<(), EditError> {
+        if !is_valid_property_key(key) {
+            return Err(EditError::InvalidPropertyKey(key.to_owned()));
+        }
+        find_mut(&mut self.layers, id)
+            .ok_or(EditError::UnknownNode(*id))?
+            .set_custom_property(key, value);
+        self.source.modified = true;
+        Ok(())
+    }
+
+    /// Rename the node.
+    pub fn rename_node(&mut self, id: &Uuid, name: &str) -> Result<(), EditError> {
+        find_mut(&mut self.layers, id)
+            .ok_or(EditError::UnknownNode(*id))?
+            .set_name(name);
+        self.source.modified = true;
+        Ok(())
+    }
+
+    /// Replace matches of `pattern` in names of all nodes (layers and masks) with
+    /// `replacement`, which can refer to capture groups like [`Regex::replace_all()`] does.
+    /// Returns the nodes that were renamed, in document order.
+    pub fn rename_layers(&mut self, pattern: &Regex, replacement: &str) -> Vec<Uuid> {
+        let renames: Vec<(Uuid, String)> = self
+            .all_nodes()
+            .into_iter()
+            .filter_map(|node| match pattern.replace_all(node.name(), replacement) {
+                Cow::Owned(name) if name != node.name() => Some((node.uuid(), name)),
+                _ => None,
+            })
+            .collect();
+        for (uuid, name) in &renames {
+            self.rename_node(uuid, name)
+                .expect("the node was just found");
+        }
+        renames.into_iter().map(|(uuid, _)| uuid).collect()
+    }
+
+    /// Set a text field of the document information.
+    pub fn set_doc_info(&mut self, field: DocInfoField, value: &str) {
+        self.doc_info.set_field(field, value);
+        self.pending_entries.insert(
+            "documentinfo.xml".to_owned(),
+            self.doc_info.to_xml().into_bytes(),
+        );
+    }
+
+    /// Replace matches of `pattern` in all text fields of the document information with
+    /// `replacement`, like [`rename_layers()`](Self::rename_layers) does. Returns the fields
+    /// that were changed.
+    pub fn replace_in_doc_info(&mut self, pattern: &Regex, replacement: &str) -> Vec<DocInfoField> {
+        let changes: Vec<(DocInfoField, String)> = DocInfoField::ALL
+            .into_iter()
+            .filter_map(|field| {
+                let value = self.doc_info.field(field);
+                match pattern.replace_all(value, replacement) {
+                    Cow::Owned(new) if new != value => Some((field, new)),
+                    _ => None,
+                }
+            })
+            .collect();
+        for (field, value) in &changes {
+            self.set_doc_info(*field, value);
+        }
+        changes.into_iter().map(|(field, _)| field).collect()
+    }
+
+    /// Remove a custom property of the node, returning its value if it had one.
+    pub fn remove_custom_property(
+        &mut self,
+        id: &Uuid,
+        key: &str,
+    ) -> Result<Option<String>, EditError> {
+        let value = find_mut(&mut self.layers, id)
+            .ok_or(EditError::UnknownNode(*id))?
+            .remove_custom_property(key);
+        if value.is_some() {
+            self.source.modified = true;
+        }
+        Ok(value)
+    }
+
+    // Removes the node from the tree; its entries become orphaned
+    fn remove_node(&mut self, parent: Option<Uuid>, index: usize) -> Result<(), EditError> {
+        let node = self.children_mut(parent.as_ref())?.remove(index);
+        fn forget(file: &mut KraFile, node: &Node) {
+            file.files.remove(&node.uuid());
+            if let Some(entries) = file.node_entries.remove(&node.uuid()) {
+                file.orphaned_entries.extend(entries);
+            }
+            for child in node.children() {
+                forget(file, child);
+            }
+        }
+        forget(self, &node);
+        self.orphaned_entries.sort();
+        self.source.modified = true;
+        Ok(())
+    }
+
+    /// Crop the image to the rectangle, which can also be given as `(x, y, width, height)`.
+    ///
+    /// Pixels of paint layers outside of the rectangle are removed, and the rest are moved
+    /// along with the image. Other nodes are moved by changing their offsets, their contents
+    /// are not cropped. Offsets cannot be negative, so if any node would end up to the left
+    /// of or above the new image, nothing is changed.
+    ///
+    /// `mergedimage.png` and `preview.png` are not changed.
+    pub fn crop(&mut self, rect: impl Into<IRect>) -> Result<(), EditError> {
+        let IRect {
+            x,
+            y,
+            width,
+            height,
+        } = rect.into();
+        self.check_size(width, height)?;
+        let mut offsets = Vec::new();
+        for node in self.offset_nodes() {
+            let new_x = i32::try_from(node.x() as i64 - x as i64);
+            let new_y = i32::try_from(node.y() as i64 - y as i64);
+            match (new_x, new_y) {
+                (Ok(new_x @ 0..), Ok(new_y @ 0..)) => offsets.push((node.uuid(), new_x, new_y)),
+                _ => return Err(EditError::NegativeOffset(node.uuid())),
+            }
+        }
+
+        let mut changed = Vec::new();
+        for layer in self.paint_layers() {
+            let data = self.read_tiled_data(&layer.filename)?;
+            let pixel_size = data.header.pixel_size as usize;
+            let (left, top, data_width, data_height, pixels) =
+                data.to_dense(self.config.limits.max_pixels)?;
+            let (left, top) = (left + layer.x, top + layer.y);
+            let kept = IRect::new(left, top, data_width, data_height)
+                .intersection(&IRect::new(x, y, width, height))
+                .unwrap_or(IRect::new(x, y, 0, 0));
+            let (x0, y0) = (kept.x(), kept.y());
+            let (new_width, new_height) = (kept.width() as usize, kept.height() as usize);
+
+            let row_size = data_width as usize * pixel_size;
+            let mut cropped = Vec::with_capacity(new_width * new_height * pixel_size);
+            for row in 0..new_height {
+                let start = (y0 - top) as usize + row;
+                let start = start * row_size + (x0 - left) as usize * pixel_size;
+                cropped.extend_from_slice(&pixels[start..start + new_width * pixel_size]);
+            }
+            let rect = (x0 - x, y0 - y, new_width, new_height);
+            changed.push((layer, data.header, data.default_pixel, rect, cropped));
+        }
+
+        for (layer, header, default_pixel, rect, pixels) in changed {
+            self.store_pixels(&layer, header, default_pixel, rect, &pixels)?;
+        }
+        for (uuid, x, y) in offsets {
+            find_mut(&mut self.layers, &uuid)
+                .ok_or(EditError::UnknownNode(uuid))?
+                .set_offset(x, y);
+        }
+        self.resize(width, height);
+        Ok(())
+    }
+
+    /// Scale the image to `(width, height)`, resampling pixels of paint layers with the filter.
+    ///
+    /// Offsets of other nodes are scaled, their contents (like vector shapes) are not.
+    ///
+    /// `mergedimage.png` and `preview.png` are not changed.
+    pub fn scale(
+        &mut self,
+        (width, height): (u32, u32),
+        filter: ScaleFilter,
+    ) -> Result<(), EditError> {
+        self.check_size(width, height)?;
+        let factor = (
+            width as f64 / self.meta.width() as f64,
+            height as f64 / self.meta.height() as f64,
+        );
+        let offsets: Vec<(Uuid, i32, i32)> = self
+            .offset_nodes()
+            .into_iter()
+            .map(|node| {
+                let x = (node.x() as f64 * factor.0).round() as i32;
+                let y = (node.y() as f64 * factor.1).round() as i32;
+                (node.uuid(), x, y)
+            })
+            .collect();
+
+        let mut changed = Vec::new();
+        for layer in self.paint_layers() {
+            let data = self.read_tiled_data(&layer.filename)?;
+            let depth = layer.colorspace.depth();
+            let default_pixel = match &data.default_pixel {
+                Some(pixel) if pixel.len() == layer.colorspace.pixel_size() => pixel.clone(),
+                _ => vec![0; layer.colorspace.pixel_size()],
+            };
+            let default = normalize(&PixelBuffer::from_le_bytes(default_pixel, depth));
+            let (left, top, data_width, data_height, pixels) =
+                data.to_dense(self.config.limits.max_pixels)?;
+            let block = Block {
+                pixels: normalize(&PixelBuffer::from_le_bytes(pixels, depth)),
+                x: left + layer.x,
+                y: top + layer.y,
+                width: data_width as usize,
+                height: data_height as usize,
+                default: &default,
+            };
+            let (x, y, new_width, new_height, pixels) = resample::scale(block, factor, filter);
+            let pixels = denormalize(pixels, depth).to_le_bytes();
+            let rect = (x, y, new_width, new_height);
+            changed.push((layer, data.header, data.default_pixel, rect, pixels));
+        }
+
+        for (layer, header, default_pixel, rect, pixels) in changed {
+            self.store_pixels(&layer, header, default_pixel, rect, &pixels)?;
+        }
+        for (uuid, x, y) in offsets {
+            find_mut(&mut self.layers, &uuid)
+                .ok_or(EditError::UnknownNode(uuid))?
+                .set_offset(x, y);
+        }
+        self.resize(width, height);
+        Ok(())
+    }
+
+    fn check_size(&self, width: u32, height: u32) -> Result<(), EditError> {
+        if width == 0 || height == 0 {
+            return Err(EditError::InvalidSize { width, height });
+        }
+        let (max_width, max_height) = self.config.limits.max_dimensions;
+        if width > max_width || height > max_height {
+            return Err(LimitError::Dimensions {
+                width,
+                height,
+                max_width,
+                max_height,
+            }
+            .into());
+        }
+        let max_pixels = self.config.limits.max_pixels;
+        if width as u64 * height as u64 > max_pixels {
+            return Err(LimitError::Pixels(max_pixels).into());
+        }
+        Ok(())
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        self.meta.set_size(width, height);
+        self.source.size = Some((width, height));
+        self.source.modified = true;
+    }
+
+    fn paint_layers(&self) -> Vec<PaintLayer> {
+        self.all_nodes()
+            .into_iter()
+            .filter_map(|node| match node.node_type() {
+                NodeType::PaintLayer(props) => Some(PaintLayer {
+                    uuid: node.uuid(),
+                    filename: node.filename().to_owned(),
+                    x: node.x(),
+                    y: node.y(),
+                    colorspace: props.colorspace(),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // Nodes that are moved by their offsets. Offsets of groups do not move them.
+    fn offset_nodes(&self) -> Vec<&Node> {
+        self.all_nodes()
+            .into_iter()
+            .filter(|node| {
+                !matches!(
+                    node.node_type(),
+                    NodeType::PaintLayer(_) | NodeType::GroupLayer(_)
+                )
+            })
+            .collect()
+    }
+
+    // Replaces pixels of the paint layer with `pixels` at `(x, y, width, height)` in image
+    // coordinates. The offset of the layer becomes zero.
+    fn store_pixels(
+        &mut self,
+        layer: &PaintLayer,
+        header: TileHeader,
+        default_pixel: Option<Vec<u8>>,
+        (x, y, width, height): (i32, i32, usize, usize),
+        pixels: &[u8],
+    ) -> Result<(), EditError> {
+        let pixel_size = header.pixel_size as usize;
+        let default_pixel = default_pixel
+            .filter(|pixel| pixel.len() == pixel_size)
+            .unwrap_or_else(|| vec![0; pixel_size]);
+        let (tile_width, tile_height) = (header.tile_width as i32, header.tile_height as i32);
+        let left = x.div_euclid(tile_width) * tile_width;
+        let top = y.div_euclid(tile_height) * tile_height;
+        let right = (x + width as i32 + tile_width - 1).div_euclid(tile_width) * tile_width;
+        let bottom = (y + height as i32 + tile_height - 1).div_euclid(tile_height) * tile_height;
+        let aligned_width = match width * height {
+            0 => 0,
+            _ => (right - left) as usize,
+        };
+        let aligned_height = (bottom - top) as usize;
+
+        let mut aligned = default_pixel.repeat(aligned_width * aligned_height);
+        for (row, values) in pixels.chunks_exact((width * pixel_size).max(1)).enumerate() {
+            let start = ((y - top) as usize + row) * aligned_width + (x - left) as usize;
+            aligned[start * pixel_size..start * pixel_size + values.len()].copy_from_slice(values);
+        }
+        let tiles = TiledData::from_dense(
+            header,
+            (left, top, aligned_width as u32),
+            &aligned,
+            &default_pixel,
+        );
+        let path = self.layer_entry_path(&layer.filename)?;
+        self.set_entry(path, tiles.write());
+        find_mut(&mut self.layers, &layer.uuid)
+            .ok_or(EditError::UnknownNode(layer.uuid))?
+            .set_offset(0, 0);
+        Ok(())
+    }
+
+    // Parses a node from its attributes, registering it in `files`
+    fn new_node(&mut self, attributes: &[(String, String)]) -> Result<Node, EditError> {
+        let mut xml = String::from("<layer");
+        write_attributes(attributes, &mut xml);
+        xml.push_str("/>");
+        let mut reader = XmlReader::from_str(&xml);
+        let mut ctx = ParseContext {
+            config: &self.config,
+            files: std::mem::take(&mut self.files),
+            unknown_attributes: Vec::new(),
+            duplicate_uuids: Vec::new(),
+            group_depth: 0,
+        };
+        let node = parse_layer(&mut reader, &mut ctx);
+        self.files = ctx.files;
+        match node {
+            Ok(Some(node)) => Ok(node),
+            Ok(None) => Err(EditError::InvalidNode("its UUID is taken".to_owned())),
+            Err(MetadataErrorReason::LimitExceeded(err)) => Err(err.into()),
+            Err(err) => Err(EditError::InvalidNode(err.to_string())),
+        }
+    }
+
+    /// Filename for a new node of this kind, given the way Krita gives them: `layerN` for
+    /// layers and `maskN` for masks. As Krita counts up from the highest number it has seen,
+    /// `N` is above the numbers of every node and entry of the document, which keeps names
+    /// unique even after Krita adds nodes of its own.
+    pub fn unused_filename(&self, kind: NodeKind) -> String {
+        let prefix = match kind.is_mask() {
+            true => "mask",
+            false => "layer",
+        };
+        let layers_dir = format!("{}/layers/", self.meta.name());
+        let number = |name: &str| -> Option<u64> { name.strip_prefix(prefix)?.parse().ok() };
+        let highest = self
+            .node_entries
+            .values()
+            .flatten()
+            .chain(&self.orphaned_entries)
+            .chain(self.pending_entries.keys())
+            .filter_map(|entry| entry.strip_prefix(&layers_dir))
+            .filter_map(|entry| entry.split(['.', '/']).next())
+            .chain(self.all_nodes().into_iter().map(Node::filename))
+            .filter_map(number)
+            .max()
+            .unwrap_or(0);
+        format!("{}{}", prefix, highest + 1)
+    }
+
+    // Nodes under the parent, or the top-level layers
+    fn children_mut(&mut self, parent: Option<&Uuid>) -> Result<&mut Vec<Node>, EditError> {
+        match parent {
+            None => Ok(&mut self.layers),
+            Some(parent) => find_mut(&mut self.layers, parent)
+                .and_then(Node::children_mut)
+                .ok_or(EditError::UnknownNode(*parent)),
+        }
+    }
+
+    // maindoc.xml with <layers> made from the current tree, if it was changed
+    pub(crate) fn rebuilt_maindoc(&self) -> Result<Option<String>, XmlError> {
+        if !self.source.modified {
+            return Ok(None);
+        }
+        let MaindocSource { text, layers, .. } = &self.source;
+        let mut out = String::with_capacity(text.len());
+        match self.source.size {
+            Some((width, height)) => out.push_str(&resized(&text[..layers.start], width, height)?),
+            None => out.push_str(&text[..layers.start]),
+        }
+        match self.layers.is_empty() {
+            true => out.push_str("\n  <layers/>"),
+            false => {
+                out.push_str("\n  <layers>\n");
+                write_nodes(&self.layers, 3, &mut out);
+                out.push_str("  </layers>");
+            }
+        }
+        out.push_str(&text[layers.end..]);
+        Ok(Some(out))
+    }
+}
+
+// Parent and position of the node
+fn locate(nodes: &[Node], parent: Option<Uuid>, id: &Uuid) -> Option<(Option<Uuid>, usize)> {
+    nodes
+        .iter()
+        .enumerate()
+        .find_map(|(index, node)| match node.uuid() == *id {
+            true => Some((parent, index)),
+            false => locate(node.children(), Some(node.uuid()), id),
+        })
+}
+
+pub(crate) fn find<'a>(nodes: &'a [Node], id: &Uuid) -> Option<&'a Node> {
+    nodes.iter().find_map(|node| match node.uuid() == *id {
+        true => Some(node),
+        false => find(node.children(), id),
+    })
+}
+
+fn find_mut<'a>(nodes: &'a mut [Node], id: &Uuid) -> Option<&'a mut Node> {
+    for node in nodes {
+        if node.uuid() == *id {
+            return Some(node);
+        }
+        if let Some(found) = node
+            .children_mut()
+            .and_then(|children| find_mut(children, id))
+        {
+            return Some(found);
+        }
+    }
+    None
+}
+
+// How many groups contain the node, counting the node itself if it is a group
+fn group_depth(nodes: &[Node], id: &Uuid) -> Option<usize> {
+    nodes.iter().find_map(|node| {
+        let own = matches!(node.node_type(), NodeType::GroupLayer(_)) as usize;
+        match node.uuid() == *id {
+            true => Some(own),
+            false => group_depth(node.children(), id).map(|depth| depth + own),
+        }
+    })
+}
+
+// How many levels of groups the node has, including itself
+fn subtree_depth(node: &Node) -> usize {
+    match node.node_type() {
+        NodeType::GroupLayer(props) => {
+            1 + props.layers().iter().map(subtree_depth).max().unwrap_or(0)
+        }
+        _ => 0,
+    }
+}
+
+// Nodes as Krita writes them: indented by one space per level, and with empty lists of
+// children written as empty elements
+fn write_nodes(nodes: &[Node], depth: usize, out: &mut String) {
+    let indent = " ".repeat(depth);
+    for node in nodes {
+        let element = match node.kind().is_mask() {
+            true => "mask",
+            false => "layer",
+        };
+        out.push_str(&format!("{indent}<{element}"));
+        write_attributes(node.attributes(), out);
+        let (list, children) = match node.node_type() {
+            NodeType::GroupLayer(props) => ("layers", props.layers()),
+            _ => ("masks", node.children()),
+        };
+        let unparsed = node.unparsed_elements();
+        if list == "masks" && children.is_empty() && unparsed.is_empty() {
+            out.push_str("/>\n");
+            continue;
+        }
+        out.push_str(">\n");
+        // Groups always have <layers>, other layers only have <masks> if there are any
+        match (children.is_empty(), list) {
+            (true, "masks") => {}
+            (true, _) => out.push_str(&format!("{indent} <{list}/>\n")),
+            (false, _) => {
+                out.push_str(&format!("{indent} <{list}>\n"));
+                write_nodes(children, depth + 2, out);
+                out.push_str(&format!("{indent} </{list}>\n"));
+            }
+        }
+        for raw in unparsed {
+            out.push_str(&format!("{indent} {raw}\n"));
+        }
+        out.push_str(&format!("{indent}</{element}>\n"));
+    }
+}
+
+// The text with the size written into its <IMAGE> tag
+fn resized(text: &str, width: u32, height: u32) -> Result<String, XmlError> {
+    let mut reader = XmlReader::from_str(text);
+    loop {
+        let start = reader.buffer_position();
+        match reader.read_event() {
+            Ok(Event::Start(tag)) if tag.name().as_ref() == b"IMAGE" => {
+                let mut attributes = event_get_all_attrs(&tag)?;
+                set_attr(&mut attributes, "width", &width.to_string());
+                set_attr(&mut attributes, "height", &height.to_string());
+                let mut out = text[..start].to_owned();
+                out.push_str("<IMAGE");
+                write_attributes(&attributes, &mut out);
+                out.push('>');
+                out.push_str(&text[reader.buffer_position()..]);
+                return Ok(out);
+            }
+            Ok(Event::Eof) => {
+                return Err(XmlError::EventError("<IMAGE>", "end of file".to_owned()))
+            }
+            Err(err) => return Err(err.into()),
+            _ => {}
+        }
+    }
+}
+
+fn write_attributes(attributes: &[(String, String)], out: &mut String) {
+    for (key, value) in attributes {
+        out.push_str(&format!(" {}=\"{}\"", key, escape_attr(value)));
+    }
+}
+
+// Attribute value escaped like Qt's DOM does it for Krita: apostrophes are kept, `>` is
+// only escaped after `]]`, and whitespace other than spaces is written as references so
+// that it survives attribute value normalization
+fn escape_attr(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '<' => out.push_str("&lt;"),
+            '"' => out.push_str("&quot;"),
+            '&' => out.push_str("&amp;"),
+            '>' if out.ends_with("]]") => out.push_str("&gt;"),
+            '\n' => out.push_str("&#xa;"),
+            '\r' => out.push_str("&#xd;"),
+            '\t' => out.push_str("&#x9;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// Names of color channels, and their positions in a pixel
+fn color_channels(colorspace: Colorspace) -> Vec<(&'static str, usize)> {
+    let names: &[&str] = match colorspace.model() {
+        ColorModel::Rgb => &["Red", "Green", "Blue"],
+        ColorModel::Gray => &["Gray"],
+        ColorModel::Cmyk => &["Cyan", "Magenta", "Yellow", "Black"],
+        ColorModel::Lab => &["L*", "a*", "b*"],
+        ColorModel::Xyz => &["X", "Y", "Z"],
+        ColorModel::YCbCr => &["Y", "Cb", "Cr"],
+    };
+    // Integer RGB is stored as BGRA
+    let reversed = stores_bgra(colorspace);
+    names
+        .iter()
+        .enumerate()
+        .map(|(channel, name)| match reversed {
+            true => (*name, 2 - channel),
+            false => (*name, channel),
+        })
+        .collect()
+}
+
+fn gray_colorspace(depth: ChannelDepth) -> Colorspace {
+    match depth {
+        ChannelDepth::U8 => Colorspace::GRAYA,
+        ChannelDepth::U16 => Colorspace::GRAYAU16,
+        ChannelDepth::F16 => Colorspace::GRAYAF16,
+        ChannelDepth::F32 => Colorspace::GRAYAF32,
+    }
+}
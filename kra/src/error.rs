@@ -1,51 +1,211 @@
 //! Error types for the library.
 
-use std::{io, path::PathBuf, string::FromUtf8Error};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    string::FromUtf8Error,
+};
 
+use crate::{
+    id::Uuid,
+    layer::{CompositeOp, NodeKind},
+    Colorspace,
+};
 use quick_xml::Reader;
 use thiserror::Error;
 
+/// Colorspace name was not recognised.
 #[derive(Error, Debug, PartialEq, Eq, Clone, Hash)]
 #[error("unknown colorspace: {0}")]
 pub struct UnknownColorspace(pub(crate) String);
 
+/// UUID could not be parsed.
 #[derive(Error, Debug, PartialEq, Eq, Clone, Hash)]
 #[error("failed to parse UUID: {0}")]
-pub struct ParseUuidError(uuid::Error);
+pub struct ParseUuidError(UuidError);
 
+// Error of the type that Uuid is
+#[cfg(feature = "uuid")]
+type UuidError = uuid::Error;
+#[cfg(not(feature = "uuid"))]
+type UuidError = InvalidUuid;
+
+/// Text is not a UUID, see [`UuidString`](crate::id::UuidString).
+#[derive(Error, Debug, PartialEq, Eq, Clone, Hash)]
+#[error("invalid UUID: {0:?}")]
+pub struct InvalidUuid(pub(crate) String);
+
+/// Text is not a BCP 47 language tag, see [`LanguageTag`](crate::metadata::LanguageTag).
+#[derive(Error, Debug, PartialEq, Eq, Clone, Hash)]
+#[error("invalid language tag: {0:?}")]
+pub struct InvalidLanguageTag(pub(crate) String);
+
+/// Composition operator was not recognised.
 #[derive(Error, Debug, PartialEq, Eq, Clone, Hash)]
 #[error("unknown compositeop: {0}")]
 pub struct UnknownCompositeOp(pub(crate) String);
 
+/// Node type was not recognised.
 #[derive(Error, Debug, PartialEq, Eq, Clone, Hash)]
 #[error("unknown layer type: {0}")]
 pub struct UnknownLayerType(pub(crate) String);
 
+/// A layer was found where only masks are allowed.
 #[derive(Error, Debug, PartialEq, Eq, Clone, Hash)]
 #[error("expected a mask, got: {0}")]
 pub struct MaskExpected(pub(crate) String);
 
+/// Node has an attribute that is not read into the model,
+/// see [`UnknownAttributes`](crate::config::UnknownAttributes).
+#[derive(Error, Debug, PartialEq, Eq, Clone, Hash)]
+#[error("unknown attribute {name} of {kind} {uuid}")]
+pub struct UnknownAttribute {
+    pub(crate) uuid: Uuid,
+    pub(crate) kind: NodeKind,
+    pub(crate) name: String,
+}
+
+impl UnknownAttribute {
+    /// UUID of the node.
+    pub fn uuid(&self) -> &Uuid {
+        &self.uuid
+    }
+
+    /// Kind of the node.
+    pub fn kind(&self) -> NodeKind {
+        self.kind
+    }
+
+    /// Name of the attribute.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// `DOCTYPE` of an XML entry is not the one Krita writes, even after normalization,
+/// see [`DoctypeCheck`](crate::config::DoctypeCheck).
+#[derive(Error, Debug, PartialEq, Eq, Clone, Hash)]
+#[error("unexpected DOCTYPE of {entry}: expected {expected}, got {found}")]
+pub struct DoctypeMismatch {
+    pub(crate) entry: &'static str,
+    pub(crate) expected: &'static str,
+    pub(crate) found: String,
+}
+
+impl DoctypeMismatch {
+    /// Name of the entry, like `maindoc.xml`.
+    pub fn entry(&self) -> &str {
+        self.entry
+    }
+
+    /// `DOCTYPE` that Krita writes.
+    pub fn expected(&self) -> &str {
+        self.expected
+    }
+
+    /// `DOCTYPE` of the entry.
+    pub fn found(&self) -> &str {
+        &self.found
+    }
+}
+
+/// `mimetype` entry of the archive is missing or is not the one Krita writes,
+/// see [`MimetypeCheck`](crate::config::MimetypeCheck).
+#[derive(Error, Debug, PartialEq, Eq, Clone, Hash)]
+#[error("mimetype not recognised: {}", .found.as_deref().unwrap_or("no entry"))]
+pub struct UnexpectedMimetype {
+    pub(crate) found: Option<String>,
+}
+
+impl UnexpectedMimetype {
+    /// Contents of the entry, with invalid UTF-8 replaced. `None` if there is no entry.
+    pub fn found(&self) -> Option<&str> {
+        self.found.as_deref()
+    }
+}
+
+/// Invalid UTF-8 of an XML entry was replaced,
+/// see [`InvalidUtf8`](crate::config::InvalidUtf8).
+#[derive(Error, Debug, PartialEq, Eq, Clone, Hash)]
+#[error("invalid UTF-8 in {entry} was replaced")]
+pub struct ReplacedUtf8 {
+    pub(crate) entry: &'static str,
+    pub(crate) nodes: Vec<Uuid>,
+}
+
+impl ReplacedUtf8 {
+    /// Name of the entry, like `maindoc.xml`.
+    pub fn entry(&self) -> &str {
+        self.entry
+    }
+
+    /// Nodes whose names had invalid sequences, which now have U+FFFD in their place.
+    pub fn nodes(&self) -> &[Uuid] {
+        &self.nodes
+    }
+}
+
+/// UUID of a node is used by a node before it,
+/// see [`DuplicateUuids`](crate::config::DuplicateUuids).
+#[derive(Error, Debug, PartialEq, Eq, Clone, Hash)]
+#[error("UUID {uuid} is used by more than one node")]
+pub struct DuplicateUuid {
+    pub(crate) uuid: Uuid,
+    pub(crate) replacement: Option<Uuid>,
+}
+
+impl DuplicateUuid {
+    /// UUID that is used more than once.
+    pub fn uuid(&self) -> &Uuid {
+        &self.uuid
+    }
+
+    /// New UUID of the node, or `None` if it was left out.
+    pub fn replacement(&self) -> Option<&Uuid> {
+        self.replacement.as_ref()
+    }
+}
+
+/// Name template could not be parsed.
+#[derive(Error, Debug, PartialEq, Eq, Clone, Hash)]
+pub enum TemplateError {
+    /// Token inside braces was not recognised.
+    #[error("unknown template token: {0}")]
+    UnknownToken(String),
+
+    /// A brace is not closed, or a closing brace was not opened.
+    #[error("unmatched brace in template")]
+    UnmatchedBrace,
+
+    /// Format of the token is not supported.
+    #[error("invalid token format: {0}")]
+    InvalidFormat(String),
+}
+
+/// Errors that can be encountered while reading XML.
 #[derive(Debug, Clone, Error)]
 pub enum XmlError {
-    // Error used when we check some property
+    /// Error used when we check some property.
     #[error("assertion about XML metadata failed: expected {0}, got {1}")]
     AssertionFailed(&'static str, String),
 
-    // Error when XML cannot be interpreted
+    /// Error when XML cannot be interpreted.
     #[error("could not parse XML")]
     ParsingError(#[from] quick_xml::Error),
 
-    // Error when XML can be parsed but is incorrect
+    /// Error when XML can be parsed but is incorrect.
     #[error("unexpected XML event: expected {0}, got {1}")]
     EventError(&'static str, String),
 
+    /// Attribute or value is missing.
     #[error("missing XML value: {0}")]
     MissingValue(String),
 
-    // Error when XML is valid but the value contained (text or attribute) is not
+    /// Error when XML is valid but the value contained (text or attribute) is not.
     #[error("could not interpret XML value: {0}")]
     ValueError(String),
 
+    /// Text is not valid UTF-8.
     #[error("could not interpret string as utf-8: {0}")]
     EncodingError(#[from] FromUtf8Error),
 }
@@ -62,11 +222,20 @@ pub(crate) enum MetadataErrorReason {
     #[error(transparent)]
     MaskExpected(#[from] MaskExpected),
 
+    #[error(transparent)]
+    UnknownAttribute(#[from] UnknownAttribute),
+
+    #[error(transparent)]
+    DuplicateUuid(#[from] DuplicateUuid),
+
     #[error(transparent)]
     ParseUuidError(#[from] ParseUuidError),
 
     #[error(transparent)]
     XmlError(#[from] XmlError),
+
+    #[error(transparent)]
+    LimitExceeded(#[from] LimitError),
 }
 
 impl From<quick_xml::Error> for MetadataErrorReason {
@@ -81,46 +250,627 @@ impl From<FromUtf8Error> for MetadataErrorReason {
     }
 }
 
-impl From<uuid::Error> for MetadataErrorReason {
-    fn from(value: uuid::Error) -> Self {
+impl From<UuidError> for MetadataErrorReason {
+    fn from(value: UuidError) -> Self {
         MetadataErrorReason::ParseUuidError(ParseUuidError(value))
     }
 }
 
 impl MetadataErrorReason {
-    // Fills out MetadataError with the given reason and location
-    pub(crate) fn to_metadata_error(self, file: PathBuf, reader: &Reader<&[u8]>) -> MetadataError {
+    // Fills out MetadataError with the given reason and the location of the reader
+    // in `text`, which the reader reads
+    #[allow(clippy::wrong_self_convention)]
+    pub(crate) fn to_metadata_error(
+        self,
+        file: PathBuf,
+        text: &str,
+        reader: &Reader<&[u8]>,
+    ) -> MetadataError {
+        let byte_offset = reader.buffer_position();
+        let (line, column) = line_and_column(text, byte_offset);
         MetadataError {
             file,
-            buffer_pos: reader.buffer_position(),
+            byte_offset,
+            line,
+            column,
             error: self,
         }
     }
+
+    // Same as to_metadata_error(), but keeps exceeded limits as they are
+    #[allow(clippy::wrong_self_convention)]
+    pub(crate) fn to_read_error(
+        self,
+        file: PathBuf,
+        text: &str,
+        reader: &Reader<&[u8]>,
+    ) -> ReadKraError {
+        match self {
+            MetadataErrorReason::LimitExceeded(err) => ReadKraError::LimitExceeded(err),
+            other => other.to_metadata_error(file, text, reader).into(),
+        }
+    }
+}
+
+// 1-based line and column of the byte at `offset`, with columns counted in characters.
+// An offset inside of a character counts as that character, and one past the end of
+// the text as the position after its last character.
+fn line_and_column(text: &str, offset: usize) -> (usize, usize) {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    let line = before.matches('\n').count() + 1;
+    (line, before[line_start..].chars().count() + 1)
 }
 
-// Error that was thrown while parsing metadata, along with its location
+/// Error that was thrown while parsing metadata, along with its location.
+///
+/// The location is counted from the start of the XML, after the byte order mark or
+/// whitespace that may come before it.
 #[derive(Error, Debug)]
-#[error("{file} at {buffer_pos}: {error}")]
+#[error("{}:{line}:{column}: {error}", file.display())]
 pub struct MetadataError {
     //TODO: could be static? Or could be reused for parsing files in general, then
     // it'll have to be nonstatic
     file: PathBuf,
-    buffer_pos: usize,
+    byte_offset: usize,
+    line: usize,
+    column: usize,
     error: MetadataErrorReason,
 }
 
+impl MetadataError {
+    /// Entry that could not be parsed, like `maindoc.xml`.
+    pub fn file(&self) -> &Path {
+        &self.file
+    }
+
+    /// Line where parsing stopped, starting from 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Column where parsing stopped, in characters (not bytes), starting from 1.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    /// Position where parsing stopped, in bytes from the start of the XML.
+    pub fn byte_offset(&self) -> usize {
+        self.byte_offset
+    }
+}
+
 /// Errors that can be encountered while opening the file.
 #[derive(Error, Debug)]
 pub enum ReadKraError {
+    /// File could not be read.
     #[error(transparent)]
     FileError(#[from] io::Error),
 
+    /// File is not a valid zip archive.
     #[error(transparent)]
     ZipError(#[from] zip::result::ZipError),
 
+    /// `mimetype` entry does not match the one used by Krita.
     #[error("mimetype not recognised")]
     MimetypeMismatch,
 
+    /// An entry of the archive is missing or could not be read.
+    #[error("could not read entry {name}: {source}")]
+    Entry {
+        /// Name of the entry, like `maindoc.xml`.
+        name: String,
+        /// What went wrong.
+        source: zip::result::ZipError,
+    },
+
+    /// Metadata could not be parsed.
+    #[error(transparent)]
+    MetadataError(#[from] Box<MetadataError>),
+
+    /// The image is empty, as its width or height is 0.
+    #[error("invalid image size {width}x{height}")]
+    InvalidSize {
+        /// Width of the image.
+        width: u32,
+        /// Height of the image.
+        height: u32,
+    },
+
+    /// A limit set in the configuration was exceeded.
+    #[error(transparent)]
+    LimitExceeded(#[from] LimitError),
+}
+
+// Boxed, as the location makes it the largest of the errors
+impl From<MetadataError> for ReadKraError {
+    fn from(value: MetadataError) -> Self {
+        ReadKraError::MetadataError(Box::new(value))
+    }
+}
+
+/// Errors that can be encountered while opening a resource bundle.
+#[derive(Error, Debug)]
+pub enum ReadBundleError {
+    /// File could not be read.
+    #[error(transparent)]
+    FileError(#[from] io::Error),
+
+    /// File is not a valid zip archive, or an entry is missing.
+    #[error(transparent)]
+    ZipError(#[from] zip::result::ZipError),
+
+    /// `mimetype` entry does not match the one used by Krita for bundles.
+    #[error("mimetype not recognised")]
+    MimetypeMismatch,
+
+    /// `meta.xml` or the manifest could not be parsed.
+    #[error(transparent)]
+    XmlError(#[from] XmlError),
+
+    /// A limit on sizes of entries was exceeded.
+    #[error(transparent)]
+    LimitExceeded(#[from] LimitError),
+}
+
+/// Errors that can be encountered while reading a brush preset.
+#[derive(Error, Debug)]
+pub enum PresetError {
+    /// File could not be read, or a text chunk could not be decompressed.
+    #[error(transparent)]
+    FileError(#[from] io::Error),
+
+    /// Entry of the document could not be read.
     #[error(transparent)]
-    MetadataError(#[from] MetadataError),
+    DataError(#[from] DataError),
+
+    /// Data is not a valid PNG image.
+    #[error("malformed PNG data: {0}")]
+    MalformedPng(String),
+
+    /// The image does not have the settings of a preset.
+    #[error("no preset settings in the image")]
+    MissingPreset,
+
+    /// Settings of the preset could not be parsed.
+    #[error(transparent)]
+    XmlError(#[from] XmlError),
+}
+
+/// Errors that can be encountered while reading node data.
+#[derive(Error, Debug)]
+pub enum DataError {
+    /// Entry could not be read.
+    #[error(transparent)]
+    FileError(#[from] io::Error),
+
+    /// Entry could not be found or decompressed.
+    #[error(transparent)]
+    ZipError(#[from] zip::result::ZipError),
+
+    /// An entry of the archive is missing or could not be read.
+    #[error("could not read entry {name}: {source}")]
+    Entry {
+        /// Name of the entry, like `layers/layer2`.
+        name: String,
+        /// What went wrong.
+        source: zip::result::ZipError,
+    },
+
+    /// The archive is not available anymore.
+    #[error("archive is not available")]
+    NoArchive,
+
+    /// The archive cannot be opened again, to be read from several threads.
+    #[error("archive cannot be opened again")]
+    NotReopenable,
+
+    /// There is no node with this UUID.
+    #[error("node not found: {0}")]
+    UnknownNode(Uuid),
+
+    /// The node does not store pixel data.
+    #[error("node does not have pixel data: {0}")]
+    NoPixelData(Uuid),
+
+    /// The node is visible but cannot be composited yet, like filter layers, masks other
+    /// than selection masks, or blend modes that are not separable.
+    #[cfg(feature = "render")]
+    #[error("node cannot be composited: {0}")]
+    CannotComposite(Uuid),
+
+    /// Sources of file layers cannot be found, because it is not known which directory
+    /// they are relative to, see [`KraFile::set_base_dir()`](crate::KraFile::set_base_dir).
+    #[error("directory of linked files is not known")]
+    NoBaseDir,
+
+    /// A document that a file layer links to could not be read.
+    #[error("linked document {} could not be read", .0.display())]
+    LinkedDocument(PathBuf, #[source] Box<ReadKraError>),
+
+    /// Documents link to each other through file layers in a cycle.
+    #[error("file layers link to {} in a cycle", .0.display())]
+    LinkCycle(PathBuf),
+
+    /// A limit set in the configuration was exceeded.
+    #[error(transparent)]
+    LimitExceeded(#[from] LimitError),
+
+    /// Path to the data points outside of where it is allowed to.
+    #[error(transparent)]
+    PathTraversal(#[from] PathTraversalError),
+
+    /// Pixels cannot be converted between these colorspaces.
+    #[error("cannot convert pixels from {0} to {1}")]
+    UnsupportedConversion(Colorspace, Colorspace),
+
+    /// Tile data does not follow the expected format.
+    #[error("malformed tile data: expected {0}, got {1}")]
+    MalformedTiles(&'static str, String),
+
+    /// Pixels of this width and height cannot be held in memory.
+    #[error("{0}x{1} pixels are too many to hold in memory")]
+    TooLarge(u64, u64),
+
+    /// XML data of an entry could not be read.
+    #[error(transparent)]
+    XmlError(#[from] XmlError),
+
+    /// Image could not be decoded.
+    #[cfg(feature = "image")]
+    #[error(transparent)]
+    ImageError(#[from] image::ImageError),
+}
+
+/// Errors that can be encountered while writing the file.
+#[derive(Error, Debug)]
+pub enum WriteError {
+    /// Output could not be written.
+    #[error(transparent)]
+    FileError(#[from] io::Error),
+
+    /// Entry could not be copied or written.
+    #[error(transparent)]
+    ZipError(#[from] zip::result::ZipError),
+
+    /// Data from the original archive could not be read.
+    #[error(transparent)]
+    DataError(#[from] DataError),
+
+    /// maindoc.xml could not be rebuilt from the changed node tree.
+    #[error("could not rebuild maindoc.xml: {0}")]
+    MaindocError(#[from] XmlError),
+
+    /// The directory is neither empty nor holds a document.
+    #[error("directory is not empty and does not hold a document: {}", .0.display())]
+    NotADocument(PathBuf),
+
+    /// Image could not be encoded.
+    #[cfg(feature = "image")]
+    #[error(transparent)]
+    ImageError(#[from] image::ImageError),
+}
+
+/// Errors that can be encountered while converting between archives and directories.
+#[derive(Error, Debug)]
+pub enum PackError {
+    /// Output could not be created.
+    #[error(transparent)]
+    FileError(#[from] io::Error),
+
+    /// The document could not be read.
+    #[error(transparent)]
+    ReadError(#[from] ReadKraError),
+
+    /// The document could not be written.
+    #[error(transparent)]
+    WriteError(#[from] WriteError),
+}
+
+/// Errors that can be encountered while watching documents for changes.
+#[cfg(feature = "notify")]
+#[derive(Error, Debug)]
+pub enum WatchError {
+    /// The document could not be read.
+    #[error(transparent)]
+    ReadError(#[from] ReadKraError),
+
+    /// Changes of the file could not be watched.
+    #[error(transparent)]
+    NotifyError(#[from] notify::Error),
+}
+
+/// Errors that can be encountered while packing layers into an atlas.
+#[derive(Error, Debug)]
+pub enum AtlasError {
+    /// Pixels of a layer could not be read.
+    #[error(transparent)]
+    DataError(#[from] DataError),
+
+    /// Trimmed pixels of the layer do not fit into a page of the atlas.
+    #[error("layer does not fit into a page of the atlas: {0}")]
+    TooLarge(Uuid),
+}
+
+/// Errors that can be encountered while exporting a document of an export job.
+#[cfg(feature = "image")]
+#[derive(Error, Debug)]
+pub enum ExportError {
+    /// The document could not be read.
+    #[error(transparent)]
+    ReadError(#[from] ReadKraError),
+
+    /// Pixels of a layer could not be read.
+    #[error(transparent)]
+    DataError(#[from] DataError),
+
+    /// Output could not be written.
+    #[error(transparent)]
+    FileError(#[from] io::Error),
+
+    /// Image could not be encoded.
+    #[error(transparent)]
+    ImageError(#[from] image::ImageError),
+}
+
+/// Errors that can be encountered while making a document out of a sequence of images.
+#[cfg(feature = "image")]
+#[derive(Error, Debug)]
+pub enum SequenceError {
+    /// No images were given.
+    #[error("no frames were given")]
+    NoFrames,
+
+    /// The frame at this index has another size than the first one.
+    #[error("frame {0} has another size than the first one")]
+    SizeMismatch(usize),
+
+    /// There is no number in the name of this file.
+    #[error("file name does not have a frame number: {}", .0.display())]
+    Unnumbered(PathBuf),
+
+    /// Image could not be read or encoded.
+    #[error(transparent)]
+    ImageError(#[from] image::ImageError),
+
+    /// Entries of the document could not be written.
+    #[error(transparent)]
+    FileError(#[from] io::Error),
+
+    /// The document could not be put together.
+    #[error(transparent)]
+    ZipError(#[from] zip::result::ZipError),
+
+    /// The document that was put together could not be read.
+    #[error(transparent)]
+    ReadError(#[from] ReadKraError),
+}
+
+/// Errors that can be encountered while making a document from a template,
+/// see [`KraFile::from_template()`](crate::KraFile::from_template).
+#[derive(Error, Debug)]
+pub enum FromTemplateError {
+    /// Entries of the template could not be read.
+    #[error(transparent)]
+    DataError(#[from] DataError),
+
+    /// maindoc.xml could not be rebuilt from the node tree of the template.
+    #[error("could not rebuild maindoc.xml: {0}")]
+    MaindocError(#[from] XmlError),
+
+    /// Entries of the document could not be written.
+    #[error(transparent)]
+    FileError(#[from] io::Error),
+
+    /// The document could not be put together.
+    #[error(transparent)]
+    ZipError(#[from] zip::result::ZipError),
+
+    /// The document that was put together could not be read.
+    #[error(transparent)]
+    ReadError(#[from] ReadKraError),
+}
+
+/// Errors that can be encountered while loading lint rules.
+#[cfg(feature = "lint-config")]
+#[derive(Error, Debug)]
+pub enum LintConfigError {
+    /// File could not be read.
+    #[error(transparent)]
+    FileError(#[from] io::Error),
+
+    /// TOML text could not be parsed.
+    #[error(transparent)]
+    TomlError(#[from] toml::de::Error),
+
+    /// JSON text could not be parsed.
+    #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
+
+    /// Pattern of layer names is not a valid regular expression.
+    #[error(transparent)]
+    InvalidPattern(#[from] regex::Error),
+
+    /// Name of a blend mode is not known.
+    #[error(transparent)]
+    UnknownCompositeOp(#[from] UnknownCompositeOp),
+
+    /// Name of a colorspace is not known.
+    #[error(transparent)]
+    UnknownColorspace(#[from] UnknownColorspace),
+}
+
+/// Errors that can be encountered while changing the node tree.
+#[derive(Error, Debug)]
+pub enum EditError {
+    /// There is no node with this UUID.
+    #[error("node not found: {0}")]
+    UnknownNode(Uuid),
+
+    /// The node cannot be placed under this parent (`None` is the top level).
+    #[error("node {node} cannot be placed under {}", .parent.map_or("the top level".to_owned(), |parent| parent.to_string()))]
+    InvalidParent {
+        /// The node that was moved.
+        node: Uuid,
+        /// The parent it was moved to.
+        parent: Option<Uuid>,
+    },
+
+    /// The group would be placed into itself.
+    #[error("group {0} cannot be placed into itself")]
+    Cycle(Uuid),
+
+    /// The position is past the end of the siblings.
+    #[error("index {index} is out of bounds, there are {len} nodes")]
+    IndexOutOfBounds {
+        /// The position that was requested.
+        index: usize,
+        /// Number of siblings.
+        len: usize,
+    },
+
+    /// Groups would be nested too deeply.
+    #[error("groups cannot be nested deeper than {0} levels")]
+    NestingTooDeep(usize),
+
+    /// The operation needs a paint layer.
+    #[error("node is not a paint layer: {0}")]
+    NotPaintLayer(Uuid),
+
+    /// The operation needs a colorize mask.
+    #[error("node is not a colorize mask: {0}")]
+    NotColorizeMask(Uuid),
+
+    /// The key of a custom property cannot be a part of an attribute name.
+    #[error("invalid custom property key: {0:?}")]
+    InvalidPropertyKey(String),
+
+    /// There is no layer below the node.
+    #[error("there is no layer below {0}")]
+    NoLayerBelow(Uuid),
+
+    /// The node has masks, which cannot be applied yet.
+    #[error("node has masks: {0}")]
+    HasMasks(Uuid),
+
+    /// Pixels cannot be blended with this composition operator.
+    #[error("unsupported composition operator: {0:?}")]
+    UnsupportedCompositeOp(CompositeOp),
+
+    /// Pixels of this colorspace cannot be blended.
+    #[error("blending in {0} is not supported")]
+    UnsupportedColorspace(Colorspace),
+
+    /// The image cannot have this size.
+    #[error("invalid image size {width}x{height}")]
+    InvalidSize {
+        /// Requested width.
+        width: u32,
+        /// Requested height.
+        height: u32,
+    },
+
+    /// A new node could not be made from its attributes.
+    #[error("could not create node: {0}")]
+    InvalidNode(String),
+
+    /// The node would have a negative offset, which cannot be stored.
+    #[error("node {0} would be moved to a negative offset")]
+    NegativeOffset(Uuid),
+
+    /// Pixel data could not be read.
+    #[error(transparent)]
+    DataError(#[from] DataError),
+
+    /// The change would exceed a limit.
+    #[error(transparent)]
+    LimitExceeded(#[from] LimitError),
+
+    /// Image could not be encoded.
+    #[cfg(feature = "image")]
+    #[error(transparent)]
+    ImageError(#[from] image::ImageError),
+}
+
+/// A limit set in [`Limits`](crate::config::Limits) was exceeded.
+#[derive(Error, Debug, PartialEq, Eq, Clone, Hash)]
+pub enum LimitError {
+    /// An entry of the archive is too large.
+    #[error("entry {entry} is larger than {limit} bytes")]
+    EntrySize {
+        /// Name of the entry.
+        entry: String,
+        /// The limit, in bytes.
+        limit: u64,
+    },
+
+    /// There are too many nodes.
+    #[error("document has more than {0} nodes")]
+    Nodes(usize),
+
+    /// Group layers are nested too deep.
+    #[error("group layers are nested deeper than {0}")]
+    GroupDepth(usize),
+
+    /// The image is too large.
+    #[error("image size {width}x{height} exceeds {max_width}x{max_height}")]
+    Dimensions {
+        /// Width of the image.
+        width: u32,
+        /// Height of the image.
+        height: u32,
+        /// Maximum width.
+        max_width: u32,
+        /// Maximum height.
+        max_height: u32,
+    },
+
+    /// The image, or the pixels of a layer, cover too many pixels.
+    #[error("more than {0} pixels")]
+    Pixels(u64),
+
+    /// Documents that file layers link to are nested too deep.
+    #[error("documents linked by file layers are nested deeper than {0}")]
+    FileLayerDepth(usize),
+}
+
+/// A path that comes from the file points outside of its base directory,
+/// or is absolute.
+#[derive(Error, Debug, PartialEq, Eq, Clone, Hash)]
+#[error("path escapes its base directory: {0}")]
+pub struct PathTraversalError(pub String);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_and_column_of_ascii() {
+        let text = "<a>\n <b/>\n</a>";
+        assert_eq!(line_and_column(text, 0), (1, 1));
+        assert_eq!(line_and_column(text, 3), (1, 4));
+        assert_eq!(line_and_column(text, 4), (2, 1));
+        assert_eq!(line_and_column(text, 5), (2, 2));
+        assert_eq!(line_and_column(text, text.len()), (3, 5));
+        assert_eq!(line_and_column(text, text.len() + 10), (3, 5));
+        assert_eq!(line_and_column("", 0), (1, 1));
+    }
+
+    #[test]
+    fn line_and_column_of_multibyte_characters() {
+        // 'é' takes 2 bytes, '字' 3 and '🎨' 4
+        let text = "<é 字=\"🎨\">\n<字/>";
+        let offset = |pattern: &str| text.find(pattern).unwrap();
+        assert_eq!(line_and_column(text, offset("字")), (1, 4));
+        assert_eq!(line_and_column(text, offset("🎨")), (1, 7));
+        assert_eq!(line_and_column(text, offset(">")), (1, 9));
+        assert_eq!(line_and_column(text, offset("<字")), (2, 1));
+        assert_eq!(line_and_column(text, offset("/>")), (2, 3));
+        // Inside of '字'
+        assert_eq!(line_and_column(text, offset("字") + 1), (1, 4));
+    }
 }
@@ -0,0 +1,317 @@
+//! Exporting paint layers of many documents at once, for batch tools.
+
+use std::{
+    fs, io,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    thread,
+};
+
+use crate::{
+    cache::Index,
+    error::ExportError,
+    helper::run_parallel,
+    layer::NodeType,
+    naming::{NameTemplate, SanitizeOptions},
+    KraFile,
+};
+
+/// What is exported from each document: every paint layer as a PNG image, named
+/// by the template.
+#[derive(Debug, Clone)]
+pub struct ExportSpec {
+    pub(crate) template: NameTemplate,
+    pub(crate) sanitize: SanitizeOptions,
+    pub(crate) visible_only: bool,
+}
+
+impl Default for ExportSpec {
+    fn default() -> Self {
+        ExportSpec {
+            template: "{path}/{name}.png".parse().expect("template is valid"),
+            sanitize: SanitizeOptions::default(),
+            visible_only: false,
+        }
+    }
+}
+
+impl ExportSpec {
+    /// Layers named `{path}/{name}.png`, hidden ones included.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the template of file names, relative to the directory of the document.
+    pub fn template(mut self, template: NameTemplate) -> Self {
+        self.template = template;
+        self
+    }
+
+    /// Template of file names, relative to the directory of the document.
+    pub fn get_template(&self) -> &NameTemplate {
+        &self.template
+    }
+
+    /// Set how names of nodes are turned into names of files.
+    pub fn sanitize(mut self, sanitize: SanitizeOptions) -> Self {
+        self.sanitize = sanitize;
+        self
+    }
+
+    /// How names of nodes are turned into names of files.
+    pub fn get_sanitize(&self) -> &SanitizeOptions {
+        &self.sanitize
+    }
+
+    /// Set whether only layers that are shown in the image are exported, see
+    /// [`KraFile::effective_visibility()`].
+    pub fn visible_only(mut self, visible_only: bool) -> Self {
+        self.visible_only = visible_only;
+        self
+    }
+
+    /// Whether only layers that are shown in the image are exported.
+    pub fn get_visible_only(&self) -> bool {
+        self.visible_only
+    }
+}
+
+/// Export of many documents into one directory, on several threads.
+///
+/// Layers of each document go into a directory named after the document's file, without
+/// its extension, so documents should have distinct names.
+///
+/// With a [resume index](Self::resume_index), documents are recorded in it as they are
+/// exported, and the ones that did not change since are skipped. A job that was
+/// interrupted can then be run again to only export what is left. The index does not know
+/// the spec, so it should be removed when the spec changes.
+#[derive(Debug, Clone)]
+pub struct ExportJob {
+    documents: Vec<PathBuf>,
+    output: PathBuf,
+    spec: ExportSpec,
+    threads: usize,
+    resume_index: Option<PathBuf>,
+}
+
+impl ExportJob {
+    /// Job that exports `documents` into `output` on as many threads as there are cores.
+    pub fn new<I, P, Q>(documents: I, output: Q, spec: ExportSpec) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        ExportJob {
+            documents: documents
+                .into_iter()
+                .map(|path| path.as_ref().to_owned())
+                .collect(),
+            output: output.as_ref().to_owned(),
+            spec,
+            threads: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            resume_index: None,
+        }
+    }
+
+    /// Set the number of documents that are exported at once, at least 1.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Number of documents that are exported at once.
+    pub fn get_threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Set the file of the [`Index`] that exported documents are recorded in.
+    pub fn resume_index<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.resume_index = Some(path.as_ref().to_owned());
+        self
+    }
+
+    /// File of the [`Index`] that exported documents are recorded in.
+    pub fn get_resume_index(&self) -> Option<&Path> {
+        self.resume_index.as_deref()
+    }
+
+    /// Documents to export, in the order they were given.
+    pub fn documents(&self) -> &[PathBuf] {
+        &self.documents
+    }
+
+    /// Export the documents, calling `progress` on this thread as each of them is done.
+    ///
+    /// Documents that could not be exported do not stop the job, they are reported with
+    /// their errors. Only errors of reading and writing the resume index are returned,
+    /// the index is written after every exported document.
+    pub fn run<F>(&self, mut progress: F) -> io::Result<ExportReport>
+    where
+        F: FnMut(&ExportItem, ExportProgress),
+    {
+        let mut index = match &self.resume_index {
+            Some(path) => Some(Index::load(path)?),
+            None => None,
+        };
+        let total = self.documents.len();
+        let mut report = ExportReport::default();
+        let mut done = |item: ExportItem, report: &mut ExportReport| {
+            let finished = report.items.len() + 1;
+            progress(&item, ExportProgress { finished, total });
+            report.items.push(item);
+        };
+
+        let mut pending = Vec::new();
+        for path in &self.documents {
+            let skipped = match index.as_mut() {
+                Some(index) => index.is_up_to_date(path),
+                None => Ok(false),
+            };
+            match skipped {
+                Ok(true) => done(ExportItem::new(path, ExportStatus::Skipped), &mut report),
+                Ok(false) => pending.push(path.as_path()),
+                Err(err) => done(
+                    ExportItem::new(path, ExportStatus::Failed(err.into())),
+                    &mut report,
+                ),
+            }
+        }
+
+        run_parallel(
+            &pending,
+            self.threads,
+            |path| self.export_document(path),
+            |&path, result| {
+                let status = match result {
+                    Ok(files) => match index.as_mut().map(|index| index.fingerprint(path)) {
+                        Some(Err(err)) => ExportStatus::Failed(err.into()),
+                        _ => ExportStatus::Exported(files),
+                    },
+                    Err(err) => ExportStatus::Failed(err),
+                };
+                if let (Some(index), Some(index_path)) = (&index, &self.resume_index) {
+                    index.save(index_path)?;
+                }
+                done(ExportItem::new(path, status), &mut report);
+                Ok(())
+            },
+        )?;
+        Ok(report)
+    }
+
+    // Writes every exported layer of the document, returning paths of the files
+    fn export_document(&self, path: &Path) -> Result<Vec<PathBuf>, ExportError> {
+        let mut file = KraFile::read(path)?;
+        let stem = path.file_stem().unwrap_or(path.as_os_str());
+        let directory = self.output.join(stem);
+        let names = file.export_names(&self.spec.template, &self.spec.sanitize);
+        let layers: Vec<_> = file
+            .all_nodes()
+            .into_iter()
+            .filter(|node| matches!(node.node_type(), NodeType::PaintLayer(_)))
+            .filter(|node| {
+                !self.spec.visible_only || file.effective_visibility(&node.uuid()) == Some(true)
+            })
+            .map(|node| node.uuid())
+            .collect();
+
+        let mut files = Vec::new();
+        for uuid in layers {
+            let data = file.decode_layer(&uuid)?;
+            // Images cannot be empty
+            if data.width() == 0 || data.height() == 0 {
+                continue;
+            }
+            let out = directory.join(&names[&uuid]);
+            if let Some(parent) = out.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            data.to_rgba_image()?
+                .save_with_format(&out, image::ImageFormat::Png)?;
+            files.push(out);
+        }
+        Ok(files)
+    }
+}
+
+/// How far a job is, passed along with each finished document.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct ExportProgress {
+    finished: usize,
+    total: usize,
+}
+
+impl ExportProgress {
+    /// Number of documents that are done, this one included.
+    pub fn finished(&self) -> usize {
+        self.finished
+    }
+
+    /// Number of documents of the job.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+}
+
+/// What happened to one document.
+#[derive(Debug)]
+pub enum ExportStatus {
+    /// Layers were exported into these files.
+    Exported(Vec<PathBuf>),
+    /// The document did not change since it was exported the last time.
+    Skipped,
+    /// The document could not be exported.
+    Failed(ExportError),
+}
+
+/// One document of a job and what happened to it.
+#[derive(Debug)]
+pub struct ExportItem {
+    path: PathBuf,
+    status: ExportStatus,
+}
+
+impl ExportItem {
+    fn new(path: &Path, status: ExportStatus) -> Self {
+        ExportItem {
+            path: path.to_owned(),
+            status,
+        }
+    }
+
+    /// Path of the document, as it was given to the job.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// What happened to the document.
+    pub fn status(&self) -> &ExportStatus {
+        &self.status
+    }
+}
+
+/// Result of [`ExportJob::run()`].
+#[derive(Debug, Default)]
+pub struct ExportReport {
+    items: Vec<ExportItem>,
+}
+
+impl ExportReport {
+    /// Every document of the job, in the order they were done.
+    pub fn items(&self) -> &[ExportItem] {
+        &self.items
+    }
+
+    /// Documents that could not be exported.
+    pub fn failed(&self) -> impl Iterator<Item = &ExportItem> + '_ {
+        self.items
+            .iter()
+            .filter(|item| matches!(item.status, ExportStatus::Failed(_)))
+    }
+
+    /// Whether every document was exported or skipped.
+    pub fn is_success(&self) -> bool {
+        self.failed().next().is_none()
+    }
+}
@@ -0,0 +1,215 @@
+// Reading files that file layers link to: images, scaled the way the layers say, and other
+// documents, which may link to more documents in turn.
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "image")]
+use image::{imageops::FilterType, DynamicImage, ImageReader};
+
+#[cfg(feature = "image")]
+use crate::{
+    color::{convert_pixels_with, AlphaMode, ConvertOptions},
+    data::{LayerData, PixelBuffer},
+    id::Uuid,
+    Colorspace,
+};
+use crate::{
+    config::ParsingConfiguration,
+    data::{is_document, FileLayerLink},
+    error::{DataError, LimitError},
+    layer::NodeType,
+    KraFile,
+};
+
+// Resolution that images without one have, in pixels per inch
+#[cfg(feature = "image")]
+const DEFAULT_RES: f64 = 72.0;
+
+// Values of `scalingmethod`
+#[cfg(feature = "image")]
+const SCALE_TO_IMAGE_SIZE: u32 = 1;
+#[cfg(feature = "image")]
+const SCALE_TO_IMAGE_PPI: u32 = 2;
+
+// Closest filter to the one Krita uses, by the value of `scalingfilter`
+#[cfg(feature = "image")]
+fn filter_type(scaling_filter: &str) -> FilterType {
+    match scaling_filter {
+        "NearestNeighbor" | "Box" => FilterType::Nearest,
+        "Bilinear" => FilterType::Triangle,
+        "Lanczos3" => FilterType::Lanczos3,
+        _ => FilterType::CatmullRom,
+    }
+}
+
+impl KraFile {
+    /// Read the image that a file layer links to, scaled as the layer says and converted
+    /// to the colorspace of the layer.
+    ///
+    /// The source is looked up relative to [`base_dir()`](Self::base_dir). Formats that
+    /// the `image` crate is built with are supported (this crate only enables PNG).
+    /// Linked `.kra` documents give their merged image, which is composited (with the
+    /// `render` feature) if they do not store one, going into the documents they link to
+    /// up to [`Limits::max_file_layer_depth`](crate::config::Limits::max_file_layer_depth).
+    /// Documents that link back to the ones that lead to them give
+    /// [`DataError::LinkCycle`].
+    /// Images are scaled to fit the image size, keeping their aspect ratio, or by the
+    /// ratio of resolutions; the resolution of the linked image is not read, so it is
+    /// taken to be 72 PPI. The result is cached like [`decode_layer()`](Self::decode_layer)
+    /// does.
+    #[cfg(feature = "image")]
+    pub fn decode_file_layer(&mut self, uuid: &Uuid) -> Result<LayerData, DataError> {
+        let node = self
+            .all_nodes()
+            .into_iter()
+            .find(|node| node.uuid() == *uuid)
+            .ok_or(DataError::UnknownNode(*uuid))?;
+        let NodeType::FileLayer(props) = node.node_type() else {
+            return Err(DataError::NoPixelData(*uuid));
+        };
+        let path = self
+            .base_dir
+            .as_deref()
+            .map(|base_dir| props.resolve_source(base_dir));
+        let (method, filter) = (props.scaling_method(), filter_type(props.scaling_filter()));
+        let (x, y, colorspace) = (node.x(), node.y(), props.colorspace());
+
+        if let Some(data) = self.cache.get(uuid) {
+            return Ok(data.clone());
+        }
+
+        let path = path.ok_or(DataError::NoBaseDir)??;
+        let mut image = if is_document(&path) {
+            let config = ParsingConfiguration {
+                parse_layers: true,
+                keep_archive: true,
+                ..self.config.clone()
+            };
+            let mut linked = self.open_linked(&fs::canonicalize(path)?, config)?;
+            linked.merged_image_decoded()?
+        } else {
+            ImageReader::open(path)?.with_guessed_format()?.decode()?
+        };
+        let size = (image.width(), image.height());
+        let target = match method {
+            SCALE_TO_IMAGE_SIZE => (self.meta.width(), self.meta.height()),
+            SCALE_TO_IMAGE_PPI => {
+                let scale = |size: u32, res: f64| (size as f64 * res / DEFAULT_RES).round() as u32;
+                (
+                    scale(size.0, self.meta.x_res()),
+                    scale(size.1, self.meta.y_res()),
+                )
+            }
+            _ => size,
+        };
+        if target != size {
+            image = match method {
+                SCALE_TO_IMAGE_SIZE => image.resize(target.0, target.1, filter),
+                _ => image.resize_exact(target.0, target.1, filter),
+            };
+        }
+
+        let data = layer_data(image, x, y, colorspace)?;
+        self.cache.insert(*uuid, data.clone());
+        Ok(data)
+    }
+
+    /// Links of every file layer to the file it shows, including the file layers of linked
+    /// documents, which are followed once each, up to
+    /// [`Limits::max_file_layer_depth`](crate::config::Limits::max_file_layer_depth).
+    ///
+    /// Sources are looked up relative to [`base_dir()`](Self::base_dir). Sources that do
+    /// not exist are listed, but not followed.
+    pub fn file_layer_links(&self) -> Result<Vec<FileLayerLink>, DataError> {
+        let mut visited = self.link_chain.iter().cloned().collect();
+        let mut links = Vec::new();
+        self.collect_links(None, &mut visited, &mut links)?;
+        Ok(links)
+    }
+
+    fn collect_links(
+        &self,
+        document: Option<&Path>,
+        visited: &mut HashSet<PathBuf>,
+        links: &mut Vec<FileLayerLink>,
+    ) -> Result<(), DataError> {
+        for node in self.all_nodes() {
+            let NodeType::FileLayer(props) = node.node_type() else {
+                continue;
+            };
+            let base_dir = self.base_dir.as_deref().ok_or(DataError::NoBaseDir)?;
+            let source = props.resolve_source(base_dir)?;
+            let source = fs::canonicalize(&source).unwrap_or(source);
+            let cycle = is_document(&source) && self.link_chain.contains(&source);
+            links.push(FileLayerLink {
+                document: document.map(Path::to_path_buf),
+                layer: node.uuid(),
+                source: source.clone(),
+                cycle,
+            });
+            if is_document(&source) && source.exists() && visited.insert(source.clone()) {
+                let config = ParsingConfiguration {
+                    parse_layers: true,
+                    keep_archive: false,
+                    ..self.config.clone()
+                };
+                let linked = self.open_linked(&source, config)?;
+                linked.collect_links(Some(&source), visited, links)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Reads the document at the canonical path, that a file layer of this one links to
+    fn open_linked(&self, path: &Path, config: ParsingConfiguration) -> Result<Self, DataError> {
+        if self.link_chain.iter().any(|document| document == path) {
+            return Err(DataError::LinkCycle(path.to_owned()));
+        }
+        let limit = self.config.limits.max_file_layer_depth;
+        if self.link_depth >= limit {
+            return Err(LimitError::FileLayerDepth(limit).into());
+        }
+        let mut linked = KraFile::read_with_config(path, config)
+            .map_err(|err| DataError::LinkedDocument(path.to_owned(), Box::new(err)))?;
+        linked.link_chain = self.link_chain.clone();
+        linked.link_chain.push(path.to_owned());
+        linked.link_depth = self.link_depth + 1;
+        Ok(linked)
+    }
+}
+
+// Pixels of the image at the position, in the colorspace
+#[cfg(feature = "image")]
+fn layer_data(
+    image: DynamicImage,
+    x: i32,
+    y: i32,
+    colorspace: Colorspace,
+) -> Result<LayerData, DataError> {
+    let image = image.into_rgba32f();
+    let (width, height) = image.dimensions();
+    let pixels = PixelBuffer::F32(image.into_raw());
+    let pixels = convert_pixels_with(
+        &pixels,
+        (Colorspace::RGBAF32, AlphaMode::Straight),
+        colorspace,
+        &ConvertOptions::new(),
+    )
+    .ok_or(DataError::UnsupportedConversion(
+        Colorspace::RGBAF32,
+        colorspace,
+    ))?;
+    Ok(LayerData {
+        x,
+        y,
+        width,
+        height,
+        colorspace,
+        pixels,
+        alpha_mode: AlphaMode::Straight,
+    })
+}
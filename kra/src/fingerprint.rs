@@ -0,0 +1,187 @@
+//! Overview of documents that is cheaper than reading them, for scanning many files.
+
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::Path,
+    str,
+};
+
+use quick_xml::{events::Event, Reader as XmlReader};
+use zip::result::ZipError;
+
+use crate::{
+    archive::{ArchiveSource, ZipSource},
+    config::{DoctypeCheck, Limits},
+    error::{MetadataErrorReason, ReadKraError},
+    helper::strip_xml_prefix,
+    metadata::KraMetadataStart,
+    read_document_entry, read_xml_entry,
+};
+
+/// Basic facts about a document, see [`read_fingerprint()`].
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Fingerprint {
+    /// Width and height, in pixels.
+    pub(crate) dimensions: (u32, u32),
+    /// Version of Krita under which the file was saved.
+    pub(crate) krita_version: String,
+    /// Number of layers, including the ones inside of groups, if they were counted.
+    /// Masks are not counted.
+    pub(crate) layer_count: Option<usize>,
+    /// When `maindoc.xml` was last written into the archive, as `YYYY-MM-DDTHH:MM:SS`
+    /// in the local time of the one who saved it.
+    pub(crate) modified: String,
+}
+
+impl Fingerprint {
+    /// Width and height, in pixels.
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.dimensions
+    }
+
+    /// Version of Krita under which the file was saved.
+    pub fn krita_version(&self) -> &str {
+        &self.krita_version
+    }
+
+    /// Number of layers, including the ones inside of groups. Masks are not counted.
+    ///
+    /// Layers are only counted by [`read_fingerprint_with_layers()`], this is `None`
+    /// for fingerprints from [`read_fingerprint()`].
+    pub fn layer_count(&self) -> Option<usize> {
+        self.layer_count
+    }
+
+    /// When `maindoc.xml` was last written into the archive, as `YYYY-MM-DDTHH:MM:SS`
+    /// in the local time of the one who saved it.
+    pub fn modified(&self) -> &str {
+        &self.modified
+    }
+}
+
+/// Read basic facts about the document at `path`, without building its model.
+///
+/// Only `mimetype` and the start of `maindoc.xml` are read: reading stops after the
+/// `<IMAGE>` tag, so the cost does not grow with the number of layers. Layers are not
+/// counted, see [`read_fingerprint_with_layers()`]. Unknown colorspaces are not an error.
+pub fn read_fingerprint<P: AsRef<Path>>(path: P) -> Result<Fingerprint, ReadKraError> {
+    let limits = Limits::default();
+    let mut zip = open(path.as_ref(), &limits)?;
+    let modified = modified(&mut zip)?;
+    let maindoc = read_maindoc_start(&mut zip, limits.max_xml_size)?;
+    let maindoc = strip_xml_prefix(&maindoc);
+    let mut reader = XmlReader::from_str(maindoc);
+    reader.trim_text(true);
+    let start = KraMetadataStart::from_xml(&mut reader, true, DoctypeCheck::Warn, &mut Vec::new())
+        .map_err(|err| err.to_read_error("maindoc.xml".into(), maindoc, &reader))?;
+
+    Ok(Fingerprint {
+        dimensions: start.dimensions(),
+        krita_version: start.krita_version().to_owned(),
+        layer_count: None,
+        modified,
+    })
+}
+
+/// Same as [`read_fingerprint()`], but layers are counted too.
+///
+/// The whole of `maindoc.xml` is read and scanned, as there is no cheaper way to count
+/// layers, but nodes are counted without being parsed, so files that
+/// [`KraFile::read()`](crate::KraFile::read) rejects can still have a fingerprint.
+pub fn read_fingerprint_with_layers<P: AsRef<Path>>(path: P) -> Result<Fingerprint, ReadKraError> {
+    let limits = Limits::default();
+    let mut zip = open(path.as_ref(), &limits)?;
+    let modified = modified(&mut zip)?;
+
+    let maindoc = read_xml_entry(&mut zip, "maindoc.xml", limits.max_xml_size)?;
+    let maindoc = strip_xml_prefix(&maindoc);
+    let mut reader = XmlReader::from_str(maindoc);
+    reader.trim_text(true);
+    let start = KraMetadataStart::from_xml(&mut reader, true, DoctypeCheck::Warn, &mut Vec::new())
+        .map_err(|err| err.to_read_error("maindoc.xml".into(), maindoc, &reader))?;
+
+    let mut layer_count = 0;
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(tag) | Event::Empty(tag)) if tag.name().as_ref() == b"layer" => {
+                layer_count += 1
+            }
+            Ok(Event::Eof) => break,
+            Ok(_) => {}
+            Err(err) => {
+                return Err(MetadataErrorReason::from(err).to_read_error(
+                    "maindoc.xml".into(),
+                    maindoc,
+                    &reader,
+                ))
+            }
+        }
+    }
+
+    Ok(Fingerprint {
+        dimensions: start.dimensions(),
+        krita_version: start.krita_version().to_owned(),
+        layer_count: Some(layer_count),
+        modified,
+    })
+}
+
+// The archive, once its mimetype is checked
+fn open(path: &Path, limits: &Limits) -> Result<ZipSource<File>, ReadKraError> {
+    let mut zip = ZipSource::new(File::open(path)?)?;
+    let mimetype = read_document_entry(&mut zip, "mimetype", limits.max_entry_size)?;
+    if mimetype.as_slice() != r"application/x-krita".as_bytes() {
+        return Err(ReadKraError::MimetypeMismatch);
+    }
+    Ok(zip)
+}
+
+// When maindoc.xml was written into the archive, empty if the archive does not say
+fn modified(zip: &mut dyn ArchiveSource) -> Result<String, ReadKraError> {
+    Ok(zip.entry_info("maindoc.xml")?.modified.unwrap_or_default())
+}
+
+// Start of maindoc.xml, up to the end of the <IMAGE> tag. All of it is returned if the
+// tag is not found, so that parsing tells what is wrong.
+fn read_maindoc_start(zip: &mut dyn ArchiveSource, limit: u64) -> Result<String, ReadKraError> {
+    let entry_error = |source| ReadKraError::Entry {
+        name: "maindoc.xml".to_owned(),
+        source,
+    };
+    let mut entry = zip.by_name("maindoc.xml").map_err(entry_error)?.take(limit);
+    let mut data = Vec::new();
+    let mut chunk = [0; 4096];
+    loop {
+        let read = entry
+            .read(&mut chunk)
+            .map_err(|err| entry_error(ZipError::Io(err)))?;
+        if read == 0 {
+            return String::from_utf8(data)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err).into());
+        }
+        data.extend_from_slice(&chunk[..read]);
+        // The chunk can end in the middle of a character
+        let valid = match str::from_utf8(&data) {
+            Ok(text) => text,
+            Err(err) => str::from_utf8(&data[..err.valid_up_to()]).unwrap_or_default(),
+        };
+        if let Some(end) = image_tag_end(valid) {
+            return Ok(valid[..end].to_owned());
+        }
+    }
+}
+
+// Byte offset right after the <IMAGE> tag, if the text holds all of it
+fn image_tag_end(text: &str) -> Option<usize> {
+    let mut reader = XmlReader::from_str(text);
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(tag)) if tag.name().as_ref() == b"IMAGE" => {
+                return Some(reader.buffer_position())
+            }
+            Ok(Event::Eof) | Err(_) => return None,
+            Ok(_) => {}
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! Points and rectangles in image coordinates, in pixels.
+
+/// Point in image coordinates.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+pub struct IPoint {
+    pub(crate) x: i32,
+    pub(crate) y: i32,
+}
+
+impl IPoint {
+    /// Point at `(x, y)`.
+    pub fn new(x: i32, y: i32) -> Self {
+        IPoint { x, y }
+    }
+
+    /// Horizontal coordinate.
+    pub fn x(&self) -> i32 {
+        self.x
+    }
+
+    /// Vertical coordinate.
+    pub fn y(&self) -> i32 {
+        self.y
+    }
+}
+
+impl From<(i32, i32)> for IPoint {
+    fn from((x, y): (i32, i32)) -> Self {
+        IPoint { x, y }
+    }
+}
+
+/// Rectangle in image coordinates, from its top left corner.
+///
+/// Edges are exclusive on the right and at the bottom, so a rectangle with zero width or
+/// height is empty and contains no pixels.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+pub struct IRect {
+    pub(crate) x: i32,
+    pub(crate) y: i32,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+}
+
+impl IRect {
+    /// Rectangle at `(x, y)` of the given size.
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        IRect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Rectangle between the edges, empty if `right` is not after `left` or `bottom` is
+    /// not below `top`.
+    pub fn from_edges(left: i32, top: i32, right: i32, bottom: i32) -> Self {
+        let size = |from: i32, to: i32| if to > from { to.abs_diff(from) } else { 0 };
+        IRect::new(left, top, size(left, right), size(top, bottom))
+    }
+
+    /// Left edge.
+    pub fn x(&self) -> i32 {
+        self.x
+    }
+
+    /// Top edge.
+    pub fn y(&self) -> i32 {
+        self.y
+    }
+
+    /// Width, in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height, in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Top left corner.
+    pub fn origin(&self) -> IPoint {
+        IPoint::new(self.x, self.y)
+    }
+
+    /// Right edge, exclusive.
+    pub fn right(&self) -> i32 {
+        self.x.saturating_add_unsigned(self.width)
+    }
+
+    /// Bottom edge, exclusive.
+    pub fn bottom(&self) -> i32 {
+        self.y.saturating_add_unsigned(self.height)
+    }
+
+    /// Number of pixels inside.
+    pub fn area(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+
+    /// Whether there are no pixels inside.
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Whether the pixel at `point` is inside.
+    pub fn contains(&self, point: IPoint) -> bool {
+        (self.x..self.right()).contains(&point.x) && (self.y..self.bottom()).contains(&point.y)
+    }
+
+    /// Whether every pixel of `other` is inside. Empty rectangles are inside any other.
+    pub fn contains_rect(&self, other: &IRect) -> bool {
+        other.is_empty()
+            || (self.x <= other.x
+                && self.y <= other.y
+                && other.right() <= self.right()
+                && other.bottom() <= self.bottom())
+    }
+
+    /// Area that is inside both rectangles, or `None` if they do not overlap.
+    pub fn intersection(&self, other: &IRect) -> Option<IRect> {
+        let rect = IRect::from_edges(
+            self.x.max(other.x),
+            self.y.max(other.y),
+            self.right().min(other.right()),
+            self.bottom().min(other.bottom()),
+        );
+        (!rect.is_empty()).then_some(rect)
+    }
+
+    /// Whether the rectangles have pixels in common.
+    pub fn overlaps(&self, other: &IRect) -> bool {
+        self.intersection(other).is_some()
+    }
+
+    /// Smallest rectangle that contains both. Empty rectangles are ignored.
+    pub fn union(&self, other: &IRect) -> IRect {
+        match (self.is_empty(), other.is_empty()) {
+            (_, true) => *self,
+            (true, false) => *other,
+            (false, false) => IRect::from_edges(
+                self.x.min(other.x),
+                self.y.min(other.y),
+                self.right().max(other.right()),
+                self.bottom().max(other.bottom()),
+            ),
+        }
+    }
+
+    /// Same rectangle, moved by `offset`.
+    pub fn translate(&self, offset: IPoint) -> IRect {
+        IRect::new(
+            self.x.saturating_add(offset.x),
+            self.y.saturating_add(offset.y),
+            self.width,
+            self.height,
+        )
+    }
+}
+
+impl From<(i32, i32, u32, u32)> for IRect {
+    fn from((x, y, width, height): (i32, i32, u32, u32)) -> Self {
+        IRect::new(x, y, width, height)
+    }
+}
@@ -1,12 +1,17 @@
 use std::borrow::Cow;
 use std::fmt::Display;
+use std::io;
+use std::path::{Component, Path, PathBuf};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
 
 use quick_xml::events::attributes::Attribute;
 use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
 use quick_xml::Reader as XmlReader;
 
-use crate::error::XmlError;
+use crate::error::{PathTraversalError, XmlError};
 
 // These are helper functions to declutter main code
 #[inline]
@@ -17,6 +22,30 @@ pub(crate) fn next_xml_event<'a>(reader: &mut XmlReader<&'a [u8]>) -> Result<Eve
     }
 }
 
+// Raw XML read since the reader had `rest` of its input left, without surrounding whitespace.
+// Only exact between events, as the reader may have consumed `<` after a text event.
+pub(crate) fn raw_since(rest: &[u8], reader: &XmlReader<&[u8]>) -> String {
+    let read = &rest[..rest.len() - reader.get_ref().len()];
+    String::from_utf8_lossy(read).trim().to_owned()
+}
+
+// Skips the XML declaration (if there is one), along with comments and processing instructions
+pub(crate) fn next_event_after_declaration<'a>(
+    reader: &mut XmlReader<&'a [u8]>,
+) -> Result<Event<'a>, XmlError> {
+    loop {
+        match next_xml_event(reader)? {
+            Event::Decl(_) | Event::Comment(_) | Event::PI(_) => continue,
+            other => return Ok(other),
+        }
+    }
+}
+
+// Tools other than Krita may add a byte order mark or whitespace before the declaration
+pub(crate) fn strip_xml_prefix(text: &str) -> &str {
+    text.trim_start_matches('\u{feff}').trim_start()
+}
+
 #[inline]
 pub(crate) fn event_unwrap_as_doctype(event: Event) -> Result<BytesText, XmlError> {
     match event {
@@ -66,6 +95,33 @@ pub(crate) fn event_get_attr<'a>(
     Ok(attr)
 }
 
+// Same as event_get_attr(), but missing attribute is not an error
+pub(crate) fn event_get_optional_attr<'a>(
+    tag: &'a BytesStart<'a>,
+    name: &str,
+) -> Result<Option<Attribute<'a>>, XmlError> {
+    Ok(tag.try_get_attribute(name)?)
+}
+
+// All attributes of the tag, unescaped, in the order they were written
+pub(crate) fn event_get_all_attrs(tag: &BytesStart) -> Result<Vec<(String, String)>, XmlError> {
+    tag.attributes()
+        .map(|attr| {
+            let attr = attr.map_err(quick_xml::Error::from)?;
+            let key = String::from_utf8(attr.key.as_ref().to_vec())?;
+            Ok((key, attr.unescape_value()?.into_owned()))
+        })
+        .collect()
+}
+
+// Replaces the value of the attribute, or adds it
+pub(crate) fn set_attr(attributes: &mut Vec<(String, String)>, key: &str, value: &str) {
+    match attributes.iter_mut().find(|(name, _)| name == key) {
+        Some((_, old)) => *old = value.to_owned(),
+        None => attributes.push((key.to_owned(), value.to_owned())),
+    }
+}
+
 //Does not work on bools, use parse_bool() instead
 // This is because xml data stores bools as 1/0 while parse::<bool> expects true/false
 #[inline]
@@ -100,7 +156,7 @@ where
     let event = next_xml_event(reader)?;
     let tag = event_unwrap_as_empty(event)?;
     let attr = event_get_attr(&tag, "value")?;
-    Ok(parse_attr::<T>(attr)?)
+    parse_attr::<T>(attr)
 }
 
 //same but for bool
@@ -109,7 +165,7 @@ pub(crate) fn push_and_parse_bool(reader: &mut XmlReader<&[u8]>) -> Result<bool,
     let event = next_xml_event(reader)?;
     let tag = event_unwrap_as_empty(event)?;
     let attr = event_get_attr(&tag, "value")?;
-    Ok(parse_bool(attr)?)
+    parse_bool(attr)
 }
 
 //Starts immed. before the start tag
@@ -145,3 +201,104 @@ pub(crate) fn event_to_string(event: &Event) -> Result<String, XmlError> {
     let bytes: Vec<u8> = event.iter().copied().collect();
     Ok(String::from_utf8(bytes)?)
 }
+
+// Normalizes a relative path to an entry of the archive, which may use either separator.
+// Fails if the path is absolute, empty, or goes above its base through `..`.
+pub(crate) fn normalize_archive_path(path: &str) -> Result<String, PathTraversalError> {
+    let escape = || PathTraversalError(path.to_owned());
+    let has_drive = path.as_bytes().get(1) == Some(&b':');
+    if path.starts_with(['/', '\\']) || has_drive {
+        return Err(escape());
+    }
+    let mut parts = Vec::new();
+    for part in path.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop().ok_or_else(escape)?;
+            }
+            part => parts.push(part),
+        }
+    }
+    if parts.is_empty() {
+        return Err(escape());
+    }
+    Ok(parts.join("/"))
+}
+
+// Joins a relative path onto `base`, resolving `.` and `..` without touching the filesystem.
+// Fails if the path is absolute or goes above `base`.
+pub(crate) fn join_within(base: &Path, path: &Path) -> Result<PathBuf, PathTraversalError> {
+    let escape = || PathTraversalError(path.display().to_string());
+    let mut parts = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                parts.pop().ok_or_else(escape)?;
+            }
+            Component::RootDir | Component::Prefix(_) => return Err(escape()),
+        }
+    }
+    let mut joined = base.to_path_buf();
+    joined.extend(parts);
+    Ok(joined)
+}
+
+// Text as a JSON string, with quotes
+pub(crate) fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// Calls `work` on every item on up to `threads` threads, which take the next item when they
+// are done with one. Results are passed to `done` on the calling thread as they come, in no
+// particular order; if it fails, the remaining items are not started.
+pub(crate) fn run_parallel<T, R, W, D>(
+    items: &[T],
+    threads: usize,
+    work: W,
+    mut done: D,
+) -> io::Result<()>
+where
+    T: Sync,
+    R: Send,
+    W: Fn(&T) -> R + Sync,
+    D: FnMut(&T, R) -> io::Result<()>,
+{
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..threads.min(items.len()) {
+            let sender = sender.clone();
+            let (next, work) = (&next, &work);
+            scope.spawn(move || {
+                while let Some(item) = items.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    if sender.send((item, work(item))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+        for (item, result) in receiver {
+            if let Err(err) = done(item, result) {
+                // Workers stop once they cannot send
+                next.store(items.len(), Ordering::Relaxed);
+                return Err(err);
+            }
+        }
+        Ok(())
+    })
+}
@@ -0,0 +1,194 @@
+//! Identifiers of nodes.
+//!
+//! Nodes are identified by UUIDs. With the `uuid` feature (enabled by default) [`Uuid`] is
+//! [`uuid::Uuid`](https://docs.rs/uuid); without it, [`Uuid`] is [`UuidString`], which only
+//! validates and stores the text, so that minimal builds can drop the dependency.
+//! Both have the methods that this library uses, so code written against one
+//! usually compiles with the other.
+
+use std::{
+    collections::hash_map::RandomState,
+    fmt::{self, Debug, Display},
+    hash::{BuildHasher, Hasher},
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering},
+    time::SystemTime,
+};
+
+use crate::error::InvalidUuid;
+
+/// Type of node identifiers.
+#[cfg(feature = "uuid")]
+pub type Uuid = uuid::Uuid;
+
+/// Type of node identifiers.
+#[cfg(not(feature = "uuid"))]
+pub type Uuid = UuidString;
+
+/// UUID, stored as validated text in the lowercase hyphenated form.
+///
+/// Ordering and formatting are the same as those of `uuid::Uuid`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UuidString([u8; 36]);
+
+// Positions of hyphens in the hyphenated form
+const HYPHENS: [usize; 4] = [8, 13, 18, 23];
+
+impl UuidString {
+    /// Parse UUID in the hyphenated, simple (no hyphens), braced or URN form.
+    pub fn parse_str(input: &str) -> Result<Self, InvalidUuid> {
+        let invalid = || InvalidUuid(input.to_owned());
+        let text = input
+            .strip_prefix('{')
+            .and_then(|text| text.strip_suffix('}'))
+            .or_else(|| input.strip_prefix("urn:uuid:"))
+            .unwrap_or(input);
+        let digits: Vec<u8> = match text.len() {
+            32 => text.bytes().collect(),
+            36 => {
+                let hyphens_valid = HYPHENS
+                    .iter()
+                    .all(|&index| text.as_bytes().get(index) == Some(&b'-'));
+                if !hyphens_valid {
+                    return Err(invalid());
+                }
+                text.bytes().filter(|&byte| byte != b'-').collect()
+            }
+            _ => return Err(invalid()),
+        };
+        if digits.len() != 32 || !digits.iter().all(u8::is_ascii_hexdigit) {
+            return Err(invalid());
+        }
+        let digits = std::str::from_utf8(&digits).map_err(|_| invalid())?;
+        let value = u128::from_str_radix(digits, 16).map_err(|_| invalid())?;
+        Ok(Self::from_u128(value))
+    }
+
+    /// UUID with the given numeric value.
+    pub fn from_u128(value: u128) -> Self {
+        let digits = format!("{:032x}", value);
+        let mut text = [b'-'; 36];
+        let mut digits = digits.bytes();
+        for (index, byte) in text.iter_mut().enumerate() {
+            if !HYPHENS.contains(&index) {
+                *byte = digits.next().unwrap();
+            }
+        }
+        Self(text)
+    }
+
+    /// Random (version 4) UUID.
+    ///
+    /// Randomness comes from the standard library's hasher keys, which is enough for
+    /// identifiers that only need to be unique within a document.
+    pub fn new_v4() -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos());
+        let half = || {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+            hasher.write_u128(time);
+            hasher.finish() as u128
+        };
+        let value = (half() << 64) | half();
+        // Version 4, variant 1
+        let value = (value & !(0xf << 76)) | (0x4 << 76);
+        let value = (value & !(0b11 << 62)) | (0b10 << 62);
+        Self::from_u128(value)
+    }
+
+    /// Lowercase hyphenated form, like `67e55044-10b1-426f-9247-bb680e5fe0c8`.
+    pub fn as_str(&self) -> &str {
+        // Only ASCII is ever stored
+        std::str::from_utf8(&self.0).unwrap()
+    }
+
+    /// Same as [`as_str()`](Self::as_str).
+    pub fn hyphenated(&self) -> &str {
+        self.as_str()
+    }
+
+    /// Hyphenated form in braces, as Krita writes it.
+    pub fn braced(&self) -> String {
+        format!("{{{}}}", self.as_str())
+    }
+}
+
+impl FromStr for UuidString {
+    type Err = InvalidUuid;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_str(s)
+    }
+}
+
+impl Display for UuidString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Debug for UuidString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+
+    #[test]
+    fn parse_forms() {
+        for input in [
+            TEXT,
+            "67E55044-10B1-426F-9247-BB680E5FE0C8",
+            "{67e55044-10b1-426f-9247-bb680e5fe0c8}",
+            "67e5504410b1426f9247bb680e5fe0c8",
+            "urn:uuid:67e55044-10b1-426f-9247-bb680e5fe0c8",
+        ] {
+            assert_eq!(UuidString::parse_str(input).unwrap().as_str(), TEXT);
+        }
+        assert_eq!(
+            UuidString::parse_str(TEXT).unwrap().braced(),
+            format!("{{{}}}", TEXT)
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        for input in [
+            "",
+            "67e55044-10b1-426f-9247-bb680e5fe0c",
+            "67e55044-10b1-426f-9247+bb680e5fe0c8",
+            "67e55044-10b1-426f-9247-bb680e5fe0cg",
+            "{67e55044-10b1-426f-9247-bb680e5fe0c8",
+            "+7e5504410b1426f9247bb680e5fe0c8",
+        ] {
+            assert!(UuidString::parse_str(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn from_u128() {
+        assert_eq!(
+            UuidString::from_u128(1).as_str(),
+            "00000000-0000-0000-0000-000000000001"
+        );
+    }
+
+    #[test]
+    fn new_v4() {
+        let first = UuidString::new_v4();
+        assert_ne!(first, UuidString::new_v4());
+        assert_eq!(first.as_str().as_bytes()[14], b'4');
+        assert!(matches!(
+            first.as_str().as_bytes()[19],
+            b'8' | b'9' | b'a' | b'b'
+        ));
+    }
+}
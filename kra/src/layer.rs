@@ -1,27 +1,27 @@
 //! Nodes - layers and masks, and supporting structs.
 
 use std::{
-    collections::HashMap,
     fmt::{self, Display},
-    path::PathBuf,
+    path::{Path, PathBuf},
     str::FromStr,
 };
 
-use getset::Getters;
 use kra_macro::ParseTag;
-use quick_xml::events::BytesStart;
-use uuid::Uuid;
+use quick_xml::events::{BytesStart, Event};
 
-use crate::{
-    data::NodeData,
-    helper::{
-        event_get_attr, event_unwrap_as_end, event_unwrap_as_start, next_xml_event, parse_attr,
-        parse_bool,
-    },
+use crate::helper::{
+    event_get_attr, event_get_optional_attr, event_unwrap_as_end, event_unwrap_as_start,
+    join_within, next_xml_event, parse_attr, parse_bool, set_attr,
 };
 use crate::{
-    error::{MetadataErrorReason, UnknownCompositeOp, XmlError},
-    parse_layer, Colorspace,
+    error::{
+        MetadataErrorReason, PathTraversalError, UnknownCompositeOp, UnknownLayerType, XmlError,
+    },
+    id::Uuid,
+    naming::{sanitize_name, SanitizeOptions},
+    parse_layer,
+    schema::AttributeSchema,
+    Colorspace, ParseContext,
 };
 
 /// Composition operator.
@@ -325,54 +325,674 @@ impl FromStr for CompositeOp {
     }
 }
 
+impl CompositeOp {
+    /// Group that Krita lists the operator in.
+    pub fn category(self) -> BlendCategory {
+        use CompositeOp::*;
+        match self {
+            Add | Subtract | InverseSubtract | Multiply | Divide => BlendCategory::Arithmetic,
+            Xor | Or | And | Nand | Nor | Xnor | Implication | NotImplication | Converse
+            | NotConverse => BlendCategory::Binary,
+            Darken
+            | Burn
+            | LinearBurn
+            | GammaDark
+            | DarkerColor
+            | ShadeIfsIllusions
+            | FogDarkenIfsIllusions
+            | EasyBurn => BlendCategory::Darken,
+            Lighten
+            | Dodge
+            | LinearDodge
+            | Screen
+            | HardLight
+            | SoftLightIfsIllusions
+            | SoftLightPegtopDelphi
+            | SoftLight
+            | SoftLightSvg
+            | GammaLight
+            | GammaIllumination
+            | VividLight
+            | FlatLight
+            | LinearLight
+            | PinLight
+            | PnormA
+            | PnormB
+            | SuperLight
+            | TintIfsIllusions
+            | FogLightenIfsIllusions
+            | EasyDodge
+            | LuminositySai
+            | LighterColor => BlendCategory::Lighten,
+            Modulo
+            | ModuloContinuous
+            | DivisiveModulo
+            | DivisiveModuloContinuous
+            | ModuloShift
+            | ModuloShiftContinuous => BlendCategory::Modulo,
+            Diff | Equivalence | AdditiveSubtractive | Exclusion | ArcTangent | Negation => {
+                BlendCategory::Negative
+            }
+            Normal
+            | Behind
+            | Greater
+            | Overlay
+            | Erase
+            | AlphaDarken
+            | HardMix
+            | HardMixPhotoshop
+            | HardMixSofterPhotoshop
+            | GrainMerge
+            | GrainExtract
+            | Parallel
+            | Allanon
+            | GeometricMean
+            | DestinationAtop
+            | DestinationIn
+            | HardOverlay
+            | Interpolation
+            | Interpolation2X
+            | PenumbraA
+            | PenumbraB
+            | PenumbraC
+            | PenumbraD => BlendCategory::Mix,
+            Hue | Color | Saturation | IncSaturation | DecSaturation | Luminize | IncLuminosity
+            | DecLuminosity => BlendCategory::Hsy,
+            HueHsv | ColorHsv | SaturationHsv | IncSaturationHsv | DecSaturationHsv | Value
+            | IncValue | DecValue => BlendCategory::Hsv,
+            HueHsl | ColorHsl | SaturationHsl | IncSaturationHsl | DecSaturationHsl | Lightness
+            | IncLightness | DecLightness => BlendCategory::Hsl,
+            HueHsi | ColorHsi | SaturationHsi | IncSaturationHsi | DecSaturationHsi | Intensity
+            | IncIntensity | DecIntensity => BlendCategory::Hsi,
+            Reflect
+            | Glow
+            | Freeze
+            | Heat
+            | GlowHeat
+            | HeatGlow
+            | ReflectFreeze
+            | FreezeReflect
+            | HeatGlowFreezeReflectHybrid => BlendCategory::Quadratic,
+            In
+            | Out
+            | Plus
+            | Minus
+            | Copy
+            | CopyRed
+            | CopyGreen
+            | CopyBlue
+            | TangentNormalmap
+            | Colorize
+            | Bumpmap
+            | CombineNormal
+            | Clear
+            | Dissolve
+            | Displace
+            | Nocomposition
+            | PassThrough
+            | Undefined
+            | LambertLighting
+            | LambertLightingGamma22 => BlendCategory::Misc,
+        }
+    }
+
+    /// Whether Krita offers the operator only in floating point colorspaces, since it
+    /// needs values above 1.
+    pub fn is_hdr_only(self) -> bool {
+        matches!(
+            self,
+            CompositeOp::LambertLighting | CompositeOp::LambertLightingGamma22
+        )
+    }
+
+    /// Whether the operator blends every color channel on its own, in the same way.
+    /// Operators of the HSY, HSV, HSL and HSI groups, the ones that compare colors
+    /// (like darker color), copies of single channels and normal map operators are not
+    /// separable.
+    pub fn is_separable(self) -> bool {
+        use CompositeOp::*;
+        !matches!(
+            self.category(),
+            BlendCategory::Hsy | BlendCategory::Hsv | BlendCategory::Hsl | BlendCategory::Hsi
+        ) && !matches!(
+            self,
+            DarkerColor
+                | LighterColor
+                | CopyRed
+                | CopyGreen
+                | CopyBlue
+                | TangentNormalmap
+                | Bumpmap
+                | CombineNormal
+                | Colorize
+                | Dissolve
+                | Displace
+        )
+    }
+
+    /// Whether the operator changes only alpha of the pixels below, like erase does.
+    ///
+    /// Layers that [inherit alpha](Node::inherits_alpha) keep alpha of the pixels below
+    /// and only change their color, so with these operators they change nothing.
+    pub fn changes_only_alpha(self) -> bool {
+        matches!(
+            self,
+            CompositeOp::Erase | CompositeOp::DestinationIn | CompositeOp::Clear
+        )
+    }
+}
+
+/// Group of composition operators, as Krita lists them in the blending mode menu,
+/// see [`CompositeOp::category()`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[non_exhaustive]
+pub enum BlendCategory {
+    /// Addition, multiplication and the like.
+    Arithmetic,
+    /// Bitwise logic.
+    Binary,
+    /// Modes that darken.
+    Darken,
+    /// Modes that lighten.
+    Lighten,
+    /// Modes that wrap values around.
+    Modulo,
+    /// Modes based on differences.
+    Negative,
+    /// Normal and other general purpose modes.
+    Mix,
+    /// Modes of the HSY color model.
+    Hsy,
+    /// Modes of the HSV color model.
+    Hsv,
+    /// Modes of the HSL color model.
+    Hsl,
+    /// Modes of the HSI color model.
+    Hsi,
+    /// Reflect, glow, freeze and heat.
+    Quadratic,
+    /// Channel copies, normal maps and everything else.
+    Misc,
+}
+
+impl Display for BlendCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BlendCategory::Arithmetic => "Arithmetic",
+            BlendCategory::Binary => "Binary",
+            BlendCategory::Darken => "Darken",
+            BlendCategory::Lighten => "Lighten",
+            BlendCategory::Modulo => "Modulo",
+            BlendCategory::Negative => "Negative",
+            BlendCategory::Mix => "Mix",
+            BlendCategory::Hsy => "HSY",
+            BlendCategory::Hsv => "HSV",
+            BlendCategory::Hsl => "HSL",
+            BlendCategory::Hsi => "HSI",
+            BlendCategory::Quadratic => "Quadratic",
+            BlendCategory::Misc => "Misc",
+        })
+    }
+}
+
+/// Prefix of attributes that hold [custom properties](Node::custom_properties) of nodes.
+///
+/// Krita does not know these attributes, so they are lost when Krita saves the document.
+pub const CUSTOM_PROPERTY_PREFIX: &str = "kra-rs.";
+
+// Whether the key can be a part of an attribute name
+pub(crate) fn is_valid_property_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.'))
+}
+
 /// One node (layer or mask) of the image.
-#[derive(Debug, Getters)]
-#[getset(get = "pub", get_copy = "pub")]
+#[derive(Debug)]
 pub struct Node {
-    name: String,
-    uuid: Uuid,
-    filename: String,
-    visible: bool,
-    locked: bool,
-    colorlabel: u32,
+    /// Properties that every node has.
+    common: CommonNodeProps,
+    /// Type of the node, along with type-specific properties.
     node_type: NodeType,
-    y: u32,
-    x: u32,
-    in_timeline: InTimeline,
     //NOTE: masks can't have masks
+    /// Masks that are applied to the node.
     masks: Option<Vec<Node>>,
+    /// All XML attributes of the node, including the ones that are not parsed,
+    /// in the order they were read. They are used when the node is written.
+    attributes: Vec<(String, String)>,
+    /// Raw XML of child elements that are not parsed, written back as they are.
+    unparsed: Vec<String>,
 }
 
 impl Display for Node {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{0}: {1}", self.uuid, self.name)
+        write!(f, "{0}: {1}", self.common.uuid, self.common.name)
     }
 }
 
 impl Node {
+    /// Kind of the node.
+    pub fn kind(&self) -> NodeKind {
+        self.node_type.kind()
+    }
+
+    /// The node as a trait object with the properties every node has.
+    pub fn as_common(&self) -> &dyn CommonNodeProperties {
+        &self.common
+    }
+
+    /// Layer properties of the node, if it is a layer.
+    pub fn as_layer(&self) -> Option<&dyn LayerProperties> {
+        self.node_type.as_layer()
+    }
+
+    /// Properties that every node has.
+    pub fn common(&self) -> &CommonNodeProps {
+        &self.common
+    }
+
+    /// Type of the node, along with type-specific properties.
+    pub fn node_type(&self) -> &NodeType {
+        &self.node_type
+    }
+
+    /// Masks that are applied to the node. Empty for group layers, whose children
+    /// are in [`GroupLayerProps::layers()`].
+    pub fn masks(&self) -> &[Node] {
+        self.masks.as_deref().unwrap_or_default()
+    }
+
+    /// Number of nodes below this one: its masks and, for groups, every layer inside
+    /// the group along with the nodes below it. Counted without allocating.
+    pub fn descendant_count(&self) -> usize {
+        let children = match &self.node_type {
+            NodeType::GroupLayer(props) => props.layers(),
+            _ => &[],
+        };
+        self.masks()
+            .iter()
+            .chain(children)
+            .map(|node| 1 + node.descendant_count())
+            .sum()
+    }
+
+    /// All XML attributes of the node, including the ones that are not parsed,
+    /// in the order they were read. They are used when the node is written.
+    pub fn attributes(&self) -> &[(String, String)] {
+        &self.attributes
+    }
+
+    /// Raw XML of child elements of the node that are not parsed (for example, from newer
+    /// versions of Krita), in the order they were read. They are written back as they are.
+    pub fn unparsed_elements(&self) -> &[String] {
+        &self.unparsed
+    }
+
+    /// Custom properties of the node, stored as attributes that start with
+    /// [`CUSTOM_PROPERTY_PREFIX`]. Keys are without the prefix.
+    pub fn custom_properties(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
+        self.attributes.iter().filter_map(|(name, value)| {
+            let key = name.strip_prefix(CUSTOM_PROPERTY_PREFIX)?;
+            Some((key, value.as_str()))
+        })
+    }
+
+    /// Value of the custom property, see [`custom_properties()`](Self::custom_properties).
+    pub fn custom_property(&self, key: &str) -> Option<&str> {
+        self.custom_properties()
+            .find(|(name, _)| *name == key)
+            .map(|(_, value)| value)
+    }
+
+    /// Name of the node.
+    pub fn name(&self) -> &str {
+        &self.common.name
+    }
+
+    /// Unique identifier of the node.
+    pub fn uuid(&self) -> Uuid {
+        self.common.uuid
+    }
+
+    /// Name of the file that stores node's data.
+    pub fn filename(&self) -> &str {
+        &self.common.filename
+    }
+
+    /// Whether the node is visible.
+    pub fn visible(&self) -> bool {
+        self.common.visible
+    }
+
+    /// Whether the node is locked.
+    pub fn locked(&self) -> bool {
+        self.common.locked
+    }
+
+    /// Whether the layer inherits alpha: it is drawn only over the pixels of the layers
+    /// below it in the same group, keeping their alpha. Krita stores this by disabling
+    /// the alpha channel, the last one of [channel flags](PaintLayerProps::channel_flags).
+    pub fn inherits_alpha(&self) -> bool {
+        let flags = match &self.node_type {
+            NodeType::PaintLayer(props) => props.channel_flags(),
+            NodeType::FileLayer(props) => props.channel_flags(),
+            NodeType::FilterLayer(props) => props.channel_flags(),
+            NodeType::FillLayer(props) => props.channel_flags(),
+            NodeType::CloneLayer(props) => props.channel_flags(),
+            NodeType::VectorLayer(props) => props.channel_flags(),
+            _ => return false,
+        };
+        flags.ends_with('0')
+    }
+
+    /// Color label, by its index. 0 (no label) if the document does not have it.
+    pub fn colorlabel(&self) -> u32 {
+        self.common.colorlabel()
+    }
+
+    /// Color label, or `None` if its index is not one that Krita knows.
+    pub fn color_label(&self) -> Option<ColorLabel> {
+        ColorLabel::from_index(self.colorlabel())
+    }
+
+    /// Vertical offset, in pixels.
+    pub fn y(&self) -> i32 {
+        self.common.y
+    }
+
+    /// Horizontal offset, in pixels.
+    pub fn x(&self) -> i32 {
+        self.common.x
+    }
+
+    /// Whether the node is shown in the timeline.
+    pub fn in_timeline(&self) -> bool {
+        self.common.in_timeline()
+    }
+
+    /// Whether onionskinning is enabled. Always false for nodes that are not in the timeline.
+    pub fn onionskin(&self) -> bool {
+        self.common.onionskin()
+    }
+
+    /// Visibility in the timeline, as one value.
+    pub fn timeline(&self) -> InTimeline {
+        self.common.in_timeline
+    }
+
+    /// Whether the node was selected in the layer docker when the document was saved.
+    pub fn selected(&self) -> bool {
+        self.attributes
+            .iter()
+            .any(|(name, value)| name == "selected" && value == "true")
+    }
+
+    /// Notes about the node: the `description` or `comment` attribute, which tools other
+    /// than Krita may write, or else the `description` [custom property](Self::custom_property),
+    /// which can be set with [`KraFile::set_custom_property()`](crate::KraFile::set_custom_property).
+    pub fn description(&self) -> Option<&str> {
+        ["description", "comment"]
+            .iter()
+            .find_map(|key| {
+                self.attributes
+                    .iter()
+                    .find(|(name, _)| name == key)
+                    .map(|(_, value)| value.as_str())
+            })
+            .or_else(|| self.custom_property("description"))
+    }
+
+    /// Name of the node that is safe to use as a file name.
+    /// See [`sanitize_name()`](crate::naming::sanitize_name).
+    ///
+    /// Different nodes may have the same name, use
+    /// [`KraFile::sanitized_names()`](crate::KraFile::sanitized_names) to get unique ones.
+    pub fn sanitized_name(&self, options: &SanitizeOptions) -> String {
+        sanitize_name(self.name(), options)
+    }
+
     pub(crate) fn new(
         common: CommonNodeProps,
         masks: Option<Vec<Node>>,
         node_type: NodeType,
+        attributes: Vec<(String, String)>,
     ) -> Self {
         Node {
-            name: common.name,
-            uuid: common.uuid,
-            filename: common.filename,
-            visible: common.visible,
-            locked: common.locked,
-            colorlabel: common.colorlabel,
+            common,
             node_type,
-            y: common.y,
-            x: common.x,
-            in_timeline: common.in_timeline,
             masks,
+            attributes,
+            unparsed: Vec::new(),
+        }
+    }
+
+    pub(crate) fn set_unparsed(&mut self, unparsed: Vec<String>) {
+        self.unparsed = unparsed;
+    }
+
+    // Nodes that can be placed under this one: layers of a group, masks of other layers.
+    // Masks cannot have children.
+    pub(crate) fn children_mut(&mut self) -> Option<&mut Vec<Node>> {
+        match &mut self.node_type {
+            NodeType::GroupLayer(props) => Some(&mut props.layers),
+            other if other.kind().is_mask() => None,
+            _ => Some(self.masks.get_or_insert_with(Vec::new)),
+        }
+    }
+
+    // Moves the node, keeping its attributes in sync
+    pub(crate) fn set_offset(&mut self, x: i32, y: i32) {
+        self.common.x = x;
+        self.common.y = y;
+        set_attr(&mut self.attributes, "x", &x.to_string());
+        set_attr(&mut self.attributes, "y", &y.to_string());
+    }
+
+    // Renames the node, keeping its attributes in sync
+    pub(crate) fn set_name(&mut self, name: &str) {
+        self.common.name = name.to_owned();
+        set_attr(&mut self.attributes, "name", name);
+    }
+
+    // Sets the custom property; the key must be valid
+    pub(crate) fn set_custom_property(&mut self, key: &str, value: &str) {
+        let name = format!("{}{}", CUSTOM_PROPERTY_PREFIX, key);
+        set_attr(&mut self.attributes, &name, value);
+    }
+
+    // Removes the custom property, returning its value
+    pub(crate) fn remove_custom_property(&mut self, key: &str) -> Option<String> {
+        let name = format!("{}{}", CUSTOM_PROPERTY_PREFIX, key);
+        let index = self
+            .attributes
+            .iter()
+            .position(|(other, _)| *other == name)?;
+        Some(self.attributes.remove(index).1)
+    }
+
+    // Same as children_mut(), without creating the list of masks
+    pub(crate) fn children(&self) -> &[Node] {
+        match &self.node_type {
+            NodeType::GroupLayer(props) => &props.layers,
+            _ => self.masks.as_deref().unwrap_or_default(),
         }
     }
 }
 
+/// Properties that every node has.
+///
+/// This trait is object-safe, so nodes can be handled as `&dyn CommonNodeProperties`.
+pub trait CommonNodeProperties {
+    /// Name of the node.
+    fn name(&self) -> &str;
+    /// Unique identifier of the node.
+    fn uuid(&self) -> Uuid;
+    /// Name of the file that stores node's data.
+    fn filename(&self) -> &str;
+    /// Whether the node is visible.
+    fn visible(&self) -> bool;
+    /// Whether the node is locked.
+    fn locked(&self) -> bool;
+    /// Color label, by its index. 0 (no label) if the document does not have it.
+    fn colorlabel(&self) -> u32;
+    /// Horizontal offset, in pixels.
+    fn x(&self) -> i32;
+    /// Vertical offset, in pixels.
+    fn y(&self) -> i32;
+    /// Whether the node is shown in the timeline.
+    fn in_timeline(&self) -> bool;
+    /// Whether onionskinning is enabled. Always false for nodes that are not in the timeline.
+    fn onionskin(&self) -> bool;
+    /// Visibility in the timeline, as one value.
+    fn timeline(&self) -> InTimeline;
+}
+
+// Same as the trait, so that it is not needed to be in scope
+impl CommonNodeProps {
+    // Only for nodes that are being parsed, as attributes of the node keep the old one
+    pub(crate) fn set_uuid(&mut self, uuid: Uuid) {
+        self.uuid = uuid;
+    }
+
+    /// Name of the node.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Unique identifier of the node.
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    /// Name of the file that stores node's data.
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    /// Whether the node is visible.
+    pub fn visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Whether the node is locked.
+    pub fn locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Color label, by its index. 0 (no label) if the document does not have it.
+    pub fn colorlabel(&self) -> u32 {
+        self.colorlabel.unwrap_or(0)
+    }
+
+    /// Horizontal offset, in pixels.
+    pub fn x(&self) -> i32 {
+        self.x
+    }
+
+    /// Vertical offset, in pixels.
+    pub fn y(&self) -> i32 {
+        self.y
+    }
+
+    /// Whether the node is shown in the timeline.
+    pub fn in_timeline(&self) -> bool {
+        matches!(self.in_timeline, InTimeline::True(_))
+    }
+
+    /// Whether onionskinning is enabled. Always false for nodes that are not in the timeline.
+    pub fn onionskin(&self) -> bool {
+        matches!(self.in_timeline, InTimeline::True(true))
+    }
+
+    /// Visibility in the timeline, as one value.
+    pub fn timeline(&self) -> InTimeline {
+        self.in_timeline
+    }
+}
+
+impl CommonNodeProperties for CommonNodeProps {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+    fn filename(&self) -> &str {
+        &self.filename
+    }
+    fn visible(&self) -> bool {
+        self.visible
+    }
+    fn locked(&self) -> bool {
+        self.locked
+    }
+    fn colorlabel(&self) -> u32 {
+        self.colorlabel.unwrap_or(0)
+    }
+    fn x(&self) -> i32 {
+        self.x
+    }
+    fn y(&self) -> i32 {
+        self.y
+    }
+    fn in_timeline(&self) -> bool {
+        CommonNodeProps::in_timeline(self)
+    }
+    fn onionskin(&self) -> bool {
+        CommonNodeProps::onionskin(self)
+    }
+    fn timeline(&self) -> InTimeline {
+        self.in_timeline
+    }
+}
+
+/// Properties that every layer (but not mask) has.
+///
+/// This trait is object-safe, so properties of different layer types can be handled
+/// as `&dyn LayerProperties`.
+pub trait LayerProperties {
+    /// Composition operator.
+    fn composite_op(&self) -> CompositeOp;
+    /// Opacity, from 0 to 255.
+    fn opacity(&self) -> u8;
+    /// Whether the node is collapsed in the layer docker.
+    fn collapsed(&self) -> bool;
+}
+
+macro_rules! impl_layer_properties {
+    ($($props:ty),*) => {
+        $(
+            impl LayerProperties for $props {
+                fn composite_op(&self) -> CompositeOp {
+                    self.composite_op
+                }
+                fn opacity(&self) -> u8 {
+                    self.opacity
+                }
+                fn collapsed(&self) -> bool {
+                    self.collapsed
+                }
+            }
+        )*
+    };
+}
+
+impl_layer_properties!(
+    PaintLayerProps,
+    GroupLayerProps,
+    FileLayerProps,
+    FilterLayerProps,
+    FillLayerProps,
+    CloneLayerProps,
+    VectorLayerProps
+);
+
 /// Visibility of a node in the timeline.
-#[derive(Debug)]
+///
+/// Nodes also have separate [`in_timeline()`](Node::in_timeline) and
+/// [`onionskin()`](Node::onionskin) flags, which are easier to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum InTimeline {
     /// Node is visible in timeline.
     True(Onionskin),
@@ -383,28 +1003,38 @@ pub enum InTimeline {
 /// Whether onionskinning is enabled.
 pub type Onionskin = bool;
 
-#[derive(Getters, ParseTag)]
-#[getset(get = "pub", get_copy = "pub")]
-pub(crate) struct CommonNodeProps {
+/// Properties that every node has.
+#[derive(Debug, ParseTag)]
+pub struct CommonNodeProps {
+    /// Name of the node.
     #[XmlAttr(pre_parse = "unescape_value()?.into()", fun_override = "name")]
     name: String,
+    /// Unique identifier of the node.
     #[XmlAttr(
         pre_parse = "unescape_value()?",
         fun_override = "Uuid::from_str(uuid.as_ref())?"
     )]
     uuid: Uuid,
+    /// Name of the file that stores node's data.
     #[XmlAttr(pre_parse = "unescape_value()?.into()", fun_override = "filename")]
     filename: String,
+    /// Whether the node is visible.
     #[XmlAttr(fun_override = "parse_bool(visible)?")]
     visible: bool,
+    /// Whether the node is locked.
     #[XmlAttr(fun_override = "parse_bool(locked)?")]
     locked: bool,
-    #[XmlAttr(fun_override = "parse_attr(colorlabel)?")]
-    colorlabel: u32,
+    /// Color label, by its index.
+    /// Old documents may not have it, in which case Krita uses 0 (no label).
+    #[XmlAttr(optional = true, fun_override = "parse_attr(colorlabel)?")]
+    colorlabel: Option<u32>,
+    /// Vertical offset, in pixels.
     #[XmlAttr(fun_override = "parse_attr(y)?")]
-    y: u32,
+    y: i32,
+    /// Horizontal offset, in pixels.
     #[XmlAttr(fun_override = "parse_attr(x)?")]
-    x: u32,
+    x: i32,
+    /// Visibility in the timeline.
     #[XmlAttr(
         qname = "intimeline",
         pre_parse = "unescape_value()?",
@@ -421,11 +1051,9 @@ fn parse_in_timeline(input: &str, tag: &BytesStart) -> Result<InTimeline, Metada
             tag,
             "onionskin",
         )?)?)),
-        what => {
-            return Err(MetadataErrorReason::XmlError(XmlError::ValueError(
-                what.to_string(),
-            )))
-        }
+        what => Err(MetadataErrorReason::XmlError(XmlError::ValueError(
+            what.to_string(),
+        ))),
     }
 }
 
@@ -439,40 +1067,261 @@ pub enum NodeType {
     GroupLayer(GroupLayerProps),
     /// Layer that links to a file in the file system.
     FileLayer(FileLayerProps),
+    /// Layer that applies a filter to layers below it.
     FilterLayer(FilterLayerProps),
     /// Layer that fills the image with a color.
     FillLayer(FillLayerProps),
+    /// Layer that shows contents of another layer.
     CloneLayer(CloneLayerProps),
+    /// Layer that contains vector shapes.
     VectorLayer(VectorLayerProps),
+    /// Mask that controls transparency of its layer.
     TransparencyMask(TransparencyMaskProps),
+    /// Mask that applies a filter to its layer.
     FilterMask(FilterMaskProps),
+    /// Mask that transforms its layer.
     TransformMask(TransformMaskProps),
+    /// Mask that stores a local selection.
     SelectionMask(SelectionMaskProps),
+    /// Mask that fills in areas of its layer based on color hints.
     ColorizeMask(ColorizeMaskProps),
 }
 
+impl NodeType {
+    /// Colorspace of node's pixels, if it has its own.
+    pub fn colorspace(&self) -> Option<Colorspace> {
+        match self {
+            NodeType::PaintLayer(props) => Some(props.colorspace),
+            NodeType::FileLayer(props) => Some(props.colorspace),
+            NodeType::ColorizeMask(props) => Some(props.colorspace),
+            _ => None,
+        }
+    }
+
+    /// Composition operator of a mask, if it is a mask and the file has it.
+    /// Colorize masks always have one, other masks only in files of newer versions of Krita.
+    pub fn mask_composite_op(&self) -> Option<CompositeOp> {
+        match self {
+            NodeType::TransparencyMask(props) => props.composite_op(),
+            NodeType::FilterMask(props) => props.composite_op(),
+            NodeType::TransformMask(props) => props.composite_op(),
+            NodeType::SelectionMask(props) => props.composite_op(),
+            NodeType::ColorizeMask(props) => Some(props.composite_op()),
+            _ => None,
+        }
+    }
+
+    /// Layer properties, if this is a layer.
+    pub fn as_layer(&self) -> Option<&dyn LayerProperties> {
+        match self {
+            NodeType::PaintLayer(props) => Some(props),
+            NodeType::GroupLayer(props) => Some(props),
+            NodeType::FileLayer(props) => Some(props),
+            NodeType::FilterLayer(props) => Some(props),
+            NodeType::FillLayer(props) => Some(props),
+            NodeType::CloneLayer(props) => Some(props),
+            NodeType::VectorLayer(props) => Some(props),
+            NodeType::TransparencyMask(_)
+            | NodeType::FilterMask(_)
+            | NodeType::TransformMask(_)
+            | NodeType::SelectionMask(_)
+            | NodeType::ColorizeMask(_) => None,
+        }
+    }
+
+    /// Kind of the node, without its properties.
+    pub fn kind(&self) -> NodeKind {
+        match self {
+            NodeType::PaintLayer(_) => NodeKind::PaintLayer,
+            NodeType::GroupLayer(_) => NodeKind::GroupLayer,
+            NodeType::FileLayer(_) => NodeKind::FileLayer,
+            NodeType::FilterLayer(_) => NodeKind::FilterLayer,
+            NodeType::FillLayer(_) => NodeKind::FillLayer,
+            NodeType::CloneLayer(_) => NodeKind::CloneLayer,
+            NodeType::VectorLayer(_) => NodeKind::VectorLayer,
+            NodeType::TransparencyMask(_) => NodeKind::TransparencyMask,
+            NodeType::FilterMask(_) => NodeKind::FilterMask,
+            NodeType::TransformMask(_) => NodeKind::TransformMask,
+            NodeType::SelectionMask(_) => NodeKind::SelectionMask,
+            NodeType::ColorizeMask(_) => NodeKind::ColorizeMask,
+        }
+    }
+}
+
+/// Kind of a node, like [`NodeType`] but without properties.
+///
+/// Parses from and displays as Krita's `nodetype` values, such as `paintlayer`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[non_exhaustive]
+pub enum NodeKind {
+    /// Paint layer.
+    PaintLayer,
+    /// Group layer.
+    GroupLayer,
+    /// File layer.
+    FileLayer,
+    /// Filter (adjustment) layer.
+    FilterLayer,
+    /// Fill (generator) layer.
+    FillLayer,
+    /// Clone layer.
+    CloneLayer,
+    /// Vector (shape) layer.
+    VectorLayer,
+    /// Transparency mask.
+    TransparencyMask,
+    /// Filter mask.
+    FilterMask,
+    /// Transform mask.
+    TransformMask,
+    /// Selection mask.
+    SelectionMask,
+    /// Colorize mask.
+    ColorizeMask,
+}
+
+impl NodeKind {
+    /// Whether nodes of this kind are masks.
+    pub fn is_mask(&self) -> bool {
+        matches!(
+            self,
+            NodeKind::TransparencyMask
+                | NodeKind::FilterMask
+                | NodeKind::TransformMask
+                | NodeKind::SelectionMask
+                | NodeKind::ColorizeMask
+        )
+    }
+
+    /// Whether nodes of this kind are layers.
+    pub fn is_layer(&self) -> bool {
+        !self.is_mask()
+    }
+}
+
+impl FromStr for NodeKind {
+    type Err = UnknownLayerType;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "paintlayer" => NodeKind::PaintLayer,
+            "grouplayer" => NodeKind::GroupLayer,
+            "filelayer" => NodeKind::FileLayer,
+            "adjustmentlayer" => NodeKind::FilterLayer,
+            "generatorlayer" => NodeKind::FillLayer,
+            "clonelayer" => NodeKind::CloneLayer,
+            "shapelayer" => NodeKind::VectorLayer,
+            "transparencymask" => NodeKind::TransparencyMask,
+            "filtermask" => NodeKind::FilterMask,
+            "transformmask" => NodeKind::TransformMask,
+            "selectionmask" => NodeKind::SelectionMask,
+            "colorizemask" => NodeKind::ColorizeMask,
+            other => return Err(UnknownLayerType(other.to_owned())),
+        })
+    }
+}
+
+impl Display for NodeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let node_type = match self {
+            NodeKind::PaintLayer => "paintlayer",
+            NodeKind::GroupLayer => "grouplayer",
+            NodeKind::FileLayer => "filelayer",
+            NodeKind::FilterLayer => "adjustmentlayer",
+            NodeKind::FillLayer => "generatorlayer",
+            NodeKind::CloneLayer => "clonelayer",
+            NodeKind::VectorLayer => "shapelayer",
+            NodeKind::TransparencyMask => "transparencymask",
+            NodeKind::FilterMask => "filtermask",
+            NodeKind::TransformMask => "transformmask",
+            NodeKind::SelectionMask => "selectionmask",
+            NodeKind::ColorizeMask => "colorizemask",
+        };
+        write!(f, "{}", node_type)
+    }
+}
+
+/// Color label of a node, as Krita shows it in the layer docker.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[non_exhaustive]
+pub enum ColorLabel {
+    /// No label.
+    NoLabel,
+    /// Blue.
+    Blue,
+    /// Green.
+    Green,
+    /// Yellow.
+    Yellow,
+    /// Orange.
+    Orange,
+    /// Brown.
+    Brown,
+    /// Red.
+    Red,
+    /// Purple.
+    Purple,
+    /// Grey.
+    Grey,
+}
+
+// In the order of Krita's indices
+const COLOR_LABELS: [ColorLabel; 9] = [
+    ColorLabel::NoLabel,
+    ColorLabel::Blue,
+    ColorLabel::Green,
+    ColorLabel::Yellow,
+    ColorLabel::Orange,
+    ColorLabel::Brown,
+    ColorLabel::Red,
+    ColorLabel::Purple,
+    ColorLabel::Grey,
+];
+
+impl ColorLabel {
+    /// Label with Krita's index, which is stored in `colorlabel`.
+    pub fn from_index(index: u32) -> Option<Self> {
+        COLOR_LABELS.get(index as usize).copied()
+    }
+
+    /// Krita's index of the label.
+    pub fn index(&self) -> u32 {
+        COLOR_LABELS
+            .iter()
+            .position(|label| label == self)
+            .expect("every label has an index") as u32
+    }
+}
+
 /// Properties specific to paint layer.
-#[derive(Debug, Getters, ParseTag)]
-#[getset(get = "pub", get_copy = "pub")]
+#[derive(Debug, ParseTag)]
 pub struct PaintLayerProps {
+    /// Composition operator.
     #[XmlAttr(qname = "compositeop", fun_override = "parse_attr(composite_op)?")]
     composite_op: CompositeOp,
+    /// Opacity, from 0 to 255.
     #[XmlAttr(fun_override = "parse_attr(opacity)?")]
     opacity: u8,
+    /// Whether the node is collapsed in the layer docker.
     #[XmlAttr(fun_override = "parse_bool(collapsed)?")]
     collapsed: bool,
+    /// Colorspace of the node.
     #[XmlAttr(
         qname = "colorspacename",
         pre_parse = "unescape_value()?",
         fun_override = "Colorspace::try_from(colorspace.as_ref())?"
     )]
     colorspace: Colorspace,
+    /// Which channels are locked (alpha lock).
+    /// Old documents may not have it, in which case no channels are locked.
     #[XmlAttr(
         qname = "channellockflags",
+        optional = true,
         pre_parse = "unescape_value()?.into_owned()",
         fun_override = "channel_lock_flags"
     )]
-    channel_lock_flags: String,
+    channel_lock_flags: Option<String>,
+    /// Which channels are enabled.
     #[XmlAttr(
         qname = "channelflags",
         pre_parse = "unescape_value()?.into()",
@@ -481,86 +1330,207 @@ pub struct PaintLayerProps {
     channel_flags: String,
 }
 
+impl PaintLayerProps {
+    /// Composition operator.
+    pub fn composite_op(&self) -> CompositeOp {
+        self.composite_op
+    }
+
+    /// Opacity, from 0 to 255.
+    pub fn opacity(&self) -> u8 {
+        self.opacity
+    }
+
+    /// Whether the node is collapsed in the layer docker.
+    pub fn collapsed(&self) -> bool {
+        self.collapsed
+    }
+
+    /// Colorspace of the node.
+    pub fn colorspace(&self) -> Colorspace {
+        self.colorspace
+    }
+
+    /// Which channels are locked (alpha lock).
+    /// Old documents may not have it, in which case no channels are locked.
+    pub fn channel_lock_flags(&self) -> Option<&str> {
+        self.channel_lock_flags.as_deref()
+    }
+
+    /// Which channels are enabled.
+    pub fn channel_flags(&self) -> &str {
+        &self.channel_flags
+    }
+}
+
 /// Properties specific to group layer.
-#[derive(Debug, Getters, ParseTag)]
-#[getset(get = "pub", get_copy = "pub")]
-#[ExtraArgs(
-    extra_args = "reader: &mut quick_xml::Reader<&[u8]>, files: &mut HashMap<Uuid, NodeData>"
-)]
+#[derive(Debug, ParseTag)]
+#[ExtraArgs(extra_args = "reader: &mut quick_xml::Reader<&[u8]>, ctx: &mut ParseContext")]
 pub struct GroupLayerProps {
+    /// Composition operator.
     #[XmlAttr(qname = "compositeop", fun_override = "parse_attr(composite_op)?")]
     pub(crate) composite_op: CompositeOp,
+    /// Whether the node is collapsed in the layer docker.
     #[XmlAttr(fun_override = "parse_bool(collapsed)?")]
     pub(crate) collapsed: bool,
+    /// Whether the group is in pass-through mode.
     #[XmlAttr(fun_override = "parse_bool(passthrough)?")]
     pub(crate) passthrough: bool,
+    /// Opacity, from 0 to 255.
     #[XmlAttr(fun_override = "parse_attr(opacity)?")]
     pub(crate) opacity: u8,
-    #[XmlAttr(
-        extract_data = false,
-        fun_override = "group_get_layers(reader, files)?"
-    )]
+    /// Layers inside the group.
+    #[XmlAttr(extract_data = false, fun_override = "group_get_layers(reader, ctx)?")]
     pub(crate) layers: Vec<Node>,
 }
 
+impl GroupLayerProps {
+    /// Composition operator.
+    pub fn composite_op(&self) -> CompositeOp {
+        self.composite_op
+    }
+
+    /// Whether the node is collapsed in the layer docker.
+    pub fn collapsed(&self) -> bool {
+        self.collapsed
+    }
+
+    /// Whether the group is in pass-through mode.
+    pub fn passthrough(&self) -> bool {
+        self.passthrough
+    }
+
+    /// Opacity, from 0 to 255.
+    pub fn opacity(&self) -> u8 {
+        self.opacity
+    }
+
+    /// Layers inside the group.
+    pub fn layers(&self) -> &[Node] {
+        &self.layers
+    }
+
+    /// Number of layers directly inside the group.
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Whether the group has no layers.
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+}
+
 // Go over layers in the group, stopping at </layer>
 fn group_get_layers(
     reader: &mut quick_xml::Reader<&[u8]>,
-    files: &mut HashMap<Uuid, NodeData>,
+    ctx: &mut ParseContext,
 ) -> Result<Vec<Node>, MetadataErrorReason> {
+    ctx.enter_group()?;
     let mut layers: Vec<Node> = Vec::new();
-    //<layers>
-    let event = next_xml_event(reader)?;
-    event_unwrap_as_start(event)?;
-
-    loop {
-        match parse_layer(reader, files) {
-            Ok(layer) => layers.push(layer),
-            Err(MetadataErrorReason::XmlError(XmlError::EventError(a, ref b)))
-            // This assumes that we have hit </layers>
-                if (a == "layer/mask start event" && b == "layers") =>
-            {
-                break
+    match next_xml_event(reader)? {
+        //<layers/>, a group without children
+        Event::Empty(_) => {}
+        //<layers>
+        event => {
+            event_unwrap_as_start(event)?;
+            loop {
+                match parse_layer(reader, ctx) {
+                    Ok(layer) => layers.extend(layer),
+                    Err(MetadataErrorReason::XmlError(XmlError::EventError(a, ref b)))
+                    // This assumes that we have hit </layers>
+                        if (a == "layer/mask start event" && b == "layers") =>
+                    {
+                        break
+                    }
+                    //Actual error
+                    Err(other) => return Err(other),
+                }
             }
-            //Actual error
-            Err(other) => return Err(other),
         }
     }
 
     //</layer>
     let event = next_xml_event(reader)?;
     event_unwrap_as_end(event)?;
+    ctx.leave_group();
     Ok(layers)
 }
 
 /// Properties specific to filter mask.
-#[derive(Debug, Getters, ParseTag)]
-#[getset(get = "pub", get_copy = "pub")]
+#[derive(Debug, ParseTag)]
 pub struct FilterMaskProps {
+    /// Name of the filter.
     #[XmlAttr(
         qname = "filtername",
         pre_parse = "unescape_value()?",
         fun_override = "filter_name.to_string()"
     )]
     filter_name: String,
+    /// Version of the filter.
     #[XmlAttr(qname = "filterversion", fun_override = "parse_attr(filter_version)?")]
     filter_version: u32,
+    /// Composition operator, which newer versions of Krita may write.
+    #[XmlAttr(
+        qname = "compositeop",
+        optional = true,
+        fun_override = "parse_attr(composite_op)?"
+    )]
+    composite_op: Option<CompositeOp>,
+}
+
+impl FilterMaskProps {
+    /// Name of the filter.
+    pub fn filter_name(&self) -> &str {
+        &self.filter_name
+    }
+
+    /// Version of the filter.
+    pub fn filter_version(&self) -> u32 {
+        self.filter_version
+    }
+
+    /// Composition operator, if the file has it.
+    pub fn composite_op(&self) -> Option<CompositeOp> {
+        self.composite_op
+    }
 }
 
 /// Properties specific to selection mask.
-#[derive(Debug, Getters, ParseTag)]
-#[getset(get = "pub", get_copy = "pub")]
+#[derive(Debug, ParseTag)]
 pub struct SelectionMaskProps {
+    /// Whether the selection is active.
     #[XmlAttr(fun_override = "parse_bool(active)?")]
     active: bool,
+    /// Composition operator, which newer versions of Krita may write.
+    #[XmlAttr(
+        qname = "compositeop",
+        optional = true,
+        fun_override = "parse_attr(composite_op)?"
+    )]
+    composite_op: Option<CompositeOp>,
 }
 
-#[derive(Debug, Getters, ParseTag)]
-#[getset(get = "pub", get_copy = "pub")]
+impl SelectionMaskProps {
+    /// Whether the selection is active.
+    pub fn active(&self) -> bool {
+        self.active
+    }
+
+    /// Composition operator, if the file has it.
+    pub fn composite_op(&self) -> Option<CompositeOp> {
+        self.composite_op
+    }
+}
+
+/// Properties specific to file layer.
+#[derive(Debug, ParseTag)]
 pub struct FileLayerProps {
+    /// Whether the node is collapsed in the layer docker.
     #[XmlAttr(fun_override = "parse_bool(collapsed)?")]
     collapsed: bool,
     //TODO: enum
+    /// Filter used for scaling.
     #[XmlAttr(
         qname = "scalingfilter",
         pre_parse = "unescape_value()?.into()",
@@ -568,12 +1538,16 @@ pub struct FileLayerProps {
     )]
     scaling_filter: String,
     // parse_attr() because this bool is true/false
+    /// Whether the image is scaled.
     #[XmlAttr(fun_override = "parse_attr(scale)?")]
     scale: bool,
+    /// Composition operator.
     #[XmlAttr(qname = "compositeop", fun_override = "parse_attr(composite_op)?")]
     composite_op: CompositeOp,
+    /// Opacity, from 0 to 255.
     #[XmlAttr(fun_override = "parse_attr(opacity)?")]
     opacity: u8,
+    /// Colorspace of the node.
     #[XmlAttr(
         qname = "colorspacename",
         pre_parse = "unescape_value()?",
@@ -581,14 +1555,17 @@ pub struct FileLayerProps {
     )]
     colorspace: Colorspace,
     //TODO: figure out correct type
+    /// Scaling method.
     #[XmlAttr(qname = "scalingmethod", fun_override = "parse_attr(scaling_method)?")]
     scaling_method: u32,
+    /// Path to the linked file.
     #[XmlAttr(
         qname = "source",
         pre_parse = "unescape_value()?.to_string().into()",
         fun_override = "source"
     )]
     source: PathBuf,
+    /// Which channels are enabled.
     #[XmlAttr(
         qname = "channelflags",
         pre_parse = "unescape_value()?.into()",
@@ -597,145 +1574,351 @@ pub struct FileLayerProps {
     channel_flags: String,
 }
 
+impl FileLayerProps {
+    /// Whether the node is collapsed in the layer docker.
+    pub fn collapsed(&self) -> bool {
+        self.collapsed
+    }
+
+    /// Filter used for scaling.
+    pub fn scaling_filter(&self) -> &str {
+        &self.scaling_filter
+    }
+
+    /// Whether the image is scaled.
+    pub fn scale(&self) -> bool {
+        self.scale
+    }
+
+    /// Composition operator.
+    pub fn composite_op(&self) -> CompositeOp {
+        self.composite_op
+    }
+
+    /// Opacity, from 0 to 255.
+    pub fn opacity(&self) -> u8 {
+        self.opacity
+    }
+
+    /// Colorspace of the node.
+    pub fn colorspace(&self) -> Colorspace {
+        self.colorspace
+    }
+
+    /// Scaling method.
+    pub fn scaling_method(&self) -> u32 {
+        self.scaling_method
+    }
+
+    /// Path to the linked file.
+    pub fn source(&self) -> &Path {
+        &self.source
+    }
+
+    /// Which channels are enabled.
+    pub fn channel_flags(&self) -> &str {
+        &self.channel_flags
+    }
+
+    /// Path to the linked file, relative to `base_dir`.
+    ///
+    /// `base_dir` is usually the directory of the `.kra` file. Sources that are absolute or
+    /// point outside of `base_dir` are rejected.
+    pub fn resolve_source(
+        &self,
+        base_dir: impl AsRef<Path>,
+    ) -> Result<PathBuf, PathTraversalError> {
+        join_within(base_dir.as_ref(), &self.source)
+    }
+}
+
 //TODO: mention that it is called adjustment layer somewhere
-#[derive(Debug, Getters, ParseTag)]
-#[getset(get = "pub", get_copy = "pub")]
+/// Properties specific to filter layer.
+#[derive(Debug, ParseTag)]
 pub struct FilterLayerProps {
+    /// Name of the filter.
     #[XmlAttr(
         qname = "filtername",
         pre_parse = "unescape_value()?",
         fun_override = "filter_name.to_string()"
     )]
     filter_name: String,
+    /// Version of the filter.
     #[XmlAttr(qname = "filterversion", fun_override = "parse_attr(filter_version)?")]
     filter_version: u32,
+    /// Which channels are enabled.
     #[XmlAttr(
         qname = "channelflags",
         pre_parse = "unescape_value()?.into()",
         fun_override = "channel_flags"
     )]
     channel_flags: String,
+    /// Whether the node is collapsed in the layer docker.
     #[XmlAttr(fun_override = "parse_bool(collapsed)?")]
     collapsed: bool,
+    /// Composition operator.
     #[XmlAttr(qname = "compositeop", fun_override = "parse_attr(composite_op)?")]
     composite_op: CompositeOp,
+    /// Opacity, from 0 to 255.
     #[XmlAttr(fun_override = "parse_attr(opacity)?")]
     opacity: u8,
 }
 
+impl FilterLayerProps {
+    /// Name of the filter.
+    pub fn filter_name(&self) -> &str {
+        &self.filter_name
+    }
+
+    /// Version of the filter.
+    pub fn filter_version(&self) -> u32 {
+        self.filter_version
+    }
+
+    /// Which channels are enabled.
+    pub fn channel_flags(&self) -> &str {
+        &self.channel_flags
+    }
+
+    /// Whether the node is collapsed in the layer docker.
+    pub fn collapsed(&self) -> bool {
+        self.collapsed
+    }
+
+    /// Composition operator.
+    pub fn composite_op(&self) -> CompositeOp {
+        self.composite_op
+    }
+
+    /// Opacity, from 0 to 255.
+    pub fn opacity(&self) -> u8 {
+        self.opacity
+    }
+}
+
 //TODO: mention somewhere that it is called generatorlayer
-#[derive(Debug, Getters, ParseTag)]
-#[getset(get = "pub", get_copy = "pub")]
+/// Properties specific to fill layer.
+#[derive(Debug, ParseTag)]
 pub struct FillLayerProps {
+    /// Opacity, from 0 to 255.
     #[XmlAttr(fun_override = "parse_attr(opacity)?")]
     opacity: u8,
+    /// Composition operator.
     #[XmlAttr(qname = "compositeop", fun_override = "parse_attr(composite_op)?")]
     composite_op: CompositeOp,
     //TODO: enum?
+    /// Name of the generator.
     #[XmlAttr(
         qname = "generatorname",
         pre_parse = "unescape_value()?.into()",
         fun_override = "generator_name"
     )]
     generator_name: String,
+    /// Version of the generator.
     #[XmlAttr(
         qname = "generatorversion",
         fun_override = "parse_attr(generator_version)?"
     )]
     generator_version: u32,
+    /// Which channels are enabled.
     #[XmlAttr(
         qname = "channelflags",
         pre_parse = "unescape_value()?.into()",
         fun_override = "channel_flags"
     )]
     channel_flags: String,
+    /// Whether the node is collapsed in the layer docker.
     #[XmlAttr(fun_override = "parse_bool(collapsed)?")]
     collapsed: bool,
 }
 
-#[derive(Debug, Getters, ParseTag)]
-#[getset(get = "pub", get_copy = "pub")]
+impl FillLayerProps {
+    /// Opacity, from 0 to 255.
+    pub fn opacity(&self) -> u8 {
+        self.opacity
+    }
+
+    /// Composition operator.
+    pub fn composite_op(&self) -> CompositeOp {
+        self.composite_op
+    }
+
+    /// Name of the generator.
+    pub fn generator_name(&self) -> &str {
+        &self.generator_name
+    }
+
+    /// Version of the generator.
+    pub fn generator_version(&self) -> u32 {
+        self.generator_version
+    }
+
+    /// Which channels are enabled.
+    pub fn channel_flags(&self) -> &str {
+        &self.channel_flags
+    }
+
+    /// Whether the node is collapsed in the layer docker.
+    pub fn collapsed(&self) -> bool {
+        self.collapsed
+    }
+}
+
+/// Properties specific to clone layer.
+#[derive(Debug, ParseTag)]
 pub struct CloneLayerProps {
     //TODO: figure out proper type
+    /// Type of the clone.
     #[XmlAttr(qname = "clonetype", fun_override = "parse_attr(clone_type)?")]
     clone_type: u32,
+    /// Name of the source layer.
     #[XmlAttr(
         qname = "clonefrom",
         pre_parse = "unescape_value()?.into()",
         fun_override = "clone_from"
     )]
     clone_from: String,
+    /// Composition operator.
     #[XmlAttr(qname = "compositeop", fun_override = "parse_attr(composite_op)?")]
     composite_op: CompositeOp,
+    /// Opacity, from 0 to 255.
     #[XmlAttr(fun_override = "parse_attr(opacity)?")]
     opacity: u8,
+    /// UUID of the source layer.
     #[XmlAttr(
         qname = "clonefromuuid",
         pre_parse = "unescape_value()?",
         fun_override = "Uuid::from_str(clone_from_uuid.as_ref())?"
     )]
     clone_from_uuid: Uuid,
+    /// Which channels are enabled.
     #[XmlAttr(
         qname = "channelflags",
         pre_parse = "unescape_value()?.into()",
         fun_override = "channel_flags"
     )]
     channel_flags: String,
+    /// Whether the node is collapsed in the layer docker.
     #[XmlAttr(fun_override = "parse_bool(collapsed)?")]
     collapsed: bool,
 }
 
-// No props beyond common ones
-#[derive(Debug)]
-pub struct TransparencyMaskProps();
+impl CloneLayerProps {
+    /// Type of the clone.
+    pub fn clone_type(&self) -> u32 {
+        self.clone_type
+    }
+
+    /// Name of the source layer.
+    pub fn clone_from(&self) -> &str {
+        &self.clone_from
+    }
+
+    /// Composition operator.
+    pub fn composite_op(&self) -> CompositeOp {
+        self.composite_op
+    }
+
+    /// Opacity, from 0 to 255.
+    pub fn opacity(&self) -> u8 {
+        self.opacity
+    }
+
+    /// UUID of the source layer.
+    pub fn clone_from_uuid(&self) -> Uuid {
+        self.clone_from_uuid
+    }
+
+    /// Which channels are enabled.
+    pub fn channel_flags(&self) -> &str {
+        &self.channel_flags
+    }
+
+    /// Whether the node is collapsed in the layer docker.
+    pub fn collapsed(&self) -> bool {
+        self.collapsed
+    }
+}
+
+/// Properties specific to transparency mask.
+#[derive(Debug, ParseTag)]
+pub struct TransparencyMaskProps {
+    /// Composition operator, which newer versions of Krita may write.
+    #[XmlAttr(
+        qname = "compositeop",
+        optional = true,
+        fun_override = "parse_attr(composite_op)?"
+    )]
+    composite_op: Option<CompositeOp>,
+}
 
 impl TransparencyMaskProps {
-    pub(crate) fn new() -> TransparencyMaskProps {
-        TransparencyMaskProps()
+    /// Composition operator, if the file has it.
+    pub fn composite_op(&self) -> Option<CompositeOp> {
+        self.composite_op
     }
 }
-// Same here
-#[derive(Debug)]
-pub struct TransformMaskProps();
+
+/// Properties specific to transform mask.
+#[derive(Debug, ParseTag)]
+pub struct TransformMaskProps {
+    /// Composition operator, which newer versions of Krita may write.
+    #[XmlAttr(
+        qname = "compositeop",
+        optional = true,
+        fun_override = "parse_attr(composite_op)?"
+    )]
+    composite_op: Option<CompositeOp>,
+}
 
 impl TransformMaskProps {
-    pub(crate) fn new() -> TransformMaskProps {
-        TransformMaskProps()
+    /// Composition operator, if the file has it.
+    pub fn composite_op(&self) -> Option<CompositeOp> {
+        self.composite_op
     }
 }
 
-#[derive(Debug, Getters, ParseTag)]
-#[getset(get = "pub", get_copy = "pub")]
+/// Properties specific to colorize mask.
+#[derive(Debug, ParseTag)]
 pub struct ColorizeMaskProps {
+    /// Whether the coloring is limited to layer bounds.
     #[XmlAttr(
         qname = "limit-to-device",
         fun_override = "parse_bool(limit_to_device)?"
     )]
     limit_to_device: bool,
+    /// Whether the coloring is shown.
     #[XmlAttr(qname = "show-coloring", fun_override = "parse_bool(show_coloring)?")]
     show_coloring: bool,
     //TODO: is it a proper type?
+    /// Cleanup strength.
     #[XmlAttr(fun_override = "parse_attr(cleanup)?")]
     cleanup: u8,
+    /// Whether edge detection is used.
     #[XmlAttr(
         qname = "use-edge-detection",
         fun_override = "parse_bool(use_edge_detection)?"
     )]
     use_edge_detection: bool,
+    /// Size of edge detection.
     #[XmlAttr(
         qname = "edge-detection-size",
         fun_override = "parse_attr(edge_detection_size)?"
     )]
     edge_detection_size: u32,
+    /// Radius of fuzzy selection.
     #[XmlAttr(qname = "fuzzy-radius", fun_override = "parse_attr(fuzzy_radius)?")]
     fuzzy_radius: u32,
+    /// Whether keystrokes are being edited.
     #[XmlAttr(
         qname = "edit-keystrokes",
         fun_override = "parse_bool(edit_keystrokes)?"
     )]
     edit_keystrokes: bool,
+    /// Composition operator.
     #[XmlAttr(qname = "compositeop", fun_override = "parse_attr(composite_op)?")]
     composite_op: CompositeOp,
+    /// Colorspace of the node.
     #[XmlAttr(
         qname = "colorspacename",
         pre_parse = "unescape_value()?",
@@ -744,20 +1927,93 @@ pub struct ColorizeMaskProps {
     colorspace: Colorspace,
 }
 
+impl ColorizeMaskProps {
+    /// Whether the coloring is limited to layer bounds.
+    pub fn limit_to_device(&self) -> bool {
+        self.limit_to_device
+    }
+
+    /// Whether the coloring is shown.
+    pub fn show_coloring(&self) -> bool {
+        self.show_coloring
+    }
+
+    /// Cleanup strength.
+    pub fn cleanup(&self) -> u8 {
+        self.cleanup
+    }
+
+    /// Whether edge detection is used.
+    pub fn use_edge_detection(&self) -> bool {
+        self.use_edge_detection
+    }
+
+    /// Size of edge detection.
+    pub fn edge_detection_size(&self) -> u32 {
+        self.edge_detection_size
+    }
+
+    /// Radius of fuzzy selection.
+    pub fn fuzzy_radius(&self) -> u32 {
+        self.fuzzy_radius
+    }
+
+    /// Whether keystrokes are being edited.
+    pub fn edit_keystrokes(&self) -> bool {
+        self.edit_keystrokes
+    }
+
+    /// Composition operator.
+    pub fn composite_op(&self) -> CompositeOp {
+        self.composite_op
+    }
+
+    /// Colorspace of the node.
+    pub fn colorspace(&self) -> Colorspace {
+        self.colorspace
+    }
+}
+
 // TODO: called shapelayer, mention somewhere
-#[derive(Debug, Getters, ParseTag)]
-#[getset(get = "pub", get_copy = "pub")]
+/// Properties specific to vector layer.
+#[derive(Debug, ParseTag)]
 pub struct VectorLayerProps {
+    /// Composition operator.
     #[XmlAttr(qname = "compositeop", fun_override = "parse_attr(composite_op)?")]
     composite_op: CompositeOp,
+    /// Opacity, from 0 to 255.
     #[XmlAttr(fun_override = "parse_attr(opacity)?")]
     opacity: u8,
+    /// Which channels are enabled.
     #[XmlAttr(
         qname = "channelflags",
         pre_parse = "unescape_value()?.into()",
         fun_override = "channel_flags"
     )]
     channel_flags: String,
+    /// Whether the node is collapsed in the layer docker.
     #[XmlAttr(fun_override = "parse_bool(collapsed)?")]
     collapsed: bool,
 }
+
+impl VectorLayerProps {
+    /// Composition operator.
+    pub fn composite_op(&self) -> CompositeOp {
+        self.composite_op
+    }
+
+    /// Opacity, from 0 to 255.
+    pub fn opacity(&self) -> u8 {
+        self.opacity
+    }
+
+    /// Which channels are enabled.
+    pub fn channel_flags(&self) -> &str {
+        &self.channel_flags
+    }
+
+    /// Whether the node is collapsed in the layer docker.
+    pub fn collapsed(&self) -> bool {
+        self.collapsed
+    }
+}
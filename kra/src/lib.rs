@@ -3,6 +3,9 @@
 //! It can be used for importing files into applications that wish to operate on layers
 //! or metadata.
 //!
+//! Reading and decoding never panic on malformed or malicious files, they fail with errors
+//! instead. To also bound the memory that reading takes, set [`Limits`](config::Limits).
+//!
 //! The library uses GPL-3.0-only license, as portions of it are or will be adapted
 //! from Krita's source code.
 //!
@@ -10,36 +13,98 @@
 
 #![warn(missing_docs)]
 
+pub mod annotation;
+pub mod archive;
+pub mod atlas;
+pub(crate) mod blend;
+pub mod bundle;
+pub mod cache;
+pub mod color;
+pub mod compat;
+pub mod config;
 pub mod data;
+pub mod diff;
+pub mod edit;
 pub mod error;
+#[cfg(feature = "image")]
+pub mod exporter;
+mod filelayer;
+pub mod fingerprint;
+pub mod geom;
 pub(crate) mod helper;
+pub mod id;
 pub mod layer;
+pub mod lint;
 pub mod metadata;
+pub mod model;
+pub mod naming;
+mod occlusion;
+pub mod parse;
+#[cfg(feature = "render")]
+mod render;
+pub mod report;
+pub(crate) mod resample;
+pub mod resources;
+pub mod scan;
+pub mod schema;
+#[cfg(feature = "image")]
+mod sequence;
+pub mod shared;
+mod template;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod textconv;
+pub mod texture;
+pub mod tile;
+#[cfg(feature = "notify")]
+pub mod watch;
+pub mod writer;
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
     fmt::{self, Display},
-    fs::File,
-    io::Read,
-    path::Path,
+    fs::{self, File},
+    io::{self, BufReader, Read, Seek, Write},
+    path::{Path, PathBuf},
+    time::{Instant, SystemTime},
 };
 
-use data::{NodeData, Unloaded};
+use annotation::{parse_pipeline_metadata, write_pipeline_metadata, Annotation, AnnotationContent};
+use archive::{ArchiveSource, DirectorySource, EntryInfo, ZipSource};
+use cache::LayerCache;
+use color::{
+    convert_pixels_with, AlphaMode, ChannelDepth, ChannelType, ColorModel, ConvertOptions,
+    Transfer, F16,
+};
+use config::{DuplicateUuids, MimetypeCheck, ParsingConfiguration, UnknownAttributes};
+use data::{
+    CropSuggestion, EmptyLayers, LayerData, MergedImageSource, NodeData, PixelBuffer, Unloaded,
+};
+use edit::MaindocSource;
 use error::{
-    MaskExpected, MetadataErrorReason, ReadKraError, UnknownColorspace, UnknownLayerType, XmlError,
+    DataError, DoctypeMismatch, DuplicateUuid, LimitError, MaskExpected, MetadataErrorReason,
+    ReadKraError, ReplacedUtf8, UnexpectedMimetype, UnknownAttribute, UnknownColorspace,
+    WriteError, XmlError,
 };
-use getset::Getters;
+use geom::{IPoint, IRect};
 use helper::{
-    event_get_attr, event_to_string, event_unwrap_as_end, event_unwrap_as_start, next_xml_event,
+    event_get_all_attrs, event_get_attr, event_to_string, event_unwrap_as_start, next_xml_event,
+    normalize_archive_path, raw_since, set_attr,
 };
+use id::Uuid;
 use layer::{
-    CloneLayerProps, ColorizeMaskProps, CommonNodeProps, FileLayerProps, FillLayerProps,
-    FilterLayerProps, FilterMaskProps, GroupLayerProps, Node, NodeType, PaintLayerProps,
-    SelectionMaskProps, TransformMaskProps, TransparencyMaskProps, VectorLayerProps,
+    CloneLayerProps, ColorLabel, ColorizeMaskProps, CommonNodeProps, FileLayerProps,
+    FillLayerProps, FilterLayerProps, FilterMaskProps, GroupLayerProps, Node, NodeKind, NodeType,
+    PaintLayerProps, SelectionMaskProps, TransformMaskProps, TransparencyMaskProps,
+    VectorLayerProps,
 };
-use metadata::{KraMetadata, KraMetadataEnd, KraMetadataStart};
-use uuid::Uuid;
-use zip::ZipArchive;
+use metadata::{KraMetadata, Summary};
+use naming::{NameTemplate, SanitizeOptions, TemplateValues, UniqueNames};
+use parse::{parse_document_with_report, Document};
+use report::{phase_start, ReadReport};
+use tile::{TileHeader, TiledData};
+use writer::{Compression, OptimizeOptions, WriteOptions};
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
 
 use quick_xml::events::{BytesStart, Event};
 use quick_xml::Reader as XmlReader;
@@ -51,229 +116,1803 @@ use crate::metadata::DocumentInfo;
 #[non_exhaustive]
 #[derive(Default)]
 pub enum Colorspace {
-    /// Default RGBA colorspace.
+    /// Default RGBA colorspace, 8 bits per channel.
     #[default]
     RGBA,
+    /// RGBA, 16 bits per channel.
+    RGBA16,
+    /// RGBA, 16-bit floating point channels.
+    RGBAF16,
+    /// RGBA, 32-bit floating point channels.
+    RGBAF32,
+    /// Grayscale with alpha, 8 bits per channel.
+    GRAYA,
+    /// Grayscale with alpha, 16 bits per channel.
+    GRAYAU16,
+    /// Grayscale with alpha, 16-bit floating point channels.
+    GRAYAF16,
+    /// Grayscale with alpha, 32-bit floating point channels.
+    GRAYAF32,
+    /// CMYK with alpha, 8 bits per channel.
+    CMYKA,
+    /// CMYK with alpha, 16 bits per channel.
+    CMYKA16,
+    /// CMYK with alpha, 32-bit floating point channels.
+    CMYKAF32,
+    /// L*a*b* with alpha, 8 bits per channel.
+    LABA,
+    /// L*a*b* with alpha, 16 bits per channel.
+    LABAU16,
+    /// L*a*b* with alpha, 32-bit floating point channels.
+    LABAF32,
+    /// XYZ with alpha, 8 bits per channel.
+    XYZA8,
+    /// XYZ with alpha, 16 bits per channel.
+    XYZA16,
+    /// XYZ with alpha, 16-bit floating point channels.
+    XYZAF16,
+    /// XYZ with alpha, 32-bit floating point channels.
+    XYZAF32,
+    /// YCbCr with alpha, 8 bits per channel.
+    YCBCRA8,
+    /// YCbCr with alpha, 16 bits per channel.
+    YCBCRAU16,
+    /// YCbCr with alpha, 32-bit floating point channels.
+    YCBCRAF32,
 }
 
+// Identifiers that Krita writes into `colorspacename`
+const COLORSPACE_IDS: [(Colorspace, &str); 21] = [
+    (Colorspace::RGBA, "RGBA"),
+    (Colorspace::RGBA16, "RGBA16"),
+    (Colorspace::RGBAF16, "RGBAF16"),
+    (Colorspace::RGBAF32, "RGBAF32"),
+    (Colorspace::GRAYA, "GRAYA"),
+    (Colorspace::GRAYAU16, "GRAYAU16"),
+    (Colorspace::GRAYAF16, "GRAYAF16"),
+    (Colorspace::GRAYAF32, "GRAYAF32"),
+    (Colorspace::CMYKA, "CMYK"),
+    (Colorspace::CMYKA16, "CMYKA16"),
+    (Colorspace::CMYKAF32, "CMYKAF32"),
+    (Colorspace::LABA, "LABA"),
+    (Colorspace::LABAU16, "LABAU16"),
+    (Colorspace::LABAF32, "LABAF32"),
+    (Colorspace::XYZA8, "XYZA8"),
+    (Colorspace::XYZA16, "XYZA16"),
+    (Colorspace::XYZAF16, "XYZAF16"),
+    (Colorspace::XYZAF32, "XYZAF32"),
+    (Colorspace::YCBCRA8, "YCBCRA8"),
+    (Colorspace::YCBCRAU16, "YCBCRAU16"),
+    (Colorspace::YCBCRAF32, "YCBCRF32"),
+];
+
+// Names from older versions of Krita, following its convertColorSpaceNames()
+const COLORSPACE_ALIASES: [(&str, Colorspace); 13] = [
+    ("RGB", Colorspace::RGBA),
+    ("RgbAF16", Colorspace::RGBAF16),
+    ("RgbAF32", Colorspace::RGBAF32),
+    ("Grayscale + Alpha", Colorspace::GRAYA),
+    ("GRAYA16", Colorspace::GRAYAU16),
+    ("GrayF16", Colorspace::GRAYAF16),
+    ("GrayF32", Colorspace::GRAYAF32),
+    ("CMYKA", Colorspace::CMYKA),
+    ("LABA16", Colorspace::LABAU16),
+    ("XyzAF16", Colorspace::XYZAF16),
+    ("XyzAF32", Colorspace::XYZAF32),
+    ("YCbCrA", Colorspace::YCBCRA8),
+    ("YCbCrAU16", Colorspace::YCBCRAU16),
+];
+
 impl TryFrom<&str> for Colorspace {
     type Error = UnknownColorspace;
 
+    /// Parses Krita's colorspace identifier, also accepting names used by older versions.
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        match value {
-            "RGBA" => Ok(Colorspace::RGBA),
-            other => Err(UnknownColorspace(other.to_owned())),
+        COLORSPACE_IDS
+            .iter()
+            .find(|(_, id)| *id == value)
+            .map(|(colorspace, _)| *colorspace)
+            .or_else(|| {
+                COLORSPACE_ALIASES
+                    .iter()
+                    .find(|(alias, _)| *alias == value)
+                    .map(|(_, colorspace)| *colorspace)
+            })
+            .ok_or_else(|| UnknownColorspace(value.to_owned()))
+    }
+}
+
+impl Colorspace {
+    /// Depth of each channel.
+    pub fn depth(&self) -> ChannelDepth {
+        match self {
+            Colorspace::RGBA
+            | Colorspace::GRAYA
+            | Colorspace::CMYKA
+            | Colorspace::LABA
+            | Colorspace::XYZA8
+            | Colorspace::YCBCRA8 => ChannelDepth::U8,
+            Colorspace::RGBA16
+            | Colorspace::GRAYAU16
+            | Colorspace::CMYKA16
+            | Colorspace::LABAU16
+            | Colorspace::XYZA16
+            | Colorspace::YCBCRAU16 => ChannelDepth::U16,
+            Colorspace::RGBAF16 | Colorspace::GRAYAF16 | Colorspace::XYZAF16 => ChannelDepth::F16,
+            Colorspace::RGBAF32
+            | Colorspace::GRAYAF32
+            | Colorspace::CMYKAF32
+            | Colorspace::LABAF32
+            | Colorspace::XYZAF32
+            | Colorspace::YCBCRAF32 => ChannelDepth::F32,
+        }
+    }
+
+    /// Color model, regardless of channel depth.
+    pub fn model(&self) -> ColorModel {
+        match self {
+            Colorspace::RGBA | Colorspace::RGBA16 | Colorspace::RGBAF16 | Colorspace::RGBAF32 => {
+                ColorModel::Rgb
+            }
+            Colorspace::GRAYA
+            | Colorspace::GRAYAU16
+            | Colorspace::GRAYAF16
+            | Colorspace::GRAYAF32 => ColorModel::Gray,
+            Colorspace::CMYKA | Colorspace::CMYKA16 | Colorspace::CMYKAF32 => ColorModel::Cmyk,
+            Colorspace::LABA | Colorspace::LABAU16 | Colorspace::LABAF32 => ColorModel::Lab,
+            Colorspace::XYZA8 | Colorspace::XYZA16 | Colorspace::XYZAF16 | Colorspace::XYZAF32 => {
+                ColorModel::Xyz
+            }
+            Colorspace::YCBCRA8 | Colorspace::YCBCRAU16 | Colorspace::YCBCRAF32 => {
+                ColorModel::YCbCr
+            }
+        }
+    }
+
+    /// Number of channels, including alpha.
+    pub fn channel_count(&self) -> usize {
+        match self.model() {
+            ColorModel::Gray => 2,
+            ColorModel::Cmyk => 5,
+            _ => 4,
+        }
+    }
+
+    /// Size of one pixel, in bytes.
+    pub fn pixel_size(&self) -> usize {
+        self.channel_count() * self.depth().size()
+    }
+
+    // Whether the pixel, stored in this colorspace, is fully transparent.
+    // Alpha is always the last channel.
+    pub(crate) fn is_transparent(&self, pixel: &[u8]) -> bool {
+        let size = self.depth().size();
+        let Some(alpha) = pixel
+            .len()
+            .checked_sub(size)
+            .and_then(|start| pixel.get(start..))
+        else {
+            return true;
+        };
+        fn is_zero<T: ChannelType>(alpha: &[u8]) -> bool {
+            T::from_le_bytes(alpha) <= T::ZERO
+        }
+        match self.depth() {
+            ChannelDepth::U8 => is_zero::<u8>(alpha),
+            ChannelDepth::U16 => is_zero::<u16>(alpha),
+            ChannelDepth::F16 => is_zero::<F16>(alpha),
+            ChannelDepth::F32 => is_zero::<f32>(alpha),
+        }
+    }
+
+    // Whether the pixel's alpha channel is at its maximum or above it
+    pub(crate) fn is_opaque(&self, pixel: &[u8]) -> bool {
+        let size = self.depth().size();
+        let Some(alpha) = pixel
+            .len()
+            .checked_sub(size)
+            .and_then(|start| pixel.get(start..))
+        else {
+            return false;
+        };
+        fn is_max<T: ChannelType>(alpha: &[u8]) -> bool {
+            T::from_le_bytes(alpha).to_f32() >= 1.0
+        }
+        match self.depth() {
+            ChannelDepth::U8 => is_max::<u8>(alpha),
+            ChannelDepth::U16 => is_max::<u16>(alpha),
+            ChannelDepth::F16 => is_max::<F16>(alpha),
+            ChannelDepth::F32 => is_max::<f32>(alpha),
         }
     }
 }
 
 impl Display for Colorspace {
+    /// Writes Krita's identifier of the colorspace.
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Colorspace::RGBA => write!(f, "RGBA"),
-        }
+        let (_, id) = COLORSPACE_IDS
+            .iter()
+            .find(|(colorspace, _)| colorspace == self)
+            .expect("every colorspace has an identifier");
+        write!(f, "{}", id)
     }
 }
 
 /// A .kra file.
-#[derive(Debug, Getters)]
-#[getset(get = "pub")]
+#[derive(Debug)]
 pub struct KraFile {
-    file: Option<ZipArchive<File>>,
+    /// Archive the document was read from.
+    file: Option<Box<dyn ArchiveSource>>,
+    /// Image metadata.
     meta: KraMetadata,
+    /// Document information.
     doc_info: DocumentInfo,
+    /// Top-level layers.
     layers: Vec<Node>,
-    files: HashMap<Uuid, NodeData>,
+    /// Data of every node, by its UUID.
+    files: BTreeMap<Uuid, NodeData>,
+    /// Configuration that was used to read the file.
+    config: ParsingConfiguration,
+    /// Archive entries that belong to every node (pixel data, default pixel, profile,
+    /// keyframes, filter configuration...), by its UUID. Entry names are sorted.
+    node_entries: BTreeMap<Uuid, Vec<String>>,
+    /// Entries of the `layers/` directory that do not belong to any node, like the ones
+    /// left behind after deleting layers. Empty if nodes were not parsed.
+    orphaned_entries: Vec<String>,
+    /// Attributes of nodes that are not read into the model. Only collected with
+    /// [`UnknownAttributes::Warn`].
+    unknown_attributes: Vec<UnknownAttribute>,
+    /// `DOCTYPE`s of XML entries that are not the ones Krita writes. Only collected with
+    /// [`DoctypeCheck::Warn`](config::DoctypeCheck::Warn).
+    doctype_mismatches: Vec<DoctypeMismatch>,
+    /// `mimetype` entry that is missing or is not the one Krita writes. Only kept with
+    /// [`MimetypeCheck::Warn`].
+    unexpected_mimetype: Option<UnexpectedMimetype>,
+    /// XML entries whose invalid UTF-8 was replaced. Only collected with
+    /// [`InvalidUtf8::Replace`](config::InvalidUtf8::Replace).
+    replaced_utf8: Vec<ReplacedUtf8>,
+    /// Nodes whose UUID is used by a node before them, which were left out or got new UUIDs.
+    duplicate_uuids: Vec<DuplicateUuid>,
+    /// Directory that sources of file layers are relative to.
+    base_dir: Option<PathBuf>,
+    // Canonical paths of the documents that link to this one through file layers, from
+    // the outermost one, and of this one, when they were read from paths
+    link_chain: Vec<PathBuf>,
+    // How many links away from the document that was read first
+    link_depth: usize,
+    // Entries that replace (or are added to) the ones of the archive when writing
+    pending_entries: BTreeMap<String, Vec<u8>>,
+    source: MaindocSource,
+    cache: LayerCache,
+    // When the document was read, for the editing time
+    opened: Instant,
     //TODO: use `png` crate
 }
 
 impl KraFile {
+    /// Archive the document was read from.
+    pub fn file(&self) -> Option<&dyn ArchiveSource> {
+        self.file.as_deref()
+    }
+
+    /// Image metadata.
+    pub fn meta(&self) -> &KraMetadata {
+        &self.meta
+    }
+
+    /// Document information.
+    pub fn doc_info(&self) -> &DocumentInfo {
+        &self.doc_info
+    }
+
+    /// Top-level layers.
+    pub fn layers(&self) -> &[Node] {
+        &self.layers
+    }
+
+    /// Number of nodes in the document, masks included. Counted without allocating.
+    pub fn node_count(&self) -> usize {
+        self.layers
+            .iter()
+            .map(|node| 1 + node.descendant_count())
+            .sum()
+    }
+
+    /// Overview of the document, for listings and tooltips.
+    pub fn summary(&self) -> Summary {
+        let nodes = self.all_nodes();
+        Summary {
+            name: self.meta.name().to_owned(),
+            dimensions: (self.meta.width(), self.meta.height()),
+            colorspace: self.meta.colorspace(),
+            layer_count: nodes.iter().filter(|node| !node.kind().is_mask()).count(),
+            has_animation: nodes
+                .iter()
+                .flat_map(|node| node.attributes())
+                .any(|(key, value)| key == "keyframes" && !value.is_empty()),
+            krita_version: self.meta.krita_version().to_owned(),
+            author: self.doc_info.author().full_name().to_owned(),
+        }
+    }
+
+    /// Data of every node, by its UUID. Iteration is ordered by UUID, not by document
+    /// order; use [`all_nodes()`](Self::all_nodes) for that.
+    pub fn files(&self) -> &BTreeMap<Uuid, NodeData> {
+        &self.files
+    }
+
+    /// Configuration that was used to read the file.
+    pub fn config(&self) -> &ParsingConfiguration {
+        &self.config
+    }
+
+    /// Archive entries that belong to every node (pixel data, default pixel, profile,
+    /// keyframes, filter configuration...), by its UUID. Iteration is ordered by UUID,
+    /// and entry names are sorted.
+    pub fn node_entries(&self) -> &BTreeMap<Uuid, Vec<String>> {
+        &self.node_entries
+    }
+
+    /// Entries of the `layers/` directory that do not belong to any node, like the ones
+    /// left behind after deleting layers. Empty if nodes were not parsed.
+    pub fn orphaned_entries(&self) -> &[String] {
+        &self.orphaned_entries
+    }
+
+    /// Attributes of nodes that are not read into the model. Only collected with
+    /// [`UnknownAttributes::Warn`].
+    pub fn unknown_attributes(&self) -> &[UnknownAttribute] {
+        &self.unknown_attributes
+    }
+
+    /// `DOCTYPE`s of XML entries that are not the ones Krita writes. Only collected with
+    /// [`DoctypeCheck::Warn`](config::DoctypeCheck::Warn).
+    pub fn doctype_mismatches(&self) -> &[DoctypeMismatch] {
+        &self.doctype_mismatches
+    }
+
+    /// `mimetype` entry that is missing or is not the one Krita writes. Only kept with
+    /// [`MimetypeCheck::Warn`].
+    pub fn unexpected_mimetype(&self) -> Option<&UnexpectedMimetype> {
+        self.unexpected_mimetype.as_ref()
+    }
+
+    /// XML entries whose invalid UTF-8 was replaced. Only collected with
+    /// [`InvalidUtf8::Replace`](config::InvalidUtf8::Replace).
+    pub fn replaced_utf8(&self) -> &[ReplacedUtf8] {
+        &self.replaced_utf8
+    }
+
+    /// Nodes whose UUID is used by a node before them, which were left out or got new
+    /// UUIDs, see [`DuplicateUuids`](config::DuplicateUuids).
+    pub fn duplicate_uuids(&self) -> &[DuplicateUuid] {
+        &self.duplicate_uuids
+    }
+
+    /// Directory that sources of file layers are relative to. It is the directory that
+    /// contains the file (or the unpacked document) when it is read from a path, and
+    /// `None` otherwise.
+    pub fn base_dir(&self) -> Option<&Path> {
+        self.base_dir.as_deref()
+    }
+
+    /// Set the directory that sources of file layers are relative to.
+    pub fn set_base_dir(&mut self, base_dir: Option<PathBuf>) {
+        self.base_dir = base_dir;
+    }
+
+    /// Sizes and modification times of all entries, in archive order, read without reading
+    /// their contents. Entries that were changed in memory come with their new size,
+    /// and new ones come last.
+    ///
+    /// Directories of zip archives are left out.
+    pub fn entry_info(&mut self) -> Result<Vec<EntryInfo>, DataError> {
+        let changed = |name: &str, data: &Vec<u8>| {
+            let mut info = EntryInfo::new(name, data.len() as u64);
+            info.changed = true;
+            info
+        };
+        let archive = self.file.as_mut().ok_or(DataError::NoArchive)?;
+        let names = archive.entry_names().to_vec();
+        let mut out = Vec::with_capacity(names.len());
+        for name in names.iter().filter(|name| !name.ends_with('/')) {
+            match self.pending_entries.get(name) {
+                Some(data) => out.push(changed(name, data)),
+                None => out.push(archive.entry_info(name)?),
+            }
+        }
+        for (name, data) in &self.pending_entries {
+            if !names.contains(name) {
+                out.push(changed(name, data));
+            }
+        }
+        Ok(out)
+    }
+
+    /// When the document was last saved, as written by Krita into `documentinfo.xml`,
+    /// like `2024-01-01T00:00:00`. `None` if it is not there.
+    pub fn saved_at(&self) -> Option<&str> {
+        Some(self.doc_info.about().date()).filter(|date| !date.is_empty())
+    }
+
     //TODO: the function should load all files except mergedimage and preview,
     // and including file layers, and does not store the file.
-    // TODO: builder for customised read()
     // TODO: mention all of this in the documentation.
-    /// Open and parse `.kra` file.
+    /// Open and parse `.kra` file, or a directory with an unpacked one.
     pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, ReadKraError> {
-        let file = File::open(path)?;
-        let mut zip = ZipArchive::new(file)?;
-
-        //Replacement of try_collect(), which is unstable
-        let mimetype: Vec<u8> = zip
-            .by_name("mimetype")?
-            .bytes()
-            .into_iter()
-            .collect::<Result<Vec<_>, _>>()?;
-        if mimetype.as_slice() != r"application/x-krita".as_bytes() {
-            return Err(ReadKraError::MimetypeMismatch);
-        }
+        Self::read_with_config(path, ParsingConfiguration::default())
+    }
 
-        let mut doc_info = String::new();
-        zip.by_name("documentinfo.xml")?
-            .read_to_string(&mut doc_info)?;
-        let mut doc_info = XmlReader::from_str(doc_info.as_str());
+    /// Open and parse `.kra` file, using the given configuration.
+    ///
+    /// If `path` is a directory, it is read as an unpacked document
+    /// (see [`DirectorySource`]).
+    pub fn read_with_config<P: AsRef<Path>>(
+        path: P,
+        config: ParsingConfiguration,
+    ) -> Result<Self, ReadKraError> {
+        Self::open(path.as_ref(), config, None)
+    }
 
-        doc_info.trim_text(true);
-        let doc_info = DocumentInfo::from_xml(&mut doc_info)
-            .map_err(|err| err.to_metadata_error("documentinfo.xml".into(), &doc_info))?;
+    /// Open and parse `.kra` file (or a directory), measuring how long each phase takes.
+    ///
+    /// Meant for finding out why some file is slow to open.
+    pub fn read_with_report<P: AsRef<Path>>(
+        path: P,
+        config: ParsingConfiguration,
+    ) -> Result<(Self, ReadReport), ReadKraError> {
+        let mut report = ReadReport::default();
+        let file = Self::open(path.as_ref(), config, Some(&mut report))?;
+        Ok((file, report))
+    }
 
-        let mut maindoc = String::new();
-        zip.by_name("maindoc.xml")?.read_to_string(&mut maindoc)?;
-        let mut maindoc = XmlReader::from_str(maindoc.as_str());
+    fn open(
+        path: &Path,
+        config: ParsingConfiguration,
+        mut report: Option<&mut ReadReport>,
+    ) -> Result<Self, ReadKraError> {
+        let start = phase_start(&report);
+        let mut kra = if path.is_dir() {
+            let archive = DirectorySource::open(path)?;
+            if let Some(report) = report.as_deref_mut() {
+                report.zip_io.record(start, 0);
+            }
+            Self::read_from_with_report(archive, config, report)?
+        } else {
+            let file = File::open(path)?;
+            let archive = ZipSource::from_file(file, path)?;
+            if let Some(report) = report.as_deref_mut() {
+                report.zip_io.record(start, 0);
+            }
+            Self::read_from_with_report(archive, config, report)?
+        };
+        kra.base_dir = path.parent().map(Path::to_path_buf);
+        kra.link_chain.extend(fs::canonicalize(path).ok());
+        Ok(kra)
+    }
 
-        maindoc.trim_text(true);
-        let meta_start = KraMetadataStart::from_xml(&mut maindoc)
-            .map_err(|err| err.to_metadata_error("maindoc.xml".into(), &maindoc))?;
+    /// Parse a document whose entries come from `archive`, using the given configuration.
+    pub fn read_from<A: ArchiveSource + 'static>(
+        archive: A,
+        config: ParsingConfiguration,
+    ) -> Result<Self, ReadKraError> {
+        Self::read_from_with_report(archive, config, None)
+    }
 
-        let mut files = HashMap::new();
+    fn read_from_with_report<A: ArchiveSource + 'static>(
+        mut archive: A,
+        config: ParsingConfiguration,
+        mut report: Option<&mut ReadReport>,
+    ) -> Result<Self, ReadKraError> {
+        let start = phase_start(&report);
+        let limits = config.limits;
+        let entry_names = archive.entry_names().to_vec();
 
-        let layers = get_layers(&mut maindoc, &mut files)
-            .map_err(|err| err.to_metadata_error("maindoc".into(), &maindoc))?;
+        let check = config.mimetype_check;
+        let mimetype = match check {
+            MimetypeCheck::Warn if !entry_names.iter().any(|name| name == "mimetype") => None,
+            _ => Some(read_document_entry(
+                &mut archive,
+                "mimetype",
+                limits.max_entry_size,
+            )?),
+        };
+        let unexpected_mimetype = match mimetype.as_deref() {
+            Some(b"application/x-krita") => None,
+            _ if check == MimetypeCheck::Error => return Err(ReadKraError::MimetypeMismatch),
+            found => Some(UnexpectedMimetype {
+                found: found.map(|found| String::from_utf8_lossy(found).into_owned()),
+            }),
+        };
 
-        let meta_end = KraMetadataEnd::from_xml(&mut maindoc)
-            .map_err(|err| err.to_metadata_error("maindoc.xml".into(), &maindoc))?;
+        let doc_info = read_document_entry(&mut archive, "documentinfo.xml", limits.max_xml_size)?;
+        let maindoc = read_document_entry(&mut archive, "maindoc.xml", limits.max_xml_size)?;
+        let bytes = mimetype.map_or(0, |mimetype| mimetype.len()) + doc_info.len() + maindoc.len();
+        if let Some(report) = report.as_deref_mut() {
+            report.zip_io.record(start, bytes as u64);
+        }
 
-        let meta = KraMetadata::new(meta_start, meta_end);
+        let Document {
+            meta,
+            doc_info,
+            layers,
+            files,
+            unknown_attributes,
+            doctype_mismatches,
+            replaced_utf8,
+            duplicate_uuids,
+            source,
+        } = parse_document_with_report(&maindoc, &doc_info, &config, report.as_deref_mut())?;
 
-        Ok(KraFile {
-            file: None,
+        let cache = LayerCache::new(config.cache_budget);
+        let mut kra = KraFile {
+            file: config
+                .keep_archive
+                .then(|| Box::new(archive) as Box<dyn ArchiveSource>),
             meta,
             doc_info,
             layers,
             files,
-        })
+            config,
+            node_entries: BTreeMap::new(),
+            orphaned_entries: Vec::new(),
+            unknown_attributes,
+            doctype_mismatches,
+            unexpected_mimetype,
+            replaced_utf8,
+            duplicate_uuids,
+            base_dir: None,
+            link_chain: Vec::new(),
+            link_depth: 0,
+            pending_entries: BTreeMap::new(),
+            source,
+            cache,
+            opened: Instant::now(),
+        };
+        let start = phase_start(&report);
+        (kra.node_entries, kra.orphaned_entries) = kra.map_node_entries(&entry_names);
+        if let Some(report) = report {
+            report.data_load.record(start, 0);
+        }
+        Ok(kra)
     }
-}
 
-//Starts immed. before the required <layer> | <layer/> | <mask> | <mask/>
-fn parse_layer(
-    reader: &mut XmlReader<&[u8]>,
-    files: &mut HashMap<Uuid, NodeData>,
-) -> Result<Node, MetadataErrorReason> {
-    let event = next_xml_event(reader)?;
+    // Finds entries in the layers/ directory whose names start with the node's filename,
+    // followed by nothing, an extension or a subdirectory. Other entries there are orphaned.
+    fn map_node_entries(
+        &self,
+        entry_names: &[String],
+    ) -> (BTreeMap<Uuid, Vec<String>>, Vec<String>) {
+        let nodes = self.all_nodes();
+        let mut entries: BTreeMap<Uuid, Vec<String>> =
+            nodes.iter().map(|node| (node.uuid(), Vec::new())).collect();
+        let mut orphaned = Vec::new();
+        let Ok(image_name) = normalize_archive_path(self.meta.name()) else {
+            return (entries, orphaned);
+        };
+        let prefix = format!("{}/layers/", image_name);
+        let by_filename: HashMap<&str, Uuid> = nodes
+            .iter()
+            .filter(|node| !node.filename().is_empty())
+            .map(|node| (node.filename(), node.uuid()))
+            .collect();
 
-    // If the event is not empty, and it is not a group layer, it contains masks
-    let could_contain_masks = match event {
-        Event::Start(..) => true,
-        _ => false,
-    };
+        for name in entry_names {
+            let Some(rest) = name.strip_prefix(&prefix) else {
+                continue;
+            };
+            let filename = rest.split(['.', '/']).next().unwrap_or_default();
+            match by_filename.get(filename) {
+                Some(uuid) => entries.entry(*uuid).or_default().push(name.clone()),
+                // Without parsed nodes, nothing can be said to belong to them
+                None if self.config.parse_layers => orphaned.push(name.clone()),
+                None => {}
+            }
+        }
+        for names in entries.values_mut() {
+            names.sort();
+        }
+        orphaned.sort();
+        (entries, orphaned)
+    }
 
-    let tag: BytesStart = match event {
-        Event::Start(t) | Event::Empty(t) => t,
-        other => {
-            return Err(
-                XmlError::EventError("layer/mask start event", event_to_string(&other)?).into(),
-            );
+    /// Find paint layers that have no painted content, and hidden paint layers that do.
+    ///
+    /// Only the presence of tiles (and the default pixel) is checked, pixel data is not decoded.
+    /// Because of that, a layer that was painted on and then erased is still considered to
+    /// have content.
+    pub fn find_empty_layers(&mut self) -> Result<EmptyLayers, DataError> {
+        let paint_layers: Vec<(Uuid, String, bool, Colorspace)> = self
+            .all_nodes()
+            .into_iter()
+            .filter_map(|node| match node.node_type() {
+                NodeType::PaintLayer(props) => Some((
+                    node.uuid(),
+                    node.filename().to_owned(),
+                    node.visible(),
+                    props.colorspace(),
+                )),
+                _ => None,
+            })
+            .collect();
+
+        let mut report = EmptyLayers::default();
+        for (uuid, filename, visible, colorspace) in paint_layers {
+            let has_content = self.has_content(&filename, colorspace)?;
+            match (has_content, visible) {
+                (false, _) => report.empty.push(uuid),
+                (true, false) => report.hidden_with_content.push(uuid),
+                (true, true) => {}
+            }
         }
-    };
+        Ok(report)
+    }
 
-    let common = CommonNodeProps::parse_tag(&tag)?;
+    // Checks whether node's pixel data has tiles or non-transparent default pixel
+    fn has_content(&mut self, filename: &str, colorspace: Colorspace) -> Result<bool, DataError> {
+        let path = self.layer_entry_path(filename)?;
+        let header = match self.pending_entries.get(&path) {
+            Some(pending) => TileHeader::read(&mut pending.as_slice())?,
+            None => {
+                let zip = self.file.as_mut().ok_or(DataError::NoArchive)?;
+                let entry = zip
+                    .by_name(&path)
+                    .map_err(|err| entry_error(&path, err.into()))?;
+                TileHeader::read(&mut BufReader::new(entry))
+                    .map_err(|err| entry_error(&path, err))?
+            }
+        };
+        if header.tile_count > 0 {
+            return Ok(true);
+        }
+        Ok(self
+            .read_default_pixel(filename)?
+            .is_some_and(|pixel| !colorspace.is_transparent(&pixel)))
+    }
+
+    /// Bounds of non-transparent pixels of a paint layer.
+    ///
+    /// The bounds are in image coordinates and can extend beyond the canvas.
+    /// Returns `None` if the layer is fully transparent.
+    pub fn exact_bounds(&mut self, uuid: &Uuid) -> Result<Option<IRect>, DataError> {
+        let node = self
+            .all_nodes()
+            .into_iter()
+            .find(|node| node.uuid() == *uuid)
+            .ok_or(DataError::UnknownNode(*uuid))?;
+        let (filename, x, y, colorspace) = match node.node_type() {
+            NodeType::PaintLayer(props) => (
+                node.filename().to_owned(),
+                node.x(),
+                node.y(),
+                props.colorspace(),
+            ),
+            _ => return Err(DataError::NoPixelData(*uuid)),
+        };
+        self.layer_bounds(&filename, x, y, colorspace)
+    }
 
-    let node_type = event_get_attr(&tag, "nodetype")?.unescape_value()?;
-    let node_type = match node_type.as_ref() {
-        //TODO: finish (Selection mask) and verify
-        "grouplayer" => {
-            files.insert(common.uuid().to_owned(), NodeData::DoesNotExist);
-            NodeType::GroupLayer(GroupLayerProps::parse_tag(&tag, reader, files)?)
+    /// Suggest a smaller canvas that still contains everything that is visible.
+    ///
+    /// The area is the union of exact bounds of all visible paint layers, limited to the
+    /// current canvas. Returns `None` if nothing is visible.
+    pub fn suggest_crop(&mut self) -> Result<Option<CropSuggestion>, DataError> {
+        fn walk(nodes: &[Node], out: &mut Vec<(String, i32, i32, Colorspace)>) {
+            for node in nodes.iter().filter(|node| node.visible()) {
+                match node.node_type() {
+                    NodeType::PaintLayer(props) => out.push((
+                        node.filename().to_owned(),
+                        node.x(),
+                        node.y(),
+                        props.colorspace(),
+                    )),
+                    NodeType::GroupLayer(props) => walk(props.layers(), out),
+                    _ => {}
+                }
+            }
         }
-        "paintlayer" => {
-            files.insert(
-                common.uuid().to_owned(),
-                NodeData::Unloaded(Unloaded::Image),
-            );
-            NodeType::PaintLayer(PaintLayerProps::parse_tag(&tag)?)
+        let mut visible = Vec::new();
+        walk(&self.layers, &mut visible);
+
+        let mut union = IRect::default();
+        for (filename, x, y, colorspace) in visible {
+            if let Some(bounds) = self.layer_bounds(&filename, x, y, colorspace)? {
+                union = union.union(&bounds);
+            }
         }
-        "filtermask" => {
-            files.insert(
-                common.uuid().to_owned(),
-                NodeData::Unloaded(Unloaded::Filter),
-            );
-            NodeType::FilterMask(FilterMaskProps::parse_tag(&tag)?)
+
+        let canvas = IRect::new(0, 0, self.meta.width(), self.meta.height());
+        Ok(union.intersection(&canvas).map(|rect| CropSuggestion {
+            x: rect.x() as u32,
+            y: rect.y() as u32,
+            width: rect.width(),
+            height: rect.height(),
+        }))
+    }
+
+    /// Decode pixel data of a paint layer.
+    ///
+    /// The result covers all stored tiles of the layer, areas between them are filled
+    /// with the default pixel. It is cached if
+    /// [`ParsingConfiguration::cache_budget()`] allows.
+    pub fn decode_layer(&mut self, uuid: &Uuid) -> Result<LayerData, DataError> {
+        let node = self
+            .all_nodes()
+            .into_iter()
+            .find(|node| node.uuid() == *uuid)
+            .ok_or(DataError::UnknownNode(*uuid))?;
+        let (filename, x, y, colorspace) = match node.node_type() {
+            NodeType::PaintLayer(props) => (
+                node.filename().to_owned(),
+                node.x(),
+                node.y(),
+                props.colorspace(),
+            ),
+            _ => return Err(DataError::NoPixelData(*uuid)),
+        };
+
+        if let Some(data) = self.cache.get(uuid) {
+            return Ok(data.clone());
         }
-        "filelayer" => {
-            files.insert(common.uuid().to_owned(), NodeData::DoesNotExist);
-            NodeType::FileLayer(FileLayerProps::parse_tag(&tag)?)
+
+        let data = decode_tiles(
+            self.read_tiled_data(&filename)?,
+            (x, y),
+            colorspace,
+            self.config.limits.max_pixels,
+        )?;
+        self.cache.insert(*uuid, data.clone());
+        Ok(data)
+    }
+
+    /// Forget decoded layers, see [`ParsingConfiguration::cache_budget()`].
+    pub fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
+
+    /// Whether pixels of the node are in a colorspace other than the image's one, and need
+    /// to be converted before compositing them with the rest of the image.
+    ///
+    /// Nodes without a colorspace never need conversion.
+    pub fn needs_conversion(&self, node: &Node) -> bool {
+        node.node_type()
+            .colorspace()
+            .is_some_and(|colorspace| colorspace != self.meta.colorspace())
+    }
+
+    /// Decode pixel data of a paint layer, converting it into the image's colorspace.
+    ///
+    /// Only changes of channel depth, and conversions between RGB and grayscale are
+    /// supported. Color profiles are not taken into account: values are only rescaled,
+    /// see [`decode_layer_converted_with()`](Self::decode_layer_converted_with) to apply
+    /// a transfer function.
+    pub fn decode_layer_converted(&mut self, uuid: &Uuid) -> Result<LayerData, DataError> {
+        self.decode_layer_converted_with(uuid, &ConvertOptions::new())
+    }
+
+    /// Decode pixel data of a paint layer with the given alpha mode, see
+    /// [`LayerData::into_alpha_mode()`].
+    pub fn decode_layer_with_alpha(
+        &mut self,
+        uuid: &Uuid,
+        alpha_mode: AlphaMode,
+    ) -> Result<LayerData, DataError> {
+        Ok(self.decode_layer(uuid)?.into_alpha_mode(alpha_mode))
+    }
+
+    /// Same as [`decode_layer_converted()`](Self::decode_layer_converted), with the given
+    /// alpha mode and transfer function. They are applied along with the change of
+    /// colorspace, in one pass over the pixels.
+    pub fn decode_layer_converted_with(
+        &mut self,
+        uuid: &Uuid,
+        options: &ConvertOptions,
+    ) -> Result<LayerData, DataError> {
+        let mut data = self.decode_layer(uuid)?;
+        let target = self.meta.colorspace();
+        if data.colorspace == target && options.transfer == Transfer::Unchanged {
+            return Ok(data.into_alpha_mode(options.alpha_mode));
         }
-        "adjustmentlayer" => {
-            files.insert(
-                common.uuid().to_owned(),
-                NodeData::Unloaded(Unloaded::Filter),
-            );
-            NodeType::FilterLayer(FilterLayerProps::parse_tag(&tag)?)
+        data.pixels = convert_pixels_with(
+            &data.pixels,
+            (data.colorspace, data.alpha_mode),
+            target,
+            options,
+        )
+        .ok_or(DataError::UnsupportedConversion(data.colorspace, target))?;
+        data.colorspace = target;
+        data.alpha_mode = options.alpha_mode;
+        Ok(data)
+    }
+
+    /// Decode the coloring of a colorize mask, as Krita computed it when the document was saved.
+    ///
+    /// The coloring is not computed here, so masks that were never updated in Krita
+    /// have none and fail with a missing entry.
+    pub fn decode_colorize_mask(&mut self, uuid: &Uuid) -> Result<LayerData, DataError> {
+        let (data, offset, colorspace) = self.read_coloring(uuid)?;
+        decode_tiles(data, offset, colorspace, self.config.limits.max_pixels)
+    }
+
+    // Stored coloring of the colorize mask, along with the mask's offset and colorspace
+    pub(crate) fn read_coloring(
+        &mut self,
+        uuid: &Uuid,
+    ) -> Result<(TiledData, (i32, i32), Colorspace), DataError> {
+        let node = self
+            .all_nodes()
+            .into_iter()
+            .find(|node| node.uuid() == *uuid)
+            .ok_or(DataError::UnknownNode(*uuid))?;
+        let NodeType::ColorizeMask(props) = node.node_type() else {
+            return Err(DataError::NoPixelData(*uuid));
+        };
+        let (offset, colorspace) = ((node.x(), node.y()), props.colorspace());
+        let path = format!(
+            "{}.colorizemask/coloring",
+            self.layer_entry_path(node.filename())?
+        );
+        Ok((self.entries().read_tiled(&path)?, offset, colorspace))
+    }
+
+    // Bounds of layer's non-transparent pixels in image coordinates.
+    // Layers with non-transparent default pixel cover the whole canvas.
+    fn layer_bounds(
+        &mut self,
+        filename: &str,
+        x: i32,
+        y: i32,
+        colorspace: Colorspace,
+    ) -> Result<Option<IRect>, DataError> {
+        let data = self.read_tiled_data(filename)?;
+        if data
+            .default_pixel
+            .as_ref()
+            .is_some_and(|pixel| !colorspace.is_transparent(pixel))
+        {
+            return Ok(Some(IRect::new(
+                0,
+                0,
+                self.meta.width(),
+                self.meta.height(),
+            )));
         }
-        "generatorlayer" => {
-            files.insert(
-                common.uuid().to_owned(),
-                NodeData::Unloaded(Unloaded::Filter),
-            );
-            NodeType::FillLayer(FillLayerProps::parse_tag(&tag)?)
+        Ok(data
+            .exact_bounds(|pixel| colorspace.is_transparent(pixel))
+            .map(|bounds| bounds.translate(IPoint::new(x, y))))
+    }
+
+    /// Annotations of the document, in archive order.
+    pub fn annotations(&mut self) -> Result<Vec<Annotation>, DataError> {
+        let prefix = format!("{}/annotations/", self.meta.name());
+        let zip = self.file.as_mut().ok_or(DataError::NoArchive)?;
+        let names = zip.entry_names().to_vec();
+
+        let mut annotations = Vec::new();
+        for path in names {
+            let Some(name) = path.strip_prefix(&prefix) else {
+                continue;
+            };
+            if name.is_empty() || name.ends_with('/') {
+                continue;
+            }
+            annotations.push(Annotation {
+                name: name.to_owned(),
+                content: AnnotationContent::new(self.read_entry(&path)?),
+            });
         }
-        "clonelayer" => {
-            files.insert(common.uuid().to_owned(), NodeData::DoesNotExist);
-            NodeType::CloneLayer(CloneLayerProps::parse_tag(&tag)?)
+        Ok(annotations)
+    }
+
+    /// Document-level key/value metadata of pipelines, like a project, an asset ID
+    /// or a license. Empty if the document does not have any.
+    ///
+    /// It is stored in the [`PIPELINE_METADATA`](annotation::PIPELINE_METADATA) annotation.
+    pub fn pipeline_metadata(&mut self) -> Result<BTreeMap<String, String>, DataError> {
+        let path = self.pipeline_metadata_path()?;
+        let data = match self.read_entry(&path) {
+            Ok(data) => data,
+            Err(DataError::Entry {
+                source: zip::result::ZipError::FileNotFound,
+                ..
+            }) => return Ok(BTreeMap::new()),
+            Err(other) => return Err(other),
+        };
+        let text = String::from_utf8(data).map_err(XmlError::from)?;
+        Ok(parse_pipeline_metadata(&text)?)
+    }
+
+    /// Replace the pipeline metadata, see [`pipeline_metadata()`](Self::pipeline_metadata).
+    /// It is saved when the document is written.
+    pub fn set_pipeline_metadata(
+        &mut self,
+        metadata: &BTreeMap<String, String>,
+    ) -> Result<(), DataError> {
+        let path = self.pipeline_metadata_path()?;
+        self.set_entry(path, write_pipeline_metadata(metadata).into_bytes());
+        Ok(())
+    }
+
+    fn pipeline_metadata_path(&self) -> Result<String, DataError> {
+        let image_name = normalize_archive_path(self.meta.name())?;
+        Ok(format!(
+            "{}/annotations/{}",
+            image_name,
+            annotation::PIPELINE_METADATA
+        ))
+    }
+
+    /// Write the document into an archive.
+    ///
+    /// The archive the document was read from must be kept open, entries are copied from it.
+    /// Changes made to the document, like [`regenerate_preview()`](Self::regenerate_preview)
+    /// or [`move_node()`](Self::move_node), are written too.
+    pub fn write<W: Write + Seek>(
+        &mut self,
+        writer: W,
+        options: &WriteOptions,
+    ) -> Result<(), WriteError> {
+        let replacements = self.replacements(options)?;
+        self.write_with(writer, options, &replacements)
+    }
+
+    /// Write the document into a directory, with the same layout as the archive
+    /// (see [`DirectorySource`]). Compression of the options is not used.
+    ///
+    /// The directory is created if needed. If it already holds a document, files that are
+    /// not entries of this one anymore are removed; other directories must be empty.
+    /// Hidden files are left alone.
+    pub fn write_dir<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        options: &WriteOptions,
+    ) -> Result<(), WriteError> {
+        let root = path.as_ref();
+        let replacements = self.replacements(options)?;
+        let orphaned: HashSet<&str> = match options.drop_orphaned_entries {
+            true => self.orphaned_entries.iter().map(String::as_str).collect(),
+            false => HashSet::new(),
+        };
+        let limit = self.config.limits.max_entry_size;
+        let zip = self.file.as_mut().ok_or(DataError::NoArchive)?;
+
+        fs::create_dir_all(root)?;
+        let existing = DirectorySource::open(root)?;
+        let is_document = fs::read(root.join("mimetype"))
+            .is_ok_and(|mimetype| mimetype == b"application/x-krita");
+        if !existing.entry_names().is_empty() && !is_document {
+            return Err(WriteError::NotADocument(root.to_owned()));
         }
-        "transparencymask" => {
-            files.insert(
-                common.uuid().to_owned(),
-                NodeData::Unloaded(Unloaded::TransparencyMask),
-            );
-            NodeType::TransparencyMask(TransparencyMaskProps::new())
+
+        let mut written = HashSet::new();
+        let mut write_entry = |name: &str, data: &[u8]| -> Result<(), WriteError> {
+            let name = normalize_archive_path(name).map_err(DataError::from)?;
+            let path = root.join(&name);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, data)?;
+            written.insert(name);
+            Ok(())
+        };
+        write_entry("mimetype", b"application/x-krita")?;
+        for name in zip.entry_names().to_vec() {
+            if name == "mimetype" || name.ends_with('/') || orphaned.contains(name.as_str()) {
+                continue;
+            }
+            match replacements.get(&name) {
+                Some(data) => write_entry(&name, data)?,
+                None => write_entry(
+                    &name,
+                    &read_entry_limited(zip.as_mut(), &name, limit)
+                        .map_err(|err| entry_error(&name, err))?,
+                )?,
+            }
         }
-        "transformmask" => {
-            files.insert(
-                common.uuid().to_owned(),
-                NodeData::Unloaded(Unloaded::TransformMask),
-            );
-            NodeType::TransformMask(TransformMaskProps::new())
+        for (name, data) in &replacements {
+            if !zip.entry_names().contains(name) {
+                write_entry(name, data)?;
+            }
         }
-        "colorizemask" => {
-            files.insert(
-                common.uuid().to_owned(),
-                NodeData::Unloaded(Unloaded::ColorizeMask),
-            );
-            NodeType::ColorizeMask(ColorizeMaskProps::parse_tag(&tag)?)
+
+        // Left from the previous version of the document
+        for name in existing.entry_names() {
+            if written.contains(name) {
+                continue;
+            }
+            let path = root.join(name);
+            fs::remove_file(&path)?;
+            // Only succeeds for directories that became empty
+            for dir in path.ancestors().skip(1).take_while(|dir| *dir != root) {
+                if fs::remove_dir(dir).is_err() {
+                    break;
+                }
+            }
         }
-        "shapelayer" => {
-            files.insert(
-                common.uuid().to_owned(),
-                NodeData::Unloaded(Unloaded::Vector),
+        Ok(())
+    }
+
+    // Pending entries, along with maindoc.xml if the node tree was changed and
+    // documentinfo.xml if the options change it
+    fn replacements(
+        &self,
+        options: &WriteOptions,
+    ) -> Result<BTreeMap<String, Vec<u8>>, WriteError> {
+        let mut replacements = self.pending_entries.clone();
+        if let Some(maindoc) = self.rebuilt_maindoc()? {
+            replacements.insert("maindoc.xml".to_owned(), maindoc.into_bytes());
+        }
+        if options.author_profile.is_some() || options.update_editing_stats {
+            let mut doc_info = self.doc_info.clone();
+            if let Some(profile) = &options.author_profile {
+                doc_info.apply_author(profile, options.author_update);
+            }
+            if options.update_editing_stats {
+                doc_info.record_editing(self.opened.elapsed(), SystemTime::now());
+            }
+            replacements.insert(
+                "documentinfo.xml".to_owned(),
+                doc_info.to_xml().into_bytes(),
             );
-            NodeType::VectorLayer(VectorLayerProps::parse_tag(&tag)?)
         }
-        "selectionmask" => {
-            files.insert(
-                common.uuid().to_owned(),
-                NodeData::Unloaded(Unloaded::SelectionMask),
+        Ok(replacements)
+    }
+
+    /// Write a smaller, clean copy of the document: orphaned entries are dropped,
+    /// entries are recompressed and, optionally, the preview is made anew.
+    ///
+    /// `mergedimage.png` is kept as it is.
+    pub fn optimize<W: Write + Seek>(
+        &mut self,
+        writer: W,
+        options: &OptimizeOptions,
+    ) -> Result<(), WriteError> {
+        #[cfg(feature = "image")]
+        if let Some(size) = options.preview_size {
+            self.regenerate_preview(size)?;
+        }
+        self.write(writer, &options.write)
+    }
+
+    /// Make a new `preview.png` from `mergedimage.png`, scaled down to fit into
+    /// `size`x`size` (Krita uses 256). It is used when the document is written.
+    ///
+    /// This is for documents whose preview is missing or stale.
+    #[cfg(feature = "image")]
+    pub fn regenerate_preview(&mut self, size: u32) -> Result<(), WriteError> {
+        use image::GenericImageView;
+
+        let mut image = self.merged_image_decoded()?;
+        let (width, height) = image.dimensions();
+        if width > size || height > size {
+            image = image.thumbnail(size, size);
+        }
+        let mut png = Vec::new();
+        image.write_to(&mut io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+        self.set_entry("preview.png".to_owned(), png);
+        Ok(())
+    }
+
+    // Writes entries of the original archive, with some of them replaced or added.
+    // Replaced entries stay where they were, new ones go last.
+    fn write_with<W: Write + Seek>(
+        &mut self,
+        writer: W,
+        options: &WriteOptions,
+        replacements: &BTreeMap<String, Vec<u8>>,
+    ) -> Result<(), WriteError> {
+        let orphaned: HashSet<&str> = match options.drop_orphaned_entries {
+            true => self.orphaned_entries.iter().map(String::as_str).collect(),
+            false => HashSet::new(),
+        };
+        let limit = self.config.limits.max_entry_size;
+        let zip = self.file.as_mut().ok_or(DataError::NoArchive)?;
+
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+        let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        let file_options = match options.compression {
+            Compression::Keep => deflated,
+            Compression::Stored => stored,
+            Compression::Deflated(level) => deflated.compression_level(level),
+        };
+
+        let mut out = ZipWriter::new(writer);
+        // Krita expects mimetype to be the first entry, and not compressed
+        out.start_file("mimetype", stored)?;
+        out.write_all(b"application/x-krita")?;
+
+        let mut replaced = HashSet::new();
+        for name in zip.entry_names().to_vec() {
+            if name == "mimetype" || orphaned.contains(name.as_str()) {
+                continue;
+            }
+            if let Some(data) = replacements.get(&name) {
+                out.start_file(name.as_str(), file_options)?;
+                out.write_all(data)?;
+                replaced.insert(name);
+                continue;
+            }
+            if name.ends_with('/') {
+                out.add_directory(name, file_options)?;
+                continue;
+            }
+            // Only entries of zip archives can be copied as they are
+            if matches!(options.compression, Compression::Keep) {
+                if let Some(entry) = zip.raw_zip_entry(&name) {
+                    out.raw_copy_file(entry?)?;
+                    continue;
+                }
+            }
+            let data = read_entry_limited(zip.as_mut(), &name, limit)
+                .map_err(|err| entry_error(&name, err))?;
+            out.start_file(name, file_options)?;
+            out.write_all(&data)?;
+        }
+        for (name, data) in replacements {
+            if !replaced.contains(name) {
+                out.start_file(name.as_str(), file_options)?;
+                out.write_all(data)?;
+            }
+        }
+        out.finish()?;
+        Ok(())
+    }
+
+    /// Contents of `mergedimage.png`, which is the whole image as rendered by Krita.
+    ///
+    /// With the `render` feature, documents without it are [composited](Self::composite)
+    /// and encoded as PNG instead, see
+    /// [`merged_image_with_source()`](Self::merged_image_with_source). The stored image
+    /// is not updated by edits.
+    pub fn merged_image(&mut self) -> Result<Vec<u8>, DataError> {
+        Ok(self.merged_image_with_source()?.0)
+    }
+
+    /// Same as [`merged_image()`](Self::merged_image), along with whether the image was
+    /// stored in the document or composited now.
+    pub fn merged_image_with_source(&mut self) -> Result<(Vec<u8>, MergedImageSource), DataError> {
+        const NAME: &str = "mergedimage.png";
+        #[cfg(feature = "render")]
+        {
+            let stored = self.pending_entries.contains_key(NAME)
+                || self
+                    .file()
+                    .is_none_or(|archive| archive.entry_names().iter().any(|name| name == NAME));
+            if !stored {
+                let mut png = Vec::new();
+                self.composite()?
+                    .write_to(&mut io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+                return Ok((png, MergedImageSource::Composited));
+            }
+        }
+        Ok((self.read_entry(NAME)?, MergedImageSource::Stored))
+    }
+
+    /// Decoded `mergedimage.png`, with EXIF orientation applied if present.
+    #[cfg(feature = "image")]
+    pub fn merged_image_decoded(&mut self) -> Result<image::DynamicImage, DataError> {
+        use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+
+        let png = self.merged_image()?;
+        let mut decoder =
+            ImageReader::with_format(std::io::Cursor::new(png), ImageFormat::Png).into_decoder()?;
+        let orientation = decoder.orientation()?;
+        let mut image = DynamicImage::from_decoder(decoder)?;
+        image.apply_orientation(orientation);
+        Ok(image)
+    }
+
+    // Replaces (or adds) the entry. Decoded layers may be stale after that.
+    pub(crate) fn set_entry(&mut self, name: String, data: Vec<u8>) {
+        self.pending_entries.insert(name, data);
+        self.cache.clear();
+    }
+
+    // Reads the whole entry of the archive, or its changed version
+    pub(crate) fn read_entry(&mut self, name: &str) -> Result<Vec<u8>, DataError> {
+        self.entries().read(name)
+    }
+
+    // Reads and decodes all tiles of the node, along with its default pixel
+    pub(crate) fn read_tiled_data(&mut self, filename: &str) -> Result<TiledData, DataError> {
+        let path = self.layer_entry_path(filename)?;
+        self.entries().read_tiled(&path)
+    }
+
+    fn read_default_pixel(&mut self, filename: &str) -> Result<Option<Vec<u8>>, DataError> {
+        let path = self.layer_entry_path(filename)?;
+        self.entries().read_default_pixel(&path)
+    }
+
+    fn entries(&mut self) -> EntryReader<'_> {
+        EntryReader {
+            pending: &self.pending_entries,
+            archive: self
+                .file
+                .as_mut()
+                .map(|archive| archive.as_mut() as &mut dyn ArchiveSource),
+            limit: self.config.limits.max_entry_size,
+        }
+    }
+
+    // Path to node's data in the archive.
+    // Both the image name and the filename come from maindoc.xml, so they are not trusted.
+    fn layer_entry_path(&self, filename: &str) -> Result<String, DataError> {
+        layer_entry_path(self.meta.name(), filename)
+    }
+
+    /// Sanitized names of all nodes, including masks, that are unique within the document.
+    ///
+    /// Nodes are named in document order, so the first node with a name keeps it
+    /// and the next ones get a number added (`name_2`, `name_3`...).
+    /// Iteration is ordered by UUID.
+    pub fn sanitized_names(&self, options: &SanitizeOptions) -> BTreeMap<Uuid, String> {
+        let mut names = UniqueNames::new();
+        self.all_nodes()
+            .into_iter()
+            .map(|node| (node.uuid(), names.unique(&node.sanitized_name(options))))
+            .collect()
+    }
+
+    /// Values for a [`NameTemplate`] of all nodes, including masks.
+    ///
+    /// Names are the ones from [`sanitized_names()`](Self::sanitized_names).
+    /// Masks are considered to be children of their layer. Iteration is ordered by UUID.
+    pub fn template_values(&self, options: &SanitizeOptions) -> BTreeMap<Uuid, TemplateValues> {
+        fn walk(
+            nodes: &[Node],
+            names: &BTreeMap<Uuid, String>,
+            path: &mut Vec<String>,
+            out: &mut BTreeMap<Uuid, TemplateValues>,
+        ) {
+            for (index, node) in nodes.iter().enumerate() {
+                let name = names[&node.uuid()].clone();
+                out.insert(
+                    node.uuid(),
+                    TemplateValues::new(name.clone(), path.clone(), index, node.uuid()),
+                );
+                path.push(name);
+                walk(node.masks(), names, path, out);
+                if let NodeType::GroupLayer(props) = node.node_type() {
+                    walk(props.layers(), names, path, out);
+                }
+                path.pop();
+            }
+        }
+        let names = self.sanitized_names(options);
+        let mut out = BTreeMap::new();
+        walk(&self.layers, &names, &mut Vec::new(), &mut out);
+        out
+    }
+
+    /// File names of all nodes, including masks, made from the template.
+    ///
+    /// `{composition}` and `{frame}` are left empty, use
+    /// [`template_values()`](Self::template_values) to set them. Iteration is ordered by UUID.
+    pub fn export_names(
+        &self,
+        template: &NameTemplate,
+        options: &SanitizeOptions,
+    ) -> BTreeMap<Uuid, String> {
+        self.template_values(options)
+            .into_iter()
+            .map(|(uuid, values)| (uuid, template.render(&values)))
+            .collect()
+    }
+
+    /// Human-readable paths of all nodes, including masks, that are unique within
+    /// the document.
+    ///
+    /// A path is made of the names of the node and of every node above it, separated
+    /// by `/`, with `/` and `\` in names escaped by `\`. Masks are considered to be
+    /// children of their layer. If siblings have the same name, the first one keeps it
+    /// and the next ones get their number among them added (`Sketch`, `Sketch[2]`...).
+    /// Iteration is ordered by UUID.
+    pub fn display_paths(&self) -> BTreeMap<Uuid, String> {
+        fn walk(nodes: &[&Node], prefix: &str, out: &mut BTreeMap<Uuid, String>) {
+            let mut taken = HashSet::new();
+            let mut count: HashMap<&str, usize> = HashMap::new();
+            for node in nodes {
+                let name = node.name().replace('\\', "\\\\").replace('/', "\\/");
+                let seen = count.entry(node.name()).or_insert(0);
+                *seen += 1;
+                let mut segment = name.clone();
+                let mut index = *seen;
+                while !taken.insert(segment.clone()) {
+                    index = index.max(2);
+                    segment = format!("{}[{}]", name, index);
+                    index += 1;
+                }
+                let path = format!("{}{}", prefix, segment);
+                let mut children: Vec<&Node> = node.masks().iter().collect();
+                if let NodeType::GroupLayer(props) = node.node_type() {
+                    children.extend(props.layers());
+                }
+                walk(&children, &format!("{}/", path), out);
+                out.insert(node.uuid(), path);
+            }
+        }
+        let mut out = BTreeMap::new();
+        walk(&self.layers.iter().collect::<Vec<_>>(), "", &mut out);
+        out
+    }
+
+    /// The node with the [display path](Self::display_paths).
+    pub fn find_by_display_path(&self, path: &str) -> Option<&Node> {
+        let paths = self.display_paths();
+        self.all_nodes()
+            .into_iter()
+            .find(|node| paths.get(&node.uuid()).is_some_and(|p| p == path))
+    }
+
+    /// Whether the node is shown in the image, which it is only if it and every group
+    /// it is in are visible. Masks are also hidden if their layer is.
+    ///
+    /// `None` if there is no such node.
+    pub fn effective_visibility(&self, uuid: &Uuid) -> Option<bool> {
+        let path = self.node_path(uuid)?;
+        Some(path.iter().all(|node| node.visible()))
+    }
+
+    /// Opacity the node is shown with, from 0 to 1: its own opacity multiplied by
+    /// the opacity of every group it is in, or 0 if it is
+    /// [not visible](Self::effective_visibility).
+    ///
+    /// Pass-through groups are not composited on their own, but their opacity still applies
+    /// to each of their layers, so it is counted the same way. Masks do not have an opacity,
+    /// they take the one of their layer.
+    ///
+    /// `None` if there is no such node.
+    pub fn effective_opacity(&self, uuid: &Uuid) -> Option<f32> {
+        let path = self.node_path(uuid)?;
+        if !path.iter().all(|node| node.visible()) {
+            return Some(0.0);
+        }
+        Some(
+            path.iter()
+                .filter_map(|node| node.as_layer())
+                .map(|layer| layer.opacity() as f32 / u8::MAX as f32)
+                .product(),
+        )
+    }
+
+    /// Nodes with the color label, masks included, in document order.
+    pub fn find_by_colorlabel(&self, label: ColorLabel) -> Vec<&Node> {
+        self.all_nodes()
+            .into_iter()
+            .filter(|node| node.colorlabel() == label.index())
+            .collect()
+    }
+
+    /// Locked nodes, masks included, in document order.
+    pub fn find_locked(&self) -> Vec<&Node> {
+        self.all_nodes()
+            .into_iter()
+            .filter(|node| node.locked())
+            .collect()
+    }
+
+    /// The node that was selected in the layer docker when the document was saved.
+    pub fn selected_node(&self) -> Option<&Node> {
+        self.all_nodes().into_iter().find(|node| node.selected())
+    }
+
+    /// The active selection mask, if there was an active selection when the document was
+    /// saved. Global selections are top-level selection masks.
+    pub fn active_selection(&self) -> Option<&Node> {
+        self.all_nodes().into_iter().find(
+            |node| matches!(node.node_type(), NodeType::SelectionMask(props) if props.active()),
+        )
+    }
+
+    // The node and every node above it (groups and the layer of a mask), from the top down
+    fn node_path(&self, uuid: &Uuid) -> Option<Vec<&Node>> {
+        fn walk<'a>(nodes: &'a [Node], uuid: &Uuid, path: &mut Vec<&'a Node>) -> bool {
+            for node in nodes {
+                path.push(node);
+                if node.uuid() == *uuid || walk(node.masks(), uuid, path) {
+                    return true;
+                }
+                if let NodeType::GroupLayer(props) = node.node_type() {
+                    if walk(props.layers(), uuid, path) {
+                        return true;
+                    }
+                }
+                path.pop();
+            }
+            false
+        }
+        let mut path = Vec::new();
+        walk(&self.layers, uuid, &mut path).then_some(path)
+    }
+
+    // All nodes, including masks, in document order
+    pub(crate) fn all_nodes(&self) -> Vec<&Node> {
+        fn walk<'a>(nodes: &'a [Node], out: &mut Vec<&'a Node>) {
+            for node in nodes {
+                out.push(node);
+                walk(node.masks(), out);
+                if let NodeType::GroupLayer(props) = node.node_type() {
+                    walk(props.layers(), out);
+                }
+            }
+        }
+        let mut out = Vec::new();
+        walk(&self.layers, &mut out);
+        out
+    }
+}
+
+// Reads the whole entry, failing if it is larger than `limit`
+pub(crate) fn read_entry_limited<E>(
+    zip: &mut dyn ArchiveSource,
+    name: &str,
+    limit: u64,
+) -> Result<Vec<u8>, E>
+where
+    E: From<io::Error> + From<zip::result::ZipError> + From<LimitError>,
+{
+    let exceeded = || LimitError::EntrySize {
+        entry: name.to_owned(),
+        limit,
+    };
+    let entry = zip.by_name(name)?;
+    if entry.size() > limit {
+        return Err(exceeded().into());
+    }
+    // Declared size could be wrong
+    let mut data = Vec::new();
+    entry.take(limit.saturating_add(1)).read_to_end(&mut data)?;
+    if data.len() as u64 > limit {
+        return Err(exceeded().into());
+    }
+    Ok(data)
+}
+
+// Same as read_entry_limited(), but errors of the archive tell which entry was read
+pub(crate) fn read_document_entry(
+    zip: &mut dyn ArchiveSource,
+    name: &str,
+    limit: u64,
+) -> Result<Vec<u8>, ReadKraError> {
+    let entry = |source| ReadKraError::Entry {
+        name: name.to_owned(),
+        source,
+    };
+    read_entry_limited(zip, name, limit).map_err(|err| match err {
+        ReadKraError::ZipError(source) => entry(source),
+        ReadKraError::FileError(err) => entry(zip::result::ZipError::Io(err)),
+        other => other,
+    })
+}
+
+// Error of the archive while reading the entry, which tells which entry was read
+pub(crate) fn entry_error(name: &str, err: DataError) -> DataError {
+    let entry = |source| DataError::Entry {
+        name: name.to_owned(),
+        source,
+    };
+    match err {
+        DataError::ZipError(source) => entry(source),
+        DataError::FileError(err) => entry(zip::result::ZipError::Io(err)),
+        other => other,
+    }
+}
+
+fn read_xml_entry(
+    zip: &mut dyn ArchiveSource,
+    name: &str,
+    limit: u64,
+) -> Result<String, ReadKraError> {
+    let data = read_document_entry(zip, name, limit)?;
+    String::from_utf8(data).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err).into())
+}
+
+// Entries as they are now: changed ones, or the ones of the archive
+pub(crate) struct EntryReader<'a> {
+    pub(crate) pending: &'a BTreeMap<String, Vec<u8>>,
+    pub(crate) archive: Option<&'a mut dyn ArchiveSource>,
+    pub(crate) limit: u64,
+}
+
+impl EntryReader<'_> {
+    pub(crate) fn read(&mut self, name: &str) -> Result<Vec<u8>, DataError> {
+        if let Some(data) = self.pending.get(name) {
+            return Ok(data.clone());
+        }
+        let archive = self.archive.as_deref_mut().ok_or(DataError::NoArchive)?;
+        read_entry_limited(archive, name, self.limit).map_err(|err| entry_error(name, err))
+    }
+
+    // Reads and decodes all tiles at `path`, along with the default pixel
+    pub(crate) fn read_tiled(&mut self, path: &str) -> Result<TiledData, DataError> {
+        let mut data = match self.pending.get(path) {
+            Some(pending) => TiledData::read(&mut pending.as_slice())?,
+            None => {
+                let limit = self.limit;
+                let archive = self.archive.as_deref_mut().ok_or(DataError::NoArchive)?;
+                let entry = archive
+                    .by_name(path)
+                    .map_err(|err| entry_error(path, err.into()))?;
+                if entry.size() > limit {
+                    let entry = path.to_owned();
+                    return Err(LimitError::EntrySize { entry, limit }.into());
+                }
+                TiledData::read(&mut BufReader::new(entry.take(limit)))
+                    .map_err(|err| entry_error(path, err))?
+            }
+        };
+        data.default_pixel = self.read_default_pixel(path)?;
+        Ok(data)
+    }
+
+    // Default pixel is optional
+    pub(crate) fn read_default_pixel(&mut self, path: &str) -> Result<Option<Vec<u8>>, DataError> {
+        match self.read(&format!("{}.defaultpixel", path)) {
+            Ok(default_pixel) => Ok(Some(default_pixel)),
+            Err(DataError::Entry {
+                source: zip::result::ZipError::FileNotFound,
+                ..
+            }) => Ok(None),
+            Err(other) => Err(other),
+        }
+    }
+}
+
+// Path to the data of the node with `filename`, in the image called `image_name`
+pub(crate) fn layer_entry_path(image_name: &str, filename: &str) -> Result<String, DataError> {
+    Ok(format!(
+        "{}/layers/{}",
+        normalize_archive_path(image_name)?,
+        normalize_archive_path(filename)?
+    ))
+}
+
+// Dense pixels of a paint layer at (x, y) from its tiles
+pub(crate) fn decode_tiles(
+    data: TiledData,
+    (x, y): (i32, i32),
+    colorspace: Colorspace,
+    max_pixels: u64,
+) -> Result<LayerData, DataError> {
+    if data.header.pixel_size as usize != colorspace.pixel_size() {
+        return Err(DataError::MalformedTiles(
+            "pixel size matching the colorspace",
+            data.header.pixel_size.to_string(),
+        ));
+    }
+    let (left, top, width, height, bytes) = data.to_dense(max_pixels)?;
+    // Far edges must fit too
+    let fits = |start: i32, offset: i32, size: u32| {
+        start
+            .checked_add(offset)
+            .filter(|&start| start as i64 + size as i64 <= i32::MAX as i64)
+    };
+    let (Some(x), Some(y)) = (fits(left, x, width), fits(top, y, height)) else {
+        return Err(DataError::MalformedTiles(
+            "tiles within the range of coordinates",
+            format!("{},{} at an offset of {},{}", left, top, x, y),
+        ));
+    };
+    Ok(LayerData {
+        x,
+        y,
+        width,
+        height,
+        colorspace,
+        pixels: PixelBuffer::from_le_bytes(bytes, colorspace.depth()),
+        alpha_mode: AlphaMode::Straight,
+    })
+}
+
+// State that is carried through parsing of maindoc.xml
+pub(crate) struct ParseContext<'a> {
+    pub(crate) config: &'a ParsingConfiguration,
+    // Data of every node that was parsed so far
+    pub(crate) files: BTreeMap<Uuid, NodeData>,
+    // Collected if the configuration says so
+    pub(crate) unknown_attributes: Vec<UnknownAttribute>,
+    // Nodes whose UUID was already used
+    pub(crate) duplicate_uuids: Vec<DuplicateUuid>,
+    // Number of groups that the node being parsed is in
+    pub(crate) group_depth: usize,
+}
+
+impl ParseContext<'_> {
+    // Goes into a group, which must be left with leave_group() after its layers are parsed
+    pub(crate) fn enter_group(&mut self) -> Result<(), MetadataErrorReason> {
+        let max_group_depth = self.config.limits.max_group_depth;
+        if self.group_depth >= max_group_depth {
+            return Err(LimitError::GroupDepth(max_group_depth).into());
+        }
+        self.group_depth += 1;
+        Ok(())
+    }
+
+    pub(crate) fn leave_group(&mut self) {
+        self.group_depth -= 1;
+    }
+
+    // Registers a parsed node
+    pub(crate) fn add_node(
+        &mut self,
+        uuid: Uuid,
+        data: NodeData,
+    ) -> Result<(), MetadataErrorReason> {
+        let max_nodes = self.config.limits.max_nodes;
+        if self.files.len() >= max_nodes {
+            return Err(LimitError::Nodes(max_nodes).into());
+        }
+        self.files.insert(uuid, data);
+        Ok(())
+    }
+
+    // Gives the node a new UUID if its one was already used, or returns false
+    // if the node is left out
+    fn check_uuid(
+        &mut self,
+        common: &mut CommonNodeProps,
+        attributes: &mut Vec<(String, String)>,
+    ) -> Result<bool, MetadataErrorReason> {
+        let uuid = common.uuid();
+        if !self.files.contains_key(&uuid) {
+            return Ok(true);
+        }
+        let replacement = match self.config.duplicate_uuids {
+            DuplicateUuids::Error => {
+                return Err(DuplicateUuid {
+                    uuid,
+                    replacement: None,
+                }
+                .into())
+            }
+            DuplicateUuids::KeepFirst => None,
+            DuplicateUuids::Regenerate => {
+                let new = Uuid::new_v4();
+                common.set_uuid(new);
+                set_attr(attributes, "uuid", &new.braced().to_string());
+                Some(new)
+            }
+        };
+        self.duplicate_uuids
+            .push(DuplicateUuid { uuid, replacement });
+        Ok(replacement.is_some())
+    }
+
+    // Looks for attributes of the node that are not read into the model
+    fn check_attributes(
+        &mut self,
+        uuid: Uuid,
+        kind: NodeKind,
+        attributes: &[(String, String)],
+    ) -> Result<(), MetadataErrorReason> {
+        let mode = self.config.unknown_attributes;
+        if mode == UnknownAttributes::Ignore {
+            return Ok(());
+        }
+        let unknown = attributes
+            .iter()
+            .filter(|(name, _)| !schema::is_known(kind, name))
+            .map(|(name, _)| UnknownAttribute {
+                uuid,
+                kind,
+                name: name.clone(),
+            });
+        for attribute in unknown {
+            match mode {
+                UnknownAttributes::Error => return Err(attribute.into()),
+                _ => self.unknown_attributes.push(attribute),
+            }
+        }
+        Ok(())
+    }
+}
+
+//Starts immed. before the required <layer> | <layer/> | <mask> | <mask/>
+// None if the node is left out, see DuplicateUuids
+fn parse_layer(
+    reader: &mut XmlReader<&[u8]>,
+    ctx: &mut ParseContext,
+) -> Result<Option<Node>, MetadataErrorReason> {
+    let event = next_xml_event(reader)?;
+
+    // If the event is not empty, and it is not a group layer, it contains masks
+    let could_contain_masks = matches!(event, Event::Start(..));
+
+    let tag: BytesStart = match event {
+        Event::Start(t) | Event::Empty(t) => t,
+        other => {
+            return Err(
+                XmlError::EventError("layer/mask start event", event_to_string(&other)?).into(),
             );
-            NodeType::SelectionMask(SelectionMaskProps::parse_tag(&tag)?)
         }
-        _ => {
-            return Err(MetadataErrorReason::UnknownLayerType(UnknownLayerType(
-                node_type.into_owned(),
-            )));
+    };
+
+    let mut common = CommonNodeProps::parse_tag(&tag)?;
+
+    let kind: NodeKind = event_get_attr(&tag, "nodetype")?
+        .unescape_value()?
+        .parse()?;
+    let mut attributes = event_get_all_attrs(&tag)?;
+    if !ctx.check_uuid(&mut common, &mut attributes)? {
+        if could_contain_masks {
+            reader
+                .read_to_end(tag.name())
+                .map_err(XmlError::ParsingError)?;
         }
+        return Ok(None);
+    }
+    ctx.check_attributes(common.uuid(), kind, &attributes)?;
+    let node_type = parse_node_type(kind, &common, &tag, reader, ctx)?;
+
+    let (masks, unparsed) = match (could_contain_masks, &node_type) {
+        (_, NodeType::GroupLayer(_)) => (None, Vec::new()),
+        (false, _) => (None, Vec::new()),
+        (true, _) => parse_layer_children(reader, ctx)?,
     };
 
-    let masks = match (could_contain_masks, &node_type) {
-        (_, NodeType::GroupLayer(_)) => None,
-        (false, _) => None,
-        (true, _) => Some(parse_mask(reader, files)?),
+    let mut node = Node::new(common, masks, node_type, attributes);
+    node.set_unparsed(unparsed);
+    Ok(Some(node))
+}
+
+// Registers the node and parses its type-specific properties.
+// Group layers also parse their children, so this needs the reader.
+fn parse_node_type(
+    kind: NodeKind,
+    common: &CommonNodeProps,
+    tag: &BytesStart,
+    reader: &mut XmlReader<&[u8]>,
+    ctx: &mut ParseContext,
+) -> Result<NodeType, MetadataErrorReason> {
+    let data = match kind {
+        NodeKind::PaintLayer => NodeData::Unloaded(Unloaded::Image),
+        NodeKind::FilterLayer | NodeKind::FillLayer | NodeKind::FilterMask => {
+            NodeData::Unloaded(Unloaded::Filter)
+        }
+        NodeKind::VectorLayer => NodeData::Unloaded(Unloaded::Vector),
+        NodeKind::TransparencyMask => NodeData::Unloaded(Unloaded::TransparencyMask),
+        NodeKind::TransformMask => NodeData::Unloaded(Unloaded::TransformMask),
+        NodeKind::SelectionMask => NodeData::Unloaded(Unloaded::SelectionMask),
+        NodeKind::ColorizeMask => NodeData::Unloaded(Unloaded::ColorizeMask),
+        NodeKind::GroupLayer | NodeKind::FileLayer | NodeKind::CloneLayer => NodeData::DoesNotExist,
     };
+    ctx.add_node(common.uuid(), data)?;
 
-    Ok(Node::new(common, masks, node_type))
+    //TODO: finish (Selection mask) and verify
+    Ok(match kind {
+        NodeKind::GroupLayer => NodeType::GroupLayer(GroupLayerProps::parse_tag(tag, reader, ctx)?),
+        NodeKind::PaintLayer => NodeType::PaintLayer(PaintLayerProps::parse_tag(tag)?),
+        NodeKind::FilterMask => NodeType::FilterMask(FilterMaskProps::parse_tag(tag)?),
+        NodeKind::FileLayer => NodeType::FileLayer(FileLayerProps::parse_tag(tag)?),
+        NodeKind::FilterLayer => NodeType::FilterLayer(FilterLayerProps::parse_tag(tag)?),
+        NodeKind::FillLayer => NodeType::FillLayer(FillLayerProps::parse_tag(tag)?),
+        NodeKind::CloneLayer => NodeType::CloneLayer(CloneLayerProps::parse_tag(tag)?),
+        NodeKind::TransparencyMask => {
+            NodeType::TransparencyMask(TransparencyMaskProps::parse_tag(tag)?)
+        }
+        NodeKind::TransformMask => NodeType::TransformMask(TransformMaskProps::parse_tag(tag)?),
+        NodeKind::ColorizeMask => NodeType::ColorizeMask(ColorizeMaskProps::parse_tag(tag)?),
+        NodeKind::VectorLayer => NodeType::VectorLayer(VectorLayerProps::parse_tag(tag)?),
+        NodeKind::SelectionMask => NodeType::SelectionMask(SelectionMaskProps::parse_tag(tag)?),
+    })
 }
 
 fn get_layers(
     reader: &mut XmlReader<&[u8]>,
-    files: &mut HashMap<Uuid, NodeData>,
+    ctx: &mut ParseContext,
 ) -> Result<Vec<Node>, MetadataErrorReason> {
     let mut layers: Vec<Node> = Vec::new();
-    //<layers>
-    let event = next_xml_event(reader)?;
-    event_unwrap_as_start(event)?;
+    match next_xml_event(reader)? {
+        //<layers/>, a document without layers
+        Event::Empty(_) => return Ok(layers),
+        //<layers>
+        event => event_unwrap_as_start(event)?,
+    };
 
     loop {
-        match parse_layer(reader, files) {
-            Ok(layer) => layers.push(layer),
+        match parse_layer(reader, ctx) {
+            Ok(layer) => layers.extend(layer),
             Err(MetadataErrorReason::XmlError(XmlError::EventError(a, ref b)))
                 //</layers>
                 if (a == "layer/mask start event" && b == "layers") =>
@@ -289,16 +1928,88 @@ fn get_layers(
     Ok(layers)
 }
 
+// Skips everything from <layers> to </layers>
+fn skip_layers(reader: &mut XmlReader<&[u8]>) -> Result<(), MetadataErrorReason> {
+    let tag = match next_xml_event(reader)? {
+        //<layers/>
+        Event::Empty(_) => return Ok(()),
+        //<layers>
+        event => event_unwrap_as_start(event)?,
+    };
+    reader
+        .read_to_end(tag.name())
+        .map_err(XmlError::ParsingError)?;
+    Ok(())
+}
+
+// Children of a non-group <layer>, ending after </layer>.
+// Returns masks, if there are any, and raw XML of unknown elements (for example, from newer
+// versions of Krita), so that they are written back.
+fn parse_layer_children(
+    reader: &mut XmlReader<&[u8]>,
+    ctx: &mut ParseContext,
+) -> Result<(Option<Vec<Node>>, Vec<String>), MetadataErrorReason> {
+    let mut masks = None;
+    let mut unparsed = Vec::new();
+    loop {
+        let rest = *reader.get_ref();
+        match next_xml_event(reader)? {
+            Event::Start(tag) if tag.name().as_ref() == b"masks" => {
+                masks = Some(parse_mask(reader, ctx)?);
+            }
+            Event::Start(tag) => {
+                reader
+                    .read_to_end(tag.name())
+                    .map_err(XmlError::ParsingError)?;
+                unparsed.push(raw_since(rest, reader));
+            }
+            Event::Empty(_) => unparsed.push(raw_since(rest, reader)),
+            Event::Comment(_) => {}
+            //</layer>
+            Event::End(tag) if tag.name().as_ref() == b"layer" => break,
+            other => {
+                return Err(MetadataErrorReason::XmlError(XmlError::EventError(
+                    "layer child or end event",
+                    event_to_string(&other)?,
+                )))
+            }
+        }
+    }
+    Ok((masks, unparsed))
+}
+
+// Children of a <mask>, ending after </mask>. No children are known, so this returns
+// raw XML of all of them.
+fn parse_mask_children(reader: &mut XmlReader<&[u8]>) -> Result<Vec<String>, MetadataErrorReason> {
+    let mut unparsed = Vec::new();
+    loop {
+        let rest = *reader.get_ref();
+        match next_xml_event(reader)? {
+            Event::Start(tag) => {
+                reader
+                    .read_to_end(tag.name())
+                    .map_err(XmlError::ParsingError)?;
+                unparsed.push(raw_since(rest, reader));
+            }
+            Event::Empty(_) => unparsed.push(raw_since(rest, reader)),
+            //</mask>
+            Event::End(_) => break,
+            Event::Eof => {
+                return Err(XmlError::EventError("mask child or end event", "EOF".into()).into())
+            }
+            _ => {}
+        }
+    }
+    Ok(unparsed)
+}
+
 //TODO: this and parse_layer() share similarities that I would like to control
 // together (like matching the layer type, or getting layers, which may be similar with grouplayer's).
+// Starts immed. after <masks>, ends after </masks>
 fn parse_mask(
     reader: &mut XmlReader<&[u8]>,
-    files: &mut HashMap<Uuid, NodeData>,
+    ctx: &mut ParseContext,
 ) -> Result<Vec<Node>, MetadataErrorReason> {
-    //<masks>
-    let event = next_xml_event(reader)?;
-    event_unwrap_as_start(event)?;
-
     let mut masks: Vec<Node> = Vec::new();
 
     // masks
@@ -306,74 +2017,57 @@ fn parse_mask(
         match next_xml_event(reader)? {
             Event::End(tag) => {
                 //</masks>
-                if tag.as_ref() == "masks".as_bytes() {
+                if tag.name().as_ref() == b"masks" {
                     break;
                 } else {
                     return Err(MetadataErrorReason::XmlError(XmlError::EventError(
                         "masks end event",
-                        String::from_utf8(tag.as_ref().to_vec())?,
+                        String::from_utf8(tag.name().as_ref().to_vec())?,
                     )));
                 }
             }
-            Event::Empty(tag) => {
-                let common = CommonNodeProps::parse_tag(&tag)?;
-                let node_type = event_get_attr(&tag, "nodetype")?.unescape_value()?;
-                let node_type = match node_type.as_ref() {
-                    "filtermask" => {
-                        files.insert(
-                            common.uuid().to_owned(),
-                            NodeData::Unloaded(Unloaded::Filter),
-                        );
-                        NodeType::FilterMask(FilterMaskProps::parse_tag(&tag)?)
-                    }
-                    "transparencymask" => {
-                        files.insert(
-                            common.uuid().to_owned(),
-                            NodeData::Unloaded(Unloaded::TransparencyMask),
-                        );
-                        NodeType::TransparencyMask(TransparencyMaskProps::new())
-                    }
-                    "transformmask" => {
-                        files.insert(
-                            common.uuid().to_owned(),
-                            NodeData::Unloaded(Unloaded::TransformMask),
-                        );
-                        NodeType::TransformMask(TransformMaskProps::new())
-                    }
-                    "colorizemask" => {
-                        files.insert(
-                            common.uuid().to_owned(),
-                            NodeData::Unloaded(Unloaded::ColorizeMask),
-                        );
-                        NodeType::ColorizeMask(ColorizeMaskProps::parse_tag(&tag)?)
-                    }
-                    "selectionmask" => {
-                        files.insert(
-                            common.uuid().to_owned(),
-                            NodeData::Unloaded(Unloaded::SelectionMask),
-                        );
-                        NodeType::SelectionMask(SelectionMaskProps::parse_tag(&tag)?)
-                    }
-                    _ => {
-                        return Err(MetadataErrorReason::MaskExpected(MaskExpected(
-                            node_type.into_owned(),
-                        )));
-                    }
-                };
-                masks.push(Node::new(common, None, node_type))
+            Event::Empty(tag) => masks.extend(parse_single_mask(&tag, reader, ctx)?),
+            Event::Start(tag) => {
+                let mask = parse_single_mask(&tag, reader, ctx)?;
+                let unparsed = parse_mask_children(reader)?;
+                masks.extend(mask.map(|mut mask| {
+                    mask.set_unparsed(unparsed);
+                    mask
+                }));
             }
             other => {
                 return Err(MetadataErrorReason::XmlError(XmlError::EventError(
-                    "empty or end event",
+                    "mask or end event",
                     event_to_string(&other)?,
                 )))
             }
         }
     }
 
-    //</layer>
-    let event = next_xml_event(reader)?;
-    event_unwrap_as_end(event)?;
-
     Ok(masks)
 }
+
+// None if the mask is left out, see DuplicateUuids
+fn parse_single_mask(
+    tag: &BytesStart,
+    reader: &mut XmlReader<&[u8]>,
+    ctx: &mut ParseContext,
+) -> Result<Option<Node>, MetadataErrorReason> {
+    let mut common = CommonNodeProps::parse_tag(tag)?;
+    let node_type = event_get_attr(tag, "nodetype")?.unescape_value()?;
+    let kind = match node_type.parse::<NodeKind>() {
+        Ok(kind) if kind.is_mask() => kind,
+        _ => {
+            return Err(MetadataErrorReason::MaskExpected(MaskExpected(
+                node_type.into_owned(),
+            )));
+        }
+    };
+    let mut attributes = event_get_all_attrs(tag)?;
+    if !ctx.check_uuid(&mut common, &mut attributes)? {
+        return Ok(None);
+    }
+    ctx.check_attributes(common.uuid(), kind, &attributes)?;
+    let node_type = parse_node_type(kind, &common, tag, reader, ctx)?;
+    Ok(Some(Node::new(common, None, node_type, attributes)))
+}
@@ -0,0 +1,384 @@
+//! Checking documents against conventions, like the ones a studio sets for its assets.
+
+use std::fmt::{self, Display};
+#[cfg(feature = "lint-config")]
+use std::{fs, path::Path, str::FromStr};
+
+use regex::Regex;
+#[cfg(feature = "lint-config")]
+use serde::Deserialize;
+
+#[cfg(feature = "lint-config")]
+use crate::error::LintConfigError;
+
+use crate::{
+    helper::json_string,
+    id::Uuid,
+    layer::{CompositeOp, NodeType},
+    Colorspace, KraFile,
+};
+
+/// Conventions that [`KraFile::lint()`] checks. By default, nothing is checked.
+#[derive(Debug, Clone, Default)]
+pub struct LintRules {
+    pub(crate) max_layers: Option<usize>,
+    pub(crate) forbidden_blend_modes: Vec<CompositeOp>,
+    pub(crate) layer_name: Option<Regex>,
+    pub(crate) required_groups: Vec<String>,
+    pub(crate) colorspaces: Option<Vec<Colorspace>>,
+    pub(crate) max_dimensions: Option<(u32, u32)>,
+}
+
+impl LintRules {
+    /// Rules that check nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the largest number of layers, masks excluded.
+    pub fn max_layers(mut self, max_layers: usize) -> Self {
+        self.max_layers = Some(max_layers);
+        self
+    }
+
+    /// Largest number of layers, masks excluded.
+    pub fn get_max_layers(&self) -> Option<usize> {
+        self.max_layers
+    }
+
+    /// Forbid layers from using the blend mode.
+    pub fn forbid_blend_mode(mut self, blend_mode: CompositeOp) -> Self {
+        self.forbidden_blend_modes.push(blend_mode);
+        self
+    }
+
+    /// Blend modes that layers cannot use.
+    pub fn get_forbidden_blend_modes(&self) -> &[CompositeOp] {
+        &self.forbidden_blend_modes
+    }
+
+    /// Set the pattern that names of layers have to match.
+    pub fn layer_name(mut self, pattern: Regex) -> Self {
+        self.layer_name = Some(pattern);
+        self
+    }
+
+    /// Pattern that names of layers have to match.
+    pub fn get_layer_name(&self) -> Option<&Regex> {
+        self.layer_name.as_ref()
+    }
+
+    /// Require a group layer with the name, at any depth.
+    pub fn require_group(mut self, name: &str) -> Self {
+        self.required_groups.push(name.to_owned());
+        self
+    }
+
+    /// Names of group layers that have to exist.
+    pub fn get_required_groups(&self) -> &[String] {
+        &self.required_groups
+    }
+
+    /// Set the colorspaces that the image and nodes with pixels of their own can use.
+    pub fn allowed_colorspaces(mut self, colorspaces: &[Colorspace]) -> Self {
+        self.colorspaces = Some(colorspaces.to_vec());
+        self
+    }
+
+    /// Colorspaces that the image and nodes with pixels of their own can use.
+    pub fn get_allowed_colorspaces(&self) -> Option<&[Colorspace]> {
+        self.colorspaces.as_deref()
+    }
+
+    /// Set the largest width and height of the image.
+    pub fn max_dimensions(mut self, width: u32, height: u32) -> Self {
+        self.max_dimensions = Some((width, height));
+        self
+    }
+
+    /// Largest width and height of the image.
+    pub fn get_max_dimensions(&self) -> Option<(u32, u32)> {
+        self.max_dimensions
+    }
+}
+
+// Rules as they are written in files, with Krita's names of blend modes and colorspaces
+#[cfg(feature = "lint-config")]
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct RulesFile {
+    max_layers: Option<usize>,
+    #[serde(default)]
+    forbidden_blend_modes: Vec<String>,
+    layer_name: Option<String>,
+    #[serde(default)]
+    required_groups: Vec<String>,
+    colorspaces: Option<Vec<String>>,
+    max_dimensions: Option<(u32, u32)>,
+}
+
+#[cfg(feature = "lint-config")]
+impl TryFrom<RulesFile> for LintRules {
+    type Error = LintConfigError;
+
+    fn try_from(file: RulesFile) -> Result<Self, Self::Error> {
+        Ok(LintRules {
+            max_layers: file.max_layers,
+            forbidden_blend_modes: file
+                .forbidden_blend_modes
+                .iter()
+                .map(|name| CompositeOp::from_str(name))
+                .collect::<Result<_, _>>()?,
+            layer_name: file.layer_name.as_deref().map(Regex::new).transpose()?,
+            required_groups: file.required_groups,
+            colorspaces: file
+                .colorspaces
+                .map(|names| {
+                    names
+                        .iter()
+                        .map(|name| Colorspace::try_from(name.as_str()))
+                        .collect::<Result<_, _>>()
+                })
+                .transpose()?,
+            max_dimensions: file.max_dimensions,
+        })
+    }
+}
+
+#[cfg(feature = "lint-config")]
+impl LintRules {
+    /// Rules written as TOML, like:
+    ///
+    /// ```toml
+    /// max-layers = 50
+    /// forbidden-blend-modes = ["add", "dissolve"]
+    /// layer-name = "^[a-z]+(_[a-z0-9]+)*$"
+    /// required-groups = ["Effects"]
+    /// colorspaces = ["RGBA"]
+    /// max-dimensions = [4096, 4096]
+    /// ```
+    ///
+    /// Blend modes and colorspaces are named as Krita writes them in `maindoc.xml`. Missing
+    /// keys are not checked, and unknown ones are errors.
+    pub fn from_toml(text: &str) -> Result<Self, LintConfigError> {
+        toml::from_str::<RulesFile>(text)?.try_into()
+    }
+
+    /// Rules written as JSON, with the same keys as [`from_toml()`](Self::from_toml).
+    pub fn from_json(text: &str) -> Result<Self, LintConfigError> {
+        serde_json::from_str::<RulesFile>(text)?.try_into()
+    }
+
+    /// Rules from the file at `path`, which is read as JSON if its extension is `json`,
+    /// and as TOML otherwise, like `kra-lint.toml`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, LintConfigError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        match path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            true => Self::from_json(&text),
+            false => Self::from_toml(&text),
+        }
+    }
+}
+
+/// Rule of [`LintRules`] that was broken.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[non_exhaustive]
+pub enum Rule {
+    /// There are too many layers.
+    MaxLayers,
+    /// A layer uses a forbidden blend mode.
+    ForbiddenBlendMode,
+    /// Name of a layer does not match the pattern.
+    LayerName,
+    /// A required group is missing.
+    RequiredGroup,
+    /// The image or a node uses a colorspace that is not allowed.
+    Colorspace,
+    /// The image is too large.
+    MaxDimensions,
+}
+
+// Names used in reports
+impl Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Rule::MaxLayers => "max-layers",
+            Rule::ForbiddenBlendMode => "forbidden-blend-mode",
+            Rule::LayerName => "layer-name",
+            Rule::RequiredGroup => "required-group",
+            Rule::Colorspace => "colorspace",
+            Rule::MaxDimensions => "max-dimensions",
+        })
+    }
+}
+
+/// One broken rule.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Violation {
+    /// The rule.
+    pub(crate) rule: Rule,
+    /// Node that breaks the rule, if it is about one node.
+    pub(crate) node: Option<Uuid>,
+    /// What is wrong, for people to read.
+    pub(crate) message: String,
+}
+
+impl Violation {
+    /// The rule.
+    pub fn rule(&self) -> Rule {
+        self.rule
+    }
+
+    /// Node that breaks the rule, if it is about one node.
+    pub fn node(&self) -> Option<Uuid> {
+        self.node
+    }
+
+    /// What is wrong, for people to read.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.rule, self.message)
+    }
+}
+
+/// Result of [`KraFile::lint()`].
+#[derive(Debug, PartialEq, Eq, Clone, Hash, Default)]
+pub struct LintReport {
+    /// Broken rules, document-wide ones first and then the ones of nodes in document order.
+    pub(crate) violations: Vec<Violation>,
+}
+
+impl LintReport {
+    /// Broken rules, document-wide ones first and then the ones of nodes in document order.
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+
+    /// Whether no rules were broken.
+    pub fn is_clean(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// The report as JSON: an array of objects with the `rule`, the `node` (a UUID or `null`)
+    /// and the `message`.
+    pub fn to_json(&self) -> String {
+        let violations: Vec<String> = self
+            .violations
+            .iter()
+            .map(|violation| {
+                format!(
+                    "{{\"rule\":{},\"node\":{},\"message\":{}}}",
+                    json_string(&violation.rule.to_string()),
+                    violation
+                        .node
+                        .map_or("null".to_owned(), |uuid| json_string(&uuid.to_string())),
+                    json_string(&violation.message)
+                )
+            })
+            .collect();
+        format!("[{}]", violations.join(","))
+    }
+}
+
+impl KraFile {
+    /// Check the document against the rules. Only metadata and the node tree are checked.
+    pub fn lint(&self, rules: &LintRules) -> LintReport {
+        let mut violations = Vec::new();
+        let mut violation = |rule, node, message| {
+            violations.push(Violation {
+                rule,
+                node,
+                message,
+            })
+        };
+
+        let (width, height) = (self.meta().width(), self.meta().height());
+        if let Some((max_width, max_height)) = rules.max_dimensions {
+            if width > max_width || height > max_height {
+                violation(
+                    Rule::MaxDimensions,
+                    None,
+                    format!("image is {width}x{height}, larger than {max_width}x{max_height}"),
+                );
+            }
+        }
+        let allowed = |colorspace| {
+            rules
+                .colorspaces
+                .as_ref()
+                .is_none_or(|allowed| allowed.contains(&colorspace))
+        };
+        if !allowed(self.meta().colorspace()) {
+            violation(
+                Rule::Colorspace,
+                None,
+                format!("image uses {}", self.meta().colorspace()),
+            );
+        }
+        let nodes = self.all_nodes();
+        let layers: Vec<_> = nodes.iter().filter(|node| !node.kind().is_mask()).collect();
+        if let Some(max_layers) = rules.max_layers {
+            if layers.len() > max_layers {
+                violation(
+                    Rule::MaxLayers,
+                    None,
+                    format!("{} layers, more than {max_layers}", layers.len()),
+                );
+            }
+        }
+        for name in &rules.required_groups {
+            let found = layers.iter().any(|node| {
+                matches!(node.node_type(), NodeType::GroupLayer(_)) && node.name() == name
+            });
+            if !found {
+                violation(
+                    Rule::RequiredGroup,
+                    None,
+                    format!("no group named \"{name}\""),
+                );
+            }
+        }
+
+        for node in nodes {
+            let uuid = Some(node.uuid());
+            if let Some(layer) = node.as_layer() {
+                let blend_mode = layer.composite_op();
+                if rules.forbidden_blend_modes.contains(&blend_mode) {
+                    violation(
+                        Rule::ForbiddenBlendMode,
+                        uuid,
+                        format!("\"{}\" uses {blend_mode:?}", node.name()),
+                    );
+                }
+                if let Some(pattern) = &rules.layer_name {
+                    if !pattern.is_match(node.name()) {
+                        violation(
+                            Rule::LayerName,
+                            uuid,
+                            format!("\"{}\" does not match {pattern}", node.name()),
+                        );
+                    }
+                }
+            }
+            if let Some(colorspace) = node.node_type().colorspace() {
+                if !allowed(colorspace) {
+                    violation(
+                        Rule::Colorspace,
+                        uuid,
+                        format!("\"{}\" uses {colorspace}", node.name()),
+                    );
+                }
+            }
+        }
+        LintReport { violations }
+    }
+}
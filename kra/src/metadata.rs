@@ -1,17 +1,28 @@
 //! Types that make up file's metadata
 
-use std::fmt::{self, Display};
+use std::{
+    fmt::{self, Display},
+    ops::RangeInclusive,
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
 
-use getset::Getters;
-use quick_xml::{events::Event, reader::Reader as XmlReader};
+use quick_xml::{
+    escape::escape,
+    events::{BytesStart, Event},
+    name::QName,
+    reader::Reader as XmlReader,
+};
 
 use crate::helper::{
-    event_get_attr, event_to_string, event_unwrap_as_doctype, event_unwrap_as_empty,
-    event_unwrap_as_end, event_unwrap_as_start, get_text_between_tags, next_xml_event, parse_attr,
-    push_and_parse_bool, push_and_parse_value,
+    event_get_attr, event_get_optional_attr, event_to_string, event_unwrap_as_doctype,
+    event_unwrap_as_empty, event_unwrap_as_end, event_unwrap_as_start, get_text_between_tags,
+    next_event_after_declaration, next_xml_event, parse_attr, parse_bool, push_and_parse_bool,
+    push_and_parse_value,
 };
 use crate::{
-    error::{MetadataErrorReason, XmlError},
+    config::DoctypeCheck,
+    error::{DoctypeMismatch, InvalidLanguageTag, MetadataErrorReason, XmlError},
     Colorspace,
 };
 
@@ -25,9 +36,43 @@ const DOCUMENTINFO_XMLNS: &str = r"http://www.calligra.org/DTD/document-info";
 const SYNTAX_VERSION: &str = "2.0";
 const MIMETYPE: &str = "application/x-kra";
 
+// Tools other than Krita may use double quotes or other whitespace, which mean the same
+fn normalize_doctype(doctype: &str) -> String {
+    doctype
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace('"', "'")
+}
+
+fn check_doctype(
+    entry: &'static str,
+    expected: &'static str,
+    found: &str,
+    doctype_check: DoctypeCheck,
+    doctype_mismatches: &mut Vec<DoctypeMismatch>,
+) -> Result<(), MetadataErrorReason> {
+    if normalize_doctype(found) == expected {
+        return Ok(());
+    }
+    match doctype_check {
+        DoctypeCheck::Error => Err(MetadataErrorReason::XmlError(XmlError::AssertionFailed(
+            expected,
+            found.to_owned(),
+        ))),
+        DoctypeCheck::Warn => {
+            doctype_mismatches.push(DoctypeMismatch {
+                entry,
+                expected,
+                found: found.to_owned(),
+            });
+            Ok(())
+        }
+    }
+}
+
 /// Metadata of the image.
-#[derive(Debug, PartialEq, Eq, Clone, Hash, Getters)]
-#[getset(get = "pub", get_copy = "pub")]
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub struct KraMetadata {
     /// Version of Krita under which the file was saved.
     krita_version: String,
@@ -37,6 +82,12 @@ pub struct KraMetadata {
     description: String,
     /// Colorspace of the image.
     colorspace: Colorspace,
+    /// Colorspace name as it is written in the file.
+    colorspace_raw: String,
+    /// Whether the colorspace name was not recognised, and [`Colorspace::RGBA`] was used
+    /// instead. Only happens when reading with
+    /// [`ParsingConfiguration::colorspace_fallback()`](crate::config::ParsingConfiguration::colorspace_fallback).
+    colorspace_fallback: bool,
     /// Color profile of the image.
     profile: String,
     /// Height, in pixels.
@@ -44,9 +95,9 @@ pub struct KraMetadata {
     /// Width, in pixels.
     width: u32,
     /// Dots per inch vertically.
-    y_res: u32,
+    y_res: OF<f64>,
     /// Dots per inch horisontally.
-    x_res: u32,
+    x_res: OF<f64>,
 
     //TODO: look into KraMetadataEnd for these two fields
     /// Projection background color.
@@ -55,6 +106,109 @@ pub struct KraMetadata {
     global_assistants_color: String,
     /// Mirror axis configuration.
     mirror_axis: MirrorAxis,
+    /// Guides of the image.
+    guides: Guides,
+    /// Animation settings, if the document has them.
+    animation: Option<Animation>,
+}
+
+impl KraMetadata {
+    /// Version of Krita under which the file was saved.
+    pub fn krita_version(&self) -> &str {
+        &self.krita_version
+    }
+
+    /// Name of the image.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Description of the image.
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Colorspace of the image.
+    pub fn colorspace(&self) -> Colorspace {
+        self.colorspace
+    }
+
+    /// Colorspace name as it is written in the file.
+    pub fn colorspace_raw(&self) -> &str {
+        &self.colorspace_raw
+    }
+
+    /// Whether the colorspace name was not recognised, and [`Colorspace::RGBA`] was used
+    /// instead. Only happens when reading with
+    /// [`ParsingConfiguration::colorspace_fallback()`](crate::config::ParsingConfiguration::colorspace_fallback).
+    pub fn colorspace_fallback(&self) -> bool {
+        self.colorspace_fallback
+    }
+
+    /// Color profile of the image.
+    pub fn profile(&self) -> &str {
+        &self.profile
+    }
+
+    /// Height, in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Width, in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Dots per inch vertically.
+    pub fn y_res(&self) -> f64 {
+        self.y_res.into_inner()
+    }
+
+    /// Dots per inch horisontally.
+    pub fn x_res(&self) -> f64 {
+        self.x_res.into_inner()
+    }
+
+    /// Projection background color.
+    pub fn projection_background_color(&self) -> &str {
+        &self.projection_background_color
+    }
+
+    /// Global assistants color.
+    pub fn global_assistants_color(&self) -> &str {
+        &self.global_assistants_color
+    }
+
+    /// Mirror axis configuration.
+    pub fn mirror_axis(&self) -> &MirrorAxis {
+        &self.mirror_axis
+    }
+
+    /// Guides of the image. Empty if the image has none.
+    pub fn guides(&self) -> &Guides {
+        &self.guides
+    }
+
+    /// Animation settings: framerate, playback range and the current frame.
+    /// `None` if the document does not have them, as in documents of old versions of Krita.
+    pub fn animation(&self) -> Option<&Animation> {
+        self.animation.as_ref()
+    }
+
+    /// Positions of horizontal and vertical guides, in pixels from the top and left edges.
+    pub fn guide_pixels(&self) -> (Vec<f64>, Vec<f64>) {
+        let to_pixels = |positions: &[OF<f64>], res: f64| {
+            positions
+                .iter()
+                .map(|position| position.into_inner() * res / 72.0)
+                .collect()
+        };
+        (
+            to_pixels(&self.guides.horizontal, self.y_res()),
+            to_pixels(&self.guides.vertical, self.x_res()),
+        )
+    }
 }
 
 impl Display for KraMetadata {
@@ -64,12 +218,29 @@ impl Display for KraMetadata {
 }
 
 impl KraMetadata {
+    /// Dots per inch horisontally, rounded to an integer.
+    pub fn x_res_rounded(&self) -> u32 {
+        self.x_res.round() as u32
+    }
+
+    /// Dots per inch vertically, rounded to an integer.
+    pub fn y_res_rounded(&self) -> u32 {
+        self.y_res.round() as u32
+    }
+
+    pub(crate) fn set_size(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+    }
+
     pub(crate) fn new(start: KraMetadataStart, end: KraMetadataEnd) -> KraMetadata {
         KraMetadata {
             krita_version: start.krita_version,
             name: start.name,
             description: start.description,
             colorspace: start.colorspace,
+            colorspace_raw: start.colorspace_raw,
+            colorspace_fallback: start.colorspace_fallback,
             profile: start.profile,
             height: start.height,
             width: start.width,
@@ -78,6 +249,8 @@ impl KraMetadata {
             projection_background_color: end.projection_background_color,
             global_assistants_color: end.global_assistants_color,
             mirror_axis: end.mirror_axis,
+            guides: end.guides,
+            animation: end.animation,
         }
     }
 }
@@ -93,6 +266,10 @@ pub(crate) struct KraMetadataStart {
     description: String,
     /// Colorspace of the image.
     colorspace: Colorspace,
+    /// Colorspace name as it is written in the file.
+    colorspace_raw: String,
+    /// Whether the colorspace name was not recognised.
+    colorspace_fallback: bool,
     /// Color profile of the image.
     profile: String,
     /// Height, in pixels.
@@ -100,24 +277,38 @@ pub(crate) struct KraMetadataStart {
     /// Width, in pixels.
     width: u32,
     /// Dots per inch vertically.
-    y_res: u32,
+    y_res: OF<f64>,
     /// Dots per inch horisontally.
-    x_res: u32,
+    x_res: OF<f64>,
 }
 
 impl KraMetadataStart {
-    pub(crate) fn from_xml(reader: &mut XmlReader<&[u8]>) -> Result<Self, MetadataErrorReason> {
-        //TODO: do we need to check this declaration properly?
-        next_xml_event(reader)?;
+    // Width and height, in pixels
+    pub(crate) fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
 
-        let event = next_xml_event(reader)?;
+    pub(crate) fn krita_version(&self) -> &str {
+        &self.krita_version
+    }
+
+    // If `colorspace_fallback` is set, unknown colorspace becomes RGBA instead of an error
+    pub(crate) fn from_xml(
+        reader: &mut XmlReader<&[u8]>,
+        colorspace_fallback: bool,
+        doctype_check: DoctypeCheck,
+        doctype_mismatches: &mut Vec<DoctypeMismatch>,
+    ) -> Result<Self, MetadataErrorReason> {
+        //TODO: do we need to check this declaration properly?
+        let event = next_event_after_declaration(reader)?;
         let doctype = event_unwrap_as_doctype(event)?.unescape()?;
-        if doctype != MAINDOC_DOCTYPE {
-            return Err(MetadataErrorReason::XmlError(XmlError::AssertionFailed(
-                MAINDOC_DOCTYPE,
-                doctype.to_string(),
-            )));
-        };
+        check_doctype(
+            "maindoc.xml",
+            MAINDOC_DOCTYPE,
+            &doctype,
+            doctype_check,
+            doctype_mismatches,
+        )?;
 
         let event = next_xml_event(reader)?;
         let doc_start = event_unwrap_as_start(event)?;
@@ -153,11 +344,15 @@ impl KraMetadataStart {
         let profile = event_get_attr(&image_props, "profile")?;
         let name = event_get_attr(&image_props, "name")?;
         let description = event_get_attr(&image_props, "description")?;
-        let colorspace = Colorspace::try_from(
-            event_get_attr(&image_props, "colorspacename")?
-                .unescape_value()?
-                .as_ref(),
-        )?;
+        let colorspace_raw = event_get_attr(&image_props, "colorspacename")?
+            .unescape_value()?
+            .to_string();
+        let (colorspace, colorspace_fallback) = match Colorspace::try_from(colorspace_raw.as_str())
+        {
+            Ok(colorspace) => (colorspace, false),
+            Err(_) if colorspace_fallback => (Colorspace::RGBA, true),
+            Err(err) => return Err(err.into()),
+        };
         let height = event_get_attr(&image_props, "height")?;
         let width = event_get_attr(&image_props, "width")?;
         let x_res = event_get_attr(&image_props, "x-res")?;
@@ -168,6 +363,8 @@ impl KraMetadataStart {
             name: name.unescape_value()?.to_string(),
             description: description.unescape_value()?.to_string(),
             colorspace,
+            colorspace_raw,
+            colorspace_fallback,
             profile: profile.unescape_value()?.to_string(),
             height: parse_attr(height)?,
             width: parse_attr(width)?,
@@ -188,6 +385,10 @@ pub(crate) struct KraMetadataEnd {
     global_assistants_color: String,
     /// Mirror axis configuration.
     mirror_axis: MirrorAxis,
+    /// Guides of the image.
+    guides: Guides,
+    /// Animation settings.
+    animation: Option<Animation>,
 }
 
 impl KraMetadataEnd {
@@ -201,37 +402,138 @@ impl KraMetadataEnd {
         let event = next_xml_event(reader)?;
         let tag = event_unwrap_as_empty(event)?;
         let global_assistants_color = parse_attr(event_get_attr(&tag, "SimpleColorData")?)?;
-        let mirror_axis = MirrorAxis::from_xml(reader)?;
+
+        // Elements that are not read, like the grid or compositions, are skipped
+        let mut guides = Guides::default();
+        let mut animation = None;
+        let mut mirror_axis = None;
+        let mirror_axis = loop {
+            match next_xml_event(reader)? {
+                Event::Start(tag) if tag.name().as_ref() == b"MirrorAxis" => {
+                    mirror_axis = Some(MirrorAxis::from_xml(reader)?);
+                }
+                Event::Start(tag) if tag.name().as_ref() == b"guides" => {
+                    guides = Guides::from_xml(reader, &tag)?;
+                }
+                Event::Start(tag) if tag.name().as_ref() == b"animation" => {
+                    animation = Some(Animation::from_xml(reader)?);
+                }
+                Event::Start(tag) => {
+                    reader
+                        .read_to_end(tag.name())
+                        .map_err(XmlError::ParsingError)?;
+                }
+                Event::Empty(_) | Event::Text(_) | Event::Comment(_) => (),
+                // The end of <IMAGE>, which must have had the mirror axis
+                event @ (Event::End(_) | Event::Eof) => match mirror_axis.take() {
+                    Some(mirror_axis) => break mirror_axis,
+                    None => {
+                        return Err(XmlError::EventError(
+                            "MirrorAxis start event",
+                            event_to_string(&event)?,
+                        )
+                        .into())
+                    }
+                },
+                other => {
+                    return Err(XmlError::EventError(
+                        "MirrorAxis start event",
+                        event_to_string(&other)?,
+                    )
+                    .into())
+                }
+            }
+        };
 
         Ok(KraMetadataEnd {
             projection_background_color,
             global_assistants_color,
             mirror_axis,
+            guides,
+            animation,
         })
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Hash, Getters)]
-#[getset(get = "pub", get_copy = "pub")]
+/// Mirror axis configuration.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub struct MirrorAxis {
+    /// Whether horizontal mirroring is enabled.
     mirror_horizontal: bool,
+    /// Whether vertical mirroring is enabled.
     mirror_vertical: bool,
+    /// Whether horizontal axis is locked.
     lock_horizontal: bool,
+    /// Whether vertical axis is locked.
     lock_vertical: bool,
+    /// Whether horizontal axis decoration is hidden.
     hide_horizontal_decoration: bool,
+    /// Whether vertical axis decoration is hidden.
     hide_vertical_decoration: bool,
 
+    /// Size of the axis handles.
     handle_size: OF<f32>,
+    /// Position of the horizontal axis handle.
     horizontal_handle_position: OF<f32>,
+    /// Position of the vertical axis handle.
     vertical_handle_position: OF<f32>,
+    /// Position of the axes' center.
     axis_position: [OF<f32>; 2],
 }
 
 impl MirrorAxis {
-    pub(crate) fn from_xml(reader: &mut XmlReader<&[u8]>) -> Result<Self, MetadataErrorReason> {
-        // <MirrorAxis>
-        next_xml_event(reader)?;
+    /// Whether horizontal mirroring is enabled.
+    pub fn mirror_horizontal(&self) -> bool {
+        self.mirror_horizontal
+    }
 
+    /// Whether vertical mirroring is enabled.
+    pub fn mirror_vertical(&self) -> bool {
+        self.mirror_vertical
+    }
+
+    /// Whether horizontal axis is locked.
+    pub fn lock_horizontal(&self) -> bool {
+        self.lock_horizontal
+    }
+
+    /// Whether vertical axis is locked.
+    pub fn lock_vertical(&self) -> bool {
+        self.lock_vertical
+    }
+
+    /// Whether horizontal axis decoration is hidden.
+    pub fn hide_horizontal_decoration(&self) -> bool {
+        self.hide_horizontal_decoration
+    }
+
+    /// Whether vertical axis decoration is hidden.
+    pub fn hide_vertical_decoration(&self) -> bool {
+        self.hide_vertical_decoration
+    }
+
+    /// Size of the axis handles.
+    pub fn handle_size(&self) -> f32 {
+        self.handle_size.into_inner()
+    }
+
+    /// Position of the horizontal axis handle.
+    pub fn horizontal_handle_position(&self) -> f32 {
+        self.horizontal_handle_position.into_inner()
+    }
+
+    /// Position of the vertical axis handle.
+    pub fn vertical_handle_position(&self) -> f32 {
+        self.vertical_handle_position.into_inner()
+    }
+
+    /// Position of the axes' center.
+    pub fn axis_position(&self) -> [f32; 2] {
+        self.axis_position.map(OF::into_inner)
+    }
+
+    // Starts after <MirrorAxis>
+    pub(crate) fn from_xml(reader: &mut XmlReader<&[u8]>) -> Result<Self, MetadataErrorReason> {
         let mirror_horizontal = push_and_parse_bool(reader)?;
         let mirror_vertical = push_and_parse_bool(reader)?;
         let lock_horizontal = push_and_parse_bool(reader)?;
@@ -247,6 +549,10 @@ impl MirrorAxis {
         let tag = event_unwrap_as_empty(event)?;
         let x = event_get_attr(&tag, "x")?;
         let y = event_get_attr(&tag, "y")?;
+        // Settings after these, if a newer version of Krita writes any, are skipped
+        reader
+            .read_to_end(QName(b"MirrorAxis"))
+            .map_err(XmlError::ParsingError)?;
 
         Ok(MirrorAxis {
             mirror_horizontal,
@@ -263,56 +569,911 @@ impl MirrorAxis {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Hash, Getters)]
-#[getset(get = "pub", get_copy = "pub")]
+/// Guides of the image.
+///
+/// Positions are in points (1/72 of an inch) from the top or left edge of the image, as
+/// Krita stores them. [`KraMetadata::guide_pixels()`] gives them in pixels.
+#[derive(Debug, PartialEq, Eq, Clone, Hash, Default)]
+pub struct Guides {
+    /// Whether guides are shown.
+    visible: bool,
+    /// Whether guides are locked.
+    locked: bool,
+    /// Whether painting snaps to guides.
+    snap: bool,
+    /// Positions of horizontal guides.
+    horizontal: Vec<OF<f64>>,
+    /// Positions of vertical guides.
+    vertical: Vec<OF<f64>>,
+}
+
+impl Guides {
+    /// Whether guides are shown.
+    pub fn visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Whether guides are locked.
+    pub fn locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Whether painting snaps to guides.
+    pub fn snap(&self) -> bool {
+        self.snap
+    }
+
+    /// Positions of horizontal guides, in points from the top edge.
+    pub fn horizontal(&self) -> Vec<f64> {
+        self.horizontal
+            .iter()
+            .map(|value| value.into_inner())
+            .collect()
+    }
+
+    /// Positions of vertical guides, in points from the left edge.
+    pub fn vertical(&self) -> Vec<f64> {
+        self.vertical
+            .iter()
+            .map(|value| value.into_inner())
+            .collect()
+    }
+
+    /// Whether there are no guides.
+    pub fn is_empty(&self) -> bool {
+        self.horizontal.is_empty() && self.vertical.is_empty()
+    }
+
+    // Starts after <guides>, which is given
+    pub(crate) fn from_xml(
+        reader: &mut XmlReader<&[u8]>,
+        tag: &BytesStart,
+    ) -> Result<Self, MetadataErrorReason> {
+        let flag = |name| -> Result<bool, XmlError> {
+            event_get_optional_attr(tag, name)?.map_or(Ok(false), parse_bool)
+        };
+        let mut guides = Guides {
+            visible: flag("showGuides")?,
+            locked: flag("lockGuides")?,
+            snap: flag("snapToGuides")?,
+            ..Guides::default()
+        };
+        loop {
+            match next_xml_event(reader)? {
+                Event::Start(tag) if tag.name().as_ref() == b"horizontalGuides" => {
+                    guides.horizontal = guide_positions(reader)?;
+                }
+                Event::Start(tag) if tag.name().as_ref() == b"verticalGuides" => {
+                    guides.vertical = guide_positions(reader)?;
+                }
+                // Like the unit of rulers
+                Event::Start(tag) => {
+                    reader
+                        .read_to_end(tag.name())
+                        .map_err(XmlError::ParsingError)?;
+                }
+                Event::End(_) => return Ok(guides),
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Animation settings of the image.
+///
+/// Frames are numbered from 0, and the playback range includes both of its ends.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct Animation {
+    /// Frames per second.
+    framerate: u32,
+    /// First frame of the playback range.
+    start: i32,
+    /// Last frame of the playback range.
+    end: i32,
+    /// Frame the playhead is at.
+    current_time: i32,
+}
+
+impl Default for Animation {
+    // What Krita uses for new documents
+    fn default() -> Self {
+        Animation {
+            framerate: 24,
+            start: 0,
+            end: 100,
+            current_time: 0,
+        }
+    }
+}
+
+impl Animation {
+    /// Frames per second.
+    pub fn framerate(&self) -> u32 {
+        self.framerate
+    }
+
+    /// Frames of the playback range.
+    pub fn range(&self) -> RangeInclusive<i32> {
+        self.start..=self.end
+    }
+
+    /// Frame the playhead is at.
+    pub fn current_time(&self) -> i32 {
+        self.current_time
+    }
+
+    /// Number of frames in the playback range.
+    pub fn duration_frames(&self) -> u32 {
+        (self.end as i64 - self.start as i64 + 1).clamp(0, u32::MAX as i64) as u32
+    }
+
+    /// Length of the playback range, in seconds. 0 if the framerate is 0.
+    pub fn duration_seconds(&self) -> f64 {
+        match self.framerate {
+            0 => 0.0,
+            framerate => self.duration_frames() as f64 / framerate as f64,
+        }
+    }
+
+    /// Frame shown `ms` milliseconds after playback starts at the beginning of the range.
+    /// `None` if playback is over by then, or if the framerate is 0.
+    pub fn frame_at_time(&self, ms: u64) -> Option<i32> {
+        if self.framerate == 0 {
+            return None;
+        }
+        let offset = ms as u128 * self.framerate as u128 / 1000;
+        if offset >= self.duration_frames() as u128 {
+            return None;
+        }
+        Some(self.start + offset as i32)
+    }
+
+    // Starts after <animation>; settings that are missing keep Krita's defaults
+    pub(crate) fn from_xml(reader: &mut XmlReader<&[u8]>) -> Result<Self, XmlError> {
+        let mut animation = Animation::default();
+        loop {
+            match next_xml_event(reader)? {
+                Event::Empty(tag) => match tag.name().as_ref() {
+                    b"framerate" => {
+                        animation.framerate = parse_attr(event_get_attr(&tag, "value")?)?
+                    }
+                    b"range" => {
+                        animation.start = parse_attr(event_get_attr(&tag, "from")?)?;
+                        animation.end = parse_attr(event_get_attr(&tag, "to")?)?;
+                    }
+                    b"currentTime" => {
+                        animation.current_time = parse_attr(event_get_attr(&tag, "value")?)?
+                    }
+                    _ => (),
+                },
+                // Like export settings
+                Event::Start(tag) => {
+                    reader
+                        .read_to_end(tag.name())
+                        .map_err(XmlError::ParsingError)?;
+                }
+                Event::End(_) => return Ok(animation),
+                Event::Eof => {
+                    return Err(XmlError::EventError("animation end event", "EOF".into()))
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+// Values of <item_N type="value" value="..."/> until the end of the array
+fn guide_positions(reader: &mut XmlReader<&[u8]>) -> Result<Vec<OF<f64>>, XmlError> {
+    let mut positions = Vec::new();
+    loop {
+        match next_xml_event(reader)? {
+            Event::Empty(tag) => positions.push(parse_attr(event_get_attr(&tag, "value")?)?),
+            Event::End(_) => return Ok(positions),
+            other => return Err(XmlError::EventError("array item", event_to_string(&other)?)),
+        }
+    }
+}
+
+/// General information about the document.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub struct DocInfoAbout {
+    /// Title of the document.
     title: String,
+    /// Description of the document.
     description: String,
+    /// Subject of the document.
     subject: String,
+    /// Abstract of the document.
     r#abstract: String,
+    /// Keywords.
     keyword: String,
+    /// Name of the person who created the document.
     initial_creator: String,
+    /// How many times the document was saved.
     editing_cycles: String,
+    /// Total time spent editing the document.
     editing_time: String,
+    /// Date of the last modification.
     date: String,
+    /// Date of creation.
     creation_date: String,
+    /// Language of the document.
     language: String,
+    /// License of the document.
     license: String,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Hash, Getters)]
-#[getset(get = "pub", get_copy = "pub")]
+impl DocInfoAbout {
+    /// Title of the document.
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Description of the document.
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Subject of the document.
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    /// Abstract of the document.
+    pub fn r#abstract(&self) -> &str {
+        &self.r#abstract
+    }
+
+    /// Keywords.
+    pub fn keyword(&self) -> &str {
+        &self.keyword
+    }
+
+    /// Name of the person who created the document.
+    pub fn initial_creator(&self) -> &str {
+        &self.initial_creator
+    }
+
+    /// How many times the document was saved.
+    pub fn editing_cycles(&self) -> &str {
+        &self.editing_cycles
+    }
+
+    /// Total time spent editing the document.
+    pub fn editing_time(&self) -> &str {
+        &self.editing_time
+    }
+
+    /// Total time spent editing the document, which Krita stores in seconds.
+    /// `None` if it is missing or is not a number.
+    pub fn editing_duration(&self) -> Option<Duration> {
+        self.editing_time
+            .trim()
+            .parse()
+            .ok()
+            .map(Duration::from_secs)
+    }
+
+    /// How many times the document was saved, `None` if it is missing or is not a number.
+    pub fn editing_cycle_count(&self) -> Option<u64> {
+        self.editing_cycles.trim().parse().ok()
+    }
+
+    /// Date of the last modification.
+    pub fn date(&self) -> &str {
+        &self.date
+    }
+
+    /// Date of creation.
+    pub fn creation_date(&self) -> &str {
+        &self.creation_date
+    }
+
+    /// Language of the document, as it is written.
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    /// Language of the document as a tag. `None` if there is no language, or it is not a
+    /// tag that can be parsed (the text is still given by [`language()`](Self::language)).
+    pub fn language_tag(&self) -> Option<LanguageTag> {
+        self.language.parse().ok()
+    }
+
+    /// License of the document.
+    pub fn license(&self) -> &str {
+        &self.license
+    }
+}
+
+/// BCP 47 language tag, like `en`, `pt-BR` or `sr-Latn-RS`.
+///
+/// Subtags are kept in their canonical case: lowercase language, titlecase script and
+/// uppercase region. Underscores are accepted in place of hyphens, and POSIX locales like
+/// `de_DE.UTF-8` are read without their encoding and modifier. Tags that are entirely
+/// private use or grandfathered are not accepted.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct LanguageTag {
+    language: String,
+    script: Option<String>,
+    region: Option<String>,
+    variants: Vec<String>,
+    // Extensions and private use subtags, joined with hyphens
+    extensions: String,
+}
+
+impl LanguageTag {
+    /// Primary language subtag, with extended language subtags if there are any
+    /// (like `zh-yue`).
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    /// Script subtag, like `Latn`.
+    pub fn script(&self) -> Option<&str> {
+        self.script.as_deref()
+    }
+
+    /// Region subtag, like `BR` or `419`.
+    pub fn region(&self) -> Option<&str> {
+        self.region.as_deref()
+    }
+
+    /// Variant subtags, like `1996`.
+    pub fn variants(&self) -> &[String] {
+        &self.variants
+    }
+
+    /// Extension and private use subtags (like `u-ca-buddhist` or `x-private`),
+    /// empty if there are none.
+    pub fn extensions(&self) -> &str {
+        &self.extensions
+    }
+
+    /// Whether the tag falls under the language range, as in basic filtering of RFC 4647:
+    /// `de` matches `de` and `de-AT`, but not `den`. `*` matches every tag.
+    pub fn matches(&self, range: &str) -> bool {
+        let tag = self.to_string().to_ascii_lowercase();
+        let range = range.replace('_', "-").to_ascii_lowercase();
+        range == "*"
+            || tag == range
+            || tag
+                .strip_prefix(&range)
+                .is_some_and(|rest| rest.starts_with('-'))
+    }
+}
+
+impl FromStr for LanguageTag {
+    type Err = InvalidLanguageTag;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidLanguageTag(text.to_owned());
+        let is_alpha = |subtag: &str, lengths: RangeInclusive<usize>| {
+            lengths.contains(&subtag.len()) && subtag.bytes().all(|byte| byte.is_ascii_alphabetic())
+        };
+        // POSIX locales may end with an encoding and a modifier
+        let tag = text.trim().split(['.', '@']).next().unwrap_or_default();
+        let mut subtags = tag.split(['-', '_']).peekable();
+
+        let mut language = match subtags.next() {
+            Some(language) if is_alpha(language, 2..=8) => language.to_ascii_lowercase(),
+            _ => return Err(invalid()),
+        };
+        if language.len() <= 3 {
+            while let Some(extlang) = subtags.next_if(|subtag| is_alpha(subtag, 3..=3)) {
+                language.push('-');
+                language.push_str(&extlang.to_ascii_lowercase());
+            }
+        }
+        let script = subtags
+            .next_if(|subtag| is_alpha(subtag, 4..=4))
+            .map(|script| script[..1].to_ascii_uppercase() + &script[1..].to_ascii_lowercase());
+        let region = subtags
+            .next_if(|subtag| {
+                is_alpha(subtag, 2..=2)
+                    || subtag.len() == 3 && subtag.bytes().all(|byte| byte.is_ascii_digit())
+            })
+            .map(|region| region.to_ascii_uppercase());
+        let mut variants = Vec::new();
+        while let Some(variant) = subtags.next_if(|subtag| {
+            let alphanumeric = subtag.bytes().all(|byte| byte.is_ascii_alphanumeric());
+            let starts_with_digit = subtag.starts_with(|c: char| c.is_ascii_digit());
+            alphanumeric
+                && ((5..=8).contains(&subtag.len()) || subtag.len() == 4 && starts_with_digit)
+        }) {
+            variants.push(variant.to_ascii_lowercase());
+        }
+
+        // Each singleton starts an extension, `x` starts private use until the end
+        let extensions: Vec<String> = subtags.map(str::to_ascii_lowercase).collect();
+        let (mut in_extension, mut private_use, mut expects_subtag) = (false, false, false);
+        for subtag in &extensions {
+            let alphanumeric = subtag.bytes().all(|byte| byte.is_ascii_alphanumeric());
+            if !alphanumeric || !(1..=8).contains(&subtag.len()) {
+                return Err(invalid());
+            }
+            match subtag.len() {
+                _ if private_use => expects_subtag = false,
+                1 if !expects_subtag => {
+                    private_use = subtag == "x";
+                    in_extension = true;
+                    expects_subtag = true;
+                }
+                2.. if in_extension => expects_subtag = false,
+                _ => return Err(invalid()),
+            }
+        }
+        if expects_subtag {
+            return Err(invalid());
+        }
+
+        Ok(LanguageTag {
+            language,
+            script,
+            region,
+            variants,
+            extensions: extensions.join("-"),
+        })
+    }
+}
+
+impl Display for LanguageTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.language)?;
+        for subtag in self
+            .script
+            .iter()
+            .chain(&self.region)
+            .chain(&self.variants)
+            .chain(Some(&self.extensions).filter(|extensions| !extensions.is_empty()))
+        {
+            write!(f, "-{}", subtag)?;
+        }
+        Ok(())
+    }
+}
+
+/// Information about the author of the document.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub struct DocInfoAuthor {
+    /// Full name of the author.
     full_name: String,
+    /// First name of the author.
     creator_first_name: String,
+    /// Last name of the author.
     creator_last_name: String,
+    /// Initials of the author.
     initial: String,
+    /// Title of the author.
     author_title: String,
+    /// Position of the author.
     position: String,
+    /// Company of the author.
     company: String,
 }
 
+impl DocInfoAuthor {
+    /// Full name of the author.
+    pub fn full_name(&self) -> &str {
+        &self.full_name
+    }
+
+    /// First name of the author.
+    pub fn creator_first_name(&self) -> &str {
+        &self.creator_first_name
+    }
+
+    /// Last name of the author.
+    pub fn creator_last_name(&self) -> &str {
+        &self.creator_last_name
+    }
+
+    /// Initials of the author.
+    pub fn initial(&self) -> &str {
+        &self.initial
+    }
+
+    /// Title of the author.
+    pub fn author_title(&self) -> &str {
+        &self.author_title
+    }
+
+    /// Position of the author.
+    pub fn position(&self) -> &str {
+        &self.position
+    }
+
+    /// Company of the author.
+    pub fn company(&self) -> &str {
+        &self.company
+    }
+}
+
+// Date in the format of Krita's `date` fields, like 2024-01-01T00:00:00, in UTC
+pub(crate) fn format_date(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let (days, time) = (seconds / 86400, seconds % 86400);
+    // Days to a civil date, from Howard Hinnant's algorithms
+    let days = days as i64 + 719468;
+    let era = days / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// Author profile, like the ones Krita keeps in `.authorinfo` files, that can be written
+/// into [`DocInfoAuthor`] of documents, see
+/// [`WriteOptions::author_profile()`](crate::writer::WriteOptions::author_profile).
+///
+/// Fields are empty unless they are set.
+#[derive(Debug, PartialEq, Eq, Clone, Hash, Default)]
+pub struct AuthorProfile {
+    pub(crate) nickname: String,
+    pub(crate) given_name: String,
+    pub(crate) initials: String,
+    pub(crate) family_name: String,
+    pub(crate) title: String,
+    pub(crate) position: String,
+    pub(crate) company: String,
+}
+
+impl AuthorProfile {
+    /// Profile with every field empty.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the profile from the contents of Krita's `.authorinfo` file.
+    ///
+    /// Krita writes the author's full name as the nickname and uses the middle name for
+    /// initials, and so does this. Contacts and unknown elements are skipped.
+    pub fn from_authorinfo(xml: &str) -> Result<Self, XmlError> {
+        let mut reader = XmlReader::from_str(xml);
+        let mut profile = AuthorProfile::new();
+        let mut depth = 0;
+        let mut field: Option<&mut String> = None;
+        loop {
+            match reader.read_event()? {
+                Event::Start(start) => {
+                    depth += 1;
+                    field = match (depth, start.name().as_ref()) {
+                        (2, b"nickname") => Some(&mut profile.nickname),
+                        (2, b"givenname") => Some(&mut profile.given_name),
+                        (2, b"middlename") => Some(&mut profile.initials),
+                        (2, b"familyname") => Some(&mut profile.family_name),
+                        (2, b"title") => Some(&mut profile.title),
+                        (2, b"position") => Some(&mut profile.position),
+                        (2, b"company") => Some(&mut profile.company),
+                        _ => None,
+                    };
+                }
+                Event::End(_) => {
+                    depth -= 1;
+                    field = None;
+                }
+                Event::Text(text) => {
+                    if let Some(field) = field.as_mut() {
+                        field.push_str(&text.unescape()?);
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        Ok(profile)
+    }
+
+    /// Set the nickname, which is written as the full name.
+    pub fn nickname(mut self, nickname: &str) -> Self {
+        self.nickname = nickname.to_owned();
+        self
+    }
+
+    /// Nickname, which is written as the full name.
+    pub fn get_nickname(&self) -> &str {
+        &self.nickname
+    }
+
+    /// Set the first name.
+    pub fn given_name(mut self, given_name: &str) -> Self {
+        self.given_name = given_name.to_owned();
+        self
+    }
+
+    /// First name.
+    pub fn get_given_name(&self) -> &str {
+        &self.given_name
+    }
+
+    /// Set the initials.
+    pub fn initials(mut self, initials: &str) -> Self {
+        self.initials = initials.to_owned();
+        self
+    }
+
+    /// Initials.
+    pub fn get_initials(&self) -> &str {
+        &self.initials
+    }
+
+    /// Set the last name.
+    pub fn family_name(mut self, family_name: &str) -> Self {
+        self.family_name = family_name.to_owned();
+        self
+    }
+
+    /// Last name.
+    pub fn get_family_name(&self) -> &str {
+        &self.family_name
+    }
+
+    /// Set the title of the author.
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = title.to_owned();
+        self
+    }
+
+    /// Title of the author.
+    pub fn get_title(&self) -> &str {
+        &self.title
+    }
+
+    /// Set the position of the author.
+    pub fn position(mut self, position: &str) -> Self {
+        self.position = position.to_owned();
+        self
+    }
+
+    /// Position of the author.
+    pub fn get_position(&self) -> &str {
+        &self.position
+    }
+
+    /// Set the company of the author.
+    pub fn company(mut self, company: &str) -> Self {
+        self.company = company.to_owned();
+        self
+    }
+
+    /// Company of the author.
+    pub fn get_company(&self) -> &str {
+        &self.company
+    }
+}
+
+/// How an [`AuthorProfile`] changes author fields of the document.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+pub enum AuthorUpdate {
+    /// Every author field is taken from the profile, empty ones included, as Krita does
+    /// when it saves.
+    #[default]
+    Replace,
+    /// Only fields that are empty in the document are taken from the profile.
+    Fill,
+}
+
+/// Text field of [`DocumentInfo`] that can be changed, see
+/// [`KraFile::set_doc_info()`](crate::KraFile::set_doc_info).
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[non_exhaustive]
+pub enum DocInfoField {
+    /// Title of the document.
+    Title,
+    /// Description of the document.
+    Description,
+    /// Subject of the document.
+    Subject,
+    /// Abstract of the document.
+    Abstract,
+    /// Keywords.
+    Keyword,
+    /// Name of the person who created the document.
+    InitialCreator,
+    /// Language of the document.
+    Language,
+    /// License of the document.
+    License,
+    /// Full name of the author.
+    FullName,
+    /// First name of the author.
+    CreatorFirstName,
+    /// Last name of the author.
+    CreatorLastName,
+    /// Initials of the author.
+    Initial,
+    /// Title of the author.
+    AuthorTitle,
+    /// Position of the author.
+    Position,
+    /// Company of the author.
+    Company,
+}
+
+impl DocInfoField {
+    /// All fields, in the order they are written.
+    pub const ALL: [DocInfoField; 15] = [
+        DocInfoField::Title,
+        DocInfoField::Description,
+        DocInfoField::Subject,
+        DocInfoField::Abstract,
+        DocInfoField::Keyword,
+        DocInfoField::InitialCreator,
+        DocInfoField::Language,
+        DocInfoField::License,
+        DocInfoField::FullName,
+        DocInfoField::CreatorFirstName,
+        DocInfoField::CreatorLastName,
+        DocInfoField::Initial,
+        DocInfoField::AuthorTitle,
+        DocInfoField::Position,
+        DocInfoField::Company,
+    ];
+}
+
 /// File metadata.
-#[derive(Debug, PartialEq, Eq, Clone, Hash, Getters)]
-#[getset(get = "pub", get_copy = "pub")]
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub struct DocumentInfo {
+    /// General information.
     about: DocInfoAbout,
+    /// Author information.
     author: DocInfoAuthor,
 }
 
 impl DocumentInfo {
-    pub(crate) fn from_xml(reader: &mut XmlReader<&[u8]>) -> Result<Self, MetadataErrorReason> {
-        //TODO: as with maindoc, this skips initial declaration
-        let _event = next_xml_event(reader)?;
+    /// General information.
+    pub fn about(&self) -> &DocInfoAbout {
+        &self.about
+    }
 
-        let event = next_xml_event(reader)?;
-        let doctype = event_unwrap_as_doctype(event)?.unescape()?;
-        if doctype != DOCUMENTINFO_DOCTYPE {
-            return Err(MetadataErrorReason::XmlError(XmlError::AssertionFailed(
-                DOCUMENTINFO_DOCTYPE,
-                doctype.to_string(),
-            )));
+    /// Author information.
+    pub fn author(&self) -> &DocInfoAuthor {
+        &self.author
+    }
+
+    /// Value of the field.
+    pub fn field(&self, field: DocInfoField) -> &str {
+        match field {
+            DocInfoField::Title => &self.about.title,
+            DocInfoField::Description => &self.about.description,
+            DocInfoField::Subject => &self.about.subject,
+            DocInfoField::Abstract => &self.about.r#abstract,
+            DocInfoField::Keyword => &self.about.keyword,
+            DocInfoField::InitialCreator => &self.about.initial_creator,
+            DocInfoField::Language => &self.about.language,
+            DocInfoField::License => &self.about.license,
+            DocInfoField::FullName => &self.author.full_name,
+            DocInfoField::CreatorFirstName => &self.author.creator_first_name,
+            DocInfoField::CreatorLastName => &self.author.creator_last_name,
+            DocInfoField::Initial => &self.author.initial,
+            DocInfoField::AuthorTitle => &self.author.author_title,
+            DocInfoField::Position => &self.author.position,
+            DocInfoField::Company => &self.author.company,
+        }
+    }
+
+    pub(crate) fn set_field(&mut self, field: DocInfoField, value: &str) {
+        let target = match field {
+            DocInfoField::Title => &mut self.about.title,
+            DocInfoField::Description => &mut self.about.description,
+            DocInfoField::Subject => &mut self.about.subject,
+            DocInfoField::Abstract => &mut self.about.r#abstract,
+            DocInfoField::Keyword => &mut self.about.keyword,
+            DocInfoField::InitialCreator => &mut self.about.initial_creator,
+            DocInfoField::Language => &mut self.about.language,
+            DocInfoField::License => &mut self.about.license,
+            DocInfoField::FullName => &mut self.author.full_name,
+            DocInfoField::CreatorFirstName => &mut self.author.creator_first_name,
+            DocInfoField::CreatorLastName => &mut self.author.creator_last_name,
+            DocInfoField::Initial => &mut self.author.initial,
+            DocInfoField::AuthorTitle => &mut self.author.author_title,
+            DocInfoField::Position => &mut self.author.position,
+            DocInfoField::Company => &mut self.author.company,
         };
+        *target = value.to_owned();
+    }
+
+    // Changes author fields according to the profile
+    pub(crate) fn apply_author(&mut self, profile: &AuthorProfile, update: AuthorUpdate) {
+        let author = &mut self.author;
+        for (target, value) in [
+            (&mut author.full_name, &profile.nickname),
+            (&mut author.creator_first_name, &profile.given_name),
+            (&mut author.initial, &profile.initials),
+            (&mut author.creator_last_name, &profile.family_name),
+            (&mut author.author_title, &profile.title),
+            (&mut author.position, &profile.position),
+            (&mut author.company, &profile.company),
+        ] {
+            if update == AuthorUpdate::Replace || target.is_empty() {
+                value.clone_into(target);
+            }
+        }
+    }
+
+    // Counts one more save, made `now` after editing for `elapsed`. Values that are not
+    // numbers are counted from zero.
+    pub(crate) fn record_editing(&mut self, elapsed: Duration, now: SystemTime) {
+        let about = &mut self.about;
+        let cycles: u64 = about.editing_cycles.trim().parse().unwrap_or(0);
+        about.editing_cycles = (cycles + 1).to_string();
+        let seconds: u64 = about.editing_time.trim().parse().unwrap_or(0);
+        about.editing_time = (seconds + elapsed.as_secs()).to_string();
+        about.date = format_date(now);
+    }
+
+    // documentinfo.xml as Krita writes it
+    pub(crate) fn to_xml(&self) -> String {
+        let about = &self.about;
+        let author = &self.author;
+        let element = |name: &str, value: &str| format!("  <{0}>{1}</{0}>\n", name, escape(value));
+        let mut out = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE {}>\n<document-info xmlns=\"{}\">\n <about>\n",
+            DOCUMENTINFO_DOCTYPE, DOCUMENTINFO_XMLNS
+        );
+        for (name, value) in [
+            ("title", &about.title),
+            ("description", &about.description),
+            ("subject", &about.subject),
+            ("abstract", &about.r#abstract),
+            ("keyword", &about.keyword),
+            ("initial-creator", &about.initial_creator),
+            ("editing-cycles", &about.editing_cycles),
+            ("editing-time", &about.editing_time),
+            ("date", &about.date),
+            ("creation-date", &about.creation_date),
+            ("language", &about.language),
+            ("license", &about.license),
+        ] {
+            out.push_str(&element(name, value));
+        }
+        out.push_str(" </about>\n <author>\n");
+        for (name, value) in [
+            ("full-name", &author.full_name),
+            ("creator-first-name", &author.creator_first_name),
+            ("creator-last-name", &author.creator_last_name),
+            ("initial", &author.initial),
+            ("author-title", &author.author_title),
+            ("position", &author.position),
+            ("company", &author.company),
+        ] {
+            out.push_str(&element(name, value));
+        }
+        out.push_str(" </author>\n</document-info>\n");
+        out
+    }
+
+    pub(crate) fn from_xml(
+        reader: &mut XmlReader<&[u8]>,
+        doctype_check: DoctypeCheck,
+        doctype_mismatches: &mut Vec<DoctypeMismatch>,
+    ) -> Result<Self, MetadataErrorReason> {
+        //TODO: as with maindoc, this skips initial declaration
+        let event = next_event_after_declaration(reader)?;
+        let doctype = event_unwrap_as_doctype(event)?.unescape()?;
+        check_doctype(
+            "documentinfo.xml",
+            DOCUMENTINFO_DOCTYPE,
+            &doctype,
+            doctype_check,
+            doctype_mismatches,
+        )?;
 
         //<document-info>
         let event = next_xml_event(reader)?;
@@ -396,3 +1557,65 @@ impl DocumentInfo {
         }
     }
 }
+
+/// Overview of a document, from [`KraFile::summary()`](crate::KraFile::summary).
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Summary {
+    pub(crate) name: String,
+    pub(crate) dimensions: (u32, u32),
+    pub(crate) colorspace: Colorspace,
+    pub(crate) layer_count: usize,
+    pub(crate) has_animation: bool,
+    pub(crate) krita_version: String,
+    pub(crate) author: String,
+}
+
+impl Summary {
+    /// Name of the image.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Width and height, in pixels.
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.dimensions
+    }
+
+    /// Colorspace of the image.
+    pub fn colorspace(&self) -> Colorspace {
+        self.colorspace
+    }
+
+    /// Number of layers, masks excluded. 0 if nodes are not parsed.
+    pub fn layer_count(&self) -> usize {
+        self.layer_count
+    }
+
+    /// Whether any node has keyframes.
+    pub fn has_animation(&self) -> bool {
+        self.has_animation
+    }
+
+    /// Version of Krita under which the file was saved.
+    pub fn krita_version(&self) -> &str {
+        &self.krita_version
+    }
+
+    /// Full name of the author, empty if it is not set.
+    pub fn author(&self) -> &str {
+        &self.author
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates() {
+        let at = |seconds| SystemTime::UNIX_EPOCH + Duration::from_secs(seconds);
+        assert_eq!(format_date(at(0)), "1970-01-01T00:00:00");
+        assert_eq!(format_date(at(951_827_696)), "2000-02-29T12:34:56");
+        assert_eq!(format_date(at(1_704_067_199)), "2023-12-31T23:59:59");
+    }
+}
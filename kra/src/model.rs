@@ -0,0 +1,454 @@
+//! Plain data model of documents, meant to stay stable between versions of the library.
+//!
+//! Types of the other modules follow the parsing code and change with it. Types here only
+//! have public fields of standard types, and change only along with [`VERSION`]. They are
+//! made from the other ones with [`From`], for example `Document::from(&kra_file)`.
+//!
+//! Values that are open-ended in Krita (kinds of nodes, composition operators, colorspaces)
+//! are kept as the text that is written in the file.
+
+use crate::{
+    layer::{self, NodeType},
+    metadata::{self, DocumentInfo, KraMetadata},
+    KraFile,
+};
+
+/// Version of the model. It is raised on every change of the types in this module.
+pub const VERSION: u32 = 1;
+
+/// The whole document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    /// Metadata of the image.
+    pub image: Image,
+    /// Information about the document and its author.
+    pub info: Info,
+    /// Top-level layers, from the topmost one.
+    pub layers: Vec<Node>,
+}
+
+/// Metadata of the image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    /// Version of Krita under which the file was saved.
+    pub krita_version: String,
+    /// Name of the image.
+    pub name: String,
+    /// Description of the image.
+    pub description: String,
+    /// Colorspace name, like `RGBA`.
+    pub colorspace: String,
+    /// Color profile of the image.
+    pub profile: String,
+    /// Width, in pixels.
+    pub width: u32,
+    /// Height, in pixels.
+    pub height: u32,
+    /// Dots per inch horizontally.
+    pub x_res: f64,
+    /// Dots per inch vertically.
+    pub y_res: f64,
+    /// Projection background color.
+    pub projection_background_color: String,
+    /// Global assistants color.
+    pub global_assistants_color: String,
+    /// Mirror axis configuration.
+    pub mirror_axis: MirrorAxis,
+}
+
+/// Mirror axis configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MirrorAxis {
+    /// Whether horizontal mirroring is enabled.
+    pub mirror_horizontal: bool,
+    /// Whether vertical mirroring is enabled.
+    pub mirror_vertical: bool,
+    /// Whether horizontal axis is locked.
+    pub lock_horizontal: bool,
+    /// Whether vertical axis is locked.
+    pub lock_vertical: bool,
+    /// Whether horizontal axis decoration is hidden.
+    pub hide_horizontal_decoration: bool,
+    /// Whether vertical axis decoration is hidden.
+    pub hide_vertical_decoration: bool,
+    /// Size of the axis handles.
+    pub handle_size: f32,
+    /// Position of the horizontal axis handle.
+    pub horizontal_handle_position: f32,
+    /// Position of the vertical axis handle.
+    pub vertical_handle_position: f32,
+    /// Position of the axes' center, as (x, y).
+    pub axis_position: (f32, f32),
+}
+
+/// Information about the document and its author.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Info {
+    /// Title of the document.
+    pub title: String,
+    /// Description of the document.
+    pub description: String,
+    /// Subject of the document.
+    pub subject: String,
+    /// Abstract of the document.
+    pub r#abstract: String,
+    /// Keywords.
+    pub keyword: String,
+    /// Name of the person who created the document.
+    pub initial_creator: String,
+    /// How many times the document was saved.
+    pub editing_cycles: String,
+    /// Total time spent editing the document.
+    pub editing_time: String,
+    /// Date of the last modification.
+    pub date: String,
+    /// Date of creation.
+    pub creation_date: String,
+    /// Language of the document.
+    pub language: String,
+    /// License of the document.
+    pub license: String,
+    /// Full name of the author.
+    pub author_full_name: String,
+    /// First name of the author.
+    pub author_first_name: String,
+    /// Last name of the author.
+    pub author_last_name: String,
+    /// Initials of the author.
+    pub author_initial: String,
+    /// Title of the author.
+    pub author_title: String,
+    /// Position of the author.
+    pub author_position: String,
+    /// Company of the author.
+    pub author_company: String,
+}
+
+/// Layer or mask.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Node {
+    /// Kind of the node, like `paintlayer`.
+    pub kind: String,
+    /// Unique identifier, in the lowercase hyphenated form.
+    pub uuid: String,
+    /// Name of the node.
+    pub name: String,
+    /// Name of the file that stores node's data.
+    pub filename: String,
+    /// Whether the node is visible.
+    pub visible: bool,
+    /// Whether the node is locked.
+    pub locked: bool,
+    /// Color label, by its index. 0 is no label.
+    pub color_label: u32,
+    /// Horizontal offset, in pixels.
+    pub x: i64,
+    /// Vertical offset, in pixels.
+    pub y: i64,
+    /// Whether the node is shown in the timeline.
+    pub in_timeline: bool,
+    /// Whether onionskinning is enabled.
+    pub onionskin: bool,
+    /// Properties specific to the kind of the node.
+    pub properties: Properties,
+    /// Masks of the node, from the topmost one.
+    pub masks: Vec<Node>,
+    /// All XML attributes of the node, in the order they were read.
+    pub attributes: Vec<(String, String)>,
+}
+
+/// Properties that every layer (but not mask) has.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LayerCommon {
+    /// Composition operator, like `normal`.
+    pub composite_op: String,
+    /// Opacity, from 0 to 255.
+    pub opacity: u8,
+    /// Whether the layer is collapsed in the layer docker.
+    pub collapsed: bool,
+    /// Which channels are enabled. Empty for group layers, which do not have them.
+    pub channel_flags: String,
+}
+
+/// Properties specific to kinds of nodes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Properties {
+    /// Paint layer.
+    Paint {
+        /// Properties that every layer has.
+        layer: LayerCommon,
+        /// Colorspace name.
+        colorspace: String,
+        /// Which channels are locked, if the document says.
+        channel_lock_flags: Option<String>,
+    },
+    /// Group layer.
+    Group {
+        /// Properties that every layer has.
+        layer: LayerCommon,
+        /// Whether the group is in pass-through mode.
+        passthrough: bool,
+        /// Layers inside the group, from the topmost one.
+        layers: Vec<Node>,
+    },
+    /// File layer.
+    File {
+        /// Properties that every layer has.
+        layer: LayerCommon,
+        /// Colorspace name.
+        colorspace: String,
+        /// Path to the linked file, as it is written.
+        source: String,
+        /// Whether the image is scaled.
+        scale: bool,
+        /// Filter used for scaling.
+        scaling_filter: String,
+        /// Scaling method.
+        scaling_method: u32,
+    },
+    /// Filter (adjustment) layer.
+    Filter {
+        /// Properties that every layer has.
+        layer: LayerCommon,
+        /// Name of the filter.
+        filter_name: String,
+        /// Version of the filter.
+        filter_version: u32,
+    },
+    /// Fill (generator) layer.
+    Fill {
+        /// Properties that every layer has.
+        layer: LayerCommon,
+        /// Name of the generator.
+        generator_name: String,
+        /// Version of the generator.
+        generator_version: u32,
+    },
+    /// Clone layer.
+    Clone {
+        /// Properties that every layer has.
+        layer: LayerCommon,
+        /// Type of the clone.
+        clone_type: u32,
+        /// Name of the source layer.
+        clone_from: String,
+        /// UUID of the source layer, in the lowercase hyphenated form.
+        clone_from_uuid: String,
+    },
+    /// Vector (shape) layer.
+    Vector {
+        /// Properties that every layer has.
+        layer: LayerCommon,
+    },
+    /// Transparency mask.
+    TransparencyMask,
+    /// Filter mask.
+    FilterMask {
+        /// Name of the filter.
+        filter_name: String,
+        /// Version of the filter.
+        filter_version: u32,
+    },
+    /// Transform mask.
+    TransformMask,
+    /// Selection mask.
+    SelectionMask {
+        /// Whether the selection is active.
+        active: bool,
+    },
+    /// Colorize mask.
+    ColorizeMask {
+        /// Composition operator.
+        composite_op: String,
+        /// Colorspace name.
+        colorspace: String,
+        /// Whether the coloring is limited to layer bounds.
+        limit_to_device: bool,
+        /// Whether the coloring is shown.
+        show_coloring: bool,
+        /// Cleanup strength.
+        cleanup: u8,
+        /// Whether edge detection is used.
+        use_edge_detection: bool,
+        /// Size of edge detection.
+        edge_detection_size: u32,
+        /// Radius of fuzzy selection.
+        fuzzy_radius: u32,
+        /// Whether keystrokes are being edited.
+        edit_keystrokes: bool,
+    },
+}
+
+impl From<&KraFile> for Document {
+    fn from(file: &KraFile) -> Self {
+        Document {
+            image: file.meta().into(),
+            info: file.doc_info().into(),
+            layers: file.layers().iter().map(Node::from).collect(),
+        }
+    }
+}
+
+impl From<&KraMetadata> for Image {
+    fn from(meta: &KraMetadata) -> Self {
+        Image {
+            krita_version: meta.krita_version().to_owned(),
+            name: meta.name().to_owned(),
+            description: meta.description().to_owned(),
+            colorspace: meta.colorspace_raw().to_owned(),
+            profile: meta.profile().to_owned(),
+            width: meta.width(),
+            height: meta.height(),
+            x_res: meta.x_res(),
+            y_res: meta.y_res(),
+            projection_background_color: meta.projection_background_color().to_owned(),
+            global_assistants_color: meta.global_assistants_color().to_owned(),
+            mirror_axis: meta.mirror_axis().into(),
+        }
+    }
+}
+
+impl From<&metadata::MirrorAxis> for MirrorAxis {
+    fn from(axis: &metadata::MirrorAxis) -> Self {
+        let [x, y] = axis.axis_position();
+        MirrorAxis {
+            mirror_horizontal: axis.mirror_horizontal(),
+            mirror_vertical: axis.mirror_vertical(),
+            lock_horizontal: axis.lock_horizontal(),
+            lock_vertical: axis.lock_vertical(),
+            hide_horizontal_decoration: axis.hide_horizontal_decoration(),
+            hide_vertical_decoration: axis.hide_vertical_decoration(),
+            handle_size: axis.handle_size(),
+            horizontal_handle_position: axis.horizontal_handle_position(),
+            vertical_handle_position: axis.vertical_handle_position(),
+            axis_position: (x, y),
+        }
+    }
+}
+
+impl From<&DocumentInfo> for Info {
+    fn from(info: &DocumentInfo) -> Self {
+        let (about, author) = (info.about(), info.author());
+        Info {
+            title: about.title().to_owned(),
+            description: about.description().to_owned(),
+            subject: about.subject().to_owned(),
+            r#abstract: about.r#abstract().to_owned(),
+            keyword: about.keyword().to_owned(),
+            initial_creator: about.initial_creator().to_owned(),
+            editing_cycles: about.editing_cycles().to_owned(),
+            editing_time: about.editing_time().to_owned(),
+            date: about.date().to_owned(),
+            creation_date: about.creation_date().to_owned(),
+            language: about.language().to_owned(),
+            license: about.license().to_owned(),
+            author_full_name: author.full_name().to_owned(),
+            author_first_name: author.creator_first_name().to_owned(),
+            author_last_name: author.creator_last_name().to_owned(),
+            author_initial: author.initial().to_owned(),
+            author_title: author.author_title().to_owned(),
+            author_position: author.position().to_owned(),
+            author_company: author.company().to_owned(),
+        }
+    }
+}
+
+impl From<&layer::Node> for Node {
+    fn from(node: &layer::Node) -> Self {
+        Node {
+            kind: node.kind().to_string(),
+            uuid: node.uuid().hyphenated().to_string(),
+            name: node.name().to_owned(),
+            filename: node.filename().to_owned(),
+            visible: node.visible(),
+            locked: node.locked(),
+            color_label: node.colorlabel(),
+            x: node.x() as i64,
+            y: node.y() as i64,
+            in_timeline: node.in_timeline(),
+            onionskin: node.onionskin(),
+            properties: properties(node),
+            masks: node.masks().iter().map(Node::from).collect(),
+            attributes: node.attributes().to_owned(),
+        }
+    }
+}
+
+// Value of the attribute as it is written, for properties that are parsed into
+// types that cannot be written back
+fn raw_attribute(node: &layer::Node, name: &str) -> String {
+    node.attributes()
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.clone())
+        .unwrap_or_default()
+}
+
+fn properties(node: &layer::Node) -> Properties {
+    let layer = |opacity: u8, collapsed: bool, channel_flags: &str| LayerCommon {
+        composite_op: raw_attribute(node, "compositeop"),
+        opacity,
+        collapsed,
+        channel_flags: channel_flags.to_owned(),
+    };
+    let colorspace = || raw_attribute(node, "colorspacename");
+    match node.node_type() {
+        NodeType::PaintLayer(props) => Properties::Paint {
+            layer: layer(props.opacity(), props.collapsed(), props.channel_flags()),
+            colorspace: colorspace(),
+            channel_lock_flags: props.channel_lock_flags().map(str::to_owned),
+        },
+        NodeType::GroupLayer(props) => Properties::Group {
+            layer: layer(props.opacity(), props.collapsed(), ""),
+            passthrough: props.passthrough(),
+            layers: props.layers().iter().map(Node::from).collect(),
+        },
+        NodeType::FileLayer(props) => Properties::File {
+            layer: layer(props.opacity(), props.collapsed(), props.channel_flags()),
+            colorspace: colorspace(),
+            source: raw_attribute(node, "source"),
+            scale: props.scale(),
+            scaling_filter: props.scaling_filter().to_owned(),
+            scaling_method: props.scaling_method(),
+        },
+        NodeType::FilterLayer(props) => Properties::Filter {
+            layer: layer(props.opacity(), props.collapsed(), props.channel_flags()),
+            filter_name: props.filter_name().to_owned(),
+            filter_version: props.filter_version(),
+        },
+        NodeType::FillLayer(props) => Properties::Fill {
+            layer: layer(props.opacity(), props.collapsed(), props.channel_flags()),
+            generator_name: props.generator_name().to_owned(),
+            generator_version: props.generator_version(),
+        },
+        NodeType::CloneLayer(props) => Properties::Clone {
+            layer: layer(props.opacity(), props.collapsed(), props.channel_flags()),
+            clone_type: props.clone_type(),
+            clone_from: props.clone_from().to_owned(),
+            clone_from_uuid: props.clone_from_uuid().hyphenated().to_string(),
+        },
+        NodeType::VectorLayer(props) => Properties::Vector {
+            layer: layer(props.opacity(), props.collapsed(), props.channel_flags()),
+        },
+        NodeType::TransparencyMask(_) => Properties::TransparencyMask,
+        NodeType::FilterMask(props) => Properties::FilterMask {
+            filter_name: props.filter_name().to_owned(),
+            filter_version: props.filter_version(),
+        },
+        NodeType::TransformMask(_) => Properties::TransformMask,
+        NodeType::SelectionMask(props) => Properties::SelectionMask {
+            active: props.active(),
+        },
+        NodeType::ColorizeMask(props) => Properties::ColorizeMask {
+            composite_op: raw_attribute(node, "compositeop"),
+            colorspace: colorspace(),
+            limit_to_device: props.limit_to_device(),
+            show_coloring: props.show_coloring(),
+            cleanup: props.cleanup(),
+            use_edge_detection: props.use_edge_detection(),
+            edge_detection_size: props.edge_detection_size(),
+            fuzzy_radius: props.fuzzy_radius(),
+            edit_keystrokes: props.edit_keystrokes(),
+        },
+    }
+}
@@ -0,0 +1,507 @@
+//! Turning node names into names of files, and templates of these names.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+use crate::{error::TemplateError, id::Uuid};
+
+/// Options of [`Node::sanitized_name()`](crate::layer::Node::sanitized_name).
+///
+/// By default, Unicode is kept, path-hostile characters are replaced with `_`,
+/// and names are at most 200 bytes long.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct SanitizeOptions {
+    pub(crate) ascii: bool,
+    pub(crate) replacement: char,
+    pub(crate) max_len: usize,
+    pub(crate) fallback: String,
+}
+
+impl Default for SanitizeOptions {
+    fn default() -> Self {
+        SanitizeOptions {
+            ascii: false,
+            replacement: '_',
+            max_len: 200,
+            fallback: "unnamed".to_owned(),
+        }
+    }
+}
+
+impl SanitizeOptions {
+    /// Default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set whether the name is transliterated to ASCII. Accented latin letters lose
+    /// their accents, everything else that is not ASCII (like emoji) is replaced.
+    pub fn ascii(mut self, ascii: bool) -> Self {
+        self.ascii = ascii;
+        self
+    }
+
+    /// Whether the name is transliterated to ASCII.
+    pub fn get_ascii(&self) -> bool {
+        self.ascii
+    }
+
+    /// Set the character that replaces the ones that cannot be used.
+    /// It must be safe itself, otherwise `_` is used.
+    pub fn replacement(mut self, replacement: char) -> Self {
+        self.replacement = replacement;
+        self
+    }
+
+    /// The character that replaces the ones that cannot be used.
+    pub fn get_replacement(&self) -> char {
+        self.replacement
+    }
+
+    /// Set the maximum length of the name, in bytes. Numbers added by
+    /// [`UniqueNames`] are not counted.
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /// The maximum length of the name, in bytes.
+    pub fn get_max_len(&self) -> usize {
+        self.max_len
+    }
+
+    /// Set the name used when nothing is left after sanitization.
+    pub fn fallback(mut self, fallback: impl Into<String>) -> Self {
+        self.fallback = fallback.into();
+        self
+    }
+
+    /// The name used when nothing is left after sanitization.
+    pub fn get_fallback(&self) -> &str {
+        &self.fallback
+    }
+}
+
+// Names that cannot be used for files on Windows, with any extension
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+fn is_unsafe(c: char) -> bool {
+    c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|')
+}
+
+// ASCII version of a latin letter, without its accents
+fn transliterate(c: char) -> Option<&'static str> {
+    Some(match c {
+        'À'..='Å' => "A",
+        'à'..='å' | 'ā' | 'ă' | 'ą' => "a",
+        'Æ' => "AE",
+        'æ' => "ae",
+        'Ç' => "C",
+        'ç' | 'ć' | 'č' => "c",
+        'È'..='Ë' => "E",
+        'è'..='ë' | 'ē' | 'ę' | 'ě' => "e",
+        'Ì'..='Ï' => "I",
+        'ì'..='ï' | 'ī' => "i",
+        'Ð' => "D",
+        'ð' | 'ď' => "d",
+        'Ñ' => "N",
+        'ñ' | 'ń' | 'ň' => "n",
+        'Ò'..='Ö' | 'Ø' => "O",
+        'ò'..='ö' | 'ø' | 'ō' | 'ő' => "o",
+        'Ù'..='Ü' => "U",
+        'ù'..='ü' | 'ū' | 'ů' | 'ű' => "u",
+        'Ý' => "Y",
+        'ý' | 'ÿ' => "y",
+        'Þ' => "TH",
+        'þ' => "th",
+        'ß' => "ss",
+        'ł' => "l",
+        'Ł' => "L",
+        'ř' => "r",
+        'ś' | 'š' => "s",
+        'Š' => "S",
+        'ť' => "t",
+        'ź' | 'ż' | 'ž' => "z",
+        'Ž' => "Z",
+        'Œ' => "OE",
+        'œ' => "oe",
+        _ => return None,
+    })
+}
+
+/// Makes a name safe to use as a file name. See [`SanitizeOptions`].
+///
+/// The result is never empty, does not start or end with dots or spaces,
+/// and is not a name reserved on Windows.
+pub fn sanitize_name(name: &str, options: &SanitizeOptions) -> String {
+    let replacement = match is_unsafe(options.replacement)
+        || (options.ascii && !options.replacement.is_ascii())
+    {
+        true => '_',
+        false => options.replacement,
+    };
+
+    let mut out = String::with_capacity(name.len());
+    let mut push = |text: &str| {
+        for c in text.chars() {
+            // Do not repeat replacements
+            if c == replacement && out.ends_with(replacement) {
+                continue;
+            }
+            out.push(c);
+        }
+    };
+    for c in name.chars() {
+        if is_unsafe(c) {
+            push(replacement.encode_utf8(&mut [0; 4]));
+        } else if options.ascii && !c.is_ascii() {
+            match transliterate(c) {
+                Some(text) => push(text),
+                None => push(replacement.encode_utf8(&mut [0; 4])),
+            }
+        } else {
+            push(c.encode_utf8(&mut [0; 4]));
+        }
+    }
+
+    let mut out = truncate(out.trim_matches(['.', ' ']), options.max_len);
+    let stem_len = out.find('.').unwrap_or(out.len());
+    if RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(&out[..stem_len]))
+    {
+        out.insert(stem_len, replacement);
+    }
+    match out.is_empty() {
+        true => truncate(&options.fallback, options.max_len),
+        false => out,
+    }
+}
+
+// Cuts the text to at most max_len bytes, at a character boundary
+fn truncate(text: &str, max_len: usize) -> String {
+    let mut end = text.len().min(max_len);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text[..end].trim_end_matches(['.', ' ']).to_owned()
+}
+
+/// Keeps track of names that were given out, and makes new ones unique by
+/// adding a number to them (`name`, `name_2`, `name_3`...).
+///
+/// Names are compared case-insensitively, as file systems on Windows and macOS do.
+#[derive(Debug, Default, Clone)]
+pub struct UniqueNames {
+    // Lowercase names
+    taken: HashSet<String>,
+    // Lowercase name -> next number to try for it
+    next: HashMap<String, usize>,
+}
+
+impl UniqueNames {
+    /// No names are taken.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the name, or the name with a number if it is already taken,
+    /// and marks the result as taken.
+    pub fn unique(&mut self, name: &str) -> String {
+        let key = name.to_lowercase();
+        if self.taken.insert(key.clone()) {
+            return name.to_owned();
+        }
+        let next = self.next.entry(key).or_insert(2);
+        loop {
+            let candidate = format!("{}_{}", name, next);
+            *next += 1;
+            if self.taken.insert(candidate.to_lowercase()) {
+                return candidate;
+            }
+        }
+    }
+
+    /// Whether the name was given out already.
+    pub fn is_taken(&self, name: &str) -> bool {
+        self.taken.contains(&name.to_lowercase())
+    }
+}
+
+/// A value that can be used in a [`NameTemplate`], written as `{token}`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Token {
+    /// `{name}`: sanitized name of the node.
+    Name,
+    /// `{group}`: sanitized name of the parent group (or the layer, for masks).
+    Group,
+    /// `{path}`: sanitized names of all parents, joined with `/`.
+    Path,
+    /// `{index}`: position of the node among its siblings, starting from 0.
+    Index,
+    /// `{uuid}`: UUID of the node.
+    Uuid,
+    /// `{composition}`: name of the composition that is exported.
+    Composition,
+    /// `{frame}`: number of the frame that is exported.
+    Frame,
+}
+
+impl Token {
+    fn is_numeric(&self) -> bool {
+        matches!(self, Token::Index | Token::Frame)
+    }
+}
+
+impl FromStr for Token {
+    type Err = TemplateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "name" => Token::Name,
+            "group" => Token::Group,
+            "path" => Token::Path,
+            "index" => Token::Index,
+            "uuid" => Token::Uuid,
+            "composition" => Token::Composition,
+            "frame" => Token::Frame,
+            _ => return Err(TemplateError::UnknownToken(s.to_owned())),
+        })
+    }
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let token = match self {
+            Token::Name => "name",
+            Token::Group => "group",
+            Token::Path => "path",
+            Token::Index => "index",
+            Token::Uuid => "uuid",
+            Token::Composition => "composition",
+            Token::Frame => "frame",
+        };
+        write!(f, "{}", token)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+enum TemplatePart {
+    Text(String),
+    // Token and the width it is padded to with zeroes
+    Token(Token, usize),
+}
+
+/// Template of file names for exported nodes, like `"{group}/{name}_{index}.png"`.
+///
+/// See [`Token`] for what can be used. Numeric tokens can be padded with zeroes,
+/// like `{frame:04}`. Literal braces are written as `{{` and `}}`.
+///
+/// Templates are parsed from strings, so they can come from configuration:
+/// ```
+/// # use kra::naming::NameTemplate;
+/// let template: NameTemplate = "{path}/{name}_{frame:03}.png".parse().unwrap();
+/// assert_eq!(template.to_string(), "{path}/{name}_{frame:03}.png");
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct NameTemplate {
+    parts: Vec<TemplatePart>,
+}
+
+impl FromStr for NameTemplate {
+    type Err = TemplateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '}' => return Err(TemplateError::UnmatchedBrace),
+                '{' => {
+                    let rest = chars.as_str();
+                    let end = rest.find('}').ok_or(TemplateError::UnmatchedBrace)?;
+                    let (token, width) = match rest[..end].split_once(':') {
+                        Some((token, width)) => (token, Some(width)),
+                        None => (&rest[..end], None),
+                    };
+                    let token: Token = token.parse()?;
+                    let width = match width {
+                        None => 0,
+                        Some(width) if token.is_numeric() && width.starts_with('0') => width
+                            .parse()
+                            .map_err(|_| TemplateError::InvalidFormat(rest[..end].to_owned()))?,
+                        Some(_) => {
+                            return Err(TemplateError::InvalidFormat(rest[..end].to_owned()))
+                        }
+                    };
+                    if !text.is_empty() {
+                        parts.push(TemplatePart::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(TemplatePart::Token(token, width));
+                    chars = rest[end + 1..].chars();
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(TemplatePart::Text(text));
+        }
+        Ok(NameTemplate { parts })
+    }
+}
+
+impl Display for NameTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for part in &self.parts {
+            match part {
+                TemplatePart::Text(text) => {
+                    write!(f, "{}", text.replace('{', "{{").replace('}', "}}"))?
+                }
+                TemplatePart::Token(token, 0) => write!(f, "{{{}}}", token)?,
+                TemplatePart::Token(token, width) => write!(f, "{{{}:0{}}}", token, width)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl NameTemplate {
+    /// Fills the template with the values.
+    ///
+    /// Empty path segments are dropped, so that `{group}/{name}` of a node
+    /// that is not in a group is just its name. Tokens without a value
+    /// (like `{frame}` when no frame was set) are left empty.
+    pub fn render(&self, values: &TemplateValues) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                TemplatePart::Text(text) => out.push_str(text),
+                TemplatePart::Token(token, width) => {
+                    let value = match token {
+                        Token::Name => values.name.clone(),
+                        Token::Group => values.group.clone(),
+                        Token::Path => values.path.join("/"),
+                        Token::Index => format!("{:0width$}", values.index, width = width),
+                        Token::Uuid => values.uuid.hyphenated().to_string(),
+                        Token::Composition => values.composition.clone().unwrap_or_default(),
+                        Token::Frame => values
+                            .frame
+                            .map(|frame| format!("{:0width$}", frame, width = width))
+                            .unwrap_or_default(),
+                    };
+                    out.push_str(&value);
+                }
+            }
+        }
+        out.split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Tokens used in the template.
+    pub fn tokens(&self) -> impl Iterator<Item = Token> + '_ {
+        self.parts.iter().filter_map(|part| match part {
+            TemplatePart::Token(token, _) => Some(*token),
+            TemplatePart::Text(_) => None,
+        })
+    }
+}
+
+/// Values of [`Token`]s for one node.
+///
+/// Get them with [`KraFile::template_values()`](crate::KraFile::template_values), and set
+/// the composition and the frame that are exported if the template uses them.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct TemplateValues {
+    /// Sanitized name of the node.
+    name: String,
+    /// Sanitized name of the parent.
+    group: String,
+    /// Sanitized names of all parents, from the top.
+    path: Vec<String>,
+    /// Position of the node among its siblings, starting from 0.
+    index: usize,
+    /// UUID of the node.
+    uuid: Uuid,
+    /// Name of the composition that is exported.
+    composition: Option<String>,
+    /// Number of the frame that is exported.
+    frame: Option<u32>,
+}
+
+impl TemplateValues {
+    /// Sanitized name of the node.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Sanitized name of the parent.
+    pub fn group(&self) -> &str {
+        &self.group
+    }
+
+    /// Sanitized names of all parents, from the top.
+    pub fn path(&self) -> &[String] {
+        &self.path
+    }
+
+    /// Position of the node among its siblings, starting from 0.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// UUID of the node.
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    /// Name of the composition that is exported.
+    pub fn composition(&self) -> Option<&str> {
+        self.composition.as_deref()
+    }
+
+    /// Number of the frame that is exported.
+    pub fn frame(&self) -> Option<u32> {
+        self.frame
+    }
+
+    pub(crate) fn new(name: String, path: Vec<String>, index: usize, uuid: Uuid) -> Self {
+        TemplateValues {
+            name,
+            group: path.last().cloned().unwrap_or_default(),
+            path,
+            index,
+            uuid,
+            composition: None,
+            frame: None,
+        }
+    }
+
+    /// Set the name of the composition that is exported.
+    pub fn with_composition(mut self, composition: impl Into<String>) -> Self {
+        self.composition = Some(composition.into());
+        self
+    }
+
+    /// Set the number of the frame that is exported.
+    pub fn with_frame(mut self, frame: u32) -> Self {
+        self.frame = Some(frame);
+        self
+    }
+}
@@ -0,0 +1,176 @@
+// Finding paint layers that cannot be seen, because opaque layers above them cover them.
+//
+// Only what is certain is reported: layers cover the ones below only with normal blending,
+// full opacity and all channels, and when nothing between them and the image (masks,
+// groups with other blend modes or opacity) can change that.
+
+use crate::{
+    error::DataError,
+    geom::{IPoint, IRect},
+    id::Uuid,
+    layer::{CompositeOp, Node, NodeType},
+    Colorspace, KraFile,
+};
+
+// Visible layers, from the top down
+enum Item {
+    Paint {
+        uuid: Uuid,
+        filename: String,
+        offset: IPoint,
+        colorspace: Colorspace,
+        covers: bool,
+    },
+    Group {
+        covers: bool,
+        children: Vec<Item>,
+    },
+}
+
+// What a layer has at a pixel
+#[derive(PartialEq, Clone, Copy)]
+enum Cover {
+    Empty,
+    Partial,
+    Opaque,
+}
+
+fn plan(nodes: &[Node]) -> Vec<Item> {
+    let mut items = Vec::new();
+    for node in nodes.iter().filter(|node| node.visible()) {
+        let unmasked = node
+            .masks()
+            .iter()
+            .all(|mask| !mask.visible() || matches!(mask.node_type(), NodeType::SelectionMask(_)));
+        match node.node_type() {
+            NodeType::PaintLayer(props) => items.push(Item::Paint {
+                uuid: node.uuid(),
+                filename: node.filename().to_owned(),
+                offset: IPoint::new(node.x(), node.y()),
+                colorspace: props.colorspace(),
+                covers: unmasked
+                    && props.composite_op() == CompositeOp::Normal
+                    && props.opacity() == u8::MAX
+                    && props.channel_flags().chars().all(|flag| flag == '1'),
+            }),
+            NodeType::GroupLayer(props) => items.push(Item::Group {
+                covers: unmasked
+                    && (props.passthrough() || props.composite_op() == CompositeOp::Normal)
+                    && props.opacity() == u8::MAX,
+                children: plan(props.layers()),
+            }),
+            _ => {}
+        }
+    }
+    items
+}
+
+impl KraFile {
+    /// Find visible paint layers that cannot be seen in the image, because everything they
+    /// have on the canvas is covered by opaque pixels of layers above them.
+    ///
+    /// Only layers with normal blending and full opacity cover the ones below, and only if
+    /// they and the groups they are in have no visible masks (other than selection masks).
+    /// Pixels outside of the canvas are not taken into account, and layers that have nothing
+    /// on the canvas are not reported.
+    pub fn find_occluded_layers(&mut self) -> Result<Vec<Uuid>, DataError> {
+        let canvas = IRect::new(0, 0, self.meta.width(), self.meta.height());
+        let items = plan(&self.layers);
+        let mut covered = vec![false; canvas.area() as usize];
+        let mut occluded = Vec::new();
+        self.find_occluded(&items, canvas, &mut covered, &mut occluded)?;
+        Ok(occluded)
+    }
+
+    // Goes over the items, marking pixels of the canvas that are covered from now on
+    fn find_occluded(
+        &mut self,
+        items: &[Item],
+        canvas: IRect,
+        covered: &mut [bool],
+        occluded: &mut Vec<Uuid>,
+    ) -> Result<(), DataError> {
+        let index = |x: i32, y: i32| y as usize * canvas.width() as usize + x as usize;
+        for item in items {
+            match item {
+                Item::Paint {
+                    uuid,
+                    filename,
+                    offset,
+                    colorspace,
+                    covers,
+                } => {
+                    let Some(bounds) = self
+                        .layer_bounds(filename, offset.x(), offset.y(), *colorspace)?
+                        .and_then(|bounds| bounds.intersection(&canvas))
+                    else {
+                        continue;
+                    };
+                    if pixels(bounds).all(|pixel| covered[index(pixel.x(), pixel.y())]) {
+                        occluded.push(*uuid);
+                        continue;
+                    }
+                    // Transparent pixels within the bounds do not need to be covered
+                    let cover = self.coverage(filename, *offset, *colorspace, bounds)?;
+                    let mut visible = false;
+                    for (pixel, cover) in pixels(bounds).zip(cover) {
+                        let covered = &mut covered[index(pixel.x(), pixel.y())];
+                        visible |= cover != Cover::Empty && !*covered;
+                        *covered |= *covers && cover == Cover::Opaque;
+                    }
+                    if !visible {
+                        occluded.push(*uuid);
+                    }
+                }
+                Item::Group {
+                    covers: true,
+                    children,
+                } => self.find_occluded(children, canvas, covered, occluded)?,
+                // Layers inside still cover each other, but not what is below the group
+                Item::Group { children, .. } => {
+                    let mut inner = covered.to_vec();
+                    self.find_occluded(children, canvas, &mut inner, occluded)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // What the layer has at each pixel of the area, row by row
+    fn coverage(
+        &mut self,
+        filename: &str,
+        offset: IPoint,
+        colorspace: Colorspace,
+        area: IRect,
+    ) -> Result<Vec<Cover>, DataError> {
+        let of = |pixel: &[u8]| match pixel {
+            pixel if colorspace.is_opaque(pixel) => Cover::Opaque,
+            pixel if colorspace.is_transparent(pixel) => Cover::Empty,
+            _ => Cover::Partial,
+        };
+        let data = self.read_tiled_data(filename)?;
+        let default = data.default_pixel.as_deref().map_or(Cover::Empty, of);
+        let mut cover = vec![default; area.area() as usize];
+        let pixel_size = data.header.pixel_size as usize;
+        let tile_width = data.header.tile_width as usize;
+        for tile in &data.tiles {
+            for (index, pixel) in tile.data.chunks_exact(pixel_size).enumerate() {
+                let x = offset.x() + tile.x + (index % tile_width) as i32;
+                let y = offset.y() + tile.y + (index / tile_width) as i32;
+                if area.contains(IPoint::new(x, y)) {
+                    let at =
+                        (y - area.y()) as usize * area.width() as usize + (x - area.x()) as usize;
+                    cover[at] = of(pixel);
+                }
+            }
+        }
+        Ok(cover)
+    }
+}
+
+// Pixels of the area, row by row
+fn pixels(area: IRect) -> impl Iterator<Item = IPoint> {
+    (area.y()..area.bottom())
+        .flat_map(move |y| (area.x()..area.right()).map(move |x| IPoint::new(x, y)))
+}
@@ -0,0 +1,263 @@
+//! Parsing of the XML parts of a document, without the file system or the archive.
+//!
+//! [`KraFile::read()`](crate::KraFile::read) reads entries of the archive and hands them over
+//! to [`parse_document()`]. It can be used directly where the archive is read by other means,
+//! for example inside plugins of other applications that only get the bytes of the entries.
+
+use std::{borrow::Cow, collections::BTreeMap, io};
+
+use quick_xml::Reader as XmlReader;
+
+use crate::{
+    config::{InvalidUtf8, ParsingConfiguration},
+    data::NodeData,
+    edit::MaindocSource,
+    error::{
+        DoctypeMismatch, DuplicateUuid, LimitError, ReadKraError, ReplacedUtf8, UnknownAttribute,
+    },
+    get_layers,
+    helper::strip_xml_prefix,
+    id::Uuid,
+    layer::Node,
+    metadata::{DocumentInfo, KraMetadata, KraMetadataEnd, KraMetadataStart},
+    report::{phase_start, ReadReport},
+    skip_layers, ParseContext,
+};
+
+/// Document model, as described by `maindoc.xml` and `documentinfo.xml`.
+#[derive(Debug)]
+pub struct Document {
+    /// Image metadata.
+    pub(crate) meta: KraMetadata,
+    /// Document information.
+    pub(crate) doc_info: DocumentInfo,
+    /// Top-level layers.
+    pub(crate) layers: Vec<Node>,
+    /// Data of every node, by its UUID.
+    pub(crate) files: BTreeMap<Uuid, NodeData>,
+    /// Attributes of nodes that are not read into the model, see
+    /// [`UnknownAttributes`](crate::config::UnknownAttributes).
+    pub(crate) unknown_attributes: Vec<UnknownAttribute>,
+    /// `DOCTYPE`s that are not the ones Krita writes, see
+    /// [`DoctypeCheck`](crate::config::DoctypeCheck).
+    pub(crate) doctype_mismatches: Vec<DoctypeMismatch>,
+    /// XML entries whose invalid UTF-8 was replaced, see
+    /// [`InvalidUtf8`].
+    pub(crate) replaced_utf8: Vec<ReplacedUtf8>,
+    /// Nodes whose UUID is used by a node before them, see
+    /// [`DuplicateUuids`](crate::config::DuplicateUuids).
+    pub(crate) duplicate_uuids: Vec<DuplicateUuid>,
+    pub(crate) source: MaindocSource,
+}
+
+impl Document {
+    /// Image metadata.
+    pub fn meta(&self) -> &KraMetadata {
+        &self.meta
+    }
+
+    /// Document information.
+    pub fn doc_info(&self) -> &DocumentInfo {
+        &self.doc_info
+    }
+
+    /// Top-level layers.
+    pub fn layers(&self) -> &[Node] {
+        &self.layers
+    }
+
+    /// Data of every node, by its UUID. Iteration is ordered by UUID.
+    pub fn files(&self) -> &BTreeMap<Uuid, NodeData> {
+        &self.files
+    }
+
+    /// Attributes of nodes that are not read into the model, see
+    /// [`UnknownAttributes`](crate::config::UnknownAttributes).
+    pub fn unknown_attributes(&self) -> &[UnknownAttribute] {
+        &self.unknown_attributes
+    }
+
+    /// `DOCTYPE`s that are not the ones Krita writes, see
+    /// [`DoctypeCheck`](crate::config::DoctypeCheck).
+    pub fn doctype_mismatches(&self) -> &[DoctypeMismatch] {
+        &self.doctype_mismatches
+    }
+
+    /// XML entries whose invalid UTF-8 was replaced, see
+    /// [`InvalidUtf8`].
+    pub fn replaced_utf8(&self) -> &[ReplacedUtf8] {
+        &self.replaced_utf8
+    }
+
+    /// Nodes whose UUID is used by a node before them, see
+    /// [`DuplicateUuids`](crate::config::DuplicateUuids).
+    pub fn duplicate_uuids(&self) -> &[DuplicateUuid] {
+        &self.duplicate_uuids
+    }
+}
+
+/// Parse contents of `maindoc.xml` and `documentinfo.xml`.
+///
+/// Limits of the configuration apply to sizes of both, to dimensions of the image and to
+/// the number of nodes.
+pub fn parse_document(
+    maindoc: &[u8],
+    documentinfo: &[u8],
+    config: &ParsingConfiguration,
+) -> Result<Document, ReadKraError> {
+    parse_document_with_report(maindoc, documentinfo, config, None)
+}
+
+// Same as parse_document(), recording time of XML parsing and of building the tree
+// if a report is given
+pub(crate) fn parse_document_with_report(
+    maindoc: &[u8],
+    documentinfo: &[u8],
+    config: &ParsingConfiguration,
+    mut report: Option<&mut ReadReport>,
+) -> Result<Document, ReadKraError> {
+    let start = phase_start(&report);
+    let limits = &config.limits;
+    let mut replaced_utf8 = Vec::new();
+    let doc_info_text = xml_text("documentinfo.xml", documentinfo, config, &mut replaced_utf8)?;
+    let doc_info_text = strip_xml_prefix(&doc_info_text);
+    let mut doc_info = XmlReader::from_str(doc_info_text);
+
+    doc_info.trim_text(true);
+    let mut doctype_mismatches = Vec::new();
+    let doc_info =
+        DocumentInfo::from_xml(&mut doc_info, config.doctype_check, &mut doctype_mismatches)
+            .map_err(|err| {
+                err.to_read_error("documentinfo.xml".into(), doc_info_text, &doc_info)
+            })?;
+
+    let maindoc_text = xml_text("maindoc.xml", maindoc, config, &mut replaced_utf8)?;
+    let maindoc_text = strip_xml_prefix(&maindoc_text);
+    let mut maindoc = XmlReader::from_str(maindoc_text);
+
+    maindoc.trim_text(true);
+    let meta_start = KraMetadataStart::from_xml(
+        &mut maindoc,
+        config.colorspace_fallback,
+        config.doctype_check,
+        &mut doctype_mismatches,
+    )
+    .map_err(|err| err.to_read_error("maindoc.xml".into(), maindoc_text, &maindoc))?;
+
+    let (width, height) = meta_start.dimensions();
+    if width == 0 || height == 0 {
+        return Err(ReadKraError::InvalidSize { width, height });
+    }
+    let (max_width, max_height) = limits.max_dimensions;
+    if width > max_width || height > max_height {
+        return Err(LimitError::Dimensions {
+            width,
+            height,
+            max_width,
+            max_height,
+        }
+        .into());
+    }
+    if width as u64 * height as u64 > limits.max_pixels {
+        return Err(LimitError::Pixels(limits.max_pixels).into());
+    }
+
+    let mut ctx = ParseContext {
+        config,
+        files: BTreeMap::new(),
+        unknown_attributes: Vec::new(),
+        duplicate_uuids: Vec::new(),
+        group_depth: 0,
+    };
+
+    if let Some(report) = report.as_deref_mut() {
+        report.xml_parse.record(start, 0);
+    }
+    let start = phase_start(&report);
+    let layers_start = maindoc.buffer_position();
+    let layers = if config.parse_layers {
+        get_layers(&mut maindoc, &mut ctx)
+    } else {
+        skip_layers(&mut maindoc).map(|_| Vec::new())
+    }
+    .map_err(|err| err.to_read_error("maindoc.xml".into(), maindoc_text, &maindoc))?;
+    let ParseContext {
+        files,
+        unknown_attributes,
+        duplicate_uuids,
+        ..
+    } = ctx;
+    let layers_end = maindoc.buffer_position();
+    if let Some(replaced) = replaced_utf8
+        .iter_mut()
+        .find(|replaced| replaced.entry == "maindoc.xml")
+    {
+        replaced_names(&layers, &mut replaced.nodes);
+    }
+    let source = MaindocSource::new(maindoc_text, layers_start..layers_end);
+    if let Some(report) = report.as_deref_mut() {
+        report
+            .tree_build
+            .record(start, (layers_end - layers_start) as u64);
+    }
+
+    let start = phase_start(&report);
+
+    let meta_end = KraMetadataEnd::from_xml(&mut maindoc)
+        .map_err(|err| err.to_read_error("maindoc.xml".into(), maindoc_text, &maindoc))?;
+    let xml_bytes = documentinfo.len() + maindoc_text.len() - (layers_end - layers_start);
+    if let Some(report) = report {
+        report.xml_parse.record(start, xml_bytes as u64);
+    }
+
+    Ok(Document {
+        meta: KraMetadata::new(meta_start, meta_end),
+        doc_info,
+        layers,
+        files,
+        unknown_attributes,
+        doctype_mismatches,
+        replaced_utf8,
+        duplicate_uuids,
+        source,
+    })
+}
+
+// Checks the size of the entry and decodes it, replacing invalid UTF-8 if allowed
+fn xml_text<'a>(
+    entry: &'static str,
+    data: &'a [u8],
+    config: &ParsingConfiguration,
+    replaced_utf8: &mut Vec<ReplacedUtf8>,
+) -> Result<Cow<'a, str>, ReadKraError> {
+    let limit = config.limits.max_xml_size;
+    if data.len() as u64 > limit {
+        return Err(LimitError::EntrySize {
+            entry: entry.to_owned(),
+            limit,
+        }
+        .into());
+    }
+    match std::str::from_utf8(data) {
+        Ok(text) => Ok(Cow::Borrowed(text)),
+        Err(_) if config.invalid_utf8 == InvalidUtf8::Replace => {
+            replaced_utf8.push(ReplacedUtf8 {
+                entry,
+                nodes: Vec::new(),
+            });
+            Ok(String::from_utf8_lossy(data))
+        }
+        Err(err) => Err(io::Error::new(io::ErrorKind::InvalidData, err).into()),
+    }
+}
+
+// Nodes with replaced sequences in their names
+fn replaced_names(nodes: &[Node], found: &mut Vec<Uuid>) {
+    for node in nodes {
+        if node.name().contains(char::REPLACEMENT_CHARACTER) {
+            found.push(node.uuid());
+        }
+        replaced_names(node.masks(), found);
+        replaced_names(node.children(), found);
+    }
+}
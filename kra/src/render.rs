@@ -0,0 +1,354 @@
+// Compositing of the whole image from its layers, for documents without `mergedimage.png`.
+//
+// Only what Krita does with plain pixels is done here: paint layers, file layers (if their
+// images are loaded), clone layers (if asked to) and groups with separable blend modes,
+// clipped by selection masks (if asked to). Anything else that is visible makes
+// compositing fail, rather than give an image that differs from Krita's one.
+
+use std::mem::size_of;
+
+use crate::{
+    blend::Blend,
+    edit::find,
+    error::DataError,
+    id::Uuid,
+    layer::{Node, NodeType},
+    tile::buffer_len,
+    KraFile,
+};
+
+// What is drawn, from the bottom up
+enum Item {
+    Paint {
+        uuid: Uuid,
+        // Pixels come from the image that a file layer links to
+        linked: bool,
+        inherit_alpha: bool,
+        blend: Blend,
+        opacity: f32,
+        // Added to the position of the pixels, for clone layers
+        offset: (i64, i64),
+        // Selection masks that limit where the item is drawn
+        clips: Vec<Uuid>,
+    },
+    Group {
+        blend: Blend,
+        opacity: f32,
+        passthrough: bool,
+        children: Vec<Item>,
+        offset: (i64, i64),
+        clips: Vec<Uuid>,
+    },
+}
+
+impl Item {
+    fn clips_mut(&mut self) -> &mut Vec<Uuid> {
+        match self {
+            Item::Paint { clips, .. } | Item::Group { clips, .. } => clips,
+        }
+    }
+}
+
+// Pixels of a selection mask, placed in the image: how much of each pixel is selected
+struct Selection {
+    x: i64,
+    y: i64,
+    width: i64,
+    height: i64,
+    values: Vec<u8>,
+    // Value outside of the stored tiles
+    default: u8,
+}
+
+impl Selection {
+    fn at(&self, x: i64, y: i64) -> f32 {
+        let (x, y) = (x - self.x, y - self.y);
+        let value = match (0..self.width).contains(&x) && (0..self.height).contains(&y) {
+            true => self.values[(y * self.width + x) as usize],
+            false => self.default,
+        };
+        value as f32 / u8::MAX as f32
+    }
+}
+
+// Turns nodes into items
+struct Planner<'a> {
+    // Top-level nodes, where sources of clone layers are found
+    layers: &'a [Node],
+    load_file_layers: bool,
+    flatten_clone_layers: bool,
+    apply_selection_masks: bool,
+    // Clone layers whose sources are being planned, so that cycles are found
+    clones: Vec<Uuid>,
+}
+
+impl Planner<'_> {
+    // Visible nodes that can be drawn, in drawing order
+    fn plan(&mut self, nodes: &[Node]) -> Result<Vec<Item>, DataError> {
+        let mut items = Vec::new();
+        for node in nodes.iter().rev().filter(|node| node.visible()) {
+            let cannot = || DataError::CannotComposite(node.uuid());
+            let selection_only = node.masks().iter().all(|mask| {
+                !mask.visible() || matches!(mask.node_type(), NodeType::SelectionMask(_))
+            });
+            if !selection_only {
+                return Err(cannot());
+            }
+            let layer = node.as_layer().ok_or_else(cannot)?;
+            let blend = Blend::new(layer.composite_op()).ok_or_else(cannot)?;
+            let opacity = layer.opacity() as f32 / u8::MAX as f32;
+            let mut item = self.item(node, node, blend, opacity, (0, 0))?;
+            if self.apply_selection_masks {
+                item.clips_mut().extend(
+                    node.masks()
+                        .iter()
+                        .filter(|mask| match mask.node_type() {
+                            NodeType::SelectionMask(props) => mask.visible() && props.active(),
+                            _ => false,
+                        })
+                        .map(Node::uuid),
+                );
+            }
+            items.push(item);
+        }
+        Ok(items)
+    }
+
+    // Item that draws pixels of `source` as `node` (which is the source, or a clone of it)
+    fn item(
+        &mut self,
+        node: &Node,
+        source: &Node,
+        blend: Blend,
+        opacity: f32,
+        offset: (i64, i64),
+    ) -> Result<Item, DataError> {
+        let cannot = || DataError::CannotComposite(node.uuid());
+        Ok(match source.node_type() {
+            NodeType::PaintLayer(_) => Item::Paint {
+                uuid: source.uuid(),
+                linked: false,
+                inherit_alpha: node.inherits_alpha(),
+                blend,
+                opacity,
+                offset,
+                clips: Vec::new(),
+            },
+            NodeType::FileLayer(_) if self.load_file_layers => Item::Paint {
+                uuid: source.uuid(),
+                linked: true,
+                inherit_alpha: node.inherits_alpha(),
+                blend,
+                opacity,
+                offset,
+                clips: Vec::new(),
+            },
+            NodeType::GroupLayer(props) => Item::Group {
+                blend,
+                opacity,
+                // A clone has the contents of the group, even if it passes them through
+                passthrough: props.passthrough() && node.uuid() == source.uuid(),
+                children: self.plan(props.layers())?,
+                offset,
+                clips: Vec::new(),
+            },
+            NodeType::CloneLayer(props) if self.flatten_clone_layers => {
+                if self.clones.contains(&source.uuid()) {
+                    return Err(cannot());
+                }
+                let cloned = find(self.layers, &props.clone_from_uuid())
+                    .filter(|cloned| cloned.as_layer().is_some())
+                    .ok_or_else(cannot)?;
+                let offset = (offset.0 + source.x() as i64, offset.1 + source.y() as i64);
+                self.clones.push(source.uuid());
+                let item = self.item(node, cloned, blend, opacity, offset);
+                self.clones.pop();
+                item?
+            }
+            _ => return Err(cannot()),
+        })
+    }
+}
+
+impl KraFile {
+    /// Composite the image from its layers, as 8-bit RGBA pixels.
+    ///
+    /// Only paint layers and groups are supported, with separable blend modes like
+    /// multiply or screen, and file layers if
+    /// [`ParsingConfiguration::load_file_layers()`](crate::config::ParsingConfiguration::load_file_layers)
+    /// is set, and clone layers if
+    /// [`ParsingConfiguration::flatten_clone_layers()`](crate::config::ParsingConfiguration::flatten_clone_layers)
+    /// is set; other visible nodes give [`DataError::CannotComposite`]. Visible selection
+    /// masks are ignored, unless
+    /// [`ParsingConfiguration::apply_selection_masks()`](crate::config::ParsingConfiguration::apply_selection_masks)
+    /// is set, in which case the active ones clip their layers.
+    /// Pass-through groups apply their opacity to each of their layers, and layers that
+    /// [inherit alpha](crate::layer::Node::inherits_alpha) are drawn only over the layers
+    /// below them in their group. Values are blended as they are stored, without color
+    /// management, and the background is transparent.
+    pub fn composite(&mut self) -> Result<image::RgbaImage, DataError> {
+        let (width, height) = (self.meta.width(), self.meta.height());
+        let items = Planner {
+            layers: &self.layers,
+            load_file_layers: self.config.load_file_layers,
+            flatten_clone_layers: self.config.flatten_clone_layers,
+            apply_selection_masks: self.config.apply_selection_masks,
+            clones: Vec::new(),
+        }
+        .plan(&self.layers)?;
+        let len = buffer_len(width as u64, height as u64, 4 * size_of::<f32>())?;
+        let mut canvas = vec![0.0; len / size_of::<f32>()];
+        self.draw(&items, &mut canvas, 1.0)?;
+        let bytes = canvas
+            .into_iter()
+            .map(|value| (value.clamp(0.0, 1.0) * u8::MAX as f32).round() as u8)
+            .collect();
+        Ok(image::RgbaImage::from_raw(width, height, bytes)
+            .expect("canvas has the size of the image"))
+    }
+
+    // Draws the items over RGBA values of the size of the image
+    fn draw(&mut self, items: &[Item], canvas: &mut [f32], opacity: f32) -> Result<(), DataError> {
+        let width = self.meta.width() as i64;
+        let height = self.meta.height() as i64;
+        for item in items {
+            match item {
+                Item::Paint {
+                    uuid,
+                    linked,
+                    inherit_alpha,
+                    blend,
+                    opacity: own,
+                    offset: (dx, dy),
+                    clips,
+                } => {
+                    let clip = self.clip(clips)?;
+                    let data = match linked {
+                        true => self.decode_file_layer(uuid)?,
+                        false => self.decode_layer(uuid)?,
+                    };
+                    let pixels = data.to_rgba32f()?;
+                    let layer_width = data.width() as i64;
+                    let (x, y) = (data.x() as i64 + dx, data.y() as i64 + dy);
+                    // Only the part of the layer that is inside the image
+                    let left = x.clamp(0, width);
+                    let right = (x + layer_width).clamp(0, width);
+                    if left >= right {
+                        continue;
+                    }
+                    for row in 0..data.height() as i64 {
+                        let y = y + row;
+                        if !(0..height).contains(&y) {
+                            continue;
+                        }
+                        let src_start = (row * layer_width + left - x) as usize * 4;
+                        let dst_start = (y * width + left) as usize * 4;
+                        let len = (right - left) as usize * 4;
+                        for (index, (dst, src)) in canvas[dst_start..dst_start + len]
+                            .chunks_exact_mut(4)
+                            .zip(pixels[src_start..src_start + len].chunks_exact(4))
+                            .enumerate()
+                        {
+                            let opacity = own * opacity * clip_at(&clip, left + index as i64, y);
+                            match inherit_alpha {
+                                true => blend.apply_inheriting_alpha(dst, src, opacity),
+                                false => blend.apply(dst, src, opacity),
+                            }
+                        }
+                    }
+                }
+                Item::Group {
+                    opacity: own,
+                    passthrough: true,
+                    children,
+                    ..
+                } => self.draw(children, canvas, own * opacity)?,
+                Item::Group {
+                    blend,
+                    opacity: own,
+                    children,
+                    offset,
+                    clips,
+                    ..
+                } => {
+                    let clip = self.clip(clips)?;
+                    let mut group = vec![0.0; canvas.len()];
+                    self.draw(children, &mut group, 1.0)?;
+                    let group = shift(group, *offset, width, height);
+                    for (index, (dst, src)) in canvas
+                        .chunks_exact_mut(4)
+                        .zip(group.chunks_exact(4))
+                        .enumerate()
+                    {
+                        let (x, y) = (index as i64 % width, index as i64 / width);
+                        blend.apply(dst, src, own * opacity * clip_at(&clip, x, y));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Selection masks with the UUIDs
+    fn clip(&mut self, uuids: &[Uuid]) -> Result<Vec<Selection>, DataError> {
+        uuids.iter().map(|uuid| self.read_selection(uuid)).collect()
+    }
+
+    // Pixels of a selection mask, which Krita stores as one byte per pixel
+    // in `<filename>.pixelselection`
+    fn read_selection(&mut self, uuid: &Uuid) -> Result<Selection, DataError> {
+        let node = find(&self.layers, uuid).ok_or(DataError::UnknownNode(*uuid))?;
+        let (filename, x, y) = (node.filename().to_owned(), node.x(), node.y());
+        let path = format!("{}.pixelselection", self.layer_entry_path(&filename)?);
+        let data = self.entries().read_tiled(&path)?;
+        if data.header.pixel_size != 1 {
+            return Err(DataError::MalformedTiles(
+                "pixel size of a selection",
+                data.header.pixel_size.to_string(),
+            ));
+        }
+        let default = data
+            .default_pixel
+            .as_deref()
+            .and_then(|pixel| pixel.first().copied())
+            .unwrap_or(0);
+        let (left, top, width, height, values) = data.to_dense(self.config.limits.max_pixels)?;
+        Ok(Selection {
+            x: left as i64 + x as i64,
+            y: top as i64 + y as i64,
+            width: width as i64,
+            height: height as i64,
+            values,
+            default,
+        })
+    }
+}
+
+// How much of the pixel is selected by all of the selections
+fn clip_at(clip: &[Selection], x: i64, y: i64) -> f32 {
+    clip.iter().map(|selection| selection.at(x, y)).product()
+}
+
+// Moves RGBA values of the size of the image by the offset, leaving transparency behind
+fn shift(pixels: Vec<f32>, (dx, dy): (i64, i64), width: i64, height: i64) -> Vec<f32> {
+    if (dx, dy) == (0, 0) {
+        return pixels;
+    }
+    let mut shifted = vec![0.0; pixels.len()];
+    let left = dx.clamp(0, width);
+    let right = (width + dx).clamp(0, width);
+    if left >= right {
+        return shifted;
+    }
+    let len = (right - left) as usize * 4;
+    for y in 0..height {
+        let src_y = y - dy;
+        if !(0..height).contains(&src_y) {
+            continue;
+        }
+        let src_start = (src_y * width + left - dx) as usize * 4;
+        let dst_start = (y * width + left) as usize * 4;
+        shifted[dst_start..dst_start + len].copy_from_slice(&pixels[src_start..src_start + len]);
+    }
+    shifted
+}
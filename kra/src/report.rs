@@ -0,0 +1,113 @@
+//! Where the time goes when documents are read, see
+//! [`KraFile::read_with_report()`](crate::KraFile::read_with_report).
+
+use std::{
+    fmt::{self, Display},
+    time::{Duration, Instant},
+};
+
+/// Time spent in each phase of reading a document, and how much data each phase went through.
+///
+/// Pixel data of nodes is read only when it is decoded, so it is not counted.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReadReport {
+    /// Opening the archive and reading `mimetype`, `documentinfo.xml` and `maindoc.xml`.
+    /// Bytes are the uncompressed sizes of the entries.
+    pub(crate) zip_io: PhaseReport,
+    /// Parsing of `documentinfo.xml` and of image metadata in `maindoc.xml`.
+    /// Bytes are the XML outside of the node tree.
+    pub(crate) xml_parse: PhaseReport,
+    /// Parsing of the node tree. Bytes are the XML of the tree.
+    pub(crate) tree_build: PhaseReport,
+    /// Finding archive entries that belong to nodes. No bytes are read.
+    pub(crate) data_load: PhaseReport,
+}
+
+impl ReadReport {
+    /// Opening the archive and reading `mimetype`, `documentinfo.xml` and `maindoc.xml`.
+    /// Bytes are the uncompressed sizes of the entries.
+    pub fn zip_io(&self) -> PhaseReport {
+        self.zip_io
+    }
+
+    /// Parsing of `documentinfo.xml` and of image metadata in `maindoc.xml`.
+    /// Bytes are the XML outside of the node tree.
+    pub fn xml_parse(&self) -> PhaseReport {
+        self.xml_parse
+    }
+
+    /// Parsing of the node tree. Bytes are the XML of the tree.
+    pub fn tree_build(&self) -> PhaseReport {
+        self.tree_build
+    }
+
+    /// Finding archive entries that belong to nodes. No bytes are read.
+    pub fn data_load(&self) -> PhaseReport {
+        self.data_load
+    }
+}
+
+/// Time and processed bytes of one phase, see [`ReadReport`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PhaseReport {
+    /// Time spent in the phase.
+    pub(crate) time: Duration,
+    /// Bytes that the phase went through.
+    pub(crate) bytes: u64,
+}
+
+impl PhaseReport {
+    /// Time spent in the phase.
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    /// Bytes that the phase went through.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    // Adds the time since `start`, if it was taken
+    pub(crate) fn record(&mut self, start: Option<Instant>, bytes: u64) {
+        if let Some(start) = start {
+            self.time += start.elapsed();
+        }
+        self.bytes += bytes;
+    }
+}
+
+// Start of a phase. The clock is read only if a report is being made, as it is not
+// available on every target, like wasm32-unknown-unknown.
+pub(crate) fn phase_start(report: &Option<&mut ReadReport>) -> Option<Instant> {
+    report.as_ref().map(|_| Instant::now())
+}
+
+impl ReadReport {
+    /// Time spent in all phases.
+    pub fn total(&self) -> Duration {
+        self.phases().iter().map(|(_, phase)| phase.time).sum()
+    }
+
+    fn phases(&self) -> [(&'static str, &PhaseReport); 4] {
+        [
+            ("zip io", &self.zip_io),
+            ("xml parse", &self.xml_parse),
+            ("tree build", &self.tree_build),
+            ("data load", &self.data_load),
+        ]
+    }
+}
+
+// One line per phase, then the total
+impl Display for ReadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, phase) in self.phases() {
+            writeln!(
+                f,
+                "{:<10} {:>12.3?} {:>12} bytes",
+                name, phase.time, phase.bytes
+            )?;
+        }
+        write!(f, "{:<10} {:>12.3?}", "total", self.total())
+    }
+}
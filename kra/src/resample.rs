@@ -0,0 +1,218 @@
+// Resampling of pixels for scaling.
+//
+// Pixels are normalized channel values with alpha as the last channel. They are
+// premultiplied while filtering, so that colors of transparent pixels do not bleed.
+
+use crate::edit::ScaleFilter;
+
+impl ScaleFilter {
+    // How far the kernel reaches, in source pixels when upscaling
+    fn support(&self) -> f32 {
+        match self {
+            ScaleFilter::Nearest => 0.5,
+            ScaleFilter::Bilinear => 1.0,
+            ScaleFilter::Bicubic => 2.0,
+        }
+    }
+
+    fn kernel(&self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            ScaleFilter::Nearest => (x < 0.5) as u8 as f32,
+            ScaleFilter::Bilinear => (1.0 - x).max(0.0),
+            // Catmull-Rom
+            ScaleFilter::Bicubic => match x {
+                x if x < 1.0 => (1.5 * x - 2.5) * x * x + 1.0,
+                x if x < 2.0 => ((-0.5 * x + 2.5) * x - 4.0) * x + 2.0,
+                _ => 0.0,
+            },
+        }
+    }
+}
+
+// A block of pixels at a position, with `default` everywhere around it
+pub(crate) struct Block<'a> {
+    pub(crate) pixels: Vec<f32>,
+    pub(crate) x: i32,
+    pub(crate) y: i32,
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) default: &'a [f32],
+}
+
+// Scales the block by (scale_x, scale_y) around the origin. Returns pixels that cover
+// the scaled block, along with their position and size.
+pub(crate) fn scale(
+    block: Block,
+    (scale_x, scale_y): (f64, f64),
+    filter: ScaleFilter,
+) -> (i32, i32, usize, usize, Vec<f32>) {
+    let channels = block.default.len();
+    let default = premultiplied(block.default.to_vec(), channels);
+    let pixels = premultiplied(block.pixels, channels);
+
+    let (x, width, pixels) = scale_rows(
+        &pixels,
+        (block.x, block.width, block.height),
+        scale_x,
+        filter,
+        &default,
+    );
+    let pixels = transpose(&pixels, width, block.height, channels);
+    let (y, height, pixels) = scale_rows(
+        &pixels,
+        (block.y, block.height, width),
+        scale_y,
+        filter,
+        &default,
+    );
+    let pixels = transpose(&pixels, height, width, channels);
+    (x, y, width, height, unpremultiplied(pixels, channels))
+}
+
+// Scales every row; returns the new start and length of rows
+fn scale_rows(
+    pixels: &[f32],
+    (start, len, rows): (i32, usize, usize),
+    scale: f64,
+    filter: ScaleFilter,
+    default: &[f32],
+) -> (i32, usize, Vec<f32>) {
+    let channels = default.len();
+    let new_start = (start as f64 * scale).floor() as i32;
+    let new_end = ((start as f64 + len as f64) * scale).ceil() as i32;
+    let new_len = (new_end - new_start).max(0) as usize;
+
+    // Source pixels and their weights for every new pixel
+    let widen = match filter {
+        ScaleFilter::Nearest => 1.0,
+        _ => (1.0 / scale).max(1.0) as f32,
+    };
+    let support = filter.support() * widen;
+    let taps: Vec<Vec<(isize, f32)>> = (0..new_len)
+        .map(|index| {
+            let center = ((new_start as f64 + index as f64 + 0.5) / scale - start as f64) as f32;
+            if filter == ScaleFilter::Nearest {
+                return vec![(center.floor() as isize, 1.0)];
+            }
+            let first = (center - 0.5 - support).floor() as isize;
+            let last = (center - 0.5 + support).ceil() as isize;
+            let mut taps: Vec<(isize, f32)> = (first..=last)
+                .map(|source| {
+                    let distance = source as f32 + 0.5 - center;
+                    (source, filter.kernel(distance / widen))
+                })
+                .filter(|(_, weight)| *weight != 0.0)
+                .collect();
+            let sum: f32 = taps.iter().map(|(_, weight)| weight).sum();
+            if sum != 0.0 {
+                taps.iter_mut().for_each(|(_, weight)| *weight /= sum);
+            }
+            taps
+        })
+        .collect();
+
+    let mut out = vec![0.0; new_len * rows * channels];
+    for row in 0..rows {
+        let source = &pixels[row * len * channels..(row + 1) * len * channels];
+        let target = &mut out[row * new_len * channels..(row + 1) * new_len * channels];
+        for (pixel, taps) in target.chunks_exact_mut(channels).zip(&taps) {
+            for (index, weight) in taps {
+                let value = match usize::try_from(*index) {
+                    Ok(index) if index < len => &source[index * channels..(index + 1) * channels],
+                    _ => default,
+                };
+                for (out, value) in pixel.iter_mut().zip(value) {
+                    *out += value * weight;
+                }
+            }
+        }
+    }
+    (new_start, new_len, out)
+}
+
+fn transpose(pixels: &[f32], width: usize, height: usize, channels: usize) -> Vec<f32> {
+    let mut out = vec![0.0; pixels.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let from = (y * width + x) * channels;
+            let to = (x * height + y) * channels;
+            out[to..to + channels].copy_from_slice(&pixels[from..from + channels]);
+        }
+    }
+    out
+}
+
+fn premultiplied(mut pixels: Vec<f32>, channels: usize) -> Vec<f32> {
+    for pixel in pixels.chunks_exact_mut(channels) {
+        let (color, alpha) = pixel.split_at_mut(channels - 1);
+        color.iter_mut().for_each(|value| *value *= alpha[0]);
+    }
+    pixels
+}
+
+fn unpremultiplied(mut pixels: Vec<f32>, channels: usize) -> Vec<f32> {
+    for pixel in pixels.chunks_exact_mut(channels) {
+        let (color, alpha) = pixel.split_at_mut(channels - 1);
+        // Kernels with negative lobes can overshoot
+        alpha[0] = alpha[0].clamp(0.0, 1.0);
+        match alpha[0] > 0.0 {
+            true => color.iter_mut().for_each(|value| *value /= alpha[0]),
+            false => color.iter_mut().for_each(|value| *value = 0.0),
+        }
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(pixels: Vec<f32>, width: usize, default: &[f32]) -> Block<'_> {
+        let height = pixels.len() / default.len() / width;
+        Block {
+            pixels,
+            x: 0,
+            y: 0,
+            width,
+            height,
+            default,
+        }
+    }
+
+    #[test]
+    fn nearest_upscale() {
+        let pixels = vec![0.0, 1.0, 1.0, 1.0];
+        let (x, y, width, height, out) = scale(
+            block(pixels, 2, &[0.0, 0.0]),
+            (2.0, 1.0),
+            ScaleFilter::Nearest,
+        );
+        assert_eq!((x, y, width, height), (0, 0, 4, 1));
+        assert_eq!(out, [0.0, 1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn bilinear_downscale_averages() {
+        let pixels = vec![0.0, 1.0, 1.0, 1.0];
+        let (_, _, width, _, out) = scale(
+            block(pixels, 2, &[0.5, 1.0]),
+            (0.5, 1.0),
+            ScaleFilter::Bilinear,
+        );
+        assert_eq!(width, 1);
+        assert_eq!(out, [0.5, 1.0]);
+    }
+
+    #[test]
+    fn transparent_colors_do_not_bleed() {
+        let pixels = vec![1.0, 1.0, 0.0, 0.0];
+        let (_, _, _, _, out) = scale(
+            block(pixels, 2, &[0.0, 0.0]),
+            (0.5, 1.0),
+            ScaleFilter::Bilinear,
+        );
+        // Transparent pixels around the block lower alpha too
+        assert_eq!(out, [1.0, 0.375]);
+    }
+}
@@ -0,0 +1,4 @@
+//! Krita resources, like brush presets, whether they come from bundles, documents or
+//! their own files.
+
+pub mod preset;
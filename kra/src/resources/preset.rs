@@ -0,0 +1,222 @@
+//! Brush presets (`.kpp` files), which are PNG images of the preset with its settings
+//! as XML in a text chunk.
+
+use std::{fs, io::Read, path::Path};
+
+use flate2::read::ZlibDecoder;
+use quick_xml::{events::Event, Reader as XmlReader};
+
+use crate::{
+    error::{PresetError, XmlError},
+    helper::{event_get_attr, event_to_string, next_xml_event},
+    KraFile,
+};
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Settings of a brush preset.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Preset {
+    /// Name of the preset.
+    name: String,
+    /// Paint operation the preset is for, like `paintbrush` or `colorsmudge`.
+    paintop: String,
+    /// Version of the preset format, empty if it is not written.
+    version: String,
+    /// Settings of the paint operation, in the order they were written.
+    params: Vec<(String, String)>,
+}
+
+impl Preset {
+    /// Name of the preset.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Paint operation the preset is for, like `paintbrush` or `colorsmudge`.
+    pub fn paintop(&self) -> &str {
+        &self.paintop
+    }
+
+    /// Version of the preset format, empty if it is not written.
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Settings of the paint operation, in the order they were written.
+    pub fn params(&self) -> &[(String, String)] {
+        &self.params
+    }
+
+    /// One of the settings, by its name.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Read the `.kpp` file at `path`.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, PresetError> {
+        Self::from_png(&fs::read(path)?)
+    }
+
+    /// Parse the contents of a `.kpp` file, for example a resource of a
+    /// [bundle](crate::bundle::Bundle::read_resource).
+    pub fn from_png(data: &[u8]) -> Result<Self, PresetError> {
+        let chunks = text_chunks(data)?;
+        let text = |key: &str| {
+            chunks
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, text)| text.as_str())
+        };
+        let xml = text("preset").ok_or(PresetError::MissingPreset)?;
+        let mut preset = parse_preset(xml)?;
+        preset.version = text("version").unwrap_or_default().to_owned();
+        Ok(preset)
+    }
+}
+
+impl KraFile {
+    /// Brush presets stored in the document, as paths of their entries along with
+    /// the presets, in archive order. Entries whose names end with `.kpp` are presets.
+    pub fn embedded_presets(&mut self) -> Result<Vec<(String, Preset)>, PresetError> {
+        let names: Vec<String> = self
+            .file()
+            .map(|archive| archive.entry_names().to_vec())
+            .unwrap_or_default();
+        let mut presets = Vec::new();
+        for name in names.into_iter().filter(|name| name.ends_with(".kpp")) {
+            let preset = Preset::from_png(&self.read_entry(&name)?)?;
+            presets.push((name, preset));
+        }
+        Ok(presets)
+    }
+}
+
+// Keywords and texts of tEXt, zTXt and iTXt chunks, in file order
+fn text_chunks(data: &[u8]) -> Result<Vec<(String, String)>, PresetError> {
+    let malformed = |what: &str| PresetError::MalformedPng(what.to_owned());
+    let mut rest = data
+        .strip_prefix(PNG_SIGNATURE)
+        .ok_or_else(|| malformed("signature"))?;
+    let mut chunks = Vec::new();
+    while !rest.is_empty() {
+        if rest.len() < 12 {
+            return Err(malformed("chunk header"));
+        }
+        let length = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        let kind = &rest[4..8];
+        let body = rest
+            .get(8..8 + length)
+            .ok_or_else(|| malformed("chunk length"))?;
+        // Chunk data is followed by its CRC
+        rest = rest
+            .get(12 + length..)
+            .ok_or_else(|| malformed("chunk CRC"))?;
+
+        match kind {
+            b"IEND" => break,
+            b"tEXt" | b"zTXt" | b"iTXt" => (),
+            _ => continue,
+        }
+        let separator = body
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or_else(|| malformed("text chunk"))?;
+        let (keyword, text) = (latin1(&body[..separator]), &body[separator + 1..]);
+        let text = match kind {
+            b"tEXt" => latin1(text),
+            b"zTXt" => {
+                let compressed = text.get(1..).ok_or_else(|| malformed("zTXt chunk"))?;
+                latin1(&inflate(compressed)?)
+            }
+            b"iTXt" => {
+                let (&compressed, text) =
+                    text.split_first().ok_or_else(|| malformed("iTXt chunk"))?;
+                // Compression method, language tag and translated keyword come before the text
+                let text = text
+                    .get(1..)
+                    .and_then(|text| text.splitn(3, |&byte| byte == 0).nth(2))
+                    .ok_or_else(|| malformed("iTXt chunk"))?;
+                let text = match compressed {
+                    0 => text.to_vec(),
+                    _ => inflate(text)?,
+                };
+                String::from_utf8(text).map_err(XmlError::from)?
+            }
+            _ => unreachable!("only text chunks are left"),
+        };
+        chunks.push((keyword, text));
+    }
+    Ok(chunks)
+}
+
+fn inflate(data: &[u8]) -> Result<Vec<u8>, PresetError> {
+    let mut out = Vec::new();
+    ZlibDecoder::new(data).read_to_end(&mut out)?;
+    Ok(out)
+}
+
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&byte| byte as char).collect()
+}
+
+// <Preset name="..." paintopid="..."><param name="..." type="...">value</param>...</Preset>
+fn parse_preset(xml: &str) -> Result<Preset, XmlError> {
+    let mut reader = XmlReader::from_str(xml);
+    reader.trim_text(true);
+    let mut preset = None;
+    loop {
+        match next_xml_event(&mut reader)? {
+            Event::Start(tag) | Event::Empty(tag) if tag.name().as_ref() == b"Preset" => {
+                preset = Some(Preset {
+                    name: event_get_attr(&tag, "name")?.unescape_value()?.into_owned(),
+                    paintop: event_get_attr(&tag, "paintopid")?
+                        .unescape_value()?
+                        .into_owned(),
+                    version: String::new(),
+                    params: Vec::new(),
+                });
+            }
+            Event::Start(tag) if tag.name().as_ref() == b"param" => {
+                let name = event_get_attr(&tag, "name")?.unescape_value()?.into_owned();
+                let value = param_value(&mut reader)?;
+                if let Some(preset) = preset.as_mut() {
+                    preset.params.push((name, value));
+                }
+            }
+            Event::Empty(tag) if tag.name().as_ref() == b"param" => {
+                let name = event_get_attr(&tag, "name")?.unescape_value()?.into_owned();
+                if let Some(preset) = preset.as_mut() {
+                    preset.params.push((name, String::new()));
+                }
+            }
+            Event::Eof => {
+                return preset.ok_or_else(|| XmlError::MissingValue("Preset".to_owned()));
+            }
+            _ => {}
+        }
+    }
+}
+
+// Text or CDATA until </param>; values can hold XML of their own, which is kept as text
+fn param_value(reader: &mut XmlReader<&[u8]>) -> Result<String, XmlError> {
+    let mut value = String::new();
+    loop {
+        match next_xml_event(reader)? {
+            Event::Text(text) => value.push_str(&text.unescape()?),
+            Event::CData(data) => value.push_str(
+                std::str::from_utf8(&data).map_err(|err| XmlError::ValueError(err.to_string()))?,
+            ),
+            Event::End(_) => return Ok(value),
+            other => {
+                return Err(XmlError::EventError(
+                    "param value",
+                    event_to_string(&other)?,
+                ))
+            }
+        }
+    }
+}
@@ -0,0 +1,479 @@
+//! Reading metadata of every document in a directory tree, on several threads, for tools
+//! that catalog or check collections of files.
+
+use std::{
+    cmp::Reverse,
+    fmt::{self, Display},
+    fs, io,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    config::ParsingConfiguration, data::is_document, error::ReadKraError, helper::run_parallel,
+    Colorspace, KraFile,
+};
+
+/// Options of [`scan()`].
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    pub(crate) config: ParsingConfiguration,
+    pub(crate) threads: usize,
+    pub(crate) recursive: bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions {
+            config: ParsingConfiguration::default().keep_archive(false),
+            threads: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            recursive: true,
+        }
+    }
+}
+
+impl ScanOptions {
+    /// Documents are fully parsed without keeping their archives open, on as many threads
+    /// as there are cores, and subdirectories are scanned too.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the configuration that documents are read with.
+    pub fn config(mut self, config: ParsingConfiguration) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Configuration that documents are read with.
+    pub fn get_config(&self) -> &ParsingConfiguration {
+        &self.config
+    }
+
+    /// Set the number of documents that are read at once, at least 1.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Number of documents that are read at once.
+    pub fn get_threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Set whether subdirectories are scanned too.
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// Whether subdirectories are scanned too.
+    pub fn get_recursive(&self) -> bool {
+        self.recursive
+    }
+}
+
+/// Facts about one document that was read.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Manifest {
+    /// Size of the file, in bytes.
+    pub(crate) file_size: u64,
+    /// Width and height, in pixels.
+    pub(crate) dimensions: (u32, u32),
+    /// Colorspace of the image.
+    pub(crate) colorspace: Colorspace,
+    /// Version of Krita under which the file was saved.
+    pub(crate) krita_version: String,
+    /// Title from the document information.
+    pub(crate) title: String,
+    /// Number of nodes, masks included. 0 if nodes are not parsed.
+    pub(crate) node_count: usize,
+    /// Number of layers, masks excluded. 0 if nodes are not parsed.
+    pub(crate) layer_count: usize,
+    /// Total time spent editing the document, if it is known.
+    pub(crate) editing_time: Option<Duration>,
+    /// How many times the document was saved, if it is known.
+    pub(crate) editing_cycles: Option<u64>,
+    /// Date of creation from the document information.
+    pub(crate) creation_date: String,
+}
+
+impl Manifest {
+    /// Size of the file, in bytes.
+    pub fn file_size(&self) -> u64 {
+        self.file_size
+    }
+
+    /// Width and height, in pixels.
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.dimensions
+    }
+
+    /// Colorspace of the image.
+    pub fn colorspace(&self) -> Colorspace {
+        self.colorspace
+    }
+
+    /// Version of Krita under which the file was saved.
+    pub fn krita_version(&self) -> &str {
+        &self.krita_version
+    }
+
+    /// Title from the document information.
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Number of nodes, masks included. 0 if nodes are not parsed.
+    pub fn node_count(&self) -> usize {
+        self.node_count
+    }
+
+    /// Number of layers, masks excluded. 0 if nodes are not parsed.
+    pub fn layer_count(&self) -> usize {
+        self.layer_count
+    }
+
+    /// Total time spent editing the document, if it is known.
+    pub fn editing_time(&self) -> Option<Duration> {
+        self.editing_time
+    }
+
+    /// How many times the document was saved, if it is known.
+    pub fn editing_cycles(&self) -> Option<u64> {
+        self.editing_cycles
+    }
+
+    /// Date of creation from the document information, like `2024-03-01T12:00:00`.
+    pub fn creation_date(&self) -> &str {
+        &self.creation_date
+    }
+}
+
+/// One document that was found and what reading it gave.
+#[derive(Debug)]
+pub struct ScanItem {
+    path: PathBuf,
+    result: Result<Manifest, ReadKraError>,
+}
+
+impl ScanItem {
+    /// Path of the document.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Facts about the document, or why it could not be read.
+    pub fn result(&self) -> Result<&Manifest, &ReadKraError> {
+        self.result.as_ref()
+    }
+}
+
+/// Totals over all documents of a scan.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct ScanStats {
+    documents: usize,
+    failed: usize,
+    bytes: u64,
+    nodes: usize,
+    elapsed: Duration,
+}
+
+impl ScanStats {
+    /// Number of documents that were found.
+    pub fn documents(&self) -> usize {
+        self.documents
+    }
+
+    /// Number of documents that could not be read.
+    pub fn failed(&self) -> usize {
+        self.failed
+    }
+
+    /// Size of all documents that were read, in bytes.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Number of nodes in all documents that were read.
+    pub fn nodes(&self) -> usize {
+        self.nodes
+    }
+
+    /// How long the scan took.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+impl Display for ScanStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} documents ({} failed), {} bytes, {} nodes in {:.2}s",
+            self.documents,
+            self.failed,
+            self.bytes,
+            self.nodes,
+            self.elapsed.as_secs_f64()
+        )
+    }
+}
+
+/// Result of [`scan()`].
+#[derive(Debug, Default)]
+pub struct ScanReport {
+    items: Vec<ScanItem>,
+    stats: ScanStats,
+}
+
+impl ScanReport {
+    /// Every document that was found, ordered by path.
+    pub fn items(&self) -> &[ScanItem] {
+        &self.items
+    }
+
+    /// Documents that could not be read.
+    pub fn failed(&self) -> impl Iterator<Item = &ScanItem> + '_ {
+        self.items.iter().filter(|item| item.result.is_err())
+    }
+
+    /// Totals over all documents.
+    pub fn stats(&self) -> &ScanStats {
+        &self.stats
+    }
+
+    /// Editing time, saves, creation dates and layers of the documents that were read,
+    /// added up.
+    pub fn editing_stats(&self) -> EditingStats {
+        let mut stats = EditingStats::default();
+        for (path, manifest) in self
+            .items
+            .iter()
+            .filter_map(|item| Some((&item.path, item.result.as_ref().ok()?)))
+        {
+            stats.layers += manifest.layer_count;
+            stats.cycles += manifest.editing_cycles.unwrap_or(0);
+            if let Some(time) = manifest.editing_time {
+                stats.editing_time += time;
+                stats.most_edited.push((path.clone(), time));
+            }
+            let created = manifest.creation_date.as_str();
+            if !created.is_empty() {
+                let earliest = stats
+                    .earliest_creation
+                    .get_or_insert_with(|| created.to_owned());
+                if created < earliest.as_str() {
+                    *earliest = created.to_owned();
+                }
+                let latest = stats
+                    .latest_creation
+                    .get_or_insert_with(|| created.to_owned());
+                if created > latest.as_str() {
+                    *latest = created.to_owned();
+                }
+            }
+        }
+        // Stable, so documents with the same time stay ordered by path
+        stats.most_edited.sort_by_key(|(_, time)| Reverse(*time));
+        stats
+    }
+}
+
+/// Editing statistics over the documents of a scan, from [`ScanReport::editing_stats()`].
+///
+/// Documents that could not be read, and values that are missing from document information,
+/// are left out.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Hash)]
+pub struct EditingStats {
+    editing_time: Duration,
+    cycles: u64,
+    layers: usize,
+    earliest_creation: Option<String>,
+    latest_creation: Option<String>,
+    most_edited: Vec<(PathBuf, Duration)>,
+}
+
+impl EditingStats {
+    /// Time spent editing all documents.
+    pub fn editing_time(&self) -> Duration {
+        self.editing_time
+    }
+
+    /// Time spent editing all documents, in hours.
+    pub fn total_hours(&self) -> f64 {
+        self.editing_time.as_secs_f64() / 3600.0
+    }
+
+    /// How many times the documents were saved, in total.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Number of layers in all documents, masks excluded.
+    pub fn layers(&self) -> usize {
+        self.layers
+    }
+
+    /// Creation date of the oldest document.
+    pub fn earliest_creation(&self) -> Option<&str> {
+        self.earliest_creation.as_deref()
+    }
+
+    /// Creation date of the newest document.
+    pub fn latest_creation(&self) -> Option<&str> {
+        self.latest_creation.as_deref()
+    }
+
+    /// Up to `count` documents that were edited for the longest time, longest first.
+    pub fn most_edited(&self, count: usize) -> &[(PathBuf, Duration)] {
+        &self.most_edited[..count.min(self.most_edited.len())]
+    }
+}
+
+impl Display for EditingStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.1} hours of editing over {} documents, {} saves, {} layers",
+            self.total_hours(),
+            self.most_edited.len(),
+            self.cycles,
+            self.layers
+        )
+    }
+}
+
+/// Lists every document, with errors of the ones that failed, and a line of totals.
+impl Display for ScanReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for item in &self.items {
+            match &item.result {
+                Ok(manifest) => {
+                    let (width, height) = manifest.dimensions;
+                    writeln!(
+                        f,
+                        "{}: {}x{} {}, {} nodes, Krita {}",
+                        item.path.display(),
+                        width,
+                        height,
+                        manifest.colorspace,
+                        manifest.node_count,
+                        manifest.krita_version
+                    )?;
+                }
+                Err(err) => writeln!(f, "{}: error: {}", item.path.display(), err)?,
+            }
+        }
+        write!(f, "{}", self.stats)
+    }
+}
+
+/// Read every document under `dir`: `.kra` files, and unpacked documents (directories with
+/// a `mimetype` entry of Krita, see [`DirectorySource`](crate::archive::DirectorySource)).
+/// Hidden files and directories are skipped.
+///
+/// Documents that could not be read are reported with their errors, only errors of listing
+/// directories are returned.
+pub fn scan<P: AsRef<Path>>(dir: P, options: &ScanOptions) -> io::Result<ScanReport> {
+    let start = Instant::now();
+    let mut documents = Vec::new();
+    find_documents(dir.as_ref(), options.recursive, &mut documents)?;
+    documents.sort();
+
+    let mut items = Vec::with_capacity(documents.len());
+    run_parallel(
+        &documents,
+        options.threads,
+        |path| read_manifest(path, &options.config),
+        |path, result| {
+            items.push(ScanItem {
+                path: path.clone(),
+                result,
+            });
+            Ok(())
+        },
+    )?;
+    items.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut stats = ScanStats {
+        documents: items.len(),
+        ..ScanStats::default()
+    };
+    for item in &items {
+        match &item.result {
+            Ok(manifest) => {
+                stats.bytes += manifest.file_size;
+                stats.nodes += manifest.node_count;
+            }
+            Err(_) => stats.failed += 1,
+        }
+    }
+    stats.elapsed = start.elapsed();
+    Ok(ScanReport { items, stats })
+}
+
+// Appends paths of documents under `dir`, without looking into unpacked documents
+fn find_documents(dir: &Path, recursive: bool, out: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if is_unpacked_document(&path) {
+                out.push(path);
+            } else if recursive {
+                find_documents(&path, recursive, out)?;
+            }
+        } else if file_type.is_file() && is_document(&path) {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn is_unpacked_document(dir: &Path) -> bool {
+    fs::read(dir.join("mimetype")).is_ok_and(|mimetype| mimetype == b"application/x-krita")
+}
+
+fn read_manifest(path: &Path, config: &ParsingConfiguration) -> Result<Manifest, ReadKraError> {
+    let file = KraFile::read_with_config(path, config.clone())?;
+    let file_size = match path.is_dir() {
+        true => dir_size(path)?,
+        false => fs::metadata(path)?.len(),
+    };
+    let meta = file.meta();
+    let about = file.doc_info().about();
+    Ok(Manifest {
+        file_size,
+        dimensions: (meta.width(), meta.height()),
+        colorspace: meta.colorspace(),
+        krita_version: meta.krita_version().to_owned(),
+        title: about.title().to_owned(),
+        node_count: file.node_count(),
+        layer_count: file.summary().layer_count(),
+        editing_time: about.editing_duration(),
+        editing_cycles: about.editing_cycle_count(),
+        creation_date: about.creation_date().to_owned(),
+    })
+}
+
+// Size of all files under `dir`
+fn dir_size(dir: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            size += dir_size(&entry.path())?;
+        } else if file_type.is_file() {
+            size += entry.metadata()?.len();
+        }
+    }
+    Ok(size)
+}
@@ -0,0 +1,244 @@
+//! Description of the part of the format that this library reads.
+//!
+//! Attributes are listed from the parsing code itself, so the description is always
+//! in line with it. `cargo run --example schema` prints it as Markdown, or as JSON
+//! with `--json`.
+
+use crate::{
+    helper::json_string,
+    layer::{
+        CloneLayerProps, ColorizeMaskProps, CommonNodeProps, FileLayerProps, FillLayerProps,
+        FilterLayerProps, FilterMaskProps, GroupLayerProps, NodeKind, PaintLayerProps,
+        SelectionMaskProps, TransformMaskProps, TransparencyMaskProps, VectorLayerProps,
+        CUSTOM_PROPERTY_PREFIX,
+    },
+};
+
+/// XML attribute that is read into a field of the model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttributeSchema {
+    /// Name of the attribute.
+    pub(crate) qname: &'static str,
+    /// Field (and getter) of the model it is read into.
+    pub(crate) field: &'static str,
+    /// Rust type of the field.
+    pub(crate) rust_type: &'static str,
+    /// Whether the attribute may be missing.
+    pub(crate) optional: bool,
+    /// Documentation of the field.
+    pub(crate) doc: &'static str,
+}
+
+impl AttributeSchema {
+    /// Name of the attribute.
+    pub fn qname(&self) -> &'static str {
+        self.qname
+    }
+
+    /// Field (and getter) of the model it is read into.
+    pub fn field(&self) -> &'static str {
+        self.field
+    }
+
+    /// Rust type of the field.
+    pub fn rust_type(&self) -> &'static str {
+        self.rust_type
+    }
+
+    /// Whether the attribute may be missing.
+    pub fn optional(&self) -> bool {
+        self.optional
+    }
+
+    /// Documentation of the field.
+    pub fn doc(&self) -> &'static str {
+        self.doc
+    }
+}
+
+/// Attributes that are read from nodes of one kind, besides the [common ones](common_attributes).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodeSchema {
+    /// Kind of the node, which is the value of its `nodetype` attribute.
+    pub(crate) kind: NodeKind,
+    /// Attributes specific to the kind.
+    pub(crate) attributes: &'static [AttributeSchema],
+}
+
+impl NodeSchema {
+    /// Kind of the node, which is the value of its `nodetype` attribute.
+    pub fn kind(&self) -> NodeKind {
+        self.kind
+    }
+
+    /// Attributes specific to the kind.
+    pub fn attributes(&self) -> &'static [AttributeSchema] {
+        self.attributes
+    }
+
+    /// Name of the node's element, `layer` or `mask`.
+    pub fn element(&self) -> &'static str {
+        match self.kind.is_mask() {
+            true => "mask",
+            false => "layer",
+        }
+    }
+}
+
+// Read by hand and not by ParseTag-derived code
+const NODE_ATTRIBUTES: &[AttributeSchema] = &[
+    AttributeSchema {
+        qname: "nodetype",
+        field: "kind",
+        rust_type: "NodeKind",
+        optional: false,
+        doc: "Kind of the node.",
+    },
+    AttributeSchema {
+        qname: "onionskin",
+        field: "onionskin",
+        rust_type: "bool",
+        optional: true,
+        doc: "Whether onionskinning is enabled, read if `intimeline` is 1.",
+    },
+    AttributeSchema {
+        qname: "selected",
+        field: "selected",
+        rust_type: "bool",
+        optional: true,
+        doc: "Whether the node was selected in the layer docker when the document was saved.",
+    },
+    AttributeSchema {
+        qname: "description",
+        field: "description",
+        rust_type: "String",
+        optional: true,
+        doc: "Notes about the node, written by tools other than Krita.",
+    },
+    AttributeSchema {
+        qname: "comment",
+        field: "description",
+        rust_type: "String",
+        optional: true,
+        doc: "Notes about the node, read if there is no `description`.",
+    },
+];
+
+// Whether the attribute is read from nodes of the kind, or is a custom property
+pub(crate) fn is_known(kind: NodeKind, qname: &str) -> bool {
+    if qname.starts_with(CUSTOM_PROPERTY_PREFIX) {
+        return true;
+    }
+    let specific = node_schemas()
+        .into_iter()
+        .find(|schema| schema.kind == kind)
+        .map_or(&[][..], |schema| schema.attributes);
+    NODE_ATTRIBUTES
+        .iter()
+        .chain(CommonNodeProps::XML_ATTRIBUTES)
+        .chain(specific)
+        .any(|attribute| attribute.qname == qname)
+}
+
+/// Attributes that are read from every node.
+pub fn common_attributes() -> Vec<AttributeSchema> {
+    NODE_ATTRIBUTES
+        .iter()
+        .chain(CommonNodeProps::XML_ATTRIBUTES)
+        .copied()
+        .collect()
+}
+
+/// Every kind of node, with attributes specific to it.
+pub fn node_schemas() -> Vec<NodeSchema> {
+    [
+        (NodeKind::PaintLayer, PaintLayerProps::XML_ATTRIBUTES),
+        (NodeKind::GroupLayer, GroupLayerProps::XML_ATTRIBUTES),
+        (NodeKind::FileLayer, FileLayerProps::XML_ATTRIBUTES),
+        (NodeKind::FilterLayer, FilterLayerProps::XML_ATTRIBUTES),
+        (NodeKind::FillLayer, FillLayerProps::XML_ATTRIBUTES),
+        (NodeKind::CloneLayer, CloneLayerProps::XML_ATTRIBUTES),
+        (NodeKind::VectorLayer, VectorLayerProps::XML_ATTRIBUTES),
+        (
+            NodeKind::TransparencyMask,
+            TransparencyMaskProps::XML_ATTRIBUTES,
+        ),
+        (NodeKind::FilterMask, FilterMaskProps::XML_ATTRIBUTES),
+        (NodeKind::TransformMask, TransformMaskProps::XML_ATTRIBUTES),
+        (NodeKind::SelectionMask, SelectionMaskProps::XML_ATTRIBUTES),
+        (NodeKind::ColorizeMask, ColorizeMaskProps::XML_ATTRIBUTES),
+    ]
+    .into_iter()
+    .map(|(kind, attributes)| NodeSchema { kind, attributes })
+    .collect()
+}
+
+/// The whole description as Markdown, with a table of attributes per kind of node.
+pub fn to_markdown() -> String {
+    let mut out = String::from("# Nodes\n\n");
+    out.push_str("Every node is a `layer` or a `mask` element.\n\n");
+    out.push_str("## Common attributes\n\n");
+    push_markdown_table(&mut out, &common_attributes());
+    for node in node_schemas() {
+        out.push_str(&format!("\n## `{}` ({})\n\n", node.kind, node.element()));
+        if node.attributes.is_empty() {
+            out.push_str("No attributes besides the common ones.\n");
+        } else {
+            push_markdown_table(&mut out, node.attributes);
+        }
+    }
+    out
+}
+
+fn push_markdown_table(out: &mut String, attributes: &[AttributeSchema]) {
+    out.push_str("| Attribute | Field | Type | Required | Description |\n");
+    out.push_str("|---|---|---|---|---|\n");
+    for attribute in attributes {
+        out.push_str(&format!(
+            "| `{}` | `{}` | `{}` | {} | {} |\n",
+            attribute.qname,
+            attribute.field,
+            attribute.rust_type,
+            if attribute.optional { "no" } else { "yes" },
+            attribute.doc.replace('|', "\\|")
+        ));
+    }
+}
+
+/// The whole description as JSON: an object with `common` attributes and `nodes`,
+/// each with its `nodetype`, `element` and `attributes`.
+pub fn to_json() -> String {
+    let nodes: Vec<String> = node_schemas()
+        .iter()
+        .map(|node| {
+            format!(
+                "{{\"nodetype\":{},\"element\":{},\"attributes\":{}}}",
+                json_string(&node.kind.to_string()),
+                json_string(node.element()),
+                json_attributes(node.attributes)
+            )
+        })
+        .collect();
+    format!(
+        "{{\"common\":{},\"nodes\":[{}]}}",
+        json_attributes(&common_attributes()),
+        nodes.join(",")
+    )
+}
+
+fn json_attributes(attributes: &[AttributeSchema]) -> String {
+    let attributes: Vec<String> = attributes
+        .iter()
+        .map(|attribute| {
+            format!(
+                "{{\"name\":{},\"field\":{},\"type\":{},\"optional\":{},\"doc\":{}}}",
+                json_string(attribute.qname),
+                json_string(attribute.field),
+                json_string(attribute.rust_type),
+                attribute.optional,
+                json_string(attribute.doc)
+            )
+        })
+        .collect();
+    format!("[{}]", attributes.join(","))
+}
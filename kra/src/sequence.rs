@@ -0,0 +1,240 @@
+//! Making animated documents out of sequences of images, like numbered PNGs rendered
+//! by other tools.
+
+use std::{
+    io::{Cursor, Write},
+    path::Path,
+};
+
+use image::RgbaImage;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::{
+    archive::ZipSource,
+    config::ParsingConfiguration,
+    error::SequenceError,
+    id::Uuid,
+    tile::{TileHeader, TiledData, TILE_SIZE},
+    KraFile,
+};
+
+const IMAGE_NAME: &str = "Unnamed";
+const LAYER_FILENAME: &str = "layer2";
+
+impl KraFile {
+    /// Animated document with one paint layer, which has a keyframe for every image,
+    /// one frame after another. The animation plays at `framerate` frames per second and
+    /// its range covers all frames. The first image is also the merged image.
+    ///
+    /// Images must all have the same size, which becomes the size of the document.
+    pub fn from_frames(frames: &[RgbaImage], framerate: u32) -> Result<Self, SequenceError> {
+        let first = frames.first().ok_or(SequenceError::NoFrames)?;
+        let (width, height) = first.dimensions();
+        if let Some(index) = frames
+            .iter()
+            .position(|frame| frame.dimensions() != (width, height))
+        {
+            return Err(SequenceError::SizeMismatch(index));
+        }
+
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("mimetype", stored)?;
+        zip.write_all(b"application/x-krita")?;
+        zip.start_file("documentinfo.xml", FileOptions::default())?;
+        zip.write_all(DOCUMENT_INFO.as_bytes())?;
+        zip.start_file("maindoc.xml", FileOptions::default())?;
+        zip.write_all(maindoc(width, height, frames.len(), framerate).as_bytes())?;
+
+        let layers = format!("{IMAGE_NAME}/layers/");
+        zip.start_file(
+            format!("{layers}{LAYER_FILENAME}.keyframes.xml"),
+            FileOptions::default(),
+        )?;
+        zip.write_all(keyframes(frames.len()).as_bytes())?;
+        for (index, frame) in frames.iter().enumerate() {
+            zip.start_file(
+                format!("{layers}{}", frame_filename(index)),
+                FileOptions::default(),
+            )?;
+            zip.write_all(&tiles(frame))?;
+        }
+        let mut png = Vec::new();
+        first.write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)?;
+        zip.start_file("mergedimage.png", FileOptions::default())?;
+        zip.write_all(&png)?;
+
+        let bytes = zip.finish()?.into_inner();
+        Ok(KraFile::read_from(
+            ZipSource::from_bytes(bytes)?,
+            ParsingConfiguration::default(),
+        )?)
+    }
+
+    /// Same as [`from_frames()`](Self::from_frames), with images read from files.
+    ///
+    /// Frames are ordered by the last number in names of the files, like `walk_0012.png`,
+    /// so the paths can be given in any order. Files without a number are an error.
+    pub fn from_image_sequence<P: AsRef<Path>>(
+        paths: &[P],
+        framerate: u32,
+    ) -> Result<Self, SequenceError> {
+        let mut numbered = paths
+            .iter()
+            .map(|path| {
+                let path = path.as_ref();
+                frame_number(path)
+                    .map(|number| (number, path))
+                    .ok_or_else(|| SequenceError::Unnumbered(path.to_owned()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        numbered.sort();
+        let frames = numbered
+            .into_iter()
+            .map(|(_, path)| Ok(image::open(path)?.to_rgba8()))
+            .collect::<Result<Vec<_>, SequenceError>>()?;
+        Self::from_frames(&frames, framerate)
+    }
+}
+
+// Last run of digits in the file name, without the extension
+fn frame_number(path: &Path) -> Option<u64> {
+    let stem = path.file_stem()?.to_str()?;
+    let end = stem.rfind(|c: char| c.is_ascii_digit())? + 1;
+    let start = stem[..end]
+        .rfind(|c: char| !c.is_ascii_digit())
+        .map_or(0, |index| index + 1);
+    stem[start..end].parse().ok()
+}
+
+// Krita names pixel data of the first keyframe like the layer, and of the others with
+// the frame's ID after it
+fn frame_filename(index: usize) -> String {
+    match index {
+        0 => LAYER_FILENAME.to_owned(),
+        _ => format!("{LAYER_FILENAME}.f{index}"),
+    }
+}
+
+// Pixels as BGRA tiles, padded with transparent pixels to whole tiles
+fn tiles(frame: &RgbaImage) -> Vec<u8> {
+    let padded = |size: u32| size.div_ceil(TILE_SIZE) * TILE_SIZE;
+    let (width, height) = frame.dimensions();
+    let padded_width = padded(width) as usize;
+    let mut pixels = vec![0; padded_width * padded(height) as usize * 4];
+    for (x, y, pixel) in frame.enumerate_pixels() {
+        let [red, green, blue, alpha] = pixel.0;
+        let start = (y as usize * padded_width + x as usize) * 4;
+        pixels[start..start + 4].copy_from_slice(&[blue, green, red, alpha]);
+    }
+    let header = TileHeader {
+        tile_width: TILE_SIZE,
+        tile_height: TILE_SIZE,
+        pixel_size: 4,
+        tile_count: 0,
+    };
+    TiledData::from_dense(header, (0, 0, padded_width as u32), &pixels, &[0; 4]).write()
+}
+
+fn keyframes(count: usize) -> String {
+    let keyframes: String = (0..count)
+        .map(|index| {
+            format!(
+                "  <keyframe time=\"{index}\" frame=\"{}\" color-label=\"0\">\n   <offset type=\"point\" x=\"0\" y=\"0\"/>\n  </keyframe>\n",
+                frame_filename(index)
+            )
+        })
+        .collect();
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE keyframes PUBLIC '-//KDE//DTD krita-keyframes 1.0//EN' 'http://www.calligra.org/DTD/krita-keyframes-1.0.dtd'>
+<keyframes>
+ <channel name="content">
+{keyframes} </channel>
+</keyframes>
+"#
+    )
+}
+
+fn maindoc(width: u32, height: u32, frame_count: usize, framerate: u32) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE DOC PUBLIC '-//KDE//DTD krita 2.0//EN' 'http://www.calligra.org/DTD/krita-2.0.dtd'>
+<DOC xmlns="http://www.calligra.org/DTD/krita" syntaxVersion="2.0" kritaVersion="5.2.2" editor="Krita">
+ <IMAGE mime="application/x-kra" x-res="72" profile="sRGB-elle-V2-srgbtrc.icc" y-res="72" colorspacename="RGBA" name="{IMAGE_NAME}" description="" height="{height}" width="{width}">
+  <layers>
+   <layer name="Frames" uuid="{{{uuid}}}" filename="{LAYER_FILENAME}" visible="1" locked="0" colorlabel="0" x="0" y="0" intimeline="1" nodetype="paintlayer" compositeop="normal" opacity="255" collapsed="0" colorspacename="RGBA" channellockflags="1111" channelflags="" keyframes="{LAYER_FILENAME}.keyframes.xml" onionskin="0"/>
+  </layers>
+  <ProjectionBackgroundColor ColorData="AAAAAA=="/>
+  <GlobalAssistantsColor SimpleColorData="176,176,176,255"/>
+  <MirrorAxis>
+   <mirrorHorizontal type="value" value="0"/>
+   <mirrorVertical type="value" value="0"/>
+   <lockHorizontal type="value" value="0"/>
+   <lockVertical type="value" value="0"/>
+   <hideHorizontalDecoration type="value" value="0"/>
+   <hideVerticalDecoration type="value" value="0"/>
+   <handleSize type="value" value="32"/>
+   <horizontalHandlePosition type="value" value="{half_width}"/>
+   <verticalHandlePosition type="value" value="{half_height}"/>
+   <axisPosition type="pointf" x="{half_width}" y="{half_height}"/>
+  </MirrorAxis>
+  <animation>
+   <framerate type="value" value="{framerate}"/>
+   <range type="timerange" from="0" to="{last_frame}"/>
+   <currentTime type="value" value="0"/>
+  </animation>
+ </IMAGE>
+</DOC>
+"#,
+        uuid = Uuid::new_v4(),
+        half_width = width / 2,
+        half_height = height / 2,
+        last_frame = frame_count - 1,
+    )
+}
+
+const DOCUMENT_INFO: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE document-info PUBLIC '-//KDE//DTD document-info 1.1//EN' 'http://www.calligra.org/DTD/document-info-1.1.dtd'>
+<document-info xmlns="http://www.calligra.org/DTD/document-info">
+ <about>
+  <title></title>
+  <description></description>
+  <subject></subject>
+  <abstract><![CDATA[]]></abstract>
+  <keyword></keyword>
+  <initial-creator></initial-creator>
+  <editing-cycles>1</editing-cycles>
+  <editing-time></editing-time>
+  <date></date>
+  <creation-date></creation-date>
+  <language></language>
+  <license></license>
+ </about>
+ <author>
+  <full-name></full-name>
+  <creator-first-name></creator-first-name>
+  <creator-last-name></creator-last-name>
+  <initial></initial>
+  <author-title></author-title>
+  <position></position>
+  <company></company>
+ </author>
+</document-info>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_numbers() {
+        let number = |path: &str| frame_number(Path::new(path));
+        assert_eq!(number("walk_0012.png"), Some(12));
+        assert_eq!(number("shot2/take3_frame07.png"), Some(7));
+        assert_eq!(number("42.png"), Some(42));
+        assert_eq!(number("walk.png"), None);
+        assert_eq!(frame_filename(0), "layer2");
+        assert_eq!(frame_filename(3), "layer2.f3");
+    }
+}
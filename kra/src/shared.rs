@@ -0,0 +1,120 @@
+//! Decoding of layers from several threads at once.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::Mutex,
+};
+
+use crate::{
+    archive::ArchiveSource, data::LayerData, decode_tiles, error::DataError, id::Uuid,
+    layer::NodeType, layer_entry_path, Colorspace, EntryReader, KraFile,
+};
+
+/// Decoder of paint layers that can be shared between threads, see
+/// [`KraFile::layer_reader()`].
+///
+/// Every thread reads from its own instance of the archive. Instances are kept in a pool
+/// and reused, the pool is only locked to take or to return one, so layers are read and
+/// decoded in parallel.
+pub struct LayerReader {
+    image_name: String,
+    // Every node by its UUID, `None` for nodes that are not paint layers
+    nodes: HashMap<Uuid, Option<PaintLayer>>,
+    // Entries that were changed in memory
+    pending: BTreeMap<String, Vec<u8>>,
+    limit: u64,
+    max_pixels: u64,
+    // Instances of the archive that are not in use
+    pool: Mutex<Vec<Box<dyn ArchiveSource>>>,
+    // Instance that new ones are opened from
+    origin: Mutex<Box<dyn ArchiveSource>>,
+}
+
+// What is needed to find and decode pixels of a paint layer
+#[derive(Debug)]
+struct PaintLayer {
+    filename: String,
+    offset: (i32, i32),
+    colorspace: Colorspace,
+}
+
+impl LayerReader {
+    /// Decode pixel data of a paint layer, same as [`KraFile::decode_layer()`].
+    ///
+    /// Changes made to the document after the reader was made are not seen.
+    pub fn decode_layer(&self, uuid: &Uuid) -> Result<LayerData, DataError> {
+        let layer = self
+            .nodes
+            .get(uuid)
+            .ok_or(DataError::UnknownNode(*uuid))?
+            .as_ref()
+            .ok_or(DataError::NoPixelData(*uuid))?;
+        let path = layer_entry_path(&self.image_name, &layer.filename)?;
+        let mut archive = self.take()?;
+        let mut entries = EntryReader {
+            pending: &self.pending,
+            archive: Some(archive.as_mut()),
+            limit: self.limit,
+        };
+        let data = entries.read_tiled(&path);
+        self.pool.lock().unwrap().push(archive);
+        decode_tiles(data?, layer.offset, layer.colorspace, self.max_pixels)
+    }
+
+    // An instance of the archive that is not used by other threads
+    fn take(&self) -> Result<Box<dyn ArchiveSource>, DataError> {
+        if let Some(archive) = self.pool.lock().unwrap().pop() {
+            return Ok(archive);
+        }
+        let origin = self.origin.lock().unwrap();
+        Ok(origin.reopen().ok_or(DataError::NotReopenable)??)
+    }
+}
+
+// Pixels of changed entries would flood the output
+impl fmt::Debug for LayerReader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LayerReader")
+            .field("nodes", &self.nodes.len())
+            .field("pending", &self.pending.keys().collect::<Vec<_>>())
+            .field("pool", &self.pool.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl KraFile {
+    /// Decoder of paint layers that can be used from several threads at once.
+    ///
+    /// The archive must be kept open and be possible to open again (see
+    /// [`ArchiveSource::reopen()`]), which is true for archives read from a path,
+    /// from bytes, or from a directory.
+    pub fn layer_reader(&self) -> Result<LayerReader, DataError> {
+        let archive = self.file.as_ref().ok_or(DataError::NoArchive)?;
+        let origin = archive.reopen().ok_or(DataError::NotReopenable)??;
+        let nodes = self
+            .all_nodes()
+            .into_iter()
+            .map(|node| {
+                let layer = match node.node_type() {
+                    NodeType::PaintLayer(props) => Some(PaintLayer {
+                        filename: node.filename().to_owned(),
+                        offset: (node.x(), node.y()),
+                        colorspace: props.colorspace(),
+                    }),
+                    _ => None,
+                };
+                (node.uuid(), layer)
+            })
+            .collect();
+        Ok(LayerReader {
+            image_name: self.meta.name().to_owned(),
+            nodes,
+            pending: self.pending_entries.clone(),
+            limit: self.config.limits.max_entry_size,
+            max_pixels: self.config.limits.max_pixels,
+            pool: Mutex::new(Vec::new()),
+            origin: Mutex::new(origin),
+        })
+    }
+}
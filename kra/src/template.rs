@@ -0,0 +1,108 @@
+// New documents that start from the layers of another one.
+
+use std::io::{Cursor, Write};
+
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::{
+    archive::ZipSource,
+    error::{DataError, FromTemplateError},
+    layer::NodeType,
+    tile::{TileHeader, TiledData},
+    EntryReader, KraFile,
+};
+
+impl KraFile {
+    /// New document with the same nodes, image metadata and document information as
+    /// `template`, but with blank paint layers, for starting every asset from a standard
+    /// set of layers.
+    ///
+    /// Paint layers keep their attributes, profiles and keyframes, but every frame is
+    /// transparent. Data of other nodes, like filter configurations, vector shapes and pixels
+    /// of masks, is copied. Changes made to the template that were not written yet are
+    /// included. `mergedimage.png`, `preview.png` and
+    /// [orphaned entries](Self::orphaned_entries) are left out.
+    ///
+    /// The archive of the template must be kept open and be possible to open again
+    /// (see [`ArchiveSource::reopen()`](crate::archive::ArchiveSource::reopen)).
+    pub fn from_template(template: &KraFile) -> Result<Self, FromTemplateError> {
+        let archive = template.file.as_ref().ok_or(DataError::NoArchive)?;
+        let mut archive = archive.reopen().ok_or(DataError::NotReopenable)??;
+        let mut names = archive.entry_names().to_vec();
+        names.extend(
+            template
+                .pending_entries
+                .keys()
+                .filter(|name| !names.contains(name))
+                .cloned()
+                .collect::<Vec<_>>(),
+        );
+        let blank: Vec<&String> = template
+            .all_nodes()
+            .into_iter()
+            .filter(|node| matches!(node.node_type(), NodeType::PaintLayer(_)))
+            .flat_map(|node| {
+                template
+                    .node_entries
+                    .get(&node.uuid())
+                    .into_iter()
+                    .flatten()
+            })
+            .collect();
+        let mut entries = EntryReader {
+            pending: &template.pending_entries,
+            archive: Some(archive.as_mut()),
+            limit: template.config.limits.max_entry_size,
+        };
+
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("mimetype", stored)?;
+        zip.write_all(b"application/x-krita")?;
+        for name in &names {
+            let left_out = matches!(
+                name.as_str(),
+                "mimetype" | "mergedimage.png" | "preview.png"
+            );
+            if left_out || name.ends_with('/') || template.orphaned_entries.contains(name) {
+                continue;
+            }
+            let data = match (name.as_str(), blank.contains(&name)) {
+                ("maindoc.xml", _) => match template.rebuilt_maindoc()? {
+                    Some(maindoc) => maindoc.into_bytes(),
+                    None => entries.read(name)?,
+                },
+                (_, true) => blanked(name, entries.read(name)?),
+                (_, false) => entries.read(name)?,
+            };
+            zip.start_file(name.as_str(), FileOptions::default())?;
+            zip.write_all(&data)?;
+        }
+
+        let bytes = zip.finish()?.into_inner();
+        Ok(KraFile::read_from(
+            ZipSource::from_bytes(bytes)?,
+            template.config.clone(),
+        )?)
+    }
+}
+
+// Entry of a paint layer without painted pixels: tiled data without tiles,
+// and a transparent default pixel. Other entries are kept.
+fn blanked(name: &str, data: Vec<u8>) -> Vec<u8> {
+    if name.ends_with(".defaultpixel") {
+        return vec![0; data.len()];
+    }
+    match TileHeader::read(&mut data.as_slice()) {
+        Ok(header) => TiledData {
+            header: TileHeader {
+                tile_count: 0,
+                ..header
+            },
+            tiles: Vec::new(),
+            default_pixel: None,
+        }
+        .write(),
+        Err(_) => data,
+    }
+}
@@ -0,0 +1,796 @@
+//! Construction of minimal valid `.kra` files, for tests of crates that use this one.
+//! With the `render` feature, also comparison of [composited](crate::KraFile::composite)
+//! images with the ones Krita saved, see [`compare_render()`].
+//!
+//! Enabled by the `testing` feature.
+//!
+//! ```
+//! use kra::testing::{DocumentBuilder, LayerBuilder};
+//!
+//! let bytes = DocumentBuilder::new(128, 128)
+//!     .layer(LayerBuilder::group("Group").child(LayerBuilder::paint("Inner")))
+//!     .layer(LayerBuilder::paint("Background").default_pixel([255, 255, 255, 255]))
+//!     .to_bytes();
+//! assert!(!bytes.is_empty());
+//! ```
+
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, Cursor, Seek, Write},
+    ops::Deref,
+    path::{Path, PathBuf},
+    process,
+};
+
+use quick_xml::escape::escape;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::{
+    id::Uuid,
+    tile::{linearize, lzf_compress, COMPRESSED_DATA_FLAG, TILE_SIZE},
+};
+
+/// Builder of a minimal `.kra` file with 8-bit RGBA layers.
+///
+/// Entries that are added are written after the ones the builder makes, and replace
+/// the ones with the same name, so that any entry can be changed.
+#[derive(Debug, Clone)]
+pub struct DocumentBuilder {
+    name: String,
+    width: u32,
+    height: u32,
+    krita_version: String,
+    colorspace: String,
+    resolution: (String, String),
+    elements: Vec<String>,
+    info: Vec<(String, String)>,
+    layers: Vec<LayerBuilder>,
+    entries: Vec<(String, Vec<u8>)>,
+    removed: Vec<String>,
+}
+
+impl DocumentBuilder {
+    /// Empty document of the given size, in pixels.
+    pub fn new(width: u32, height: u32) -> Self {
+        DocumentBuilder {
+            name: "Unnamed".to_owned(),
+            width,
+            height,
+            krita_version: "5.2.2".to_owned(),
+            colorspace: "RGBA".to_owned(),
+            resolution: ("300".to_owned(), "300".to_owned()),
+            elements: Vec::new(),
+            info: Vec::new(),
+            layers: Vec::new(),
+            entries: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
+
+    /// Set name of the image, which is also the directory of its entries.
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_owned();
+        self
+    }
+
+    /// Set the version of Krita that the document says it was saved with.
+    pub fn krita_version(mut self, version: &str) -> Self {
+        self.krita_version = version.to_owned();
+        self
+    }
+
+    /// Set the colorspace of the image, like `RGBA16`.
+    pub fn colorspace(mut self, colorspace: &str) -> Self {
+        self.colorspace = colorspace.to_owned();
+        self
+    }
+
+    /// Set horizontal and vertical resolution, as they are written (`300` by default).
+    pub fn resolution(mut self, x: &str, y: &str) -> Self {
+        self.resolution = (x.to_owned(), y.to_owned());
+        self
+    }
+
+    /// Add an element to `<IMAGE>`, after the layers, like `<Guides>`.
+    pub fn element(mut self, xml: &str) -> Self {
+        self.elements.push(xml.to_owned());
+        self
+    }
+
+    /// Set text of an element of `documentinfo.xml`, like `editing-cycles` or `full-name`.
+    pub fn info(mut self, element: &str, text: &str) -> Self {
+        self.info.retain(|(name, _)| name != element);
+        self.info.push((element.to_owned(), text.to_owned()));
+        self
+    }
+
+    /// Add a top-level layer below the ones that were already added.
+    pub fn layer(mut self, layer: LayerBuilder) -> Self {
+        self.layers.push(layer);
+        self
+    }
+
+    /// Add an entry at the root of the archive, like `mergedimage.png`.
+    pub fn entry(mut self, path: &str, data: impl Into<Vec<u8>>) -> Self {
+        self.entries.push((path.to_owned(), data.into()));
+        self
+    }
+
+    /// Add an entry in the directory of the image, like `layers/layer2.pixelselection`.
+    pub fn image_entry(self, path: &str, data: impl Into<Vec<u8>>) -> Self {
+        let path = format!("{}/{}", self.name, path);
+        self.entry(&path, data)
+    }
+
+    /// Leave out an entry, like `mimetype`, even if the builder would make it.
+    pub fn without_entry(mut self, path: &str) -> Self {
+        self.removed.push(path.to_owned());
+        self
+    }
+
+    /// Contents of `documentinfo.xml`.
+    pub fn documentinfo(&self) -> String {
+        let field = |element: &str, default: &str| {
+            let text = self
+                .info
+                .iter()
+                .find(|(name, _)| name == element)
+                .map_or(default, |(_, text)| text.as_str());
+            format!("<{element}>{text}</{element}>")
+        };
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE document-info PUBLIC '-//KDE//DTD document-info 1.1//EN' 'http://www.calligra.org/DTD/document-info-1.1.dtd'>
+<document-info xmlns="http://www.calligra.org/DTD/document-info">
+ <about>
+  {}
+  {}
+  {}
+  {}
+  {}
+  {}
+  {}
+  {}
+  {}
+  {}
+  {}
+  {}
+ </about>
+ <author>
+  {}
+  {}
+  {}
+  {}
+  {}
+  {}
+  {}
+ </author>
+</document-info>
+"#,
+            field("title", &escape(&self.name)),
+            field("description", ""),
+            field("subject", ""),
+            field("abstract", "<![CDATA[]]>"),
+            field("keyword", ""),
+            field("initial-creator", "Unknown"),
+            field("editing-cycles", "1"),
+            field("editing-time", ""),
+            field("date", "2024-01-01T00:00:00"),
+            field("creation-date", "2024-01-01T00:00:00"),
+            field("language", ""),
+            field("license", ""),
+            field("full-name", ""),
+            field("creator-first-name", ""),
+            field("creator-last-name", ""),
+            field("initial", ""),
+            field("author-title", ""),
+            field("position", ""),
+            field("company", ""),
+        )
+    }
+
+    /// Contents of `maindoc.xml`.
+    pub fn maindoc(&self) -> String {
+        let mut layers = String::new();
+        let mut next_id = 1;
+        for layer in &self.layers {
+            layer.write_xml(&mut layers, &mut next_id);
+        }
+        let elements: String = self
+            .elements
+            .iter()
+            .map(|element| format!("  {}\n", element))
+            .collect();
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE DOC PUBLIC '-//KDE//DTD krita 2.0//EN' 'http://www.calligra.org/DTD/krita-2.0.dtd'>
+<DOC xmlns="http://www.calligra.org/DTD/krita" syntaxVersion="2.0" kritaVersion="{version}" editor="Krita">
+ <IMAGE mime="application/x-kra" x-res="{x_res}" profile="sRGB-elle-V2-srgbtrc.icc" y-res="{y_res}" colorspacename="{colorspace}" name="{name}" description="" height="{height}" width="{width}">
+  <layers>
+{layers}  </layers>
+  <ProjectionBackgroundColor ColorData="AAAAAA=="/>
+  <GlobalAssistantsColor SimpleColorData="176,176,176,255"/>
+{elements}  <MirrorAxis>
+   <mirrorHorizontal type="value" value="0"/>
+   <mirrorVertical type="value" value="0"/>
+   <lockHorizontal type="value" value="0"/>
+   <lockVertical type="value" value="0"/>
+   <hideHorizontalDecoration type="value" value="0"/>
+   <hideVerticalDecoration type="value" value="0"/>
+   <handleSize type="value" value="32"/>
+   <horizontalHandlePosition type="value" value="{half_width}"/>
+   <verticalHandlePosition type="value" value="{half_height}"/>
+   <axisPosition type="pointf" x="{half_width}" y="{half_height}"/>
+  </MirrorAxis>
+ </IMAGE>
+</DOC>
+"#,
+            version = escape(&self.krita_version),
+            x_res = escape(&self.resolution.0),
+            y_res = escape(&self.resolution.1),
+            colorspace = escape(&self.colorspace),
+            name = escape(&self.name),
+            width = self.width,
+            height = self.height,
+            half_width = self.width / 2,
+            half_height = self.height / 2,
+        )
+    }
+
+    // Entries of the archive in the order they are written, with the added ones last
+    fn archive_entries(&self) -> Vec<(String, Vec<u8>)> {
+        let mut entries = vec![
+            ("mimetype".to_owned(), b"application/x-krita".to_vec()),
+            (
+                "documentinfo.xml".to_owned(),
+                self.documentinfo().into_bytes(),
+            ),
+            ("maindoc.xml".to_owned(), self.maindoc().into_bytes()),
+        ];
+        let mut next_id = 1;
+        for layer in &self.layers {
+            layer.data_entries(&self.name, &mut next_id, &mut entries);
+        }
+        for (path, data) in &self.entries {
+            match entries.iter_mut().find(|(name, _)| name == path) {
+                Some(entry) => entry.1 = data.clone(),
+                None => entries.push((path.clone(), data.clone())),
+            }
+        }
+        entries.retain(|(name, _)| !self.removed.contains(name));
+        entries
+    }
+
+    /// Write the archive into `writer`.
+    pub fn write<W: Write + Seek>(&self, writer: W) -> zip::result::ZipResult<()> {
+        let mut zip = ZipWriter::new(writer);
+        for (path, data) in self.archive_entries() {
+            // Krita requires mimetype to be the first entry, stored uncompressed
+            let options = match path.as_str() {
+                "mimetype" => FileOptions::default().compression_method(CompressionMethod::Stored),
+                _ => FileOptions::default(),
+            };
+            zip.start_file(path, options)?;
+            zip.write_all(&data)?;
+        }
+        zip.finish()?;
+        Ok(())
+    }
+
+    /// The archive, as bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Cursor::new(Vec::new());
+        self.write(&mut bytes)
+            .expect("writing into memory does not fail");
+        bytes.into_inner()
+    }
+
+    /// Write the archive into a file at `path`.
+    pub fn write_to<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write(File::create(path)?)?;
+        Ok(())
+    }
+
+    /// Write the archive into `kra-<name>-<process ID>.kra` in the temporary directory,
+    /// which is removed when the returned path is dropped.
+    pub fn write_temp(&self, name: &str) -> io::Result<TempPath> {
+        let path = TempPath::new(&format!("kra-{}-{}.kra", name, process::id()));
+        self.write_to(&path)?;
+        Ok(path)
+    }
+}
+
+/// Path of a file or directory in the temporary directory, which is removed
+/// when this is dropped.
+#[must_use]
+#[derive(Debug)]
+pub struct TempPath(PathBuf);
+
+impl TempPath {
+    /// Path of `file_name` in the temporary directory. Nothing is created.
+    pub fn new(file_name: &str) -> Self {
+        TempPath(env::temp_dir().join(file_name))
+    }
+}
+
+impl Deref for TempPath {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempPath {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        let _ = match self.0.is_dir() {
+            true => fs::remove_dir_all(&self.0),
+            false => fs::remove_file(&self.0),
+        };
+    }
+}
+
+// Tiles of pixel data that the builder makes
+#[derive(Debug, Clone)]
+enum Tile {
+    // Every pixel is the same
+    Filled(i32, i32, Vec<u8>),
+    // All pixels, row by row
+    Pixels(i32, i32, Vec<u8>),
+}
+
+/// Builder of a node of [`DocumentBuilder`]: a layer, or a mask of one.
+///
+/// Unless set, UUIDs are assigned in document order: the first node gets 1, the next 2,
+/// and so on. Filenames are `layer<n>` (or `mask<n>`) with the same number.
+///
+/// Paint layers get pixel data, which is empty unless tiles are added. Other nodes
+/// get it only if tiles, a default pixel or [data](Self::data) are set; selection masks
+/// store it in `<filename>.pixelselection`.
+#[derive(Debug, Clone)]
+pub struct LayerBuilder {
+    element: &'static str,
+    name: String,
+    uuid: Option<Uuid>,
+    filename: Option<String>,
+    attributes: Vec<(String, String)>,
+    elements: Vec<String>,
+    children: Option<Vec<LayerBuilder>>,
+    masks: Vec<LayerBuilder>,
+    tiles: Vec<Tile>,
+    default_pixel: Option<Vec<u8>>,
+    data: Option<Vec<u8>>,
+}
+
+impl LayerBuilder {
+    fn new(element: &'static str, name: &str, nodetype: &str) -> Self {
+        let mut attributes: Vec<_> = [
+            ("visible", "1"),
+            ("locked", "0"),
+            ("colorlabel", "0"),
+            ("x", "0"),
+            ("y", "0"),
+            ("intimeline", "0"),
+            ("nodetype", nodetype),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_owned(), value.to_owned()))
+        .collect();
+        if element == "layer" {
+            for (key, value) in [
+                ("compositeop", "normal"),
+                ("opacity", "255"),
+                ("collapsed", "0"),
+            ] {
+                attributes.push((key.to_owned(), value.to_owned()));
+            }
+        }
+        LayerBuilder {
+            element,
+            name: name.to_owned(),
+            uuid: None,
+            filename: None,
+            attributes,
+            elements: Vec::new(),
+            children: None,
+            masks: Vec::new(),
+            tiles: Vec::new(),
+            default_pixel: None,
+            data: None,
+        }
+    }
+
+    /// Layer of the node type, like `adjustmentlayer`, with only the attributes
+    /// that all layers have.
+    pub fn layer(name: &str, nodetype: &str) -> Self {
+        Self::new("layer", name, nodetype)
+    }
+
+    /// Mask of the node type, like `transparencymask`. Selection masks are active.
+    pub fn mask(name: &str, nodetype: &str) -> Self {
+        let mask = Self::new("mask", name, nodetype);
+        match nodetype {
+            "selectionmask" => mask.attribute("active", "1"),
+            _ => mask,
+        }
+    }
+
+    /// Empty paint layer.
+    pub fn paint(name: &str) -> Self {
+        Self::layer(name, "paintlayer")
+            .attribute("colorspacename", "RGBA")
+            .attribute("channellockflags", "1111")
+            .attribute("channelflags", "")
+    }
+
+    /// Empty group layer.
+    pub fn group(name: &str) -> Self {
+        let mut group = Self::layer(name, "grouplayer").attribute("passthrough", "0");
+        group.children = Some(Vec::new());
+        group
+    }
+
+    /// File layer that shows the file at `source`, relative to the document.
+    pub fn file_layer(name: &str, source: &str) -> Self {
+        Self::layer(name, "filelayer")
+            .attribute("colorspacename", "RGBA")
+            .attribute("scalingmethod", "0")
+            .attribute("scalingfilter", "Bicubic")
+            .attribute("scale", "true")
+            .attribute("source", source)
+            .attribute("channelflags", "")
+    }
+
+    /// Clone layer of the layer with the UUID.
+    pub fn clone_layer(name: &str, source: Uuid) -> Self {
+        Self::layer(name, "clonelayer")
+            .attribute("clonetype", "1")
+            .attribute("clonefrom", "Source")
+            .attribute("clonefromuuid", &format!("{{{}}}", source))
+            .attribute("channelflags", "")
+    }
+
+    /// Set UUID of the node.
+    pub fn uuid(mut self, uuid: Uuid) -> Self {
+        self.uuid = Some(uuid);
+        self
+    }
+
+    /// Set the filename of the node's entries.
+    pub fn filename(mut self, filename: &str) -> Self {
+        self.filename = Some(filename.to_owned());
+        self
+    }
+
+    /// Set an attribute, replacing the one with the same name.
+    pub fn attribute(mut self, key: &str, value: &str) -> Self {
+        match self.attributes.iter_mut().find(|(name, _)| name == key) {
+            Some(attribute) => attribute.1 = value.to_owned(),
+            None => self.attributes.push((key.to_owned(), value.to_owned())),
+        }
+        self
+    }
+
+    /// Leave out an attribute that the builder would write.
+    pub fn without_attribute(mut self, key: &str) -> Self {
+        self.attributes.retain(|(name, _)| name != key);
+        self
+    }
+
+    /// Add an element inside the node, like `<keyframes>`.
+    pub fn element(mut self, xml: &str) -> Self {
+        self.elements.push(xml.to_owned());
+        self
+    }
+
+    /// Set whether the node is visible.
+    pub fn visible(self, visible: bool) -> Self {
+        self.attribute("visible", if visible { "1" } else { "0" })
+    }
+
+    /// Set offset of the node, in pixels.
+    pub fn offset(self, x: u32, y: u32) -> Self {
+        self.attribute("x", &x.to_string())
+            .attribute("y", &y.to_string())
+    }
+
+    /// Set opacity, from 0 to 255.
+    pub fn opacity(self, opacity: u8) -> Self {
+        self.attribute("opacity", &opacity.to_string())
+    }
+
+    /// Set the blend mode, like `multiply`.
+    pub fn composite_op(self, op: &str) -> Self {
+        self.attribute("compositeop", op)
+    }
+
+    /// Set the colorspace of the pixel data, like `RGBA16`.
+    pub fn colorspace(self, colorspace: &str) -> Self {
+        self.attribute("colorspacename", colorspace)
+    }
+
+    /// Fill a 64x64 tile at `(x, y)` (relative to the layer's offset) with a pixel,
+    /// which for 8-bit RGBA is BGRA.
+    pub fn fill_tile(mut self, x: i32, y: i32, pixel: impl AsRef<[u8]>) -> Self {
+        self.tiles.push(Tile::Filled(x, y, pixel.as_ref().to_vec()));
+        self
+    }
+
+    /// Add a 64x64 tile at `(x, y)` with all of its pixels, row by row, which is stored
+    /// compressed. The pixel size is the length divided by the number of pixels.
+    pub fn tile(mut self, x: i32, y: i32, pixels: impl Into<Vec<u8>>) -> Self {
+        self.tiles.push(Tile::Pixels(x, y, pixels.into()));
+        self
+    }
+
+    /// Set the pixel that is used where there are no tiles.
+    pub fn default_pixel(mut self, pixel: impl AsRef<[u8]>) -> Self {
+        self.default_pixel = Some(pixel.as_ref().to_vec());
+        self
+    }
+
+    /// Set contents of the pixel data entry as they are, instead of the tiles.
+    pub fn data(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.data = Some(data.into());
+        self
+    }
+
+    /// Add a child layer below the ones that were already added.
+    ///
+    /// # Panics
+    ///
+    /// If this is not a group layer.
+    pub fn child(mut self, child: LayerBuilder) -> Self {
+        self.children
+            .as_mut()
+            .expect("only group layers have children")
+            .push(child);
+        self
+    }
+
+    /// Add a mask below the ones that were already added.
+    pub fn with_mask(mut self, mask: LayerBuilder) -> Self {
+        self.masks.push(mask);
+        self
+    }
+
+    /// Contents of the node's pixel data entry: the [data](Self::data) if it was set,
+    /// the tiles otherwise.
+    pub fn pixel_data(&self) -> Vec<u8> {
+        match &self.data {
+            Some(data) => data.clone(),
+            None => tile_data(&self.tiles, self.default_pixel.as_deref()),
+        }
+    }
+
+    // Filename of the node that got the number
+    fn filename_for(&self, id: u128) -> String {
+        match &self.filename {
+            Some(filename) => filename.clone(),
+            None => format!("{}{}", self.element, id),
+        }
+    }
+
+    // Appends the element of the node, assigning UUIDs and filenames from `next_id`
+    fn write_xml(&self, out: &mut String, next_id: &mut u128) {
+        let id = *next_id;
+        *next_id += 1;
+        let uuid = self.uuid.unwrap_or(Uuid::from_u128(id));
+        out.push_str(&format!(
+            r#"<{} name="{}" uuid="{{{}}}" filename="{}""#,
+            self.element,
+            escape(&self.name),
+            uuid,
+            escape(&self.filename_for(id))
+        ));
+        for (key, value) in &self.attributes {
+            out.push_str(&format!(r#" {}="{}""#, key, escape(value)));
+        }
+        if self.elements.is_empty() && self.children.is_none() && self.masks.is_empty() {
+            out.push_str("/>\n");
+            return;
+        }
+        out.push_str(">\n");
+        for element in &self.elements {
+            out.push_str(element);
+            out.push('\n');
+        }
+        if let Some(children) = &self.children {
+            out.push_str("<layers>\n");
+            for child in children {
+                child.write_xml(out, next_id);
+            }
+            out.push_str("</layers>\n");
+        }
+        if !self.masks.is_empty() {
+            out.push_str("<masks>\n");
+            for mask in &self.masks {
+                mask.write_xml(out, next_id);
+            }
+            out.push_str("</masks>\n");
+        }
+        out.push_str(&format!("</{}>\n", self.element));
+    }
+
+    // Adds entries of pixel data, numbering nodes the same way as write_xml()
+    fn data_entries(
+        &self,
+        image_name: &str,
+        next_id: &mut u128,
+        entries: &mut Vec<(String, Vec<u8>)>,
+    ) {
+        let id = *next_id;
+        *next_id += 1;
+        let paint = self
+            .attributes
+            .iter()
+            .any(|attribute| attribute == &("nodetype".to_owned(), "paintlayer".to_owned()));
+        let has_data =
+            !self.tiles.is_empty() || self.default_pixel.is_some() || self.data.is_some();
+        if paint || has_data {
+            let selection = self
+                .attributes
+                .iter()
+                .any(|attribute| attribute == &("nodetype".to_owned(), "selectionmask".to_owned()));
+            let path = format!(
+                "{}/layers/{}{}",
+                image_name,
+                self.filename_for(id),
+                if selection { ".pixelselection" } else { "" }
+            );
+            entries.push((path.clone(), self.pixel_data()));
+            if let Some(pixel) = &self.default_pixel {
+                entries.push((format!("{}.defaultpixel", path), pixel.clone()));
+            }
+        }
+        for child in self.children.iter().flatten().chain(&self.masks) {
+            child.data_entries(image_name, next_id, entries);
+        }
+    }
+}
+
+/// Difference between the image [composited](crate::KraFile::composite) by this crate
+/// and `mergedimage.png` saved by Krita, see [`compare_render()`].
+#[cfg(feature = "render")]
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct RenderComparison {
+    width: u32,
+    height: u32,
+    // Largest difference of a channel, for every pixel
+    deltas: Vec<u8>,
+    tolerance: u8,
+}
+
+#[cfg(feature = "render")]
+impl RenderComparison {
+    /// Width of the image.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the image.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Largest difference of a channel of the pixel, from 0 to 255. Colors of pixels
+    /// that are transparent in both images are not compared.
+    pub fn delta(&self, x: u32, y: u32) -> u8 {
+        self.deltas[(y * self.width + x) as usize]
+    }
+
+    /// Largest difference of any pixel.
+    pub fn max_delta(&self) -> u8 {
+        self.deltas.iter().copied().max().unwrap_or(0)
+    }
+
+    /// Largest difference that is allowed.
+    pub fn tolerance(&self) -> u8 {
+        self.tolerance
+    }
+
+    /// Pixels whose difference is larger than the tolerance, as `(x, y, delta)`,
+    /// row by row.
+    pub fn mismatches(&self) -> Vec<(u32, u32, u8)> {
+        self.deltas
+            .iter()
+            .enumerate()
+            .filter(|(_, delta)| **delta > self.tolerance)
+            .map(|(index, delta)| {
+                let index = index as u32;
+                (index % self.width, index / self.width, *delta)
+            })
+            .collect()
+    }
+
+    /// Whether no pixel differs by more than the tolerance.
+    pub fn matches(&self) -> bool {
+        self.max_delta() <= self.tolerance
+    }
+
+    /// Differences as a grayscale image, brighter where they are larger.
+    pub fn delta_image(&self) -> image::GrayImage {
+        image::GrayImage::from_raw(self.width, self.height, self.deltas.clone())
+            .expect("there is a difference for every pixel")
+    }
+}
+
+/// Composite the document and compare the result with its `mergedimage.png`, allowing
+/// channels to differ by up to `tolerance`, to check the compositor against Krita.
+///
+/// Pixels that `mergedimage.png` does not have, if it is smaller than the image,
+/// differ by 255. Documents without `mergedimage.png` give
+/// [`DataError::Entry`](crate::error::DataError::Entry) with [`ZipError::FileNotFound`],
+/// and ones that cannot be composited give
+/// [`DataError::CannotComposite`](crate::error::DataError::CannotComposite).
+///
+/// [`ZipError::FileNotFound`]: zip::result::ZipError::FileNotFound
+#[cfg(feature = "render")]
+pub fn compare_render(
+    file: &mut crate::KraFile,
+    tolerance: u8,
+) -> Result<RenderComparison, crate::error::DataError> {
+    // Not merged_image(), which composites documents without one
+    let png = file.read_entry("mergedimage.png")?;
+    let stored = image::load_from_memory_with_format(&png, image::ImageFormat::Png)?.to_rgba8();
+    let composited = file.composite()?;
+    let deltas = composited
+        .enumerate_pixels()
+        .map(|(x, y, ours)| {
+            let Some(theirs) = stored.get_pixel_checked(x, y) else {
+                return u8::MAX;
+            };
+            let channels = match (ours[3], theirs[3]) {
+                (0, 0) => 0,
+                _ => 4,
+            };
+            (0..channels)
+                .map(|channel| ours[channel].abs_diff(theirs[channel]))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    Ok(RenderComparison {
+        width: composited.width(),
+        height: composited.height(),
+        deltas,
+        tolerance,
+    })
+}
+
+// Tiled pixel data. The pixel size is the one of the tiles, or the default pixel, or 4.
+fn tile_data(tiles: &[Tile], default_pixel: Option<&[u8]>) -> Vec<u8> {
+    let pixel_count = (TILE_SIZE * TILE_SIZE) as usize;
+    let pixel_size = match tiles.first() {
+        Some(Tile::Filled(_, _, pixel)) => pixel.len(),
+        Some(Tile::Pixels(_, _, pixels)) => pixels.len() / pixel_count,
+        None => default_pixel.map_or(4, <[u8]>::len),
+    };
+    let mut data = format!(
+        "VERSION 2\nTILEWIDTH {0}\nTILEHEIGHT {0}\nPIXELSIZE {1}\nDATA {2}\n",
+        TILE_SIZE,
+        pixel_size,
+        tiles.len()
+    )
+    .into_bytes();
+    for tile in tiles {
+        let (x, y, stored) = match tile {
+            // Uncompressed data flag, then the pixels
+            Tile::Filled(x, y, pixel) => (x, y, [&[0][..], &pixel.repeat(pixel_count)].concat()),
+            Tile::Pixels(x, y, pixels) => {
+                let compressed = lzf_compress(&linearize(pixels, pixel_size));
+                (x, y, [&[COMPRESSED_DATA_FLAG][..], &compressed].concat())
+            }
+        };
+        data.extend(format!("{},{},LZF,{}\n", x, y, stored.len()).into_bytes());
+        data.extend(stored);
+    }
+    data
+}
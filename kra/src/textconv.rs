@@ -0,0 +1,141 @@
+// Text form of documents for `git diff`.
+
+use std::collections::HashMap;
+
+use crate::{entry_error, error::DataError, layer::Node, KraFile};
+
+impl KraFile {
+    /// Deterministic, line-oriented description of the document, for use as a textconv
+    /// driver of `git diff`.
+    ///
+    /// It lists metadata, every node with all of its attributes (sorted), and entries of
+    /// the archive with their sizes, checksums and [display paths](Self::display_paths)
+    /// of their nodes, so changes of pixel data show up as well.
+    /// Fields that change on every save (editing time, number of saves, date) are left out,
+    /// and so are `maindoc.xml` and `documentinfo.xml` from the list of entries.
+    /// Entries are only listed if the archive was kept, and for entries changed in memory
+    /// only their new size is known.
+    ///
+    /// To use it, wrap it into a program that prints the description of the file given
+    /// as the argument (see `examples/textconv.rs`), and configure git:
+    ///
+    /// ```text
+    /// # .gitattributes
+    /// *.kra diff=kra
+    ///
+    /// # .git/config
+    /// [diff "kra"]
+    ///     textconv = kra-textconv
+    /// ```
+    pub fn textconv(&mut self) -> Result<String, DataError> {
+        let mut out = String::new();
+        let meta = &self.meta;
+        let about = self.doc_info.about();
+        let author = self.doc_info.author();
+        let lines = [
+            ("document", None),
+            ("name", Some(meta.name())),
+            ("description", Some(meta.description())),
+            ("size", Some(&format!("{}x{}", meta.width(), meta.height()))),
+            (
+                "resolution",
+                Some(&format!("{}x{}", meta.x_res(), meta.y_res())),
+            ),
+            ("colorspace", Some(meta.colorspace_raw())),
+            ("profile", Some(meta.profile())),
+            ("krita_version", Some(meta.krita_version())),
+            ("info", None),
+            ("title", Some(about.title())),
+            ("description", Some(about.description())),
+            ("subject", Some(about.subject())),
+            ("keyword", Some(about.keyword())),
+            ("initial_creator", Some(about.initial_creator())),
+            ("creation_date", Some(about.creation_date())),
+            ("language", Some(about.language())),
+            ("license", Some(about.license())),
+            ("author", Some(author.full_name())),
+            ("company", Some(author.company())),
+        ];
+        for (key, value) in lines {
+            match value {
+                None => out.push_str(&format!("{}\n", key)),
+                Some(value) => out.push_str(&format!("  {}: {:?}\n", key, value)),
+            }
+        }
+
+        out.push_str("nodes\n");
+        for node in &self.layers {
+            write_node(node, 1, &mut out);
+        }
+
+        // Paths of the nodes that entries belong to, which tell apart nodes with the same name
+        let paths = self.display_paths();
+        let owners: HashMap<String, String> = self
+            .all_nodes()
+            .into_iter()
+            .flat_map(|node| {
+                let entries = self.node_entries.get(&node.uuid()).into_iter().flatten();
+                let path = &paths[&node.uuid()];
+                entries.map(|entry| (entry.clone(), path.clone()))
+            })
+            .collect();
+        let Some(zip) = self.file.as_mut() else {
+            return Ok(out);
+        };
+        let mut entries = Vec::with_capacity(zip.entry_names().len());
+        for name in zip.entry_names().to_vec() {
+            // Described above, and saved anew every time
+            if matches!(name.as_str(), "maindoc.xml" | "documentinfo.xml") {
+                continue;
+            }
+            let description = match self.pending_entries.get(&name) {
+                Some(data) => format!("size={} changed", data.len()),
+                None => {
+                    let error = |err: zip::result::ZipError| entry_error(&name, err.into());
+                    let size = zip.by_name(&name).map_err(error)?.size();
+                    let crc32 = zip.crc32(&name).map_err(error)?;
+                    format!("size={} crc32={:08x}", size, crc32)
+                }
+            };
+            entries.push((name, description));
+        }
+        for (name, data) in &self.pending_entries {
+            if !entries.iter().any(|(entry, _)| entry == name) {
+                entries.push((name.clone(), format!("size={} new", data.len())));
+            }
+        }
+        entries.sort();
+
+        out.push_str("entries\n");
+        for (name, description) in entries {
+            out.push_str(&format!("  {} {}", name, description));
+            if let Some(node) = owners.get(&name) {
+                out.push_str(&format!(" node={:?}", node));
+            }
+            out.push('\n');
+        }
+        Ok(out)
+    }
+}
+
+fn write_node(node: &Node, depth: usize, out: &mut String) {
+    let mut attributes: Vec<&(String, String)> = node
+        .attributes()
+        .iter()
+        .filter(|(key, _)| key != "name" && key != "nodetype")
+        .collect();
+    attributes.sort();
+    out.push_str(&format!(
+        "{}{} {:?}",
+        "  ".repeat(depth),
+        node.kind(),
+        node.name()
+    ));
+    for (key, value) in attributes {
+        out.push_str(&format!(" {}={:?}", key, value));
+    }
+    out.push('\n');
+    for child in node.children() {
+        write_node(child, depth + 1, out);
+    }
+}
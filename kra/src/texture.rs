@@ -0,0 +1,130 @@
+//! Layout of decoded layers for uploading them to the GPU as they are.
+
+use std::{
+    borrow::Cow,
+    fmt::{self, Display},
+};
+
+use crate::{
+    color::{ChannelDepth, ColorModel},
+    data::{LayerData, PixelBuffer},
+};
+
+/// Format of texels, named like the variants of `wgpu::TextureFormat`.
+///
+/// Grayscale layers use two-channel formats, with gray in the first channel and
+/// alpha in the second one.
+#[allow(missing_docs)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[non_exhaustive]
+pub enum TextureFormat {
+    Bgra8Unorm,
+    Rgba16Unorm,
+    Rgba16Float,
+    Rgba32Float,
+    Rg8Unorm,
+    Rg16Unorm,
+    Rg16Float,
+    Rg32Float,
+}
+
+impl TextureFormat {
+    /// Size of one texel, in bytes.
+    pub fn texel_size(&self) -> usize {
+        match self {
+            TextureFormat::Rg8Unorm => 2,
+            TextureFormat::Bgra8Unorm | TextureFormat::Rg16Unorm | TextureFormat::Rg16Float => 4,
+            TextureFormat::Rgba16Unorm | TextureFormat::Rgba16Float | TextureFormat::Rg32Float => 8,
+            TextureFormat::Rgba32Float => 16,
+        }
+    }
+}
+
+// Same as the name of the variant, which is also the wgpu one
+impl Display for TextureFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Pixels of a layer, ready to be copied into a texture, see
+/// [`LayerData::as_texture_desc()`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct TextureDesc<'a> {
+    /// Format of the texels.
+    pub(crate) format: TextureFormat,
+    /// Width, in texels.
+    pub(crate) width: u32,
+    /// Height, in texels.
+    pub(crate) height: u32,
+    /// Texels, row by row, with no padding between rows.
+    pub(crate) data: Cow<'a, [u8]>,
+}
+
+impl TextureDesc<'_> {
+    /// Format of the texels.
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    /// Width, in texels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height, in texels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Bytes from the start of one row to the start of the next one.
+    ///
+    /// wgpu needs it to be a multiple of 256 when copying from a buffer, but not when
+    /// writing a texture from memory with `Queue::write_texture()`.
+    pub fn bytes_per_row(&self) -> u32 {
+        self.width * self.format.texel_size() as u32
+    }
+
+    /// Texels, row by row, with no padding between rows. Values wider than a byte are
+    /// little-endian.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl LayerData {
+    /// The pixels in a layout that GPUs take as it is, or `None` if there is no such format
+    /// for the colorspace. Only RGB and grayscale colorspaces are supported.
+    ///
+    /// 8-bit pixels are borrowed. Others are copied into bytes, and 16-bit RGB pixels are
+    /// reordered from BGRA into RGBA, as there is no 16-bit BGRA format. Alpha mode is that of
+    /// the layer data, see [`LayerData::into_alpha_mode()`].
+    pub fn as_texture_desc(&self) -> Option<TextureDesc<'_>> {
+        let format = match (self.colorspace.model(), self.colorspace.depth()) {
+            (ColorModel::Rgb, ChannelDepth::U8) => TextureFormat::Bgra8Unorm,
+            (ColorModel::Rgb, ChannelDepth::U16) => TextureFormat::Rgba16Unorm,
+            (ColorModel::Rgb, ChannelDepth::F16) => TextureFormat::Rgba16Float,
+            (ColorModel::Rgb, ChannelDepth::F32) => TextureFormat::Rgba32Float,
+            (ColorModel::Gray, ChannelDepth::U8) => TextureFormat::Rg8Unorm,
+            (ColorModel::Gray, ChannelDepth::U16) => TextureFormat::Rg16Unorm,
+            (ColorModel::Gray, ChannelDepth::F16) => TextureFormat::Rg16Float,
+            (ColorModel::Gray, ChannelDepth::F32) => TextureFormat::Rg32Float,
+            _ => return None,
+        };
+        let data = match &self.pixels {
+            PixelBuffer::U8(values) => Cow::Borrowed(values.as_slice()),
+            PixelBuffer::U16(values) if format == TextureFormat::Rgba16Unorm => values
+                .chunks_exact(4)
+                .flat_map(|pixel| [pixel[2], pixel[1], pixel[0], pixel[3]])
+                .flat_map(u16::to_le_bytes)
+                .collect(),
+            other => Cow::Owned(other.to_le_bytes()),
+        };
+        Some(TextureDesc {
+            format,
+            width: self.width,
+            height: self.height,
+            data,
+        })
+    }
+}
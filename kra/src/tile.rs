@@ -0,0 +1,597 @@
+//! Krita's tiled pixel data format.
+//!
+//! Pixel data of paint layers and masks is an entry that starts with a header of
+//! `KEY value` lines (`VERSION`, `TILEWIDTH`, `TILEHEIGHT`, `PIXELSIZE` and `DATA`, the number
+//! of tiles). Each tile follows as an `x,y,LZF,size` line and `size` bytes: a flag, then
+//! pixels, [compressed](COMPRESSED_DATA_FLAG) or as they are. Pixels of a tile are
+//! [planar](linearize): first bytes of all pixels, then second bytes, and so on.
+//!
+//! Constants and helpers here are for code that reads or writes such entries by itself.
+
+use std::io::{self, BufRead, Read};
+
+use crate::{
+    error::{DataError, LimitError},
+    geom::{IPoint, IRect},
+    ChannelDepth, ColorModel, Colorspace,
+};
+
+/// Width and height of the tiles that Krita writes, in pixels.
+pub const TILE_SIZE: u32 = 64;
+
+/// Version of the format, the only one that is supported.
+pub const TILE_FORMAT_VERSION: u32 = 2;
+
+/// First line of the header, which every entry of tiled data starts with.
+pub const HEADER_MAGIC: &[u8] = b"VERSION 2\n";
+
+/// Compression named in the line of every tile.
+pub const COMPRESSION: &str = "LZF";
+
+/// Positions of red, green, blue and alpha in pixels of integer RGB colorspaces, which are
+/// stored as BGRA (see [`stores_bgra()`]).
+pub const BGRA_CHANNEL_ORDER: [usize; 4] = [2, 1, 0, 3];
+
+/// Whether Krita stores pixels of the colorspace with blue first: integer RGB colorspaces
+/// are stored as BGRA, floating point ones as RGBA.
+pub fn stores_bgra(colorspace: Colorspace) -> bool {
+    colorspace.model() == ColorModel::Rgb
+        && matches!(colorspace.depth(), ChannelDepth::U8 | ChannelDepth::U16)
+}
+
+/// Column and row of the tile that has the pixel, in a grid of [`TILE_SIZE`] tiles
+/// with a tile at (0, 0).
+pub fn tile_coords(pixel: IPoint) -> IPoint {
+    let size = TILE_SIZE as i32;
+    IPoint::new(pixel.x().div_euclid(size), pixel.y().div_euclid(size))
+}
+
+/// Top left pixel of the tile that has the pixel, which is where tiles are placed.
+pub fn tile_origin(pixel: IPoint) -> IPoint {
+    let size = TILE_SIZE as i32;
+    let coords = tile_coords(pixel);
+    IPoint::new(coords.x() * size, coords.y() * size)
+}
+
+/// Origins of the tiles that cover the rectangle, row by row. None if it is empty.
+pub fn tiles_covering(rect: IRect) -> impl Iterator<Item = IPoint> {
+    let size = TILE_SIZE as i32;
+    let (first, last) = match rect.is_empty() {
+        true => (IPoint::new(0, 0), IPoint::new(-1, -1)),
+        false => (
+            tile_coords(rect.origin()),
+            tile_coords(IPoint::new(rect.right() - 1, rect.bottom() - 1)),
+        ),
+    };
+    (first.y()..=last.y()).flat_map(move |row| {
+        (first.x()..=last.x()).map(move |column| IPoint::new(column * size, row * size))
+    })
+}
+
+/// Header of a tiled pixel data entry (such as `layers/layer2`).
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub(crate) struct TileHeader {
+    pub(crate) tile_width: u32,
+    pub(crate) tile_height: u32,
+    pub(crate) pixel_size: u32,
+    pub(crate) tile_count: u32,
+}
+
+impl TileHeader {
+    // Reads the header, leaving the reader immed. before the first tile
+    pub(crate) fn read(reader: &mut impl BufRead) -> Result<Self, DataError> {
+        let version = read_header_value(reader, "VERSION")?;
+        if version != TILE_FORMAT_VERSION {
+            return Err(DataError::MalformedTiles(
+                "tile format version 2",
+                version.to_string(),
+            ));
+        }
+        let tile_width = read_header_value(reader, "TILEWIDTH")?;
+        let tile_height = read_header_value(reader, "TILEHEIGHT")?;
+        let pixel_size = read_header_value(reader, "PIXELSIZE")?;
+        let tile_count = read_header_value(reader, "DATA")?;
+        if tile_width == 0 || tile_height == 0 || pixel_size == 0 {
+            return Err(DataError::MalformedTiles(
+                "non-zero tile and pixel sizes",
+                format!("{}x{}, {}", tile_width, tile_height, pixel_size),
+            ));
+        }
+        buffer_len(tile_width as u64, tile_height as u64, pixel_size as usize)?;
+        Ok(TileHeader {
+            tile_width,
+            tile_height,
+            pixel_size,
+            tile_count,
+        })
+    }
+
+    // Size of decompressed tile, in bytes
+    pub(crate) fn tile_size(&self) -> usize {
+        self.tile_width as usize * self.tile_height as usize * self.pixel_size as usize
+    }
+}
+
+// Length of a buffer of `width` by `height` pixels, which fails instead of overflowing
+// or going past what a `Vec` can hold
+pub(crate) fn buffer_len(width: u64, height: u64, pixel_size: usize) -> Result<usize, DataError> {
+    width
+        .checked_mul(height)
+        .and_then(|count| count.checked_mul(pixel_size as u64))
+        .and_then(|len| usize::try_from(len).ok())
+        .filter(|&len| len <= isize::MAX as usize)
+        .ok_or(DataError::TooLarge(width, height))
+}
+
+// Reads a line of the form `KEY value`
+fn read_header_value(reader: &mut impl BufRead, key: &'static str) -> Result<u32, DataError> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    line.trim_end()
+        .strip_prefix(key)
+        .and_then(|value| value.strip_prefix(' '))
+        .and_then(|value| value.parse().ok())
+        .ok_or(DataError::MalformedTiles(key, line.trim_end().to_owned()))
+}
+
+/// Flag that precedes compressed data of a tile. After any other byte, pixels are stored
+/// as they are.
+pub const COMPRESSED_DATA_FLAG: u8 = 1;
+
+/// One decoded tile.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub(crate) struct Tile {
+    // Position of the top left pixel, relative to the node's offset
+    pub(crate) x: i32,
+    pub(crate) y: i32,
+    // Interleaved pixels, row by row
+    pub(crate) data: Vec<u8>,
+}
+
+/// Decoded pixel data of a node.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub(crate) struct TiledData {
+    pub(crate) header: TileHeader,
+    pub(crate) tiles: Vec<Tile>,
+    // Pixel that is used where there are no tiles
+    pub(crate) default_pixel: Option<Vec<u8>>,
+}
+
+impl TiledData {
+    // Reads the header and all tiles
+    pub(crate) fn read(reader: &mut impl BufRead) -> Result<Self, DataError> {
+        let header = TileHeader::read(reader)?;
+        let tiles = (0..header.tile_count)
+            .map(|_| Tile::read(reader, &header))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(TiledData {
+            header,
+            tiles,
+            default_pixel: None,
+        })
+    }
+
+    // Encodes the header and all tiles. The default pixel is stored separately.
+    pub(crate) fn write(&self) -> Vec<u8> {
+        let header = &self.header;
+        let mut out = format!(
+            "VERSION {}\nTILEWIDTH {}\nTILEHEIGHT {}\nPIXELSIZE {}\nDATA {}\n",
+            TILE_FORMAT_VERSION,
+            header.tile_width,
+            header.tile_height,
+            header.pixel_size,
+            self.tiles.len()
+        )
+        .into_bytes();
+        for tile in &self.tiles {
+            tile.write(&mut out, header);
+        }
+        out
+    }
+
+    // Splits pixels into tiles of the header's size. Pixels are at (left, top), which
+    // must be at a tile boundary, and the size must be a multiple of the tile size.
+    // Tiles that only have the default pixel are left out.
+    pub(crate) fn from_dense(
+        header: TileHeader,
+        (left, top, width): (i32, i32, u32),
+        pixels: &[u8],
+        default_pixel: &[u8],
+    ) -> Self {
+        let (tile_width, tile_height) = (header.tile_width as usize, header.tile_height as usize);
+        let pixel_size = header.pixel_size as usize;
+        let row_size = width as usize * pixel_size;
+        let height = pixels.len() / row_size.max(1);
+        let mut tiles = Vec::new();
+        for tile_y in (0..height).step_by(tile_height) {
+            for tile_x in (0..width as usize).step_by(tile_width) {
+                let mut data = Vec::with_capacity(header.tile_size());
+                for row in tile_y..tile_y + tile_height {
+                    let start = row * row_size + tile_x * pixel_size;
+                    data.extend_from_slice(&pixels[start..start + tile_width * pixel_size]);
+                }
+                if data
+                    .chunks_exact(pixel_size)
+                    .all(|pixel| pixel == default_pixel)
+                {
+                    continue;
+                }
+                tiles.push(Tile {
+                    x: left + tile_x as i32,
+                    y: top + tile_y as i32,
+                    data,
+                });
+            }
+        }
+        TiledData {
+            header: TileHeader {
+                tile_count: tiles.len() as u32,
+                ..header
+            },
+            tiles,
+            default_pixel: Some(default_pixel.to_vec()),
+        }
+    }
+
+    // Pixels of all tiles in one buffer, along with its position and size.
+    // Returns (left, top, width, height, pixels). Fails if the tiles are spread over
+    // more than `max_pixels`, or over more than can be addressed.
+    pub(crate) fn to_dense(
+        &self,
+        max_pixels: u64,
+    ) -> Result<(i32, i32, u32, u32, Vec<u8>), DataError> {
+        let (tile_width, tile_height) = (
+            self.header.tile_width as i64,
+            self.header.tile_height as i64,
+        );
+        let Some(left) = self.tiles.iter().map(|tile| tile.x).min() else {
+            return Ok((0, 0, 0, 0, Vec::new()));
+        };
+        let top = self.tiles.iter().map(|tile| tile.y).min().unwrap_or(0);
+        let right = self
+            .tiles
+            .iter()
+            .map(|tile| tile.x as i64 + tile_width)
+            .max()
+            .unwrap_or(0);
+        let bottom = self
+            .tiles
+            .iter()
+            .map(|tile| tile.y as i64 + tile_height)
+            .max()
+            .unwrap_or(0);
+        let (width, height) = ((right - left as i64) as u64, (bottom - top as i64) as u64);
+        if width.saturating_mul(height) > max_pixels {
+            return Err(LimitError::Pixels(max_pixels).into());
+        }
+        if width > u32::MAX as u64 || height > u32::MAX as u64 {
+            return Err(DataError::TooLarge(width, height));
+        }
+
+        let pixel_size = self.header.pixel_size as usize;
+        let default_pixel = match &self.default_pixel {
+            Some(pixel) if pixel.len() == pixel_size => pixel.clone(),
+            _ => vec![0; pixel_size],
+        };
+        buffer_len(width, height, pixel_size)?;
+        let (width, height) = (width as usize, height as usize);
+        let mut pixels = default_pixel.repeat(width * height);
+
+        let row_size = tile_width as usize * pixel_size;
+        for tile in &self.tiles {
+            let (x, y) = (
+                (tile.x as i64 - left as i64) as usize,
+                (tile.y as i64 - top as i64) as usize,
+            );
+            for (row, data) in tile.data.chunks_exact(row_size).enumerate() {
+                let start = ((y + row) * width + x) * pixel_size;
+                pixels[start..start + row_size].copy_from_slice(data);
+            }
+        }
+        Ok((left, top, width as u32, height as u32, pixels))
+    }
+
+    // Bounds of pixels that are not transparent
+    pub(crate) fn exact_bounds(&self, is_transparent: impl Fn(&[u8]) -> bool) -> Option<IRect> {
+        let pixel_size = self.header.pixel_size as usize;
+        let width = self.header.tile_width as usize;
+        let mut bounds: Option<(i32, i32, i32, i32)> = None;
+        for tile in &self.tiles {
+            for (index, pixel) in tile.data.chunks_exact(pixel_size).enumerate() {
+                if is_transparent(pixel) {
+                    continue;
+                }
+                let x = tile.x + (index % width) as i32;
+                let y = tile.y + (index / width) as i32;
+                bounds = Some(match bounds {
+                    None => (x, y, x + 1, y + 1),
+                    Some((left, top, right, bottom)) => {
+                        (left.min(x), top.min(y), right.max(x + 1), bottom.max(y + 1))
+                    }
+                });
+            }
+        }
+        bounds.map(|(left, top, right, bottom)| IRect::from_edges(left, top, right, bottom))
+    }
+}
+
+impl Tile {
+    // Writes the tile, compressed if that makes it smaller
+    fn write(&self, out: &mut Vec<u8>, header: &TileHeader) {
+        let compressed = lzf_compress(&linearize(&self.data, header.pixel_size as usize));
+        let (flag, data) = match compressed.len() < self.data.len() {
+            true => (COMPRESSED_DATA_FLAG, compressed.as_slice()),
+            false => (0, self.data.as_slice()),
+        };
+        out.extend(
+            format!("{},{},{},{}\n", self.x, self.y, COMPRESSION, data.len() + 1).into_bytes(),
+        );
+        out.push(flag);
+        out.extend_from_slice(data);
+    }
+
+    // Reads one tile, starting at its `x,y,LZF,size` line
+    fn read(reader: &mut impl BufRead, header: &TileHeader) -> Result<Self, DataError> {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let malformed =
+            || DataError::MalformedTiles("tile description", line.trim_end().to_owned());
+
+        let mut parts = line.trim_end().split(',');
+        let x: i32 = parts
+            .next()
+            .and_then(|x| x.parse().ok())
+            .ok_or_else(malformed)?;
+        let y: i32 = parts
+            .next()
+            .and_then(|y| y.parse().ok())
+            .ok_or_else(malformed)?;
+        if parts.next() != Some(COMPRESSION) {
+            return Err(malformed());
+        }
+        let size: usize = parts
+            .next()
+            .and_then(|size| size.parse().ok())
+            .ok_or_else(malformed)?;
+        // Pixel coordinates of the whole tile must fit, so that they never overflow later
+        if x as i64 + header.tile_width as i64 > i32::MAX as i64
+            || y as i64 + header.tile_height as i64 > i32::MAX as i64
+        {
+            return Err(malformed());
+        }
+
+        // Even incompressible data only grows by one byte per 32 bytes,
+        // so anything larger is not worth allocating
+        let tile_size = header.tile_size();
+        if size > tile_size + tile_size / 32 + 2 {
+            return Err(DataError::MalformedTiles(
+                "tile no larger than its worst-case compression",
+                size.to_string(),
+            ));
+        }
+        // The size is not trusted with an allocation before the data is there
+        let mut compressed = Vec::new();
+        reader.take(size as u64).read_to_end(&mut compressed)?;
+        if compressed.len() != size {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+
+        let data = match compressed.split_first() {
+            Some((&COMPRESSED_DATA_FLAG, rest)) => {
+                let linear = lzf_decompress(rest, tile_size)?;
+                delinearize(&linear, header.pixel_size as usize)
+            }
+            Some((_, rest)) if rest.len() == tile_size => rest.to_vec(),
+            _ => {
+                return Err(DataError::MalformedTiles(
+                    "uncompressed tile of correct size",
+                    size.to_string(),
+                ))
+            }
+        };
+        Ok(Tile { x, y, data })
+    }
+}
+
+/// Decompress data compressed by liblzf, which Krita uses for tiles.
+/// Output must be exactly `len` bytes long.
+pub fn lzf_decompress(input: &[u8], len: usize) -> Result<Vec<u8>, DataError> {
+    let malformed =
+        || DataError::MalformedTiles("valid LZF data", format!("{} bytes", input.len()));
+    // Every byte of input gives at most 132 bytes of output
+    let mut output: Vec<u8> = Vec::with_capacity(len.min(input.len().saturating_mul(132)));
+    let mut input = input.iter().copied();
+
+    while let Some(ctrl) = input.next() {
+        if ctrl < 32 {
+            // Literal run
+            for _ in 0..=ctrl {
+                output.push(input.next().ok_or_else(malformed)?);
+            }
+        } else {
+            // Back reference
+            let mut run = (ctrl >> 5) as usize;
+            if run == 7 {
+                run += input.next().ok_or_else(malformed)? as usize;
+            }
+            let offset =
+                (((ctrl & 0x1f) as usize) << 8) + input.next().ok_or_else(malformed)? as usize + 1;
+            let start = output.len().checked_sub(offset).ok_or_else(malformed)?;
+            // Source and destination may overlap
+            for index in start..start + run + 2 {
+                output.push(output[index]);
+            }
+        }
+        if output.len() > len {
+            return Err(malformed());
+        }
+    }
+
+    if output.len() != len {
+        return Err(malformed());
+    }
+    Ok(output)
+}
+
+/// Compress data in the format of liblzf. Repeats are found through a hash of 3 bytes,
+/// which is simpler than what liblzf does, but the output is compatible.
+pub fn lzf_compress(input: &[u8]) -> Vec<u8> {
+    const HASH_BITS: u32 = 14;
+    const MAX_OFFSET: usize = 1 << 13;
+    const MAX_LITERAL: usize = 32;
+    const MAX_MATCH: usize = 7 + 255 + 2;
+
+    fn flush_literals(output: &mut Vec<u8>, literals: &[u8]) {
+        for run in literals.chunks(MAX_LITERAL) {
+            output.push(run.len() as u8 - 1);
+            output.extend_from_slice(run);
+        }
+    }
+
+    let mut output = Vec::with_capacity(input.len() / 2);
+    let mut table = vec![usize::MAX; 1 << HASH_BITS];
+    let (mut index, mut literal_start) = (0, 0);
+    while index + 2 < input.len() {
+        let key = u32::from_le_bytes([input[index], input[index + 1], input[index + 2], 0]);
+        let hash = (key.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize;
+        let candidate = std::mem::replace(&mut table[hash], index);
+        if candidate == usize::MAX
+            || index - candidate > MAX_OFFSET
+            || input[candidate..candidate + 3] != input[index..index + 3]
+        {
+            index += 1;
+            continue;
+        }
+
+        let max_len = MAX_MATCH.min(input.len() - index);
+        let mut len = 3;
+        while len < max_len && input[candidate + len] == input[index + len] {
+            len += 1;
+        }
+        flush_literals(&mut output, &input[literal_start..index]);
+        let offset = index - candidate - 1;
+        let run = len - 2;
+        if run < 7 {
+            output.push(((run as u8) << 5) | (offset >> 8) as u8);
+        } else {
+            output.push((7 << 5) | (offset >> 8) as u8);
+            output.push((run - 7) as u8);
+        }
+        output.push(offset as u8);
+        index += len;
+        literal_start = index;
+    }
+    flush_literals(&mut output, &input[literal_start..]);
+    output
+}
+
+/// Groups n-th bytes of all pixels together, as Krita does before compression.
+///
+/// # Panics
+///
+/// If `pixel_size` is 0, or the length of `data` is not a multiple of it.
+pub fn linearize(data: &[u8], pixel_size: usize) -> Vec<u8> {
+    assert_whole_pixels(data, pixel_size);
+    (0..pixel_size)
+        .flat_map(|byte| data.iter().skip(byte).step_by(pixel_size).copied())
+        .collect()
+}
+
+/// Undoes [`linearize()`]: interleaves bytes of pixels again, after decompression.
+///
+/// # Panics
+///
+/// If `pixel_size` is 0, or the length of `linear` is not a multiple of it.
+pub fn delinearize(linear: &[u8], pixel_size: usize) -> Vec<u8> {
+    assert_whole_pixels(linear, pixel_size);
+    let pixel_count = linear.len() / pixel_size;
+    if pixel_count == 0 {
+        return Vec::new();
+    }
+    let mut data = vec![0; linear.len()];
+    for (byte, plane) in linear.chunks_exact(pixel_count).enumerate() {
+        for (pixel, value) in plane.iter().enumerate() {
+            data[pixel * pixel_size + byte] = *value;
+        }
+    }
+    data
+}
+
+fn assert_whole_pixels(data: &[u8], pixel_size: usize) {
+    assert!(
+        pixel_size != 0 && data.len().is_multiple_of(pixel_size),
+        "{} bytes are not whole pixels of {} bytes",
+        data.len(),
+        pixel_size
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lzf_literal_and_back_reference() {
+        // "abc", then 6 bytes starting 3 bytes back
+        let compressed = [2, b'a', b'b', b'c', 0b100_00000, 2];
+        assert_eq!(lzf_decompress(&compressed, 9).unwrap(), b"abcabcabc");
+    }
+
+    #[test]
+    fn lzf_rejects_bad_reference() {
+        assert!(lzf_decompress(&[0b001_00000, 5], 3).is_err());
+    }
+
+    #[test]
+    fn lzf_round_trip() {
+        let mut data: Vec<u8> = (0..5000u32).map(|value| (value % 251) as u8).collect();
+        data.extend([7; 1000]);
+        data.extend(b"abcabcabd");
+        let compressed = lzf_compress(&data);
+        assert!(compressed.len() < data.len());
+        assert_eq!(lzf_decompress(&compressed, data.len()).unwrap(), data);
+        assert_eq!(lzf_decompress(&lzf_compress(b"ab"), 2).unwrap(), b"ab");
+    }
+
+    #[test]
+    fn tiles_round_trip() {
+        let header = TileHeader {
+            tile_width: 2,
+            tile_height: 2,
+            pixel_size: 2,
+            tile_count: 0,
+        };
+        // Two tiles side by side, the right one is all default
+        let pixels = [1, 2, 3, 4, 0, 0, 0, 0, 5, 6, 7, 8, 0, 0, 0, 0];
+        let data = TiledData::from_dense(header, (-2, 4, 4), &pixels, &[0, 0]);
+        assert_eq!(data.tiles.len(), 1);
+        assert_eq!((data.tiles[0].x, data.tiles[0].y), (-2, 4));
+
+        let mut read = TiledData::read(&mut data.write().as_slice()).unwrap();
+        read.default_pixel = Some(vec![0, 0]);
+        assert_eq!(read, data);
+    }
+
+    #[test]
+    fn linearize_pixels() {
+        assert_eq!(linearize(&[1, 4, 2, 5, 3, 6], 2), [1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn delinearize_pixels() {
+        assert_eq!(delinearize(&[1, 2, 3, 4, 5, 6], 2), [1, 4, 2, 5, 3, 6]);
+        assert!(delinearize(&[], 4).is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn delinearize_partial_pixel() {
+        delinearize(&[1, 2, 3], 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn linearize_zero_pixel_size() {
+        linearize(&[1, 2], 0);
+    }
+}
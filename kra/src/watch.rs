@@ -0,0 +1,113 @@
+//! Watching documents for changes, for example to reload assets while they are drawn.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher as _};
+
+use crate::{
+    config::ParsingConfiguration,
+    diff::{diff, Change},
+    error::{ReadKraError, WatchError},
+    KraFile,
+};
+
+/// Watches documents until it is dropped, see [`watch()`].
+#[derive(Debug)]
+pub struct Watcher {
+    _inner: RecommendedWatcher,
+}
+
+/// A watched document that was read again after it changed.
+#[derive(Debug)]
+pub struct Update<'a> {
+    file: &'a KraFile,
+    changes: Vec<Change>,
+}
+
+impl Update<'_> {
+    /// The document as it was read now. Its archive is closed, so only metadata
+    /// and the node tree are available.
+    pub fn file(&self) -> &KraFile {
+        self.file
+    }
+
+    /// What changed since the document was read the last time.
+    ///
+    /// Empty if only pixels changed, or if the same save was noticed again.
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+}
+
+/// Watch the documents at `paths`, reading them again each time they change.
+///
+/// The documents are read right away, so they have to exist. After that, `callback` is called
+/// from another thread with the path of the document and either what changed or why it could
+/// not be read, for example while it is still being written. The last successful read is
+/// what the next one is compared with. Errors of watching itself come with the path they are
+/// about, which may be empty.
+///
+/// Directories of the documents are watched, so that documents that are saved by replacing
+/// them, as Krita does, are still followed.
+pub fn watch<P, F>(paths: &[P], mut callback: F) -> Result<Watcher, WatchError>
+where
+    P: AsRef<Path>,
+    F: FnMut(&Path, Result<Update<'_>, WatchError>) + Send + 'static,
+{
+    let mut documents = HashMap::new();
+    let mut directories = HashSet::new();
+    for path in paths {
+        // Events name files by absolute paths
+        let path = fs::canonicalize(path).map_err(ReadKraError::from)?;
+        let file = read(&path)?;
+        if let Some(directory) = path.parent() {
+            directories.insert(directory.to_owned());
+        }
+        documents.insert(path, file);
+    }
+
+    let mut inner = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let event = match event {
+            Ok(event) => event,
+            Err(err) => {
+                let path = err.paths.first().cloned().unwrap_or_default();
+                callback(&path, Err(err.into()));
+                return;
+            }
+        };
+        if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+            return;
+        }
+        for path in event.paths {
+            let Some(previous) = documents.get_mut(&path) else {
+                continue;
+            };
+            match read(&path) {
+                Ok(file) => {
+                    let changes = diff(previous, &file);
+                    *previous = file;
+                    let update = Update {
+                        file: previous,
+                        changes,
+                    };
+                    callback(&path, Ok(update));
+                }
+                Err(err) => callback(&path, Err(err)),
+            }
+        }
+    })?;
+    for directory in directories {
+        inner.watch(&directory, RecursiveMode::NonRecursive)?;
+    }
+    Ok(Watcher { _inner: inner })
+}
+
+// Only what can be compared is read
+fn read(path: &Path) -> Result<KraFile, WatchError> {
+    let config = ParsingConfiguration::new().keep_archive(false);
+    Ok(KraFile::read_with_config(path, config)?)
+}
@@ -0,0 +1,153 @@
+//! Options of writing documents back into archives.
+//!
+//! Writing starts from the archive the document was read from, so it has to be kept open
+//! (see [`ParsingConfiguration::keep_archive()`](crate::config::ParsingConfiguration::keep_archive)).
+//! Entries that were not changed are copied.
+
+use crate::metadata::{AuthorProfile, AuthorUpdate};
+
+/// How entries are compressed when written.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+pub enum Compression {
+    /// Copy compressed data of unchanged entries as it is, if they come from a zip archive.
+    /// New entries, and entries of other sources, are deflated.
+    #[default]
+    Keep,
+    /// Do not compress.
+    Stored,
+    /// Deflate with the given level (0-9), or the default one.
+    Deflated(Option<i32>),
+}
+
+/// Options of [`KraFile::write()`](crate::KraFile::write).
+#[derive(Debug, PartialEq, Eq, Clone, Hash, Default)]
+pub struct WriteOptions {
+    pub(crate) compression: Compression,
+    pub(crate) drop_orphaned_entries: bool,
+    pub(crate) author_profile: Option<AuthorProfile>,
+    pub(crate) author_update: AuthorUpdate,
+    pub(crate) update_editing_stats: bool,
+}
+
+impl WriteOptions {
+    /// Default options: compression is kept, and every entry is written.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how entries are compressed. `mimetype` is never compressed, as Krita expects.
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// How entries are compressed.
+    pub fn get_compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Set whether [orphaned entries](crate::KraFile::orphaned_entries) are left out.
+    pub fn drop_orphaned_entries(mut self, drop_orphaned_entries: bool) -> Self {
+        self.drop_orphaned_entries = drop_orphaned_entries;
+        self
+    }
+
+    /// Whether orphaned entries are left out.
+    pub fn get_drop_orphaned_entries(&self) -> bool {
+        self.drop_orphaned_entries
+    }
+
+    /// Set the profile that author fields of `documentinfo.xml` are written from.
+    /// The document itself is not changed.
+    pub fn author_profile(mut self, author_profile: Option<AuthorProfile>) -> Self {
+        self.author_profile = author_profile;
+        self
+    }
+
+    /// Profile that author fields of `documentinfo.xml` are written from.
+    pub fn get_author_profile(&self) -> Option<&AuthorProfile> {
+        self.author_profile.as_ref()
+    }
+
+    /// Set how the author profile changes author fields.
+    pub fn author_update(mut self, author_update: AuthorUpdate) -> Self {
+        self.author_update = author_update;
+        self
+    }
+
+    /// How the author profile changes author fields.
+    pub fn get_author_update(&self) -> AuthorUpdate {
+        self.author_update
+    }
+
+    /// Set whether `documentinfo.xml` is written with one more editing cycle, the time
+    /// since the document was read added to the editing time, and the current date, like
+    /// Krita does when it saves. The document itself is not changed.
+    pub fn update_editing_stats(mut self, update_editing_stats: bool) -> Self {
+        self.update_editing_stats = update_editing_stats;
+        self
+    }
+
+    /// Whether editing statistics of `documentinfo.xml` are updated.
+    pub fn get_update_editing_stats(&self) -> bool {
+        self.update_editing_stats
+    }
+}
+
+/// Options of [`KraFile::optimize()`](crate::KraFile::optimize).
+///
+/// By default, orphaned entries are dropped and everything is deflated with
+/// the best compression.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct OptimizeOptions {
+    pub(crate) write: WriteOptions,
+    pub(crate) preview_size: Option<u32>,
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        OptimizeOptions {
+            write: WriteOptions::new()
+                .compression(Compression::Deflated(Some(9)))
+                .drop_orphaned_entries(true),
+            preview_size: None,
+        }
+    }
+}
+
+impl OptimizeOptions {
+    /// Default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how entries are compressed.
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.write.compression = compression;
+        self
+    }
+
+    /// Set whether orphaned entries are left out.
+    pub fn drop_orphaned_entries(mut self, drop_orphaned_entries: bool) -> Self {
+        self.write.drop_orphaned_entries = drop_orphaned_entries;
+        self
+    }
+
+    /// Set whether `preview.png` is made anew from `mergedimage.png`, and its maximum size.
+    /// Krita uses 256.
+    #[cfg(feature = "image")]
+    pub fn regenerate_preview(mut self, size: Option<u32>) -> Self {
+        self.preview_size = size;
+        self
+    }
+
+    /// Options used for writing.
+    pub fn get_write_options(&self) -> &WriteOptions {
+        &self.write
+    }
+
+    /// Maximum size of the regenerated preview, if it is regenerated.
+    pub fn get_preview_size(&self) -> Option<u32> {
+        self.preview_size
+    }
+}
@@ -0,0 +1,105 @@
+use std::{collections::BTreeMap, fs::File, process};
+
+use kra::{
+    annotation::{AnnotationContent, Artifact, PIPELINE_METADATA},
+    testing::{DocumentBuilder, TempPath},
+    writer::WriteOptions,
+    KraFile,
+};
+
+#[test]
+fn session_annotations() {
+    let session = r#"<?xml version="1.0"?><session><window/></session>"#;
+    let path = DocumentBuilder::new(128, 128)
+        .image_entry("annotations/icc", vec![0, 0, 2, 0x30])
+        .image_entry("annotations/kritasession", session)
+        .write_temp("session_annotations")
+        .unwrap();
+
+    let mut file = KraFile::read(&path).unwrap();
+    let annotations = file.annotations().unwrap();
+    assert_eq!(annotations.len(), 2);
+    assert_eq!(annotations[0].name(), "icc");
+    assert_eq!(
+        annotations[0].content(),
+        &AnnotationContent::Binary(vec![0, 0, 2, 0x30])
+    );
+    assert_eq!(annotations[1].name(), "kritasession");
+    assert_eq!(annotations[1].as_xml(), Some(session));
+}
+
+#[test]
+fn pipeline_metadata_round_trip() {
+    let path = DocumentBuilder::new(128, 128)
+        .write_temp("pipeline_metadata")
+        .unwrap();
+    let mut file = KraFile::read(&path).unwrap();
+    assert!(file.pipeline_metadata().unwrap().is_empty());
+
+    let metadata = BTreeMap::from([
+        ("project".to_owned(), "Tiles & <Props>".to_owned()),
+        ("asset-id".to_owned(), "42".to_owned()),
+        ("license".to_owned(), String::new()),
+    ]);
+    file.set_pipeline_metadata(&metadata).unwrap();
+    assert_eq!(file.pipeline_metadata().unwrap(), metadata);
+
+    let written = TempPath::new(&format!("kra-pipeline-metadata-{}.kra", process::id()));
+    file.write(File::create(&written).unwrap(), &WriteOptions::new())
+        .unwrap();
+    let mut file = KraFile::read(&written).unwrap();
+    assert_eq!(file.pipeline_metadata().unwrap(), metadata);
+    let annotations = file.annotations().unwrap();
+    assert_eq!(annotations[0].name(), PIPELINE_METADATA);
+    assert!(annotations[0].as_xml().is_some());
+}
+
+#[test]
+fn session_artifacts() {
+    let taskset = r#"<!DOCTYPE Taskset>
+<Taskset name="Inking &amp; cleanup" version="1">
+ <action>edit_undo</action>
+ <action> clear </action>
+</Taskset>"#;
+    let recorded = r#"<?xml version="1.0"?>
+<RecordedActions>
+ <RecordedAction id="FreehandPaintAction" name="Freehand"><Node path="/0"/></RecordedAction>
+ <RecordedAction id="FilterAction" name="Blur" filter="blur"/>
+</RecordedActions>"#;
+    let path = DocumentBuilder::new(128, 128)
+        .image_entry("annotations/macro", recorded)
+        .image_entry("annotations/tasks", taskset)
+        .image_entry("annotations/kritasession", "<session/>")
+        .image_entry("annotations/broken", "<Taskset><action>")
+        .write_temp("session_artifacts")
+        .unwrap();
+
+    let mut file = KraFile::read(&path).unwrap();
+    let artifacts: Vec<Artifact> = file
+        .annotations()
+        .unwrap()
+        .iter()
+        .filter_map(|annotation| annotation.artifact())
+        .collect();
+    assert_eq!(artifacts.len(), 2);
+
+    let Artifact::RecordedActions(actions) = &artifacts[0] else {
+        panic!("expected recorded actions, got {:?}", artifacts[0]);
+    };
+    assert_eq!(actions.len(), 2);
+    assert_eq!(
+        (actions[0].id(), actions[0].name()),
+        ("FreehandPaintAction", "Freehand")
+    );
+    assert_eq!(
+        actions[1].attributes()[2],
+        ("filter".to_owned(), "blur".to_owned())
+    );
+
+    let Artifact::Taskset(taskset) = &artifacts[1] else {
+        panic!("expected a task set, got {:?}", artifacts[1]);
+    };
+    assert_eq!(taskset.name(), "Inking & cleanup");
+    assert_eq!(taskset.version(), "1");
+    assert_eq!(taskset.actions(), ["edit_undo", "clear"]);
+}
@@ -0,0 +1,182 @@
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    process,
+};
+
+use kra::{
+    archive::{pack, unpack, ArchiveSource, DirectorySource, ZipSource},
+    config::ParsingConfiguration,
+    error::WriteError,
+    metadata::DocInfoField,
+    testing::TempPath,
+    writer::WriteOptions,
+    KraFile,
+};
+use zip::{result::ZipError, ZipArchive};
+
+fn fixture() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/clone-and-pass-through.kra")
+}
+
+fn temp_path(name: &str) -> TempPath {
+    let path = TempPath::new(&format!("kra-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&path);
+    path
+}
+
+// Extracts the fixture into a new directory
+fn extracted(test_name: &str) -> TempPath {
+    let dir = temp_path(test_name);
+    let mut zip = ZipArchive::new(fs::File::open(fixture()).unwrap()).unwrap();
+    zip.extract(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn sources_agree() {
+    let config = ParsingConfiguration::default;
+    let mut from_file = KraFile::read(fixture()).unwrap();
+    let bytes = fs::read(fixture()).unwrap();
+    let mut in_memory =
+        KraFile::read_from(ZipSource::from_bytes(bytes).unwrap(), config()).unwrap();
+    let dir = extracted("sources_agree");
+    let mut from_dir = KraFile::read_from(DirectorySource::open(&dir).unwrap(), config()).unwrap();
+
+    // Lists metadata, nodes, and every entry with its size and checksum
+    let expected = from_file.textconv().unwrap();
+    assert_eq!(in_memory.textconv().unwrap(), expected);
+    assert_eq!(from_dir.textconv().unwrap(), expected);
+}
+
+#[test]
+fn directory_entries() {
+    let dir = extracted("directory_entries");
+    let mut source = DirectorySource::open(&dir).unwrap();
+    let names = source.entry_names();
+    assert_eq!(names[0], "mimetype");
+    assert!(names.iter().any(|name| name == "maindoc.xml"));
+    assert!(names[1..].windows(2).all(|pair| pair[0] < pair[1]));
+
+    assert_eq!(source.by_name("mimetype").unwrap().size(), 19);
+    // Only entries that were found can be opened
+    let outside = TempPath::new("kra-outside");
+    fs::write(&outside, b"").unwrap();
+    for name in ["missing", "../kra-outside", "/etc/hostname"] {
+        assert!(matches!(source.by_name(name), Err(ZipError::FileNotFound)));
+    }
+}
+
+#[test]
+fn entry_info() {
+    let mut file = KraFile::read(fixture()).unwrap();
+    assert_eq!(file.saved_at(), Some("2024-01-01T00:00:00"));
+    let info = file.entry_info().unwrap();
+    let names: Vec<&str> = info.iter().map(|entry| entry.name()).collect();
+    assert_eq!(names, file.file().unwrap().entry_names());
+    let maindoc = &info[2];
+    assert_eq!(maindoc.name(), "maindoc.xml");
+    assert_eq!(maindoc.size(), 2406);
+    assert!(maindoc.compressed_size().is_some());
+    assert_eq!(maindoc.modified(), Some("2024-01-01T00:00:00"));
+    assert!(!maindoc.changed());
+
+    file.set_doc_info(DocInfoField::Title, "Changed");
+    let info = file.entry_info().unwrap();
+    assert!(info[1].changed() && info[1].modified().is_none());
+
+    let dir = extracted("entry_info");
+    let mut from_dir = KraFile::read(&dir).unwrap();
+    let info = from_dir.entry_info().unwrap();
+    let maindoc = info.iter().find(|entry| entry.name() == "maindoc.xml");
+    let maindoc = maindoc.unwrap();
+    assert_eq!(maindoc.size(), 2406);
+    assert_eq!(maindoc.compressed_size(), None);
+    assert_eq!(maindoc.modified().map(str::len), Some(19));
+}
+
+#[test]
+fn read_directory() {
+    let dir = extracted("read_directory");
+    // Left by version control and file managers
+    fs::create_dir(dir.join(".git")).unwrap();
+    fs::write(dir.join(".git/HEAD"), b"ref: refs/heads/main").unwrap();
+    fs::write(dir.join(".DS_Store"), b"").unwrap();
+
+    let mut file = KraFile::read(&dir).unwrap();
+    let expected = KraFile::read(fixture()).unwrap().textconv().unwrap();
+    assert_eq!(file.textconv().unwrap(), expected);
+
+    // Packed back into an archive
+    let path = TempPath::new(&format!("kra-read_directory-{}.kra", process::id()));
+    file.write(fs::File::create(&path).unwrap(), &WriteOptions::new())
+        .unwrap();
+    let mut written = KraFile::read(&path).unwrap();
+    assert_eq!(written.file().as_ref().unwrap().entry_names().len(), 6);
+    assert_eq!(written.textconv().unwrap(), expected);
+}
+
+// Contents of every entry, by name
+fn entries(path: &Path) -> Vec<(String, Vec<u8>)> {
+    let mut zip = ZipArchive::new(fs::File::open(path).unwrap()).unwrap();
+    let mut entries: Vec<(String, Vec<u8>)> = (0..zip.len())
+        .map(|index| {
+            let mut entry = zip.by_index(index).unwrap();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).unwrap();
+            (entry.name().to_owned(), data)
+        })
+        .collect();
+    entries.sort();
+    entries
+}
+
+#[test]
+fn unpack_and_pack() {
+    let dir = temp_path("unpack_and_pack");
+    unpack(fixture(), &dir).unwrap();
+    assert_eq!(
+        fs::read(dir.join("mimetype")).unwrap(),
+        b"application/x-krita"
+    );
+    assert!(dir.join("golden-5.2/layers/layer1.defaultpixel").is_file());
+
+    let packed = temp_path("unpack_and_pack.kra");
+    pack(&dir, &packed).unwrap();
+    assert_eq!(entries(&packed), entries(&fixture()));
+}
+
+#[test]
+fn write_dir_over_document() {
+    let dir = extracted("write_dir_over_document");
+    let orphaned = dir.join("golden-5.2/layers/layer99");
+    fs::write(&orphaned, b"").unwrap();
+    fs::write(dir.join(".gitattributes"), b"").unwrap();
+
+    let mut file = KraFile::read(&dir).unwrap();
+    assert_eq!(file.orphaned_entries(), &["golden-5.2/layers/layer99"]);
+    let options = WriteOptions::new().drop_orphaned_entries(true);
+    file.write_dir(&dir, &options).unwrap();
+    assert!(!orphaned.exists());
+    assert!(dir.join(".gitattributes").exists());
+    assert_eq!(DirectorySource::open(&dir).unwrap().entry_names().len(), 6);
+}
+
+#[test]
+fn write_dir_elsewhere() {
+    let mut file = KraFile::read(fixture()).unwrap();
+    let dir = temp_path("write_dir_elsewhere");
+    fs::create_dir(&dir).unwrap();
+    fs::write(dir.join("notes.txt"), b"").unwrap();
+    assert!(matches!(
+        file.write_dir(&dir, &WriteOptions::new()),
+        Err(WriteError::NotADocument(path)) if path == *dir
+    ));
+    assert!(dir.join("notes.txt").exists());
+
+    // Created if missing
+    let dir = dir.join("document");
+    file.write_dir(&dir, &WriteOptions::new()).unwrap();
+    assert!(dir.join("maindoc.xml").is_file());
+}
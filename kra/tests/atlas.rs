@@ -0,0 +1,139 @@
+use kra::{
+    atlas::AtlasOptions,
+    error::AtlasError,
+    id::Uuid,
+    testing::{DocumentBuilder, LayerBuilder},
+    KraFile,
+};
+
+// UUIDs of the nodes, in document order
+const COIN: u128 = 1;
+const BANNER: u128 = 2;
+const EMPTY: u128 = 3;
+
+fn uuid(value: u128) -> Uuid {
+    Uuid::from_u128(value)
+}
+
+fn document() -> DocumentBuilder {
+    let tagged = |name| LayerBuilder::paint(name).attribute("kra-rs.atlas", "ui");
+    // Blue, green, red, alpha
+    DocumentBuilder::new(128, 128)
+        .layer(
+            tagged("Coin")
+                .offset(10, 20)
+                .fill_tile(0, 0, [0, 0, 255, 255]),
+        )
+        .layer(
+            tagged("Banner")
+                .fill_tile(0, 0, [255, 0, 0, 255])
+                .fill_tile(64, 0, [255, 0, 0, 255]),
+        )
+        .layer(tagged("Empty"))
+        .layer(LayerBuilder::paint("Background"))
+}
+
+fn read(test_name: &str, document: DocumentBuilder) -> KraFile {
+    KraFile::read(document.write_temp(test_name).unwrap()).unwrap()
+}
+
+#[test]
+fn one_page() {
+    let mut file = read("atlas_one_page", document());
+    let layers = file.tagged_layers("atlas", "ui");
+    assert_eq!(layers, [uuid(COIN), uuid(BANNER), uuid(EMPTY)]);
+
+    file.set_custom_property(&uuid(BANNER), "description", "Stretched \"wide\"")
+        .unwrap();
+    let atlas = file.pack_atlas(&layers, &AtlasOptions::new()).unwrap();
+    let [page] = atlas.pages() else {
+        panic!("expected one page");
+    };
+    // Banner is larger, so it is placed first
+    assert_eq!((page.width(), page.height()), (64 + 1 + 128, 64));
+    let [coin, banner] = atlas.sprites() else {
+        panic!("expected two sprites");
+    };
+    assert_eq!((banner.x(), banner.y()), (0, 0));
+    assert_eq!((banner.width(), banner.height()), (128, 64));
+    assert_eq!((coin.x(), coin.y()), (129, 0));
+    assert_eq!(coin.name(), "Coin");
+    assert_eq!(coin.source(), (10, 20));
+    assert_eq!(coin.description(), None);
+    assert_eq!(banner.description(), Some("Stretched \"wide\""));
+
+    let pixel = |x: usize, y: usize| {
+        let start = (y * page.width() as usize + x) * 4;
+        &page.pixels()[start..start + 4]
+    };
+    assert_eq!(pixel(0, 63), [0, 0, 255, 255]);
+    assert_eq!(pixel(128, 0), [0, 0, 0, 0]);
+    assert_eq!(pixel(129, 63), [255, 0, 0, 255]);
+
+    let json = atlas.to_json();
+    assert!(json.starts_with(r#"{"image":{"width":"#));
+    assert!(json.contains(r#""source_y":0,"description":"Stretched \"wide\""}"#));
+    assert!(json.contains(&format!(
+        r#"{{"uuid":"{}","name":"Coin","page":0,"x":129,"y":0,"width":64,"height":64,"source_x":10,"source_y":20}}"#,
+        uuid(COIN)
+    )));
+}
+
+#[test]
+fn several_pages() {
+    let mut file = read("atlas_several_pages", document());
+    let layers = [uuid(COIN), uuid(BANNER)];
+    let options = AtlasOptions::new().max_size(128).padding(2);
+    let atlas = file.pack_atlas(&layers, &options).unwrap();
+    let sizes: Vec<_> = atlas
+        .pages()
+        .iter()
+        .map(|page| (page.width(), page.height()))
+        .collect();
+    assert_eq!(sizes, [(128, 64), (64, 64)]);
+    assert_eq!(atlas.sprites()[0].page(), 1);
+
+    assert!(matches!(
+        file.pack_atlas(&layers, &AtlasOptions::new().max_size(100)),
+        Err(AtlasError::TooLarge(id)) if id == uuid(BANNER)
+    ));
+}
+
+#[test]
+fn nine_slice() {
+    // At 300 DPI, 4.8 points are 20 pixels
+    let guides = r#"<guides showGuides="1" snapToGuides="0" lockGuides="1">
+   <horizontalGuides type="array">
+    <item_0 type="value" value="2.4"/>
+    <item_1 type="value" value="12"/>
+   </horizontalGuides>
+   <verticalGuides type="array">
+    <item_0 type="value" value="24"/>
+    <item_1 type="value" value="4.8"/>
+   </verticalGuides>
+   <unit type="value" value="0"/>
+  </guides>"#;
+    let mut file = read("atlas_nine_slice", document().element(guides));
+    let meta = file.meta();
+    assert!(meta.guides().visible() && meta.guides().locked() && !meta.guides().snap());
+    assert_eq!(meta.guides().horizontal(), [2.4, 12.0]);
+    assert_eq!(meta.guide_pixels().1, [100.0, 20.0]);
+
+    let atlas = file
+        .pack_atlas(&[uuid(COIN), uuid(BANNER)], &AtlasOptions::new())
+        .unwrap();
+    let [coin, banner] = atlas.sprites() else {
+        panic!("expected two sprites");
+    };
+    // Only one vertical guide crosses the coin
+    assert_eq!(coin.nine_slice(), None);
+    let insets = banner.nine_slice().unwrap();
+    assert_eq!(
+        (insets.left(), insets.top(), insets.right(), insets.bottom()),
+        (20, 10, 28, 14)
+    );
+    assert!(atlas
+        .to_json()
+        .contains(r#""source_y":0,"nine_slice":{"left":20,"top":10,"right":28,"bottom":14}}"#));
+    assert!(file.nine_slice(30, 0, 50, 64).is_none());
+}
@@ -0,0 +1,87 @@
+use std::io::Write;
+
+use flate2::{write::ZlibEncoder, Compression};
+use kra::{error::PresetError, resources::preset::Preset, testing::DocumentBuilder, KraFile};
+
+const PRESET: &str = r#"<Preset paintopid="paintbrush" name="Ink &amp; Pen" embedded_resources="0">
+ <param type="string" name="CompositeOp"><![CDATA[normal]]></param>
+ <param type="string" name="brush_definition"><![CDATA[<Brush type="auto_brush"/>]]></param>
+ <param type="string" name="EraserMode">false</param>
+</Preset>"#;
+
+fn chunk(kind: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(kind);
+    hasher.update(data);
+    [
+        &(data.len() as u32).to_be_bytes()[..],
+        kind,
+        data,
+        &hasher.finalize().to_be_bytes(),
+    ]
+    .concat()
+}
+
+// Header, the version as tEXt and the preset as zTXt, like Qt writes them
+fn kpp() -> Vec<u8> {
+    let mut header = Vec::new();
+    header.extend(1u32.to_be_bytes());
+    header.extend(1u32.to_be_bytes());
+    header.extend([8, 6, 0, 0, 0]);
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(PRESET.as_bytes()).unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    [
+        b"\x89PNG\r\n\x1a\n".to_vec(),
+        chunk(b"IHDR", &header),
+        chunk(b"tEXt", b"version\x002.2"),
+        chunk(
+            b"zTXt",
+            &[b"preset\x00\x00".as_slice(), &compressed].concat(),
+        ),
+        chunk(b"IEND", &[]),
+    ]
+    .concat()
+}
+
+#[test]
+fn preset_settings() {
+    let preset = Preset::from_png(&kpp()).unwrap();
+    assert_eq!(preset.name(), "Ink & Pen");
+    assert_eq!(preset.paintop(), "paintbrush");
+    assert_eq!(preset.version(), "2.2");
+    assert_eq!(preset.params().len(), 3);
+    assert_eq!(preset.param("CompositeOp"), Some("normal"));
+    assert_eq!(
+        preset.param("brush_definition"),
+        Some(r#"<Brush type="auto_brush"/>"#)
+    );
+    assert_eq!(preset.param("EraserMode"), Some("false"));
+
+    assert!(matches!(
+        Preset::from_png(b"not a png"),
+        Err(PresetError::MalformedPng(_))
+    ));
+    let image = [b"\x89PNG\r\n\x1a\n".to_vec(), chunk(b"IEND", &[])].concat();
+    assert!(matches!(
+        Preset::from_png(&image),
+        Err(PresetError::MissingPreset)
+    ));
+}
+
+#[test]
+fn embedded_presets() {
+    let path = DocumentBuilder::new(128, 128)
+        .image_entry("presets/pen.kpp", kpp())
+        .write_temp("embedded_presets")
+        .unwrap();
+    let mut file = KraFile::read(path).unwrap();
+    let presets = file.embedded_presets().unwrap();
+    let [(name, preset)] = presets.as_slice() else {
+        panic!("expected one preset");
+    };
+    assert!(name.ends_with("/presets/pen.kpp"));
+    assert_eq!(preset.name(), "Ink & Pen");
+}
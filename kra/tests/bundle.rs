@@ -0,0 +1,91 @@
+use std::{fs::File, io::Write, process};
+
+use kra::{bundle::Bundle, error::ReadBundleError, testing::TempPath};
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+const META: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<meta:meta xmlns:meta="urn:oasis:names:tc:opendocument:xmlns:meta:1.0" xmlns:dc="http://purl.org/dc/elements/1.1/">
+ <meta:generator>Krita (5.2.2)</meta:generator>
+ <dc:author>Someone</dc:author>
+ <dc:title>Inking &amp; lines</dc:title>
+ <dc:description>Brushes for inking</dc:description>
+ <meta:initial-creator>Someone</meta:initial-creator>
+ <dc:creator>Someone else</dc:creator>
+ <meta:creation-date>2024-01-02T03:04:05</meta:creation-date>
+ <meta:dc-date>2024-02-03T04:05:06</meta:dc-date>
+ <meta:meta-userdefined meta:name="email" meta:value="someone@example.com"/>
+ <meta:meta-userdefined meta:name="license" meta:value="CC-BY"/>
+ <meta:bundle-version>1</meta:bundle-version>
+</meta:meta>
+"#;
+
+const MANIFEST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE manifest:manifest PUBLIC "-//OpenOffice.org//DTD Manifest 1.0//EN" "Manifest.dtd">
+<manifest:manifest xmlns:manifest="urn:oasis:names:tc:opendocument:xmlns:manifest:1.0" manifest:version="1.2">
+ <manifest:file-entry manifest:media-type="application/x-krita-resourcebundle" manifest:full-path="/"/>
+ <manifest:file-entry manifest:media-type="brushes" manifest:full-path="brushes/pen.gbr" manifest:md5sum="0123456789abcdef0123456789abcdef">
+  <manifest:tag>Ink</manifest:tag>
+  <manifest:tag>Favorites</manifest:tag>
+ </manifest:file-entry>
+ <manifest:file-entry manifest:media-type="patterns" manifest:full-path="patterns/paper.png"/>
+</manifest:manifest>
+"#;
+
+fn write_bundle(test_name: &str, mimetype: &str) -> TempPath {
+    let path = TempPath::new(&format!("kra-{}-{}.bundle", test_name, process::id()));
+    let mut zip = ZipWriter::new(File::create(&path).unwrap());
+    zip.start_file(
+        "mimetype",
+        FileOptions::default().compression_method(CompressionMethod::Stored),
+    )
+    .unwrap();
+    zip.write_all(mimetype.as_bytes()).unwrap();
+    for (name, data) in [
+        ("meta.xml", META.as_bytes()),
+        ("META-INF/manifest.xml", MANIFEST.as_bytes()),
+        ("brushes/pen.gbr", b"pen"),
+        ("patterns/paper.png", b"paper"),
+    ] {
+        zip.start_file(name, FileOptions::default()).unwrap();
+        zip.write_all(data).unwrap();
+    }
+    zip.finish().unwrap();
+    path
+}
+
+#[test]
+fn read_bundle() {
+    let path = write_bundle("bundle_read", "application/x-krita-resourcebundle");
+    let mut bundle = Bundle::read(path).unwrap();
+    let meta = bundle.meta();
+    assert_eq!(meta.title(), "Inking & lines");
+    assert_eq!(meta.author(), "Someone");
+    assert_eq!(meta.generator(), "Krita (5.2.2)");
+    assert_eq!(meta.date(), "2024-02-03T04:05:06");
+    assert_eq!(meta.version(), "1");
+    assert_eq!(meta.user_defined_value("license"), Some("CC-BY"));
+    assert_eq!(meta.user_defined().len(), 2);
+
+    let [pen, paper] = bundle.resources() else {
+        panic!("expected two resources");
+    };
+    assert_eq!(pen.kind(), "brushes");
+    assert_eq!(pen.file_name(), "pen.gbr");
+    assert_eq!(pen.md5(), "0123456789abcdef0123456789abcdef");
+    assert_eq!(pen.tags(), ["Ink", "Favorites"]);
+    assert_eq!(paper.md5(), "");
+
+    let patterns: Vec<_> = bundle.resources_of_kind("patterns").cloned().collect();
+    assert_eq!(patterns.len(), 1);
+    assert_eq!(bundle.read_resource(&patterns[0]).unwrap(), b"paper");
+    assert_eq!(bundle.preview().unwrap(), None);
+}
+
+#[test]
+fn not_a_bundle() {
+    let path = write_bundle("bundle_mimetype", "application/x-krita");
+    assert!(matches!(
+        Bundle::read(path),
+        Err(ReadBundleError::MimetypeMismatch)
+    ));
+}
@@ -0,0 +1,45 @@
+use kra::{
+    config::ParsingConfiguration,
+    id::Uuid,
+    testing::{DocumentBuilder, LayerBuilder},
+    KraFile,
+};
+
+fn read(test_name: &str, cache_budget: usize) -> KraFile {
+    let path = DocumentBuilder::new(128, 128)
+        .layer(
+            LayerBuilder::paint("Upper")
+                .offset(64, 0)
+                .fill_tile(0, 0, [255, 0, 0, 255]),
+        )
+        .layer(LayerBuilder::paint("Lower").fill_tile(0, 0, [0, 0, 255, 255]))
+        .write_temp(test_name)
+        .unwrap();
+    let config = ParsingConfiguration::default().cache_budget(cache_budget);
+    KraFile::read_with_config(path, config).unwrap()
+}
+
+#[test]
+fn cached_layers_are_the_same() {
+    let lower = Uuid::from_u128(2);
+    let mut uncached = read("cached_layers_are_the_same", 0);
+    let mut cached = read("cached_layers_are_the_same", 1 << 20);
+    let expected = uncached.decode_layer(&lower).unwrap();
+    assert_eq!(cached.decode_layer(&lower).unwrap(), expected);
+    assert_eq!(cached.decode_layer(&lower).unwrap(), expected);
+    cached.clear_cache();
+    assert_eq!(cached.decode_layer(&lower).unwrap(), expected);
+}
+
+#[test]
+fn changes_are_not_hidden() {
+    let (upper, lower) = (Uuid::from_u128(1), Uuid::from_u128(2));
+    let mut file = read("changes_are_not_hidden", 1 << 20);
+    file.decode_layer(&upper).unwrap();
+    let before = file.decode_layer(&lower).unwrap();
+    assert_eq!(before.width(), 64);
+
+    file.merge_down(&upper).unwrap();
+    assert_eq!(file.decode_layer(&lower).unwrap().width(), 128);
+    assert!(file.decode_layer(&upper).is_err());
+}
@@ -0,0 +1,97 @@
+use std::{fs::File, process};
+
+use kra::{
+    data::PixelBuffer,
+    error::{DataError, EditError},
+    id::Uuid,
+    layer::NodeKind,
+    testing::{DocumentBuilder, LayerBuilder, TempPath},
+    writer::WriteOptions,
+    KraFile,
+};
+
+// UUIDs of the nodes, in document order
+const LAYER: u128 = 1;
+const MASK: u128 = 2;
+
+fn uuid(value: u128) -> Uuid {
+    Uuid::from_u128(value)
+}
+
+fn read(test_name: &str) -> KraFile {
+    let mut mask = LayerBuilder::mask("Flats", "colorizemask");
+    for (key, value) in [
+        ("limit-to-device", "0"),
+        ("show-coloring", "1"),
+        ("cleanup", "0"),
+        ("use-edge-detection", "0"),
+        ("edge-detection-size", "4"),
+        ("fuzzy-radius", "0"),
+        ("edit-keystrokes", "0"),
+        ("compositeop", "multiply"),
+        ("colorspacename", "RGBA"),
+    ] {
+        mask = mask.attribute(key, value);
+    }
+    let coloring = LayerBuilder::paint("Coloring").fill_tile(64, 0, [255, 0, 0, 255]);
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Lineart").with_mask(mask))
+        .image_entry("layers/mask2.colorizemask/coloring", coloring.pixel_data())
+        .write_temp(test_name)
+        .unwrap();
+    KraFile::read(path).unwrap()
+}
+
+#[test]
+fn decode_stored_coloring() {
+    let mut file = read("decode_colorize_mask");
+    let data = file.decode_colorize_mask(&uuid(MASK)).unwrap();
+    assert_eq!(
+        (data.x(), data.y(), data.width(), data.height()),
+        (64, 0, 64, 64)
+    );
+    let PixelBuffer::U8(pixels) = data.pixels() else {
+        panic!("expected 8-bit pixels");
+    };
+    assert_eq!(&pixels[..4], &[255, 0, 0, 255]);
+
+    assert!(matches!(
+        file.decode_colorize_mask(&uuid(LAYER)),
+        Err(DataError::NoPixelData(_))
+    ));
+}
+
+#[test]
+fn flatten_into_layer_above() {
+    let mut file = read("flatten_colorize_mask");
+    let flats = file.flatten_colorize_mask(&uuid(MASK)).unwrap();
+    assert!(matches!(
+        file.flatten_colorize_mask(&uuid(LAYER)),
+        Err(EditError::NotColorizeMask(_))
+    ));
+
+    let path = TempPath::new(&format!("kra-flattened-{}.kra", process::id()));
+    file.write(File::create(&path).unwrap(), &WriteOptions::new())
+        .unwrap();
+    let mut written = KraFile::read(&path).unwrap();
+    let [new, lineart] = written.layers() else {
+        panic!("expected two layers");
+    };
+    assert_eq!(new.uuid(), flats);
+    assert_eq!(new.name(), "Flats");
+    assert_eq!(new.kind(), NodeKind::PaintLayer);
+    // The mask stays on its layer
+    assert_eq!(lineart.masks()[0].uuid(), uuid(MASK));
+
+    let data = written.decode_layer(&flats).unwrap();
+    assert_eq!(
+        (data.x(), data.y(), data.width(), data.height()),
+        (64, 0, 64, 64)
+    );
+    let PixelBuffer::U8(pixels) = data.pixels() else {
+        panic!("expected 8-bit pixels");
+    };
+    assert!(pixels
+        .chunks_exact(4)
+        .all(|pixel| pixel == [255, 0, 0, 255]));
+}
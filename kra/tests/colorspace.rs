@@ -0,0 +1,92 @@
+use kra::{
+    color::ChannelDepth, config::ParsingConfiguration, testing::DocumentBuilder, Colorspace,
+    KraFile,
+};
+
+#[test]
+fn identifiers_round_trip() {
+    for id in [
+        "RGBA",
+        "RGBA16",
+        "RGBAF16",
+        "RGBAF32",
+        "GRAYA",
+        "GRAYAU16",
+        "GRAYAF16",
+        "GRAYAF32",
+        "CMYK",
+        "CMYKA16",
+        "CMYKAF32",
+        "LABA",
+        "LABAU16",
+        "LABAF32",
+        "XYZA8",
+        "XYZA16",
+        "XYZAF16",
+        "XYZAF32",
+        "YCBCRA8",
+        "YCBCRAU16",
+        "YCBCRF32",
+    ] {
+        let colorspace = Colorspace::try_from(id).unwrap();
+        assert_eq!(colorspace.to_string(), id);
+    }
+}
+
+#[test]
+fn legacy_names() {
+    let cases = [
+        ("RGB", Colorspace::RGBA),
+        ("RgbAF32", Colorspace::RGBAF32),
+        ("Grayscale + Alpha", Colorspace::GRAYA),
+        ("GrayF16", Colorspace::GRAYAF16),
+        ("XyzAF32", Colorspace::XYZAF32),
+        ("YCbCrAU16", Colorspace::YCBCRAU16),
+    ];
+    for (name, expected) in cases {
+        assert_eq!(Colorspace::try_from(name), Ok(expected), "{}", name);
+    }
+    assert!(Colorspace::try_from("rgba").is_err());
+    assert!(Colorspace::try_from("HSV").is_err());
+}
+
+#[test]
+fn pixel_layout() {
+    assert_eq!(Colorspace::GRAYAU16.channel_count(), 2);
+    assert_eq!(Colorspace::GRAYAU16.pixel_size(), 4);
+    assert_eq!(Colorspace::CMYKAF32.depth(), ChannelDepth::F32);
+    assert_eq!(Colorspace::CMYKAF32.pixel_size(), 20);
+    assert_eq!(Colorspace::LABAU16.pixel_size(), 8);
+}
+
+#[test]
+fn legacy_name_in_maindoc() {
+    let path = DocumentBuilder::new(128, 128)
+        .colorspace("Grayscale + Alpha")
+        .write_temp("legacy_name_in_maindoc")
+        .unwrap();
+    let file = KraFile::read(&path).unwrap();
+    assert_eq!(file.meta().colorspace(), Colorspace::GRAYA);
+}
+
+#[test]
+fn raw_name_and_fallback() {
+    let path = DocumentBuilder::new(128, 128)
+        .write_temp("raw_name")
+        .unwrap();
+    let file = KraFile::read(&path).unwrap();
+    assert_eq!(file.meta().colorspace_raw(), "RGBA");
+    assert!(!file.meta().colorspace_fallback());
+
+    let path = DocumentBuilder::new(128, 128)
+        .colorspace("HSVA")
+        .write_temp("unknown_colorspace")
+        .unwrap();
+    assert!(KraFile::read(&path).is_err());
+
+    let config = ParsingConfiguration::new().colorspace_fallback(true);
+    let file = KraFile::read_with_config(&path, config).unwrap();
+    assert_eq!(file.meta().colorspace(), Colorspace::RGBA);
+    assert_eq!(file.meta().colorspace_raw(), "HSVA");
+    assert!(file.meta().colorspace_fallback());
+}
@@ -0,0 +1,74 @@
+use kra::{
+    compat::Target,
+    id::Uuid,
+    layer::CompositeOp,
+    testing::{DocumentBuilder, LayerBuilder},
+    KraFile,
+};
+
+#[test]
+fn blend_mode_report() {
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Sky").composite_op("linear_burn"))
+        .layer(
+            LayerBuilder::group("Effects")
+                .child(LayerBuilder::paint("Glow").composite_op("soft_light_svg")),
+        )
+        .write_temp("compat_report")
+        .unwrap();
+    let file = KraFile::read(path).unwrap();
+    let (sky, glow) = (Uuid::from_u128(1), Uuid::from_u128(3));
+
+    let report = file.compat_report(Target::Psd);
+    assert_eq!(report.target(), Target::Psd);
+    let found: Vec<_> = report
+        .issues()
+        .iter()
+        .map(|issue| (issue.node(), issue.blend_mode(), issue.substitute()))
+        .collect();
+    assert_eq!(found, [(glow, CompositeOp::SoftLightSvg, "sLit")]);
+
+    let report = file.compat_report(Target::Css);
+    let found: Vec<_> = report
+        .issues()
+        .iter()
+        .map(|issue| (issue.node(), issue.substitute()))
+        .collect();
+    assert_eq!(found, [(sky, "normal")]);
+
+    assert!(!file.compat_report(Target::Ora).is_lossless());
+    assert_eq!(
+        Target::Ora.blend_mode(CompositeOp::Multiply),
+        Some("svg:multiply")
+    );
+    assert_eq!(Target::Ora.substitute(CompositeOp::HueHsl), "svg:hue");
+    assert_eq!(Target::Ora.to_string(), "OpenRaster");
+}
+
+#[test]
+fn blend_categories() {
+    use kra::layer::BlendCategory;
+
+    let ops = [
+        "multiply", "xor", "burn", "screen", "hue_hsl", "glow", "copy_red",
+    ];
+    let categories: Vec<_> = ops
+        .iter()
+        .map(|op| op.parse::<CompositeOp>().unwrap().category())
+        .collect();
+    assert_eq!(
+        categories,
+        [
+            BlendCategory::Arithmetic,
+            BlendCategory::Binary,
+            BlendCategory::Darken,
+            BlendCategory::Lighten,
+            BlendCategory::Hsl,
+            BlendCategory::Quadratic,
+            BlendCategory::Misc,
+        ]
+    );
+    assert_eq!(BlendCategory::Hsy.to_string(), "HSY");
+    assert!(CompositeOp::LambertLighting.is_hdr_only());
+    assert!(!CompositeOp::Normal.is_hdr_only());
+}
@@ -0,0 +1,75 @@
+use kra::{
+    geom::{IPoint, IRect},
+    id::Uuid,
+    testing::{DocumentBuilder, LayerBuilder},
+    KraFile,
+};
+
+// Opaque pixels at x in 3..5, y in 2..4
+fn dot() -> Vec<u8> {
+    let mut pixels = vec![0; 64 * 64 * 4];
+    for y in 2..4 {
+        for x in 3..5 {
+            pixels[(y * 64 + x) * 4..][..4].copy_from_slice(&[0, 0, 255, 255]);
+        }
+    }
+    pixels
+}
+
+#[test]
+fn exact_bounds_and_crop() {
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Dot").offset(10, 5).tile(0, 0, dot()))
+        .layer(
+            LayerBuilder::paint("Hidden")
+                .visible(false)
+                .fill_tile(64, 64, [0, 0, 0, 255]),
+        )
+        .layer(
+            LayerBuilder::group("Hidden group")
+                .visible(false)
+                .child(LayerBuilder::paint("In group").fill_tile(0, 64, [0, 0, 0, 255])),
+        )
+        .write_temp("exact_bounds_and_crop")
+        .unwrap();
+    let (dot, hidden) = (Uuid::from_u128(1), Uuid::from_u128(2));
+
+    let mut file = KraFile::read(&path).unwrap();
+    assert_eq!(
+        file.exact_bounds(&dot).unwrap(),
+        Some(IRect::new(13, 7, 2, 2))
+    );
+    assert_eq!(
+        file.exact_bounds(&hidden).unwrap(),
+        Some(IRect::new(64, 64, 64, 64))
+    );
+
+    let crop = file.suggest_crop().unwrap().unwrap();
+    assert_eq!(
+        (crop.x(), crop.y(), crop.width(), crop.height()),
+        (13, 7, 2, 2)
+    );
+    assert_eq!(crop.rect(), IRect::new(13, 7, 2, 2));
+    file.crop(crop.rect()).unwrap();
+    assert_eq!(
+        file.exact_bounds(&dot).unwrap(),
+        Some(IRect::new(0, 0, 2, 2))
+    );
+}
+
+#[test]
+fn rect_math() {
+    let a = IRect::new(0, 0, 10, 10);
+    let b = IRect::new(5, -5, 10, 10);
+    assert_eq!(a.intersection(&b), Some(IRect::new(5, 0, 5, 5)));
+    assert_eq!(a.union(&b), IRect::new(0, -5, 15, 15));
+    assert!(a.overlaps(&b));
+    assert_eq!(a.intersection(&IRect::new(10, 0, 5, 5)), None);
+    assert_eq!(a.union(&IRect::default()), a);
+    assert_eq!(IRect::from_edges(5, 5, 2, 8), IRect::new(5, 5, 0, 3));
+    assert!(a.contains(IPoint::new(9, 0)));
+    assert!(!a.contains(IPoint::new(10, 0)));
+    assert!(a.contains_rect(&IRect::new(2, 2, 8, 8)));
+    assert_eq!(b.translate(IPoint::new(-5, 5)), IRect::new(0, 0, 10, 10));
+    assert_eq!((b.right(), b.bottom(), b.area()), (15, 5, 100));
+}
@@ -0,0 +1,80 @@
+use std::{fs::File, process};
+
+use kra::{
+    config::ParsingConfiguration,
+    error::EditError,
+    id::Uuid,
+    testing::{DocumentBuilder, LayerBuilder, TempPath},
+    writer::WriteOptions,
+    KraFile,
+};
+
+#[test]
+fn read_from_attributes() {
+    let layer = LayerBuilder::paint("Button")
+        .attribute("kra-rs.export.atlas", "ui")
+        .attribute("kra-rs.asset-id", "42");
+    let path = DocumentBuilder::new(128, 128)
+        .layer(layer)
+        .write_temp("read_custom_properties")
+        .unwrap();
+
+    // Custom properties are not unknown attributes
+    let file = KraFile::read_with_config(&path, ParsingConfiguration::strict()).unwrap();
+    let node = &file.layers()[0];
+    let properties: Vec<_> = node.custom_properties().collect();
+    assert_eq!(properties, [("export.atlas", "ui"), ("asset-id", "42")]);
+    assert_eq!(node.custom_property("export.atlas"), Some("ui"));
+    assert_eq!(node.custom_property("missing"), None);
+}
+
+#[test]
+fn round_trip() {
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Button"))
+        .write_temp("custom_properties_round_trip")
+        .unwrap();
+    let mut file = KraFile::read(&path).unwrap();
+    let layer = Uuid::from_u128(1);
+
+    file.set_custom_property(&layer, "export.atlas", "ui & icons")
+        .unwrap();
+    file.set_custom_property(&layer, "temporary", "1").unwrap();
+    assert_eq!(
+        file.remove_custom_property(&layer, "temporary").unwrap(),
+        Some("1".to_owned())
+    );
+    assert!(matches!(
+        file.set_custom_property(&layer, "export:atlas", "ui"),
+        Err(EditError::InvalidPropertyKey(_))
+    ));
+    assert!(matches!(
+        file.set_custom_property(&Uuid::from_u128(0), "key", "value"),
+        Err(EditError::UnknownNode(_))
+    ));
+
+    let written = TempPath::new(&format!("kra-custom-properties-{}.kra", process::id()));
+    file.write(File::create(&written).unwrap(), &WriteOptions::new())
+        .unwrap();
+    let file = KraFile::read(&written).unwrap();
+    let properties: Vec<_> = file.layers()[0].custom_properties().collect();
+    assert_eq!(properties, [("export.atlas", "ui & icons")]);
+}
+
+#[test]
+fn descriptions() {
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Button").attribute("comment", "Shadow is baked in"))
+        .layer(LayerBuilder::paint("Icon"))
+        .write_temp("layer_descriptions")
+        .unwrap();
+
+    // Attributes that other tools write are known
+    let mut file = KraFile::read_with_config(&path, ParsingConfiguration::strict()).unwrap();
+    assert_eq!(file.layers()[0].description(), Some("Shadow is baked in"));
+    assert_eq!(file.layers()[1].description(), None);
+
+    file.set_custom_property(&Uuid::from_u128(2), "description", "Keep square")
+        .unwrap();
+    assert_eq!(file.layers()[1].description(), Some("Keep square"));
+}
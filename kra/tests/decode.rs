@@ -0,0 +1,89 @@
+use kra::{
+    data::PixelBuffer,
+    id::Uuid,
+    testing::{DocumentBuilder, LayerBuilder},
+    Colorspace, KraFile,
+};
+
+#[test]
+fn decode_deep_layers() {
+    let u16_pixel: Vec<u8> = [1u16, 2, 3, u16::MAX]
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+    let f32_pixel: Vec<u8> = [0.25f32, 0.5, 2.0, 1.0]
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+    let path = DocumentBuilder::new(128, 128)
+        .layer(
+            LayerBuilder::paint("U16")
+                .colorspace("RGBA16")
+                .fill_tile(64, 0, u16_pixel),
+        )
+        .layer(
+            LayerBuilder::paint("F32")
+                .colorspace("RGBAF32")
+                .fill_tile(0, 0, f32_pixel),
+        )
+        .write_temp("decode_deep_layers")
+        .unwrap();
+
+    let mut file = KraFile::read(&path).unwrap();
+
+    let data = file.decode_layer(&Uuid::from_u128(1)).unwrap();
+    assert_eq!(
+        (data.x(), data.y(), data.width(), data.height()),
+        (64, 0, 64, 64)
+    );
+    match data.pixels() {
+        PixelBuffer::U16(pixels) => {
+            assert_eq!(pixels.len(), 64 * 64 * 4);
+            assert_eq!(&pixels[..4], &[1, 2, 3, u16::MAX]);
+        }
+        other => panic!("expected 16-bit pixels, got {:?}", other),
+    }
+
+    let data = file.decode_layer(&Uuid::from_u128(2)).unwrap();
+    match data.pixels() {
+        PixelBuffer::F32(pixels) => assert_eq!(&pixels[..4], &[0.25, 0.5, 2.0, 1.0]),
+        other => panic!("expected floating point pixels, got {:?}", other),
+    }
+}
+
+#[test]
+fn convert_to_image_colorspace() {
+    // BGRA, 16 bits per channel
+    let pixel: Vec<u8> = [0u16, 0x8080, 0xffff, 0xffff]
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+    let path = DocumentBuilder::new(128, 128)
+        .layer(
+            LayerBuilder::paint("Deep")
+                .colorspace("RGBA16")
+                .fill_tile(0, 0, pixel),
+        )
+        .layer(LayerBuilder::paint("Plain").fill_tile(0, 0, [1, 2, 3, 4]))
+        .write_temp("convert_to_image_colorspace")
+        .unwrap();
+
+    let mut file = KraFile::read(&path).unwrap();
+    let (deep, plain) = (Uuid::from_u128(1), Uuid::from_u128(2));
+    let nodes: Vec<_> = file
+        .layers()
+        .iter()
+        .map(|node| file.needs_conversion(node))
+        .collect();
+    assert_eq!(nodes, [true, false]);
+
+    let converted = file.decode_layer_converted(&deep).unwrap();
+    assert_eq!(converted.colorspace(), Colorspace::RGBA);
+    let PixelBuffer::U8(pixels) = converted.pixels() else {
+        panic!("expected 8-bit pixels");
+    };
+    assert_eq!(&pixels[..4], [0, 128, 255, 255]);
+
+    let unchanged = file.decode_layer_converted(&plain).unwrap();
+    assert_eq!(unchanged, file.decode_layer(&plain).unwrap());
+}
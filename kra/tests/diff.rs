@@ -0,0 +1,84 @@
+use kra::{
+    diff::{diff, Change},
+    id::Uuid,
+    testing::{DocumentBuilder, LayerBuilder},
+    KraFile,
+};
+
+const GROUP: u128 = 1;
+const SKETCH: u128 = 2;
+const INK: u128 = 3;
+const COLOR: u128 = 4;
+
+fn uuid(value: u128) -> Uuid {
+    Uuid::from_u128(value)
+}
+
+// Node that keeps its UUID and filename wherever it is
+fn node(layer: LayerBuilder, id: u128) -> LayerBuilder {
+    layer.uuid(uuid(id)).filename(&format!("layer{}", id))
+}
+
+fn read(test_name: &str, document: DocumentBuilder) -> KraFile {
+    KraFile::read(document.write_temp(test_name).unwrap()).unwrap()
+}
+
+fn document(layers: impl IntoIterator<Item = LayerBuilder>) -> DocumentBuilder {
+    layers
+        .into_iter()
+        .fold(DocumentBuilder::new(128, 128), DocumentBuilder::layer)
+}
+
+#[test]
+fn same_document() {
+    let layers = [node(LayerBuilder::paint("Sketch"), SKETCH)];
+    let old = read("diff_same_old", document(layers.clone()));
+    let new = read("diff_same_new", document(layers));
+    assert_eq!(diff(&old, &new), []);
+}
+
+#[test]
+fn changed_nodes() {
+    let old = read(
+        "diff_changed_old",
+        document([
+            node(LayerBuilder::group("Lines"), GROUP).child(node(LayerBuilder::paint("Ink"), INK)),
+            node(LayerBuilder::paint("Sketch"), SKETCH),
+            node(LayerBuilder::paint("Color"), COLOR),
+        ]),
+    );
+    let new = read(
+        "diff_changed_new",
+        document([
+            node(LayerBuilder::paint("Color"), COLOR),
+            node(LayerBuilder::paint("Sketch"), SKETCH).visible(false),
+            node(LayerBuilder::paint("Ink"), INK),
+        ]),
+    );
+    assert_eq!(
+        diff(&old, &new),
+        [
+            Change::NodeChanged(uuid(SKETCH)),
+            Change::NodeMoved(uuid(INK)),
+            Change::NodeRemoved(uuid(GROUP)),
+            Change::Reordered(None),
+        ]
+    );
+    assert_eq!(
+        diff(&new, &old),
+        [
+            Change::NodeAdded(uuid(GROUP)),
+            Change::NodeMoved(uuid(INK)),
+            Change::NodeChanged(uuid(SKETCH)),
+            Change::Reordered(None),
+        ]
+    );
+}
+
+#[test]
+fn changed_metadata() {
+    let document = document([node(LayerBuilder::paint("Sketch"), SKETCH)]);
+    let old = read("diff_metadata_old", document.clone());
+    let new = read("diff_metadata_new", document.resolution("72", "300"));
+    assert_eq!(diff(&old, &new), [Change::Metadata]);
+}
@@ -0,0 +1,488 @@
+use std::{env, fs::File, path::PathBuf, process};
+
+use kra::{
+    config::{Limits, ParsingConfiguration},
+    data::{LayerData, PixelBuffer},
+    edit::TextLayer,
+    error::EditError,
+    id::Uuid,
+    layer::{CompositeOp, Node, NodeKind, NodeType},
+    metadata::DocInfoField,
+    testing::{DocumentBuilder, LayerBuilder, TempPath},
+    writer::WriteOptions,
+    Colorspace, KraFile,
+};
+use regex::Regex;
+
+// UUIDs of the nodes that write() makes, in document order
+const PAINT: u128 = 1;
+const GROUP: u128 = 2;
+const CHILD: u128 = 3;
+const MASK: u128 = 4;
+const INNER: u128 = 5;
+
+fn uuid(id: u128) -> Uuid {
+    Uuid::from_u128(id)
+}
+
+fn write(test_name: &str) -> TempPath {
+    let masked = LayerBuilder::paint("Masked")
+        .attribute("onionskin", "0")
+        .with_mask(LayerBuilder::mask("Mask & more", "transparencymask"));
+    let group = LayerBuilder::group("Group")
+        .child(masked)
+        .child(LayerBuilder::group("Inner"));
+    DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Paint"))
+        .layer(group)
+        .write_temp(test_name)
+        .unwrap()
+}
+
+// Names of the nodes, with children in brackets
+fn tree(nodes: &[Node]) -> String {
+    nodes
+        .iter()
+        .map(|node| {
+            let children: &[Node] = match node.node_type() {
+                NodeType::GroupLayer(props) => props.layers(),
+                _ => node.masks(),
+            };
+            match children.is_empty() {
+                true => node.name().to_owned(),
+                false => format!("{}[{}]", node.name(), tree(children)),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn write_and_read(file: &mut KraFile, test_name: &str) -> KraFile {
+    let out = TempPath::new(&format!("kra-{}-out-{}.kra", test_name, process::id()));
+    file.write(File::create(&out).unwrap(), &WriteOptions::new())
+        .unwrap();
+    KraFile::read(&out).unwrap()
+}
+
+#[test]
+fn move_nodes() {
+    let mut file = KraFile::read(write("move_nodes")).unwrap();
+    assert_eq!(
+        tree(file.layers()),
+        "Paint, Group[Masked[Mask & more], Inner]"
+    );
+
+    // Into a group
+    file.move_node(&uuid(PAINT), Some(&uuid(INNER)), 0).unwrap();
+    assert_eq!(
+        tree(file.layers()),
+        "Group[Masked[Mask & more], Inner[Paint]]"
+    );
+    // Reorder within the same parent
+    file.move_node(&uuid(INNER), Some(&uuid(GROUP)), 0).unwrap();
+    assert_eq!(
+        tree(file.layers()),
+        "Group[Inner[Paint], Masked[Mask & more]]"
+    );
+    // Out to the top level
+    file.move_node(&uuid(CHILD), None, 1).unwrap();
+    assert_eq!(
+        tree(file.layers()),
+        "Group[Inner[Paint]], Masked[Mask & more]"
+    );
+    // A mask to another layer
+    file.move_node(&uuid(MASK), Some(&uuid(PAINT)), 0).unwrap();
+    assert_eq!(
+        tree(file.layers()),
+        "Group[Inner[Paint[Mask & more]]], Masked"
+    );
+
+    let written = write_and_read(&mut file, "move_nodes");
+    assert_eq!(
+        tree(written.layers()),
+        "Group[Inner[Paint[Mask & more]]], Masked"
+    );
+    // Attributes that are not parsed are kept
+    let masked = &written.layers()[1];
+    assert!(masked
+        .attributes()
+        .contains(&("onionskin".to_owned(), "0".to_owned())));
+    // The rest of maindoc.xml is kept
+    assert_eq!(written.meta().name(), "Unnamed");
+    assert_eq!(written.meta().width(), 128);
+}
+
+#[test]
+fn unparsed_elements_are_kept() {
+    let layer = LayerBuilder::paint("Vendor")
+        .element(r#"<vendor:data version="2"><entry key="a">text</entry></vendor:data>"#)
+        .element("<keyframes/>")
+        .with_mask(
+            LayerBuilder::mask("Mask", "transparencymask").element(r#"<future value="1"/>"#),
+        );
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Paint"))
+        .layer(layer)
+        .write_temp("unparsed_elements_are_kept")
+        .unwrap();
+    let mut file = KraFile::read(path).unwrap();
+    let vendor = [
+        r#"<vendor:data version="2"><entry key="a">text</entry></vendor:data>"#,
+        "<keyframes/>",
+    ];
+    assert_eq!(file.layers()[1].unparsed_elements(), vendor);
+    assert_eq!(
+        file.layers()[1].masks()[0].unparsed_elements(),
+        [r#"<future value="1"/>"#]
+    );
+
+    file.move_node(&uuid(PAINT), None, 1).unwrap();
+    let written = write_and_read(&mut file, "unparsed_elements_are_kept");
+    assert_eq!(tree(written.layers()), "Vendor[Mask], Paint");
+    assert_eq!(written.layers()[0].unparsed_elements(), vendor);
+    assert_eq!(
+        written.layers()[0].masks()[0].unparsed_elements(),
+        [r#"<future value="1"/>"#]
+    );
+    assert!(written.layers()[1].unparsed_elements().is_empty());
+}
+
+#[test]
+fn invalid_moves() {
+    let mut file = KraFile::read(write("invalid_moves")).unwrap();
+    let before = tree(file.layers());
+
+    let (group, mask, inner, paint) = (uuid(GROUP), uuid(MASK), uuid(INNER), uuid(PAINT));
+    assert!(matches!(
+        file.move_node(&group, Some(&inner), 0),
+        Err(EditError::Cycle(id)) if id == group
+    ));
+    assert!(matches!(
+        file.move_node(&group, Some(&group), 0),
+        Err(EditError::Cycle(id)) if id == group
+    ));
+    assert!(matches!(
+        file.move_node(&mask, None, 0),
+        Err(EditError::InvalidParent { node, parent: None }) if node == mask
+    ));
+    assert!(matches!(
+        file.move_node(&mask, Some(&group), 0),
+        Err(EditError::InvalidParent { node, parent: Some(parent) })
+            if node == mask && parent == group
+    ));
+    assert!(matches!(
+        file.move_node(&inner, Some(&paint), 0),
+        Err(EditError::InvalidParent { node, parent: Some(parent) })
+            if node == inner && parent == paint
+    ));
+    assert!(matches!(
+        file.move_node(&paint, None, 2),
+        Err(EditError::IndexOutOfBounds { index: 2, len: 1 })
+    ));
+    assert!(matches!(
+        file.move_node(&paint, Some(&group), 3),
+        Err(EditError::IndexOutOfBounds { index: 3, len: 2 })
+    ));
+    let unknown = Uuid::from_u128(99);
+    assert!(matches!(
+        file.move_node(&unknown, None, 0),
+        Err(EditError::UnknownNode(id)) if id == unknown
+    ));
+    assert!(matches!(
+        file.move_node(&paint, Some(&unknown), 0),
+        Err(EditError::UnknownNode(id)) if id == unknown
+    ));
+    assert_eq!(tree(file.layers()), before);
+}
+
+#[test]
+fn nesting_limit() {
+    let depth = Limits::default().max_group_depth;
+    let mut nested = LayerBuilder::group("Level");
+    for _ in 1..depth {
+        nested = LayerBuilder::group("Level").child(nested);
+    }
+    // The levels come first, the deepest one last
+    let (deepest, paint, group) = (
+        uuid(depth as u128),
+        uuid(depth as u128 + 1),
+        uuid(depth as u128 + 2),
+    );
+    let path = DocumentBuilder::new(128, 128)
+        .layer(nested)
+        .layer(LayerBuilder::paint("Paint"))
+        .layer(LayerBuilder::group("Group"))
+        .write_temp("nesting_limit")
+        .unwrap();
+    let mut file = KraFile::read(&path).unwrap();
+
+    // A layer still fits into the deepest group, another group does not
+    file.move_node(&paint, Some(&deepest), 0).unwrap();
+    assert!(matches!(
+        file.move_node(&group, Some(&deepest), 0),
+        Err(EditError::NestingTooDeep(limit)) if limit == depth
+    ));
+
+    // The limit comes from the configuration the file was read with
+    let limits = Limits {
+        max_group_depth: depth + 1,
+        ..Default::default()
+    };
+    let mut file =
+        KraFile::read_with_config(&path, ParsingConfiguration::new().limits(limits)).unwrap();
+    file.move_node(&group, Some(&deepest), 0).unwrap();
+}
+
+#[test]
+fn unchanged_maindoc_is_copied() {
+    let path = write("unchanged_maindoc_is_copied");
+    let mut file = KraFile::read(&path).unwrap();
+    let written = write_and_read(&mut file, "unchanged_maindoc_is_copied");
+    assert_eq!(tree(written.layers()), tree(file.layers()));
+}
+
+// The upper layer, over a layer with blue and green tiles, and an empty group
+fn write_pair(test_name: &str, upper: LayerBuilder) -> TempPath {
+    let lower = LayerBuilder::paint("Lower")
+        .uuid(uuid(CHILD))
+        .fill_tile(0, 0, [255, 0, 0, 255])
+        .fill_tile(64, 64, [0, 255, 0, 255]);
+    DocumentBuilder::new(128, 128)
+        .layer(upper.uuid(uuid(PAINT)))
+        .layer(lower)
+        .layer(LayerBuilder::group("Group").uuid(uuid(GROUP)))
+        .write_temp(test_name)
+        .unwrap()
+}
+
+// BGRA pixel at image coordinates
+fn pixel(data: &LayerData, x: i32, y: i32) -> [u8; 4] {
+    let PixelBuffer::U8(pixels) = data.pixels() else {
+        panic!("expected 8-bit pixels");
+    };
+    let index = ((y - data.y()) as usize * data.width() as usize + (x - data.x()) as usize) * 4;
+    pixels[index..index + 4].try_into().unwrap()
+}
+
+#[test]
+fn merge_down() {
+    // Red, half transparent through opacity, and moved by half a tile
+    let upper = LayerBuilder::paint("Upper")
+        .offset(32, 0)
+        .opacity(128)
+        .fill_tile(0, 0, [0, 0, 255, 255]);
+    let path = write_pair("merge_down", upper);
+    let mut file = KraFile::read(&path).unwrap();
+    file.merge_down(&uuid(PAINT)).unwrap();
+    assert_eq!(tree(file.layers()), "Lower, Group");
+    assert!(file.files().get(&uuid(PAINT)).is_none());
+    assert!(file
+        .orphaned_entries()
+        .iter()
+        .any(|entry| entry.ends_with("layers/layer1")));
+
+    let data = file.decode_layer(&uuid(CHILD)).unwrap();
+    assert_eq!(
+        (data.x(), data.y(), data.width(), data.height()),
+        (0, 0, 128, 128)
+    );
+    // Only the lower layer
+    assert_eq!(pixel(&data, 10, 10), [255, 0, 0, 255]);
+    // Red over blue
+    assert_eq!(pixel(&data, 40, 10), [127, 0, 128, 255]);
+    // Red over nothing
+    assert_eq!(pixel(&data, 80, 10), [0, 0, 255, 128]);
+    assert_eq!(pixel(&data, 100, 10), [0, 0, 0, 0]);
+    assert_eq!(pixel(&data, 100, 100), [0, 255, 0, 255]);
+    assert_eq!(pixel(&data, 10, 100), [0, 0, 0, 0]);
+
+    let mut written = write_and_read(&mut file, "merge_down");
+    assert_eq!(tree(written.layers()), "Lower, Group");
+    assert_eq!(written.decode_layer(&uuid(CHILD)).unwrap(), data);
+}
+
+#[test]
+fn merge_down_blending() {
+    let upper = LayerBuilder::paint("Upper")
+        .composite_op("multiply")
+        .fill_tile(0, 0, [128, 128, 128, 255]);
+    let path = write_pair("merge_down_blending", upper);
+    let mut file = KraFile::read(&path).unwrap();
+    file.merge_down(&uuid(PAINT)).unwrap();
+    let data = file.decode_layer(&uuid(CHILD)).unwrap();
+    assert_eq!(pixel(&data, 0, 0), [128, 0, 0, 255]);
+}
+
+#[test]
+fn merge_down_hidden() {
+    let upper = LayerBuilder::paint("Upper")
+        .visible(false)
+        .fill_tile(0, 0, [0, 0, 255, 255]);
+    let path = write_pair("merge_down_hidden", upper);
+    let mut file = KraFile::read(&path).unwrap();
+    let before = file.decode_layer(&uuid(CHILD)).unwrap();
+    file.merge_down(&uuid(PAINT)).unwrap();
+    assert_eq!(file.decode_layer(&uuid(CHILD)).unwrap(), before);
+}
+
+#[test]
+fn invalid_merges() {
+    let upper = LayerBuilder::paint("Upper").composite_op("hue");
+    let path = write_pair("invalid_merges", upper);
+    let mut file = KraFile::read(&path).unwrap();
+    assert!(matches!(
+        file.merge_down(&uuid(PAINT)),
+        Err(EditError::UnsupportedCompositeOp(CompositeOp::Hue))
+    ));
+    // A group is below
+    assert!(matches!(
+        file.merge_down(&uuid(CHILD)),
+        Err(EditError::NotPaintLayer(id)) if id == uuid(GROUP)
+    ));
+    assert!(matches!(
+        file.merge_down(&uuid(GROUP)),
+        Err(EditError::NotPaintLayer(id)) if id == uuid(GROUP)
+    ));
+    file.move_node(&uuid(GROUP), None, 0).unwrap();
+    assert!(matches!(
+        file.merge_down(&uuid(CHILD)),
+        Err(EditError::NoLayerBelow(id)) if id == uuid(CHILD)
+    ));
+    assert_eq!(tree(file.layers()), "Group, Upper, Lower");
+}
+
+#[test]
+fn split_channels() {
+    let upper = LayerBuilder::paint("Upper")
+        .offset(16, 32)
+        .fill_tile(0, 0, [10, 20, 30, 200]);
+    let path = write_pair("split_channels", upper);
+    let mut file = KraFile::read(&path).unwrap();
+    let created = file.split_channels(&uuid(PAINT)).unwrap();
+    assert_eq!(created.len(), 3);
+    assert_eq!(tree(file.layers()), "Red, Green, Blue, Upper, Lower, Group");
+
+    let filenames: Vec<&str> = file.layers().iter().map(|node| node.filename()).collect();
+    assert_eq!(
+        filenames,
+        ["layer4", "layer5", "layer6", "layer1", "layer2", "layer3"]
+    );
+    let check = |file: &mut KraFile| {
+        for (id, value) in created.iter().zip([30, 20, 10]) {
+            let data = file.decode_layer(id).unwrap();
+            assert_eq!(data.colorspace(), Colorspace::GRAYA);
+            assert_eq!((data.x(), data.y()), (16, 32));
+            let PixelBuffer::U8(pixels) = data.pixels() else {
+                panic!("expected 8-bit pixels");
+            };
+            assert_eq!(&pixels[..2], [value, 200]);
+        }
+    };
+    check(&mut file);
+
+    let mut written = write_and_read(&mut file, "split_channels");
+    assert_eq!(
+        tree(written.layers()),
+        "Red, Green, Blue, Upper, Lower, Group"
+    );
+    assert_eq!(written.layers()[0].uuid(), created[0]);
+    assert!(written.orphaned_entries().is_empty());
+    check(&mut written);
+}
+
+#[test]
+fn split_channels_of_group() {
+    let mut file = KraFile::read(write("split_channels_of_group")).unwrap();
+    assert!(matches!(
+        file.split_channels(&uuid(GROUP)),
+        Err(EditError::NotPaintLayer(id)) if id == uuid(GROUP)
+    ));
+}
+
+#[test]
+fn rename_layers() {
+    let mut file = KraFile::read(write("rename_layers")).unwrap();
+    let pattern = Regex::new(r"^(\w+) & (\w+)$").unwrap();
+    assert_eq!(file.rename_layers(&pattern, "${2}_$1"), [uuid(MASK)]);
+    let pattern = Regex::new("a").unwrap();
+    assert_eq!(
+        file.rename_layers(&pattern, "A"),
+        [uuid(PAINT), uuid(CHILD), uuid(MASK)]
+    );
+    assert!(file.rename_layers(&pattern, "A").is_empty());
+    assert!(matches!(
+        file.rename_node(&Uuid::from_u128(0), "Missing"),
+        Err(EditError::UnknownNode(_))
+    ));
+
+    let file = write_and_read(&mut file, "rename_layers");
+    assert_eq!(
+        tree(file.layers()),
+        "PAint, Group[MAsked[more_MAsk], Inner]"
+    );
+}
+
+#[test]
+fn doc_info_fields() {
+    let mut file = KraFile::read(write("doc_info_fields")).unwrap();
+    file.set_doc_info(DocInfoField::Title, "Cover <draft>");
+    file.set_doc_info(DocInfoField::Company, "Studio");
+    let pattern = Regex::new("draft").unwrap();
+    assert_eq!(
+        file.replace_in_doc_info(&pattern, "final"),
+        [DocInfoField::Title]
+    );
+    assert_eq!(file.doc_info().field(DocInfoField::Title), "Cover <final>");
+
+    // The node tree is kept as it was
+    let written = write_and_read(&mut file, "doc_info_fields");
+    assert_eq!(written.doc_info(), file.doc_info());
+    assert_eq!(written.doc_info().author().company(), "Studio");
+    assert_eq!(written.doc_info().about().date(), "2024-01-01T00:00:00");
+    assert_eq!(tree(written.layers()), tree(file.layers()));
+}
+
+#[test]
+fn text_layer() {
+    let mut file = KraFile::read(write("text_layer")).unwrap();
+    let text = TextLayer::new("Fish & chips\nSecond line")
+        .font("Noto Sans")
+        .size(24.0)
+        .position(50.0, 100.0)
+        .color([255, 0, 128]);
+    let id = file.add_text_layer(&text).unwrap();
+
+    let out = TempPath::new(&format!("kra-text_layer-out-{}.kra", process::id()));
+    file.write(File::create(&out).unwrap(), &WriteOptions::new())
+        .unwrap();
+    let file = KraFile::read(&out).unwrap();
+    let layer = &file.layers()[0];
+    assert_eq!(layer.uuid(), id);
+    assert_eq!(layer.name(), "Fish & chips");
+    assert!(matches!(layer.node_type(), NodeType::VectorLayer(_)));
+    let entry = "Unnamed/layers/layer6.shapelayer/content.svg";
+    assert_eq!(file.node_entries()[&id], [entry]);
+
+    let mut archive = zip::ZipArchive::new(File::open(&out).unwrap()).unwrap();
+    let svg = std::io::read_to_string(archive.by_name(entry).unwrap()).unwrap();
+    // 300 DPI, so 72 points are 300 pixels
+    assert!(svg.contains(r#"width="30.72pt""#));
+    assert!(svg.contains(r##"transform="translate(12, 24)" fill="#ff0080""##));
+    assert!(svg.contains("font-family: Noto Sans;font-size: 24;"));
+    assert!(svg.contains(
+        r#"<tspan x="0">Fish &amp; chips</tspan><tspan x="0" dy="1.2em">Second line</tspan>"#
+    ));
+}
+
+#[test]
+fn unused_filenames() {
+    let fixture =
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/group-and-mask.kra");
+    let mut file = KraFile::read(fixture).unwrap();
+    assert_eq!(file.unused_filename(NodeKind::GroupLayer), "layer6");
+    assert_eq!(file.unused_filename(NodeKind::TransparencyMask), "mask2");
+
+    file.split_channels(&file.layers()[1].uuid()).unwrap();
+    assert_eq!(file.unused_filename(NodeKind::PaintLayer), "layer9");
+    assert_eq!(file.unused_filename(NodeKind::FilterMask), "mask2");
+}
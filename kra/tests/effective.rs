@@ -0,0 +1,64 @@
+use kra::{
+    id::Uuid,
+    testing::{DocumentBuilder, LayerBuilder},
+    KraFile,
+};
+
+// UUIDs of the nodes, in document order
+const TOP: u128 = 1;
+const OUTER: u128 = 2;
+const HALF: u128 = 3;
+const MASK: u128 = 4;
+const HIDDEN_GROUP: u128 = 5;
+const INSIDE_HIDDEN: u128 = 6;
+
+fn uuid(value: u128) -> Uuid {
+    Uuid::from_u128(value)
+}
+
+fn read(test_name: &str) -> KraFile {
+    let half = LayerBuilder::paint("Half")
+        .opacity(51)
+        .with_mask(LayerBuilder::mask("Mask", "transparencymask"));
+    let hidden = LayerBuilder::group("Hidden")
+        .visible(false)
+        .child(LayerBuilder::paint("Inside hidden"));
+    let outer = LayerBuilder::group("Outer")
+        .opacity(102)
+        .attribute("passthrough", "1")
+        .child(half)
+        .child(hidden);
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Top"))
+        .layer(outer)
+        .write_temp(test_name)
+        .unwrap();
+    KraFile::read(path).unwrap()
+}
+
+#[test]
+fn visibility_of_ancestors() {
+    let file = read("effective_visibility");
+    assert_eq!(file.effective_visibility(&uuid(TOP)), Some(true));
+    assert_eq!(file.effective_visibility(&uuid(MASK)), Some(true));
+    assert_eq!(file.effective_visibility(&uuid(HIDDEN_GROUP)), Some(false));
+    // Visible by itself, but inside of a hidden group
+    assert_eq!(file.effective_visibility(&uuid(INSIDE_HIDDEN)), Some(false));
+    assert_eq!(file.effective_visibility(&Uuid::from_u128(0)), None);
+}
+
+#[test]
+fn opacity_of_ancestors() {
+    let file = read("effective_opacity");
+    let close = |uuid: u128, expected: f32| {
+        let opacity = file.effective_opacity(&self::uuid(uuid)).unwrap();
+        assert!((opacity - expected).abs() < 1e-6, "{opacity} != {expected}");
+    };
+    close(TOP, 1.0);
+    close(OUTER, 0.4);
+    // Opacity of the pass-through group applies to its layers
+    close(HALF, 0.4 * 0.2);
+    close(MASK, 0.4 * 0.2);
+    close(INSIDE_HIDDEN, 0.0);
+    assert_eq!(file.effective_opacity(&Uuid::from_u128(0)), None);
+}
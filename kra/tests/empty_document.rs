@@ -0,0 +1,67 @@
+use kra::{
+    config::ParsingConfiguration,
+    layer::NodeType,
+    testing::{DocumentBuilder, LayerBuilder, TempPath},
+    KraFile,
+};
+
+// Document without layers, with `<layers/>` instead of an empty element
+fn self_closing_layers(test_name: &str) -> TempPath {
+    let document = DocumentBuilder::new(128, 128);
+    let maindoc = document
+        .maindoc()
+        .replace("<layers>\n  </layers>", "<layers/>");
+    assert!(maindoc.contains("<layers/>"));
+    document
+        .entry("maindoc.xml", maindoc)
+        .write_temp(test_name)
+        .unwrap()
+}
+
+#[test]
+fn empty_layers_element() {
+    let path = DocumentBuilder::new(128, 128)
+        .write_temp("empty_layers_element")
+        .unwrap();
+    let file = KraFile::read(&path).unwrap();
+    assert!(file.layers().is_empty());
+    assert!(file.files().is_empty());
+}
+
+#[test]
+fn self_closing_layers_element() {
+    let path = self_closing_layers("self_closing_layers_element");
+    let file = KraFile::read(&path).unwrap();
+    assert!(file.layers().is_empty());
+    assert!(file.files().is_empty());
+    assert_eq!(file.meta().width(), 128);
+}
+
+#[test]
+fn self_closing_layers_element_skipped() {
+    let path = self_closing_layers("self_closing_layers_element_skipped");
+    let file = KraFile::read_with_config(&path, ParsingConfiguration::metadata_only()).unwrap();
+    assert!(file.layers().is_empty());
+}
+
+#[test]
+fn group_with_self_closing_layers_element() {
+    let document = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::group("Group"))
+        .layer(LayerBuilder::paint("Paint"));
+    let maindoc = document
+        .maindoc()
+        .replace("<layers>\n</layers>", "<layers/>");
+    assert!(maindoc.contains("<layers/>"));
+    let path = document
+        .entry("maindoc.xml", maindoc)
+        .write_temp("group_with_self_closing_layers_element")
+        .unwrap();
+    let file = KraFile::read(&path).unwrap();
+    assert_eq!(file.layers().len(), 2);
+    match file.layers()[0].node_type() {
+        NodeType::GroupLayer(group) => assert!(group.layers().is_empty()),
+        other => panic!("expected a group layer, got {other:?}"),
+    }
+    assert_eq!(file.layers()[1].name(), "Paint");
+}
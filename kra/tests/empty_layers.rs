@@ -0,0 +1,59 @@
+use kra::{
+    id::Uuid,
+    testing::{DocumentBuilder, LayerBuilder},
+    KraFile,
+};
+
+#[test]
+fn find_empty_layers() {
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Painted").fill_tile(0, 0, [0, 0, 255, 255]))
+        .layer(LayerBuilder::paint("Empty").default_pixel([0, 0, 0, 0]))
+        .layer(
+            LayerBuilder::paint("Hidden")
+                .visible(false)
+                .fill_tile(64, 0, [0, 0, 255, 255]),
+        )
+        .layer(LayerBuilder::paint("Filled").default_pixel([255, 255, 255, 255]))
+        .write_temp("find_empty_layers")
+        .unwrap();
+
+    let mut file = KraFile::read(&path).unwrap();
+    let report = file.find_empty_layers().unwrap();
+    assert_eq!(report.empty(), &[Uuid::from_u128(2)]);
+    assert_eq!(report.hidden_with_content(), &[Uuid::from_u128(3)]);
+}
+
+#[test]
+fn find_occluded_layers() {
+    let uuid = |n: u128| Uuid::from_u128(0x100 + n);
+    let layer = |name: &str, n: u128| LayerBuilder::paint(name).uuid(uuid(n));
+    let opaque = [0, 0, 255, 255];
+    let group = LayerBuilder::group("Faded")
+        .uuid(uuid(5))
+        .opacity(128)
+        .child(layer("Inner cover", 6).fill_tile(0, 64, opaque))
+        .child(layer("Inner behind", 7).fill_tile(0, 64, opaque));
+    let path = DocumentBuilder::new(128, 128)
+        .layer(layer("Cover", 1).fill_tile(0, 0, opaque))
+        .layer(
+            layer("Tint", 2)
+                .composite_op("multiply")
+                .fill_tile(64, 0, opaque),
+        )
+        .layer(layer("Behind", 3).fill_tile(0, 0, [0, 255, 0, 100]))
+        .layer(
+            layer("Peeking", 4)
+                .fill_tile(0, 0, opaque)
+                .fill_tile(64, 0, opaque),
+        )
+        .layer(layer("Hidden", 8).visible(false).fill_tile(64, 64, opaque))
+        .layer(group)
+        .layer(layer("Below group", 9).fill_tile(0, 64, opaque))
+        .write_temp("find_occluded_layers")
+        .unwrap();
+
+    let mut file = KraFile::read(&path).unwrap();
+    let occluded = file.find_occluded_layers().unwrap();
+    assert_eq!(occluded, [uuid(3), uuid(7)]);
+}
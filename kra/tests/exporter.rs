@@ -0,0 +1,93 @@
+#![cfg(feature = "image")]
+
+use std::{fs, process};
+
+use kra::{
+    error::ExportError,
+    exporter::{ExportJob, ExportSpec, ExportStatus},
+    testing::{DocumentBuilder, LayerBuilder, TempPath},
+};
+
+fn write_document(test_name: &str) -> TempPath {
+    DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Sky").fill_tile(0, 0, [255, 0, 0, 255]))
+        .layer(
+            LayerBuilder::group("Trees")
+                .visible(false)
+                .child(LayerBuilder::paint("Oak").fill_tile(0, 0, [0, 255, 0, 255])),
+        )
+        .write_temp(test_name)
+        .unwrap()
+}
+
+#[test]
+fn export_and_resume() {
+    let document = write_document("exporter_resume");
+    let missing = TempPath::new("kra-exporter-missing.kra");
+    let output = TempPath::new(&format!("kra-exporter-out-{}", process::id()));
+    let index = TempPath::new(&format!("kra-exporter-{}.index", process::id()));
+    let _ = fs::remove_dir_all(&output);
+    let _ = fs::remove_file(&index);
+
+    let job = ExportJob::new([&document, &missing], &output, ExportSpec::new())
+        .threads(2)
+        .resume_index(&index);
+    let mut finished = Vec::new();
+    let report = job
+        .run(|_, progress| finished.push((progress.finished(), progress.total())))
+        .unwrap();
+    assert_eq!(finished, [(1, 2), (2, 2)]);
+    assert!(!report.is_success());
+
+    let directory = output.join(document.file_stem().unwrap());
+    let exported = report
+        .items()
+        .iter()
+        .find(|item| item.path() == &*document)
+        .unwrap();
+    let ExportStatus::Exported(files) = exported.status() else {
+        panic!("document was not exported: {:?}", exported.status());
+    };
+    assert_eq!(files.len(), 2);
+    assert!(files.contains(&directory.join("Sky.png")));
+    assert!(files.contains(&directory.join("Trees/Oak.png")));
+    let image = image::open(directory.join("Trees/Oak.png"))
+        .unwrap()
+        .to_rgba8();
+    assert_eq!(image.get_pixel(0, 0).0, [0, 255, 0, 255]);
+
+    let [failed] = report.failed().collect::<Vec<_>>()[..] else {
+        panic!("expected one failed document");
+    };
+    assert_eq!(failed.path(), &*missing);
+    assert!(matches!(
+        failed.status(),
+        ExportStatus::Failed(ExportError::ReadError(_))
+    ));
+
+    // Exported documents are recorded, and skipped when the job runs again
+    let report = job.run(|_, _| {}).unwrap();
+    let statuses: Vec<_> = report.items().iter().map(|item| item.status()).collect();
+    assert!(matches!(
+        statuses[..],
+        [ExportStatus::Skipped, ExportStatus::Failed(_)]
+    ));
+}
+
+#[test]
+fn visible_only() {
+    let document = write_document("exporter_visible_only");
+    let output = TempPath::new(&format!("kra-exporter-visible-{}", process::id()));
+    let _ = fs::remove_dir_all(&output);
+    let spec = ExportSpec::new()
+        .template("{name}.png".parse().unwrap())
+        .visible_only(true);
+    let report = ExportJob::new([&document], &output, spec)
+        .run(|_, _| {})
+        .unwrap();
+    let ExportStatus::Exported(files) = report.items()[0].status() else {
+        panic!("document was not exported");
+    };
+    let directory = output.join(document.file_stem().unwrap());
+    assert_eq!(files, &[directory.join("Sky.png")]);
+}
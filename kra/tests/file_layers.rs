@@ -0,0 +1,121 @@
+use std::{env, fs, process};
+
+use kra::{
+    config::ParsingConfiguration,
+    id::Uuid,
+    testing::{DocumentBuilder, LayerBuilder, TempPath},
+    KraFile,
+};
+
+// Document with file layers that link to the sources
+fn write(test_name: &str, layers: &[(&str, &str)]) -> TempPath {
+    layers
+        .iter()
+        .fold(
+            DocumentBuilder::new(128, 128),
+            |document, (name, source)| document.layer(LayerBuilder::file_layer(name, source)),
+        )
+        .write_temp(test_name)
+        .unwrap()
+}
+
+// Name of the document that write_temp() gives for the test name
+fn document_name(test_name: &str) -> String {
+    format!("kra-{}-{}.kra", test_name, process::id())
+}
+
+#[test]
+fn file_layer_links() {
+    let inner = write("links_inner", &[("Back", &document_name("links_outer"))]);
+    let outer = write(
+        "links_outer",
+        &[
+            ("Inner", &document_name("links_inner")),
+            ("Missing", "missing.png"),
+        ],
+    );
+
+    let file = KraFile::read(&outer).unwrap();
+    let links = file.file_layer_links().unwrap();
+    let (inner_path, outer_path) = (
+        fs::canonicalize(&inner).unwrap(),
+        fs::canonicalize(&outer).unwrap(),
+    );
+    let summary: Vec<_> = links
+        .iter()
+        .map(|link| (link.document(), link.source(), link.is_cycle()))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (None, inner_path.as_path(), false),
+            (Some(inner_path.as_path()), outer_path.as_path(), true),
+            (None, env::temp_dir().join("missing.png").as_path(), false),
+        ]
+    );
+    assert!(links[0].is_document() && !links[2].is_document());
+    // The layer of the inner document
+    assert_eq!(links[1].layer(), Uuid::from_u128(1));
+
+    let limits = kra::config::Limits {
+        max_file_layer_depth: 0,
+        ..Default::default()
+    };
+    let file = KraFile::read_with_config(&outer, ParsingConfiguration::new().limits(limits));
+    assert!(matches!(
+        file.unwrap().file_layer_links(),
+        Err(kra::error::DataError::LimitExceeded(_))
+    ));
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn linked_documents() {
+    use image::{ImageFormat, Rgba, RgbaImage};
+    use kra::error::DataError;
+
+    let mut png = Vec::new();
+    RgbaImage::from_pixel(128, 128, Rgba([0, 0, 255, 255]))
+        .write_to(&mut std::io::Cursor::new(&mut png), ImageFormat::Png)
+        .unwrap();
+    let inner = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::file_layer(
+            "Self",
+            &document_name("linked_inner"),
+        ))
+        .entry("mergedimage.png", png)
+        .write_temp("linked_inner")
+        .unwrap();
+    let outer = write("linked_outer", &[("Inner", &document_name("linked_inner"))]);
+
+    let mut file = KraFile::read(&outer).unwrap();
+    let data = file.decode_file_layer(&Uuid::from_u128(1)).unwrap();
+    let pixels = data.to_rgba_image().unwrap();
+    assert_eq!(pixels.dimensions(), (128, 128));
+    assert_eq!(pixels.get_pixel(5, 5), &Rgba([0, 0, 255, 255]));
+
+    // The inner document links to itself
+    let mut file = KraFile::read(&inner).unwrap();
+    assert!(matches!(
+        file.decode_file_layer(&Uuid::from_u128(1)),
+        Err(DataError::LinkCycle(_))
+    ));
+}
+
+#[test]
+fn file_layer_depth_default() {
+    // Each document links to the next one, further than the default allows
+    let chain: Vec<_> = (0..18)
+        .map(|index| {
+            let next = document_name(&format!("chain_{}", index + 1));
+            write(&format!("chain_{index}"), &[("Next", &next)])
+        })
+        .collect();
+    let file = KraFile::read(&chain[0]).unwrap();
+    assert!(matches!(
+        file.file_layer_links(),
+        Err(kra::error::DataError::LimitExceeded(
+            kra::error::LimitError::FileLayerDepth(16)
+        ))
+    ));
+}
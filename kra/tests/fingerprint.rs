@@ -0,0 +1,98 @@
+use std::{fs, path::Path};
+
+use kra::{
+    error::ReadKraError,
+    fingerprint::{read_fingerprint, read_fingerprint_with_layers},
+    layer::{Node, NodeType},
+    testing::{DocumentBuilder, LayerBuilder},
+    KraFile,
+};
+
+fn count_layers(nodes: &[Node]) -> usize {
+    nodes
+        .iter()
+        .filter(|node| !node.kind().is_mask())
+        .map(|node| match node.node_type() {
+            NodeType::GroupLayer(props) => 1 + count_layers(props.layers()),
+            _ => 1,
+        })
+        .sum()
+}
+
+#[test]
+fn matches_full_read() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "kra") {
+            continue;
+        }
+        let fingerprint = read_fingerprint_with_layers(&path).unwrap();
+        let file = KraFile::read(&path).unwrap();
+        let meta = file.meta();
+        assert_eq!(
+            fingerprint.dimensions(),
+            (meta.width(), meta.height()),
+            "{}",
+            path.display()
+        );
+        assert_eq!(fingerprint.krita_version(), meta.krita_version());
+        assert_eq!(fingerprint.layer_count(), Some(count_layers(file.layers())));
+        assert_eq!(
+            read_fingerprint(&path).unwrap().dimensions(),
+            (meta.width(), meta.height())
+        );
+    }
+}
+
+#[test]
+fn nested_layers() {
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Top"))
+        .layer(LayerBuilder::group("Group").child(LayerBuilder::paint("Inner")))
+        .write_temp("fingerprint_nested_layers")
+        .unwrap();
+    let fingerprint = read_fingerprint(&path).unwrap();
+    assert_eq!(fingerprint.dimensions(), (128, 128));
+    assert_eq!(fingerprint.krita_version(), "5.2.2");
+    assert_eq!(fingerprint.layer_count(), None);
+    let counted = read_fingerprint_with_layers(&path).unwrap();
+    assert_eq!(counted.layer_count(), Some(3));
+    assert_eq!(counted.modified(), fingerprint.modified());
+    // YYYY-MM-DDTHH:MM:SS
+    let modified = fingerprint.modified().as_bytes();
+    assert_eq!(modified.len(), 19);
+    assert_eq!(
+        (modified[4], modified[10], modified[13]),
+        (b'-', b'T', b':')
+    );
+}
+
+#[test]
+fn only_start_of_maindoc_is_read() {
+    // Many layers, and broken XML after them
+    let mut document = DocumentBuilder::new(128, 128);
+    for index in 0..2000 {
+        document = document.layer(LayerBuilder::paint(&format!("Layer {}", index)));
+    }
+    let maindoc = document.maindoc().replace("</IMAGE>", "</MISMATCH>");
+    let path = document
+        .entry("maindoc.xml", maindoc)
+        .write_temp("fingerprint_only_start")
+        .unwrap();
+    let fingerprint = read_fingerprint(&path).unwrap();
+    assert_eq!(fingerprint.dimensions(), (128, 128));
+    assert!(read_fingerprint_with_layers(&path).is_err());
+}
+
+#[test]
+fn wrong_mimetype() {
+    let path = DocumentBuilder::new(128, 128)
+        .entry("mimetype", b"image/openraster".to_vec())
+        .write_temp("fingerprint_wrong_mimetype")
+        .unwrap();
+    assert!(matches!(
+        read_fingerprint(path),
+        Err(ReadKraError::MimetypeMismatch)
+    ));
+}
@@ -0,0 +1,29 @@
+use kra::{
+    id::Uuid,
+    layer::NodeKind,
+    testing::{DocumentBuilder, LayerBuilder},
+    KraFile,
+};
+
+#[test]
+fn unknown_children_of_layer() {
+    let mask = LayerBuilder::mask("Mask", "transparencymask").element("<future/>");
+    let path = DocumentBuilder::new(128, 128)
+        .layer(
+            LayerBuilder::paint("Paint")
+                .element(r#"<metadata><entry key="a">b</entry></metadata>"#)
+                .with_mask(mask)
+                .element("<extra/>"),
+        )
+        .layer(LayerBuilder::paint("Other").element(r#"<keyframes><frame time="0"/></keyframes>"#))
+        .write_temp("unknown_children_of_layer")
+        .unwrap();
+
+    let file = KraFile::read(&path).unwrap();
+    assert_eq!(file.layers().len(), 2);
+    let masks = file.layers()[0].masks();
+    assert_eq!(masks.len(), 1);
+    assert_eq!(masks[0].kind(), NodeKind::TransparencyMask);
+    assert_eq!(masks[0].uuid(), Uuid::from_u128(2));
+    assert!(file.layers()[1].masks().is_empty());
+}
@@ -0,0 +1,166 @@
+// Parses every fixture in tests/fixtures and compares a summary of its metadata
+// with the `.expected` file next to it.
+//
+// Run with KRA_BLESS=1 to rewrite the expected summaries after an intended change.
+
+use std::{
+    env,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use kra::{
+    layer::{Node, NodeType},
+    KraFile,
+};
+
+fn fixtures() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut fixtures: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "kra"))
+        .collect();
+    fixtures.sort();
+    fixtures
+}
+
+fn summarize_node(node: &Node, depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth);
+    write!(
+        out,
+        "{indent}{} {:?} {} file={} visible={} locked={} colorlabel={} at={},{}",
+        node.kind(),
+        node.name(),
+        node.uuid(),
+        node.filename(),
+        node.visible(),
+        node.locked(),
+        node.colorlabel(),
+        node.x(),
+        node.y(),
+    )
+    .unwrap();
+    if let Some(props) = node.as_layer() {
+        write!(
+            out,
+            " opacity={} op={:?} collapsed={}",
+            props.opacity(),
+            props.composite_op(),
+            props.collapsed()
+        )
+        .unwrap();
+    }
+    match node.node_type() {
+        NodeType::GroupLayer(props) => {
+            writeln!(out, " passthrough={}", props.passthrough()).unwrap();
+            for child in props.layers() {
+                summarize_node(child, depth + 1, out);
+            }
+        }
+        NodeType::FilterLayer(props) => writeln!(
+            out,
+            " filter={}@{}",
+            props.filter_name(),
+            props.filter_version()
+        )
+        .unwrap(),
+        NodeType::FillLayer(props) => writeln!(
+            out,
+            " generator={}@{}",
+            props.generator_name(),
+            props.generator_version()
+        )
+        .unwrap(),
+        NodeType::CloneLayer(props) => writeln!(
+            out,
+            " clone_from={:?} {}",
+            props.clone_from(),
+            props.clone_from_uuid()
+        )
+        .unwrap(),
+        _ => writeln!(out).unwrap(),
+    }
+    for mask in node.masks() {
+        summarize_node(mask, depth + 1, out);
+    }
+}
+
+fn summarize(file: &KraFile) -> String {
+    let meta = file.meta();
+    let mut out = String::new();
+    writeln!(out, "krita_version: {}", meta.krita_version()).unwrap();
+    writeln!(out, "name: {}", meta.name()).unwrap();
+    writeln!(out, "title: {}", file.doc_info().about().title()).unwrap();
+    writeln!(out, "size: {}x{}", meta.width(), meta.height()).unwrap();
+    writeln!(out, "resolution: {}x{}", meta.x_res(), meta.y_res()).unwrap();
+    writeln!(out, "colorspace: {}", meta.colorspace()).unwrap();
+    writeln!(out, "profile: {}", meta.profile()).unwrap();
+    writeln!(
+        out,
+        "mirror: horizontal={} vertical={}",
+        meta.mirror_axis().mirror_horizontal(),
+        meta.mirror_axis().mirror_vertical()
+    )
+    .unwrap();
+    writeln!(out, "nodes:").unwrap();
+    for node in file.layers() {
+        summarize_node(node, 1, &mut out);
+    }
+    out
+}
+
+#[test]
+fn golden_fixtures() {
+    let bless = env::var_os("KRA_BLESS").is_some();
+    let fixtures = fixtures();
+    assert!(!fixtures.is_empty());
+
+    for fixture in fixtures {
+        let file =
+            KraFile::read(&fixture).unwrap_or_else(|err| panic!("{}: {}", fixture.display(), err));
+        let summary = summarize(&file);
+        let expected_path = fixture.with_extension("expected");
+        if bless {
+            fs::write(&expected_path, &summary).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&expected_path)
+            .unwrap_or_else(|err| panic!("{}: {}", expected_path.display(), err));
+        assert_eq!(summary, expected, "{}", fixture.display());
+    }
+}
+
+// Compares the compositor with mergedimage.png of the fixtures that have one
+#[cfg(feature = "render")]
+#[test]
+fn rendered_fixtures() {
+    use kra::{error::DataError, testing::compare_render};
+    use zip::result::ZipError;
+
+    const TOLERANCE: u8 = 2;
+    let mut compared = 0;
+    for fixture in fixtures() {
+        let mut file = KraFile::read(&fixture).unwrap();
+        let comparison = match compare_render(&mut file, TOLERANCE) {
+            Ok(comparison) => comparison,
+            Err(DataError::Entry {
+                name,
+                source: ZipError::FileNotFound,
+            }) if name == "mergedimage.png" => continue,
+            Err(err) => panic!("{}: {}", fixture.display(), err),
+        };
+        let mismatches = comparison.mismatches();
+        assert!(
+            comparison.matches(),
+            "{}: {} pixels differ by up to {}, first ones: {:?}",
+            fixture.display(),
+            mismatches.len(),
+            comparison.max_delta(),
+            &mismatches[..mismatches.len().min(8)]
+        );
+        compared += 1;
+    }
+    assert!(compared > 0, "no fixture has mergedimage.png");
+}
@@ -0,0 +1,49 @@
+use std::{fs, process};
+
+use kra::{
+    cache::Index,
+    testing::{DocumentBuilder, LayerBuilder, TempPath},
+};
+
+#[test]
+fn reads_only_changed_documents() {
+    let document = DocumentBuilder::new(128, 128).layer(LayerBuilder::paint("Sketch"));
+    let path = document.write_temp("indexed").unwrap();
+    let mut index = Index::new();
+    assert!(!index.is_up_to_date(&path).unwrap());
+    assert_eq!(index.fingerprint(&path).unwrap().layer_count(), Some(1));
+    assert!(index.is_up_to_date(&path).unwrap());
+
+    // Kept between runs
+    let saved = TempPath::new(&format!("kra-index-{}.txt", process::id()));
+    index.save(&saved).unwrap();
+    let mut loaded = Index::load(&saved).unwrap();
+    assert_eq!(loaded, index);
+    assert!(loaded.is_up_to_date(&path).unwrap());
+
+    // Written again with the same contents
+    fs::write(&path, fs::read(&path).unwrap()).unwrap();
+    assert!(loaded.is_up_to_date(&path).unwrap());
+
+    let changed = document
+        .layer(LayerBuilder::paint("Ink"))
+        .write_temp("indexed_changed")
+        .unwrap();
+    fs::rename(changed, &path).unwrap();
+    assert!(!loaded.is_up_to_date(&path).unwrap());
+    assert_eq!(loaded.fingerprint(&path).unwrap().layer_count(), Some(2));
+
+    fs::remove_file(&path).unwrap();
+    loaded.prune();
+    assert_eq!(loaded.paths().count(), 0);
+}
+
+#[test]
+fn missing_or_foreign_index() {
+    let missing = TempPath::new(&format!("kra-index-missing-{}.txt", process::id()));
+    assert_eq!(Index::load(&missing).unwrap(), Index::new());
+
+    let foreign = TempPath::new(&format!("kra-index-foreign-{}.txt", process::id()));
+    fs::write(&foreign, "kra-index 0\nold\tformat\n").unwrap();
+    assert_eq!(Index::load(&foreign).unwrap(), Index::new());
+}
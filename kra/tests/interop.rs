@@ -0,0 +1,166 @@
+use kra::{
+    color::{AlphaMode, ConvertOptions, Transfer},
+    data::PixelBuffer,
+    id::Uuid,
+    testing::{DocumentBuilder, LayerBuilder},
+    texture::TextureFormat,
+    Colorspace, KraFile,
+};
+
+// UUIDs of the layers, in document order
+const RGBA: u128 = 1;
+const GRAY: u128 = 2;
+const DEEP: u128 = 3;
+const LINEAR: u128 = 4;
+
+fn read(test_name: &str) -> KraFile {
+    let linear = [0.18f32, 0.18, 0.18, 1.0].map(f32::to_le_bytes).concat();
+    let path = DocumentBuilder::new(128, 128)
+        // Blue, green, red, alpha
+        .layer(LayerBuilder::paint("Color").fill_tile(0, 0, [30, 20, 10, 128]))
+        .layer(
+            LayerBuilder::paint("Gray")
+                .colorspace("GRAYA")
+                .fill_tile(0, 0, [51, 255]),
+        )
+        .layer(LayerBuilder::paint("Deep").colorspace("RGBA16").fill_tile(
+            0,
+            0,
+            [1, 0, 2, 0, 3, 0, 255, 255],
+        ))
+        .layer(
+            LayerBuilder::paint("Linear")
+                .colorspace("RGBAF32")
+                .fill_tile(0, 0, linear),
+        )
+        .write_temp(test_name)
+        .unwrap();
+    KraFile::read(&path).unwrap()
+}
+
+fn uuid(value: u128) -> Uuid {
+    Uuid::from_u128(value)
+}
+
+#[test]
+fn rgba_buffers() {
+    let mut file = read("rgba_buffers");
+    let color = file.decode_layer(&uuid(RGBA)).unwrap();
+    let rgba = color.to_rgba8().unwrap();
+    assert_eq!(rgba.len(), 64 * 64 * 4);
+    assert_eq!(&rgba[..4], &[10, 20, 30, 128]);
+
+    let gray = file.decode_layer(&uuid(GRAY)).unwrap();
+    assert_eq!(&gray.to_rgba8().unwrap()[..4], &[51, 51, 51, 255]);
+    assert_eq!(&gray.to_rgba32f().unwrap()[..4], &[0.2, 0.2, 0.2, 1.0]);
+}
+
+#[test]
+fn alpha_modes() {
+    let mut file = read("alpha_modes");
+    let color = file.decode_layer(&uuid(RGBA)).unwrap();
+    assert_eq!(color.alpha_mode(), AlphaMode::Straight);
+    assert_eq!(
+        &color
+            .to_rgba8_with(&ConvertOptions::new().alpha_mode(AlphaMode::Premultiplied))
+            .unwrap()[..4],
+        &[5, 10, 15, 128]
+    );
+
+    let premultiplied = file
+        .decode_layer_with_alpha(&uuid(RGBA), AlphaMode::Premultiplied)
+        .unwrap();
+    assert_eq!(premultiplied.alpha_mode(), AlphaMode::Premultiplied);
+    let PixelBuffer::U8(pixels) = premultiplied.pixels() else {
+        panic!("expected 8-bit pixels");
+    };
+    assert_eq!(&pixels[..4], &[15, 10, 5, 128]);
+    // Straight alpha again
+    assert_eq!(&premultiplied.to_rgba8().unwrap()[..4], &[10, 20, 30, 128]);
+
+    // Converted from RGBA16 in the same pass
+    let deep = file
+        .decode_layer_converted_with(
+            &uuid(DEEP),
+            &ConvertOptions::new().alpha_mode(AlphaMode::Premultiplied),
+        )
+        .unwrap();
+    assert_eq!(deep.alpha_mode(), AlphaMode::Premultiplied);
+    assert_eq!(deep.colorspace(), Colorspace::RGBA);
+}
+
+#[test]
+fn srgb_transfer() {
+    let mut file = read("srgb_transfer");
+    let linear = file.decode_layer(&uuid(LINEAR)).unwrap();
+    // Values are only rescaled by default
+    assert_eq!(&linear.to_rgba8().unwrap()[..4], &[46, 46, 46, 255]);
+    let options = ConvertOptions::new().transfer(Transfer::LinearToSrgb);
+    assert_eq!(
+        &linear.to_rgba8_with(&options).unwrap()[..4],
+        &[118, 118, 118, 255]
+    );
+
+    // The image is 8-bit RGBA
+    let converted = file
+        .decode_layer_converted_with(&uuid(LINEAR), &options)
+        .unwrap();
+    let PixelBuffer::U8(pixels) = converted.pixels() else {
+        panic!("expected 8-bit pixels");
+    };
+    assert_eq!(&pixels[..4], &[118, 118, 118, 255]);
+}
+
+#[test]
+fn texture_layout() {
+    let mut file = read("texture_layout");
+    let color = file.decode_layer(&uuid(RGBA)).unwrap();
+    let texture = color.as_texture_desc().unwrap();
+    assert_eq!(texture.format(), TextureFormat::Bgra8Unorm);
+    assert_eq!(texture.format().to_string(), "Bgra8Unorm");
+    assert_eq!((texture.width(), texture.height()), (64, 64));
+    assert_eq!(texture.bytes_per_row(), 64 * 4);
+    assert_eq!(texture.data().len(), 64 * 64 * 4);
+    assert_eq!(&texture.data()[..4], &[30, 20, 10, 128]);
+
+    let gray = file.decode_layer(&uuid(GRAY)).unwrap();
+    let texture = gray.as_texture_desc().unwrap();
+    assert_eq!(texture.format(), TextureFormat::Rg8Unorm);
+    assert_eq!(texture.bytes_per_row(), 64 * 2);
+    assert_eq!(&texture.data()[..2], &[51, 255]);
+
+    // There is no 16-bit BGRA format
+    let deep = file.decode_layer(&uuid(DEEP)).unwrap();
+    let texture = deep.as_texture_desc().unwrap();
+    assert_eq!(texture.format(), TextureFormat::Rgba16Unorm);
+    assert_eq!(&texture.data()[..8], &[3, 0, 2, 0, 1, 0, 255, 255]);
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn image_crate() {
+    let mut file = read("image_crate");
+    let color = file.decode_layer(&uuid(RGBA)).unwrap();
+    let image = color.to_rgba_image().unwrap();
+    assert_eq!(image.dimensions(), (64, 64));
+    assert_eq!(image.get_pixel(63, 63).0, [10, 20, 30, 128]);
+
+    let image = color.to_rgba32f_image().unwrap();
+    assert_eq!(image.get_pixel(0, 0).0[3], 128.0 / 255.0);
+}
+
+#[cfg(feature = "tiny-skia")]
+#[test]
+fn tiny_skia_pixmap() {
+    let mut file = read("tiny_skia_pixmap");
+    let color = file.decode_layer(&uuid(RGBA)).unwrap();
+    let pixmap = color.to_pixmap().unwrap().unwrap();
+    assert_eq!((pixmap.width(), pixmap.height()), (64, 64));
+    // Premultiplied alpha
+    let pixel = pixmap.pixel(0, 0).unwrap();
+    assert_eq!(
+        [pixel.red(), pixel.green(), pixel.blue(), pixel.alpha()],
+        [5, 10, 15, 128]
+    );
+    assert_eq!(pixel.demultiply().red(), 10);
+}
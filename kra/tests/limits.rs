@@ -0,0 +1,190 @@
+use std::thread;
+
+use kra::{
+    config::{Limits, ParsingConfiguration},
+    error::{DataError, LimitError, ReadKraError},
+    id::Uuid,
+    testing::{DocumentBuilder, LayerBuilder, TempPath},
+    KraFile,
+};
+
+fn two_layers(test_name: &str) -> TempPath {
+    DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("One").fill_tile(0, 0, [0, 0, 255, 255]))
+        .layer(LayerBuilder::paint("Two"))
+        .write_temp(test_name)
+        .unwrap()
+}
+
+fn read_with(path: &std::path::Path, limits: Limits) -> Result<KraFile, ReadKraError> {
+    KraFile::read_with_config(path, ParsingConfiguration::new().limits(limits))
+}
+
+#[test]
+fn default_limits_allow_everything() {
+    let path = two_layers("default_limits_allow_everything");
+    let mut file = KraFile::read_with_config(&path, ParsingConfiguration::default()).unwrap();
+    file.find_empty_layers().unwrap();
+}
+
+#[test]
+fn xml_size() {
+    let path = two_layers("limit_xml_size");
+    let limits = Limits {
+        max_xml_size: 100,
+        ..Default::default()
+    };
+    match read_with(&path, limits) {
+        Err(ReadKraError::LimitExceeded(LimitError::EntrySize { entry, limit })) => {
+            assert_eq!(entry, "documentinfo.xml");
+            assert_eq!(limit, 100);
+        }
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn node_count() {
+    let path = two_layers("limit_node_count");
+    let limits = Limits {
+        max_nodes: 1,
+        ..Default::default()
+    };
+    assert!(matches!(
+        read_with(&path, limits),
+        Err(ReadKraError::LimitExceeded(LimitError::Nodes(1)))
+    ));
+    let limits = Limits {
+        max_nodes: 2,
+        ..Default::default()
+    };
+    assert!(read_with(&path, limits).is_ok());
+}
+
+// Groups nested `depth` levels deep, with a paint layer in the innermost one
+fn nested_groups(test_name: &str, depth: usize) -> TempPath {
+    let mut layer = LayerBuilder::paint("Leaf");
+    for _ in 0..depth {
+        layer = LayerBuilder::group("Group").child(layer);
+    }
+    DocumentBuilder::new(128, 128)
+        .layer(layer)
+        .write_temp(test_name)
+        .unwrap()
+}
+
+#[test]
+fn group_depth() {
+    let path = nested_groups("limit_group_depth", 3);
+    let limits = Limits {
+        max_group_depth: 2,
+        ..Default::default()
+    };
+    assert!(matches!(
+        read_with(&path, limits),
+        Err(ReadKraError::LimitExceeded(LimitError::GroupDepth(2)))
+    ));
+    let limits = Limits {
+        max_group_depth: 3,
+        ..Default::default()
+    };
+    assert_eq!(read_with(&path, limits).unwrap().files().len(), 4);
+
+    // Far deeper than the default, which keeps the stack from overflowing
+    // The builder recurses once per level, so it gets a stack large enough for that
+    let path = thread::Builder::new()
+        .stack_size(64 * 1024 * 1024)
+        .spawn(|| nested_groups("limit_group_depth_default", 5000))
+        .unwrap()
+        .join()
+        .unwrap();
+    assert!(matches!(
+        KraFile::read(&path),
+        Err(ReadKraError::LimitExceeded(LimitError::GroupDepth(64)))
+    ));
+}
+
+#[test]
+fn dimensions() {
+    let path = two_layers("limit_dimensions");
+    let limits = Limits {
+        max_dimensions: (128, 64),
+        ..Default::default()
+    };
+    assert!(matches!(
+        read_with(&path, limits),
+        Err(ReadKraError::LimitExceeded(LimitError::Dimensions {
+            width: 128,
+            height: 128,
+            ..
+        }))
+    ));
+}
+
+#[test]
+fn entry_size() {
+    let path = two_layers("limit_entry_size");
+    let limits = Limits {
+        max_entry_size: 1000,
+        ..Default::default()
+    };
+    let mut file = read_with(&path, limits).unwrap();
+    assert!(matches!(
+        file.decode_layer(&Uuid::from_u128(1)),
+        Err(DataError::LimitExceeded(LimitError::EntrySize { .. }))
+    ));
+}
+
+#[test]
+fn pixels() {
+    let path = two_layers("limit_pixels");
+    let limits = Limits {
+        max_pixels: 128 * 128 - 1,
+        ..Default::default()
+    };
+    assert!(matches!(
+        read_with(&path, limits),
+        Err(ReadKraError::LimitExceeded(LimitError::Pixels(16383)))
+    ));
+}
+
+#[test]
+fn zero_size() {
+    let path = DocumentBuilder::new(128, 0)
+        .layer(LayerBuilder::paint("One"))
+        .write_temp("zero_size")
+        .unwrap();
+    assert!(matches!(
+        KraFile::read(&path),
+        Err(ReadKraError::InvalidSize {
+            width: 128,
+            height: 0
+        })
+    ));
+}
+
+#[test]
+fn tiles_far_apart() {
+    let layer = LayerBuilder::paint("One")
+        .fill_tile(i32::MIN, i32::MIN, [0, 0, 255, 255])
+        .fill_tile(i32::MAX - 64, i32::MAX - 64, [0, 0, 255, 255]);
+    let path = DocumentBuilder::new(128, 128)
+        .layer(layer)
+        .write_temp("tiles_far_apart")
+        .unwrap();
+    let limits = Limits {
+        max_pixels: 1 << 24,
+        ..Default::default()
+    };
+    let mut file = read_with(&path, limits).unwrap();
+    assert!(matches!(
+        file.decode_layer(&Uuid::from_u128(1)),
+        Err(DataError::LimitExceeded(LimitError::Pixels(_)))
+    ));
+
+    let mut file = KraFile::read(&path).unwrap();
+    assert!(matches!(
+        file.decode_layer(&Uuid::from_u128(1)),
+        Err(DataError::TooLarge(4294967295, 4294967295))
+    ));
+}
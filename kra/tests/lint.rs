@@ -0,0 +1,127 @@
+use kra::{
+    id::Uuid,
+    layer::CompositeOp,
+    lint::{LintRules, Rule},
+    testing::{DocumentBuilder, LayerBuilder},
+    Colorspace, KraFile,
+};
+use regex::Regex;
+
+// UUIDs of the nodes, in document order
+const GROUP: u128 = 2;
+const GLOW: u128 = 3;
+
+fn read(test_name: &str) -> KraFile {
+    let glow = LayerBuilder::paint("fx_glow")
+        .composite_op("add")
+        .colorspace("RGBA16");
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("bg_sky"))
+        .layer(LayerBuilder::group("Effects").child(glow))
+        .write_temp(test_name)
+        .unwrap();
+    KraFile::read(path).unwrap()
+}
+
+#[test]
+fn clean_document() {
+    let file = read("lint_clean");
+    assert!(file.lint(&LintRules::new()).is_clean());
+
+    let rules = LintRules::new()
+        .max_layers(3)
+        .layer_name(Regex::new("^([a-z]+_[a-z]+|Effects)$").unwrap())
+        .require_group("Effects")
+        .max_dimensions(128, 128);
+    let report = file.lint(&rules);
+    assert!(report.is_clean());
+    assert_eq!(report.to_json(), "[]");
+}
+
+#[test]
+fn violations() {
+    let file = read("lint_violations");
+    let rules = LintRules::new()
+        .max_layers(2)
+        .forbid_blend_mode(CompositeOp::Add)
+        .layer_name(Regex::new("^[a-z]+_[a-z]+$").unwrap())
+        .require_group("Characters")
+        .allowed_colorspaces(&[Colorspace::RGBA])
+        .max_dimensions(64, 64);
+    let report = file.lint(&rules);
+    let found: Vec<_> = report
+        .violations()
+        .iter()
+        .map(|violation| (violation.rule(), violation.node()))
+        .collect();
+    let uuid = |value| Some(Uuid::from_u128(value));
+    assert_eq!(
+        found,
+        [
+            (Rule::MaxDimensions, None),
+            (Rule::MaxLayers, None),
+            (Rule::RequiredGroup, None),
+            (Rule::LayerName, uuid(GROUP)),
+            (Rule::ForbiddenBlendMode, uuid(GLOW)),
+            (Rule::Colorspace, uuid(GLOW)),
+        ]
+    );
+    assert_eq!(
+        report.violations()[2].to_string(),
+        r#"required-group: no group named "Characters""#
+    );
+    assert!(report.to_json().starts_with(
+        r#"[{"rule":"max-dimensions","node":null,"message":"image is 128x128, larger than 64x64"},"#
+    ));
+}
+
+#[cfg(feature = "lint-config")]
+#[test]
+fn rules_from_files() {
+    use std::{fs, process};
+
+    use kra::{error::LintConfigError, testing::TempPath};
+
+    let toml = r#"
+max-layers = 2
+forbidden-blend-modes = ["add"]
+required-groups = ["Effects"]
+colorspaces = ["RGBA", "RGBA16"]
+max-dimensions = [256, 256]
+"#;
+    let path = TempPath::new(&format!("kra-lint-{}.toml", process::id()));
+    fs::write(&path, toml).unwrap();
+    let rules = LintRules::load(&path).unwrap();
+    assert_eq!(rules.get_max_layers(), Some(2));
+    assert_eq!(rules.get_forbidden_blend_modes(), [CompositeOp::Add]);
+    assert_eq!(rules.get_max_dimensions(), Some((256, 256)));
+
+    let report = read("lint_rules_from_files").lint(&rules);
+    let found: Vec<_> = report
+        .violations()
+        .iter()
+        .map(|violation| violation.rule())
+        .collect();
+    assert_eq!(found, [Rule::MaxLayers, Rule::ForbiddenBlendMode]);
+
+    let json = r#"{"layer-name": "^[a-z]+$", "colorspaces": ["RGBA"]}"#;
+    let rules = LintRules::from_json(json).unwrap();
+    assert_eq!(rules.get_layer_name().unwrap().as_str(), "^[a-z]+$");
+    assert_eq!(
+        rules.get_allowed_colorspaces(),
+        Some([Colorspace::RGBA].as_slice())
+    );
+
+    assert!(matches!(
+        LintRules::from_toml(r#"forbidden-blend-modes = ["shiny"]"#),
+        Err(LintConfigError::UnknownCompositeOp(_))
+    ));
+    assert!(matches!(
+        LintRules::from_toml("max-layer = 2"),
+        Err(LintConfigError::TomlError(_))
+    ));
+    assert!(matches!(
+        LintRules::from_json(r#"{"layer-name": "("}"#),
+        Err(LintConfigError::InvalidPattern(_))
+    ));
+}
@@ -0,0 +1,365 @@
+#![cfg(feature = "image")]
+
+use std::io::Cursor;
+
+use image::{GenericImageView, ImageFormat, Rgba, RgbaImage};
+use kra::{
+    data::MergedImageSource,
+    testing::{DocumentBuilder, LayerBuilder},
+    KraFile,
+};
+
+#[test]
+fn decode_merged_image() {
+    let mut png = Vec::new();
+    RgbaImage::from_pixel(3, 2, Rgba([10, 20, 30, 255]))
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .unwrap();
+    let path = DocumentBuilder::new(128, 128)
+        .entry("mergedimage.png", png.clone())
+        .write_temp("decode_merged_image")
+        .unwrap();
+
+    let mut file = KraFile::read(&path).unwrap();
+    assert_eq!(file.merged_image().unwrap(), png);
+    assert_eq!(
+        file.merged_image_with_source().unwrap().1,
+        MergedImageSource::Stored
+    );
+    let image = file.merged_image_decoded().unwrap();
+    assert_eq!(image.dimensions(), (3, 2));
+    assert_eq!(image.get_pixel(2, 1), Rgba([10, 20, 30, 255]));
+}
+
+#[cfg(feature = "render")]
+#[test]
+fn composite_without_merged_image() {
+    use kra::{error::DataError, id::Uuid};
+
+    // The file layer is the first node, and its file does not exist
+    let document = |file_visible| {
+        let blue = LayerBuilder::paint("Blue")
+            .opacity(51)
+            .fill_tile(64, 64, [255, 0, 0, 255]);
+        let red = LayerBuilder::paint("Red")
+            .fill_tile(0, 0, [0, 0, 255, 255])
+            .fill_tile(64, 64, [0, 0, 255, 255]);
+        DocumentBuilder::new(128, 128)
+            .layer(LayerBuilder::file_layer("Hidden", "missing.png").visible(file_visible))
+            .layer(LayerBuilder::group("Sea").child(blue))
+            .layer(red)
+    };
+
+    let path = document(false)
+        .write_temp("composite_without_merged_image")
+        .unwrap();
+    let mut file = KraFile::read(&path).unwrap();
+    let (png, source) = file.merged_image_with_source().unwrap();
+    assert_eq!(source, MergedImageSource::Composited);
+    let image = image::load_from_memory(&png).unwrap().to_rgba8();
+    assert_eq!(image.dimensions(), (128, 128));
+    assert_eq!(image.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+    // Blue at 20% over red
+    assert_eq!(image.get_pixel(64, 64), &Rgba([204, 0, 51, 255]));
+    assert_eq!(image.get_pixel(127, 0), &Rgba([0, 0, 0, 0]));
+
+    let path = document(true).write_temp("composite_unsupported").unwrap();
+    let mut file = KraFile::read(&path).unwrap();
+    assert!(matches!(
+        file.merged_image(),
+        Err(DataError::CannotComposite(uuid)) if uuid == Uuid::from_u128(1)
+    ));
+}
+
+#[test]
+fn watermark() {
+    use kra::edit::WatermarkOptions;
+
+    let mut png = Vec::new();
+    RgbaImage::from_pixel(128, 128, Rgba([255, 255, 255, 255]))
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .unwrap();
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Paint"))
+        .entry("mergedimage.png", png)
+        .write_temp("watermark")
+        .unwrap();
+    let mut file = KraFile::read(&path).unwrap();
+    let logo = RgbaImage::from_pixel(70, 10, Rgba([255, 0, 0, 255]));
+    let options = WatermarkOptions::new()
+        .name("Logo")
+        .position(100, 60)
+        .opacity(128);
+    let id = file.add_watermark(&logo, &options).unwrap();
+
+    let layer = &file.layers()[0];
+    assert_eq!((layer.uuid(), layer.name()), (id, "Logo"));
+    assert_eq!(layer.as_layer().unwrap().opacity(), 128);
+    let data = file.decode_layer(&id).unwrap();
+    assert_eq!((data.x(), data.y()), (64, 0));
+    let pixels = data.to_rgba_image().unwrap();
+    assert_eq!(pixels.get_pixel(100 - 64, 60), &Rgba([255, 0, 0, 255]));
+    assert_eq!(pixels.get_pixel(99 - 64, 60), &Rgba([0, 0, 0, 0]));
+
+    let merged = file.merged_image_decoded().unwrap().to_rgba8();
+    assert_eq!(merged.get_pixel(99, 60), &Rgba([255, 255, 255, 255]));
+    assert_eq!(merged.get_pixel(127, 69), &Rgba([255, 127, 127, 255]));
+    assert_eq!(merged.get_pixel(127, 70), &Rgba([255, 255, 255, 255]));
+}
+
+#[test]
+fn file_layer_images() {
+    use kra::{config::ParsingConfiguration, error::DataError, id::Uuid, testing::TempPath};
+
+    let source = format!("kra-file_layer_images-{}.png", std::process::id());
+    let mut linked = RgbaImage::from_pixel(2, 1, Rgba([0, 255, 0, 255]));
+    linked.put_pixel(1, 0, Rgba([0, 0, 255, 128]));
+    let linked_path = TempPath::new(&source);
+    linked.save(&linked_path).unwrap();
+
+    let layer = LayerBuilder::file_layer("File", &source)
+        .offset(3, 0)
+        .attribute("scalingmethod", "1")
+        .attribute("scalingfilter", "NearestNeighbor");
+    let path = DocumentBuilder::new(128, 128)
+        .layer(layer)
+        .write_temp("file_layer_images")
+        .unwrap();
+    let config = ParsingConfiguration::new().cache_budget(1 << 20);
+    let mut file = KraFile::read_with_config(&path, config).unwrap();
+    let uuid = Uuid::from_u128(1);
+    let data = file.decode_file_layer(&uuid).unwrap();
+    // Scaled to fit 128x128, keeping the aspect ratio
+    assert_eq!(
+        (data.x(), data.y(), data.width(), data.height()),
+        (3, 0, 128, 64)
+    );
+    let pixels = data.to_rgba_image().unwrap();
+    assert_eq!(pixels.get_pixel(0, 0), &Rgba([0, 255, 0, 255]));
+    assert_eq!(pixels.get_pixel(127, 63), &Rgba([0, 0, 255, 128]));
+
+    file.set_base_dir(None);
+    assert_eq!(file.decode_file_layer(&uuid).unwrap(), data);
+    file.clear_cache();
+    assert!(matches!(
+        file.decode_file_layer(&uuid),
+        Err(DataError::NoBaseDir)
+    ));
+}
+
+#[cfg(feature = "render")]
+#[test]
+fn composite_file_layers() {
+    use kra::{config::ParsingConfiguration, testing::TempPath};
+
+    let source = format!("kra-composite_file_layers-{}.png", std::process::id());
+    let linked = TempPath::new(&source);
+    RgbaImage::from_pixel(64, 64, Rgba([255, 0, 0, 255]))
+        .save(&linked)
+        .unwrap();
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::file_layer("File", &source).opacity(51))
+        .write_temp("composite_file_layers")
+        .unwrap();
+    let config = ParsingConfiguration::new().load_file_layers(true);
+    let mut file = KraFile::read_with_config(&path, config).unwrap();
+    let image = file.composite().unwrap();
+    assert_eq!(image.get_pixel(63, 63), &Rgba([255, 0, 0, 51]));
+    assert_eq!(image.get_pixel(64, 0), &Rgba([0, 0, 0, 0]));
+}
+
+// The fixtures hold reference renders of several blending modes and of layers that
+// inherit alpha as their mergedimage.png, see tests/fixtures/README.md
+#[cfg(feature = "render")]
+#[test]
+fn composite_blend_modes() {
+    use kra::{layer::CompositeOp, testing::compare_render};
+
+    let fixtures = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut file = KraFile::read(fixtures.join("blend-modes.kra")).unwrap();
+    let ops: Vec<_> = file
+        .layers()
+        .iter()
+        .map(|node| node.as_layer().unwrap().composite_op())
+        .collect();
+    assert_eq!(
+        ops[..8],
+        [
+            CompositeOp::Multiply,
+            CompositeOp::Screen,
+            CompositeOp::Overlay,
+            CompositeOp::Darken,
+            CompositeOp::Lighten,
+            CompositeOp::Diff,
+            CompositeOp::Add,
+            CompositeOp::Subtract,
+        ]
+    );
+    assert!(ops.iter().all(|op| op.is_separable()));
+    assert!(!CompositeOp::Hue.is_separable());
+    assert!(CompositeOp::Erase.changes_only_alpha());
+    let comparison = compare_render(&mut file, 2).unwrap();
+    assert!(comparison.matches(), "{:?}", comparison.mismatches());
+
+    let mut file = KraFile::read(fixtures.join("inherit-alpha.kra")).unwrap();
+    let inherits: Vec<_> = file
+        .layers()
+        .iter()
+        .map(|node| node.inherits_alpha())
+        .collect();
+    assert_eq!(inherits, [true, true, false]);
+    let comparison = compare_render(&mut file, 2).unwrap();
+    assert!(comparison.matches(), "{:?}", comparison.mismatches());
+    // Nothing below the tint
+    assert_eq!(
+        file.composite().unwrap().get_pixel(64, 0),
+        &Rgba([0, 0, 0, 0])
+    );
+}
+
+#[cfg(feature = "render")]
+#[test]
+fn compare_render_with_merged_image() {
+    use kra::testing::compare_render;
+
+    let mut merged = RgbaImage::new(128, 128);
+    for (x, y, pixel) in merged.enumerate_pixels_mut() {
+        // Colors of transparent pixels do not matter
+        *pixel = match x < 64 && y < 64 {
+            true => Rgba([255, 0, 0, 255]),
+            false => Rgba([9, 9, 9, 0]),
+        };
+    }
+    merged.put_pixel(1, 1, Rgba([250, 0, 0, 255]));
+    merged.put_pixel(2, 1, Rgba([254, 1, 0, 255]));
+    merged.put_pixel(70, 70, Rgba([0, 0, 0, 40]));
+    let mut png = Vec::new();
+    merged
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .unwrap();
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Red").fill_tile(0, 0, [0, 0, 255, 255]))
+        .entry("mergedimage.png", png)
+        .write_temp("compare_render_with_merged_image")
+        .unwrap();
+
+    let mut file = KraFile::read(&path).unwrap();
+    let comparison = compare_render(&mut file, 2).unwrap();
+    assert_eq!((comparison.width(), comparison.height()), (128, 128));
+    assert_eq!(comparison.delta(2, 1), 1);
+    assert_eq!(comparison.delta(100, 100), 0);
+    assert_eq!(comparison.max_delta(), 40);
+    assert_eq!(comparison.mismatches(), [(1, 1, 5), (70, 70, 40)]);
+    assert!(!comparison.matches());
+    assert_eq!(comparison.delta_image().get_pixel(70, 70).0, [40]);
+    assert!(compare_render(&mut file, 40).unwrap().matches());
+}
+
+#[cfg(feature = "render")]
+#[test]
+fn composite_clone_layers() {
+    use kra::{config::ParsingConfiguration, error::DataError, id::Uuid};
+
+    // The clones come first, then the group and its child, then the red layer
+    let (group, red) = (Uuid::from_u128(3), Uuid::from_u128(5));
+    let blue = LayerBuilder::paint("Blue").fill_tile(0, 0, [255, 0, 0, 255]);
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::clone_layer("Clone", group).offset(0, 64))
+        .layer(LayerBuilder::clone_layer("Clone", red).offset(64, 0))
+        .layer(LayerBuilder::group("Group").visible(false).child(blue))
+        .layer(
+            LayerBuilder::paint("Red")
+                .visible(false)
+                .fill_tile(0, 0, [0, 0, 255, 255]),
+        )
+        .write_temp("composite_clone_layers")
+        .unwrap();
+
+    let mut file = KraFile::read(&path).unwrap();
+    assert!(matches!(
+        file.composite(),
+        Err(DataError::CannotComposite(_))
+    ));
+
+    let config = ParsingConfiguration::new().flatten_clone_layers(true);
+    let mut file = KraFile::read_with_config(&path, config.clone()).unwrap();
+    let image = file.composite().unwrap();
+    // Sources are hidden, but their clones are drawn where they were moved to
+    assert_eq!(image.get_pixel(0, 0), &Rgba([0, 0, 0, 0]));
+    assert_eq!(image.get_pixel(64, 0), &Rgba([255, 0, 0, 255]));
+    assert_eq!(image.get_pixel(0, 64), &Rgba([0, 0, 255, 255]));
+    assert_eq!(image.get_pixel(64, 64), &Rgba([0, 0, 0, 0]));
+
+    // A clone of a group that holds the clone cannot be drawn
+    let cycle =
+        LayerBuilder::group("Group").child(LayerBuilder::clone_layer("Clone", Uuid::from_u128(1)));
+    let path = DocumentBuilder::new(128, 128)
+        .layer(cycle)
+        .write_temp("composite_clone_cycle")
+        .unwrap();
+    let mut file = KraFile::read_with_config(&path, config).unwrap();
+    assert!(matches!(
+        file.composite(),
+        Err(DataError::CannotComposite(_))
+    ));
+}
+
+#[cfg(feature = "render")]
+#[test]
+fn composite_selection_masks() {
+    use kra::config::ParsingConfiguration;
+
+    let red = [0, 0, 255, 255];
+    // Fully selected, half selected, and not selected where there are no tiles
+    let selection = LayerBuilder::mask("Selection", "selectionmask")
+        .fill_tile(0, 0, [255])
+        .fill_tile(0, 64, [128]);
+    let layer = LayerBuilder::paint("Layer")
+        .fill_tile(0, 0, red)
+        .fill_tile(64, 0, red)
+        .fill_tile(0, 64, red)
+        .with_mask(selection);
+    let path = DocumentBuilder::new(128, 128)
+        .layer(layer)
+        .write_temp("composite_selection_masks")
+        .unwrap();
+
+    // Selection masks are ignored by default
+    let mut file = KraFile::read(&path).unwrap();
+    let image = file.composite().unwrap();
+    assert_eq!(image.get_pixel(64, 0), &Rgba([255, 0, 0, 255]));
+
+    let config = ParsingConfiguration::new().apply_selection_masks(true);
+    let mut file = KraFile::read_with_config(&path, config).unwrap();
+    let image = file.composite().unwrap();
+    assert_eq!(image.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+    assert_eq!(image.get_pixel(64, 0), &Rgba([0, 0, 0, 0]));
+    assert_eq!(image.get_pixel(0, 64), &Rgba([255, 0, 0, 128]));
+}
+
+#[cfg(feature = "render")]
+#[test]
+fn inactive_selection_masks_do_not_clip() {
+    use kra::config::ParsingConfiguration;
+
+    // The active mask selects the left tile, the inactive one the right tile
+    let active = LayerBuilder::mask("Active", "selectionmask").fill_tile(0, 0, [255]);
+    let inactive = LayerBuilder::mask("Inactive", "selectionmask")
+        .attribute("active", "0")
+        .fill_tile(64, 0, [255]);
+    let layer = LayerBuilder::paint("Layer")
+        .fill_tile(0, 0, [0, 0, 255, 255])
+        .fill_tile(64, 0, [0, 0, 255, 255])
+        .with_mask(active)
+        .with_mask(inactive);
+    let path = DocumentBuilder::new(128, 128)
+        .layer(layer)
+        .write_temp("inactive_selection_masks")
+        .unwrap();
+
+    let config = ParsingConfiguration::new().apply_selection_masks(true);
+    let mut file = KraFile::read_with_config(&path, config).unwrap();
+    let image = file.composite().unwrap();
+    assert_eq!(image.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+    assert_eq!(image.get_pixel(64, 0), &Rgba([0, 0, 0, 0]));
+}
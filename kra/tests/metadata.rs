@@ -0,0 +1,196 @@
+use kra::{
+    config::ParsingConfiguration,
+    layer::{InTimeline, NodeType},
+    metadata::{DocInfoField, LanguageTag},
+    testing::{DocumentBuilder, LayerBuilder},
+    KraFile,
+};
+
+#[test]
+fn fractional_resolution() {
+    let path = DocumentBuilder::new(128, 128)
+        .resolution("118.11", "72.5")
+        .write_temp("fractional_resolution")
+        .unwrap();
+
+    let file = KraFile::read(&path).unwrap();
+    assert_eq!(file.meta().x_res(), 118.11);
+    assert_eq!(file.meta().y_res(), 72.5);
+    assert_eq!(file.meta().x_res_rounded(), 118);
+    assert_eq!(file.meta().y_res_rounded(), 73);
+}
+
+#[test]
+fn old_paint_layer_attributes() {
+    let old = LayerBuilder::paint("Old")
+        .without_attribute("colorlabel")
+        .without_attribute("channellockflags");
+    let path = DocumentBuilder::new(128, 128)
+        .layer(old)
+        .layer(LayerBuilder::paint("New"))
+        .write_temp("old_paint_layer_attributes")
+        .unwrap();
+
+    let file = KraFile::read(&path).unwrap();
+    let [old, new] = file.layers() else {
+        panic!("expected two layers");
+    };
+    assert!(!old.attributes().iter().any(|(key, _)| key == "colorlabel"));
+    assert_eq!(old.colorlabel(), 0);
+    assert_eq!(old.as_common().colorlabel(), 0);
+    assert_eq!(new.colorlabel(), 0);
+    let (NodeType::PaintLayer(old), NodeType::PaintLayer(new)) = (old.node_type(), new.node_type())
+    else {
+        panic!("expected paint layers");
+    };
+    assert_eq!(old.channel_lock_flags(), None);
+    assert_eq!(new.channel_lock_flags(), Some("1111"));
+}
+
+#[test]
+fn timeline_flags() {
+    let path = DocumentBuilder::new(128, 128)
+        .layer(
+            LayerBuilder::paint("Animated")
+                .attribute("intimeline", "1")
+                .attribute("onionskin", "1"),
+        )
+        .layer(LayerBuilder::paint("Still").attribute("onionskin", "1"))
+        .write_temp("timeline_flags")
+        .unwrap();
+
+    let file = KraFile::read(&path).unwrap();
+    let [animated, still] = file.layers() else {
+        panic!("expected two layers");
+    };
+    assert!(animated.in_timeline());
+    assert!(animated.onionskin());
+    assert_eq!(animated.timeline(), InTimeline::True(true));
+    // Onionskinning only applies to nodes in the timeline
+    assert!(!still.in_timeline());
+    assert!(!still.onionskin());
+    assert_eq!(still.timeline(), InTimeline::False);
+}
+
+#[test]
+fn session_flags() {
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::mask("Selection", "selectionmask"))
+        .layer(LayerBuilder::paint("Active").attribute("selected", "true"))
+        .write_temp("session_flags")
+        .unwrap();
+
+    // The flag is not an unknown attribute
+    let file = KraFile::read_with_config(&path, ParsingConfiguration::strict()).unwrap();
+    let [selection, active] = file.layers() else {
+        panic!("expected two nodes");
+    };
+    assert!(!selection.selected());
+    assert!(active.selected());
+    assert_eq!(file.selected_node().unwrap().uuid(), active.uuid());
+    assert_eq!(file.active_selection().unwrap().uuid(), selection.uuid());
+}
+
+#[test]
+fn summary() {
+    let walk = LayerBuilder::paint("Walk").attribute("keyframes", "layer2.keyframes.xml");
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::group("Group").child(walk))
+        .write_temp("summary")
+        .unwrap();
+    let mut file = KraFile::read(&path).unwrap();
+    file.set_doc_info(DocInfoField::FullName, "Ann Example");
+
+    let summary = file.summary();
+    assert_eq!(summary.name(), file.meta().name());
+    assert_eq!(summary.dimensions(), (128, 128));
+    assert_eq!(summary.colorspace(), file.meta().colorspace());
+    assert_eq!(summary.layer_count(), 2);
+    assert!(summary.has_animation());
+    assert_eq!(summary.krita_version(), "5.2.2");
+    assert_eq!(summary.author(), "Ann Example");
+
+    let path = DocumentBuilder::new(128, 128)
+        .write_temp("summary_still")
+        .unwrap();
+    let summary = KraFile::read(&path).unwrap().summary();
+    assert_eq!(summary.layer_count(), 0);
+    assert!(!summary.has_animation());
+    assert_eq!(summary.author(), "");
+}
+
+#[test]
+fn language_tags() {
+    let tag: LanguageTag = "sr-latn-rs".parse().unwrap();
+    assert_eq!(tag.language(), "sr");
+    assert_eq!(tag.script(), Some("Latn"));
+    assert_eq!(tag.region(), Some("RS"));
+    assert_eq!(tag.to_string(), "sr-Latn-RS");
+    assert!(tag.matches("sr") && tag.matches("SR-Latn") && tag.matches("*"));
+    assert!(!tag.matches("sr-Cyrl") && !tag.matches("s"));
+
+    let tag: LanguageTag = "de_DE.UTF-8".parse().unwrap();
+    assert_eq!(tag.to_string(), "de-DE");
+    let tag: LanguageTag = "es-419".parse().unwrap();
+    assert_eq!(tag.region(), Some("419"));
+    let tag: LanguageTag = "zh-yue-Hant-HK".parse().unwrap();
+    assert_eq!((tag.language(), tag.script()), ("zh-yue", Some("Hant")));
+    let tag: LanguageTag = "de-CH-1996-u-co-phonebk-x-Krita".parse().unwrap();
+    assert_eq!(tag.variants(), ["1996"]);
+    assert_eq!(tag.extensions(), "u-co-phonebk-x-krita");
+    assert_eq!(tag.to_string(), "de-CH-1996-u-co-phonebk-x-krita");
+
+    for invalid in [
+        "",
+        "e",
+        "English (US)",
+        "en--US",
+        "en-u",
+        "en-x",
+        "x-private",
+        "en-US-a-b",
+    ] {
+        assert!(invalid.parse::<LanguageTag>().is_err(), "{invalid}");
+    }
+
+    let path = DocumentBuilder::new(128, 128)
+        .write_temp("language_tags")
+        .unwrap();
+    let mut file = KraFile::read(&path).unwrap();
+    assert_eq!(file.doc_info().about().language_tag(), None);
+    file.set_doc_info(DocInfoField::Language, "pt_BR");
+    let tag = file.doc_info().about().language_tag().unwrap();
+    assert!(tag.matches("pt"));
+    file.set_doc_info(DocInfoField::Language, "Portuguese");
+    assert_eq!(file.doc_info().about().language(), "Portuguese");
+    assert_eq!(file.doc_info().about().language_tag(), None);
+}
+
+#[test]
+fn animation() {
+    let document = DocumentBuilder::new(128, 128);
+    let path = document.write_temp("animation_none").unwrap();
+    assert!(KraFile::read(&path).unwrap().meta().animation().is_none());
+
+    let path = document
+        .element(
+            r#"<animation>
+   <framerate type="value" value="12"/>
+   <range type="timerange" from="10" to="33"/>
+   <currentTime type="value" value="15"/>
+  </animation>"#,
+        )
+        .write_temp("animation")
+        .unwrap();
+    let file = KraFile::read(&path).unwrap();
+    let animation = file.meta().animation().unwrap();
+    assert_eq!(animation.framerate(), 12);
+    assert_eq!(animation.range(), 10..=33);
+    assert_eq!(animation.current_time(), 15);
+    assert_eq!(animation.duration_frames(), 24);
+    assert_eq!(animation.duration_seconds(), 2.0);
+    assert_eq!(animation.frame_at_time(0), Some(10));
+    assert_eq!(animation.frame_at_time(1000), Some(22));
+    assert_eq!(animation.frame_at_time(1999), Some(33));
+    assert_eq!(animation.frame_at_time(2000), None);
+}
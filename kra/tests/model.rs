@@ -0,0 +1,71 @@
+use kra::{
+    model::{Document, Node, Properties},
+    testing::{DocumentBuilder, LayerBuilder},
+    KraFile,
+};
+
+#[test]
+fn document_from_file() {
+    let path = DocumentBuilder::new(128, 128)
+        .layer(
+            LayerBuilder::group("Group")
+                .visible(false)
+                .child(LayerBuilder::paint("Inner")),
+        )
+        .write_temp("model_document_from_file")
+        .unwrap();
+    let file = KraFile::read(path).unwrap();
+    let document = Document::from(&file);
+
+    assert_eq!(document.image.name, *file.meta().name());
+    assert_eq!(document.image.width, file.meta().width());
+    assert_eq!(document.image.colorspace, "RGBA");
+    assert_eq!(document.info.title, *file.doc_info().about().title());
+
+    let [group] = document.layers.as_slice() else {
+        panic!("{:?}", document.layers);
+    };
+    assert_eq!(group.kind, "grouplayer");
+    assert_eq!(group.uuid, "00000000-0000-0000-0000-000000000001");
+    assert!(!group.visible);
+    let Properties::Group { layer, layers, .. } = &group.properties else {
+        panic!("{:?}", group.properties);
+    };
+    assert_eq!(layer.composite_op, "normal");
+    assert_eq!(layer.opacity, 255);
+
+    let [Node {
+        name, properties, ..
+    }] = layers.as_slice()
+    else {
+        panic!("{:?}", layers);
+    };
+    assert_eq!(name, "Inner");
+    let Properties::Paint {
+        colorspace,
+        channel_lock_flags,
+        ..
+    } = properties
+    else {
+        panic!("{:?}", properties);
+    };
+    assert_eq!(colorspace, "RGBA");
+    assert_eq!(channel_lock_flags.as_deref(), Some("1111"));
+}
+
+#[test]
+fn fixtures_convert() {
+    for name in [
+        "paint",
+        "group-and-mask",
+        "filter-and-fill",
+        "clone-and-pass-through",
+        "empty",
+    ] {
+        let path = format!("{}/tests/fixtures/{}.kra", env!("CARGO_MANIFEST_DIR"), name);
+        let file = KraFile::read(path).unwrap();
+        let document = Document::from(&file);
+        assert_eq!(document.layers.len(), file.layers().len());
+        assert_eq!(document.clone(), document);
+    }
+}
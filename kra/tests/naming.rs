@@ -0,0 +1,214 @@
+use kra::{
+    error::TemplateError,
+    id::Uuid,
+    naming::{sanitize_name, NameTemplate, SanitizeOptions, Token, UniqueNames},
+    testing::{DocumentBuilder, LayerBuilder},
+    KraFile,
+};
+
+#[test]
+fn path_hostile_characters() {
+    let options = SanitizeOptions::new();
+    assert_eq!(
+        sanitize_name("lines/inks: final?", &options),
+        "lines_inks_ final_"
+    );
+    assert_eq!(sanitize_name("a<>|b", &options), "a_b");
+    assert_eq!(sanitize_name("..\\..\\etc", &options), "_.._etc");
+    assert_eq!(sanitize_name(" .hidden. ", &options), "hidden");
+    assert_eq!(sanitize_name("tab\there", &options), "tab_here");
+}
+
+#[test]
+fn unicode_is_kept_by_default() {
+    let options = SanitizeOptions::new();
+    assert_eq!(sanitize_name("Ébauche 🎨", &options), "Ébauche 🎨");
+}
+
+#[test]
+fn ascii_transliteration() {
+    let options = SanitizeOptions::new().ascii(true);
+    assert_eq!(sanitize_name("Ébauche 🎨", &options), "Ebauche _");
+    assert_eq!(sanitize_name("Straße", &options), "Strasse");
+    assert_eq!(sanitize_name("线稿", &options), "_");
+    // The replacement must be ASCII too
+    let options = options.replacement('·');
+    assert_eq!(sanitize_name("a🎨b", &options), "a_b");
+}
+
+#[test]
+fn custom_replacement() {
+    let options = SanitizeOptions::new().replacement('-');
+    assert_eq!(sanitize_name("a/b", &options), "a-b");
+    // Unsafe replacements are not used
+    let options = SanitizeOptions::new().replacement('/');
+    assert_eq!(sanitize_name("a:b", &options), "a_b");
+}
+
+#[test]
+fn empty_and_reserved_names() {
+    let options = SanitizeOptions::new();
+    assert_eq!(sanitize_name("", &options), "unnamed");
+    assert_eq!(sanitize_name(" ... ", &options), "unnamed");
+    assert_eq!(
+        sanitize_name("", &SanitizeOptions::new().fallback("layer")),
+        "layer"
+    );
+    assert_eq!(sanitize_name("con", &options), "con_");
+    assert_eq!(sanitize_name("NUL.txt", &options), "NUL_.txt");
+    assert_eq!(sanitize_name("console", &options), "console");
+}
+
+#[test]
+fn max_len() {
+    let options = SanitizeOptions::new().max_len(5);
+    assert_eq!(sanitize_name("abcdefgh", &options), "abcde");
+    // Not in the middle of a character
+    assert_eq!(sanitize_name("abcdé", &options), "abcd");
+}
+
+#[test]
+fn unique_names() {
+    let mut names = UniqueNames::new();
+    assert_eq!(names.unique("Layer"), "Layer");
+    assert_eq!(names.unique("layer"), "layer_2");
+    assert_eq!(names.unique("Layer"), "Layer_3");
+    assert_eq!(names.unique("Layer_4"), "Layer_4");
+    assert_eq!(names.unique("Layer"), "Layer_5");
+    assert!(names.is_taken("LAYER_2"));
+    assert!(!names.is_taken("Layer_6"));
+}
+
+#[test]
+fn sanitized_names_are_unique_in_document() {
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Ink/Line"))
+        .layer(LayerBuilder::group("Group").child(LayerBuilder::paint("Ink:Line")))
+        .write_temp("sanitized_names")
+        .unwrap();
+    let file = KraFile::read(&path).unwrap();
+
+    let options = SanitizeOptions::new();
+    assert_eq!(file.layers()[0].sanitized_name(&options), "Ink_Line");
+
+    let names = file.sanitized_names(&options);
+    assert_eq!(names.len(), 3);
+    assert_eq!(names[&Uuid::from_u128(1)], "Ink_Line");
+    assert_eq!(names[&Uuid::from_u128(2)], "Group");
+    assert_eq!(names[&Uuid::from_u128(3)], "Ink_Line_2");
+}
+
+#[test]
+fn maps_of_nodes_are_ordered_by_uuid() {
+    let layer = |name, n| LayerBuilder::paint(name).uuid(Uuid::from_u128(n));
+    let path = DocumentBuilder::new(128, 128)
+        .layer(layer("C", 3))
+        .layer(layer("A", 1))
+        .layer(layer("B", 2))
+        .write_temp("maps_ordered")
+        .unwrap();
+    let file = KraFile::read(&path).unwrap();
+
+    let expected: Vec<Uuid> = (1..=3).map(Uuid::from_u128).collect();
+    let options = SanitizeOptions::new();
+    assert!(file.files().keys().eq(&expected));
+    assert!(file.node_entries().keys().eq(&expected));
+    assert!(file.sanitized_names(&options).keys().eq(&expected));
+    assert!(file.display_paths().into_values().eq(["A", "B", "C"]));
+}
+
+#[test]
+fn display_paths_tell_apart_siblings() {
+    let group = LayerBuilder::group("Group")
+        .child(LayerBuilder::paint("Sketch"))
+        .child(LayerBuilder::paint("Sketch"))
+        .child(LayerBuilder::paint("a/b"));
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Sketch"))
+        .layer(group)
+        .write_temp("display_paths")
+        .unwrap();
+    let file = KraFile::read(&path).unwrap();
+
+    let paths = file.display_paths();
+    let path_of = |n| paths[&Uuid::from_u128(n)].as_str();
+    assert_eq!(path_of(1), "Sketch");
+    assert_eq!(path_of(3), "Group/Sketch");
+    assert_eq!(path_of(4), "Group/Sketch[2]");
+    assert_eq!(path_of(5), "Group/a\\/b");
+
+    let node = file.find_by_display_path("Group/Sketch[2]").unwrap();
+    assert_eq!(node.uuid(), Uuid::from_u128(4));
+    assert!(file.find_by_display_path("Group/Sketch[3]").is_none());
+}
+
+#[test]
+fn template_parsing() {
+    let template: NameTemplate = "{group}/{name}_{index}.png".parse().unwrap();
+    assert_eq!(
+        template.tokens().collect::<Vec<_>>(),
+        [Token::Group, Token::Name, Token::Index]
+    );
+    assert_eq!(template.to_string(), "{group}/{name}_{index}.png");
+
+    let template: NameTemplate = "{{literal}} {frame:04}".parse().unwrap();
+    assert_eq!(template.tokens().collect::<Vec<_>>(), [Token::Frame]);
+    assert_eq!(template.to_string(), "{{literal}} {frame:04}");
+
+    assert_eq!(
+        "{nmae}".parse::<NameTemplate>(),
+        Err(TemplateError::UnknownToken("nmae".to_owned()))
+    );
+    assert_eq!(
+        "{name".parse::<NameTemplate>(),
+        Err(TemplateError::UnmatchedBrace)
+    );
+    assert_eq!(
+        "name}".parse::<NameTemplate>(),
+        Err(TemplateError::UnmatchedBrace)
+    );
+    assert_eq!(
+        "{name:04}".parse::<NameTemplate>(),
+        Err(TemplateError::InvalidFormat("name:04".to_owned()))
+    );
+}
+
+#[test]
+fn export_names_from_template() {
+    let character = LayerBuilder::group("Character")
+        .child(LayerBuilder::paint("Line art"))
+        .child(LayerBuilder::paint("Colors?"));
+    let path = DocumentBuilder::new(128, 128)
+        .layer(character)
+        .layer(LayerBuilder::paint("Background"))
+        .write_temp("export_names")
+        .unwrap();
+    let file = KraFile::read(&path).unwrap();
+    let (group, first, second, top) = (
+        Uuid::from_u128(1),
+        Uuid::from_u128(2),
+        Uuid::from_u128(3),
+        Uuid::from_u128(4),
+    );
+
+    let template: NameTemplate = "{group}/{name}_{index}.png".parse().unwrap();
+    let names = file.export_names(&template, &SanitizeOptions::new());
+    assert_eq!(names[&group], "Character_0.png");
+    assert_eq!(names[&first], "Character/Line art_0.png");
+    assert_eq!(names[&second], "Character/Colors__1.png");
+    assert_eq!(names[&top], "Background_1.png");
+
+    let values = file.template_values(&SanitizeOptions::new().ascii(true));
+    let values = values[&first]
+        .clone()
+        .with_composition("front")
+        .with_frame(7);
+    assert_eq!(values.path(), &["Character"]);
+    let template: NameTemplate = "{composition}/{path}/{name}.{frame:03}.{uuid}"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        template.render(&values),
+        format!("front/Character/Line art.007.{}", first)
+    );
+}
@@ -0,0 +1,73 @@
+use kra::{
+    config::ParsingConfiguration,
+    id::Uuid,
+    testing::{DocumentBuilder, LayerBuilder, TempPath},
+    KraFile,
+};
+
+// UUIDs of the nodes, in document order
+const PAINT: u128 = 1;
+const GROUP: u128 = 2;
+const CHILD: u128 = 3;
+
+fn write(test_name: &str) -> TempPath {
+    let empty = LayerBuilder::paint("Empty").pixel_data();
+    DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Paint").default_pixel([0; 4]))
+        .layer(LayerBuilder::group("Group").child(LayerBuilder::paint("Child").filename("layer10")))
+        .image_entry("layers/layer1.icc", Vec::new())
+        .image_entry("layers/layer1.keyframes.xml", Vec::new())
+        .image_entry("layers/layer1.f1", empty.clone())
+        .image_entry("layers/layer99", empty)
+        .image_entry("annotations/layer1", Vec::new())
+        .write_temp(test_name)
+        .unwrap()
+}
+
+fn entry(name: &str) -> String {
+    format!("Unnamed/layers/{}", name)
+}
+
+#[test]
+fn entries_of_nodes() {
+    let path = write("node_entries");
+    let file = KraFile::read(&path).unwrap();
+    let entries = file.node_entries();
+    assert_eq!(entries.len(), 3);
+    assert_eq!(
+        entries[&Uuid::from_u128(PAINT)],
+        [
+            entry("layer1"),
+            entry("layer1.defaultpixel"),
+            entry("layer1.f1"),
+            entry("layer1.icc"),
+            entry("layer1.keyframes.xml"),
+        ]
+    );
+    assert!(entries[&Uuid::from_u128(GROUP)].is_empty());
+    assert_eq!(entries[&Uuid::from_u128(CHILD)], [entry("layer10")]);
+}
+
+#[test]
+fn entries_without_archive() {
+    let path = write("node_entries_without_archive");
+    let config = ParsingConfiguration::new().keep_archive(false);
+    let file = KraFile::read_with_config(&path, config).unwrap();
+    assert_eq!(file.node_entries().len(), 3);
+}
+
+#[test]
+fn entries_without_nodes() {
+    let path = write("node_entries_without_nodes");
+    let file = KraFile::read_with_config(&path, ParsingConfiguration::preview()).unwrap();
+    assert!(file.node_entries().is_empty());
+    assert!(file.orphaned_entries().is_empty());
+}
+
+#[test]
+fn orphaned_entries() {
+    let path = write("orphaned_entries");
+    let file = KraFile::read(&path).unwrap();
+    // Entries outside of layers/ are not considered
+    assert_eq!(file.orphaned_entries(), &[entry("layer99")]);
+}
@@ -0,0 +1,102 @@
+use kra::{
+    layer::NodeKind,
+    testing::{DocumentBuilder, LayerBuilder},
+    KraFile,
+};
+
+#[test]
+fn nodetype_strings() {
+    for node_type in [
+        "paintlayer",
+        "grouplayer",
+        "filelayer",
+        "adjustmentlayer",
+        "generatorlayer",
+        "clonelayer",
+        "shapelayer",
+        "transparencymask",
+        "filtermask",
+        "transformmask",
+        "selectionmask",
+        "colorizemask",
+    ] {
+        let kind: NodeKind = node_type.parse().unwrap();
+        assert_eq!(kind.to_string(), node_type);
+        assert_eq!(kind.is_mask(), node_type.ends_with("mask"));
+    }
+    assert!("paintlayers".parse::<NodeKind>().is_err());
+}
+
+#[test]
+fn kind_of_parsed_nodes() {
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::group("Group").child(LayerBuilder::paint("Paint")))
+        .write_temp("kind_of_parsed_nodes")
+        .unwrap();
+
+    let file = KraFile::read(&path).unwrap();
+    let group = &file.layers()[0];
+    assert_eq!(group.kind(), NodeKind::GroupLayer);
+    assert_eq!(group.node_type().kind(), NodeKind::GroupLayer);
+    let kra::layer::NodeType::GroupLayer(props) = group.node_type() else {
+        unreachable!()
+    };
+    assert_eq!(props.layers()[0].kind(), NodeKind::PaintLayer);
+}
+
+#[test]
+fn node_counts() {
+    let inner = LayerBuilder::group("Inner")
+        .child(LayerBuilder::paint("A"))
+        .child(LayerBuilder::paint("B"));
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::group("Outer").child(inner))
+        .layer(LayerBuilder::paint("C"))
+        .write_temp("node_counts")
+        .unwrap();
+    let file = KraFile::read(path).unwrap();
+    assert_eq!(file.node_count(), 5);
+
+    let outer = &file.layers()[0];
+    assert_eq!(outer.descendant_count(), 3);
+    assert_eq!(file.layers()[1].descendant_count(), 0);
+    let kra::layer::NodeType::GroupLayer(props) = outer.node_type() else {
+        unreachable!()
+    };
+    assert_eq!(props.len(), 1);
+    assert!(!props.is_empty());
+}
+
+#[test]
+fn find_by_colorlabel_and_locked() {
+    use kra::layer::ColorLabel;
+
+    let labeled = |name: &str, label: u32, locked: bool| {
+        LayerBuilder::paint(name)
+            .attribute("colorlabel", &label.to_string())
+            .attribute("locked", if locked { "1" } else { "0" })
+    };
+    let path = DocumentBuilder::new(128, 128)
+        .layer(labeled("Red", 6, false))
+        .layer(LayerBuilder::group("Group").child(labeled("Red locked", 6, true)))
+        .layer(labeled("Grey", 8, true))
+        .write_temp("find_by_colorlabel")
+        .unwrap();
+    let file = KraFile::read(path).unwrap();
+
+    let names = |nodes: Vec<&kra::layer::Node>| -> Vec<String> {
+        nodes.iter().map(|node| node.name().to_owned()).collect()
+    };
+    assert_eq!(
+        names(file.find_by_colorlabel(ColorLabel::Red)),
+        ["Red", "Red locked"]
+    );
+    assert_eq!(
+        names(file.find_by_colorlabel(ColorLabel::NoLabel)),
+        ["Group"]
+    );
+    assert_eq!(names(file.find_locked()), ["Red locked", "Grey"]);
+    assert_eq!(file.layers()[2].color_label(), Some(ColorLabel::Grey));
+    assert_eq!(ColorLabel::from_index(9), None);
+    assert_eq!(ColorLabel::Purple.index(), 7);
+}
@@ -0,0 +1,280 @@
+use std::{fs::File, io::Read, path::Path};
+
+use kra::{
+    config::{
+        DoctypeCheck, DuplicateUuids, InvalidUtf8, Limits, MimetypeCheck, ParsingConfiguration,
+    },
+    error::{LimitError, ReadKraError},
+    id::Uuid,
+    layer::CompositeOp,
+    parse::parse_document,
+    testing::{DocumentBuilder, LayerBuilder},
+    KraFile,
+};
+use zip::{result::ZipError, ZipArchive};
+
+#[test]
+fn parse_bytes() {
+    let builder = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Top"))
+        .layer(LayerBuilder::group("Group").visible(false));
+    let document = parse_document(
+        builder.maindoc().as_bytes(),
+        builder.documentinfo().as_bytes(),
+        &ParsingConfiguration::default(),
+    )
+    .unwrap();
+    assert_eq!(document.meta().width(), 128);
+    assert_eq!(document.doc_info().about().title(), "Unnamed");
+    let names: Vec<&str> = document.layers().iter().map(|node| node.name()).collect();
+    assert_eq!(names, ["Top", "Group"]);
+    assert_eq!(document.files().len(), 2);
+}
+
+#[test]
+fn doctype_variations() {
+    let builder = DocumentBuilder::new(128, 128);
+    let maindoc = builder.maindoc().replace(
+        "'-//KDE//DTD krita 2.0//EN' 'http",
+        "\"-//KDE//DTD krita 2.0//EN\"\n    \"http",
+    );
+    let maindoc = maindoc.replace("krita-2.0.dtd'", "krita-2.0.dtd\"");
+    let config = ParsingConfiguration::default();
+    parse_document(
+        maindoc.as_bytes(),
+        builder.documentinfo().as_bytes(),
+        &config,
+    )
+    .unwrap();
+
+    let documentinfo = builder
+        .documentinfo()
+        .replace("document-info 1.1//EN", "document-info 1.2//EN");
+    let err = parse_document(maindoc.as_bytes(), documentinfo.as_bytes(), &config).unwrap_err();
+    assert!(matches!(err, ReadKraError::MetadataError(_)), "{err:?}");
+
+    let config = config.doctype_check(DoctypeCheck::Warn);
+    let document = parse_document(maindoc.as_bytes(), documentinfo.as_bytes(), &config).unwrap();
+    let mismatches = document.doctype_mismatches();
+    assert_eq!(mismatches.len(), 1);
+    assert_eq!(mismatches[0].entry(), "documentinfo.xml");
+    assert!(mismatches[0].found().contains("1.2"));
+}
+
+#[test]
+fn invalid_utf8_names() {
+    let builder = DocumentBuilder::new(128, 128).layer(LayerBuilder::paint("Old name"));
+    let (maindoc, documentinfo) = (builder.maindoc(), builder.documentinfo());
+    let start = maindoc.find("Old name").unwrap();
+    let mut maindoc = maindoc.into_bytes();
+    maindoc[start + 3] = 0xff;
+    let config = ParsingConfiguration::default();
+    let err = parse_document(&maindoc, documentinfo.as_bytes(), &config).unwrap_err();
+    assert!(matches!(err, ReadKraError::FileError(_)), "{err:?}");
+
+    let config = config.invalid_utf8(InvalidUtf8::Replace);
+    let document = parse_document(&maindoc, documentinfo.as_bytes(), &config).unwrap();
+    assert_eq!(document.layers()[0].name(), "Old\u{fffd}name");
+    let replaced = document.replaced_utf8();
+    assert_eq!(replaced.len(), 1);
+    assert_eq!(replaced[0].entry(), "maindoc.xml");
+    assert_eq!(replaced[0].nodes(), [Uuid::from_u128(1)]);
+}
+
+#[test]
+fn duplicate_uuids() {
+    let twice = Uuid::from_u128(1);
+    let builder = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("First"))
+        .layer(LayerBuilder::group("Group").child(LayerBuilder::paint("Second").uuid(twice)));
+    let (maindoc, documentinfo) = (builder.maindoc(), builder.documentinfo());
+    let parse = |policy| {
+        let config = ParsingConfiguration::default().duplicate_uuids(policy);
+        parse_document(maindoc.as_bytes(), documentinfo.as_bytes(), &config)
+    };
+
+    assert!(matches!(
+        parse(DuplicateUuids::Error),
+        Err(ReadKraError::MetadataError(_))
+    ));
+
+    let document = parse(DuplicateUuids::KeepFirst).unwrap();
+    assert_eq!(document.files().len(), 2);
+    let kra::layer::NodeType::GroupLayer(group) = document.layers()[1].node_type() else {
+        panic!("not a group");
+    };
+    assert!(group.is_empty());
+    let duplicates = document.duplicate_uuids();
+    assert_eq!(duplicates.len(), 1);
+    assert_eq!(
+        (duplicates[0].uuid(), duplicates[0].replacement()),
+        (&twice, None)
+    );
+
+    let document = parse(DuplicateUuids::Regenerate).unwrap();
+    assert_eq!(document.files().len(), 3);
+    let replacement = *document.duplicate_uuids()[0].replacement().unwrap();
+    let kra::layer::NodeType::GroupLayer(group) = document.layers()[1].node_type() else {
+        panic!("not a group");
+    };
+    let second = &group.layers()[0];
+    assert_eq!((second.name(), second.uuid()), ("Second", replacement));
+    assert_ne!(replacement, twice);
+    assert!(second
+        .attributes()
+        .contains(&("uuid".to_owned(), replacement.braced().to_string())));
+}
+
+#[test]
+fn same_as_read() {
+    let path =
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/clone-and-pass-through.kra");
+    let mut zip = ZipArchive::new(File::open(&path).unwrap()).unwrap();
+    let mut entry = |name: &str| {
+        let mut data = Vec::new();
+        zip.by_name(name).unwrap().read_to_end(&mut data).unwrap();
+        data
+    };
+    let (maindoc, documentinfo) = (entry("maindoc.xml"), entry("documentinfo.xml"));
+    let document =
+        parse_document(&maindoc, &documentinfo, &ParsingConfiguration::default()).unwrap();
+    let file = KraFile::read(&path).unwrap();
+    assert_eq!(document.meta(), file.meta());
+    assert_eq!(document.doc_info(), file.doc_info());
+    assert_eq!(
+        format!("{:?}", document.layers()),
+        format!("{:?}", file.layers())
+    );
+}
+
+#[test]
+fn size_limit() {
+    let builder = DocumentBuilder::new(128, 128);
+    let (maindoc, documentinfo) = (builder.maindoc(), builder.documentinfo());
+    assert!(maindoc.len() > documentinfo.len());
+    let config = ParsingConfiguration::default().limits(Limits {
+        max_xml_size: documentinfo.len() as u64,
+        ..Default::default()
+    });
+    let result = parse_document(maindoc.as_bytes(), documentinfo.as_bytes(), &config);
+    assert!(matches!(
+        result,
+        Err(ReadKraError::LimitExceeded(LimitError::EntrySize { entry, .. })) if entry == "maindoc.xml"
+    ));
+}
+
+#[test]
+fn missing_mimetype() {
+    let missing = DocumentBuilder::new(128, 128)
+        .without_entry("mimetype")
+        .write_temp("missing_mimetype")
+        .unwrap();
+    let other = DocumentBuilder::new(128, 128)
+        .entry("mimetype", "application/zip")
+        .write_temp("other_mimetype")
+        .unwrap();
+    assert!(matches!(
+        KraFile::read(&missing),
+        Err(ReadKraError::Entry { name, source: ZipError::FileNotFound }) if name == "mimetype"
+    ));
+    assert!(matches!(
+        KraFile::read(&other),
+        Err(ReadKraError::MimetypeMismatch)
+    ));
+
+    let config = ParsingConfiguration::new().mimetype_check(MimetypeCheck::Warn);
+    let file = KraFile::read_with_config(&missing, config.clone()).unwrap();
+    assert_eq!(file.unexpected_mimetype().unwrap().found(), None);
+    let file = KraFile::read_with_config(&other, config.clone()).unwrap();
+    assert_eq!(
+        file.unexpected_mimetype().unwrap().found(),
+        Some("application/zip")
+    );
+
+    let broken = DocumentBuilder::new(128, 128)
+        .without_entry("mimetype")
+        .without_entry("documentinfo.xml")
+        .entry("maindoc.xml", "<DOC")
+        .write_temp("missing_mimetype_broken")
+        .unwrap();
+    assert!(KraFile::read_with_config(&broken, config).is_err());
+}
+
+#[test]
+fn corrupt_entry() {
+    let path = DocumentBuilder::new(128, 128)
+        .write_temp("corrupt_entry")
+        .unwrap();
+    let mut bytes = std::fs::read(&path).unwrap();
+    // Local header of the entry, its name, then compressed data
+    let name = b"maindoc.xml";
+    let start = bytes
+        .windows(name.len())
+        .position(|window| window == name)
+        .unwrap();
+    for byte in &mut bytes[start + name.len() + 10..start + name.len() + 40] {
+        *byte ^= 0x55;
+    }
+    std::fs::write(&path, bytes).unwrap();
+
+    let err = KraFile::read(&path).unwrap_err();
+    assert!(
+        matches!(&err, ReadKraError::Entry { name, .. } if name == "maindoc.xml"),
+        "{err:?}"
+    );
+    assert!(err
+        .to_string()
+        .starts_with("could not read entry maindoc.xml: "));
+}
+
+#[test]
+fn error_location() {
+    let builder = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Ёлка 🎄").attribute("opacity", "opaque"));
+    let maindoc = builder.maindoc();
+    let config = ParsingConfiguration::default();
+    let err = parse_document(
+        maindoc.as_bytes(),
+        builder.documentinfo().as_bytes(),
+        &config,
+    )
+    .unwrap_err();
+    let ReadKraError::MetadataError(err) = err else {
+        panic!("unexpected error: {err:?}");
+    };
+    assert_eq!(err.file(), Path::new("maindoc.xml"));
+
+    // Parsing stops after the tag of the layer
+    let end = maindoc.find("channelflags=\"\"/>").unwrap() + "channelflags=\"\"/>".len();
+    assert_eq!(err.byte_offset(), end);
+    let line = maindoc[..end].lines().last().unwrap();
+    assert_eq!(err.line(), maindoc[..end].lines().count());
+    assert_eq!(err.column(), line.chars().count() + 1);
+    assert!(err.column() < line.len());
+    assert!(err
+        .to_string()
+        .starts_with(&format!("maindoc.xml:{}:{}: ", err.line(), err.column())));
+}
+
+#[test]
+fn composite_ops_of_masks() {
+    let layer = LayerBuilder::paint("Layer")
+        .with_mask(LayerBuilder::mask("Mask", "transparencymask").composite_op("multiply"))
+        .with_mask(LayerBuilder::mask("Mask", "transformmask").without_attribute("compositeop"))
+        .with_mask(LayerBuilder::mask("Mask", "selectionmask").composite_op("erase"));
+    let path = DocumentBuilder::new(128, 128)
+        .layer(layer)
+        .write_temp("mask_composite_ops")
+        .unwrap();
+    let file = KraFile::read(&path).unwrap();
+    let ops: Vec<_> = file.layers()[0]
+        .masks()
+        .iter()
+        .map(|mask| mask.node_type().mask_composite_op())
+        .collect();
+    assert_eq!(
+        ops,
+        [Some(CompositeOp::Multiply), None, Some(CompositeOp::Erase)]
+    );
+    assert_eq!(file.layers()[0].node_type().mask_composite_op(), None);
+}
@@ -0,0 +1,79 @@
+use std::path::Path;
+
+use kra::{
+    error::{DataError, PathTraversalError},
+    id::Uuid,
+    layer::NodeType,
+    testing::{DocumentBuilder, LayerBuilder},
+    KraFile,
+};
+
+// UUIDs of the nodes, in document order
+const ESCAPING: u128 = 1;
+const ABSOLUTE: u128 = 2;
+const NORMAL: u128 = 3;
+
+#[test]
+fn layer_filename() {
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Escaping").filename("../../mimetype"))
+        .layer(LayerBuilder::paint("Absolute").filename("/etc/passwd"))
+        .layer(LayerBuilder::paint("Normal").filename("./sub/../layer4"))
+        .image_entry(
+            "layers/layer4",
+            LayerBuilder::paint("Normal")
+                .fill_tile(0, 0, [0, 0, 255, 255])
+                .pixel_data(),
+        )
+        .write_temp("traversal_layer_filename")
+        .unwrap();
+
+    let mut file = KraFile::read(&path).unwrap();
+    assert!(matches!(
+        file.decode_layer(&Uuid::from_u128(ESCAPING)),
+        Err(DataError::PathTraversal(PathTraversalError(path))) if path == "../../mimetype"
+    ));
+    assert!(matches!(
+        file.decode_layer(&Uuid::from_u128(ABSOLUTE)),
+        Err(DataError::PathTraversal(_))
+    ));
+    assert!(matches!(
+        file.find_empty_layers(),
+        Err(DataError::PathTraversal(_))
+    ));
+    // Normalized path that stays inside is fine
+    file.decode_layer(&Uuid::from_u128(NORMAL)).unwrap();
+}
+
+#[test]
+fn file_layer_source() {
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::file_layer("Escaping", "../../secret.png"))
+        .layer(LayerBuilder::file_layer("Absolute", "/etc/passwd"))
+        .layer(LayerBuilder::file_layer(
+            "Normal",
+            "images/./a/../linked.png",
+        ))
+        .write_temp("traversal_file_layer_source")
+        .unwrap();
+
+    let file = KraFile::read(&path).unwrap();
+    let base = Path::new("/srv/uploads");
+    let resolved: Vec<_> = file
+        .layers()
+        .iter()
+        .map(|node| match node.node_type() {
+            NodeType::FileLayer(props) => props.resolve_source(base),
+            other => panic!("unexpected node type: {:?}", other),
+        })
+        .collect();
+    assert_eq!(
+        resolved[0],
+        Err(PathTraversalError("../../secret.png".into()))
+    );
+    assert!(resolved[1].is_err());
+    assert_eq!(
+        resolved[2].as_deref().unwrap(),
+        Path::new("/srv/uploads/images/linked.png")
+    );
+}
@@ -0,0 +1,44 @@
+use kra::{
+    config::ParsingConfiguration,
+    error::DataError,
+    id::Uuid,
+    testing::{DocumentBuilder, LayerBuilder, TempPath},
+    KraFile,
+};
+
+fn write(test_name: &str) -> TempPath {
+    DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Paint").fill_tile(0, 0, [0, 0, 255, 255]))
+        .layer(LayerBuilder::group("Group").child(LayerBuilder::paint("Child")))
+        .entry("mergedimage.png", b"not really a png".to_vec())
+        .write_temp(test_name)
+        .unwrap()
+}
+
+#[test]
+fn full() {
+    let path = write("preset_full");
+    let mut file = KraFile::read_with_config(&path, ParsingConfiguration::full()).unwrap();
+    assert_eq!(file.layers().len(), 2);
+    assert_eq!(file.files().len(), 3);
+    file.decode_layer(&Uuid::from_u128(1)).unwrap();
+}
+
+#[test]
+fn metadata_only() {
+    let path = write("preset_metadata_only");
+    let mut file = KraFile::read_with_config(&path, ParsingConfiguration::metadata_only()).unwrap();
+    assert_eq!(file.meta().width(), 128);
+    assert!(!file.meta().mirror_axis().mirror_horizontal());
+    assert!(file.layers().is_empty());
+    assert!(file.files().is_empty());
+    assert!(matches!(file.merged_image(), Err(DataError::NoArchive)));
+}
+
+#[test]
+fn preview() {
+    let path = write("preset_preview");
+    let mut file = KraFile::read_with_config(&path, ParsingConfiguration::preview()).unwrap();
+    assert!(file.layers().is_empty());
+    assert_eq!(file.merged_image().unwrap(), b"not really a png");
+}
@@ -0,0 +1,60 @@
+use std::{fs, process};
+
+use kra::{
+    archive::unpack,
+    config::ParsingConfiguration,
+    report::ReadReport,
+    testing::{DocumentBuilder, LayerBuilder, TempPath},
+    KraFile,
+};
+
+fn document() -> DocumentBuilder {
+    DocumentBuilder::new(128, 128).layer(LayerBuilder::paint("Layer").fill_tile(
+        0,
+        0,
+        [255, 0, 0, 255],
+    ))
+}
+
+fn read(test_name: &str, config: ParsingConfiguration) -> (KraFile, ReadReport) {
+    let path = document().write_temp(test_name).unwrap();
+    KraFile::read_with_report(path, config).unwrap()
+}
+
+#[test]
+fn phases_are_reported() {
+    let (file, report) = read("phases_are_reported", ParsingConfiguration::default());
+    assert_eq!(file.layers().len(), 1);
+
+    let xml = (document().maindoc().len() + document().documentinfo().len()) as u64;
+    let mimetype = "application/x-krita".len() as u64;
+    assert_eq!(report.zip_io().bytes(), xml + mimetype);
+    assert_eq!(
+        report.xml_parse().bytes() + report.tree_build().bytes(),
+        xml
+    );
+    assert!(report.tree_build().bytes() > 0);
+    assert_eq!(report.data_load().bytes(), 0);
+    assert!(report.total() >= report.zip_io().time());
+    assert_eq!(report.to_string().lines().count(), 5);
+}
+
+#[test]
+fn skipped_tree() {
+    let config = ParsingConfiguration::metadata_only();
+    let (file, report) = read("skipped_tree", config);
+    assert!(file.layers().is_empty());
+    // The tree is still gone through to find the end of the metadata
+    assert!(report.tree_build().bytes() > 0);
+}
+
+#[test]
+fn directory() {
+    let path = document().write_temp("report_directory").unwrap();
+    let dir = TempPath::new(&format!("kra-report-directory-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    unpack(&path, &dir).unwrap();
+    let (_, from_dir) = KraFile::read_with_report(&dir, ParsingConfiguration::default()).unwrap();
+    let (_, from_zip) = KraFile::read_with_report(&path, ParsingConfiguration::default()).unwrap();
+    assert_eq!(from_dir.zip_io().bytes(), from_zip.zip_io().bytes());
+}
@@ -0,0 +1,128 @@
+use std::{fs, path::PathBuf, process, time::Duration};
+
+use kra::{
+    config::ParsingConfiguration,
+    error::ReadKraError,
+    scan::{scan, ScanOptions},
+    testing::{DocumentBuilder, LayerBuilder, TempPath},
+};
+use zip::ZipArchive;
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+// Directory with the 5.2 fixture at the top, the 5.0 one and an unpacked 5.2 one
+// in subdirectories, a broken document, and documents that are not looked at
+fn tree(test_name: &str) -> TempPath {
+    let root = TempPath::new(&format!("kra-{}-{}", test_name, process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("sub/unpacked")).unwrap();
+    fs::create_dir_all(root.join(".hidden")).unwrap();
+    fs::copy(fixture("clone-and-pass-through.kra"), root.join("a.kra")).unwrap();
+    fs::copy(fixture("group-and-mask.kra"), root.join("sub/b.KRA")).unwrap();
+    fs::copy(
+        fixture("clone-and-pass-through.kra"),
+        root.join(".hidden/c.kra"),
+    )
+    .unwrap();
+    fs::copy(fixture("clone-and-pass-through.kra"), root.join("a.kra~")).unwrap();
+    fs::write(root.join("broken.kra"), b"not a zip").unwrap();
+    let mut zip =
+        ZipArchive::new(fs::File::open(fixture("clone-and-pass-through.kra")).unwrap()).unwrap();
+    zip.extract(root.join("sub/unpacked")).unwrap();
+    root
+}
+
+#[test]
+fn scan_tree() {
+    let root = tree("scan_tree");
+    let report = scan(&root, &ScanOptions::new().threads(3)).unwrap();
+    let paths: Vec<PathBuf> = report
+        .items()
+        .iter()
+        .map(|item| item.path().strip_prefix(&root).unwrap().to_owned())
+        .collect();
+    assert_eq!(
+        paths,
+        ["a.kra", "broken.kra", "sub/b.KRA", "sub/unpacked"].map(PathBuf::from)
+    );
+
+    let a = report.items()[0].result().unwrap();
+    assert_eq!(a.dimensions(), (64, 128));
+    assert_eq!(
+        a.file_size(),
+        fs::metadata(root.join("a.kra")).unwrap().len()
+    );
+    let unpacked = report.items()[3].result().unwrap();
+    assert_eq!(unpacked.node_count(), a.node_count());
+    assert!(matches!(
+        report.items()[1].result(),
+        Err(ReadKraError::ZipError(_))
+    ));
+
+    let stats = report.stats();
+    assert_eq!((stats.documents(), stats.failed()), (4, 1));
+    assert_eq!(report.failed().count(), 1);
+    let summary = report.to_string();
+    assert!(summary.contains("broken.kra: error: "));
+    assert!(summary
+        .lines()
+        .last()
+        .unwrap()
+        .starts_with("4 documents (1 failed), "));
+}
+
+#[test]
+fn scan_options() {
+    let root = tree("scan_options");
+    let options = ScanOptions::new()
+        .recursive(false)
+        .config(ParsingConfiguration::metadata_only());
+    let report = scan(&root, &options).unwrap();
+    assert_eq!(report.items().len(), 2);
+    assert_eq!(report.items()[0].result().unwrap().node_count(), 0);
+    assert_eq!(report.stats().nodes(), 0);
+}
+
+#[test]
+fn editing_stats() {
+    let root = TempPath::new(&format!("kra-editing_stats-{}", process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    let documents = [
+        ("a", "3600", "3", "2023-05-01T10:00:00"),
+        ("b", "", "1", ""),
+        ("c", "7200", "12", "2021-02-03T04:05:06"),
+    ];
+    for (name, time, cycles, created) in documents {
+        DocumentBuilder::new(128, 128)
+            .layer(LayerBuilder::paint("Layer"))
+            .info("editing-time", time)
+            .info("editing-cycles", cycles)
+            .info("creation-date", created)
+            .write_to(root.join(format!("{name}.kra")))
+            .unwrap();
+    }
+    fs::write(root.join("broken.kra"), b"not a zip").unwrap();
+
+    let stats = scan(&root, &ScanOptions::new()).unwrap().editing_stats();
+    assert_eq!(stats.editing_time(), Duration::from_secs(3 * 3600));
+    assert_eq!(stats.total_hours(), 3.0);
+    assert_eq!((stats.cycles(), stats.layers()), (16, 3));
+    assert_eq!(stats.earliest_creation(), Some("2021-02-03T04:05:06"));
+    assert_eq!(stats.latest_creation(), Some("2023-05-01T10:00:00"));
+    let most_edited: Vec<PathBuf> = stats
+        .most_edited(5)
+        .iter()
+        .map(|(path, _)| path.clone())
+        .collect();
+    assert_eq!(most_edited, [root.join("c.kra"), root.join("a.kra")]);
+    assert_eq!(stats.most_edited(1).len(), 1);
+    assert_eq!(
+        stats.to_string(),
+        "3.0 hours of editing over 2 documents, 16 saves, 3 layers"
+    );
+}
@@ -0,0 +1,71 @@
+use std::{env, fs, path::Path};
+
+use kra::{
+    layer::{Node, NodeKind, NodeType},
+    schema::{common_attributes, node_schemas, to_json, to_markdown},
+    KraFile,
+};
+
+// Required attributes of the schema are in every node of the fixtures
+fn check_node(node: &Node) {
+    let schema = node_schemas()
+        .into_iter()
+        .find(|schema| schema.kind() == node.kind())
+        .unwrap();
+    let required = common_attributes()
+        .into_iter()
+        .chain(schema.attributes().iter().copied())
+        .filter(|attribute| !attribute.optional());
+    for attribute in required {
+        assert!(
+            node.attributes()
+                .iter()
+                .any(|(name, _)| name == attribute.qname()),
+            "{} of {} is missing",
+            attribute.qname(),
+            node.kind()
+        );
+    }
+    if let NodeType::GroupLayer(props) = node.node_type() {
+        props.layers().iter().for_each(check_node);
+    }
+    node.masks().iter().for_each(check_node);
+}
+
+#[test]
+fn fixtures_match_schema() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|ext| ext == "kra") {
+            KraFile::read(&path)
+                .unwrap()
+                .layers()
+                .iter()
+                .for_each(check_node);
+        }
+    }
+}
+
+#[test]
+fn descriptions() {
+    let schemas = node_schemas();
+    assert_eq!(schemas.len(), 12);
+    let group = schemas
+        .iter()
+        .find(|schema| schema.kind() == NodeKind::GroupLayer)
+        .unwrap();
+    // Children are not an attribute
+    assert!(group
+        .attributes()
+        .iter()
+        .all(|attribute| attribute.field() != "layers"));
+    assert_eq!(group.element(), "layer");
+
+    let markdown = to_markdown();
+    assert!(markdown.contains("## `transformmask` (mask)"));
+    assert!(markdown.contains("| `colorlabel` | `colorlabel` | `Option<u32>` | no |"));
+    let json = to_json();
+    assert!(json.starts_with("{\"common\":[{\"name\":\"nodetype\""));
+    assert!(json.contains("\"nodetype\":\"paintlayer\",\"element\":\"layer\""));
+}
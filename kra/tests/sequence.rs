@@ -0,0 +1,74 @@
+#![cfg(feature = "image")]
+
+use std::process;
+
+use image::{Rgba, RgbaImage};
+use kra::{error::SequenceError, layer::NodeType, testing::TempPath, KraFile};
+
+#[test]
+fn from_image_sequence() {
+    let colors = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 128]];
+    let paths: Vec<_> = colors
+        .iter()
+        .enumerate()
+        .map(|(index, color)| {
+            let path = TempPath::new(&format!(
+                "kra-sequence-{}-walk_{:03}.png",
+                process::id(),
+                index + 8
+            ));
+            RgbaImage::from_pixel(100, 70, Rgba(*color))
+                .save(&path)
+                .unwrap();
+            path
+        })
+        .collect();
+    // Given out of order, and 10 comes after 9
+    let shuffled = [&paths[2], &paths[0], &paths[1]];
+    let mut file = KraFile::from_image_sequence(&shuffled, 12).unwrap();
+
+    assert_eq!((file.meta().width(), file.meta().height()), (100, 70));
+    let [layer] = file.layers() else {
+        panic!("expected one layer");
+    };
+    assert!(matches!(layer.node_type(), NodeType::PaintLayer(_)));
+    let uuid = layer.uuid();
+    let entries = &file.node_entries()[&uuid];
+    assert_eq!(
+        entries,
+        &[
+            "Unnamed/layers/layer2",
+            "Unnamed/layers/layer2.f1",
+            "Unnamed/layers/layer2.f2",
+            "Unnamed/layers/layer2.keyframes.xml",
+        ]
+    );
+
+    let first = file.decode_layer(&uuid).unwrap().to_rgba_image().unwrap();
+    assert_eq!(first.get_pixel(99, 69), &Rgba([255, 0, 0, 255]));
+    assert_eq!(first.get_pixel(100, 69), &Rgba([0, 0, 0, 0]));
+    let merged = file.merged_image_decoded().unwrap().to_rgba8();
+    assert_eq!(merged.dimensions(), (100, 70));
+    assert_eq!(merged.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+}
+
+#[test]
+fn mismatched_frames() {
+    assert!(matches!(
+        KraFile::from_frames(&[], 24),
+        Err(SequenceError::NoFrames)
+    ));
+    let frames = [
+        RgbaImage::new(4, 4),
+        RgbaImage::new(4, 4),
+        RgbaImage::new(4, 5),
+    ];
+    assert!(matches!(
+        KraFile::from_frames(&frames, 24),
+        Err(SequenceError::SizeMismatch(2))
+    ));
+    assert!(matches!(
+        KraFile::from_image_sequence(&["walk.png"], 24),
+        Err(SequenceError::Unnumbered(_))
+    ));
+}
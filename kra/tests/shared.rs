@@ -0,0 +1,99 @@
+use std::{fs::File, process, thread};
+
+use kra::{
+    archive::ZipSource,
+    config::ParsingConfiguration,
+    error::DataError,
+    id::Uuid,
+    shared::LayerReader,
+    testing::{DocumentBuilder, LayerBuilder, TempPath},
+    KraFile,
+};
+
+const LAYERS: u32 = 16;
+
+// Paint layers 1..=LAYERS under a group with UUID LAYERS + 1
+fn document() -> DocumentBuilder {
+    let mut group = LayerBuilder::group("Group").uuid(Uuid::from_u128(LAYERS as u128 + 1));
+    for index in 1..=LAYERS {
+        let value = (index * 15) as u8;
+        group = group.child(
+            LayerBuilder::paint(&format!("Layer {}", index))
+                .uuid(Uuid::from_u128(index as u128))
+                .offset(index * 64, 0)
+                .fill_tile(0, 0, [value, 0, 255 - value, 255]),
+        );
+    }
+    DocumentBuilder::new(2048, 64).layer(group)
+}
+
+fn write(test_name: &str) -> TempPath {
+    document().write_temp(test_name).unwrap()
+}
+
+fn decode_in_threads(file: &mut KraFile) {
+    let reader = file.layer_reader().unwrap();
+    let decoded: Vec<_> = thread::scope(|scope| {
+        let handles: Vec<_> = (1..=LAYERS)
+            .map(|index| {
+                let reader = &reader;
+                scope.spawn(move || reader.decode_layer(&Uuid::from_u128(index as u128)))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap().unwrap())
+            .collect()
+    });
+    for (index, data) in (1..=LAYERS).zip(decoded) {
+        assert_eq!(
+            data,
+            file.decode_layer(&Uuid::from_u128(index as u128)).unwrap()
+        );
+    }
+}
+
+#[test]
+fn decode_from_path() {
+    let path = write("shared_decode_from_path");
+    let mut file = KraFile::read(&path).unwrap();
+    decode_in_threads(&mut file);
+}
+
+#[test]
+fn decode_from_bytes_and_directory() {
+    let source = ZipSource::from_bytes(document().to_bytes()).unwrap();
+    let mut file = KraFile::read_from(source, ParsingConfiguration::default()).unwrap();
+    decode_in_threads(&mut file);
+
+    let dir = TempPath::new(&format!("kra-shared-directory-{}", process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    kra::archive::unpack(write("shared_directory"), &dir).unwrap();
+    let mut file = KraFile::read(&dir).unwrap();
+    decode_in_threads(&mut file);
+}
+
+#[test]
+fn errors() {
+    let path = write("shared_errors");
+    let file = KraFile::read(&path).unwrap();
+    let reader = file.layer_reader().unwrap();
+    assert!(matches!(
+        reader.decode_layer(&Uuid::from_u128(LAYERS as u128 + 1)),
+        Err(DataError::NoPixelData(_))
+    ));
+    assert!(matches!(
+        reader.decode_layer(&Uuid::from_u128(1000)),
+        Err(DataError::UnknownNode(_))
+    ));
+
+    let source = ZipSource::new(File::open(write("shared_not_reopenable")).unwrap()).unwrap();
+    let file = KraFile::read_from(source, ParsingConfiguration::default()).unwrap();
+    assert!(matches!(file.layer_reader(), Err(DataError::NotReopenable)));
+}
+
+#[test]
+fn reader_is_shareable() {
+    fn shareable<T: Send + Sync>() {}
+    shareable::<LayerReader>();
+}
@@ -0,0 +1,66 @@
+use kra::{
+    config::{ParsingConfiguration, UnknownAttributes},
+    error::ReadKraError,
+    id::Uuid,
+    layer::NodeKind,
+    testing::{DocumentBuilder, LayerBuilder, TempPath},
+    KraFile,
+};
+
+// Paint layer with an attribute from the future, inside of a group without any
+fn write(test_name: &str) -> TempPath {
+    DocumentBuilder::new(128, 128)
+        .layer(
+            LayerBuilder::group("Group")
+                .child(LayerBuilder::paint("Layer").attribute("hologram", "1")),
+        )
+        .write_temp(test_name)
+        .unwrap()
+}
+
+fn read(test_name: &str, mode: UnknownAttributes) -> Result<KraFile, ReadKraError> {
+    let config = ParsingConfiguration::default().unknown_attributes(mode);
+    KraFile::read_with_config(write(test_name), config)
+}
+
+#[test]
+fn ignored_by_default() {
+    let file = read("strict_ignored", UnknownAttributes::Ignore).unwrap();
+    assert!(file.unknown_attributes().is_empty());
+}
+
+#[test]
+fn warn() {
+    let file = read("strict_warn", UnknownAttributes::Warn).unwrap();
+    let [unknown] = file.unknown_attributes() else {
+        panic!("{:?}", file.unknown_attributes());
+    };
+    assert_eq!(unknown.name(), "hologram");
+    assert_eq!(unknown.kind(), NodeKind::PaintLayer);
+    assert_eq!(unknown.uuid(), &Uuid::from_u128(2));
+}
+
+#[test]
+fn error() {
+    let err = read("strict_error", UnknownAttributes::Error).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("unknown attribute hologram of paintlayer"));
+
+    let path = write("strict_preset");
+    assert!(KraFile::read_with_config(path, ParsingConfiguration::strict()).is_err());
+}
+
+#[test]
+fn fixtures_are_known() {
+    for name in [
+        "paint",
+        "group-and-mask",
+        "filter-and-fill",
+        "clone-and-pass-through",
+        "empty",
+    ] {
+        let path = format!("{}/tests/fixtures/{}.kra", env!("CARGO_MANIFEST_DIR"), name);
+        KraFile::read_with_config(path, ParsingConfiguration::strict()).unwrap();
+    }
+}
@@ -0,0 +1,54 @@
+use kra::{
+    id::Uuid,
+    metadata::DocInfoField,
+    testing::{DocumentBuilder, LayerBuilder},
+    KraFile,
+};
+
+#[test]
+fn blank_copy_of_layers() {
+    let path = DocumentBuilder::new(128, 128)
+        .layer(
+            LayerBuilder::paint("Background")
+                .fill_tile(0, 0, [255, 255, 255, 255])
+                .default_pixel([255, 255, 255, 255]),
+        )
+        .layer(
+            LayerBuilder::group("Characters").child(
+                LayerBuilder::paint("Lines")
+                    .visible(false)
+                    .fill_tile(64, 0, [0, 0, 0, 255]),
+            ),
+        )
+        .image_entry("layers/layer9", LayerBuilder::paint("Orphan").pixel_data())
+        .entry("mergedimage.png", vec![1, 2, 3])
+        .write_temp("blank_copy_of_layers")
+        .unwrap();
+    let mut template = KraFile::read(&path).unwrap();
+    template.set_doc_info(DocInfoField::Title, "Standard layers");
+
+    let mut file = KraFile::from_template(&template).unwrap();
+    let names = |file: &KraFile| -> Vec<String> {
+        let mut paths: Vec<String> = file.display_paths().into_values().collect();
+        paths.sort();
+        paths
+    };
+    assert_eq!(names(&file), names(&template));
+    assert_eq!(
+        names(&file),
+        ["Background", "Characters", "Characters/Lines"]
+    );
+    assert_eq!(file.meta().width(), template.meta().width());
+    assert_eq!(file.doc_info().about().title(), "Standard layers");
+    let lines = file.find_by_display_path("Characters/Lines").unwrap();
+    assert!(!lines.visible());
+
+    let report = file.find_empty_layers().unwrap();
+    let mut empty = report.empty().to_vec();
+    empty.sort();
+    assert_eq!(empty, [Uuid::from_u128(1), Uuid::from_u128(3)]);
+    let entries = file.file().unwrap().entry_names();
+    assert!(!entries.contains(&"mergedimage.png".to_owned()));
+    assert!(!entries.iter().any(|name| name.ends_with("layer9")));
+    assert!(file.orphaned_entries().is_empty());
+}
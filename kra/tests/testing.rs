@@ -0,0 +1,50 @@
+use kra::{
+    id::Uuid,
+    layer::{NodeKind, NodeType},
+    testing::{DocumentBuilder, LayerBuilder},
+    KraFile,
+};
+
+#[test]
+fn built_document_parses() {
+    let document = DocumentBuilder::new(256, 128)
+        .name("Built & named")
+        .layer(
+            LayerBuilder::group("Group")
+                .child(LayerBuilder::paint("Inner").fill_tile(64, 0, [1, 2, 3, 255]))
+                .child(LayerBuilder::paint("Hidden").visible(false)),
+        )
+        .layer(
+            LayerBuilder::paint("Background")
+                .uuid(Uuid::from_u128(42))
+                .default_pixel([255, 255, 255, 255]),
+        );
+    let path = document.write_temp("built_document_parses").unwrap();
+
+    let mut file = KraFile::read(&path).unwrap();
+    assert_eq!(file.meta().name(), "Built & named");
+    assert_eq!((file.meta().width(), file.meta().height()), (256, 128));
+
+    let kinds: Vec<_> = file.layers().iter().map(|node| node.kind()).collect();
+    assert_eq!(kinds, [NodeKind::GroupLayer, NodeKind::PaintLayer]);
+    let NodeType::GroupLayer(group) = file.layers()[0].node_type() else {
+        unreachable!()
+    };
+    let names: Vec<_> = group.layers().iter().map(|node| node.name()).collect();
+    assert_eq!(names, ["Inner", "Hidden"]);
+    assert_eq!(file.layers()[1].uuid(), Uuid::from_u128(42));
+
+    let inner = file.decode_layer(&Uuid::from_u128(2)).unwrap();
+    assert_eq!((inner.x(), inner.width()), (64, 64));
+
+    let report = file.find_empty_layers().unwrap();
+    assert_eq!(report.empty(), &[Uuid::from_u128(3)]);
+}
+
+#[test]
+fn to_bytes_is_a_zip_archive() {
+    let bytes = DocumentBuilder::new(64, 64)
+        .entry("mergedimage.png", vec![1, 2, 3])
+        .to_bytes();
+    assert!(bytes.starts_with(b"PK"));
+}
@@ -0,0 +1,69 @@
+use std::path::Path;
+
+use kra::{
+    config::ParsingConfiguration,
+    id::Uuid,
+    testing::{DocumentBuilder, LayerBuilder},
+    KraFile,
+};
+
+fn fixture() -> &'static Path {
+    Path::new(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/clone-and-pass-through.kra"
+    ))
+}
+
+#[test]
+fn deterministic() {
+    let first = KraFile::read(fixture()).unwrap().textconv().unwrap();
+    let second = KraFile::read(fixture()).unwrap().textconv().unwrap();
+    assert_eq!(first, second);
+    assert!(first.starts_with("document\n  name: \"golden-5.2\"\n"));
+    assert!(first.contains("\n  size: \"64x128\"\n"));
+    assert!(first.contains(
+        "\n    paintlayer \"Lines\" channelflags=\"\" channellockflags=\"1111\" collapsed=\"0\""
+    ));
+    assert!(first.contains("\n  golden-5.2/layers/layer11 size=16455 crc32="));
+    assert!(first.contains(" node=\"Pass-through/Lines\"\n"));
+    assert!(!first.contains("maindoc.xml"));
+    assert!(!first.contains("editing"));
+}
+
+#[test]
+fn shows_changes() {
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Paint").fill_tile(0, 0, [1, 2, 3, 255]))
+        .layer(LayerBuilder::group("Group"))
+        .write_temp("textconv_changes")
+        .unwrap();
+    let mut file = KraFile::read(&path).unwrap();
+    let before = file.textconv().unwrap();
+    file.move_node(&Uuid::from_u128(1), Some(&Uuid::from_u128(2)), 0)
+        .unwrap();
+    let after = file.textconv().unwrap();
+
+    let nodes = |text: &str| -> Vec<String> {
+        text.lines()
+            .skip_while(|line| *line != "nodes")
+            .take_while(|line| *line != "entries")
+            .map(|line| line.split(" channelflags").next().unwrap().to_owned())
+            .collect()
+    };
+    assert_eq!(
+        nodes(&before),
+        ["nodes", "  paintlayer \"Paint\"", "  grouplayer \"Group\" collapsed=\"0\" colorlabel=\"0\" compositeop=\"normal\" filename=\"layer2\" intimeline=\"0\" locked=\"0\" opacity=\"255\" passthrough=\"0\" uuid=\"{00000000-0000-0000-0000-000000000002}\" visible=\"1\" x=\"0\" y=\"0\""]
+    );
+    assert_eq!(nodes(&after)[2], "    paintlayer \"Paint\"");
+}
+
+#[test]
+fn without_archive() {
+    let config = ParsingConfiguration::default().keep_archive(false);
+    let text = KraFile::read_with_config(fixture(), config)
+        .unwrap()
+        .textconv()
+        .unwrap();
+    assert!(text.ends_with("\n  paintlayer \"Background\" channelflags=\"\" channellockflags=\"1111\" collapsed=\"0\" colorlabel=\"0\" colorspacename=\"RGBA\" compositeop=\"normal\" filename=\"layer1\" intimeline=\"0\" locked=\"0\" opacity=\"255\" uuid=\"{00000000-0000-0000-0000-000000000001}\" visible=\"1\" x=\"0\" y=\"0\"\n"));
+    assert!(!text.contains("entries"));
+}
@@ -0,0 +1,45 @@
+use kra::{
+    geom::{IPoint, IRect},
+    testing::LayerBuilder,
+    tile::{
+        delinearize, linearize, lzf_compress, lzf_decompress, stores_bgra, tile_coords,
+        tile_origin, tiles_covering, HEADER_MAGIC, TILE_SIZE,
+    },
+    Colorspace,
+};
+
+#[test]
+fn grid() {
+    assert_eq!(tile_coords(IPoint::new(63, 64)), IPoint::new(0, 1));
+    assert_eq!(tile_coords(IPoint::new(-1, -64)), IPoint::new(-1, -1));
+    assert_eq!(tile_origin(IPoint::new(-1, 130)), IPoint::new(-64, 128));
+
+    let tiles: Vec<IPoint> = tiles_covering(IRect::new(-10, 60, 80, 5)).collect();
+    assert_eq!(
+        tiles,
+        [(-64, 0), (0, 0), (64, 0), (-64, 64), (0, 64), (64, 64)].map(|(x, y)| IPoint::new(x, y))
+    );
+    assert_eq!(tiles_covering(IRect::new(5, 5, 0, 10)).count(), 0);
+    assert_eq!(
+        tiles_covering(IRect::new(0, 0, TILE_SIZE, TILE_SIZE)).count(),
+        1
+    );
+}
+
+#[test]
+fn layout() {
+    assert!(LayerBuilder::paint("Empty")
+        .pixel_data()
+        .starts_with(HEADER_MAGIC));
+    assert!(stores_bgra(Colorspace::RGBA));
+    assert!(!stores_bgra(Colorspace::RGBAF32));
+
+    let pixels: Vec<u8> = (0..64 * 64 * 4).map(|index| (index % 7) as u8).collect();
+    let planar = linearize(&pixels, 4);
+    assert_eq!(planar[..3], [0, 4, 1]);
+    let compressed = lzf_compress(&planar);
+    assert!(compressed.len() < planar.len());
+    let decompressed = lzf_decompress(&compressed, planar.len()).unwrap();
+    assert_eq!(delinearize(&decompressed, 4), pixels);
+    assert!(lzf_decompress(&compressed, planar.len() - 1).is_err());
+}
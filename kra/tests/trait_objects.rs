@@ -0,0 +1,49 @@
+use kra::{
+    layer::{CommonNodeProperties, CompositeOp, LayerProperties},
+    testing::{DocumentBuilder, LayerBuilder},
+    KraFile,
+};
+
+#[test]
+fn heterogeneous_collections() {
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Paint").visible(false))
+        .layer(LayerBuilder::group("Group"))
+        .write_temp("heterogeneous_collections")
+        .unwrap();
+    let file = KraFile::read(&path).unwrap();
+
+    let common: Vec<&dyn CommonNodeProperties> =
+        file.layers().iter().map(|node| node.as_common()).collect();
+    let names: Vec<&str> = common.iter().map(|node| node.name()).collect();
+    assert_eq!(names, ["Paint", "Group"]);
+    assert!(!common[0].visible());
+    assert!(common[1].visible());
+
+    let layers: Vec<&dyn LayerProperties> = file
+        .layers()
+        .iter()
+        .filter_map(|node| node.as_layer())
+        .collect();
+    assert_eq!(layers.len(), 2);
+    assert!(layers
+        .iter()
+        .all(|props| props.opacity() == 255 && props.composite_op() == CompositeOp::Normal));
+}
+
+#[test]
+fn common_view() {
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Paint").visible(false))
+        .write_temp("common_view")
+        .unwrap();
+    let file = KraFile::read(&path).unwrap();
+
+    let node = &file.layers()[0];
+    let common = node.common();
+    assert_eq!(common.name(), node.name());
+    assert_eq!(common.uuid(), node.uuid());
+    assert_eq!(common.filename(), "layer1");
+    assert!(!common.visible());
+    assert_eq!(node.as_common().name(), "Paint");
+}
@@ -0,0 +1,194 @@
+use std::{fs::File, process};
+
+use kra::{
+    config::{Limits, ParsingConfiguration},
+    data::{LayerData, PixelBuffer},
+    edit::ScaleFilter,
+    error::{EditError, LimitError, WriteError},
+    id::Uuid,
+    testing::{DocumentBuilder, LayerBuilder, TempPath},
+    writer::WriteOptions,
+    KraFile,
+};
+
+// UUIDs of the layers, in document order
+const PAINT: u128 = 1;
+const FILE: u128 = 2;
+
+const RED: [u8; 4] = [0, 0, 255, 255];
+const BLUE: [u8; 4] = [255, 0, 0, 255];
+
+fn uuid(value: u128) -> Uuid {
+    Uuid::from_u128(value)
+}
+
+// 128x128 image with a paint layer at (16, 32), and a file layer at (100, 60)
+fn write(test_name: &str) -> TempPath {
+    DocumentBuilder::new(128, 128)
+        .layer(
+            LayerBuilder::paint("Paint")
+                .offset(16, 32)
+                .fill_tile(0, 0, RED)
+                .fill_tile(64, 0, BLUE),
+        )
+        .layer(LayerBuilder::file_layer("File", "image.png").offset(100, 60))
+        .write_temp(test_name)
+        .unwrap()
+}
+
+fn write_and_read(file: &mut KraFile, test_name: &str) -> KraFile {
+    let path = TempPath::new(&format!("kra-{}-{}-out.kra", test_name, process::id()));
+    file.write(File::create(&path).unwrap(), &WriteOptions::new())
+        .unwrap();
+    KraFile::read(&path).unwrap()
+}
+
+// BGRA pixel at image coordinates, transparent outside of the data
+fn pixel(data: &LayerData, x: i32, y: i32) -> [u8; 4] {
+    let PixelBuffer::U8(pixels) = data.pixels() else {
+        panic!("expected 8-bit pixels");
+    };
+    let (x, y) = (x - data.x(), y - data.y());
+    if x < 0 || y < 0 || x >= data.width() as i32 || y >= data.height() as i32 {
+        return [0; 4];
+    }
+    let index = (y as usize * data.width() as usize + x as usize) * 4;
+    pixels[index..index + 4].try_into().unwrap()
+}
+
+#[test]
+fn crop() {
+    let mut file = KraFile::read(write("crop")).unwrap();
+    file.crop((40, 40, 50, 20)).unwrap();
+    assert_eq!((file.meta().width(), file.meta().height()), (50, 20));
+    let node = &file.layers()[1];
+    assert_eq!((node.x(), node.y()), (60, 20));
+
+    let check = |file: &mut KraFile| {
+        let paint = &file.layers()[0];
+        assert_eq!((paint.x(), paint.y()), (0, 0));
+        let data = file.decode_layer(&uuid(PAINT)).unwrap();
+        assert_eq!(pixel(&data, 0, 0), RED);
+        assert_eq!(pixel(&data, 39, 19), RED);
+        assert_eq!(pixel(&data, 40, 19), BLUE);
+        assert_eq!(pixel(&data, 49, 0), BLUE);
+        // Cropped away
+        assert_eq!(pixel(&data, 50, 0), [0; 4]);
+        assert_eq!(pixel(&data, 0, 20), [0; 4]);
+    };
+    check(&mut file);
+
+    let mut written = write_and_read(&mut file, "crop");
+    assert_eq!((written.meta().width(), written.meta().height()), (50, 20));
+    assert_eq!(written.layers()[1].x(), 60);
+    check(&mut written);
+}
+
+#[test]
+fn crop_with_duplicate_image_attributes() {
+    let builder = DocumentBuilder::new(128, 128).layer(LayerBuilder::paint("Paint"));
+    let maindoc = builder
+        .maindoc()
+        .replace("<IMAGE ", r#"<IMAGE x-extra="1" x-extra="2" "#);
+    let path = builder
+        .entry("maindoc.xml", maindoc)
+        .write_temp("crop_with_duplicate_image_attributes")
+        .unwrap();
+    let mut file = KraFile::read(&path).unwrap();
+    file.crop((0, 0, 10, 10)).unwrap();
+    let out = TempPath::new(&format!(
+        "kra-crop_with_duplicate_image_attributes-{}-out.kra",
+        process::id()
+    ));
+    assert!(matches!(
+        file.write(File::create(&out).unwrap(), &WriteOptions::new()),
+        Err(WriteError::MaindocError(_))
+    ));
+}
+
+#[test]
+fn crop_outside_of_layer() {
+    let mut file = KraFile::read(write("crop_outside_of_layer")).unwrap();
+    file.crop((0, 0, 10, 10)).unwrap();
+    let data = file.decode_layer(&uuid(PAINT)).unwrap();
+    assert_eq!(data.width(), 0);
+}
+
+#[test]
+fn invalid_crops() {
+    let mut file = KraFile::read(write("invalid_crops")).unwrap();
+    assert!(matches!(
+        file.crop((101, 0, 10, 10)),
+        Err(EditError::NegativeOffset(id)) if id == uuid(FILE)
+    ));
+    assert!(matches!(
+        file.crop((0, 0, 0, 10)),
+        Err(EditError::InvalidSize {
+            width: 0,
+            height: 10
+        })
+    ));
+    // Nothing was changed
+    assert_eq!(file.meta().width(), 128);
+    assert_eq!(file.layers()[0].x(), 16);
+}
+
+#[test]
+fn scale_over_limit() {
+    let config = ParsingConfiguration::default().limits(Limits {
+        max_dimensions: (256, 256),
+        ..Default::default()
+    });
+    let mut file = KraFile::read_with_config(write("scale_over_limit"), config).unwrap();
+    assert!(matches!(
+        file.scale((512, 128), ScaleFilter::Nearest),
+        Err(EditError::LimitExceeded(LimitError::Dimensions {
+            width: 512,
+            ..
+        }))
+    ));
+    file.scale((256, 128), ScaleFilter::Nearest).unwrap();
+}
+
+#[test]
+fn scale_nearest() {
+    let mut file = KraFile::read(write("scale_nearest")).unwrap();
+    file.scale((64, 256), ScaleFilter::Nearest).unwrap();
+    assert_eq!((file.meta().width(), file.meta().height()), (64, 256));
+    let node = &file.layers()[1];
+    assert_eq!((node.x(), node.y()), (50, 120));
+
+    let check = |file: &mut KraFile| {
+        let data = file.decode_layer(&uuid(PAINT)).unwrap();
+        // Red was at 16..80 x 32..96
+        assert_eq!(pixel(&data, 8, 64), RED);
+        assert_eq!(pixel(&data, 39, 191), RED);
+        assert_eq!(pixel(&data, 40, 64), BLUE);
+        assert_eq!(pixel(&data, 71, 191), BLUE);
+        assert_eq!(pixel(&data, 7, 64), [0; 4]);
+        assert_eq!(pixel(&data, 8, 63), [0; 4]);
+        assert_eq!(pixel(&data, 72, 64), [0; 4]);
+        assert_eq!(pixel(&data, 8, 192), [0; 4]);
+    };
+    check(&mut file);
+    check(&mut write_and_read(&mut file, "scale_nearest"));
+}
+
+#[test]
+fn scale_smooth() {
+    for filter in [ScaleFilter::Bilinear, ScaleFilter::Bicubic] {
+        let mut file = KraFile::read(write("scale_smooth")).unwrap();
+        file.scale((256, 256), filter).unwrap();
+        let data = file.decode_layer(&uuid(PAINT)).unwrap();
+        // Red was at 16..80 x 32..96
+        assert_eq!(pixel(&data, 40, 100), RED);
+        assert_eq!(pixel(&data, 200, 100), BLUE);
+        // Between red and blue
+        let [blue, green, red, alpha] = pixel(&data, 160, 100);
+        assert!(blue > 0 && red > 0 && green == 0 && alpha == 255);
+        // Edge of the layer
+        let [.., alpha] = pixel(&data, 32, 100);
+        assert!(alpha > 0 && alpha < 255);
+        assert_eq!(pixel(&data, 20, 100), [0; 4]);
+    }
+}
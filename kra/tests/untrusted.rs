@@ -0,0 +1,223 @@
+// Reading of corrupted documents, which must fail with errors instead of panicking
+use std::{
+    fs::{self, File},
+    io::Read,
+    path::PathBuf,
+};
+
+use kra::{
+    config::{DoctypeCheck, InvalidUtf8, MimetypeCheck, ParsingConfiguration, UnknownAttributes},
+    error::DataError,
+    id::Uuid,
+    parse::parse_document,
+    testing::{DocumentBuilder, LayerBuilder},
+    tile::lzf_decompress,
+    KraFile,
+};
+use zip::{result::ZipError, ZipArchive};
+
+const EXTREMES: [&str; 7] = [
+    "0",
+    "-1",
+    "2147483647",
+    "-2147483648",
+    "4294967295",
+    "18446744073709551616",
+    "1e309",
+];
+
+// Xorshift, so that failures can be reproduced
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n.max(1) as u64) as usize
+    }
+}
+
+fn mutate(data: &[u8], rng: &mut Rng) -> Vec<u8> {
+    let mut data = data.to_vec();
+    if data.is_empty() {
+        return data;
+    }
+    let at = rng.below(data.len());
+    match rng.below(5) {
+        0 => {
+            for _ in 0..=rng.below(8) {
+                let at = rng.below(data.len());
+                data[at] = rng.below(256) as u8;
+            }
+        }
+        1 => data.truncate(at),
+        2 => {
+            let end = (at + rng.below(64)).min(data.len());
+            data.drain(at..end);
+        }
+        3 => {
+            let end = (at + rng.below(64)).min(data.len());
+            let copy = data[at..end].to_vec();
+            data.splice(at..at, copy);
+        }
+        // Numbers are replaced with ones at the edges of what types can hold
+        _ => {
+            let start = (at..data.len()).find(|&index| data[index].is_ascii_digit());
+            if let Some(start) = start {
+                let end = (start..data.len())
+                    .find(|&index| !data[index].is_ascii_digit())
+                    .unwrap_or(data.len());
+                let number = EXTREMES[rng.below(EXTREMES.len())].as_bytes();
+                data.splice(start..end, number.iter().copied());
+            }
+        }
+    }
+    data
+}
+
+fn fixture_entry(fixture: &str, entry: &str) -> Vec<u8> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(fixture);
+    let mut zip = ZipArchive::new(File::open(path).unwrap()).unwrap();
+    let mut data = Vec::new();
+    zip.by_name(entry).unwrap().read_to_end(&mut data).unwrap();
+    data
+}
+
+#[test]
+fn corrupted_xml() {
+    let configs = [
+        ParsingConfiguration::new(),
+        ParsingConfiguration::strict(),
+        ParsingConfiguration::new()
+            .unknown_attributes(UnknownAttributes::Warn)
+            .doctype_check(DoctypeCheck::Warn)
+            .mimetype_check(MimetypeCheck::Warn)
+            .invalid_utf8(InvalidUtf8::Replace)
+            .colorspace_fallback(true),
+    ];
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    for fixture in [
+        "paint.kra",
+        "group-and-mask.kra",
+        "clone-and-pass-through.kra",
+    ] {
+        let maindoc = fixture_entry(fixture, "maindoc.xml");
+        let documentinfo = fixture_entry(fixture, "documentinfo.xml");
+        for round in 0..1000 {
+            let (maindoc, documentinfo) = match round % 4 {
+                0 => (maindoc.clone(), mutate(&documentinfo, &mut rng)),
+                _ => (mutate(&maindoc, &mut rng), documentinfo.clone()),
+            };
+            let _ = parse_document(&maindoc, &documentinfo, &configs[round % configs.len()]);
+        }
+    }
+}
+
+#[test]
+fn corrupted_tiles() {
+    let uuid = Uuid::from_u128(1);
+    let pixels: Vec<u8> = (0..64 * 64 * 4).map(|index| (index % 251) as u8).collect();
+    let layers = [
+        LayerBuilder::paint("Filled")
+            .fill_tile(0, 0, [0, 0, 255, 255])
+            .fill_tile(64, -64, [255, 0, 0, 128])
+            .pixel_data(),
+        LayerBuilder::paint("Compressed")
+            .tile(-64, 0, pixels)
+            .pixel_data(),
+    ];
+    let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+    for round in 0..200 {
+        let layer = mutate(&layers[round % layers.len()], &mut rng);
+        let (x, y) = match round % 3 {
+            0 => (0, 0),
+            _ => (u32::MAX / 2, u32::MAX / 2),
+        };
+        let path = DocumentBuilder::new(128, 128)
+            .layer(LayerBuilder::paint("Layer").offset(x, y).data(layer))
+            .write_temp("corrupted_tiles")
+            .unwrap();
+        let mut file = KraFile::read(&path).unwrap();
+        let _ = file.decode_layer(&uuid);
+        let _ = file.exact_bounds(&uuid);
+        let _ = file.find_empty_layers();
+    }
+}
+
+#[test]
+fn corrupted_layer_entry() {
+    let name = "Unnamed/layers/layer1";
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Layer").fill_tile(0, 0, [0, 0, 255, 255]))
+        .write_temp("corrupted_layer_entry")
+        .unwrap();
+    // The first compressed byte of the entry makes a deflate block of an invalid type
+    let mut archive = fs::read(&path).unwrap();
+    let header = archive
+        .windows(name.len())
+        .position(|window| window == name.as_bytes())
+        .unwrap()
+        - 30;
+    let extra_len = u16::from_le_bytes([archive[header + 28], archive[header + 29]]) as usize;
+    archive[header + 30 + name.len() + extra_len] = 0xff;
+    fs::write(&path, archive).unwrap();
+
+    let mut file = KraFile::read(&path).unwrap();
+    let err = file.decode_layer(&Uuid::from_u128(1)).unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with(&format!("could not read entry {}: ", name)));
+    assert!(matches!(
+        err,
+        DataError::Entry { name: entry, source: ZipError::Io(_) } if entry == name
+    ));
+}
+
+#[test]
+fn missing_layer_entry() {
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Layer"))
+        .without_entry("Unnamed/layers/layer1")
+        .write_temp("missing_layer_entry")
+        .unwrap();
+    let mut file = KraFile::read(&path).unwrap();
+    assert!(matches!(
+        file.decode_layer(&Uuid::from_u128(1)),
+        Err(DataError::Entry { name, source: ZipError::FileNotFound })
+            if name == "Unnamed/layers/layer1"
+    ));
+}
+
+#[test]
+fn corrupted_lzf() {
+    let mut rng = Rng(0xdead_beef_cafe_f00d);
+    for _ in 0..10000 {
+        let input: Vec<u8> = (0..rng.below(64)).map(|_| rng.below(256) as u8).collect();
+        let _ = lzf_decompress(&input, rng.below(4096));
+    }
+}
+
+#[test]
+fn coordinates_at_edges() {
+    let uuid = Uuid::from_u128(1);
+    let cases = [(0, i32::MAX - 10), (i32::MAX as u32 - 64, 64)];
+    for (offset, tile) in cases {
+        let layer = LayerBuilder::paint("Layer")
+            .offset(offset, 0)
+            .fill_tile(tile, 0, [0, 0, 255, 255])
+            .fill_tile(0, 0, [0, 0, 255, 255]);
+        let path = DocumentBuilder::new(128, 128)
+            .layer(layer)
+            .write_temp("coordinates_at_edges")
+            .unwrap();
+        let mut file = KraFile::read(&path).unwrap();
+        assert!(matches!(
+            file.decode_layer(&uuid),
+            Err(DataError::MalformedTiles(..) | DataError::TooLarge(..))
+        ));
+        let _ = file.exact_bounds(&uuid);
+    }
+}
@@ -0,0 +1,44 @@
+#![cfg(feature = "notify")]
+
+use std::{fs, sync::mpsc, time::Duration};
+
+use kra::{
+    diff::Change,
+    id::Uuid,
+    testing::{DocumentBuilder, LayerBuilder},
+    watch::watch,
+};
+
+#[test]
+fn reports_saves() {
+    let path = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Sketch"))
+        .write_temp("watched")
+        .unwrap();
+    let (sender, receiver) = mpsc::channel();
+    let _watcher = watch(&[&path], move |_, update| {
+        if let Ok(update) = update {
+            let name = update.file().layers()[0].name().to_owned();
+            sender.send((name, update.changes().to_vec())).unwrap();
+        }
+    })
+    .unwrap();
+
+    // Saved by replacing the file, like Krita does
+    let saved = DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Ink"))
+        .write_temp("watched_saved")
+        .unwrap();
+    fs::rename(saved, &path).unwrap();
+
+    let changed = Change::NodeChanged(Uuid::from_u128(1));
+    loop {
+        let (name, changes) = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        if changes.is_empty() {
+            continue;
+        }
+        assert_eq!(name, "Ink");
+        assert_eq!(changes, [changed]);
+        break;
+    }
+}
@@ -0,0 +1,303 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    process,
+};
+
+use kra::{
+    config::ParsingConfiguration,
+    id::Uuid,
+    metadata::{AuthorProfile, AuthorUpdate, DocInfoField},
+    testing::{DocumentBuilder, LayerBuilder, TempPath},
+    writer::{Compression, OptimizeOptions, WriteOptions},
+    KraFile,
+};
+use zip::{CompressionMethod, ZipArchive};
+
+// A paint layer, next to an entry of a layer that is not in the document
+fn write(test_name: &str) -> TempPath {
+    let orphan = LayerBuilder::paint("Orphan")
+        .fill_tile(0, 0, [255, 0, 0, 255])
+        .pixel_data();
+    DocumentBuilder::new(128, 128)
+        .layer(LayerBuilder::paint("Paint").fill_tile(0, 0, [0, 0, 255, 255]))
+        .image_entry("layers/layer2", orphan)
+        .entry("preview.png", b"not really a png".to_vec())
+        .write_temp(test_name)
+        .unwrap()
+}
+
+fn output(test_name: &str) -> TempPath {
+    TempPath::new(&format!("kra-{}-out-{}.kra", test_name, process::id()))
+}
+
+// Names of entries, in archive order
+fn entry_names(path: &Path) -> Vec<String> {
+    let mut zip = ZipArchive::new(File::open(path).unwrap()).unwrap();
+    (0..zip.len())
+        .map(|index| zip.by_index_raw(index).unwrap().name().to_owned())
+        .collect()
+}
+
+#[test]
+fn write_copies_everything() {
+    let path = write("write_copies_everything");
+    let out = output("write_copies_everything");
+    let mut file = KraFile::read(&path).unwrap();
+    file.write(File::create(&out).unwrap(), &WriteOptions::new())
+        .unwrap();
+
+    let mut names = entry_names(&out);
+    assert_eq!(names[0], "mimetype");
+    names.sort();
+    let mut expected = entry_names(&path);
+    expected.sort();
+    assert_eq!(names, expected);
+
+    let mut written = KraFile::read(&out).unwrap();
+    assert_eq!(written.layers().len(), 1);
+    let data = written.decode_layer(&Uuid::from_u128(1)).unwrap();
+    assert_eq!(data.width(), 64);
+}
+
+#[test]
+fn write_without_orphaned_entries() {
+    let path = write("write_without_orphaned_entries");
+    let out = output("write_without_orphaned_entries");
+    let mut file = KraFile::read(&path).unwrap();
+    let options = WriteOptions::new()
+        .drop_orphaned_entries(true)
+        .compression(Compression::Stored);
+    file.write(File::create(&out).unwrap(), &options).unwrap();
+
+    let names = entry_names(&out);
+    assert!(!names.contains(&"Unnamed/layers/layer2".to_owned()));
+    assert!(names.contains(&"Unnamed/layers/layer1".to_owned()));
+
+    let mut zip = ZipArchive::new(File::open(&out).unwrap()).unwrap();
+    for index in 0..zip.len() {
+        assert_eq!(
+            zip.by_index(index).unwrap().compression(),
+            CompressionMethod::Stored
+        );
+    }
+    assert!(KraFile::read(&out).unwrap().orphaned_entries().is_empty());
+}
+
+#[test]
+fn optimize() {
+    let path = write("optimize");
+    let out = output("optimize");
+    let mut file = KraFile::read(&path).unwrap();
+    file.optimize(File::create(&out).unwrap(), &OptimizeOptions::new())
+        .unwrap();
+
+    let names = entry_names(&out);
+    assert!(!names.contains(&"Unnamed/layers/layer2".to_owned()));
+    let mut zip = ZipArchive::new(File::open(&out).unwrap()).unwrap();
+    assert_eq!(
+        zip.by_name("mimetype").unwrap().compression(),
+        CompressionMethod::Stored
+    );
+    assert_eq!(
+        zip.by_name("maindoc.xml").unwrap().compression(),
+        CompressionMethod::Deflated
+    );
+}
+
+#[test]
+fn write_needs_archive() {
+    let path = write("write_needs_archive");
+    let config = ParsingConfiguration::new().keep_archive(false);
+    let mut file = KraFile::read_with_config(&path, config).unwrap();
+    assert!(file
+        .write(std::io::Cursor::new(Vec::new()), &WriteOptions::new())
+        .is_err());
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn optimize_regenerates_preview() {
+    use image::{GenericImageView, ImageFormat, Rgba, RgbaImage};
+    use std::io::{Cursor, Read};
+
+    let mut png = Vec::new();
+    RgbaImage::from_pixel(512, 256, Rgba([10, 20, 30, 255]))
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .unwrap();
+    let path = DocumentBuilder::new(128, 128)
+        .entry("mergedimage.png", png)
+        .entry("preview.png", b"stale".to_vec())
+        .write_temp("optimize_regenerates_preview")
+        .unwrap();
+    let out = output("optimize_regenerates_preview");
+    let mut file = KraFile::read(&path).unwrap();
+    let options = OptimizeOptions::new().regenerate_preview(Some(256));
+    file.optimize(File::create(&out).unwrap(), &options)
+        .unwrap();
+
+    let mut zip = ZipArchive::new(File::open(&out).unwrap()).unwrap();
+    let mut preview = Vec::new();
+    zip.by_name("preview.png")
+        .unwrap()
+        .read_to_end(&mut preview)
+        .unwrap();
+    let preview = image::load_from_memory(&preview).unwrap();
+    assert_eq!(preview.dimensions(), (256, 128));
+    assert_eq!(preview.get_pixel(100, 100), Rgba([10, 20, 30, 255]));
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn regenerate_missing_preview() {
+    use image::{GenericImageView, ImageFormat, Rgba, RgbaImage};
+    use std::io::{Cursor, Read};
+
+    let mut png = Vec::new();
+    RgbaImage::from_pixel(100, 300, Rgba([1, 2, 3, 255]))
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .unwrap();
+    let path = DocumentBuilder::new(128, 128)
+        .entry("mergedimage.png", png)
+        .write_temp("regenerate_missing_preview")
+        .unwrap();
+    let out = output("regenerate_missing_preview");
+    let mut file = KraFile::read(&path).unwrap();
+    file.regenerate_preview(150).unwrap();
+    file.write(File::create(&out).unwrap(), &WriteOptions::new())
+        .unwrap();
+
+    assert_eq!(entry_names(&out).last().unwrap(), "preview.png");
+    let mut zip = ZipArchive::new(File::open(&out).unwrap()).unwrap();
+    let mut preview = Vec::new();
+    zip.by_name("preview.png")
+        .unwrap()
+        .read_to_end(&mut preview)
+        .unwrap();
+    let preview = image::load_from_memory(&preview).unwrap();
+    assert_eq!(preview.dimensions(), (50, 150));
+}
+
+#[test]
+fn write_author_profile() {
+    let authorinfo = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE authorinfo>
+<author>
+ <nickname>Ada L.</nickname>
+ <givenname>Ada</givenname>
+ <middlename>A. L.</middlename>
+ <familyname>Lovelace</familyname>
+ <title></title>
+ <position>Art lead</position>
+ <company>Studio &amp; Co</company>
+ <contact type="email">ada@example.com</contact>
+</author>
+"#;
+    let profile = AuthorProfile::from_authorinfo(authorinfo).unwrap();
+    assert_eq!(profile.get_nickname(), "Ada L.");
+    assert_eq!(profile.get_initials(), "A. L.");
+    assert_eq!(profile.get_company(), "Studio & Co");
+
+    let path = write("write_author_profile");
+    let out = output("write_author_profile");
+    let mut file = KraFile::read(&path).unwrap();
+    file.set_doc_info(DocInfoField::Position, "Intern");
+    let options = WriteOptions::new().author_profile(Some(profile.clone()));
+    file.write(File::create(&out).unwrap(), &options).unwrap();
+    assert_eq!(file.doc_info().author().full_name(), "");
+
+    let written = KraFile::read(&out).unwrap();
+    let author = written.doc_info().author();
+    assert_eq!(author.full_name(), "Ada L.");
+    assert_eq!(author.creator_first_name(), "Ada");
+    assert_eq!(author.creator_last_name(), "Lovelace");
+    assert_eq!(author.initial(), "A. L.");
+    assert_eq!(author.position(), "Art lead");
+    assert_eq!(written.doc_info().about().title(), "Unnamed");
+
+    let options = options.author_update(AuthorUpdate::Fill);
+    file.write(File::create(&out).unwrap(), &options).unwrap();
+    let written = KraFile::read(&out).unwrap();
+    assert_eq!(written.doc_info().author().position(), "Intern");
+    assert_eq!(written.doc_info().author().company(), "Studio & Co");
+}
+
+#[test]
+fn write_editing_stats() {
+    let path = write("write_editing_stats");
+    let out = output("write_editing_stats");
+    let mut file = KraFile::read(&path).unwrap();
+    let options = WriteOptions::new().update_editing_stats(true);
+    file.write(File::create(&out).unwrap(), &options).unwrap();
+    assert_eq!(file.doc_info().about().editing_cycles(), "1");
+
+    let written = KraFile::read(&out).unwrap();
+    let about = written.doc_info().about();
+    assert_eq!(about.editing_cycles(), "2");
+    assert!(about.editing_time().parse::<u64>().is_ok());
+    assert_ne!(about.date(), "2024-01-01T00:00:00");
+    assert_eq!(about.date().len(), "2024-01-01T00:00:00".len());
+    assert_eq!(about.creation_date(), "2024-01-01T00:00:00");
+}
+
+// Entry of a written archive, as text
+fn entry_text(path: &Path, name: &str) -> String {
+    let mut zip = ZipArchive::new(File::open(path).unwrap()).unwrap();
+    std::io::read_to_string(zip.by_name(name).unwrap()).unwrap()
+}
+
+#[test]
+fn rebuilt_maindoc_matches_krita() {
+    let fixture =
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/clone-and-pass-through.kra");
+    let out = output("rebuilt_maindoc_matches_krita");
+    let mut file = KraFile::read(&fixture).unwrap();
+    let background = Uuid::parse_str("00000000-0000-0000-0000-000000000001").unwrap();
+    file.rename_node(&background, "Background").unwrap();
+    file.write(File::create(&out).unwrap(), &WriteOptions::new())
+        .unwrap();
+    // Only the node tree is rebuilt, the fixture does not indent it
+    let (original, written) = (
+        entry_text(&fixture, "maindoc.xml"),
+        entry_text(&out, "maindoc.xml"),
+    );
+    let tree = |maindoc: &str| {
+        let (start, end) = (
+            maindoc.find("<layers>").unwrap(),
+            maindoc.rfind("</layers>").unwrap(),
+        );
+        (
+            maindoc[..start].to_owned(),
+            maindoc[start..end].to_owned(),
+            maindoc[end..].to_owned(),
+        )
+    };
+    let (before, nodes, after) = tree(&written);
+    let (original_before, _, original_after) = tree(&original);
+    assert_eq!((before, after), (original_before, original_after));
+    // Krita writes maindoc.xml with QDomDocument::toByteArray(), which indents every
+    // level by one space
+    assert_eq!(
+        nodes,
+        concat!(
+            "<layers>\n",
+            "   <layer name=\"Clone\" uuid=\"{00000000-0000-0000-0000-000000000009}\" filename=\"layer9\" visible=\"0\" locked=\"1\" colorlabel=\"0\" x=\"0\" y=\"0\" intimeline=\"0\" nodetype=\"clonelayer\" compositeop=\"normal\" opacity=\"255\" collapsed=\"0\" clonetype=\"1\" clonefrom=\"Background\" clonefromuuid=\"{00000000-0000-0000-0000-000000000001}\" channelflags=\"\"/>\n",
+            "   <layer name=\"Pass-through\" uuid=\"{00000000-0000-0000-0000-000000000010}\" filename=\"layer10\" visible=\"1\" locked=\"0\" colorlabel=\"0\" x=\"0\" y=\"0\" intimeline=\"0\" nodetype=\"grouplayer\" compositeop=\"normal\" opacity=\"255\" collapsed=\"0\" passthrough=\"1\">\n",
+            "    <layers>\n",
+            "     <layer name=\"Lines\" uuid=\"{00000000-0000-0000-0000-000000000011}\" filename=\"layer11\" visible=\"1\" locked=\"0\" colorlabel=\"0\" x=\"0\" y=\"64\" intimeline=\"0\" nodetype=\"paintlayer\" compositeop=\"normal\" opacity=\"255\" collapsed=\"0\" colorspacename=\"RGBA\" channellockflags=\"1111\" channelflags=\"\"/>\n",
+            "    </layers>\n",
+            "   </layer>\n",
+            "   <layer name=\"Background\" uuid=\"{00000000-0000-0000-0000-000000000001}\" filename=\"layer1\" visible=\"1\" locked=\"0\" colorlabel=\"0\" x=\"0\" y=\"0\" intimeline=\"0\" nodetype=\"paintlayer\" compositeop=\"normal\" opacity=\"255\" collapsed=\"0\" colorspacename=\"RGBA\" channellockflags=\"1111\" channelflags=\"\"/>\n",
+            "  ",
+        )
+    );
+
+    file.rename_node(&background, "Tom's \"sketch\" <v2>]]>\n")
+        .unwrap();
+    file.write(File::create(&out).unwrap(), &WriteOptions::new())
+        .unwrap();
+    assert!(entry_text(&out, "maindoc.xml")
+        .contains(r#"name="Tom's &quot;sketch&quot; &lt;v2>]]&gt;&#xa;""#));
+    let written = KraFile::read(&out).unwrap();
+    assert_eq!(written.layers()[2].name(), "Tom's \"sketch\" <v2>]]>\n");
+}
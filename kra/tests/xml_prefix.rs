@@ -0,0 +1,33 @@
+use kra::{testing::DocumentBuilder, KraFile};
+
+#[test]
+fn bom_and_whitespace_before_declaration() {
+    let builder = DocumentBuilder::new(128, 128);
+    let path = builder
+        .clone()
+        .entry(
+            "documentinfo.xml",
+            format!("\u{feff}{}", builder.documentinfo()),
+        )
+        .entry(
+            "maindoc.xml",
+            format!("\u{feff}\n  \n{}", builder.maindoc()),
+        )
+        .write_temp("bom_and_whitespace_before_declaration")
+        .unwrap();
+    let file = KraFile::read(&path).unwrap();
+    assert_eq!(file.meta().name(), "Unnamed");
+}
+
+#[test]
+fn missing_declaration() {
+    let builder = DocumentBuilder::new(128, 128);
+    let maindoc = builder.maindoc();
+    let (_, maindoc) = maindoc.split_once('\n').unwrap();
+    let path = builder
+        .clone()
+        .entry("maindoc.xml", maindoc)
+        .write_temp("missing_declaration")
+        .unwrap();
+    assert!(KraFile::read(&path).is_ok());
+}